@@ -0,0 +1,200 @@
+//! Debian alternatives
+//!
+//! `update-alternatives` lets several packages provide one generic name
+//! (`editor`, `x-www-browser`, `java`): each registers a link such as
+//! `/usr/bin/editor` with a priority, and the highest one wins. A package
+//! takes part by calling `update-alternatives --install` from its
+//! postinst, or by shipping links through `/etc/alternatives`. Arch has no
+//! such system, so the generic names a package expects to own are
+//! collected here for the conversion to provide or leave out.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::models::{MaintainerScript, PackageMetadata};
+
+use super::scripts::analyze_script;
+
+/// Directory of the alternatives links, relative to the root
+const ALTERNATIVES_DIR: &str = "etc/alternatives";
+
+/// A generic name a package provides through the alternatives system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Alternative {
+    /// Generic name (`editor`)
+    pub name: String,
+    /// Generic link (`/usr/bin/editor`), when known
+    pub link: Option<PathBuf>,
+    /// File of the package the link resolves to, when known
+    pub path: Option<PathBuf>,
+    /// Priority against other providers
+    pub priority: Option<i64>,
+    /// Master alternative of a `--slave` link
+    pub master: Option<String>,
+}
+
+impl Alternative {
+    fn named(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            link: None,
+            path: None,
+            priority: None,
+            master: None,
+        }
+    }
+
+    /// Alternatives registered by `update-alternatives` arguments
+    ///
+    /// Arguments that are not literal are `None`. Gives the `--install`
+    /// alternative followed by its `--slave` links, or nothing for other
+    /// actions.
+    pub fn from_install(args: &[Option<String>]) -> Vec<Self> {
+        let Some(start) = args.iter().position(|a| a.as_deref() == Some("--install")) else {
+            return Vec::new();
+        };
+        let Some(master) = Self::from_args(&args[start + 1..], None) else {
+            return Vec::new();
+        };
+
+        let mut found = vec![master.clone()];
+        for (i, arg) in args.iter().enumerate().skip(start + 5) {
+            if arg.as_deref() == Some("--slave") {
+                found.extend(Self::from_args(&args[i + 1..], Some(&master.name)));
+            }
+        }
+        found
+    }
+
+    /// `<link> <name> <path> [<priority>]`
+    fn from_args(args: &[Option<String>], master: Option<&String>) -> Option<Self> {
+        let name = args.get(1)?.as_deref()?;
+        Some(Self {
+            link: args.first().cloned().flatten().map(PathBuf::from),
+            path: args.get(2).cloned().flatten().map(PathBuf::from),
+            priority: match master {
+                Some(_) => None,
+                None => args.get(3).cloned().flatten().and_then(|p| p.parse().ok()),
+            },
+            master: master.cloned(),
+            ..Self::named(name)
+        })
+    }
+
+    /// Take the fields this one is missing from another record of the same name
+    fn merge(&mut self, other: Self) {
+        self.link = self.link.take().or(other.link);
+        self.path = self.path.take().or(other.path);
+        self.priority = self.priority.or(other.priority);
+        self.master = self.master.take().or(other.master);
+    }
+}
+
+impl fmt::Display for Alternative {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        let show = |path: &Option<PathBuf>| path.as_ref().map_or("?".to_string(), |p| p.display().to_string());
+        if self.link.is_some() || self.path.is_some() {
+            write!(f, ": {} -> {}", show(&self.link), show(&self.path))?;
+        }
+        if let Some(priority) = self.priority {
+            write!(f, " (priority {})", priority)?;
+        }
+        if let Some(master) = &self.master {
+            write!(f, " (follows {})", master)?;
+        }
+        Ok(())
+    }
+}
+
+/// Alternatives a package takes part in, sorted by generic name
+///
+/// Reads the postinst `update-alternatives --install` calls and the
+/// payload's links into and inside `/etc/alternatives`. A postinst that
+/// does not parse contributes nothing.
+pub fn detect(metadata: &PackageMetadata, root: &Path) -> Result<Vec<Alternative>> {
+    let mut found: BTreeMap<String, Alternative> = BTreeMap::new();
+    let mut add = |alternative: Alternative| match found.get_mut(&alternative.name) {
+        Some(known) => known.merge(alternative),
+        None => {
+            found.insert(alternative.name.clone(), alternative);
+        }
+    };
+
+    if let Some(analysis) = metadata.get_script(MaintainerScript::PostInst).and_then(|s| analyze_script(s).ok()) {
+        analysis.alternatives.into_iter().for_each(&mut add);
+    }
+
+    let alternatives_dir = Path::new("/").join(ALTERNATIVES_DIR);
+    for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.path_is_symlink() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        let target = std::fs::read_link(entry.path())?;
+        let name = |path: &Path| path.file_name().map(|n| n.to_string_lossy().into_owned());
+
+        if rel.parent() == Some(Path::new(ALTERNATIVES_DIR)) {
+            if let Some(name) = name(rel) {
+                add(Alternative {
+                    path: Some(target),
+                    ..Alternative::named(&name)
+                });
+            }
+        } else if target.parent() == Some(alternatives_dir.as_path()) {
+            if let Some(name) = name(&target) {
+                add(Alternative {
+                    link: Some(Path::new("/").join(rel)),
+                    ..Alternative::named(&name)
+                });
+            }
+        }
+    }
+
+    Ok(found.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_alternatives() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("etc/alternatives")).unwrap();
+        std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+        std::os::unix::fs::symlink("/etc/alternatives/x-www-browser", root.join("usr/bin/x-www-browser")).unwrap();
+        std::os::unix::fs::symlink("/usr/bin/foo-browser", root.join("etc/alternatives/x-www-browser")).unwrap();
+
+        let mut metadata = PackageMetadata::new("foo", "1.0");
+        metadata.scripts.insert(
+            MaintainerScript::PostInst,
+            r#"#!/bin/sh
+if [ "$1" = configure ]; then
+    update-alternatives --quiet --install /usr/bin/editor editor /usr/bin/foo 40 \
+        --slave /usr/share/man/man1/editor.1.gz editor.1.gz /usr/share/man/man1/foo.1.gz
+fi
+"#
+            .to_string(),
+        );
+
+        let found = detect(&metadata, root).unwrap();
+        let shown: Vec<String> = found.iter().map(|a| a.to_string()).collect();
+        assert_eq!(
+            shown,
+            [
+                "editor: /usr/bin/editor -> /usr/bin/foo (priority 40)",
+                "editor.1.gz: /usr/share/man/man1/editor.1.gz -> /usr/share/man/man1/foo.1.gz (follows editor)",
+                "x-www-browser: /usr/bin/x-www-browser -> /usr/bin/foo-browser",
+            ]
+        );
+
+        assert!(Alternative::from_install(&[Some("--remove".into()), Some("editor".into()), None]).is_empty());
+    }
+}
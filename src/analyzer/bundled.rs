@@ -0,0 +1,135 @@
+//! Libraries bundled with an application
+//!
+//! Electron and Chrome style packages ship their own copies of shared
+//! libraries next to the binary (`/opt/app/lib*.so`). Copies of libraries
+//! Arch packages provide are worth a decision: strip them and depend on the
+//! system package, or keep them and leave that package out of the
+//! dependencies.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::converter::MULTIARCH_TRIPLETS;
+
+use super::elf::{ElfInfo, LibrarySearch, DEFAULT_LIBRARY_DIRS, LIB32_DIRS};
+
+/// A shared library shipped outside the system library directories
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundledLibrary {
+    /// Path inside the package
+    pub path: PathBuf,
+    /// Library name (`DT_SONAME`, or the file name)
+    pub soname: String,
+    /// Arch package shipping a library of the same name
+    pub owner: Option<String>,
+    /// Whether the system's linker path has a library of the same name
+    pub on_system: bool,
+    /// Whether the package also depends on the owner
+    pub depended: bool,
+}
+
+impl BundledLibrary {
+    /// What to do about the copy, `None` for libraries private to the application
+    pub fn recommendation(&self) -> Option<String> {
+        match (&self.owner, self.depended) {
+            (Some(owner), true) => Some(format!(
+                "copy of {}'s library, which is also a dependency: strip the copy, or keep it and drop the dependency",
+                owner
+            )),
+            (Some(owner), false) => Some(format!(
+                "shadows {}: strip it and depend on {}, or keep it as the application's private copy",
+                owner, owner
+            )),
+            (None, _) if self.on_system => {
+                Some("shadows an installed library: strip it to use the system copy if the ABI matches".to_string())
+            }
+            (None, _) => None,
+        }
+    }
+}
+
+/// Shared libraries among a package's ELF objects that live outside the linker path
+///
+/// `owner` names the Arch package shipping a soname; `depends` are the
+/// package's dependencies, by name and by the sonames they stand for.
+pub fn find_bundled(
+    objects: &[(PathBuf, ElfInfo)],
+    root: &Path,
+    search: &LibrarySearch,
+    owner: impl Fn(&str) -> Option<String>,
+    depends: &[&str],
+) -> Vec<BundledLibrary> {
+    let mut bundled: Vec<BundledLibrary> = objects
+        .iter()
+        .filter_map(|(path, info)| {
+            let rel = path.strip_prefix(root).unwrap_or(path);
+            let file_name = rel.file_name()?.to_string_lossy();
+            if info.soname.is_none() && !file_name.contains(".so") {
+                return None;
+            }
+            if rel.parent().is_some_and(is_system_dir) {
+                return None;
+            }
+
+            let soname = info.soname.clone().unwrap_or_else(|| file_name.into_owned());
+            let owner = owner(&soname);
+            Some(BundledLibrary {
+                path: Path::new("/").join(rel),
+                on_system: search.on_system(&soname, info.is_64),
+                depended: depends.contains(&soname.as_str()) || owner.as_deref().is_some_and(|o| depends.contains(&o)),
+                owner,
+                soname,
+            })
+        })
+        .collect();
+    bundled.sort_by(|a, b| a.path.cmp(&b.path));
+    bundled
+}
+
+/// Whether a package directory (relative to the root) is on the linker path
+pub(crate) fn is_system_dir(dir: &Path) -> bool {
+    DEFAULT_LIBRARY_DIRS.iter().chain(LIB32_DIRS).any(|system| {
+        let system = Path::new(system.trim_start_matches('/'));
+        dir == system || MULTIARCH_TRIPLETS.iter().any(|triplet| dir == system.join(triplet))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_bundled() {
+        let root = Path::new("/pkg");
+        let library = |path: &str, soname: Option<&str>| {
+            let info = ElfInfo {
+                is_64: true,
+                soname: soname.map(String::from),
+                ..ElfInfo::default()
+            };
+            (root.join(path), info)
+        };
+        let objects = [
+            library("opt/app/app", None),
+            library("opt/app/libffmpeg.so", None),
+            library("opt/app/libvulkan.so.1", Some("libvulkan.so.1")),
+            library("opt/app/lib/libssl.so.3", Some("libssl.so.3")),
+            library("usr/lib/x86_64-linux-gnu/libapp.so.1", Some("libapp.so.1")),
+        ];
+        let owner = |soname: &str| match soname {
+            "libvulkan.so.1" => Some("vulkan-icd-loader".to_string()),
+            "libssl.so.3" => Some("openssl".to_string()),
+            _ => None,
+        };
+
+        let bundled = find_bundled(&objects, root, &LibrarySearch::default(), owner, &["libssl.so.3"]);
+        let paths: Vec<_> = bundled.iter().map(|b| b.path.to_string_lossy().into_owned()).collect();
+        assert_eq!(paths, ["/opt/app/lib/libssl.so.3", "/opt/app/libffmpeg.so", "/opt/app/libvulkan.so.1"]);
+
+        assert!(bundled[0].depended);
+        assert!(bundled[0].recommendation().unwrap().contains("drop the dependency"));
+        assert_eq!(bundled[1].recommendation(), None);
+        assert!(bundled[2].recommendation().unwrap().starts_with("shadows vulkan-icd-loader"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Analysis result cache
+//!
+//! Analyzing a deb extracts it, scans every ELF object and hashes every
+//! file, so the report is kept for later runs on the same deb with the same
+//! rules. Entries live in `<cache_dir>/analysis`, one file per deb and
+//! settings, and go away with `rexeb clean`. Vulnerability data and the
+//! installed packages change underneath a report, so entries expire after
+//! a day.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::Result;
+use crate::models::PackageMetadata;
+
+use super::AnalysisReport;
+
+/// Seconds an entry stays valid
+const MAX_AGE: i64 = 24 * 60 * 60;
+
+/// A cached analysis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedAnalysis {
+    /// Metadata of the analyzed deb
+    pub metadata: PackageMetadata,
+    /// The report
+    pub report: AnalysisReport,
+    /// When the entry was created (unix time)
+    pub created: i64,
+}
+
+/// Cache of analysis reports keyed by input and settings
+#[derive(Debug)]
+pub struct AnalysisCache {
+    /// Directory holding the entries
+    dir: PathBuf,
+}
+
+impl AnalysisCache {
+    /// Open the cache in a directory
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Open the cache in the configured cache directory
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.cache_dir().join("analysis"))
+    }
+
+    /// Find a fresh entry for a deb analyzed with the given settings
+    pub fn lookup(&self, input_sha256: &str, options_hash: &str) -> Option<CachedAnalysis> {
+        let data = fs::read_to_string(self.entry_path(input_sha256, options_hash)).ok()?;
+        // A corrupt or outdated entry only costs a re-analysis
+        let entry: CachedAnalysis = serde_json::from_str(&data).ok()?;
+        (chrono::Utc::now().timestamp() - entry.created < MAX_AGE).then_some(entry)
+    }
+
+    /// Record the analysis of a deb
+    pub fn store(
+        &self,
+        input_sha256: &str,
+        options_hash: &str,
+        metadata: &PackageMetadata,
+        report: &AnalysisReport,
+    ) -> Result<()> {
+        let entry = CachedAnalysis {
+            metadata: metadata.clone(),
+            report: report.clone(),
+            created: chrono::Utc::now().timestamp(),
+        };
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.entry_path(input_sha256, options_hash), serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+
+    fn entry_path(&self, input_sha256: &str, options_hash: &str) -> PathBuf {
+        let short = |hash: &str| hash[..hash.len().min(16)].to_string();
+        self.dir.join(format!("{}-{}.json", short(input_sha256), short(options_hash)))
+    }
+}
+
+/// Modification time of the pacman database, so conflict checks see newly installed packages
+pub fn pacman_db_stamp() -> Option<i64> {
+    let local = Path::new(crate::resolver::pacman::PACMAN_DB_PATH).join("local");
+    let modified = fs::metadata(local).ok()?.modified().ok()?;
+    Some(chrono::DateTime::<chrono::Utc>::from(modified).timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::rules::{Finding, Severity};
+
+    #[test]
+    fn test_store_and_lookup() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let cache = AnalysisCache::new(temp_dir.path().join("analysis"));
+        let metadata = PackageMetadata::new("foo", "1.0");
+        let report = AnalysisReport {
+            findings: vec![Finding {
+                rule: "fhs-violation".into(),
+                severity: Severity::Warning,
+                message: "/opt/foo".into(),
+            }],
+            dependency_count: 3,
+            ..Default::default()
+        };
+
+        assert!(cache.lookup("abc", "def").is_none());
+        cache.store("abc", "def", &metadata, &report).unwrap();
+
+        let entry = cache.lookup("abc", "def").unwrap();
+        assert_eq!(entry.metadata.name, "foo");
+        assert_eq!(entry.report.dependency_count, 3);
+        assert_eq!(entry.report.findings.len(), 1);
+        assert!(cache.lookup("abc", "other").is_none());
+    }
+}
@@ -0,0 +1,165 @@
+//! Checks added from outside the analyzer
+//!
+//! Organization-specific policies (allowed licenses, forbidden paths,
+//! required metadata) do not belong in rexeb itself. A downstream crate
+//! implements [`Check`] and either registers it for every analysis with
+//! [`register`] or hands it to one analyzer with
+//! [`PackageAnalyzer::with_check`](super::PackageAnalyzer::with_check):
+//!
+//! ```no_run
+//! use std::path::Path;
+//!
+//! use rexeb::analyzer::check::{self, Check};
+//! use rexeb::analyzer::rules::Severity;
+//! use rexeb::analyzer::AnalysisReport;
+//! use rexeb::PackageMetadata;
+//!
+//! struct NoOpt;
+//!
+//! impl Check for NoOpt {
+//!     fn name(&self) -> &str {
+//!         "acme-no-opt"
+//!     }
+//!
+//!     fn severity(&self) -> Severity {
+//!         Severity::Error
+//!     }
+//!
+//!     fn run(&self, metadata: &PackageMetadata, _data_dir: &Path, report: &mut AnalysisReport) -> rexeb::Result<()> {
+//!         for file in metadata.files.iter().filter(|f| f.starts_with("/opt")) {
+//!             report.findings.push(self.finding(format!("{} is below /opt", file.display())));
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! check::register(NoOpt).unwrap();
+//! ```
+//!
+//! Checks run after the built-in ones. Their names are rule ids like any
+//! other: `[analyzer]` disables them or overrides their severity.
+
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use crate::error::{RexebError, Result};
+use crate::models::PackageMetadata;
+
+use super::rules::{self, Finding, Severity};
+use super::AnalysisReport;
+
+/// Checks registered for every analysis
+static REGISTRY: RwLock<Vec<Arc<dyn Check>>> = RwLock::new(Vec::new());
+
+/// An analyzer check supplied by a downstream crate
+pub trait Check: Send + Sync {
+    /// Rule id of the check's findings
+    fn name(&self) -> &str;
+
+    /// Severity unless the configuration overrides it
+    fn severity(&self) -> Severity;
+
+    /// What the check reports
+    fn description(&self) -> &str {
+        ""
+    }
+
+    /// Inspect a package whose payload is extracted to `data_dir`
+    fn run(&self, metadata: &PackageMetadata, data_dir: &Path, report: &mut AnalysisReport) -> Result<()>;
+
+    /// Finding of this check
+    fn finding(&self, message: String) -> Finding {
+        Finding {
+            rule: self.name().to_string(),
+            severity: self.severity(),
+            message,
+        }
+    }
+}
+
+/// Run a check for every analysis
+///
+/// Names must be unique and must not shadow a built-in rule.
+pub fn register(check: impl Check + 'static) -> Result<()> {
+    let mut registry = REGISTRY.write().unwrap_or_else(|e| e.into_inner());
+    let name = check.name();
+    if rules::rule(name).is_some() || registry.iter().any(|c| c.name() == name) {
+        return Err(RexebError::Validation(format!("Analyzer check {} is already registered", name)));
+    }
+    registry.push(Arc::new(check));
+    Ok(())
+}
+
+/// Checks registered so far
+pub fn registered() -> Vec<Arc<dyn Check>> {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Whether a check of this name is registered
+pub fn is_registered(name: &str) -> bool {
+    REGISTRY.read().unwrap_or_else(|e| e.into_inner()).iter().any(|c| c.name() == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::rules::RuleSet;
+    use crate::analyzer::PackageAnalyzer;
+
+    struct MissingUrl;
+
+    impl Check for MissingUrl {
+        fn name(&self) -> &str {
+            "test-missing-url"
+        }
+
+        fn severity(&self) -> Severity {
+            Severity::Warning
+        }
+
+        fn run(&self, metadata: &PackageMetadata, _data_dir: &Path, report: &mut AnalysisReport) -> Result<()> {
+            if metadata.url.is_none() {
+                report.findings.push(self.finding(format!("{} has no homepage", metadata.name)));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_check() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let metadata = PackageMetadata::new("foo", "1.0");
+
+        let analyzer = PackageAnalyzer::new(&metadata, temp_dir.path()).unwrap().with_check(MissingUrl);
+        let report = analyzer.analyze(false, false).unwrap();
+        let findings: Vec<_> = report.findings_of("test-missing-url").collect();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Warning);
+
+        let rules = RuleSet::default().with_severity("test-missing-url", Severity::Error);
+        let analyzer = PackageAnalyzer::new(&metadata, temp_dir.path()).unwrap().with_rules(rules).with_check(MissingUrl);
+        let report = analyzer.analyze(false, false).unwrap();
+        assert_eq!(report.findings_of("test-missing-url").next().unwrap().severity, Severity::Error);
+
+        let rules = RuleSet::default().disable("test-missing-url");
+        let analyzer = PackageAnalyzer::new(&metadata, temp_dir.path()).unwrap().with_rules(rules).with_check(MissingUrl);
+        assert_eq!(analyzer.analyze(false, false).unwrap().findings_of("test-missing-url").count(), 0);
+
+        // Registered checks run in every analysis, so this one reports nothing
+        struct Quiet;
+        impl Check for Quiet {
+            fn name(&self) -> &str {
+                "test-quiet"
+            }
+            fn severity(&self) -> Severity {
+                Severity::Info
+            }
+            fn run(&self, _: &PackageMetadata, _: &Path, _: &mut AnalysisReport) -> Result<()> {
+                Ok(())
+            }
+        }
+        assert!(register(Quiet).is_ok());
+        assert!(is_registered("test-quiet") && rules::is_known("test-quiet"));
+        assert!(register(Quiet).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! Known vulnerabilities of bundled libraries and embedded runtimes
+//!
+//! Copies of OpenSSL, zlib or a whole Electron runtime shipped inside a
+//! package are not updated by pacman. Their versions are read from the
+//! version strings compiled into them and looked up in the NVD by CPE
+//! name, which, unlike OSV's ecosystems, covers upstream releases of C
+//! libraries. Versions are heuristic: a library that patches security
+//! fixes in without a version bump is reported anyway.
+
+use std::path::{Path, PathBuf};
+
+use regex::bytes::Regex;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::config::NetworkConfig;
+use crate::error::{RexebError, Result};
+
+/// A version string identifying a component, and its CPE vendor and product
+struct Signature {
+    /// Component name shown in reports
+    name: &'static str,
+    /// CPE vendor
+    vendor: &'static str,
+    /// CPE product
+    product: &'static str,
+    /// File name prefix the string is only trusted in
+    file_prefix: Option<&'static str>,
+    /// Pattern capturing the version
+    pattern: Regex,
+}
+
+lazy_static::lazy_static! {
+    static ref SIGNATURES: Vec<Signature> = [
+        ("openssl", "openssl", "openssl", None, r"OpenSSL (\d+\.\d+\.\d+[a-z]*) +\d+ [A-Z][a-z]{2} \d{4}"),
+        ("zlib", "zlib", "zlib", None, r"(?:de|in)flate (\d+\.\d+\.\d+(?:\.\d+)?) Copyright"),
+        ("libpng", "libpng", "libpng", None, r"libpng version (\d+\.\d+\.\d+)"),
+        ("libcurl", "haxx", "libcurl", None, r"libcurl/(\d+\.\d+\.\d+)"),
+        ("expat", "libexpat_project", "libexpat", None, r"expat_(\d+\.\d+\.\d+)"),
+        ("electron", "electronjs", "electron", None, r"Electron/(\d+\.\d+\.\d+)"),
+        ("node.js", "nodejs", "node.js", None, r"nodejs\.org/download/release/v(\d+\.\d+\.\d+)/"),
+        ("python", "python", "python", Some("libpython3"), r"\x00(3\.\d+\.\d+) \("),
+    ]
+    .into_iter()
+    .map(|(name, vendor, product, file_prefix, pattern)| Signature {
+        name,
+        vendor,
+        product,
+        file_prefix,
+        pattern: Regex::new(pattern).unwrap(),
+    })
+    .collect();
+}
+
+/// A third-party component found in the payload
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Component {
+    /// Path inside the package
+    pub path: PathBuf,
+    /// Component name
+    pub name: String,
+    /// Version read from the binary
+    pub version: String,
+    /// CPE 2.3 name of this version
+    pub cpe: String,
+}
+
+/// Components whose version strings appear in a file's contents
+pub fn identify(path: &Path, data: &[u8]) -> Vec<Component> {
+    let file_name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    SIGNATURES
+        .iter()
+        .filter(|sig| sig.file_prefix.is_none_or(|prefix| file_name.starts_with(prefix)))
+        .filter_map(|sig| {
+            let version = sig.pattern.captures(data)?.get(1)?;
+            let version = String::from_utf8_lossy(version.as_bytes()).into_owned();
+            Some(Component {
+                path: path.to_path_buf(),
+                name: sig.name.to_string(),
+                cpe: format!("cpe:2.3:a:{}:{}:{}:*:*:*:*:*:*:*", sig.vendor, sig.product, version),
+                version,
+            })
+        })
+        .collect()
+}
+
+/// A published vulnerability
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vulnerability {
+    /// CVE id
+    pub id: String,
+    /// CVSS severity (`CRITICAL`, `HIGH`, ...) when scored
+    pub severity: Option<String>,
+    /// English description
+    pub summary: String,
+}
+
+/// A component with known vulnerabilities
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VulnerableComponent {
+    /// The component
+    #[serde(flatten)]
+    pub component: Component,
+    /// Vulnerabilities affecting its version
+    pub vulnerabilities: Vec<Vulnerability>,
+}
+
+/// NVD CVE API response (only the parts used)
+#[derive(Debug, Deserialize)]
+struct NvdResponse {
+    #[serde(default)]
+    vulnerabilities: Vec<NvdItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NvdItem {
+    cve: NvdCve,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NvdCve {
+    id: String,
+    #[serde(default)]
+    descriptions: Vec<NvdDescription>,
+    #[serde(default)]
+    metrics: NvdMetrics,
+}
+
+#[derive(Debug, Deserialize)]
+struct NvdDescription {
+    lang: String,
+    value: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NvdMetrics {
+    #[serde(default)]
+    cvss_metric_v31: Vec<NvdCvss>,
+    #[serde(default)]
+    cvss_metric_v30: Vec<NvdCvss>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NvdCvss {
+    cvss_data: NvdCvssData,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NvdCvssData {
+    base_severity: Option<String>,
+}
+
+impl From<NvdItem> for Vulnerability {
+    fn from(item: NvdItem) -> Self {
+        let cve = item.cve;
+        let severity = cve
+            .metrics
+            .cvss_metric_v31
+            .iter()
+            .chain(&cve.metrics.cvss_metric_v30)
+            .find_map(|m| m.cvss_data.base_severity.clone());
+        let summary = cve
+            .descriptions
+            .into_iter()
+            .find(|d| d.lang == "en")
+            .map(|d| d.value)
+            .unwrap_or_default();
+        Self { id: cve.id, severity, summary }
+    }
+}
+
+/// Client for the NVD CVE API
+pub struct NvdClient {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    offline: bool,
+}
+
+impl NvdClient {
+    /// Create a client using the configured endpoint, API key, timeout and proxy
+    pub fn new(network: &NetworkConfig) -> Result<Self> {
+        Ok(Self {
+            client: network.http_client()?,
+            base_url: network.nvd_url.clone(),
+            api_key: network.nvd_api_key.clone(),
+            offline: network.offline,
+        })
+    }
+
+    /// Whether requests are disabled by offline mode
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Vulnerabilities affecting a CPE, version ranges included
+    pub async fn vulnerabilities(&self, cpe: &str) -> Result<Vec<Vulnerability>> {
+        if self.offline {
+            return Err(RexebError::Network("offline mode is enabled".into()));
+        }
+
+        let mut request = self.client.get(&self.base_url).query(&[("virtualMatchString", cpe)]);
+        if let Some(ref key) = self.api_key {
+            request = request.header("apiKey", key);
+        }
+        let resp = request.send().await.map_err(|e| RexebError::Network(e.to_string()))?;
+        if !resp.status().is_success() {
+            return Err(RexebError::Network(format!("NVD API error: {}", resp.status())));
+        }
+
+        let nvd: NvdResponse = resp.json().await.map_err(|e| RexebError::Network(e.to_string()))?;
+        Ok(nvd.vulnerabilities.into_iter().map(Vulnerability::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify_components() {
+        let data = b"\0OpenSSL 1.1.1k  25 Mar 2021\0inflate 1.2.11 Copyright 1995-2017\0";
+        let found = identify(Path::new("/opt/app/libcrypto.so.1.1"), data);
+        let names: Vec<_> = found.iter().map(|c| (c.name.as_str(), c.version.as_str())).collect();
+        assert_eq!(names, [("openssl", "1.1.1k"), ("zlib", "1.2.11")]);
+        assert_eq!(found[0].cpe, "cpe:2.3:a:openssl:openssl:1.1.1k:*:*:*:*:*:*:*");
+
+        // The Python version pattern is only trusted in libpython
+        assert!(identify(Path::new("/opt/app/app"), b"\x003.11.4 (main").is_empty());
+        assert_eq!(identify(Path::new("/opt/app/libpython3.11.so.1.0"), b"\x003.11.4 (main")[0].version, "3.11.4");
+    }
+
+    #[test]
+    fn test_parse_nvd_response() {
+        let json = r#"{"totalResults": 1, "vulnerabilities": [{"cve": {
+            "id": "CVE-2021-3711",
+            "descriptions": [{"lang": "es", "value": "x"}, {"lang": "en", "value": "SM2 decryption overflow"}],
+            "metrics": {"cvssMetricV31": [{"cvssData": {"baseScore": 9.8, "baseSeverity": "CRITICAL"}}]}
+        }}]}"#;
+        let nvd: NvdResponse = serde_json::from_str(json).unwrap();
+        let vulns: Vec<Vulnerability> = nvd.vulnerabilities.into_iter().map(Vulnerability::from).collect();
+        assert_eq!(
+            vulns,
+            [Vulnerability {
+                id: "CVE-2021-3711".to_string(),
+                severity: Some("CRITICAL".to_string()),
+                summary: "SM2 decryption overflow".to_string(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! References to Debian-only paths and tools in shipped files
+//!
+//! Scripts, desktop entries, systemd units and configuration files keep
+//! working after conversion only if what they point at exists on Arch.
+//! Debian conventions such as `/etc/default` files or `update-alternatives`
+//! calls do not, and conversion does not rewrite them.
+
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::converter::MULTIARCH_TRIPLETS;
+use crate::error::Result;
+
+/// Largest file read; configuration and scripts are far smaller
+const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Extensions of files scanned wherever they are
+const SCANNED_EXTENSIONS: &[&str] = &[
+    "desktop", "service", "socket", "timer", "path", "mount", "target", "conf", "sh",
+];
+
+lazy_static::lazy_static! {
+    static ref PATTERNS: Vec<(Regex, &'static str)> = vec![
+        (
+            Regex::new(r"/etc/default/[\w.+-]+").unwrap(),
+            "Debian defaults file; nothing on Arch creates or reads it",
+        ),
+        (
+            Regex::new(r"\b(?:/usr/sbin/)?(?:update-alternatives|update-rc\.d|invoke-rc\.d|update-initramfs|update-grub|update-menus)\b").unwrap(),
+            "Debian maintenance tool, not available on Arch",
+        ),
+        (
+            Regex::new(r"/etc/alternatives/[\w.+-]+").unwrap(),
+            "managed by update-alternatives, which Arch does not have",
+        ),
+        (
+            Regex::new(r"\bdpkg(?:-[a-z]+)?\b").unwrap(),
+            "dpkg is not available on Arch",
+        ),
+        (
+            Regex::new(r"/lib/lsb/init-functions").unwrap(),
+            "LSB init helpers, not available on Arch",
+        ),
+        (
+            Regex::new(&format!(
+                r"/usr/lib/(?:{})/",
+                MULTIARCH_TRIPLETS.iter().map(|t| regex::escape(t)).collect::<Vec<_>>().join("|")
+            ))
+            .unwrap(),
+            "Debian multiarch directory; conversion may move its libraries but does not rewrite references",
+        ),
+    ];
+}
+
+/// A Debian-only path or tool referenced by a shipped file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebianReference {
+    /// File inside the package
+    pub file: PathBuf,
+    /// Line number, from 1
+    pub line: usize,
+    /// The matched reference
+    pub reference: String,
+    /// Why it may break
+    pub note: &'static str,
+}
+
+/// References in one file's content, with their line numbers
+pub fn scan_text(content: &str) -> Vec<(usize, String, &'static str)> {
+    let mut found = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        for (pattern, note) in PATTERNS.iter() {
+            for m in pattern.find_iter(line) {
+                found.push((number + 1, m.as_str().to_string(), *note));
+            }
+        }
+    }
+    found
+}
+
+/// Scripts, desktop entries, units and configuration files below `root` with their references
+pub fn find_debian_references(root: &Path) -> Result<Vec<DebianReference>> {
+    let mut references = Vec::new();
+    for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() || entry.metadata().map_or(true, |m| m.len() > MAX_SIZE) {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+        let data = std::fs::read(entry.path())?;
+        if !is_scanned(rel, &data) {
+            continue;
+        }
+
+        let content = String::from_utf8_lossy(&data);
+        for (line, reference, note) in scan_text(&content) {
+            references.push(DebianReference {
+                file: Path::new("/").join(rel),
+                line,
+                reference,
+                note,
+            });
+        }
+    }
+    Ok(references)
+}
+
+/// Whether a file is a text file of a kind worth scanning
+fn is_scanned(rel: &Path, data: &[u8]) -> bool {
+    if data.iter().take(8192).any(|&b| b == 0) {
+        return false;
+    }
+    data.starts_with(b"#!")
+        || rel.starts_with("etc")
+        || rel.extension().is_some_and(|e| SCANNED_EXTENSIONS.iter().any(|s| e == *s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_debian_references() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("usr/bin")).unwrap();
+        std::fs::create_dir_all(root.join("usr/share/doc/app")).unwrap();
+        std::fs::write(
+            root.join("usr/bin/app-launcher"),
+            "#!/bin/sh\n. /etc/default/app\nexec /usr/lib/x86_64-linux-gnu/app/app \"$@\"\n",
+        )
+        .unwrap();
+        // Documentation is not scanned
+        std::fs::write(root.join("usr/share/doc/app/README"), "see /etc/default/app\n").unwrap();
+
+        let found = find_debian_references(root).unwrap();
+        let shown: Vec<_> = found.iter().map(|r| (r.line, r.reference.as_str())).collect();
+        assert_eq!(shown, [(2, "/etc/default/app"), (3, "/usr/lib/x86_64-linux-gnu/")]);
+        assert_eq!(found[0].file, Path::new("/usr/bin/app-launcher"));
+
+        assert_eq!(scan_text("update-alternatives --install x")[0].1, "update-alternatives");
+    }
+}
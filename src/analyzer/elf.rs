@@ -0,0 +1,531 @@
+//! In-process ELF inspection
+//!
+//! Reads the program interpreter and the dynamic section (`DT_NEEDED`,
+//! `DT_RPATH`, `DT_RUNPATH`) of the binaries in a package, so missing
+//! libraries are found without running `ldd`, which executes code from the
+//! package being inspected.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use object::elf;
+use object::read::elf::{Dyn, FileHeader, ProgramHeader, SectionHeader};
+use object::Endianness;
+
+use crate::converter::MULTIARCH_TRIPLETS;
+use crate::error::Result;
+use crate::resolver::installability::vercmp;
+
+/// Library directories when `/etc/ld.so.conf` adds none
+pub const DEFAULT_LIBRARY_DIRS: &[&str] = &["/usr/lib", "/lib", "/usr/lib64", "/lib64"];
+
+/// Library directories for 32-bit binaries
+pub const LIB32_DIRS: &[&str] = &["/usr/lib32", "/lib32"];
+
+/// Dynamic linking information of one ELF object
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ElfInfo {
+    /// Whether this is a 64-bit object
+    pub is_64: bool,
+    /// Target machine (`e_machine`)
+    pub machine: u16,
+    /// Whether the object is big endian
+    pub big_endian: bool,
+    /// Program interpreter (`PT_INTERP`)
+    pub interpreter: Option<String>,
+    /// Library name (`DT_SONAME`)
+    pub soname: Option<String>,
+    /// Required libraries (`DT_NEEDED`)
+    pub needed: Vec<String>,
+    /// Library search path entries (`DT_RPATH` and `DT_RUNPATH`)
+    pub search_path: Vec<String>,
+    /// Symbol versions required from libraries, e.g. `(libc.so.6, GLIBC_2.34)`
+    pub version_needs: Vec<(String, String)>,
+    /// Symbol versions this object defines, e.g. `GLIBC_2.34` for libc
+    pub version_defs: Vec<String>,
+}
+
+impl ElfInfo {
+    /// Parse an ELF object, `None` when the data is not a dynamic ELF object
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        match data.get(..5)? {
+            [0x7f, b'E', b'L', b'F', elf::ELFCLASS64] => parse::<elf::FileHeader64<Endianness>>(data, true),
+            [0x7f, b'E', b'L', b'F', elf::ELFCLASS32] => parse::<elf::FileHeader32<Endianness>>(data, false),
+            _ => None,
+        }
+    }
+
+    /// Read and parse a file, `None` for anything that is not ELF
+    pub fn read(path: &Path) -> Result<Option<Self>> {
+        let mut magic = [0u8; 4];
+        let mut file = fs::File::open(path)?;
+        if file.read(&mut magic)? < 4 || magic != elf::ELFMAG {
+            return Ok(None);
+        }
+        Ok(Self::parse(&fs::read(path)?))
+    }
+
+    /// Directories the search path names, with `$ORIGIN` expanded
+    ///
+    /// `origin` is the directory of the object inside the package root;
+    /// other absolute entries are looked up below `root` and on the system.
+    pub fn search_dirs(&self, root: &Path, origin: &Path) -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        for entry in &self.search_path {
+            let entry = expand_origin(entry, origin);
+            match entry.strip_prefix('/') {
+                Some(relative) if !entry.starts_with(&*origin.to_string_lossy()) => {
+                    dirs.push(root.join(relative));
+                    dirs.push(PathBuf::from(&entry));
+                }
+                _ => dirs.push(PathBuf::from(entry)),
+            }
+        }
+        dirs
+    }
+}
+
+/// What a library search path entry points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchPathKind {
+    /// Debian multiarch directory such as `/usr/lib/x86_64-linux-gnu`
+    Multiarch,
+    /// Directory relative to the object (`$ORIGIN/../lib`)
+    Origin,
+    /// Directory the dynamic linker searches anyway
+    System,
+    /// Absolute directory the package ships
+    Packaged,
+    /// Anything else: build trees, home directories, relative paths
+    Foreign,
+}
+
+impl SearchPathKind {
+    /// Classify a `DT_RPATH`/`DT_RUNPATH` entry of an object in a package below `root`
+    pub fn classify(entry: &str, root: &Path) -> Self {
+        let path = Path::new(entry);
+        if entry.starts_with("$ORIGIN") || entry.starts_with("${ORIGIN}") {
+            Self::Origin
+        } else if !path.is_absolute() {
+            Self::Foreign
+        } else if path.components().any(|c| MULTIARCH_TRIPLETS.iter().any(|t| c.as_os_str() == *t)) {
+            Self::Multiarch
+        } else if DEFAULT_LIBRARY_DIRS.iter().chain(LIB32_DIRS).any(|dir| path == Path::new(dir)) {
+            Self::System
+        } else if root.join(path.strip_prefix("/").unwrap_or(path)).is_dir() {
+            Self::Packaged
+        } else {
+            Self::Foreign
+        }
+    }
+}
+
+/// Human-readable name of an ELF `e_machine` value
+pub fn machine_name(machine: u16) -> String {
+    match machine {
+        elf::EM_386 => "i386".into(),
+        elf::EM_X86_64 => "x86-64".into(),
+        elf::EM_ARM => "ARM".into(),
+        elf::EM_AARCH64 => "AArch64".into(),
+        elf::EM_PPC => "PowerPC".into(),
+        elf::EM_PPC64 => "PowerPC64".into(),
+        elf::EM_S390 => "s390".into(),
+        elf::EM_MIPS => "MIPS".into(),
+        elf::EM_RISCV => "RISC-V".into(),
+        elf::EM_LOONGARCH => "LoongArch".into(),
+        other => format!("machine {}", other),
+    }
+}
+
+/// Substitute the object's directory for `$ORIGIN` in a search path entry
+pub fn expand_origin(entry: &str, origin: &Path) -> String {
+    let origin = origin.to_string_lossy();
+    entry.replace("${ORIGIN}", &origin).replace("$ORIGIN", &origin)
+}
+
+/// Parse one ELF class; malformed headers count as not ELF
+fn parse<Elf: FileHeader<Endian = Endianness>>(data: &[u8], is_64: bool) -> Option<ElfInfo> {
+    let header = Elf::parse(data).ok()?;
+    let endian = header.endian().ok()?;
+    let mut info = ElfInfo {
+        is_64,
+        machine: header.e_machine(endian),
+        big_endian: endian == Endianness::Big,
+        ..ElfInfo::default()
+    };
+
+    for segment in header.program_headers(endian, data).ok()? {
+        if segment.p_type(endian) == elf::PT_INTERP {
+            if let Ok(bytes) = segment.data(endian, data) {
+                let interpreter = String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string();
+                info.interpreter = Some(interpreter);
+            }
+        }
+    }
+
+    let sections = header.sections(endian, data).ok()?;
+    for section in sections.iter() {
+        let Ok(Some((entries, link))) = section.dynamic(endian, data) else {
+            continue;
+        };
+        let Ok(strings) = sections.strings(endian, data, link) else {
+            continue;
+        };
+        for entry in entries {
+            let Some(tag) = entry.tag32(endian) else {
+                continue;
+            };
+            let Ok(value) = entry.string(endian, strings) else {
+                continue;
+            };
+            let value = String::from_utf8_lossy(value).into_owned();
+            match tag {
+                elf::DT_NEEDED => info.needed.push(value),
+                elf::DT_SONAME => info.soname = Some(value),
+                elf::DT_RPATH | elf::DT_RUNPATH => {
+                    info.search_path.extend(value.split(':').filter(|p| !p.is_empty()).map(String::from))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Ok(Some((mut needs, link))) = sections.gnu_verneed(endian, data) {
+        if let Ok(strings) = sections.strings(endian, data, link) {
+            while let Ok(Some((need, mut auxs))) = needs.next() {
+                let Ok(file) = need.file(endian, strings) else {
+                    continue;
+                };
+                let file = String::from_utf8_lossy(file).into_owned();
+                while let Ok(Some(aux)) = auxs.next() {
+                    if let Ok(name) = aux.name(endian, strings) {
+                        info.version_needs.push((file.clone(), String::from_utf8_lossy(name).into_owned()));
+                    }
+                }
+            }
+        }
+    }
+
+    if let Ok(Some((mut defs, link))) = sections.gnu_verdef(endian, data) {
+        if let Ok(strings) = sections.strings(endian, data, link) {
+            while let Ok(Some((def, mut auxs))) = defs.next() {
+                // The base entry is the soname, not a version
+                if def.vd_flags.get(endian) & elf::VER_FLG_BASE != 0 {
+                    continue;
+                }
+                if let Ok(Some(aux)) = auxs.next() {
+                    if let Ok(name) = aux.name(endian, strings) {
+                        info.version_defs.push(String::from_utf8_lossy(name).into_owned());
+                    }
+                }
+            }
+        }
+    }
+
+    Some(info)
+}
+
+/// A symbol version a system library does not define
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnmetVersion {
+    /// Library the version is needed from
+    pub library: String,
+    /// Required version, e.g. `GLIBC_2.38`
+    pub version: String,
+    /// Newest version of the same family the system library has
+    pub newest: Option<String>,
+}
+
+impl fmt::Display for UnmetVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "needs {} from {}", self.version, self.library)?;
+        match self.newest {
+            Some(ref newest) => write!(f, " (this system has up to {})", newest),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where the dynamic linker looks for libraries on this system
+#[derive(Debug, Default)]
+pub struct LibrarySearch {
+    /// Directories for 64-bit objects
+    pub dirs: Vec<PathBuf>,
+    /// Directories for 32-bit objects
+    pub dirs32: Vec<PathBuf>,
+    /// Versions defined by system libraries already read
+    defined: Mutex<HashMap<PathBuf, Vec<String>>>,
+}
+
+impl LibrarySearch {
+    /// The system's directories, from `/etc/ld.so.conf` and the defaults
+    pub fn system() -> Self {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        read_ld_so_conf(Path::new("/etc/ld.so.conf"), &mut dirs, 0);
+        dirs.extend(DEFAULT_LIBRARY_DIRS.iter().map(PathBuf::from));
+        Self {
+            dirs,
+            dirs32: LIB32_DIRS.iter().map(PathBuf::from).collect(),
+            defined: Mutex::default(),
+        }
+    }
+
+    /// Whether the system's linker path has a library of this name
+    pub fn on_system(&self, soname: &str, is_64: bool) -> bool {
+        let dirs = if is_64 { &self.dirs } else { &self.dirs32 };
+        dirs.iter().any(|dir| dir.join(soname).exists())
+    }
+
+    /// System directories for an object's class
+    fn system_dirs(&self, info: &ElfInfo) -> &[PathBuf] {
+        if info.is_64 {
+            &self.dirs
+        } else {
+            &self.dirs32
+        }
+    }
+
+    /// Directories of the package an object may load libraries from
+    fn package_dirs(&self, info: &ElfInfo, root: &Path, origin: &Path) -> Vec<PathBuf> {
+        let mut dirs = info.search_dirs(root, origin);
+        for dir in self.system_dirs(info) {
+            let packaged = root.join(dir.strip_prefix("/").unwrap_or(dir));
+            dirs.extend(MULTIARCH_TRIPLETS.iter().map(|triplet| packaged.join(triplet)));
+            dirs.push(packaged);
+        }
+        dirs
+    }
+
+    /// Needed libraries of an object in a package that nothing provides
+    ///
+    /// Libraries are looked for in the object's search path, the package's
+    /// own library directories (multiarch ones included, as the converter
+    /// moves them onto the linker path) and the system's.
+    pub fn missing<'a>(&self, info: &'a ElfInfo, root: &Path, origin: &Path) -> Vec<&'a str> {
+        let mut dirs = self.package_dirs(info, root, origin);
+        dirs.extend(self.system_dirs(info).iter().cloned());
+
+        info.needed
+            .iter()
+            .filter(|soname| !dirs.iter().any(|dir| dir.join(soname.as_str()).exists()))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Required symbol versions the system's libraries do not define
+    ///
+    /// This is what makes a binary built against a newer glibc or libstdc++
+    /// fail at startup (`version 'GLIBC_2.38' not found`). Libraries the
+    /// package ships itself, or that are missing entirely, are not checked.
+    pub fn unmet_versions(&self, info: &ElfInfo, root: &Path, origin: &Path) -> Vec<UnmetVersion> {
+        let package_dirs = self.package_dirs(info, root, origin);
+        let mut unmet = Vec::new();
+
+        for (library, version) in &info.version_needs {
+            if package_dirs.iter().any(|dir| dir.join(library).exists()) {
+                continue;
+            }
+            let Some(path) = self.system_dirs(info).iter().map(|dir| dir.join(library)).find(|p| p.exists()) else {
+                continue;
+            };
+
+            let mut defined = self.defined.lock().unwrap();
+            let defs = defined.entry(path).or_insert_with_key(|path| {
+                ElfInfo::read(path).ok().flatten().map(|lib| lib.version_defs).unwrap_or_default()
+            });
+            // Libraries without version definitions cannot be checked
+            if defs.is_empty() || defs.contains(version) {
+                continue;
+            }
+
+            unmet.push(UnmetVersion {
+                library: library.clone(),
+                version: version.clone(),
+                newest: newest_of_family(defs, version),
+            });
+        }
+
+        unmet
+    }
+}
+
+/// Newest of `defs` in the family of `version`, e.g. `GLIBC_2.39` for `GLIBC_2.40`
+fn newest_of_family(defs: &[String], version: &str) -> Option<String> {
+    let family = version_family(version).0;
+    defs.iter()
+        .filter(|def| version_family(def).0 == family)
+        .max_by(|a, b| vercmp(version_family(a).1, version_family(b).1))
+        .cloned()
+}
+
+/// Split `GLIBCXX_3.4.32` into `GLIBCXX` and `3.4.32`
+fn version_family(version: &str) -> (&str, &str) {
+    version.rsplit_once('_').unwrap_or((version, ""))
+}
+
+/// Add the directories of an `ld.so.conf` file, following `include` lines
+fn read_ld_so_conf(path: &Path, dirs: &mut Vec<PathBuf>, depth: usize) {
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+    if depth > 4 {
+        return;
+    }
+
+    for line in content.lines().map(|l| l.split('#').next().unwrap_or_default().trim()) {
+        if let Some(pattern) = line.strip_prefix("include").map(str::trim) {
+            let pattern = match pattern.starts_with('/') {
+                true => PathBuf::from(pattern),
+                false => path.parent().unwrap_or(Path::new("/")).join(pattern),
+            };
+            for include in glob_files(&pattern) {
+                read_ld_so_conf(&include, dirs, depth + 1);
+            }
+        } else if line.starts_with('/') {
+            dirs.push(PathBuf::from(line));
+        }
+    }
+}
+
+/// Files matching a `dir/*.suffix` style pattern, in name order
+fn glob_files(pattern: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(name)) = (pattern.parent(), pattern.file_name().map(|n| n.to_string_lossy())) else {
+        return Vec::new();
+    };
+    let Some((prefix, suffix)) = name.split_once('*') else {
+        return vec![pattern.to_path_buf()];
+    };
+
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .map(|n| n.to_string_lossy())
+                    .is_some_and(|n| n.starts_with(prefix) && n.ends_with(suffix))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_elf_and_missing_libraries() {
+        let exe = std::env::current_exe().unwrap();
+        let info = ElfInfo::read(&exe).unwrap().expect("test binary is ELF");
+        assert!(info.needed.iter().any(|n| n.starts_with("libc.so")));
+        assert!(info.interpreter.is_some());
+
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("script.sh"), "#!/bin/sh\n").unwrap();
+        assert_eq!(ElfInfo::read(&temp_dir.path().join("script.sh")).unwrap(), None);
+
+        // Everything but the first library is bundled
+        let root = temp_dir.path();
+        let search = LibrarySearch {
+            dirs: vec![PathBuf::from("/rexeb-test-lib")],
+            ..LibrarySearch::default()
+        };
+        let bundled = root.join("rexeb-test-lib/x86_64-linux-gnu");
+        fs::create_dir_all(&bundled).unwrap();
+        for soname in &info.needed[1..] {
+            fs::write(bundled.join(soname), "").unwrap();
+        }
+        assert_eq!(search.missing(&info, root, &root.join("usr/bin")), [info.needed[0].as_str()]);
+
+        // The test binary runs here, so the system meets its requirements
+        let system = LibrarySearch::system();
+        assert!(system.unmet_versions(&info, root, root).is_empty());
+        let (library, _) = info.version_needs.iter().find(|(_, v)| v.starts_with("GLIBC_")).unwrap();
+        let future = ElfInfo {
+            version_needs: vec![(library.clone(), "GLIBC_99.0".to_string())],
+            ..info.clone()
+        };
+        let unmet = system.unmet_versions(&future, root, root);
+        assert_eq!(unmet.len(), 1);
+        assert!(unmet[0].newest.as_deref().is_some_and(|v| v.starts_with("GLIBC_2.")));
+
+        let rpath = ElfInfo {
+            search_path: vec!["$ORIGIN/../lib".to_string(), "/opt/app/lib".to_string()],
+            ..ElfInfo::default()
+        };
+        fs::create_dir_all(root.join("opt/app/lib")).unwrap();
+        let kinds: Vec<_> = ["$ORIGIN/../lib", "/usr/lib/x86_64-linux-gnu", "/usr/lib", "/opt/app/lib", "/build/foo/lib", "lib"]
+            .iter()
+            .map(|entry| SearchPathKind::classify(entry, root))
+            .collect();
+        use SearchPathKind::*;
+        assert_eq!(kinds, [Origin, Multiarch, System, Packaged, Foreign, Foreign]);
+        assert_eq!(
+            rpath.search_dirs(Path::new("/pkg"), Path::new("/pkg/opt/app/bin")),
+            [PathBuf::from("/pkg/opt/app/bin/../lib"), PathBuf::from("/pkg/opt/app/lib"), PathBuf::from("/opt/app/lib")]
+        );
+    }
+
+    #[test]
+    fn test_unmet_versions() {
+        let defs: Vec<String> = ["GLIBC_2.2.5", "GLIBC_2.9", "GLIBC_2.34", "GLIBC_PRIVATE", "GLIBCXX_3.4.30"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(version_family("GLIBCXX_3.4.32"), ("GLIBCXX", "3.4.32"));
+        assert_eq!(version_family("GLIBC_ABI_DT_RELR"), ("GLIBC_ABI_DT", "RELR"));
+        // Compared as versions: 2.34 is newer than 2.9
+        assert_eq!(newest_of_family(&defs, "GLIBC_2.38").as_deref(), Some("GLIBC_2.34"));
+        assert_eq!(newest_of_family(&defs, "GLIBCXX_3.4.32").as_deref(), Some("GLIBCXX_3.4.30"));
+        assert_eq!(newest_of_family(&defs, "CXXABI_1.3.15"), None);
+
+        // A system library whose definitions are already known
+        let temp_dir = TempDir::new().unwrap();
+        let system_dir = temp_dir.path().join("system");
+        let root = temp_dir.path().join("pkg");
+        fs::create_dir_all(&system_dir).unwrap();
+        fs::create_dir_all(root.join("opt/app/lib")).unwrap();
+        for library in ["libc.so.6", "libnodefs.so.1"] {
+            fs::write(system_dir.join(library), "").unwrap();
+        }
+        fs::write(root.join("opt/app/lib/libbundled.so.1"), "").unwrap();
+        let search = LibrarySearch {
+            dirs: vec![system_dir.clone()],
+            ..LibrarySearch::default()
+        };
+        search.defined.lock().unwrap().insert(system_dir.join("libc.so.6"), defs);
+        search.defined.lock().unwrap().insert(system_dir.join("libnodefs.so.1"), Vec::new());
+
+        let need = |library: &str, version: &str| (library.to_string(), version.to_string());
+        let info = ElfInfo {
+            is_64: true,
+            search_path: vec!["$ORIGIN/../lib".to_string()],
+            version_needs: vec![
+                need("libc.so.6", "GLIBC_2.9"),
+                need("libc.so.6", "GLIBC_2.38"),
+                // Shipped with the package, missing, or without definitions: not checked
+                need("libbundled.so.1", "BUNDLED_2.0"),
+                need("libgone.so.1", "GONE_1.0"),
+                need("libnodefs.so.1", "NODEFS_1.0"),
+            ],
+            ..ElfInfo::default()
+        };
+        let unmet = search.unmet_versions(&info, &root, &root.join("opt/app/bin"));
+        assert_eq!(
+            unmet,
+            [UnmetVersion {
+                library: "libc.so.6".into(),
+                version: "GLIBC_2.38".into(),
+                newest: Some("GLIBC_2.34".into()),
+            }]
+        );
+        assert_eq!(unmet[0].to_string(), "needs GLIBC_2.38 from libc.so.6 (this system has up to GLIBC_2.34)");
+    }
+}
@@ -0,0 +1,110 @@
+//! Conversion feasibility score
+//!
+//! Sums up an analysis in one number from 0 to 100: every warning costs
+//! [`WARNING_COST`] points and every error [`ERROR_COST`]. Error findings
+//! mean the converted package will not work as is, so any of them caps the
+//! score below [`REVIEW_SCORE`]. Notes cost nothing.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::rules::Severity;
+use super::AnalysisReport;
+
+/// Points a warning costs
+pub const WARNING_COST: u32 = 8;
+
+/// Points an error costs
+pub const ERROR_COST: u32 = 30;
+
+/// Lowest score that is safe to convert
+pub const SAFE_SCORE: u8 = 90;
+
+/// Lowest score that is worth reviewing rather than likely broken
+pub const REVIEW_SCORE: u8 = 50;
+
+/// What the score means
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Verdict {
+    /// Converts without known problems
+    Safe,
+    /// Converts, but findings deserve a look
+    NeedsReview,
+    /// The converted package will probably not work
+    LikelyBroken,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Safe => "safe",
+            Self::NeedsReview => "needs review",
+            Self::LikelyBroken => "likely broken",
+        })
+    }
+}
+
+/// Score and verdict of an analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Feasibility {
+    /// 0 (hopeless) to 100 (nothing found)
+    pub score: u8,
+    /// What the score means
+    pub verdict: Verdict,
+}
+
+impl Feasibility {
+    /// Score the findings of a report
+    pub fn of(report: &AnalysisReport) -> Self {
+        let count = |severity| report.with_severity(severity).count() as u32;
+        let (warnings, errors) = (count(Severity::Warning), count(Severity::Error));
+
+        let cost = warnings * WARNING_COST + errors * ERROR_COST;
+        let mut score = 100u32.saturating_sub(cost) as u8;
+        if errors > 0 {
+            score = score.min(REVIEW_SCORE - 1);
+        }
+
+        let verdict = match score {
+            s if s >= SAFE_SCORE => Verdict::Safe,
+            s if s >= REVIEW_SCORE => Verdict::NeedsReview,
+            _ => Verdict::LikelyBroken,
+        };
+        Self { score, verdict }
+    }
+}
+
+impl fmt::Display for Feasibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/100 ({})", self.score, self.verdict)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::rules::Finding;
+
+    #[test]
+    fn test_feasibility() {
+        let finding = |severity| Finding {
+            rule: "fhs-violation".into(),
+            severity,
+            message: String::new(),
+        };
+        let mut report = AnalysisReport::default();
+        report.findings.push(finding(Severity::Info));
+        assert_eq!(Feasibility::of(&report), Feasibility { score: 100, verdict: Verdict::Safe });
+
+        report.findings.extend([finding(Severity::Warning), finding(Severity::Warning)]);
+        assert_eq!(Feasibility::of(&report).to_string(), "84/100 (needs review)");
+
+        report.findings.push(finding(Severity::Error));
+        assert_eq!(Feasibility::of(&report), Feasibility { score: 49, verdict: Verdict::LikelyBroken });
+
+        report.findings.extend(std::iter::repeat_n(finding(Severity::Error), 4));
+        assert_eq!(Feasibility::of(&report).score, 0);
+    }
+}
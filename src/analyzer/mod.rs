@@ -1,13 +1,48 @@
 //! Package analysis and pre-conversion checks
 
-use std::collections::HashSet;
-use std::path::Path;
-use std::process::Command;
+pub mod alternatives;
+pub mod bundled;
+pub mod cache;
+pub mod check;
+pub mod cve;
+pub mod debian_refs;
+pub mod feasibility;
+pub mod elf;
+pub mod rules;
+pub mod scripts;
+pub mod size;
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 
 use serde::{Deserialize, Serialize};
 
+use alternatives::Alternative;
+use bundled::{find_bundled, is_system_dir, BundledLibrary};
+use check::Check;
+use cve::{identify, NvdClient, VulnerableComponent};
+use debian_refs::find_debian_references;
+use feasibility::Feasibility;
+use elf::{expand_origin, machine_name, ElfInfo, LibrarySearch, SearchPathKind};
+use rules::{Finding, RuleSet, Severity};
+use scripts::{analyze_script, CommandClass};
+use size::SizeReport;
+use crate::converter::{
+    desktop_files, filesystem_symlink_conflicts, review_unit, unit_files, usrmerge_collisions, usrmerge_path, validate_desktop_entry, CensusSummary,
+    FileCensus, Framework, KernelModules, MultiarchStrategy, PrefixRelocator, SecurityProfiles, UnitIssueKind, MULTIARCH_TRIPLETS,
+};
 use crate::error::Result;
-use crate::models::{DependencyType, PackageMetadata};
+use crate::models::{DependencyType, FileOwnership, PackageMetadata};
+use crate::resolver::files::debian_library_sonames;
+use crate::resolver::{FilesDb, InstalledFiles, PackageDatabase};
+use crate::sandbox::ScriptTrace;
+
+/// User id of `nobody`, which is no build machine account
+const NOBODY_UID: u64 = 65534;
+
+/// Directory of sysusers.d entries, relative to the root
+const SYSUSERS_DIR: &str = "usr/lib/sysusers.d";
 
 /// Package analyzer for pre-conversion analysis
 pub struct PackageAnalyzer<'a> {
@@ -15,39 +50,107 @@ pub struct PackageAnalyzer<'a> {
     metadata: &'a PackageMetadata,
     /// Path to extracted data
     data_dir: &'a Path,
+    /// ELF objects in the data, read on first use
+    elf_objects: OnceLock<Vec<(PathBuf, ElfInfo)>>,
+    /// Enabled rules and their severities
+    rules: RuleSet,
+    /// Checks supplied from outside the analyzer
+    checks: Vec<Arc<dyn Check>>,
 }
 
 /// Analysis report
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AnalysisReport {
-    /// Warning messages
-    pub warnings: Vec<String>,
-    /// Error messages
-    pub errors: Vec<String>,
+    /// Findings of the enabled rules, in the order the checks ran
+    pub findings: Vec<Finding>,
     /// Total dependency count
     pub dependency_count: usize,
     /// Mapped dependencies count
     pub mapped_count: usize,
     /// Unmapped dependency names
     pub unmapped_deps: Vec<String>,
-    /// File conflicts with installed packages
-    pub conflicts: Vec<String>,
     /// Number of verified files
     pub verified_files: usize,
     /// Number of files that failed verification
     pub failed_files: usize,
-    /// FHS compliance issues
-    pub fhs_issues: Vec<String>,
-    /// Library compatibility issues
-    pub lib_issues: Vec<String>,
-    /// Security concerns
-    pub security_issues: Vec<String>,
+    /// Bytes hashed during verification
+    pub verified_bytes: u64,
+    /// Time verification took, in milliseconds
+    pub verify_millis: u64,
+    /// Shared libraries shipped outside the linker path
+    pub bundled_libs: Vec<BundledLibrary>,
+    /// Multilib packages the package's 32-bit objects need
+    #[serde(default)]
+    pub multilib_deps: Vec<String>,
+    /// Generic names the package provides through Debian alternatives
+    #[serde(default)]
+    pub alternatives: Vec<Alternative>,
+    /// What the maintainer scripts did when run in a sandbox, if they were
+    #[serde(default)]
+    pub script_traces: Vec<ScriptTrace>,
+    /// Bundled components with known CVEs
+    pub security_issues: Vec<VulnerableComponent>,
+    /// Where the space of the package goes
+    pub size: SizeReport,
+    /// Files per type
+    pub file_types: CensusSummary,
+}
+
+impl AnalysisReport {
+    /// Score and verdict of the findings
+    pub fn feasibility(&self) -> Feasibility {
+        Feasibility::of(self)
+    }
+
+    /// Findings of one rule
+    pub fn findings_of<'r>(&'r self, rule: &'r str) -> impl Iterator<Item = &'r Finding> {
+        self.findings.iter().filter(move |f| f.rule == rule)
+    }
+
+    /// Findings of one severity
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.severity == severity)
+    }
+
+    /// Whether any finding is an error
+    pub fn has_errors(&self) -> bool {
+        self.with_severity(Severity::Error).next().is_some()
+    }
+
+    /// Number of findings at or above a severity
+    pub fn count_at_least(&self, threshold: Severity) -> usize {
+        self.findings.iter().filter(|f| f.severity >= threshold).count()
+    }
 }
 
 impl<'a> PackageAnalyzer<'a> {
-    /// Create a new analyzer
+    /// Create a new analyzer with the rules configured in `[analyzer]`
     pub fn new(metadata: &'a PackageMetadata, data_dir: &'a Path) -> Result<Self> {
-        Ok(Self { metadata, data_dir })
+        let config = crate::config::Config::load().unwrap_or_default();
+        Ok(Self {
+            metadata,
+            data_dir,
+            elf_objects: OnceLock::new(),
+            rules: RuleSet::from_config(&config.analyzer)?,
+            checks: check::registered(),
+        })
+    }
+
+    /// Use these rules instead of the configured ones
+    pub fn with_rules(mut self, rules: RuleSet) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// Also run this check
+    pub fn with_check(mut self, check: impl Check + 'static) -> Self {
+        self.checks.push(Arc::new(check));
+        self
+    }
+
+    /// Record a finding unless its rule is disabled
+    fn emit(&self, report: &mut AnalysisReport, rule: &str, message: impl Into<String>) {
+        report.findings.extend(self.rules.finding(rule, message));
     }
 
     /// Perform full analysis
@@ -63,12 +166,46 @@ impl<'a> PackageAnalyzer<'a> {
         // Check FHS compliance
         self.check_fhs_compliance(&mut report)?;
 
+        // Check shipped files for references to Debian-only paths
+        self.check_debian_references(&mut report)?;
+
+        // Check for paths that collide after usrmerge
+        self.check_usrmerge(&mut report)?;
+
+        // Check desktop entries
+        self.check_desktop_files(&mut report)?;
+
+        // Check systemd units
+        self.check_units(&mut report)?;
+
+        // Check for kernel modules and DKMS sources
+        self.check_kernel_modules(&mut report)?;
+
         // Check library compatibility
         self.check_library_compatibility(&mut report)?;
 
+        // Check for objects built for another architecture
+        self.check_architectures(&mut report)?;
+
+        // Check for libraries bundled with the application
+        self.check_bundled_libraries(&mut report)?;
+
+        // Check library search paths baked into binaries
+        self.check_search_paths(&mut report)?;
+
         // Check for security issues
         self.check_security(&mut report)?;
 
+        // Check owners and modes recorded in the archive
+        self.check_ownership(&mut report)?;
+
+        // Check for AppArmor and SELinux files
+        self.check_security_profiles(&mut report)?;
+
+        // Measure the payload and classify its files
+        self.check_size(&mut report)?;
+        report.file_types = FileCensus::scan(self.data_dir)?.summary(self.data_dir);
+
         // Check file conflicts with installed packages
         if check_conflicts {
             self.check_conflicts(&mut report)?;
@@ -82,6 +219,17 @@ impl<'a> PackageAnalyzer<'a> {
         // Check maintainer scripts
         self.analyze_scripts(&mut report)?;
 
+        // Collect Debian alternatives
+        self.check_alternatives(&mut report)?;
+
+        // Run the checks supplied from outside, as configured
+        for check in &self.checks {
+            let start = report.findings.len();
+            check.run(self.metadata, self.data_dir, &mut report)?;
+            let added: Vec<_> = report.findings.drain(start..).collect();
+            report.findings.extend(added.into_iter().filter_map(|f| self.rules.configure(f)));
+        }
+
         Ok(report)
     }
 
@@ -95,7 +243,7 @@ impl<'a> PackageAnalyzer<'a> {
                     report.mapped_count += 1;
                 } else if !dep.is_virtual {
                     report.unmapped_deps.push(dep.debian_name.clone());
-                    report.warnings.push(format!(
+                    self.emit(report, "unmapped-dependency", format!(
                         "Unmapped dependency: {} (no Arch equivalent found)",
                         dep.debian_name
                     ));
@@ -103,7 +251,7 @@ impl<'a> PackageAnalyzer<'a> {
 
                 // Check for known problematic dependencies
                 if self.is_problematic_dep(&dep.debian_name) {
-                    report.warnings.push(format!(
+                    self.emit(report, "problematic-dependency", format!(
                         "Potentially problematic dependency: {}",
                         dep.debian_name
                     ));
@@ -114,6 +262,18 @@ impl<'a> PackageAnalyzer<'a> {
         Ok(())
     }
 
+    /// Redo the dependency part of a report for this analyzer's metadata
+    ///
+    /// A cached report describes the deb as parsed; after resolution the
+    /// same deb has mapped dependencies, and only this part changes.
+    pub fn refresh_dependencies(&self, report: &mut AnalysisReport) -> Result<()> {
+        report.findings.retain(|f| f.rule != "unmapped-dependency" && f.rule != "problematic-dependency");
+        report.dependency_count = 0;
+        report.mapped_count = 0;
+        report.unmapped_deps.clear();
+        self.analyze_dependencies(report)
+    }
+
     /// Check if a dependency is known to be problematic
     fn is_problematic_dep(&self, name: &str) -> bool {
         let problematic = [
@@ -145,7 +305,7 @@ impl<'a> PackageAnalyzer<'a> {
                 if let std::path::Component::Normal(name) = components[1] {
                     let name_str = name.to_string_lossy();
                     if !allowed_top_level.contains(name_str.as_ref()) {
-                        report.fhs_issues.push(format!(
+                        self.emit(report, "non-fhs-directory", format!(
                             "Non-standard directory: {}",
                             path_str
                         ));
@@ -158,7 +318,7 @@ impl<'a> PackageAnalyzer<'a> {
                 || path_str.contains("/apt/")
                 || path_str.contains("/debian/")
             {
-                report.warnings.push(format!(
+                self.emit(report, "debian-specific-path", format!(
                     "Debian-specific path: {}",
                     path_str
                 ));
@@ -168,48 +328,442 @@ impl<'a> PackageAnalyzer<'a> {
         Ok(())
     }
 
+    /// Report references to Debian-only paths and tools in shipped text files
+    fn check_debian_references(&self, report: &mut AnalysisReport) -> Result<()> {
+        for reference in find_debian_references(self.data_dir)? {
+            self.emit(report, "debian-path-reference", format!(
+                "{}:{}: {} ({})",
+                reference.file.display(),
+                reference.line,
+                reference.reference,
+                reference.note
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check for files that will collide once legacy paths move into /usr
+    fn check_usrmerge(&self, report: &mut AnalysisReport) -> Result<()> {
+        for (legacy, existing) in usrmerge_collisions(&self.metadata.files) {
+            self.emit(report, "usrmerge-collision", format!(
+                "usrmerge collision: {} and {} map to the same path; {} will be dropped",
+                legacy.display(),
+                existing.display(),
+                legacy.display()
+            ));
+        }
+
+        for (path, link) in filesystem_symlink_conflicts(self.data_dir)? {
+            let what = if path == link { "ships its own" } else { "ships files below" };
+            self.emit(report, "filesystem-symlink", format!(
+                "{}: the package {} {}, which the filesystem package owns as a symlink; pacman will refuse to install it",
+                path.display(),
+                what,
+                link.display()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validate the shipped desktop entries
+    fn check_desktop_files(&self, report: &mut AnalysisReport) -> Result<()> {
+        for path in desktop_files(self.data_dir)? {
+            let rel = path.strip_prefix(self.data_dir).unwrap_or(&path);
+            let content = String::from_utf8_lossy(&std::fs::read(&path)?).into_owned();
+            for problem in validate_desktop_entry(&content) {
+                self.emit(report, "desktop-entry", format!("Desktop entry /{}: {}", rel.display(), problem));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check systemd units for Debian accounts, defaults files and programs that go missing
+    fn check_units(&self, report: &mut AnalysisReport) -> Result<()> {
+        let config = crate::config::Config::load().unwrap_or_default();
+        let relocate = MultiarchStrategy::from_config(&config.conversion.multiarch_strategy)
+            .is_ok_and(|s| s == MultiarchStrategy::Relocate);
+
+        // Where a path ends up: multiarch relocation moves it, usrmerge symlinks keep it working
+        let locate = |path: &str| {
+            if self.data_dir.join(path.trim_start_matches('/')).symlink_metadata().is_ok() {
+                let moved = MULTIARCH_TRIPLETS
+                    .iter()
+                    .map(|triplet| format!("/{}/", triplet))
+                    .find(|needle| relocate && path.contains(needle.as_str()))
+                    .map(|needle| path.replacen(&needle, "/", 1));
+                return Some(moved.unwrap_or_else(|| path.to_string()));
+            }
+            Path::new(path).exists().then(|| path.to_string())
+        };
+
+        for path in unit_files(self.data_dir)? {
+            let rel = path.strip_prefix(self.data_dir).unwrap_or(&path);
+            let content = String::from_utf8_lossy(&std::fs::read(&path)?).into_owned();
+            for issue in review_unit(&content, locate) {
+                let rule = match issue.kind {
+                    UnitIssueKind::Account => "unit-account",
+                    UnitIssueKind::EnvironmentFile => "unit-environment-file",
+                    UnitIssueKind::Executable => "unit-executable",
+                };
+                let fixed = match issue.fix.is_some() && config.conversion.fix_units {
+                    true => "; fixed during conversion",
+                    false => "",
+                };
+                self.emit(report, rule, format!("/{}:{}: {}{}", rel.display(), issue.line, issue.message, fixed));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Report prebuilt kernel modules and module sources for DKMS
+    fn check_kernel_modules(&self, report: &mut AnalysisReport) -> Result<()> {
+        let modules = KernelModules::detect(self.data_dir)?;
+        let advice = match modules.dkms.is_empty() {
+            true => "rebuild it from source for the Arch kernel",
+            false => "convert with --pkgbuild for a DKMS package instead",
+        };
+        for (path, release) in &modules.prebuilt {
+            self.emit(report, "prebuilt-kernel-module", format!(
+                "/{}: built for kernel {}, Arch kernels will not load it; {}",
+                path.display(),
+                release,
+                advice
+            ));
+        }
+        for source in &modules.dkms {
+            self.emit(report, "dkms-source", format!(
+                "/{}: DKMS source of {} {}; `rexeb convert --pkgbuild` generates a -dkms PKGBUILD for it",
+                source.dir.display(),
+                source.name,
+                source.version
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Measure the payload and report duplicated files
+    fn check_size(&self, report: &mut AnalysisReport) -> Result<()> {
+        let size = size::measure(self.data_dir)?;
+        for duplicate in &size.duplicates {
+            let paths: Vec<String> = duplicate.paths.iter().map(|p| p.display().to_string()).collect();
+            self.emit(report, "duplicate-files", format!(
+                "{} identical copies of {} bytes: {}",
+                duplicate.paths.len(),
+                duplicate.size,
+                paths.join(", ")
+            ));
+        }
+        report.size = size;
+
+        Ok(())
+    }
+
     /// Check library compatibility
     fn check_library_compatibility(&self, report: &mut AnalysisReport) -> Result<()> {
-        // Check for bundled libraries
-        for file in &self.metadata.files {
-            let path_str = file.to_string_lossy();
-            
-            if path_str.contains(".so") && !path_str.starts_with("/usr/lib") {
-                // Library in non-standard location
-                report.lib_issues.push(format!(
-                    "Library in non-standard location: {}",
-                    path_str
+        // Needed libraries and symbol versions of every shipped binary, read without running it
+        let search = LibrarySearch::system();
+        for (path, info) in self.elf_objects() {
+            let rel = path.strip_prefix(self.data_dir).unwrap_or(path);
+
+            if let Some(ref interpreter) = info.interpreter {
+                if !Path::new(interpreter).exists() {
+                    self.emit(
+                        report,
+                        "missing-interpreter",
+                        format!("/{}: interpreter {} not found", rel.display(), interpreter),
+                    );
+                }
+            }
+
+            let origin = path.parent().unwrap_or(self.data_dir);
+            let missing = search.missing(info, self.data_dir, origin);
+            if !missing.is_empty() {
+                self.emit(report, "missing-library", format!("/{}: missing {}", rel.display(), missing.join(", ")));
+            }
+            for unmet in search.unmet_versions(info, self.data_dir, origin) {
+                self.emit(report, "unmet-symbol-version", format!("/{}: {}", rel.display(), unmet));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find ELF objects built for another architecture than the package's
+    ///
+    /// They install fine and crash when run. 32-bit x86 objects in an
+    /// x86_64 package work with the multilib libraries, which are suggested.
+    fn check_architectures(&self, report: &mut AnalysisReport) -> Result<()> {
+        let Some((machine, is_64)) = self.metadata.arch.elf_machine() else {
+            return Ok(());
+        };
+
+        let shipped: HashSet<_> = self.elf_objects().iter().filter_map(|(path, _)| path.file_name()).collect();
+        let mut needed = BTreeSet::new();
+        let mut multilib = false;
+        for (path, info) in self.elf_objects() {
+            let rel = path.strip_prefix(self.data_dir).unwrap_or(path);
+            // Firmware runs on devices, not on the CPU
+            if rel.starts_with("lib/firmware") || rel.starts_with("usr/lib/firmware") {
+                continue;
+            }
+            if info.machine == machine && info.is_64 == is_64 && !info.big_endian {
+                continue;
+            }
+
+            if machine == object::elf::EM_X86_64 && info.machine == object::elf::EM_386 && !info.big_endian {
+                multilib = true;
+                needed.extend(info.needed.iter().filter(|lib| !shipped.contains(std::ffi::OsStr::new(lib.as_str()))));
+                self.emit(report, "multilib-object", format!(
+                    "/{}: 32-bit i386 object in an x86_64 package; it needs the [multilib] repository",
+                    rel.display()
+                ));
+            } else {
+                self.emit(report, "foreign-architecture", format!(
+                    "/{}: {}{}-bit {} object in a package for {}",
+                    rel.display(),
+                    if info.big_endian { "big-endian " } else { "" },
+                    if info.is_64 { 64 } else { 32 },
+                    machine_name(info.machine),
+                    self.metadata.arch.to_arch_name()
                 ));
             }
         }
 
-        // Check glibc version requirement (if we can detect it)
-        let data_dir = self.data_dir;
-        if let Ok(output) = Command::new("find")
-            .arg(data_dir)
-            .arg("-type")
-            .arg("f")
-            .arg("-executable")
-            .output()
+        if multilib {
+            let files = FilesDb::system().unwrap_or_default();
+            let mut deps = BTreeSet::from(["lib32-glibc".to_string()]);
+            deps.extend(needed.iter().filter_map(|lib| files.lib32_owner(lib)).map(String::from));
+            report.multilib_deps = deps.into_iter().collect();
+        }
+
+        Ok(())
+    }
+
+    /// Check owners and modes the archive records, which conversion replaces with root:root
+    fn check_ownership(&self, report: &mut AnalysisReport) -> Result<()> {
+        use std::collections::BTreeMap;
+
+        let mut by_owner: BTreeMap<String, (&FileOwnership, Vec<String>)> = BTreeMap::new();
+        for entry in &self.metadata.ownership {
+            let path = entry.path.display().to_string();
+            if !entry.is_root() {
+                by_owner.entry(owner_name(entry)).or_insert_with(|| (entry, Vec::new())).1.push(path.clone());
+            }
+            // World-writable files are already reported from the payload
+            if entry.is_dir && entry.is_world_writable() {
+                self.emit(report, "world-writable", format!(
+                    "World-writable directory without the sticky bit: {} (mode {:o})",
+                    path,
+                    entry.mode & 0o7777
+                ));
+            }
+            if entry.is_inaccessible() {
+                self.emit(report, "inaccessible-file", format!(
+                    "{} has mode 000, so only root can use it",
+                    path
+                ));
+            }
+        }
+
+        let accounts = sysusers_accounts(self.data_dir);
+        for (owner, (entry, paths)) in by_owner {
+            let shown = match paths.len() {
+                n if n > 3 => format!("{} and {} more", paths[..3].join(", "), n - 3),
+                _ => paths.join(", "),
+            };
+            let advice = if entry.uid >= 1000 && entry.uid != NOBODY_UID {
+                "an account of the build machine; conversion installs them as root, which is usually what was meant".to_string()
+            } else {
+                let user = entry.user.clone().unwrap_or_else(|| entry.uid.to_string());
+                let create = match accounts.contains(&user) {
+                    true => format!("the package's sysusers.d entry creates {}", user),
+                    false => format!("create {} with a sysusers.d entry", user),
+                };
+                format!(
+                    "conversion installs them as root, so software running as {} may fail to write them; {} and restore the owner with a tmpfiles.d entry",
+                    user,
+                    create
+                )
+            };
+            self.emit(report, "file-ownership", format!(
+                "{} owns {}: {}",
+                owner,
+                shown,
+                advice
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Report AppArmor profiles and SELinux policy, which do nothing on a default Arch system
+    fn check_security_profiles(&self, report: &mut AnalysisReport) -> Result<()> {
+        let profiles = SecurityProfiles::detect(self.data_dir)?;
+        for framework in [Framework::AppArmor, Framework::SELinux] {
+            let files: Vec<String> = profiles.of(framework).map(|p| format!("/{}", p.display())).collect();
+            if files.is_empty() {
+                continue;
+            }
+            self.emit(report, "security-profile", format!(
+                "{} {} file(s) ({}): {}; conversion.strip_security_profiles drops them",
+                files.len(),
+                framework.name(),
+                files.join(", "),
+                framework.note()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Find shared libraries shipped outside the linker path and what they shadow
+    fn check_bundled_libraries(&self, report: &mut AnalysisReport) -> Result<()> {
+        let mut depends: Vec<String> = Vec::new();
+        for dep in [DependencyType::Depends, DependencyType::PreDepends]
+            .into_iter()
+            .flat_map(|t| self.metadata.get_deps(t))
         {
-            let executables = String::from_utf8_lossy(&output.stdout);
-            for exec in executables.lines().take(5) {
-                // Just check a few executables
-                if let Ok(ldd_output) = Command::new("ldd").arg(exec).output() {
-                    let ldd_str = String::from_utf8_lossy(&ldd_output.stderr);
-                    if ldd_str.contains("not found") {
-                        report.lib_issues.push(format!(
-                            "Missing library for: {}",
-                            exec
+            depends.push(dep.effective_name().to_string());
+            depends.extend(debian_library_sonames(&dep.debian_name));
+        }
+        let depends: Vec<&str> = depends.iter().map(String::as_str).collect();
+
+        // The databases are only read when there is something to look up
+        let sources: OnceLock<(Option<PackageDatabase>, FilesDb)> = OnceLock::new();
+        let owner = |soname: &str| {
+            let (db, files) = sources.get_or_init(|| (PackageDatabase::new().ok(), FilesDb::system().unwrap_or_default()));
+            db.as_ref()
+                .and_then(|db| db.soname_provider(soname))
+                .or_else(|| files.library_owner(soname))
+                .map(String::from)
+        };
+
+        report.bundled_libs = find_bundled(self.elf_objects(), self.data_dir, &LibrarySearch::system(), owner, &depends);
+        for lib in &report.bundled_libs.clone() {
+            if let Some(recommendation) = lib.recommendation() {
+                self.emit(report, "bundled-library", format!("{}: {}", lib.path.display(), recommendation));
+            }
+        }
+        Ok(())
+    }
+
+    /// Audit RPATH/RUNPATH entries against what conversion repairs
+    ///
+    /// rexeb never rewrites search paths; only moving the libraries they
+    /// point at (multiarch relocation, `--prefix`) changes whether they work.
+    fn check_search_paths(&self, report: &mut AnalysisReport) -> Result<()> {
+        let config = crate::config::Config::load().unwrap_or_default();
+        let strategy =
+            MultiarchStrategy::from_config(&config.conversion.multiarch_strategy).unwrap_or(MultiarchStrategy::Relocate);
+        let prefix = config
+            .conversion
+            .prefix
+            .as_deref()
+            .and_then(|p| PrefixRelocator::new(p, self.metadata.effective_name()).ok());
+
+        for (path, info) in self.elf_objects() {
+            let rel = path.strip_prefix(self.data_dir).unwrap_or(path);
+            let origin = path.parent().unwrap_or(self.data_dir);
+
+            for entry in &info.search_path {
+                let Some((rule, note)) = search_path_note(entry, self.data_dir, origin, strategy, prefix.as_ref()) else {
+                    continue;
+                };
+                self.emit(report, rule, format!("/{}: search path {}: {}", rel.display(), entry, note));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Look up known CVEs of bundled libraries and embedded runtimes
+    ///
+    /// Separate from [`analyze`](Self::analyze) as it queries the NVD. In
+    /// offline mode, or when the NVD cannot be reached, the scan is skipped
+    /// with a note instead of failing the analysis.
+    pub async fn check_vulnerabilities(&self, report: &mut AnalysisReport) -> Result<()> {
+        let mut components = Vec::new();
+        for (path, _) in self.elf_objects() {
+            let rel = path.strip_prefix(self.data_dir).unwrap_or(path);
+            if rel.parent().is_some_and(is_system_dir) {
+                continue;
+            }
+            if let Ok(data) = std::fs::read(path) {
+                components.extend(identify(&Path::new("/").join(rel), &data));
+            }
+        }
+        if components.is_empty() {
+            return Ok(());
+        }
+
+        let config = crate::config::Config::load().unwrap_or_default();
+        let nvd = NvdClient::new(&config.network)?;
+        if nvd.is_offline() {
+            self.emit(report, "vulnerability-scan-skipped", format!(
+                "offline mode: {} bundled component(s) not checked for known vulnerabilities",
+                components.len()
+            ));
+            return Ok(());
+        }
+
+        let mut known: std::collections::HashMap<String, Vec<cve::Vulnerability>> = Default::default();
+        let total = components.len();
+        for (checked, component) in components.into_iter().enumerate() {
+            if !known.contains_key(&component.cpe) {
+                match nvd.vulnerabilities(&component.cpe).await {
+                    Ok(vulns) => {
+                        known.insert(component.cpe.clone(), vulns);
+                    }
+                    Err(e) => {
+                        // One failure (rate limit, outage) is not worth waiting out again
+                        self.emit(report, "vulnerability-scan-skipped", format!(
+                            "{} of {} bundled component(s) not checked: {}",
+                            total - checked,
+                            total,
+                            e
                         ));
+                        break;
                     }
                 }
             }
+
+            let vulnerabilities = known[&component.cpe].clone();
+            if vulnerabilities.is_empty() {
+                continue;
+            }
+            self.emit(report, "known-vulnerability", format!(
+                "{}: {} {} has {} known vulnerabilities ({})",
+                component.path.display(),
+                component.name,
+                component.version,
+                vulnerabilities.len(),
+                vulnerabilities.iter().take(3).map(|v| v.id.as_str()).collect::<Vec<_>>().join(", ")
+            ));
+            report.security_issues.push(VulnerableComponent { component, vulnerabilities });
         }
 
         Ok(())
     }
 
+    /// ELF objects below the data directory with their dynamic linking info
+    fn elf_objects(&self) -> &[(PathBuf, ElfInfo)] {
+        self.elf_objects.get_or_init(|| {
+            walkdir::WalkDir::new(self.data_dir)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .filter_map(|e| match ElfInfo::read(e.path()) {
+                    Ok(Some(info)) => Some((e.into_path(), info)),
+                    _ => None,
+                })
+                .collect()
+        })
+    }
+
     /// Check for security issues
     fn check_security(&self, report: &mut AnalysisReport) -> Result<()> {
         for file in &self.metadata.files {
@@ -224,13 +778,13 @@ impl<'a> PackageAnalyzer<'a> {
                     if let Ok(meta) = full_path.metadata() {
                         let mode = meta.mode();
                         if mode & 0o4000 != 0 {
-                            report.security_issues.push(format!(
+                            self.emit(report, "setuid-binary", format!(
                                 "SUID binary: {}",
                                 path_str
                             ));
                         }
                         if mode & 0o2000 != 0 {
-                            report.security_issues.push(format!(
+                            self.emit(report, "setgid-binary", format!(
                                 "SGID binary: {}",
                                 path_str
                             ));
@@ -245,7 +799,7 @@ impl<'a> PackageAnalyzer<'a> {
                 use std::os::unix::fs::MetadataExt;
                 if let Ok(meta) = full_path.metadata() {
                     if meta.mode() & 0o002 != 0 && !meta.is_dir() {
-                        report.security_issues.push(format!(
+                        self.emit(report, "world-writable", format!(
                             "World-writable file: {}",
                             path_str
                         ));
@@ -304,7 +858,7 @@ impl<'a> PackageAnalyzer<'a> {
 
         // Report Java conflicts
         if has_jre && has_jdk {
-            report.warnings.push(format!(
+            self.emit(report, "java-conflict", format!(
                 "Java dependency conflict detected: both JRE ({}) and JDK ({}) dependencies present. JDK will take precedence.",
                 jre_names.join(", "),
                 jdk_names.join(", ")
@@ -316,88 +870,150 @@ impl<'a> PackageAnalyzer<'a> {
 
     /// Check for file conflicts with installed packages
     fn check_conflicts(&self, report: &mut AnalysisReport) -> Result<()> {
-        // Use pacman to check for file conflicts
-        for file in &self.metadata.files {
-            let path_str = file.to_string_lossy();
-            
-            // Skip directories
-            if path_str.ends_with('/') {
-                continue;
-            }
+        let installed = InstalledFiles::system()?;
+        if installed.is_empty() {
+            return Ok(());
+        }
 
-            // Check if file exists on system
-            if file.exists() {
-                // Try to find which package owns it
-                if let Ok(output) = Command::new("pacman")
-                    .arg("-Qo")
-                    .arg(path_str.as_ref())
-                    .output()
-                {
-                    if output.status.success() {
-                        let owner = String::from_utf8_lossy(&output.stdout);
-                        report.conflicts.push(format!(
-                            "{}: owned by {}",
-                            path_str,
-                            owner.trim()
-                        ));
-                    }
-                }
+        for file in &self.metadata.files {
+            // `/bin/foo` is `/usr/bin/foo` once installed
+            let owner = installed
+                .owner(file)
+                .or_else(|| usrmerge_path(file).and_then(|merged| installed.owner(&merged)));
+            if let Some(owner) = owner {
+                self.emit(report, "file-conflict", format!("{}: owned by {}", file.display(), owner));
             }
         }
 
         Ok(())
     }
 
-    /// Verify file integrity using md5sums
+    /// Verify file integrity against the control archive's checksums
+    ///
+    /// SHA-256 sums are used where the package ships them, md5sums
+    /// otherwise. Files are hashed in parallel.
     fn verify_files(&self, report: &mut AnalysisReport) -> Result<()> {
-        use md5::Context;
+        use rayon::prelude::*;
+        use sha2::{Digest, Sha256};
         use std::io::Read;
 
-        for (path, expected_md5) in &self.metadata.md5sums {
-            let full_path = self.data_dir.join(path.strip_prefix("/").unwrap_or(path));
-            
-            if !full_path.exists() {
-                report.failed_files += 1;
-                report.warnings.push(format!("Missing file: {}", path.display()));
-                continue;
+        let mut expected: Vec<(&PathBuf, &str, &String)> = self
+            .metadata
+            .sha256sums
+            .iter()
+            .map(|(path, sum)| (path, "SHA-256", sum))
+            .chain(
+                self.metadata
+                    .md5sums
+                    .iter()
+                    .filter(|(path, _)| !self.metadata.sha256sums.contains_key(*path))
+                    .map(|(path, sum)| (path, "MD5", sum)),
+            )
+            .collect();
+        expected.sort();
+
+        let hash_file = |path: &Path, algorithm: &str| -> std::io::Result<(String, u64)> {
+            let mut file = std::fs::File::open(path)?;
+            let mut md5 = md5::Context::new();
+            let mut sha256 = Sha256::new();
+            let mut buffer = vec![0u8; 1 << 16];
+            let mut size = 0;
+            loop {
+                let n = file.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+                size += n as u64;
+                match algorithm {
+                    "SHA-256" => sha256.update(&buffer[..n]),
+                    _ => md5.consume(&buffer[..n]),
+                }
             }
+            let hash = match algorithm {
+                "SHA-256" => hex::encode(sha256.finalize()),
+                _ => hex::encode(md5.compute().0),
+            };
+            Ok((hash, size))
+        };
 
-            // Calculate MD5 hash
-            if let Ok(mut file) = std::fs::File::open(&full_path) {
-                let mut context = Context::new();
-                let mut buffer = [0u8; 8192];
-                
-                loop {
-                    match file.read(&mut buffer) {
-                        Ok(0) => break,
-                        Ok(n) => context.consume(&buffer[..n]),
-                        Err(_) => {
-                            report.failed_files += 1;
-                            break;
-                        }
-                    }
+        let started = std::time::Instant::now();
+        let results: Vec<_> = expected
+            .par_iter()
+            .map(|(path, algorithm, _)| {
+                let full_path = self.data_dir.join(path.strip_prefix("/").unwrap_or(path));
+                match full_path.exists() {
+                    true => Some(hash_file(&full_path, algorithm)),
+                    false => None,
                 }
+            })
+            .collect();
+        report.verify_millis = started.elapsed().as_millis() as u64;
 
-                let hash = hex::encode(context.compute().0);
-                if hash == *expected_md5 {
-                    report.verified_files += 1;
-                } else {
+        for ((path, algorithm, expected_sum), result) in expected.into_iter().zip(results) {
+            match result {
+                None => {
                     report.failed_files += 1;
-                    report.warnings.push(format!(
-                        "Hash mismatch for {}: expected {}, got {}",
-                        path.display(),
-                        expected_md5,
-                        hash
-                    ));
+                    self.emit(report, "missing-file", format!("Missing file: {}", path.display()));
+                }
+                Some(Err(_)) => report.failed_files += 1,
+                Some(Ok((hash, size))) => {
+                    report.verified_bytes += size;
+                    if hash == *expected_sum {
+                        report.verified_files += 1;
+                    } else {
+                        report.failed_files += 1;
+                        self.emit(report, "checksum-mismatch", format!(
+                            "{} mismatch for {}: expected {}, got {}",
+                            algorithm,
+                            path.display(),
+                            expected_sum,
+                            hash
+                        ));
+                    }
                 }
-            } else {
-                report.failed_files += 1;
             }
         }
 
         Ok(())
     }
 
+    /// Add what the maintainer scripts did in a sandbox run to a report
+    pub fn attach_script_traces(&self, report: &mut AnalysisReport, traces: Vec<ScriptTrace>) {
+        for trace in &traces {
+            let exit = match trace.exit_code {
+                0 => String::new(),
+                code => format!(", failing with exit code {}", code),
+            };
+            self.emit(report, "maintainer-script-trace", format!(
+                "{} in a sandbox{}: {}",
+                trace.script,
+                exit,
+                trace.summary()
+            ));
+        }
+        report.script_traces = traces;
+    }
+
+    /// Summarize the generic names the package provides through alternatives
+    fn check_alternatives(&self, report: &mut AnalysisReport) -> Result<()> {
+        let found = alternatives::detect(self.metadata, self.data_dir)?;
+
+        for alternative in found.iter().filter(|a| a.master.is_none()) {
+            let followers = found.iter().filter(|a| a.master.as_ref() == Some(&alternative.name)).count();
+            self.emit(report, "debian-alternative", format!(
+                "Alternative {}{}; Arch has no alternatives system, so nothing manages the link",
+                alternative,
+                match followers {
+                    0 => String::new(),
+                    n => format!(", with {} follower link(s)", n),
+                }
+            ));
+        }
+        report.alternatives = found;
+
+        Ok(())
+    }
+
     /// Analyze maintainer scripts
     fn analyze_scripts(&self, report: &mut AnalysisReport) -> Result<()> {
         use crate::models::MaintainerScript;
@@ -410,34 +1026,81 @@ impl<'a> PackageAnalyzer<'a> {
         ];
 
         for script_type in script_types {
-            if let Some(content) = self.metadata.get_script(script_type) {
-                // Check for problematic commands
-                let problematic_patterns = [
-                    ("dpkg", "dpkg commands need translation"),
-                    ("apt-get", "apt commands not available"),
-                    ("update-rc.d", "init system commands need translation"),
-                    ("systemctl preset", "systemd preset may behave differently"),
-                    ("adduser", "adduser syntax differs on Arch"),
-                ];
-
-                for (pattern, warning) in problematic_patterns {
-                    if content.contains(pattern) {
-                        report.warnings.push(format!(
-                            "{:?} script: {}",
-                            script_type,
-                            warning
-                        ));
-                    }
+            let Some(content) = self.metadata.get_script(script_type) else {
+                continue;
+            };
+            let analysis = match analyze_script(content) {
+                Ok(analysis) => analysis,
+                Err(e) => {
+                    self.emit(report, "maintainer-script-unparsable", format!(
+                        "{:?} script could not be parsed, so it was not checked: {}",
+                        script_type,
+                        e
+                    ));
+                    continue;
                 }
+            };
 
-                // Check for debconf usage
-                if content.contains("debconf") || content.contains("db_") {
-                    report.warnings.push(format!(
-                        "{:?} script uses debconf which is not available on Arch",
-                        script_type
+            for command in analysis.lost() {
+                if let CommandClass::Untranslatable(note) = command.class {
+                    if command.name.starts_with("db_") || matches!(command.name.as_str(), "." | "source") {
+                        continue;
+                    }
+                    self.emit(report, "maintainer-script-command", format!(
+                        "{:?} script, line {}: `{}`: {}",
+                        script_type,
+                        command.line,
+                        command.text,
+                        note
                     ));
                 }
             }
+
+            for effect in &analysis.network {
+                self.emit(report, "maintainer-script-network", format!(
+                    "{:?} script, line {}: `{}`: {}",
+                    script_type,
+                    effect.line,
+                    effect.text,
+                    effect.note
+                ));
+            }
+
+            if analysis.uses_debconf {
+                let (rule, detail) = match analysis.debconf_guarded {
+                    true => ("maintainer-script-debconf-guarded", "only when debconf is installed"),
+                    false => ("maintainer-script-debconf", "which is not available on Arch"),
+                };
+                self.emit(report, rule, format!("{:?} script uses debconf {}", script_type, detail));
+            }
+
+            if !analysis.users.is_empty() || !analysis.paths.is_empty() {
+                let mut created: Vec<String> = analysis.users.iter().map(|u| format!("user {}", u)).collect();
+                created.extend(analysis.paths.iter().cloned());
+                self.emit(report, "maintainer-script-creates", format!(
+                    "{:?} script creates {}",
+                    script_type,
+                    created.join(", ")
+                ));
+            }
+
+            if !analysis.commands.is_empty() {
+                let mut lost: Vec<&str> = analysis.lost().map(|c| c.name.as_str()).collect();
+                lost.sort_unstable();
+                lost.dedup();
+                let kept = analysis.commands.len() - analysis.lost().count();
+                self.emit(report, "maintainer-script-fidelity", format!(
+                    "{:?} script: {} of {} commands carry over ({:.0}%){}",
+                    script_type,
+                    kept,
+                    analysis.commands.len(),
+                    analysis.fidelity() * 100.0,
+                    match lost.is_empty() {
+                        true => String::new(),
+                        false => format!("; lost: {}", lost.join(", ")),
+                    }
+                ));
+            }
         }
 
         Ok(())
@@ -445,6 +1108,94 @@ impl<'a> PackageAnalyzer<'a> {
 }
 
 
+/// `user:group` of an entry, by name where the archive records one
+fn owner_name(entry: &FileOwnership) -> String {
+    format!(
+        "{}:{}",
+        entry.user.clone().unwrap_or_else(|| entry.uid.to_string()),
+        entry.group.clone().unwrap_or_else(|| entry.gid.to_string())
+    )
+}
+
+/// Users and groups the payload's sysusers.d entries create
+fn sysusers_accounts(root: &Path) -> HashSet<String> {
+    let Ok(entries) = std::fs::read_dir(root.join(SYSUSERS_DIR)) else {
+        return HashSet::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| std::fs::read_to_string(entry.path()).ok())
+        .flat_map(|content| {
+            content
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.split_whitespace();
+                    match fields.next() {
+                        Some("u" | "g") => fields.next().map(str::to_string),
+                        _ => None,
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Rule and note for a search path entry of an object in `origin`, or `None` if it works as shipped
+///
+/// `strategy` and `prefix` are what conversion does with multiarch
+/// directories and `/usr`.
+fn search_path_note(
+    entry: &str,
+    data_dir: &Path,
+    origin: &Path,
+    strategy: MultiarchStrategy,
+    prefix: Option<&PrefixRelocator>,
+) -> Option<(&'static str, String)> {
+    let note = match SearchPathKind::classify(entry, data_dir) {
+        SearchPathKind::System => return None,
+        SearchPathKind::Multiarch => match strategy {
+            MultiarchStrategy::Relocate => (
+                "search-path-repaired",
+                "Debian multiarch dir; repaired, its libraries are moved to /usr/lib where the linker looks anyway".to_string(),
+            ),
+            MultiarchStrategy::LdConfig => (
+                "search-path-repaired",
+                "Debian multiarch dir; repaired, the directory is kept and registered in ld.so.conf.d".to_string(),
+            ),
+            MultiarchStrategy::Keep => (
+                "search-path",
+                "Debian multiarch dir; not repaired (multiarch_strategy = keep), works only as shipped".to_string(),
+            ),
+        },
+        SearchPathKind::Origin => {
+            let dir = expand_origin(entry, origin);
+            if Path::new(&dir).is_dir() {
+                return None;
+            }
+            let shown = Path::new(&dir).strip_prefix(data_dir).unwrap_or(Path::new(&dir));
+            (
+                "search-path",
+                format!("bundled library dir /{} is not in the package; not repairable", shown.display()),
+            )
+        }
+        SearchPathKind::Packaged => match prefix {
+            Some(prefix) if entry.starts_with("/usr/") => (
+                "search-path",
+                format!(
+                    "absolute path into the package; moves below {} with the prefix and is not rewritten",
+                    prefix.prefix().display()
+                ),
+            ),
+            _ => return None,
+        },
+        SearchPathKind::Foreign => (
+            "search-path",
+            "outside the package and system (build directory?); not repaired, search paths are not rewritten".to_string(),
+        ),
+    };
+    Some(note)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -460,7 +1211,137 @@ mod tests {
         
         analyzer.check_fhs_compliance(&mut report).unwrap();
         // Should pass with empty files list
-        assert!(report.fhs_issues.is_empty());
+        assert!(report.findings.is_empty());
+    }
+
+    #[test]
+    fn test_usrmerge_collision_warning() {
+        let mut metadata = PackageMetadata::new("test", "1.0");
+        metadata.files = vec!["/bin/tool".into(), "/usr/bin/tool".into()];
+        let temp_dir = TempDir::new().unwrap();
+
+        let analyzer = PackageAnalyzer::new(&metadata, temp_dir.path()).unwrap();
+        let mut report = AnalysisReport::default();
+
+        analyzer.check_usrmerge(&mut report).unwrap();
+        assert_eq!(report.findings_of("usrmerge-collision").count(), 1);
+        assert_eq!(report.findings[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_configured_rules() {
+        let mut metadata = PackageMetadata::new("test", "1.0");
+        metadata.files = vec!["/bin/tool".into(), "/usr/bin/tool".into(), "/usr/share/debian/x".into()];
+        let temp_dir = TempDir::new().unwrap();
+
+        let rules = RuleSet::default()
+            .disable("usrmerge-collision")
+            .with_severity("debian-specific-path", Severity::Error);
+        let analyzer = PackageAnalyzer::new(&metadata, temp_dir.path()).unwrap().with_rules(rules);
+        let mut report = AnalysisReport::default();
+
+        analyzer.check_usrmerge(&mut report).unwrap();
+        analyzer.check_fhs_compliance(&mut report).unwrap();
+        assert_eq!(report.findings.len(), 1);
+        assert_eq!(report.findings[0].rule, "debian-specific-path");
+        assert!(report.has_errors());
+        assert_eq!(report.count_at_least(Severity::Warning), 1);
+    }
+
+    #[test]
+    fn test_verify_files() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("usr/bin")).unwrap();
+        std::fs::write(temp_dir.path().join("usr/bin/a"), "abc").unwrap();
+        std::fs::write(temp_dir.path().join("usr/bin/b"), "changed").unwrap();
+
+        let mut metadata = PackageMetadata::new("test", "1.0");
+        metadata.md5sums.insert("usr/bin/a".into(), "900150983cd24fb0d6963f7d28e17f72".into());
+        metadata.md5sums.insert("usr/bin/b".into(), "92eb5ffee6ae2fec3ad71c777531578f".into());
+        metadata.md5sums.insert("usr/bin/missing".into(), "0".into());
+        // SHA-256 takes precedence; this one is of "b"
+        metadata.sha256sums.insert(
+            "usr/bin/b".into(),
+            "3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d".into(),
+        );
+
+        let analyzer = PackageAnalyzer::new(&metadata, temp_dir.path()).unwrap();
+        let mut report = AnalysisReport::default();
+        analyzer.verify_files(&mut report).unwrap();
+
+        assert_eq!((report.verified_files, report.failed_files), (1, 2));
+        assert_eq!(report.verified_bytes, 10);
+        assert_eq!(report.findings_of("missing-file").count(), 1);
+        assert!(report.findings_of("checksum-mismatch").next().unwrap().message.starts_with("SHA-256 mismatch"));
+    }
+
+    #[test]
+    fn test_mixed_architectures() {
+        // Bare ELF headers: class, byte order, e_machine
+        let header = |class: u8, data: u8, machine: u16| {
+            let mut bytes = vec![0u8; if class == 2 { 64 } else { 52 }];
+            bytes[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', class, data, 1]);
+            let (kind, machine) = match data {
+                2 => (2u16.to_be_bytes(), machine.to_be_bytes()),
+                _ => (2u16.to_le_bytes(), machine.to_le_bytes()),
+            };
+            bytes[16..18].copy_from_slice(&kind);
+            bytes[18..20].copy_from_slice(&machine);
+            bytes
+        };
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("opt/app")).unwrap();
+        std::fs::create_dir_all(root.join("usr/lib/firmware")).unwrap();
+        std::fs::write(root.join("opt/app/app"), header(2, 1, object::elf::EM_X86_64)).unwrap();
+        std::fs::write(root.join("opt/app/helper32"), header(1, 1, object::elf::EM_386)).unwrap();
+        std::fs::write(root.join("opt/app/ppc"), header(2, 2, object::elf::EM_PPC64)).unwrap();
+        std::fs::write(root.join("usr/lib/firmware/dsp.elf"), header(1, 1, object::elf::EM_ARM)).unwrap();
+
+        let mut metadata = PackageMetadata::new("test", "1.0");
+        metadata.arch = crate::models::Architecture::X86_64;
+        let analyzer = PackageAnalyzer::new(&metadata, root).unwrap();
+        let mut report = AnalysisReport::default();
+        analyzer.check_architectures(&mut report).unwrap();
+
+        let foreign: Vec<_> = report.findings_of("foreign-architecture").map(|f| f.message.as_str()).collect();
+        assert_eq!(foreign, ["/opt/app/ppc: big-endian 64-bit PowerPC64 object in a package for x86_64"]);
+        assert_eq!(report.findings_of("multilib-object").count(), 1);
+        assert_eq!(report.multilib_deps, ["lib32-glibc"]);
+    }
+
+    #[test]
+    fn test_ownership() {
+        let entry = |path: &str, uid, user: &str, mode, is_dir| FileOwnership {
+            path: path.into(),
+            uid,
+            gid: uid,
+            user: Some(user.to_string()),
+            group: Some(user.to_string()),
+            mode,
+            is_dir,
+        };
+        let mut metadata = PackageMetadata::new("test", "1.0");
+        metadata.ownership = vec![
+            entry("/usr/share/foo/a", 1000, "builder", 0o644, false),
+            entry("/var/lib/foo", 120, "foo", 0o750, true),
+            entry("/var/spool/foo", 0, "root", 0o777, true),
+            entry("/usr/share/foo/secret", 0, "root", 0, false),
+        ];
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("usr/lib/sysusers.d")).unwrap();
+        std::fs::write(temp_dir.path().join("usr/lib/sysusers.d/foo.conf"), "u foo - \"Foo daemon\" /var/lib/foo\n").unwrap();
+
+        let analyzer = PackageAnalyzer::new(&metadata, temp_dir.path()).unwrap();
+        let mut report = AnalysisReport::default();
+        analyzer.check_ownership(&mut report).unwrap();
+
+        let owners: Vec<_> = report.findings_of("file-ownership").map(|f| f.message.as_str()).collect();
+        assert_eq!(owners.len(), 2);
+        assert!(owners[0].starts_with("builder:builder owns /usr/share/foo/a: an account of the build machine"));
+        assert!(owners[1].contains("the package's sysusers.d entry creates foo"));
+        assert_eq!(report.findings_of("world-writable").count(), 1);
+        assert_eq!(report.findings_of("inaccessible-file").count(), 1);
     }
 
     #[test]
@@ -474,4 +1355,47 @@ mod tests {
         assert!(analyzer.is_problematic_dep("dpkg-dev"));
         assert!(!analyzer.is_problematic_dep("glibc"));
     }
+
+    #[test]
+    fn test_search_path_notes() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("opt/app/bin")).unwrap();
+        std::fs::create_dir_all(root.join("opt/app/lib")).unwrap();
+        std::fs::create_dir_all(root.join("usr/lib/app")).unwrap();
+        let origin = root.join("opt/app/bin");
+        let prefix = PrefixRelocator::new("/opt/{pkgname}", "app").unwrap();
+        let note = |entry: &str, strategy, prefix| search_path_note(entry, root, &origin, strategy, prefix);
+
+        // Works as shipped
+        assert_eq!(note("/usr/lib", MultiarchStrategy::Relocate, None), None);
+        assert_eq!(note("$ORIGIN/../lib", MultiarchStrategy::Relocate, None), None);
+        assert_eq!(note("${ORIGIN}/../lib", MultiarchStrategy::Relocate, None), None);
+        assert_eq!(note("/usr/lib/app", MultiarchStrategy::Relocate, None), None);
+
+        // Multiarch directories are repaired unless kept
+        let multiarch = "/usr/lib/x86_64-linux-gnu";
+        for (strategy, rule) in [
+            (MultiarchStrategy::Relocate, "search-path-repaired"),
+            (MultiarchStrategy::LdConfig, "search-path-repaired"),
+            (MultiarchStrategy::Keep, "search-path"),
+        ] {
+            assert_eq!(note(multiarch, strategy, None).unwrap().0, rule);
+        }
+
+        let (rule, message) = note("$ORIGIN/../private", MultiarchStrategy::Relocate, None).unwrap();
+        assert_eq!(rule, "search-path");
+        assert!(message.contains("/opt/app/bin/../private is not in the package"));
+
+        // Packaged paths below /usr move with the prefix
+        let (_, message) = note("/usr/lib/app", MultiarchStrategy::Relocate, Some(&prefix)).unwrap();
+        assert!(message.contains("moves below /opt/app"));
+        assert_eq!(note("/opt/app/lib", MultiarchStrategy::Relocate, Some(&prefix)), None);
+
+        for foreign in ["/build/app-1.0/lib", "lib"] {
+            let (rule, message) = note(foreign, MultiarchStrategy::Relocate, None).unwrap();
+            assert_eq!(rule, "search-path");
+            assert!(message.starts_with("outside the package"));
+        }
+    }
 }
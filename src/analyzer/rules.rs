@@ -0,0 +1,416 @@
+//! Analyzer rules
+//!
+//! Every problem the analyzer reports is a finding of a named rule, in the
+//! spirit of lintian tags. The rules live in [`RULES`]; a check refers to
+//! its rule by id, and `[analyzer]` in the configuration disables rules or
+//! overrides their severity:
+//!
+//! ```toml
+//! [analyzer]
+//! disabled_rules = ["desktop-entry"]
+//!
+//! [analyzer.severity]
+//! bundled-library = "warning"
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AnalyzerConfig;
+use crate::error::{RexebError, Result};
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Worth knowing, nothing to fix
+    Info,
+    /// Likely to need attention
+    Warning,
+    /// The converted package will not work as is
+    Error,
+}
+
+impl Severity {
+    /// Parse the configuration value
+    pub fn from_config(value: &str) -> Result<Self> {
+        match value {
+            "info" => Ok(Self::Info),
+            "warning" => Ok(Self::Warning),
+            "error" => Ok(Self::Error),
+            _ => Err(RexebError::Config(format!(
+                "Invalid severity '{}' (expected info, warning or error)",
+                value
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// A named check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+    /// Identifier used in reports and the configuration
+    pub id: &'static str,
+    /// Severity unless the configuration overrides it
+    pub severity: Severity,
+    /// What the rule reports
+    pub description: &'static str,
+}
+
+/// Every rule the analyzer knows
+pub const RULES: &[Rule] = &[
+    Rule {
+        id: "unmapped-dependency",
+        severity: Severity::Warning,
+        description: "A dependency has no Arch equivalent",
+    },
+    Rule {
+        id: "problematic-dependency",
+        severity: Severity::Warning,
+        description: "A dependency on Debian tooling (dpkg, apt, debconf, snapd)",
+    },
+    Rule {
+        id: "java-conflict",
+        severity: Severity::Warning,
+        description: "Both a JRE and a JDK are dependencies",
+    },
+    Rule {
+        id: "non-fhs-directory",
+        severity: Severity::Warning,
+        description: "A file below a top-level directory the FHS does not define",
+    },
+    Rule {
+        id: "debian-specific-path",
+        severity: Severity::Warning,
+        description: "A file in a dpkg, apt or debian directory",
+    },
+    Rule {
+        id: "debian-path-reference",
+        severity: Severity::Warning,
+        description: "A script, desktop entry, unit or config file refers to a Debian-only path or tool",
+    },
+    Rule {
+        id: "unit-account",
+        severity: Severity::Warning,
+        description: "A systemd unit runs as a Debian-only user or group",
+    },
+    Rule {
+        id: "unit-environment-file",
+        severity: Severity::Warning,
+        description: "A systemd unit reads an /etc/default environment file",
+    },
+    Rule {
+        id: "unit-executable",
+        severity: Severity::Error,
+        description: "A systemd unit runs a program that is missing or moved after conversion",
+    },
+    Rule {
+        id: "usrmerge-collision",
+        severity: Severity::Warning,
+        description: "A legacy path and its /usr counterpart are both shipped",
+    },
+    Rule {
+        id: "filesystem-symlink",
+        severity: Severity::Error,
+        description: "The payload ships an entry where the filesystem package owns a symlink",
+    },
+    Rule {
+        id: "debian-alternative",
+        severity: Severity::Info,
+        description: "The package provides a generic name through Debian alternatives",
+    },
+    Rule {
+        id: "desktop-entry",
+        severity: Severity::Warning,
+        description: "A desktop entry fails validation",
+    },
+    Rule {
+        id: "missing-interpreter",
+        severity: Severity::Error,
+        description: "A binary's ELF interpreter does not exist",
+    },
+    Rule {
+        id: "foreign-architecture",
+        severity: Severity::Error,
+        description: "An ELF object is built for another architecture or byte order than the package",
+    },
+    Rule {
+        id: "multilib-object",
+        severity: Severity::Warning,
+        description: "A 32-bit x86 object in an x86_64 package needs the multilib repository",
+    },
+    Rule {
+        id: "missing-library",
+        severity: Severity::Error,
+        description: "A needed library is neither shipped nor on the system",
+    },
+    Rule {
+        id: "unmet-symbol-version",
+        severity: Severity::Error,
+        description: "A binary needs a symbol version the system's library does not define",
+    },
+    Rule {
+        id: "search-path",
+        severity: Severity::Warning,
+        description: "An RPATH/RUNPATH entry conversion does not leave working",
+    },
+    Rule {
+        id: "search-path-repaired",
+        severity: Severity::Info,
+        description: "An RPATH/RUNPATH entry conversion keeps working by moving its libraries",
+    },
+    Rule {
+        id: "prebuilt-kernel-module",
+        severity: Severity::Error,
+        description: "A kernel module built for a Debian kernel, which Arch kernels will not load",
+    },
+    Rule {
+        id: "duplicate-files",
+        severity: Severity::Info,
+        description: "Identical files shipped more than once",
+    },
+    Rule {
+        id: "dkms-source",
+        severity: Severity::Info,
+        description: "Module source for DKMS, better converted into a -dkms PKGBUILD",
+    },
+    Rule {
+        id: "bundled-library",
+        severity: Severity::Info,
+        description: "A shipped library shadows one an Arch package provides",
+    },
+    Rule {
+        id: "known-vulnerability",
+        severity: Severity::Warning,
+        description: "A bundled library or runtime has published CVEs for its version",
+    },
+    Rule {
+        id: "vulnerability-scan-skipped",
+        severity: Severity::Info,
+        description: "Bundled components could not be checked for CVEs (offline, or the NVD failed)",
+    },
+    Rule {
+        id: "security-profile",
+        severity: Severity::Info,
+        description: "AppArmor profiles or SELinux policy modules that are inert on Arch",
+    },
+    Rule {
+        id: "setuid-binary",
+        severity: Severity::Warning,
+        description: "A file has the SUID bit set",
+    },
+    Rule {
+        id: "setgid-binary",
+        severity: Severity::Warning,
+        description: "A file has the SGID bit set",
+    },
+    Rule {
+        id: "world-writable",
+        severity: Severity::Error,
+        description: "A file anyone can write to",
+    },
+    Rule {
+        id: "inaccessible-file",
+        severity: Severity::Warning,
+        description: "A file or directory has mode 000",
+    },
+    Rule {
+        id: "file-ownership",
+        severity: Severity::Warning,
+        description: "The archive gives a file an owner other than root, which conversion drops",
+    },
+    Rule {
+        id: "file-conflict",
+        severity: Severity::Error,
+        description: "A file is owned by an installed package (--conflicts)",
+    },
+    Rule {
+        id: "missing-file",
+        severity: Severity::Error,
+        description: "A file listed in md5sums or sha256sums is not in the package (--verify)",
+    },
+    Rule {
+        id: "checksum-mismatch",
+        severity: Severity::Error,
+        description: "A file does not match its md5sums or sha256sums entry (--verify)",
+    },
+    Rule {
+        id: "maintainer-script-command",
+        severity: Severity::Warning,
+        description: "A maintainer script runs a Debian-specific command",
+    },
+    Rule {
+        id: "maintainer-script-debconf",
+        severity: Severity::Warning,
+        description: "A maintainer script uses debconf",
+    },
+    Rule {
+        id: "maintainer-script-debconf-guarded",
+        severity: Severity::Info,
+        description: "A maintainer script uses debconf only when it is installed",
+    },
+    Rule {
+        id: "maintainer-script-creates",
+        severity: Severity::Info,
+        description: "Users, groups and paths a maintainer script creates",
+    },
+    Rule {
+        id: "maintainer-script-fidelity",
+        severity: Severity::Info,
+        description: "How many commands of a maintainer script carry over to the install script",
+    },
+    Rule {
+        id: "maintainer-script-network",
+        severity: Severity::Warning,
+        description: "A maintainer script downloads files or changes package sources",
+    },
+    Rule {
+        id: "maintainer-script-trace",
+        severity: Severity::Info,
+        description: "What a maintainer script did when run in a sandbox",
+    },
+    Rule {
+        id: "maintainer-script-unparsable",
+        severity: Severity::Warning,
+        description: "A maintainer script could not be parsed and was not checked",
+    },
+];
+
+/// Rule with this id
+pub fn rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+/// Whether `id` names a built-in rule or a registered [`Check`](super::check::Check)
+pub fn is_known(id: &str) -> bool {
+    rule(id).is_some() || super::check::is_registered(id)
+}
+
+/// One problem found by a rule
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    /// Id of the rule
+    pub rule: String,
+    /// Effective severity
+    pub severity: Severity,
+    /// What was found
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.rule, self.message)
+    }
+}
+
+/// Rules as configured: which are enabled and at what severity
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    disabled: HashSet<String>,
+    severities: HashMap<String, Severity>,
+}
+
+impl RuleSet {
+    /// Rule set from `[analyzer]`, rejecting unknown rules and severities
+    pub fn from_config(config: &AnalyzerConfig) -> Result<Self> {
+        let known = |id: &str| match is_known(id) {
+            true => Ok(id.to_string()),
+            false => Err(RexebError::Config(format!("Unknown analyzer rule: {}", id))),
+        };
+
+        let disabled = config
+            .disabled_rules
+            .iter()
+            .map(|id| known(id))
+            .collect::<Result<_>>()?;
+        let severities = config
+            .severity
+            .iter()
+            .map(|(id, severity)| Ok((known(id)?, Severity::from_config(severity)?)))
+            .collect::<Result<_>>()?;
+        Ok(Self { disabled, severities })
+    }
+
+    /// Disable a rule
+    pub fn disable(mut self, id: &str) -> Self {
+        self.disabled.insert(id.to_string());
+        self
+    }
+
+    /// Override a rule's severity
+    pub fn with_severity(mut self, id: &str, severity: Severity) -> Self {
+        self.severities.insert(id.to_string(), severity);
+        self
+    }
+
+    /// Severity findings of a rule get, `None` when the rule is disabled
+    ///
+    /// Panics on ids missing from [`RULES`], which are bugs in a check.
+    pub fn severity(&self, id: &str) -> Option<Severity> {
+        let rule = rule(id).unwrap_or_else(|| panic!("analyzer rule {} is not registered", id));
+        if self.disabled.contains(id) {
+            return None;
+        }
+        Some(self.severities.get(id).copied().unwrap_or(rule.severity))
+    }
+
+    /// Apply the configuration to a finding of a [`Check`](super::check::Check),
+    /// `None` when its rule is disabled
+    pub fn configure(&self, mut finding: Finding) -> Option<Finding> {
+        if self.disabled.contains(&finding.rule) {
+            return None;
+        }
+        if let Some(severity) = self.severities.get(&finding.rule) {
+            finding.severity = *severity;
+        }
+        Some(finding)
+    }
+
+    /// Finding of a rule, `None` when the rule is disabled
+    pub fn finding(&self, id: &str, message: impl Into<String>) -> Option<Finding> {
+        Some(Finding {
+            rule: id.to_string(),
+            severity: self.severity(id)?,
+            message: message.into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_set_from_config() {
+        let mut config = AnalyzerConfig::default();
+        config.disabled_rules.push("desktop-entry".to_string());
+        config.severity.insert("bundled-library".to_string(), "error".to_string());
+        let rules = RuleSet::from_config(&config).unwrap();
+
+        assert_eq!(rules.severity("desktop-entry"), None);
+        assert_eq!(rules.severity("bundled-library"), Some(Severity::Error));
+        assert_eq!(rules.severity("missing-library"), Some(Severity::Error));
+        assert_eq!(rules.finding("usrmerge-collision", "x").unwrap().to_string(), "[usrmerge-collision] x");
+
+        config.disabled_rules.push("no-such-rule".to_string());
+        assert!(RuleSet::from_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_rule_ids_are_unique() {
+        let ids: HashSet<_> = RULES.iter().map(|rule| rule.id).collect();
+        assert_eq!(ids.len(), RULES.len());
+    }
+}
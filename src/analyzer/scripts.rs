@@ -0,0 +1,463 @@
+//! Maintainer script analysis
+//!
+//! Scripts are parsed into a syntax tree rather than searched as text, so
+//! commented-out code and here-document contents do not count. Every
+//! command is classified by what conversion does with it: translated into
+//! an install script, kept as is because it works on Arch, or lost.
+//! Commands run only behind a condition that tests for them (`if
+//! command -v dpkg`, `[ -e /usr/share/debconf/confmodule ] && ...`) are
+//! recorded as guarded: the script already copes with their absence.
+//!
+//! Network side effects are recorded separately: downloads, scripts piped
+//! from a download into a shell, and APT keys or repositories. A pacman
+//! `.INSTALL` replays them on every install without the network being
+//! checked, or available at all.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::error::Result;
+use crate::parsers::shell::{self, Command, Node, Word};
+
+use super::alternatives::Alternative;
+
+/// Commands the install script translator handles
+const TRANSLATED: &[&str] = &[
+    "ldconfig",
+    "update-desktop-database",
+    "update-mime-database",
+    "gtk-update-icon-cache",
+    "systemctl",
+    "update-rc.d",
+    "invoke-rc.d",
+    "service",
+    "dpkg-maintscript-helper",
+];
+
+/// Debian-only commands and what is lost with them
+const UNTRANSLATABLE: &[(&str, &str)] = &[
+    ("dpkg", "dpkg is not available on Arch"),
+    ("apt", "apt is not available on Arch"),
+    ("ucf", "ucf configuration file handling is lost; pacman uses .pacnew files"),
+    ("ucfr", "ucf configuration file handling is lost; pacman uses .pacnew files"),
+    ("update-alternatives", "alternatives are not available on Arch"),
+    ("deb-systemd-helper", "debhelper's systemd helper is not available; enable units with systemctl"),
+    ("deb-systemd-invoke", "debhelper's systemd helper is not available; start units with systemctl"),
+    ("adduser", "adduser syntax differs on Arch; use a sysusers.d entry"),
+    ("addgroup", "addgroup is not available on Arch; use a sysusers.d entry"),
+    ("deluser", "deluser is not available on Arch"),
+    ("delgroup", "delgroup is not available on Arch"),
+    ("update-initramfs", "Arch rebuilds initramfs images with mkinitcpio hooks"),
+    ("apparmor_parser", "AppArmor is not enabled on a default Arch system"),
+    ("aa-complain", "AppArmor is not enabled on a default Arch system"),
+    ("aa-enforce", "AppArmor is not enabled on a default Arch system"),
+    ("aa-disable", "AppArmor is not enabled on a default Arch system"),
+    ("semanage", "SELinux is not supported on Arch"),
+    ("semodule", "SELinux is not supported on Arch"),
+    ("setsebool", "SELinux is not supported on Arch"),
+    ("restorecon", "SELinux is not supported on Arch"),
+    ("db_", "debconf is not available on Arch"),
+];
+
+/// Commands that download
+const FETCHERS: &[&str] = &["curl", "wget"];
+
+/// Programs that run a script read from standard input
+const INTERPRETERS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh", "python", "python3", "perl", "ruby"];
+
+/// Commands with network side effects, the subcommand they need and what they do
+const NETWORK_COMMANDS: &[(&str, Option<&str>, &str)] = &[
+    ("apt-key", None, "adds an APT signing key"),
+    ("add-apt-repository", None, "adds an APT repository"),
+    ("git", Some("clone"), "clones a repository"),
+    ("git", Some("pull"), "pulls from a repository"),
+    ("pip", Some("install"), "installs Python packages from PyPI"),
+    ("pip3", Some("install"), "installs Python packages from PyPI"),
+    ("npm", Some("install"), "installs packages from npm"),
+    ("gem", Some("install"), "installs gems from RubyGems"),
+    ("gpg", Some("--recv-keys"), "fetches keys from a keyserver"),
+];
+
+/// Directories pacman owns, where downloaded files are untracked
+const SYSTEM_DIRS: &[&str] = &["/usr", "/bin", "/sbin", "/lib", "/lib64", "/etc", "/opt"];
+
+/// Commands creating users and groups, and their options taking a value
+const ACCOUNT_COMMANDS: &[(&str, &[&str])] = &[
+    ("adduser", &["--home", "--shell", "--uid", "--gid", "--ingroup", "--gecos", "--firstuid", "--lastuid"]),
+    ("useradd", &["-d", "--home-dir", "-s", "--shell", "-u", "--uid", "-g", "--gid", "-G", "--groups", "-c", "--comment"]),
+    ("addgroup", &["--gid"]),
+    ("groupadd", &["-g", "--gid"]),
+];
+
+/// What conversion does with a command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandClass {
+    /// Translated into the install script
+    Translated,
+    /// Works on Arch as it is
+    Portable,
+    /// Lost, with the reason
+    Untranslatable(&'static str),
+}
+
+/// A classified command of a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptCommand {
+    /// Line number, from 1
+    pub line: usize,
+    /// Command name
+    pub name: String,
+    /// Source text
+    pub text: String,
+    /// What conversion does with it
+    pub class: CommandClass,
+    /// Whether it runs only behind a condition testing for it
+    pub guarded: bool,
+}
+
+/// A network side effect of a script
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkEffect {
+    /// Line number, from 1
+    pub line: usize,
+    /// Source text
+    pub text: String,
+    /// What it does
+    pub note: String,
+}
+
+/// What a maintainer script does
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ScriptAnalysis {
+    /// Every command with a literal name, in order
+    pub commands: Vec<ScriptCommand>,
+    /// Users and groups the script creates
+    pub users: BTreeSet<String>,
+    /// Paths the script creates
+    pub paths: BTreeSet<String>,
+    /// Whether the script loads debconf
+    pub uses_debconf: bool,
+    /// Whether every debconf use is guarded
+    pub debconf_guarded: bool,
+    /// Downloads and other network side effects, in order
+    pub network: Vec<NetworkEffect>,
+    /// Alternatives the script registers
+    pub alternatives: Vec<Alternative>,
+}
+
+impl ScriptAnalysis {
+    /// Commands lost in conversion that nothing guards
+    pub fn lost(&self) -> impl Iterator<Item = &ScriptCommand> {
+        self.commands
+            .iter()
+            .filter(|c| !c.guarded && matches!(c.class, CommandClass::Untranslatable(_)))
+    }
+
+    /// Share of commands carried over, translated or as they are
+    pub fn fidelity(&self) -> f64 {
+        if self.commands.is_empty() {
+            return 1.0;
+        }
+        let kept = self.commands.len() - self.lost().count();
+        kept as f64 / self.commands.len() as f64
+    }
+}
+
+/// Parse and classify a maintainer script
+pub fn analyze_script(script: &str) -> Result<ScriptAnalysis> {
+    let nodes = shell::parse(script)?;
+    let mut analysis = ScriptAnalysis {
+        debconf_guarded: true,
+        ..Default::default()
+    };
+    walk(&nodes, &[], &mut analysis)?;
+    Ok(analysis)
+}
+
+/// Classify a command by its name
+pub fn classify(name: &str) -> CommandClass {
+    let base = name.rsplit('/').next().unwrap_or(name);
+    if TRANSLATED.contains(&base) || name.starts_with("/etc/init.d/") {
+        return CommandClass::Translated;
+    }
+    UNTRANSLATABLE
+        .iter()
+        .find(|(prefix, _)| match *prefix {
+            "db_" => base.starts_with(prefix),
+            _ => base == *prefix || base.starts_with(&format!("{}-", prefix)),
+        })
+        .map_or(CommandClass::Portable, |(_, note)| CommandClass::Untranslatable(note))
+}
+
+fn walk(nodes: &[Node], guards: &[String], analysis: &mut ScriptAnalysis) -> Result<()> {
+    for node in nodes {
+        walk_node(node, guards, analysis)?;
+    }
+    Ok(())
+}
+
+fn walk_node(node: &Node, guards: &[String], analysis: &mut ScriptAnalysis) -> Result<()> {
+    let with = |extra: String| {
+        let mut guards = guards.to_vec();
+        guards.push(extra);
+        guards
+    };
+    match node {
+        Node::Simple(command) => record(command, guards, analysis)?,
+        Node::Pipeline { commands, .. } => {
+            let start = analysis.network.len();
+            walk(commands, guards, analysis)?;
+            if let Some(interpreter) = piped_interpreter(commands) {
+                // The download itself is part of the one effect
+                let line = analysis.network.get(start).map_or(0, |effect| effect.line);
+                analysis.network.truncate(start);
+                analysis.network.push(NetworkEffect {
+                    line,
+                    text: source(node),
+                    note: format!("pipes a download into {}, running code nobody reviewed", interpreter),
+                });
+            }
+        }
+        Node::Group(commands) => walk(commands, guards, analysis)?,
+        Node::List { left, right, .. } => {
+            walk_node(left, guards, analysis)?;
+            walk_node(right, &with(source(left)), analysis)?;
+        }
+        Node::If { branches, otherwise } => {
+            let mut conditions = Vec::new();
+            for (condition, body) in branches {
+                walk(condition, guards, analysis)?;
+                let text: String = condition.iter().map(source).collect::<Vec<_>>().join("; ");
+                conditions.push(text.clone());
+                walk(body, &with(text), analysis)?;
+            }
+            walk(otherwise, &with(conditions.join("; ")), analysis)?;
+        }
+        Node::Case { arms, .. } => {
+            for arm in arms {
+                walk(&arm.body, guards, analysis)?;
+            }
+        }
+        Node::Loop { condition, body } => {
+            walk(condition, guards, analysis)?;
+            let text = condition.iter().map(source).collect::<Vec<_>>().join("; ");
+            walk(body, &with(text), analysis)?;
+        }
+        Node::For { body, .. } => walk(body, guards, analysis)?,
+        Node::Function { body, .. } => walk_node(body, guards, analysis)?,
+    }
+    Ok(())
+}
+
+/// Source text of the commands in a node, for matching guards
+fn source(node: &Node) -> String {
+    match node {
+        Node::Simple(command) => command.text(),
+        Node::Pipeline { commands, .. } | Node::Group(commands) => {
+            commands.iter().map(source).collect::<Vec<_>>().join(" | ")
+        }
+        Node::List { left, right, .. } => format!("{} {}", source(left), source(right)),
+        _ => String::new(),
+    }
+}
+
+fn record(command: &Command, guards: &[String], analysis: &mut ScriptAnalysis) -> Result<()> {
+    for word in command.assignments.iter().chain(&command.words) {
+        for body in &word.substitutions {
+            walk(&shell::parse(body)?, guards, analysis)?;
+        }
+    }
+
+    let Some(name) = command.name() else {
+        return Ok(());
+    };
+    let args: Vec<String> = command.words[1..].iter().filter_map(|w| w.literal()).collect();
+    let base = name.rsplit('/').next().unwrap_or(&name).to_string();
+
+    let is_debconf = base.starts_with("db_")
+        || (matches!(base.as_str(), "." | "source") && args.iter().any(|a| a.contains("debconf/confmodule")));
+    let guarded = guards.iter().any(|guard| match is_debconf {
+        true => guard.contains("debconf") || guard.contains("confmodule"),
+        false => guard.contains(&base),
+    });
+    if is_debconf {
+        analysis.uses_debconf = true;
+        analysis.debconf_guarded &= guarded;
+    }
+
+    if let Some((_, valued)) = ACCOUNT_COMMANDS.iter().find(|(c, _)| *c == base) {
+        let mut args = args.iter();
+        let mut operands = Vec::new();
+        while let Some(arg) = args.next() {
+            if valued.contains(&arg.as_str()) {
+                args.next();
+            } else if !arg.starts_with('-') {
+                operands.push(arg.clone());
+            }
+        }
+        // `adduser user group` adds an existing user to a group
+        if base != "adduser" || operands.len() == 1 {
+            analysis.users.extend(operands.into_iter().take(1));
+        }
+    }
+    match base.as_str() {
+        "mkdir" | "touch" => analysis.paths.extend(args.iter().filter(|a| a.starts_with('/')).cloned()),
+        "install" if args.iter().any(|a| a == "-d") => {
+            analysis.paths.extend(args.iter().filter(|a| a.starts_with('/')).cloned())
+        }
+        "ln" => analysis.paths.extend(args.last().filter(|a| a.starts_with('/')).cloned()),
+        _ => {}
+    }
+
+    if base == "update-alternatives" {
+        let words: Vec<Option<String>> = command.words[1..].iter().map(Word::literal).collect();
+        analysis.alternatives.extend(Alternative::from_install(&words));
+    }
+
+    if let Some(note) = network_effect(&base, &args, command) {
+        analysis.network.push(NetworkEffect {
+            line: command.line,
+            text: command.text(),
+            note,
+        });
+    }
+
+    let class = match is_debconf {
+        true => CommandClass::Untranslatable("debconf is not available on Arch"),
+        false => classify(&name),
+    };
+    analysis.commands.push(ScriptCommand {
+        line: command.line,
+        text: command.text(),
+        name: base,
+        class,
+        guarded,
+    });
+    Ok(())
+}
+
+/// The interpreter a pipeline feeds a download into
+fn piped_interpreter(commands: &[Node]) -> Option<String> {
+    let names: Vec<String> = commands
+        .iter()
+        .map(|node| match node {
+            Node::Simple(command) => {
+                // `| sudo sh` and `| env sh` run the program named after the options
+                let words: Vec<String> = command.words.iter().filter_map(|w| w.literal()).collect();
+                let program = match words.first().map(String::as_str) {
+                    Some("sudo" | "env") => words[1..].iter().find(|w| !w.starts_with('-') && !w.contains('=')),
+                    _ => words.first(),
+                };
+                program.map(|p| p.rsplit('/').next().unwrap_or(p).to_string()).unwrap_or_default()
+            }
+            _ => String::new(),
+        })
+        .collect();
+    let fetch = names.iter().position(|name| FETCHERS.contains(&name.as_str()))?;
+    names[fetch + 1..].iter().find(|name| INTERPRETERS.contains(&name.as_str())).cloned()
+}
+
+/// What a command does over the network, if anything
+fn network_effect(base: &str, args: &[String], command: &Command) -> Option<String> {
+    let into_system = |target: &str| SYSTEM_DIRS.iter().any(|dir| Path::new(target).starts_with(dir));
+    let redirected = command
+        .redirects
+        .iter()
+        .filter(|(op, _)| op.starts_with('>'))
+        .filter_map(|(_, target)| target.literal());
+
+    if FETCHERS.contains(&base) {
+        let output_options: &[&str] = match base {
+            "curl" => &["-o", "--output", "--output-dir"],
+            _ => &["-O", "--output-document", "-P", "--directory-prefix"],
+        };
+        let targets = args
+            .windows(2)
+            .filter(|pair| output_options.contains(&pair[0].as_str()))
+            .map(|pair| pair[1].clone())
+            .chain(redirected);
+        return Some(match targets.filter(|t| into_system(t)).collect::<Vec<_>>() {
+            system if !system.is_empty() => {
+                format!("downloads into {}, files pacman does not track", system.join(", "))
+            }
+            _ => "downloads from the network".to_string(),
+        });
+    }
+
+    for target in redirected {
+        if target.starts_with("/etc/apt/sources.list") {
+            return Some("adds an APT repository".to_string());
+        }
+    }
+
+    NETWORK_COMMANDS
+        .iter()
+        .find(|(name, subcommand, _)| *name == base && subcommand.is_none_or(|sub| args.iter().any(|a| a == sub)))
+        .map(|(_, _, note)| note.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_script() {
+        let script = r#"#!/bin/sh
+set -e
+# update-alternatives --install /usr/bin/foo foo /usr/bin/foo.real 10
+if [ -e /usr/share/debconf/confmodule ]; then
+    . /usr/share/debconf/confmodule
+    db_get foo/port
+fi
+if ! getent passwd foo >/dev/null; then
+    adduser --system --home /var/lib/foo --group foo
+fi
+mkdir -p /var/lib/foo /var/log/foo
+command -v dpkg-query >/dev/null && dpkg-query -W foo
+ldconfig
+dpkg-trigger foo-trigger
+"#;
+        let analysis = analyze_script(script).unwrap();
+
+        assert!(analysis.uses_debconf && analysis.debconf_guarded);
+        assert_eq!(analysis.users.iter().collect::<Vec<_>>(), ["foo"]);
+        assert_eq!(analysis.paths.iter().collect::<Vec<_>>(), ["/var/lib/foo", "/var/log/foo"]);
+
+        let lost: Vec<_> = analysis.lost().map(|c| (c.line, c.name.as_str())).collect();
+        assert_eq!(lost, [(9, "adduser"), (14, "dpkg-trigger")]);
+        assert!(analysis.commands.iter().all(|c| c.name != "update-alternatives"));
+        assert_eq!(classify("/etc/init.d/foo"), CommandClass::Translated);
+        // set, [, ., db_get, getent, adduser, mkdir, command, dpkg-query, ldconfig, dpkg-trigger
+        assert_eq!(analysis.commands.len(), 11);
+        assert!((analysis.fidelity() - 9.0 / 11.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_network_effects() {
+        let script = r#"#!/bin/sh
+curl -fsSL https://example.com/install.sh | sudo bash -s --
+wget -q -O /usr/share/foo/data.bin https://example.com/data.bin
+wget -qO- https://example.com/key.asc | apt-key add -
+echo "deb https://example.com/apt stable main" > /etc/apt/sources.list.d/foo.list
+git -C /opt/foo pull
+pip3 install --upgrade foo
+curl -o /tmp/foo.tgz https://example.com/foo.tgz
+"#;
+        let analysis = analyze_script(script).unwrap();
+        let effects: Vec<_> = analysis.network.iter().map(|e| (e.line, e.note.as_str())).collect();
+        assert_eq!(
+            effects,
+            [
+                (2, "pipes a download into bash, running code nobody reviewed"),
+                (3, "downloads into /usr/share/foo/data.bin, files pacman does not track"),
+                (4, "downloads from the network"),
+                (4, "adds an APT signing key"),
+                (5, "adds an APT repository"),
+                (6, "pulls from a repository"),
+                (7, "installs Python packages from PyPI"),
+                (8, "downloads from the network"),
+            ]
+        );
+        assert_eq!(analysis.network[0].text, "curl -fsSL https://example.com/install.sh | sudo bash -s --");
+    }
+}
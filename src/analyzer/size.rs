@@ -0,0 +1,247 @@
+//! Payload size breakdown
+//!
+//! Shows where the space of a package goes: its largest files, the size of
+//! each top-level directory, identical files shipped more than once and
+//! what the package would weigh in each output format. Compressed sizes
+//! are estimated from a sample of every file, compressed with the codecs
+//! and levels the builder uses.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::OutputFormat;
+use crate::error::Result;
+
+/// Largest files listed
+pub const TOP_FILES: usize = 10;
+
+/// Uncompressed bytes compressed to estimate the compression ratio
+const SAMPLE_BUDGET: u64 = 4 * 1024 * 1024;
+
+/// Smallest sample taken from a file, so small files are represented
+const MIN_FILE_SAMPLE: u64 = 4096;
+
+/// A file and its size
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSize {
+    /// Path inside the package
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// Estimated package size in one output format
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompressedSize {
+    /// Package file extension (`pkg.tar.zst`, ...)
+    pub format: String,
+    /// Estimated size in bytes
+    pub size: u64,
+}
+
+/// Identical files
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DuplicateFiles {
+    /// Size of each copy
+    pub size: u64,
+    /// Paths of the copies
+    pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateFiles {
+    /// Bytes the extra copies take
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// Where the space of a package goes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeReport {
+    /// Total size of the regular files
+    pub total_size: u64,
+    /// Number of regular files
+    pub file_count: usize,
+    /// Largest files, largest first
+    pub largest: Vec<FileSize>,
+    /// Size per top-level directory (two levels below `/usr`), largest first
+    pub directories: Vec<FileSize>,
+    /// Estimated package size per output format
+    pub compressed: Vec<CompressedSize>,
+    /// Files shipped more than once, most space wasted first
+    pub duplicates: Vec<DuplicateFiles>,
+}
+
+/// Measure the regular files below a package root
+pub fn measure(root: &Path) -> Result<SizeReport> {
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            files.push((Path::new("/").join(rel), entry.metadata()?.len()));
+        }
+    }
+
+    let mut report = SizeReport {
+        total_size: files.iter().map(|(_, size)| size).sum(),
+        file_count: files.len(),
+        ..Default::default()
+    };
+
+    let mut largest: Vec<FileSize> = files
+        .iter()
+        .map(|(path, size)| FileSize { path: path.clone(), size: *size })
+        .collect();
+    largest.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    largest.truncate(TOP_FILES);
+    report.largest = largest;
+
+    let mut directories: BTreeMap<PathBuf, u64> = BTreeMap::new();
+    for (path, size) in &files {
+        *directories.entry(top_directory(path)).or_default() += size;
+    }
+    report.directories = directories.into_iter().map(|(path, size)| FileSize { path, size }).collect();
+    report.directories.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+
+    report.duplicates = find_duplicates(root, &files)?;
+    report.compressed = estimate_compressed(root, &files, report.total_size)?;
+
+    Ok(report)
+}
+
+/// `/usr/share` for `/usr/share/doc/foo`, `/opt` for `/opt/foo/bin/foo`
+fn top_directory(path: &Path) -> PathBuf {
+    let mut components = path.components().skip(1);
+    let mut dir = PathBuf::from("/");
+    if let Some(first) = components.next() {
+        dir.push(first);
+        if first.as_os_str() == "usr" {
+            dir.extend(components.next());
+        }
+    }
+    // A file directly in / or /usr counts as its own directory's
+    if dir == path {
+        dir.pop();
+    }
+    dir
+}
+
+/// Groups of identical non-empty files
+fn find_duplicates(root: &Path, files: &[(PathBuf, u64)]) -> Result<Vec<DuplicateFiles>> {
+    // Only files of equal size can be identical
+    let mut by_size: HashMap<u64, Vec<&PathBuf>> = HashMap::new();
+    for (path, size) in files.iter().filter(|(_, size)| *size > 0) {
+        by_size.entry(*size).or_default().push(path);
+    }
+
+    let mut duplicates = Vec::new();
+    for (size, paths) in by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        let mut by_digest: BTreeMap<[u8; 16], Vec<PathBuf>> = BTreeMap::new();
+        for path in paths {
+            let mut file = File::open(root.join(path.strip_prefix("/").unwrap_or(path)))?;
+            let mut context = md5::Context::new();
+            let mut buffer = [0u8; 8192];
+            loop {
+                let n = file.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+                context.consume(&buffer[..n]);
+            }
+            by_digest.entry(context.compute().0).or_default().push(path.clone());
+        }
+        duplicates.extend(
+            by_digest
+                .into_values()
+                .filter(|paths| paths.len() > 1)
+                .map(|paths| DuplicateFiles { size, paths }),
+        );
+    }
+
+    duplicates.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.paths.cmp(&b.paths)));
+    Ok(duplicates)
+}
+
+/// Estimated package size per output format
+fn estimate_compressed(root: &Path, files: &[(PathBuf, u64)], total_size: u64) -> Result<Vec<CompressedSize>> {
+    // Every file contributes a prefix in proportion to its size
+    let mut sample = Vec::new();
+    for (path, size) in files {
+        let share = match total_size <= SAMPLE_BUDGET {
+            true => *size,
+            false => (size * SAMPLE_BUDGET / total_size).max(MIN_FILE_SAMPLE),
+        };
+        File::open(root.join(path.strip_prefix("/").unwrap_or(path)))?
+            .take(share)
+            .read_to_end(&mut sample)?;
+    }
+    if sample.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut estimates = Vec::new();
+    for format in [OutputFormat::PkgTarZst, OutputFormat::PkgTarXz, OutputFormat::PkgTarGz] {
+        let compressed = compress(format, &sample)?;
+        let size = (total_size as u128 * compressed as u128 / sample.len() as u128) as u64;
+        estimates.push(CompressedSize {
+            format: format.extension().to_string(),
+            size,
+        });
+    }
+    Ok(estimates)
+}
+
+/// Compressed length of `data` at the builder's settings for a format
+fn compress(format: OutputFormat, data: &[u8]) -> Result<usize> {
+    let compressed = match format {
+        OutputFormat::PkgTarZst => zstd::encode_all(data, 19)?,
+        OutputFormat::PkgTarXz => {
+            let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+            encoder.write_all(data)?;
+            encoder.finish()?
+        }
+        OutputFormat::PkgTarGz => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()?
+        }
+    };
+    Ok(compressed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_measure_package() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("usr/share/foo")).unwrap();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::write(root.join("usr/bin/foo"), vec![b'x'; 3000]).unwrap();
+        fs::write(root.join("usr/share/foo/a.png"), vec![b'a'; 1000]).unwrap();
+        fs::write(root.join("usr/share/foo/b.png"), vec![b'a'; 1000]).unwrap();
+        fs::write(root.join("usr/share/foo/c.png"), vec![b'c'; 1000]).unwrap();
+
+        let report = measure(root).unwrap();
+        assert_eq!((report.total_size, report.file_count), (6000, 4));
+        assert_eq!(report.largest[0].path, Path::new("/usr/bin/foo"));
+        let dirs: Vec<_> = report.directories.iter().map(|d| (d.path.to_str().unwrap(), d.size)).collect();
+        assert_eq!(dirs, [("/usr/bin", 3000), ("/usr/share", 3000)]);
+
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.duplicates[0].paths, [Path::new("/usr/share/foo/a.png"), Path::new("/usr/share/foo/b.png")]);
+        assert_eq!(report.duplicates[0].wasted(), 1000);
+
+        assert_eq!(report.compressed.len(), 3);
+        assert!(report.compressed.iter().all(|c| c.size > 0 && c.size < 6000));
+        assert_eq!(top_directory(Path::new("/opt/foo/bin/foo")), Path::new("/opt"));
+    }
+}
@@ -0,0 +1,272 @@
+//! Batch conversion inputs
+//!
+//! `rexeb convert` takes any mix of files, directories and glob patterns.
+//! Directories contribute the `.deb` files directly in them, or below them
+//! with `--recursive`; patterns the shell left alone (quoted, or matching
+//! nothing) are expanded here. The same package found twice, e.g. in two
+//! download directories, is converted once. `-` reads a package piped to
+//! standard input (`curl -L ... | rexeb convert -`).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::converter::ConversionReport;
+use crate::error::{RexebError, Result};
+use crate::parsers::deb::DebParser;
+
+/// Characters making an input a glob pattern
+const GLOB_CHARS: &[char] = &['*', '?', '['];
+
+/// Input standing for standard input
+pub const STDIN: &str = "-";
+
+/// A package to convert
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchInput {
+    /// The .deb file
+    pub path: PathBuf,
+    /// Package name and version from the control file, if read
+    pub package: Option<(String, String)>,
+}
+
+/// What became of one input
+#[derive(Debug)]
+pub enum BatchOutcome {
+    /// Converted, possibly from the cache
+    Converted(ConversionReport),
+    /// Conversion failed
+    Failed(String),
+    /// Same package as another input
+    Duplicate(PathBuf),
+}
+
+/// Files named by the inputs, in order and without repeats
+pub fn discover(inputs: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let walker = walkdir::WalkDir::new(input).sort_by_file_name().max_depth(if recursive { usize::MAX } else { 1 });
+            for entry in walker {
+                let entry = entry?;
+                if entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "deb") {
+                    found.push(entry.into_path());
+                }
+            }
+        } else if !input.exists() && input.to_string_lossy().contains(GLOB_CHARS) {
+            found.extend(expand_glob(input)?);
+        } else if !input.exists() {
+            return Err(RexebError::file_not_found(input));
+        } else {
+            found.push(input.clone());
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    found.retain(|path| seen.insert(path.clone()));
+    Ok(found)
+}
+
+/// Copy a package from `reader` into `dir`
+///
+/// The ar archive is read several times during a conversion, so a piped
+/// package is spooled to disk first. The file is named like a downloaded
+/// deb when its control file can be read.
+pub fn spool(mut reader: impl std::io::Read, dir: &Path) -> Result<PathBuf> {
+    let path = dir.join("stdin.deb");
+    let mut file = std::fs::File::create(&path)?;
+    if std::io::copy(&mut reader, &mut file)? == 0 {
+        return Err(RexebError::Other("Nothing was piped to standard input".into()));
+    }
+    drop(file);
+
+    let Ok(fields) = DebParser::control_fields(&path) else {
+        return Ok(path);
+    };
+    // Debian file names leave out the epoch
+    let field = |name: &str| fields.get(name).map(|v| v.rsplit(':').next().unwrap_or(v).replace('/', "_"));
+    match (field("Package"), field("Version"), field("Architecture")) {
+        (Some(name), Some(version), Some(arch)) => {
+            let named = dir.join(format!("{}_{}_{}.deb", name, version, arch));
+            std::fs::rename(&path, &named)?;
+            Ok(named)
+        }
+        _ => Ok(path),
+    }
+}
+
+/// Files matching a pattern in its last component
+fn expand_glob(pattern: &Path) -> Result<Vec<PathBuf>> {
+    let dir = pattern.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = pattern.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if dir.to_string_lossy().contains(GLOB_CHARS) {
+        return Err(RexebError::Other(format!(
+            "{}: patterns are only supported in the file name",
+            pattern.display()
+        )));
+    }
+
+    let regex = glob_regex(&name)?;
+    let mut matches = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && regex.is_match(&entry.file_name().to_string_lossy()) {
+            matches.push(dir.join(entry.file_name()));
+        }
+    }
+    if matches.is_empty() {
+        return Err(RexebError::file_not_found(pattern));
+    }
+    matches.sort();
+    Ok(matches)
+}
+
+/// Regex matching the same names as a glob pattern
+fn glob_regex(pattern: &str) -> Result<Regex> {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                regex.push('[');
+                for c in chars.by_ref() {
+                    match c {
+                        ']' => break,
+                        '!' if regex.ends_with('[') => regex.push('^'),
+                        '\\' | '^' => regex.push_str(&regex::escape(&c.to_string())),
+                        c => regex.push(c),
+                    }
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Ok(Regex::new(&regex)?)
+}
+
+/// Read each file's name and version and set aside repeated packages
+///
+/// Files whose control data cannot be read are kept; their conversion
+/// reports the problem. Returns the inputs to convert and the duplicates
+/// with the input they repeat.
+pub fn dedupe(paths: Vec<PathBuf>) -> (Vec<BatchInput>, Vec<(PathBuf, PathBuf)>) {
+    let mut inputs = Vec::new();
+    let mut duplicates = Vec::new();
+    let mut first: HashMap<(String, String), PathBuf> = HashMap::new();
+
+    for path in paths {
+        let package = DebParser::control_fields(&path)
+            .ok()
+            .and_then(|fields| Some((fields.get("Package")?.clone(), fields.get("Version")?.clone())));
+        if let Some(ref key) = package {
+            if let Some(original) = first.get(key) {
+                duplicates.push((path, original.clone()));
+                continue;
+            }
+            first.insert(key.clone(), path.clone());
+        }
+        inputs.push(BatchInput { path, package });
+    }
+    (inputs, duplicates)
+}
+
+/// Table of what became of each input
+pub fn summary(rows: &[(BatchInput, BatchOutcome)]) -> String {
+    let cells: Vec<[String; 3]> = rows
+        .iter()
+        .map(|(input, outcome)| {
+            let (name, version) = input.package.clone().unwrap_or_else(|| {
+                let file = input.path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                (file, "?".to_string())
+            });
+            let result = match outcome {
+                BatchOutcome::Converted(report) => {
+                    let file = report.output_path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+                    match report.warnings.len() {
+                        0 => file,
+                        count => format!("{} ({} warning(s))", file, count),
+                    }
+                }
+                BatchOutcome::Failed(error) => format!("failed: {}", error.lines().next().unwrap_or_default()),
+                BatchOutcome::Duplicate(original) => format!("skipped, same package as {}", original.display()),
+            };
+            [name, version, result]
+        })
+        .collect();
+
+    let header = ["Package", "Version", "Result"].map(String::from);
+    let width = |column: usize| cells.iter().chain([&header]).map(|row| row[column].chars().count()).max().unwrap_or(0);
+    let (name_width, version_width) = (width(0), width(1));
+    std::iter::once(&header)
+        .chain(&cells)
+        .map(|[name, version, result]| format!("{:name_width$}  {:version_width$}  {}\n", name, version, result))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discover_inputs() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("debs/old")).unwrap();
+        for file in ["debs/a_1.0_amd64.deb", "debs/b_2.0_all.deb", "debs/notes.txt", "debs/old/a_0.9_amd64.deb"] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+        let names = |paths: Vec<PathBuf>| -> Vec<String> {
+            paths.iter().map(|p| p.strip_prefix(root).unwrap().display().to_string()).collect()
+        };
+
+        let flat = discover(&[root.join("debs")], false).unwrap();
+        assert_eq!(names(flat), ["debs/a_1.0_amd64.deb", "debs/b_2.0_all.deb"]);
+        let deep = discover(&[root.join("debs"), root.join("debs/b_2.0_all.deb")], true).unwrap();
+        assert_eq!(names(deep), ["debs/a_1.0_amd64.deb", "debs/b_2.0_all.deb", "debs/old/a_0.9_amd64.deb"]);
+        let globbed = discover(&[root.join("debs/a_*.deb"), root.join("debs/[!a]*.deb")], false).unwrap();
+        assert_eq!(names(globbed), ["debs/a_1.0_amd64.deb", "debs/b_2.0_all.deb"]);
+        assert!(discover(&[root.join("debs/c_*.deb")], false).is_err());
+
+        let rows = [(
+            BatchInput { path: root.join("debs/a_1.0_amd64.deb"), package: Some(("a".into(), "1.0".into())) },
+            BatchOutcome::Failed("bad archive\ndetails".into()),
+        )];
+        assert_eq!(summary(&rows), "Package  Version  Result\na        1.0      failed: bad archive\n");
+    }
+
+    #[test]
+    fn test_spool_stdin() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        let spooled = spool(&b"!<arch>\nnot really"[..], root).unwrap();
+        assert_eq!(spooled, root.join("stdin.deb"));
+        assert_eq!(std::fs::read(&spooled).unwrap(), b"!<arch>\nnot really");
+        assert!(spool(std::io::empty(), root).is_err());
+
+        // A readable deb is named like a downloaded one, without the epoch
+        let control = b"Package: hello\nVersion: 1:2.0-1\nArchitecture: amd64\n";
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(control.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, "./control", &control[..]).unwrap();
+        let control_tar = tar.into_inner().unwrap().finish().unwrap();
+
+        let mut deb = ar::Builder::new(Vec::new());
+        for (name, content) in [("debian-binary", &b"2.0\n"[..]), ("control.tar.gz", &control_tar[..])] {
+            deb.append(&ar::Header::new(name.as_bytes().to_vec(), content.len() as u64), content).unwrap();
+        }
+        let deb = deb.into_inner().unwrap();
+
+        let spooled = spool(&deb[..], root).unwrap();
+        assert_eq!(spooled, root.join("hello_2.0-1_amd64.deb"));
+        assert_eq!(std::fs::read(&spooled).unwrap(), deb);
+    }
+}
@@ -1,15 +1,56 @@
 //! Command execution handlers
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+use crate::converter::ConversionReport;
 use crate::error::Result;
 
+/// Serializes questions from packages converted concurrently
+static PROMPT_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Set by `--non-interactive`: questions fail instead of being answered with defaults
+static NON_INTERACTIVE: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Make every question fail with [`crate::error::RexebError::InputRequired`]
+///
+/// For CI and scripts, which would rather stop than get a default they
+/// did not choose. `--yes` still answers confirmations explicitly.
+pub fn set_non_interactive(enabled: bool) {
+    NON_INTERACTIVE.store(enabled, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// Whether `--non-interactive` is in effect
+fn non_interactive() -> bool {
+    NON_INTERACTIVE.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Fail under `--non-interactive` when a step would ask `question`
+///
+/// `answered` is whether the user already confirmed, i.e. passed `--yes`.
+fn require_answer(answered: bool, question: &str) -> Result<()> {
+    match non_interactive() && !answered {
+        true => Err(crate::error::RexebError::InputRequired(format!("{} (pass --yes to confirm)", question))),
+        false => Ok(()),
+    }
+}
+
 /// Execute the convert command
 pub async fn execute_convert(args: &super::ConvertArgs) -> Result<()> {
-    use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
-    use rayon::prelude::*;
-
-    let multi = MultiProgress::new();
+    use super::batch::{self, BatchInput, BatchOutcome};
+    use crate::progress::{EventStream, StreamEvent};
+    use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+    use std::sync::Arc;
+
+    // Front ends reading events get them instead of progress bars
+    let events = match (args.progress_format, &args.progress_file) {
+        (super::ProgressFormat::Bars, _) => None,
+        (super::ProgressFormat::Ndjson, Some(path)) => Some(EventStream::open(path)?),
+        (super::ProgressFormat::Ndjson, None) => Some(EventStream::stdout()),
+    };
+    let multi = match events {
+        Some(_) => MultiProgress::with_draw_target(ProgressDrawTarget::hidden()),
+        None => MultiProgress::new(),
+    };
     let style = ProgressStyle::with_template(
         "{prefix:.bold.dim} [{bar:40.cyan/blue}] {pos}/{len} {msg}"
     )
@@ -17,52 +58,617 @@ pub async fn execute_convert(args: &super::ConvertArgs) -> Result<()> {
     .progress_chars("█▓▒░ ");
 
     let output_dir = args.output.clone().unwrap_or_else(|| std::env::current_dir().unwrap());
-    
+
+    // A piped package is spooled here for the duration of the run
+    let spool_dir = tempfile::TempDir::new()?;
+    let mut spooled = None;
+    let mut inputs = args.input.clone();
+    let mut from_stdin = inputs.iter_mut().filter(|input| input.as_os_str() == batch::STDIN);
+    if let Some(input) = from_stdin.next() {
+        if from_stdin.next().is_some() {
+            return Err(crate::error::RexebError::Other("`-` can only be given once".into()));
+        }
+        if std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+            return Err(crate::error::RexebError::Other("`-` reads a package from standard input, but nothing is piped".into()));
+        }
+        *input = batch::spool(std::io::stdin().lock(), spool_dir.path())?;
+        spooled = Some(input.clone());
+    }
+
+    let paths = batch::discover(&inputs, args.recursive)?;
+    if paths.is_empty() {
+        return Err(crate::error::RexebError::Other("No .deb files found in the given inputs".into()));
+    }
+
+    if let Some(ref pkgbase) = args.pkgbase {
+        return convert_split_pkgbuild(pkgbase, &paths, &output_dir, args).await;
+    }
+
+    let batch_mode = paths.len() > 1;
+    let (inputs, duplicates) = match batch_mode {
+        true => batch::dedupe(paths),
+        false => (paths.into_iter().map(|path| BatchInput { path, package: None }).collect(), Vec::new()),
+    };
+
+    // One resolver loads the package databases once for the whole batch
+    let resolver = match batch_mode && !args.skip_deps {
+        true => {
+            let multi = multi.clone();
+            let conversion = conversion_config(args)?;
+            Some(Arc::new(conversion_resolver(args, &conversion, move |ask| multi.suspend(ask))?))
+        }
+        false => None,
+    };
+    let jobs = Arc::new(tokio::sync::Semaphore::new(rayon::current_num_threads().max(1)));
+
     // Process packages using tasks since we're async now
     let mut handles = Vec::new();
     
-    for input_path in &args.input {
-        let input_path = input_path.clone();
+    for input in &inputs {
+        let input_path = input.path.clone();
+        // The spooled file is gone after the run
+        let recorded_as = match spooled.as_ref() == Some(&input.path) {
+            true => PathBuf::from(batch::STDIN),
+            false => input.path.clone(),
+        };
         let output_dir = output_dir.clone();
         let args_clone = args.clone();
+        let resolver = resolver.clone();
+        let jobs = jobs.clone();
+        let events = events.clone();
         
         let pb = multi.add(ProgressBar::new(100));
         pb.set_style(style.clone());
         pb.set_prefix(format!("{}", input_path.file_name().unwrap_or_default().to_string_lossy()));
         
         handles.push(tokio::spawn(async move {
-            convert_single_package(&input_path, &output_dir, &args_clone, pb).await
+            let _permit = jobs.acquire().await.map_err(|e| crate::error::RexebError::Other(e.to_string()))?;
+            let label = input_path.display().to_string();
+            let progress = match events {
+                Some(ref events) => {
+                    events.emit(&StreamEvent::Started { input: label.clone() });
+                    events.progress(&label)
+                }
+                None => progress_for_bar(&pb),
+            };
+            let result =
+                convert_single_package(&input_path, &recorded_as, &output_dir, &args_clone, resolver.as_deref(), pb.clone(), progress)
+                    .await;
+            if let Err(ref e) = result {
+                pb.abandon_with_message(format!("Failed: {}", e));
+            }
+            if let Some(ref events) = events {
+                match result {
+                    Ok(ref report) => {
+                        for warning in &report.warnings {
+                            events.emit(&StreamEvent::Warning { input: label.clone(), message: warning.clone() });
+                        }
+                        let output = report.output_path.display().to_string();
+                        events.emit(&StreamEvent::Finished { input: label, output });
+                    }
+                    Err(ref e) => events.emit(&StreamEvent::Failed { input: label, error: e.to_string() }),
+                }
+            }
+            result
         }));
     }
 
     // Wait for all tasks
-    for handle in handles {
-        handle.await.map_err(|e| crate::error::RexebError::Other(e.to_string()))??;
+    let mut rows = Vec::new();
+    for (input, handle) in inputs.into_iter().zip(handles) {
+        let result = handle.await.map_err(|e| crate::error::RexebError::Other(e.to_string()))?;
+        match result {
+            Ok(report) => rows.push((input, BatchOutcome::Converted(report))),
+            Err(e) if batch_mode => rows.push((input, BatchOutcome::Failed(e.to_string()))),
+            Err(e) => return Err(e),
+        }
+    }
+
+    if let Some(ref report_path) = args.report {
+        let reports: Vec<_> = rows
+            .iter()
+            .filter_map(|(input, outcome)| match outcome {
+                BatchOutcome::Converted(report) => Some((input.path.as_path(), report)),
+                _ => None,
+            })
+            .collect();
+        std::fs::write(report_path, super::html::conversion(&reports))?;
+    }
+
+    if batch_mode {
+        rows.extend(duplicates.into_iter().map(|(path, original)| {
+            (BatchInput { path, package: None }, BatchOutcome::Duplicate(original))
+        }));
+        // Standard output may be the event stream
+        if events.is_none() || args.progress_file.is_some() {
+            println!("\n{}", batch::summary(&rows));
+        }
+
+        let failed = rows.iter().filter(|(_, outcome)| matches!(outcome, BatchOutcome::Failed(_))).count();
+        if failed > 0 {
+            return Err(crate::error::RexebError::Other(format!("{} of {} packages failed to convert", failed, rows.len())));
+        }
     }
 
     Ok(())
 }
 
-/// Convert a single package
+/// Convert a single package and record it in the history as `recorded_as`
 async fn convert_single_package(
+    input: &Path,
+    recorded_as: &Path,
+    output_dir: &Path,
+    args: &super::ConvertArgs,
+    resolver: Option<&crate::resolver::DependencyResolver>,
+    pb: indicatif::ProgressBar,
+    progress: crate::progress::Progress,
+) -> Result<ConversionReport> {
+    let report = convert_package(input, output_dir, args, resolver, pb, progress).await?;
+    // The package is there either way; a lost record only hides it from `rexeb list`
+    if let Err(e) = record_conversion(input, recorded_as, &report.output_path, args) {
+        tracing::warn!("Could not record the conversion in the history: {}", e);
+    }
+    Ok(report)
+}
+
+/// Add a finished conversion to the history
+///
+/// `recorded_as` is the input as given: a path, or `-` for standard input.
+fn record_conversion(input: &Path, recorded_as: &Path, output_path: &Path, args: &super::ConvertArgs) -> Result<()> {
+    use crate::converter::ConversionCache;
+    use crate::history::{History, HistoryRecord};
+    use crate::parsers::deb::DebParser;
+
+    let fields = DebParser::control_fields(input)?;
+    let field = |name: &str| fields.get(name).cloned().unwrap_or_default();
+    let mut options = std::collections::BTreeMap::new();
+    options.insert("format".to_string(), args.format.extension().to_string());
+    for (key, value) in [
+        ("name", &args.name),
+        ("version", &args.version_override),
+        ("release", &args.release),
+        ("origin", &args.origin),
+        ("prefix", &args.prefix),
+        ("output-name", &args.output_name),
+    ] {
+        if let Some(value) = value {
+            options.insert(key.to_string(), value.to_string());
+        }
+    }
+    for (key, set) in [("pkgbuild", args.pkgbuild), ("skip-deps", args.skip_deps), ("force", args.force)] {
+        if set {
+            options.insert(key.to_string(), "true".to_string());
+        }
+    }
+
+    let mut history = History::from_config(&crate::config::Config::load()?)?;
+    history.add(HistoryRecord {
+        id: 0,
+        name: field("Package"),
+        source_version: field("Version"),
+        input_path: match recorded_as == Path::new(super::batch::STDIN) {
+            true => recorded_as.to_path_buf(),
+            false => std::path::absolute(recorded_as)?,
+        },
+        input_sha256: ConversionCache::file_sha256(input)?,
+        output_path: std::path::absolute(output_path)?,
+        options,
+        created: chrono::Utc::now().timestamp(),
+        installed: None,
+        removed: None,
+    })?;
+    Ok(())
+}
+
+/// Convert a single package
+async fn convert_package(
     input: &Path,
     output_dir: &Path,
     args: &super::ConvertArgs,
+    resolver: Option<&crate::resolver::DependencyResolver>,
     pb: indicatif::ProgressBar,
+    progress: crate::progress::Progress,
+) -> Result<ConversionReport> {
+    use crate::converter::{ConversionCache, InstallSimulation, Namcap, PackageConverter, Templates};
+
+    let conversion = conversion_config(args)?;
+    let templates = Templates::load()?;
+
+    // Reuse an earlier conversion of the same deb with the same settings
+    let mut cache = None;
+    if !args.no_cache && !args.pkgbuild && !args.review {
+        let config = crate::config::Config::load()?;
+        let conversion_cache = ConversionCache::from_config(&config)?;
+        let input_sha256 = ConversionCache::file_sha256(input)?;
+        let options = serde_json::json!({
+            "rexeb": crate::VERSION,
+            "conversion": conversion,
+            "overrides": config.overrides,
+            "templates": templates.sources(),
+            "name": args.name,
+            "version": args.version_override,
+            "release": args.release,
+            "origin": args.origin,
+            "skip_deps": args.skip_deps,
+            "format": args.format.extension(),
+        });
+        let options_hash = cache_key(&options, args.skip_deps)?;
+
+        if let Some(record) = conversion_cache.lookup(&input_sha256, &options_hash) {
+            let path = conversion_cache.fetch(record, output_dir)?;
+            pb.set_position(100);
+            pb.finish_with_message(format!("Created {} (cached)", path.display()));
+            let mut report = ConversionReport { output_path: path, ..Default::default() };
+            report.change("reused an earlier conversion from the cache");
+            return Ok(report);
+        }
+        cache = Some((conversion_cache, input_sha256, options));
+    }
+
+    let (parser, metadata) = prepare_package(input, args, &conversion, resolver, &progress, &pb).await?;
+    let learned = crate::resolver::learned_mappings(&metadata);
+
+    // Refuse packages the analysis expects to break
+    if !args.force && conversion.min_feasibility > 0 {
+        pb.set_message("Analyzing...");
+        check_feasibility(input, &parser, &metadata, args, conversion.min_feasibility).await?;
+    }
+
+    // Create output package
+    if args.pkgbuild {
+        // Generate PKGBUILD, in a directory of its own when named by a template
+        let pkgbuild_dir = match conversion.output_name {
+            Some(ref template) => output_dir.join(metadata.output_name(template)?),
+            None => output_dir.to_path_buf(),
+        };
+        std::fs::create_dir_all(&pkgbuild_dir)?;
+        let pkgbuild_path = pkgbuild_dir.join("PKGBUILD");
+        // Module source is better packaged for DKMS than as prebuilt modules
+        let modules = crate::converter::KernelModules::detect(parser.extract_dir())?;
+        let pkgbuild = match modules.dkms.is_empty() {
+            true => templates.render_pkgbuild(&metadata)?,
+            false => templates
+                .render_generated_pkgbuild(&metadata, crate::converter::dkms_pkgbuild(&metadata, &modules.dkms))?,
+        };
+        std::fs::write(&pkgbuild_path, pkgbuild)?;
+        learn_mappings(&learned);
+        pb.set_position(100);
+        pb.finish_with_message(format!("Created {}", pkgbuild_path.display()));
+        Ok(ConversionReport { output_path: pkgbuild_path, ..Default::default() })
+    } else {
+        // Build binary package
+        let converter = PackageConverter::new(metadata, parser.extract_dir())?
+            .with_config(&conversion)
+            .with_templates(templates)
+            .with_progress(progress);
+        let mut report = converter.build(output_dir, args.format)?;
+
+        let mut validation = Ok(());
+        if conversion.run_namcap || conversion.deny_namcap_errors {
+            if conversion.deny_namcap_errors || Namcap::is_available() {
+                pb.set_message("Running namcap...");
+                validation = Namcap::check(&report.output_path)
+                    .and_then(|findings| Namcap::apply(&findings, &mut report, conversion.deny_namcap_errors));
+            } else {
+                report.warn("namcap not found, skipping post-build checks");
+            }
+        }
+        if conversion.simulate_install {
+            if InstallSimulation::is_available() {
+                pb.set_message("Simulating install...");
+                let simulation = InstallSimulation::check(&report.output_path)?;
+                InstallSimulation::apply(&simulation, &mut report);
+            } else {
+                report.warn("pacman not found, skipping install simulation");
+            }
+        }
+
+        for change in &report.changes {
+            pb.println(format!("  {} {}", console::style("→").cyan(), change));
+        }
+        for warning in &report.warnings {
+            pb.println(format!("  {} {}", console::style("⚠").yellow(), warning));
+        }
+        if let Err(e) = validation {
+            let _ = std::fs::remove_file(&report.output_path);
+            pb.abandon_with_message("namcap validation failed");
+            return Err(e);
+        }
+        learn_mappings(&learned);
+        // Keyed by the resolver state this conversion left behind: the
+        // mappings it learned and misses it recorded give the same result
+        if let Some((mut cache, input_sha256, options)) = cache {
+            cache.store(&input_sha256, &cache_key(&options, args.skip_deps)?, &report.output_path)?;
+        }
+        pb.set_position(100);
+        pb.finish_with_message(format!("Created {}", report.output_path.display()));
+        Ok(report)
+    }
+}
+
+/// Conversion cache key for settings and, unless resolution is skipped, the
+/// current resolver state
+fn cache_key(options: &serde_json::Value, skip_deps: bool) -> Result<String> {
+    use crate::converter::{resolver_stamp, ConversionCache};
+
+    let resolver = match skip_deps {
+        true => Vec::new(),
+        false => resolver_stamp(),
+    };
+    ConversionCache::options_hash(&serde_json::json!({ "options": options, "resolver": resolver }))
+}
+
+/// Analyze a prepared package and refuse it when it scores below `threshold`
+async fn check_feasibility(
+    input: &Path,
+    parser: &crate::parsers::deb::DebParser,
+    metadata: &crate::models::PackageMetadata,
+    args: &super::ConvertArgs,
+    threshold: u8,
+) -> Result<()> {
+    use crate::analyzer::cache::AnalysisCache;
+
+    let config = crate::config::Config::load()?;
+    let cache = AnalysisCache::from_config(&config);
+    let report = feasibility_report(&cache, &config, input, parser, metadata, args.skip_deps, args.no_cache).await?;
+
+    let feasibility = report.feasibility();
+    if feasibility.score < threshold {
+        return Err(crate::error::RexebError::Validation(format!(
+            "{} scores {}, below the required {}; see `rexeb analyze {}` or convert with --force",
+            metadata.name,
+            feasibility,
+            threshold,
+            input.display()
+        )));
+    }
+    Ok(())
+}
+
+/// The report `rexeb analyze` gives for a deb, with the dependencies as resolved for `metadata`
+///
+/// Shares the analysis cache with `rexeb analyze`, so converting a deb that
+/// was just analyzed does not scan it again, and vice versa.
+async fn feasibility_report(
+    cache: &crate::analyzer::cache::AnalysisCache,
+    config: &crate::config::Config,
+    input: &Path,
+    parser: &crate::parsers::deb::DebParser,
+    metadata: &crate::models::PackageMetadata,
+    skip_deps: bool,
+    no_cache: bool,
+) -> Result<crate::analyzer::AnalysisReport> {
+    use crate::analyzer::rules::RuleSet;
+    use crate::analyzer::PackageAnalyzer;
+    use crate::converter::ConversionCache;
+
+    let input_sha256 = ConversionCache::file_sha256(input)?;
+    let options_hash = analysis_options_hash(config, false, false, None)?;
+    let cached = match no_cache {
+        true => None,
+        false => cache.lookup(&input_sha256, &options_hash),
+    };
+    let mut report = match cached {
+        Some(entry) => entry.report,
+        None => {
+            // Cached as `rexeb analyze` would have it: of the deb as parsed
+            let parsed = parser.parse()?;
+            let analyzer = PackageAnalyzer::new(&parsed, parser.extract_dir())?;
+            let mut report = analyzer.analyze(false, false)?;
+            analyzer.check_vulnerabilities(&mut report).await?;
+            if !report.findings.iter().any(|f| f.rule == "vulnerability-scan-skipped") || config.network.offline {
+                cache.store(&input_sha256, &options_hash, &parsed, &report)?;
+            }
+            report
+        }
+    };
+
+    let mut rules = RuleSet::from_config(&config.analyzer)?;
+    // Nothing is mapped when resolution is skipped on purpose
+    if skip_deps {
+        rules = rules.disable("unmapped-dependency");
+    }
+    PackageAnalyzer::new(metadata, parser.extract_dir())?.with_rules(rules).refresh_dependencies(&mut report)?;
+    Ok(report)
+}
+
+/// Key of an analysis in the analysis cache, besides the deb itself
+///
+/// `trace_network` is the sandbox network choice when maintainer scripts are traced.
+fn analysis_options_hash(
+    config: &crate::config::Config,
+    conflicts: bool,
+    verify: bool,
+    trace_network: Option<bool>,
+) -> Result<String> {
+    use crate::analyzer::cache::pacman_db_stamp;
+    use crate::converter::ConversionCache;
+
+    ConversionCache::options_hash(&serde_json::json!({
+        "rexeb": crate::VERSION,
+        "analyzer": config.analyzer,
+        "conversion": config.conversion,
+        "offline": config.network.offline,
+        "checks": crate::analyzer::check::registered().iter().map(|c| c.name().to_string()).collect::<Vec<_>>(),
+        "overrides": config.overrides,
+        "conflicts": conflicts.then(pacman_db_stamp),
+        "verify": verify,
+        "trace_scripts": trace_network,
+    }))
+}
+
+/// Convert several packages into one split PKGBUILD sharing `pkgbase`
+async fn convert_split_pkgbuild(pkgbase: &str, inputs: &[PathBuf], output_dir: &Path, args: &super::ConvertArgs) -> Result<()> {
+    use crate::models::PackageMetadata;
+    use crate::progress::Progress;
+    use indicatif::ProgressBar;
+
+    let conversion = conversion_config(args)?;
+    let pb = ProgressBar::new_spinner();
+    let mut packages = Vec::new();
+
+    for input in inputs {
+        pb.set_message(format!("Parsing {}...", input.display()));
+        let (_, metadata) = prepare_package(input, args, &conversion, None, &Progress::default(), &pb).await?;
+        packages.push(metadata);
+    }
+
+    // Named by a template, the PKGBUILD gets a directory of its own like a single one
+    let pkgbuild_dir = split_pkgbuild_dir(pkgbase, &packages, output_dir, conversion.output_name.as_deref())?;
+    std::fs::create_dir_all(&pkgbuild_dir)?;
+    let pkgbuild_path = pkgbuild_dir.join("PKGBUILD");
+    std::fs::write(&pkgbuild_path, PackageMetadata::to_split_pkgbuild(pkgbase, &packages)?)?;
+    for metadata in &packages {
+        learn_mappings(&crate::resolver::learned_mappings(metadata));
+    }
+    pb.finish_with_message(format!("Created {} ({} packages)", pkgbuild_path.display(), packages.len()));
+
+    Ok(())
+}
+
+/// Directory of a split PKGBUILD: the output name template rendered for the
+/// pkgbase, or the output directory itself without one
+fn split_pkgbuild_dir(
+    pkgbase: &str,
+    packages: &[crate::models::PackageMetadata],
+    output_dir: &Path,
+    template: Option<&str>,
+) -> Result<PathBuf> {
+    let (Some(template), Some(first)) = (template, packages.first()) else {
+        return Ok(output_dir.to_path_buf());
+    };
+    // Split packages share their version, so only the name differs
+    let mut base = first.clone();
+    base.arch_name = Some(pkgbase.to_string());
+    Ok(output_dir.join(base.output_name(template)?))
+}
+
+/// Remember the guessed dependency mappings of a successful conversion
+fn learn_mappings(learned: &[crate::resolver::database::PackageMapping]) {
+    if let Err(e) = crate::resolver::PackageDatabase::learn(learned) {
+        tracing::warn!("Could not save learned mappings: {}", e);
+    }
+}
+
+/// Conversion settings from the config file with command line overrides
+fn conversion_config(args: &super::ConvertArgs) -> Result<crate::config::ConversionConfig> {
+    let mut conversion = crate::config::Config::load()?.conversion;
+
+    if let Some(ref prefix) = args.prefix {
+        conversion.prefix = Some(prefix.clone());
+    }
+    if let Some(ref output_name) = args.output_name {
+        conversion.output_name = Some(output_name.clone());
+    }
+    // A bad template should fail before any package is parsed
+    if let Some(ref output_name) = conversion.output_name {
+        crate::models::PackageMetadata::new("pkgname", "1.0").output_name(output_name)?;
+    }
+    if let Some(ref policy) = args.recommends {
+        conversion.recommends_policy = policy.clone();
+    }
+    if args.namcap {
+        conversion.run_namcap = true;
+    }
+    if args.deny_namcap_errors {
+        conversion.deny_namcap_errors = true;
+    }
+    if args.simulate {
+        conversion.simulate_install = true;
+    }
+    if args.keep_arch {
+        conversion.detect_arch_any = false;
+    }
+    if args.lenient {
+        conversion.lenient_deps = true;
+    }
+    conversion.ignore_deps.extend(args.ignore_deps.iter().cloned());
+
+    Ok(conversion)
+}
+
+/// Progress handle driving a 0-100 progress bar
+fn progress_for_bar(pb: &indicatif::ProgressBar) -> crate::progress::Progress {
+    let pb = pb.clone();
+    crate::progress::Progress::new(move |event| {
+        pb.set_position((event.fraction() * 100.0) as u64);
+        match event.total {
+            Some(total) => pb.set_message(format!("{} ({}/{})", event.stage.label(), event.current, total)),
+            None => pb.set_message(format!("{}...", event.stage.label())),
+        }
+    })
+}
+
+/// Dependency resolver for conversions, asking through `suspend` when interactive
+///
+/// `suspend` runs the question with progress bars hidden.
+fn conversion_resolver(
+    args: &super::ConvertArgs,
+    conversion: &crate::config::ConversionConfig,
+    suspend: impl Fn(&mut dyn FnMut()) + Send + Sync + 'static,
+) -> Result<crate::resolver::DependencyResolver> {
+    let config = crate::config::Config::load()?;
+    let mut resolver = crate::resolver::DependencyResolver::new()?.with_ignored(conversion.ignore_deps.iter().cloned());
+    if is_interactive(args, &config) {
+        resolver = resolver.with_prompt(conversion.prompt_confidence, mapping_prompt(suspend));
+    } else if must_not_guess(non_interactive(), args, &config) {
+        resolver = resolver.fail_on_questions();
+    }
+    Ok(resolver)
+}
+
+/// Whether questions may be asked during a conversion
+fn is_interactive(args: &super::ConvertArgs, config: &crate::config::Config) -> bool {
+    !args.yes
+        && !config.general.auto_yes
+        && !non_interactive()
+        && !args.input.iter().any(|input| input.as_os_str() == super::batch::STDIN)
+        && args.progress_format == super::ProgressFormat::Bars
+        && console::user_attended()
+}
+
+/// Whether a conversion must fail rather than settle a question with a default
+fn must_not_guess(non_interactive: bool, args: &super::ConvertArgs, config: &crate::config::Config) -> bool {
+    non_interactive && !args.yes && !config.general.auto_yes
+}
+
+/// Fail when dependencies need a review no one may give
+fn require_mappings(
+    uncertain: &[String],
+    non_interactive: bool,
+    args: &super::ConvertArgs,
+    config: &crate::config::Config,
 ) -> Result<()> {
-    use crate::converter::PackageConverter;
+    if args.skip_deps || uncertain.is_empty() || !must_not_guess(non_interactive, args, config) {
+        return Ok(());
+    }
+    Err(crate::error::RexebError::InputRequired(format!(
+        "{} need(s) a mapping: {} (add them with `rexeb map`)",
+        uncertain.len(),
+        uncertain.join(", ")
+    )))
+}
+
+/// Parse a package, apply overrides and resolve its dependencies
+///
+/// Uses `shared` for resolution if given, otherwise a resolver of its own.
+async fn prepare_package(
+    input: &Path,
+    args: &super::ConvertArgs,
+    conversion: &crate::config::ConversionConfig,
+    shared: Option<&crate::resolver::DependencyResolver>,
+    progress: &crate::progress::Progress,
+    pb: &indicatif::ProgressBar,
+) -> Result<(crate::parsers::deb::DebParser, crate::models::PackageMetadata)> {
+    use crate::models::RecommendsPolicy;
     use crate::parsers::deb::DebParser;
 
-    pb.set_message("Parsing package...");
-    pb.set_position(10);
+    let recommends_policy = RecommendsPolicy::from_config(&conversion.recommends_policy)?;
 
     // Parse the deb package
-    let parser = DebParser::new(input)?;
+    let parser = DebParser::with_progress(input, progress.clone())?;
     let mut metadata = parser.parse()?;
 
-    pb.set_position(30);
-    pb.set_message("Resolving dependencies...");
-
     // Apply overrides
     if let Some(ref name) = args.name {
         metadata.arch_name = Some(name.clone());
@@ -70,40 +676,225 @@ async fn convert_single_package(
     if let Some(ref version) = args.version_override {
         metadata.version = version.clone();
     }
-    if let Some(ref release) = args.release {
-        metadata.release = release.clone();
+
+    if let Some(ref origin) = args.origin {
+        metadata.provenance.origin_url = Some(origin.clone());
     }
 
-    // Normalize version
+    // Normalize version (splits off epoch and Debian revision)
     metadata.normalize_version();
 
-    pb.set_position(40);
+    // Release override applies to the normalized version
+    if let Some(ref release) = args.release {
+        metadata.release = release.clone();
+    }
 
     // Resolve dependencies if not skipped
-    if !args.skip_deps {
-        let resolver = crate::resolver::DependencyResolver::new()?;
-        resolver.resolve(&mut metadata).await?;
+    let config = crate::config::Config::load()?;
+    let interactive = is_interactive(args, &config);
+    let own;
+    let resolver = match (shared, args.skip_deps) {
+        (_, true) => None,
+        (Some(shared), false) => Some(shared),
+        (None, false) => {
+            let pb = pb.clone();
+            own = conversion_resolver(args, conversion, move |ask| pb.suspend(ask))?;
+            Some(&own)
+        }
+    };
+    if let Some(resolver) = resolver {
+        resolver.resolve_with_progress(&mut metadata, progress).await?;
     }
 
-    pb.set_position(60);
-    pb.set_message("Building package...");
+    // The user's corrections win over what the deb declares
+    if let Some(overrides) = config.package_override(&metadata) {
+        metadata.apply_override(overrides);
+    }
 
-    // Create output package
-    if args.pkgbuild {
-        // Generate PKGBUILD
-        let pkgbuild_path = output_dir.join("PKGBUILD");
-        std::fs::write(&pkgbuild_path, metadata.to_pkgbuild())?;
-        pb.set_position(100);
-        pb.finish_with_message(format!("Created {}", pkgbuild_path.display()));
-    } else {
-        // Build binary package
-        let converter = PackageConverter::new(metadata, parser.extract_dir())?;
-        let output_path = converter.build(output_dir, args.format)?;
-        pb.set_position(100);
-        pb.finish_with_message(format!("Created {}", output_path.display()));
+    let uncertain = uncertain_dependencies(&metadata, conversion.prompt_confidence, conversion.lenient_deps);
+    require_mappings(&uncertain, non_interactive(), args, &config)?;
+    let review = args.review || (interactive && !uncertain.is_empty());
+    if !args.skip_deps && review {
+        let _guard = PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        pb.suspend(|| review_dependencies(&mut metadata, interactive))?;
     }
 
-    Ok(())
+    // Tell about problems now rather than when pacman -U fails
+    if let Some(resolver) = resolver {
+        let check = resolver.installability(&metadata);
+        if !check.dependencies.is_empty() {
+            pb.println(format!("  {} Dependencies: {}", console::style("→").cyan(), check.summary()));
+        }
+        for (name, reason) in check.unsatisfiable() {
+            pb.println(format!("  {} {} is unsatisfiable: {}", console::style("⚠").yellow(), name, reason));
+        }
+        for name in &check.conflicts {
+            pb.println(format!("  {} Installing would remove {}", console::style("⚠").yellow(), name));
+        }
+    }
+
+    metadata.apply_recommends_policy(recommends_policy);
+    if conversion.lenient_deps {
+        for name in metadata.demote_unmapped() {
+            pb.println(format!("  {} Unmapped dependency {} is now optional", console::style("→").cyan(), name));
+        }
+    }
+
+    Ok((parser, metadata))
+}
+
+/// Required dependencies that are unmapped or mapped with low confidence
+///
+/// With `lenient` unmapped ones are left out, as they become optional.
+fn uncertain_dependencies(metadata: &crate::models::PackageMetadata, threshold: f32, lenient: bool) -> Vec<String> {
+    use crate::models::DependencyType;
+
+    [DependencyType::Depends, DependencyType::PreDepends]
+        .into_iter()
+        .flat_map(|dep_type| metadata.get_deps(dep_type))
+        .filter(|dep| !dep.is_virtual && dep.confidence < threshold && (dep.is_mapped() || !lenient))
+        .map(|dep| dep.debian_name.clone())
+        .collect()
+}
+
+/// Print the resolved dependencies and let the user correct them
+///
+/// Corrections are learned once the conversion succeeds. Without
+/// `interactive` the table is only printed.
+fn review_dependencies(metadata: &mut crate::models::PackageMetadata, interactive: bool) -> Result<()> {
+    use crate::models::{Dependency, DependencyType, ResolutionSource};
+    use crate::resolver::RESOLVED_TYPES;
+    use dialoguer::{Input, Select};
+
+    // (field, index, alternative index)
+    let mut entries: Vec<(DependencyType, usize, Option<usize>)> = Vec::new();
+    for dep_type in RESOLVED_TYPES {
+        for (i, dep) in metadata.get_deps(dep_type).iter().enumerate() {
+            entries.push((dep_type, i, None));
+            entries.extend((0..dep.alternatives.len()).map(|alt| (dep_type, i, Some(alt))));
+        }
+    }
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let entry = |metadata: &crate::models::PackageMetadata, (dep_type, i, alt): (DependencyType, usize, Option<usize>)| {
+        let dep = &metadata.get_deps(dep_type)[i];
+        alt.map_or(dep, |alt| &dep.alternatives[alt]).clone()
+    };
+    let row = |dep_type: DependencyType, alt: bool, dep: &Dependency| {
+        let field = if alt { "  | alternative" } else { dep_type.debian_field() };
+        let arch = match dep.arch_name {
+            Some(ref name) if dep.missing => console::style(format!("{} (missing)", name)).red().to_string(),
+            Some(ref name) => name.clone(),
+            None if dep.is_virtual => "(virtual)".to_string(),
+            None => match dep.suggestion {
+                Some(ref suggestion) => console::style(format!("{}?", suggestion)).yellow().to_string(),
+                None => console::style("(unmapped)").red().to_string(),
+            },
+        };
+        let source = dep.source.map_or_else(|| "-".to_string(), |s| s.to_string());
+        format!("{:<14} {:<32} {:<32} {:>4.0}%  {}", field, dep.debian_name, arch, dep.confidence * 100.0, source)
+    };
+    let rows = |metadata: &crate::models::PackageMetadata| -> Vec<String> {
+        entries
+            .iter()
+            .map(|&e| row(e.0, e.2.is_some(), &entry(metadata, e)))
+            .collect()
+    };
+
+    println!(
+        "{}",
+        console::style(format!("{:<14} {:<32} {:<32} {:>5}  {}", "Field", "Debian", "Arch", "Conf", "Source")).bold()
+    );
+    for line in rows(metadata) {
+        println!("{}", line);
+    }
+    if !interactive {
+        return Ok(());
+    }
+
+    loop {
+        let mut items = vec!["Build with these mappings".to_string()];
+        items.extend(rows(metadata));
+        let selection = Select::new()
+            .with_prompt("Select a mapping to change")
+            .items(&items)
+            .default(0)
+            .interact()
+            .map_err(|e| crate::error::RexebError::Other(e.to_string()))?;
+        if selection == 0 {
+            return Ok(());
+        }
+
+        let (dep_type, i, alt) = entries[selection - 1];
+        let Some(dep) = metadata.dependencies.get_mut(&dep_type).and_then(|deps| deps.get_mut(i)) else {
+            continue;
+        };
+        let dep = match alt {
+            Some(alt) => &mut dep.alternatives[alt],
+            None => dep,
+        };
+
+        let name: String = Input::new()
+            .with_prompt(format!("Arch package for {} (empty to leave unmapped)", dep.debian_name))
+            .with_initial_text(dep.suggestion.clone().unwrap_or_default())
+            .allow_empty(true)
+            .interact_text()
+            .map_err(|e| crate::error::RexebError::Other(e.to_string()))?;
+        let name = name.trim();
+        if name.is_empty() {
+            dep.arch_name = None;
+            dep.confidence = 0.0;
+            dep.source = None;
+        } else {
+            dep.set_arch_name(name, 1.0);
+            dep.source = Some(ResolutionSource::User);
+            dep.suggestion = None;
+            dep.missing = false;
+            dep.version_transforms.clear();
+        }
+    }
+}
+
+/// Interactive prompt for uncertain dependency mappings
+///
+/// Questions are asked one at a time with the progress bar hidden.
+fn mapping_prompt(suspend: impl Fn(&mut dyn FnMut()) + Send + Sync + 'static) -> crate::resolver::MappingPrompt {
+    use crate::resolver::MappingChoice;
+    use dialoguer::{Input, Select};
+
+    std::sync::Arc::new(move |debian_name: &str, candidates: &[(String, f32)]| {
+        let _guard = PROMPT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut choice = MappingChoice::Skip;
+        suspend(&mut || choice = {
+            let mut items: Vec<String> = candidates
+                .iter()
+                .map(|(name, score)| format!("{} ({:.0}%)", name, score * 100.0))
+                .collect();
+            items.push("Type a package name".to_string());
+            items.push("Skip this dependency".to_string());
+
+            let selection = Select::new()
+                .with_prompt(format!("No confident match for {}, use which Arch package?", debian_name))
+                .items(&items)
+                .default(0)
+                .interact();
+
+            match selection {
+                Ok(i) if i < candidates.len() => MappingChoice::Use(candidates[i].0.clone()),
+                Ok(i) if i == candidates.len() => Input::<String>::new()
+                    .with_prompt("Arch package")
+                    .interact_text()
+                    .map(|name| name.trim().to_string())
+                    .ok()
+                    .filter(|name| !name.is_empty())
+                    .map_or(MappingChoice::Skip, MappingChoice::Use),
+                _ => MappingChoice::Skip,
+            }
+        });
+        choice
+    })
 }
 
 /// Execute the update command
@@ -119,13 +910,23 @@ pub async fn execute_update(args: &super::UpdateArgs) -> Result<()> {
     let pb = ProgressBar::new_spinner();
     pb.set_style(style);
 
-    let update_all = args.all || (!args.virtual_packages && !args.mappings && !args.aur);
+    let update_all = args.all || (!args.virtual_packages && !args.mappings && !args.aur && !args.repos);
 
-    let db = PackageDatabase::new()?;
+    let config = crate::config::Config::load()?;
+    let mut db = PackageDatabase::new()?;
 
-    if update_all || args.mappings {
+    if args.repos || (update_all && !config.network.offline) {
+        pb.set_message("Downloading repository databases...");
+        let count = db.update_arch_packages(&config.network, args.force).await?;
+        pb.println(format!("Indexed {} packages from {}", count, config.network.sync_repos.join(", ")));
+    }
+
+    if args.mappings || (update_all && !config.network.offline) {
         pb.set_message("Updating package mappings...");
-        db.update_mappings(args.force).await?;
+        let count = db.update_mappings(&config.network, args.force).await?;
+        if let Some(ref url) = config.network.mappings_url {
+            pb.println(format!("Merged {} mappings from {}", count, url));
+        }
     }
 
     if update_all || args.virtual_packages {
@@ -190,6 +991,9 @@ pub async fn execute_info(args: &super::InfoArgs) -> Result<()> {
         super::InfoFormat::Toml => {
             println!("{}", toml::to_string_pretty(&metadata).map_err(|e| crate::error::RexebError::Other(e.to_string()))?);
         }
+        super::InfoFormat::Html => {
+            print!("{}", super::html::info(&metadata));
+        }
     }
 
     Ok(())
@@ -202,8 +1006,8 @@ pub async fn execute_search(args: &super::SearchArgs) -> Result<()> {
 
     let db = PackageDatabase::new()?;
     
-    let search_arch = args.arch || (!args.arch && !args.aur);
-    let search_aur = args.aur || (!args.arch && !args.aur);
+    let search_arch = args.arch || !args.aur;
+    let search_aur = args.aur || !args.arch;
 
     let mut results = Vec::new();
 
@@ -239,14 +1043,44 @@ pub async fn execute_search(args: &super::SearchArgs) -> Result<()> {
 /// Execute the analyze command
 pub async fn execute_analyze(args: &super::AnalyzeArgs) -> Result<()> {
     use crate::parsers::deb::DebParser;
+    use crate::analyzer::rules::Severity;
+    use crate::analyzer::cache::AnalysisCache;
+    use crate::analyzer::feasibility::Verdict;
     use crate::analyzer::PackageAnalyzer;
+    use crate::converter::ConversionCache;
     use console::style;
 
-    let parser = DebParser::new(&args.input)?;
-    let metadata = parser.parse()?;
+    // Reuse an earlier analysis of the same deb with the same rules and settings
+    let config = crate::config::Config::load()?;
+    let cache = AnalysisCache::from_config(&config);
+    let input_sha256 = ConversionCache::file_sha256(&args.input)?;
+    let trace_network = args.trace_scripts.then(|| sandbox_network(&config, args.network, args.no_network));
+    let options_hash = analysis_options_hash(&config, args.conflicts, args.verify, trace_network)?;
 
-    let analyzer = PackageAnalyzer::new(&metadata, parser.extract_dir())?;
-    let report = analyzer.analyze(args.conflicts, args.verify)?;
+    let cached = match args.no_cache {
+        true => None,
+        false => cache.lookup(&input_sha256, &options_hash),
+    };
+    let (metadata, report) = match cached {
+        Some(entry) => (entry.metadata, entry.report),
+        None => {
+            let parser = DebParser::new(&args.input)?;
+            let metadata = parser.parse()?;
+
+            let analyzer = PackageAnalyzer::new(&metadata, parser.extract_dir())?;
+            let mut report = analyzer.analyze(args.conflicts, args.verify)?;
+            analyzer.check_vulnerabilities(&mut report).await?;
+            if let Some(network) = trace_network {
+                let traces = crate::sandbox::trace_maintainer_scripts(&config, &metadata, parser.extract_dir(), network).await?;
+                analyzer.attach_script_traces(&mut report, traces);
+            }
+            // A scan that failed midway would be skipped again on every reuse
+            if !report.findings.iter().any(|f| f.rule == "vulnerability-scan-skipped") || config.network.offline {
+                cache.store(&input_sha256, &options_hash, &metadata, &report)?;
+            }
+            (metadata, report)
+        }
+    };
 
     match args.format {
         super::InfoFormat::Pretty => {
@@ -259,24 +1093,29 @@ pub async fn execute_analyze(args: &super::AnalyzeArgs) -> Result<()> {
             println!("  Architecture: {}", metadata.arch);
             println!("  Files: {}", metadata.files.len());
             println!("  Installed Size: {} KB", metadata.installed_size / 1024);
+            let feasibility = report.feasibility();
+            let verdict = match feasibility.verdict {
+                Verdict::Safe => style(feasibility.to_string()).green(),
+                Verdict::NeedsReview => style(feasibility.to_string()).yellow(),
+                Verdict::LikelyBroken => style(feasibility.to_string()).red(),
+            };
+            println!("  Feasibility: {}", verdict);
             println!();
 
-            // Warnings
-            if !report.warnings.is_empty() {
-                println!("{}", style("⚠ Warnings").yellow().bold());
-                for warning in &report.warnings {
-                    println!("  • {}", warning);
-                }
-                println!();
-            }
-
-            // Errors
-            if !report.errors.is_empty() {
-                println!("{}", style("✗ Errors").red().bold());
-                for error in &report.errors {
-                    println!("  • {}", error);
+            // Findings, most severe first
+            for (severity, heading) in [
+                (Severity::Error, style("✗ Errors").red().bold()),
+                (Severity::Warning, style("⚠ Warnings").yellow().bold()),
+                (Severity::Info, style("ℹ Notes").cyan().bold()),
+            ] {
+                let findings: Vec<_> = report.with_severity(severity).collect();
+                if !findings.is_empty() {
+                    println!("{}", heading);
+                    for finding in findings {
+                        println!("  • {}", finding);
+                    }
+                    println!();
                 }
-                println!();
             }
 
             // Dependency Analysis
@@ -294,20 +1133,78 @@ pub async fn execute_analyze(args: &super::AnalyzeArgs) -> Result<()> {
                     println!("    - {}", dep);
                 }
             }
+            if !report.multilib_deps.is_empty() {
+                println!("\n  Multilib dependencies (enable [multilib] in pacman.conf):");
+                for dep in &report.multilib_deps {
+                    println!("    - {}", dep);
+                }
+            }
+
+            // Known vulnerabilities
+            if !report.security_issues.is_empty() {
+                println!("\n{}", style("Security issues").red().bold());
+                for issue in &report.security_issues {
+                    println!("  {} {} ({})", issue.component.name, issue.component.version, issue.component.path.display());
+                    for vuln in &issue.vulnerabilities {
+                        let summary: String = vuln.summary.chars().take(100).collect();
+                        println!("    - {} [{}] {}", vuln.id, vuln.severity.as_deref().unwrap_or("unscored"), summary);
+                    }
+                }
+            }
+
+            // File types
+            let types = &report.file_types;
+            println!("\n{}", style("File Types").bold());
+            println!("  Executables: {}", types.executables);
+            println!("  Shared libraries: {}", types.shared_libraries);
+            if types.objects + types.static_archives > 0 {
+                println!("  Objects and static archives: {}", types.objects + types.static_archives);
+            }
+            for (interpreter, count) in &types.scripts {
+                println!("  {} scripts: {}", interpreter, count);
+            }
+            println!("  Data: {}", types.data);
+            println!("  Symlinks: {}", types.symlinks);
+            for notable in &types.notable {
+                println!("    - {}: {}", notable.path.display(), notable.note);
+            }
 
-            // Conflicts
-            if args.conflicts && !report.conflicts.is_empty() {
-                println!("\n{}", style("! Conflicts").red().bold());
-                for conflict in &report.conflicts {
-                    println!("  • {}", conflict);
+            // Size breakdown
+            let size = &report.size;
+            println!("\n{}", style("Size").bold());
+            println!("  Total: {} KB in {} files", size.total_size / 1024, size.file_count);
+            for estimate in &size.compressed {
+                println!("  As .{}: ~{} KB", estimate.format, estimate.size / 1024);
+            }
+            if !size.directories.is_empty() {
+                println!("\n  By directory:");
+                for dir in &size.directories {
+                    println!("    {:>10} KB  {}", dir.size / 1024, dir.path.display());
+                }
+            }
+            if !size.largest.is_empty() {
+                println!("\n  Largest files:");
+                for file in &size.largest {
+                    println!("    {:>10} KB  {}", file.size / 1024, file.path.display());
                 }
             }
+            let wasted: u64 = size.duplicates.iter().map(|d| d.wasted()).sum();
+            if wasted > 0 {
+                println!("\n  Duplicates: {} group(s) wasting {} KB", size.duplicates.len(), wasted / 1024);
+            }
 
             // File analysis
             if args.verify {
                 println!("\n{}", style("File Verification").bold());
                 println!("  Verified: {}", report.verified_files);
                 println!("  Failed: {}", report.failed_files);
+                let seconds = report.verify_millis.max(1) as f64 / 1000.0;
+                println!(
+                    "  Hashed: {} MB in {:.1}s ({:.0} MB/s)",
+                    report.verified_bytes / (1024 * 1024),
+                    seconds,
+                    report.verified_bytes as f64 / (1024.0 * 1024.0) / seconds
+                );
             }
         }
         super::InfoFormat::Json => {
@@ -316,39 +1213,81 @@ pub async fn execute_analyze(args: &super::AnalyzeArgs) -> Result<()> {
         super::InfoFormat::Toml => {
             println!("{}", toml::to_string_pretty(&report).map_err(|e| crate::error::RexebError::Other(e.to_string()))?);
         }
+        super::InfoFormat::Html => {
+            print!("{}", super::html::analysis(&metadata, &report, args.verify));
+        }
+    }
+
+    if let Some(threshold) = args.fail_on {
+        let failing = report.count_at_least(threshold);
+        if failing > 0 {
+            return Err(crate::error::RexebError::Findings(format!(
+                "{} finding(s) at {} or above",
+                failing, threshold
+            )));
+        }
     }
 
     Ok(())
 }
 
-/// Execute the install command
-pub async fn execute_install(args: &super::InstallArgs) -> Result<()> {
-    use std::process::Command;
-    use tempfile::TempDir;
-
-    // Convert packages first
-    let temp_dir = TempDir::new()?;
-    let convert_args = super::ConvertArgs {
-        input: args.input.clone(),
-        output: Some(temp_dir.path().to_path_buf()),
+/// Convert arguments for commands that convert into a directory of their own
+fn internal_convert_args(input: Vec<PathBuf>, output: &Path, yes: bool) -> super::ConvertArgs {
+    super::ConvertArgs {
+        input,
+        output: Some(output.to_path_buf()),
         skip_deps: false,
         force: false,
         pkgbuild: false,
-        yes: args.yes,
+        pkgbase: None,
+        prefix: None,
+        output_name: None,
+        origin: None,
+        recommends: None,
+        namcap: false,
+        deny_namcap_errors: false,
+        simulate: false,
+        keep_arch: false,
+        no_cache: false,
+        recursive: false,
+        review: false,
+        report: None,
+        lenient: false,
+        ignore_deps: Vec::new(),
+        yes,
         pseudo64: false,
         keep_temp: false,
         name: None,
         version_override: None,
         release: None,
         format: super::OutputFormat::PkgTarZst,
-    };
+        progress_format: super::ProgressFormat::Bars,
+        progress_file: None,
+    }
+}
+
+/// Execute the install command
+pub async fn execute_install(args: &super::InstallArgs) -> Result<()> {
+    use std::process::Command;
+    use tempfile::TempDir;
+
+    // pacman asks before installing
+    require_answer(args.yes, "installing needs confirmation")?;
+
+    // Convert packages first, into the package cache: the history points
+    // at them, so they stay there until `rexeb clean`
+    let packages_dir = crate::config::Config::load()?.cache_dir().join("packages");
+    std::fs::create_dir_all(&packages_dir)?;
+    let output_dir = tempfile::Builder::new().prefix("install-").tempdir_in(&packages_dir)?;
+    let convert_args = internal_convert_args(args.input.clone(), output_dir.path(), args.yes);
 
     execute_convert(&convert_args).await?;
+    let output_dir = output_dir.keep();
 
     // Find converted packages
-    let packages: Vec<_> = std::fs::read_dir(temp_dir.path())?
+    let packages: Vec<_> = std::fs::read_dir(&output_dir)?
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().map_or(false, |ext| ext == "zst"))
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "zst"))
         .map(|e| e.path())
         .collect();
 
@@ -356,6 +1295,16 @@ pub async fn execute_install(args: &super::InstallArgs) -> Result<()> {
         return Err(crate::error::RexebError::PackageBuild("No packages were created".into()));
     }
 
+    let temp_dir = TempDir::new()?;
+    if !args.no_aur {
+        install_aur_dependencies(args, &packages, temp_dir.path()).await?;
+    }
+
+    if args.via_repo {
+        install_via_repo(args, &packages, temp_dir.path())?;
+        return record_installed(&packages);
+    }
+
     // Build pacman command
     let mut cmd = Command::new("sudo");
     cmd.arg("pacman").arg("-U");
@@ -382,11 +1331,247 @@ pub async fn execute_install(args: &super::InstallArgs) -> Result<()> {
     let status = cmd.status()?;
 
     if !status.success() {
-        return Err(crate::error::RexebError::Other(
-            format!("pacman exited with status: {}", status)
-        ));
+        return Err(crate::error::RexebError::Pacman(format!("exited with status: {}", status)));
+    }
+
+    record_installed(&packages)
+}
+
+/// Mark installed packages in the conversion history
+fn record_installed(packages: &[std::path::PathBuf]) -> Result<()> {
+    let config = crate::config::Config::load()?;
+    if let Err(e) = crate::history::History::from_config(&config).and_then(|mut h| h.mark_installed(packages)) {
+        tracing::warn!("Could not record the installation in the history: {}", e);
+    }
+    Ok(())
+}
+
+/// Build and install the dependencies only the AUR has
+///
+/// Uses the configured AUR helper, or clones and builds each package base
+/// with `makepkg` (AUR dependencies of AUR packages first). Dependencies
+/// neither installed, in a sync repository nor in the AUR are left for
+/// pacman to report.
+async fn install_aur_dependencies(args: &super::InstallArgs, packages: &[std::path::PathBuf], work_dir: &Path) -> Result<()> {
+    use std::collections::HashSet;
+    use std::process::Command;
+    use crate::error::RexebError;
+    use crate::resolver::{AurClient, PackageDatabase};
+
+    let config = crate::config::Config::load()?;
+    let db = PackageDatabase::new()?;
+    let aur = AurClient::new(&config.network)?;
+    let available = |name: &str| repo_available(&db, name);
+
+    // Dependencies of the converted packages that nothing here provides
+    let mut provided: HashSet<String> = HashSet::new();
+    let mut wanted: Vec<String> = Vec::new();
+    for pkg in packages {
+        let info = crate::repo::read_pkginfo(pkg)?;
+        let values = |key: &str| info.get(key).cloned().unwrap_or_default();
+        provided.extend(values("pkgname").iter().chain(&values("provides")).map(|p| strip_version(p)));
+        wanted.extend(values("depend").iter().map(|d| strip_version(d)));
+    }
+    wanted.retain(|name| !provided.contains(name) && !available(name));
+    wanted.sort();
+    wanted.dedup();
+    if wanted.is_empty() || aur.is_offline() {
+        return Ok(());
+    }
+
+    let order = aur_build_order(wanted, &db, &aur).await?;
+    let mut bases: Vec<String> = Vec::new();
+    for pkg in &order {
+        if !bases.contains(&pkg.package_base) {
+            bases.push(pkg.package_base.clone());
+        }
+    }
+    if bases.is_empty() {
+        return Ok(());
+    }
+
+    let names: Vec<&str> = order.iter().map(|p| p.name.as_str()).collect();
+    println!("Dependencies from the AUR: {}", names.join(" "));
+    if !args.yes && !config.general.auto_yes {
+        require_answer(false, "building dependencies from the AUR needs confirmation")?;
+        let proceed = dialoguer::Confirm::new()
+            .with_prompt("Build and install them now?")
+            .default(true)
+            .interact()
+            .map_err(|e| RexebError::Other(e.to_string()))?;
+        if !proceed {
+            return Ok(());
+        }
+    }
+
+    let helper = args.aur_helper.clone().or(config.general.aur_helper).unwrap_or_else(|| "makepkg".to_string());
+    if helper != "makepkg" {
+        let mut cmd = Command::new(&helper);
+        cmd.arg("-S").arg("--asdeps").arg("--needed");
+        if args.yes {
+            cmd.arg("--noconfirm");
+        }
+        let status = cmd.args(&names).status()?;
+        if !status.success() {
+            return Err(RexebError::Pacman(format!("{} exited with status: {}", helper, status)));
+        }
+        return Ok(());
+    }
+
+    for base in bases {
+        let dir = work_dir.join("aur").join(&base);
+        let status = Command::new("git")
+            .arg("clone")
+            .arg("--depth=1")
+            .arg(aur.clone_url(&base))
+            .arg(&dir)
+            .status()?;
+        if !status.success() {
+            return Err(RexebError::Other(format!("Failed to clone {} from the AUR", base)));
+        }
+
+        let mut cmd = Command::new("makepkg");
+        cmd.current_dir(&dir).arg("--syncdeps").arg("--install").arg("--asdeps").arg("--needed");
+        if args.yes {
+            cmd.arg("--noconfirm");
+        }
+        let status = cmd.status()?;
+        if !status.success() {
+            return Err(RexebError::PackageBuild(format!("makepkg failed for {}: {}", base, status)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Package name of a dependency, without its version constraint
+fn strip_version(dep: &str) -> String {
+    dep.split(['<', '>', '=']).next().unwrap_or(dep).to_string()
+}
+
+/// Whether a dependency is installed or in a sync repository
+fn repo_available(lookup: &dyn crate::resolver::Lookup, name: &str) -> bool {
+    lookup.installed_version(name).is_some()
+        || lookup
+            .provider(name)
+            .is_some_and(|info| info.repo.is_some() || lookup.installed_version(&info.name).is_some())
+}
+
+/// The AUR packages `wanted` and their own AUR dependencies, dependencies first
+async fn aur_build_order(
+    wanted: Vec<String>,
+    lookup: &dyn crate::resolver::Lookup,
+    aur: &dyn crate::resolver::AurApi,
+) -> Result<Vec<crate::resolver::AurPackage>> {
+    use std::collections::HashSet;
+
+    // Walk AUR dependencies of AUR packages, then build them in reverse
+    let mut order = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut pending = wanted;
+    while !pending.is_empty() {
+        let names: Vec<&str> = pending.iter().map(String::as_str).collect();
+        let found = aur.info(&names).await?;
+        seen.extend(pending.drain(..));
+        for pkg in found {
+            for dep in pkg.depends.iter().chain(&pkg.make_depends).flatten() {
+                let dep = strip_version(dep);
+                if !repo_available(lookup, &dep) && !seen.contains(&dep) && !pending.contains(&dep) {
+                    pending.push(dep);
+                }
+            }
+            order.push(pkg);
+        }
+    }
+    order.reverse();
+    Ok(order)
+}
+
+/// Install converted packages from a throwaway local repository
+///
+/// Only the throwaway repository's database is placed in pacman's sync
+/// directory, so no other repository is refreshed (avoiding a partial
+/// upgrade) while pacman still resolves mapped dependencies with `-S`.
+fn install_via_repo(args: &super::InstallArgs, packages: &[std::path::PathBuf], work_dir: &Path) -> Result<()> {
+    use crate::repo::LocalRepo;
+
+    let repo = LocalRepo::new(work_dir.join("repo"), "rexeb-install")?;
+    let mut targets = Vec::new();
+    for pkg in packages {
+        // The converted package stays where the history recorded it
+        let desc = repo.add(pkg, false)?;
+        targets.push(format!("{}/{}", repo.name(), desc.name()));
+    }
+
+    let conf_path = work_dir.join("pacman.conf");
+    std::fs::write(&conf_path, repo.overlay_pacman_conf(Path::new("/etc/pacman.conf")))?;
+
+    let commands = repo_install_commands(args, &repo, &conf_path, &targets);
+    run_repo_install(&commands, |argv| {
+        std::process::Command::new(&argv[0]).args(&argv[1..]).status()
+    })
+}
+
+/// Commands installing from a throwaway repository
+#[derive(Debug)]
+struct RepoInstallCommands {
+    /// Database copied into pacman's sync directory
+    sync_db: PathBuf,
+    /// Put the database in place
+    setup: Vec<std::ffi::OsString>,
+    /// Install the targets from the repository
+    install: Vec<std::ffi::OsString>,
+    /// Remove the database again
+    cleanup: Vec<std::ffi::OsString>,
+}
+
+/// Commands installing `targets` from `repo`, with `conf_path` as its pacman.conf
+fn repo_install_commands(
+    args: &super::InstallArgs,
+    repo: &crate::repo::LocalRepo,
+    conf_path: &Path,
+    targets: &[String],
+) -> RepoInstallCommands {
+    use std::ffi::OsString;
+
+    let sync_db = Path::new(crate::resolver::pacman::PACMAN_DB_PATH)
+        .join("sync")
+        .join(format!("{}.db", repo.name()));
+    let setup = vec!["sudo".into(), "install".into(), "-m644".into(), repo.db_path().into(), sync_db.clone().into()];
+
+    let mut install: Vec<OsString> = vec!["sudo".into(), "pacman".into(), "--config".into(), conf_path.into(), "-S".into()];
+    for (set, flag) in [(args.yes, "--noconfirm"), (args.asdeps, "--asdeps"), (args.asexplicit, "--asexplicit")] {
+        if set {
+            install.push(flag.into());
+        }
     }
+    install.extend(args.pacman_args.iter().map(OsString::from));
+    install.extend(targets.iter().map(OsString::from));
+
+    let cleanup = vec!["sudo".into(), "rm".into(), "-f".into(), sync_db.clone().into()];
+    RepoInstallCommands { sync_db, setup, install, cleanup }
+}
+
+/// Run a repository install, each command through `run`
+fn run_repo_install(
+    commands: &RepoInstallCommands,
+    mut run: impl FnMut(&[std::ffi::OsString]) -> std::io::Result<std::process::ExitStatus>,
+) -> Result<()> {
+    use crate::error::RexebError;
+
+    if !run(&commands.setup)?.success() {
+        return Err(RexebError::Repository(format!("Failed to install {}", commands.sync_db.display())));
+    }
+
+    let status = run(&commands.install);
+
+    // Drop the throwaway database again regardless of the outcome
+    let _ = run(&commands.cleanup);
 
+    let status = status?;
+    if !status.success() {
+        return Err(RexebError::Pacman(format!("exited with status: {}", status)));
+    }
     Ok(())
 }
 
@@ -438,11 +1623,23 @@ pub async fn execute_clean(args: &super::CleanArgs) -> Result<()> {
     use crate::config::Config;
 
     let config = Config::load()?;
-    let clean_all = args.all || (!args.cache && !args.temp);
+    let scope = CleanScope::of(args);
 
     let mut cleaned = Vec::new();
 
-    if clean_all || args.cache {
+    // The sandbox roots live in the cache but belong to root
+    if scope.sandbox {
+        let bootstrap = crate::sandbox::Bootstrap::from_config(&config);
+        if bootstrap.dir().exists() {
+            if args.dry_run {
+                println!("Would remove: {}", bootstrap.dir().display());
+            } else if bootstrap.clean()? {
+                cleaned.push(bootstrap.dir().to_path_buf());
+            }
+        }
+    }
+
+    if scope.cache {
         let cache_dir = config.cache_dir();
         if cache_dir.exists() {
             if args.dry_run {
@@ -454,7 +1651,7 @@ pub async fn execute_clean(args: &super::CleanArgs) -> Result<()> {
         }
     }
 
-    if clean_all || args.temp {
+    if scope.temp {
         let temp_dir = std::env::temp_dir().join("rexeb");
         if temp_dir.exists() {
             if args.dry_run {
@@ -476,3 +1673,1014 @@ pub async fn execute_clean(args: &super::CleanArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// What `rexeb clean` removes
+#[derive(Debug, PartialEq, Eq)]
+struct CleanScope {
+    /// The bootstrapped sandbox roots
+    sandbox: bool,
+    /// The cache directory, sandbox roots included
+    cache: bool,
+    /// Temporary files
+    temp: bool,
+}
+
+impl CleanScope {
+    /// Scope of the flags; none means everything
+    fn of(args: &super::CleanArgs) -> Self {
+        let all = args.all || (!args.cache && !args.temp && !args.sandbox);
+        Self {
+            sandbox: all || args.cache || args.sandbox,
+            cache: all || args.cache,
+            temp: all || args.temp,
+        }
+    }
+}
+
+/// Execute the repo command
+pub async fn execute_repo(args: &super::RepoArgs) -> Result<()> {
+    use crate::config::Config;
+    use crate::repo::LocalRepo;
+
+    let mut config = Config::load()?;
+    if let Some(ref dir) = args.repo_dir {
+        config.repo.path = Some(dir.clone());
+    }
+    if let Some(ref name) = args.repo_name {
+        config.repo.name = name.clone();
+    }
+    let repo = LocalRepo::from_config(&config)?;
+
+    match &args.command {
+        super::RepoCommands::Add { packages, copy } => {
+            for package in packages {
+                let desc = repo.add(package, !copy)?;
+                println!(
+                    "{} {} {} -> {}",
+                    console::style("Added").green().bold(),
+                    desc.name(),
+                    desc.version(),
+                    repo.name()
+                );
+            }
+            println!("\nEnable the repository by adding to /etc/pacman.conf:\n\n{}", repo.pacman_conf());
+        }
+        super::RepoCommands::Remove { names } => {
+            for name in names {
+                match repo.remove(name)? {
+                    Some(desc) => println!("{} {} {}", console::style("Removed").green().bold(), desc.name(), desc.version()),
+                    None => println!("{} {} is not in {}", console::style("Skipped").yellow(), name, repo.name()),
+                }
+            }
+        }
+        super::RepoCommands::List => {
+            let entries = repo.entries()?;
+            if entries.is_empty() {
+                println!("Repository {} is empty ({})", repo.name(), repo.path().display());
+            }
+            for desc in entries {
+                println!("{} {}", console::style(desc.name()).bold(), desc.version());
+            }
+        }
+        super::RepoCommands::Conf => {
+            print!("{}", repo.pacman_conf());
+        }
+    }
+
+    Ok(())
+}
+
+/// Execute the map command
+pub async fn execute_map(args: &super::MapArgs) -> Result<()> {
+    use crate::error::RexebError;
+    use crate::resolver::database::{MappingSource, PackageDatabase};
+
+    let mut db = PackageDatabase::new()?;
+
+    match &args.command {
+        super::MapCommands::Add { debian, arch, confidence } => {
+            if !(0.0..=1.0).contains(confidence) {
+                return Err(RexebError::Config("Confidence must be between 0.0 and 1.0".into()));
+            }
+            db.add_mapping(debian, arch, *confidence);
+            db.save()?;
+            println!("{} {} -> {}", console::style("Mapped").green().bold(), debian, arch);
+        }
+        super::MapCommands::Remove { names } => {
+            for name in names {
+                match db.remove_mapping(name) {
+                    Some(mapping) if mapping.source == MappingSource::Builtin => println!(
+                        "{} {} is built in; override it with `rexeb map add` or drop it in mappings.toml",
+                        console::style("Skipped").yellow(),
+                        name
+                    ),
+                    Some(mapping) => {
+                        println!("{} {} -> {}", console::style("Removed").green().bold(), name, mapping.arch_name)
+                    }
+                    None => println!("{} {} has no mapping", console::style("Skipped").yellow(), name),
+                }
+            }
+            db.save()?;
+        }
+        super::MapCommands::List { source } => {
+            let mut mappings: Vec<_> = db
+                .mappings()
+                .filter(|m| source.is_none_or(|s| m.source == s))
+                .collect();
+            mappings.sort_by(|a, b| a.debian_name.cmp(&b.debian_name));
+            for mapping in mappings {
+                println!(
+                    "{} -> {} {}",
+                    mapping.debian_name,
+                    console::style(&mapping.arch_name).bold(),
+                    console::style(format!("({:.0}%, {})", mapping.confidence * 100.0, mapping.source.label())).dim()
+                );
+            }
+        }
+        super::MapCommands::Export { file } => {
+            db.export_mappings(file)?;
+            println!("Exported mappings to {}", file.display());
+        }
+        super::MapCommands::Import { file } => {
+            let count = db.import_mappings(file)?;
+            db.save()?;
+            println!("Imported {} mappings from {}", count, file.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Execute the diff command
+pub async fn execute_diff(args: &super::DiffArgs) -> Result<()> {
+    use crate::diff::{diff, PackageContents};
+    use console::style;
+
+    let old = PackageContents::load(&args.old)?;
+    let new = PackageContents::load(&args.new)?;
+    let changes = diff(&old, &new);
+
+    match args.format {
+        super::InfoFormat::Pretty => {
+            println!("{} {} -> {}", style("Comparing").bold(), changes.old, changes.new);
+            if changes.is_empty() {
+                println!("  No differences");
+                return Ok(());
+            }
+
+            if !changes.fields.is_empty() {
+                println!("\n{}", style("Metadata").bold());
+                for field in &changes.fields {
+                    println!(
+                        "  {}: {} -> {}",
+                        field.field,
+                        field.old.as_deref().unwrap_or("(none)"),
+                        field.new.as_deref().unwrap_or("(none)")
+                    );
+                }
+            }
+
+            if !changes.relations.is_empty() {
+                println!("\n{}", style("Relations").bold());
+                for relation in &changes.relations {
+                    println!("  {}:", relation.relation);
+                    for entry in &relation.removed {
+                        println!("    {}", style(format!("- {}", entry)).red());
+                    }
+                    for entry in &relation.added {
+                        println!("    {}", style(format!("+ {}", entry)).green());
+                    }
+                }
+            }
+
+            if !changes.added_files.is_empty() || !changes.removed_files.is_empty() || !changes.changed_files.is_empty() {
+                println!(
+                    "\n{} ({} added, {} removed, {} changed)",
+                    style("Files").bold(),
+                    changes.added_files.len(),
+                    changes.removed_files.len(),
+                    changes.changed_files.len()
+                );
+                for path in &changes.removed_files {
+                    println!("  {}", style(format!("- {}", path.display())).red());
+                }
+                for path in &changes.added_files {
+                    println!("  {}", style(format!("+ {}", path.display())).green());
+                }
+                for file in &changes.changed_files {
+                    println!("  {} {} ({})", style("~").yellow(), file.path.display(), file.changes.join(", "));
+                }
+            }
+
+            for script in &changes.scripts {
+                println!("\n{} {}", style("Script").bold(), script.name);
+                for line in &script.lines {
+                    match line.starts_with('+') {
+                        true => println!("  {}", style(line).green()),
+                        false => println!("  {}", style(line).red()),
+                    }
+                }
+            }
+        }
+        super::InfoFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&changes)?);
+        }
+        super::InfoFormat::Toml => {
+            println!("{}", toml::to_string_pretty(&changes).map_err(|e| crate::error::RexebError::Other(e.to_string()))?);
+        }
+        super::InfoFormat::Html => {
+            print!("{}", super::html::diff(&changes));
+        }
+    }
+
+    Ok(())
+}
+
+/// Execute the verify command
+pub async fn execute_verify(args: &super::VerifyArgs) -> Result<()> {
+    use crate::converter::ConversionCache;
+    use crate::diff::{verify, PackageContents, SourceCheck};
+    use crate::error::RexebError;
+    use crate::resolver::PackageDatabase;
+    use console::style;
+
+    // Read the deb the way a conversion sees it, so version and architecture compare
+    let deb = PackageContents::from_deb_converted(&args.deb, &crate::config::Config::load()?.conversion)?;
+    let package = PackageContents::load(&args.package)?;
+    let deb_sha256 = ConversionCache::file_sha256(&args.deb)?;
+    // Without a mapping database, dependencies are only found under their Debian names
+    let db = PackageDatabase::new().ok();
+    let map = |name: &str| db.as_ref().and_then(|db| db.lookup(name).ok().flatten()).map(|(arch, _)| arch);
+    let result = verify(&deb, &deb_sha256, &package, map);
+
+    match args.format {
+        super::InfoFormat::Pretty => {
+            println!("{} {} -> {}", style("Verifying").bold(), result.deb, result.package);
+            match result.source {
+                SourceCheck::Matches => println!("  Source: {}", style("converted from this deb").green()),
+                SourceCheck::Differs { ref recorded } => {
+                    println!("  Source: {} (SHA-256 {})", style("converted from another file").red(), recorded)
+                }
+                SourceCheck::NotRecorded => println!("  Source: {}", style("not recorded").yellow()),
+            }
+            for field in &result.fields {
+                println!(
+                    "  {}: {} -> {}",
+                    field.field,
+                    field.old.as_deref().unwrap_or("(none)"),
+                    field.new.as_deref().unwrap_or("(none)")
+                );
+            }
+
+            println!(
+                "\n{} ({} identical, {} moved, {} altered, {} dropped, {} added)",
+                style("Files").bold(),
+                result.identical,
+                result.moved.len(),
+                result.altered.len(),
+                result.dropped.len(),
+                result.added.len()
+            );
+            for path in &result.dropped {
+                println!("  {}", style(format!("- {}", path.display())).red());
+            }
+            for file in &result.altered {
+                println!("  {} {} ({})", style("~").yellow(), file.path.display(), file.changes.join(", "));
+            }
+            for path in &result.added {
+                println!("  {}", style(format!("+ {}", path.display())).green());
+            }
+            for (from, to) in &result.moved {
+                println!("  {} -> {}", from.display(), to.display());
+            }
+
+            if !result.dependencies.is_empty() {
+                println!("\n{}", style("Dependencies").bold());
+                for dependency in &result.dependencies {
+                    let outcome = match dependency.outcome.as_str() {
+                        "missing" => style(dependency.outcome.as_str()).red(),
+                        _ => style(dependency.outcome.as_str()).green(),
+                    };
+                    match dependency.arch {
+                        Some(ref arch) if arch != &dependency.debian => {
+                            println!("  {} ({}): {}", dependency.debian, arch, outcome)
+                        }
+                        _ => println!("  {}: {}", dependency.debian, outcome),
+                    }
+                }
+            }
+
+            if !result.scripts_dropped.is_empty() {
+                println!(
+                    "\n{} {} without an install script",
+                    style("Scripts").bold(),
+                    result.scripts_dropped.join(", ")
+                );
+            }
+        }
+        super::InfoFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+        super::InfoFormat::Toml => {
+            println!("{}", toml::to_string_pretty(&result).map_err(|e| RexebError::Other(e.to_string()))?);
+        }
+        super::InfoFormat::Html => {
+            print!("{}", super::html::verify(&result));
+        }
+    }
+
+    if let SourceCheck::Differs { .. } = result.source {
+        return Err(RexebError::Validation(format!("{} was not converted from {}", args.package.display(), args.deb.display())));
+    }
+    if args.strict && !result.is_exact() {
+        return Err(RexebError::Validation(format!("{} does not reproduce {} exactly", args.package.display(), args.deb.display())));
+    }
+    Ok(())
+}
+
+/// Whether a sandbox run gets network access: the flags, else the configuration
+fn sandbox_network(config: &crate::config::Config, network: bool, no_network: bool) -> bool {
+    match (network, no_network) {
+        (true, _) => true,
+        (_, true) => false,
+        _ => config.sandbox.network,
+    }
+}
+
+/// Execute the test command
+pub async fn execute_test(args: &super::TestArgs) -> Result<()> {
+    use crate::config::Config;
+    use crate::error::RexebError;
+    use crate::sandbox::{create_bootstrapped_sandbox, Cancellation, InstallTest, OutputLine, RunLog};
+    use console::style;
+
+    let config = Config::load()?;
+    let temp_dir = tempfile::TempDir::new()?;
+    let mut convert_args = internal_convert_args(vec![args.input.clone()], temp_dir.path(), true);
+    convert_args.skip_deps = args.skip_deps;
+    convert_args.force = args.force;
+    execute_convert(&convert_args).await?;
+
+    let package = std::fs::read_dir(temp_dir.path())?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|p| p.to_string_lossy().contains(".pkg.tar"))
+        .ok_or_else(|| RexebError::PackageBuild("No package was created".into()))?;
+
+    let mut sandbox = create_bootstrapped_sandbox(&config, args.refresh_sandbox).await?;
+    sandbox.init()?;
+    let network = sandbox_network(&config, args.network, args.no_network);
+    let label = package.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let log = RunLog::from_config(&config, label.split(".pkg.tar").next().unwrap_or(&label))?;
+    let cancel = Cancellation::new();
+    let interrupt = {
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancel.cancel();
+            }
+        })
+    };
+    let mut test = InstallTest::new(sandbox.as_ref()).with_network(network).with_log(log).with_cancellation(cancel);
+    let mut follower = None;
+    if args.follow {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel::<OutputLine>();
+        test = test.with_output(sender);
+        // stderr, so the report on stdout stays machine-readable
+        follower = Some(tokio::spawn(async move {
+            while let Some(line) = receiver.recv().await {
+                eprintln!("  {}", style(line.text()).dim());
+            }
+        }));
+    }
+    let report = test.run(&package).await;
+    drop(test);
+    interrupt.abort();
+    if let Some(follower) = follower {
+        let _ = follower.await;
+    }
+    sandbox.cleanup()?;
+    let report = report?;
+
+    match args.format {
+        super::InfoFormat::Pretty => {
+            println!("{} {}", style("Testing").bold(), report.package);
+            for step in &report.steps {
+                let outcome = match step.passed {
+                    true => style("PASS").green(),
+                    false => style("FAIL").red(),
+                };
+                println!("  {} {}", outcome, step.name);
+                if !step.passed {
+                    for line in step.detail.lines() {
+                        println!("       {}", style(line).dim());
+                    }
+                    if let Some(log) = &step.log {
+                        println!("       {}", style(format!("log: {}", log.display())).dim());
+                    }
+                }
+            }
+            let failed = report.failures().count();
+            println!("\n{} of {} checks passed", report.steps.len() - failed, report.steps.len());
+            if let Some(log_dir) = &report.log_dir {
+                println!("Logs: {}", log_dir.display());
+            }
+        }
+        super::InfoFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        super::InfoFormat::Toml => {
+            println!("{}", toml::to_string_pretty(&report).map_err(|e| RexebError::Other(e.to_string()))?);
+        }
+        super::InfoFormat::Html => {
+            print!("{}", super::html::test(&report));
+        }
+    }
+
+    if !report.passed() {
+        return Err(RexebError::Findings(format!(
+            "{} of {} sandbox checks failed",
+            report.failures().count(),
+            report.steps.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Execute the list command
+pub async fn execute_list(args: &super::ListArgs) -> Result<()> {
+    use crate::error::RexebError;
+    use crate::history::{History, HistoryFilter};
+    use console::style;
+
+    let history = History::from_config(&crate::config::Config::load()?)?;
+    let filter = HistoryFilter {
+        name: args.name.clone(),
+        installed: match (args.installed, args.not_installed) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        },
+        since: args
+            .since
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .and_then(|time| time.and_local_timezone(chrono::Local).earliest())
+            .map(|time| time.timestamp()),
+    };
+    let mut records: Vec<_> = history.query(&filter).cloned().collect();
+    if let Some(limit) = args.limit {
+        records.drain(..records.len().saturating_sub(limit));
+    }
+
+    match args.format {
+        super::InfoFormat::Pretty => {
+            if records.is_empty() {
+                println!("No conversions recorded");
+                return Ok(());
+            }
+            for record in &records {
+                let created = chrono::DateTime::from_timestamp(record.created, 0)
+                    .map(|time| time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default();
+                let status = match (record.installed, record.removed) {
+                    (Some(_), _) => style("installed").green(),
+                    (None, Some(_)) => style("removed").yellow(),
+                    (None, None) => style("converted").dim(),
+                };
+                println!(
+                    "{:>4}  {}  {} {}  {}",
+                    record.id,
+                    created,
+                    style(&record.name).bold(),
+                    record.source_version,
+                    status
+                );
+                println!("      {}", style(record.output_path.display()).dim());
+            }
+        }
+        super::InfoFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&records)?);
+        }
+        super::InfoFormat::Toml => {
+            let table = std::collections::BTreeMap::from([("conversions", &records)]);
+            println!("{}", toml::to_string_pretty(&table).map_err(|e| RexebError::Other(e.to_string()))?);
+        }
+        super::InfoFormat::Html => {
+            print!("{}", super::html::history(&records));
+        }
+    }
+    Ok(())
+}
+
+/// Execute the uninstall command
+pub async fn execute_uninstall(args: &super::UninstallArgs) -> Result<()> {
+    use std::process::Command;
+    use crate::converter::ConversionCache;
+    use crate::error::RexebError;
+    use crate::history::History;
+    use crate::repo::{converted_packages, LocalRepo, LOCAL_DB};
+    use console::style;
+
+    let installed = converted_packages(Path::new(LOCAL_DB))?;
+    if args.packages.is_empty() {
+        if installed.is_empty() {
+            println!("No converted packages are installed");
+        }
+        for desc in &installed {
+            let origin = desc.xdata("debian_version").map(|v| format!(" (Debian {})", v)).unwrap_or_default();
+            println!("{} {}{}", style(desc.name()).bold(), desc.version(), style(origin).dim());
+        }
+        return Ok(());
+    }
+
+    let mut targets = Vec::new();
+    for name in &args.packages {
+        let desc = installed.iter().find(|d| d.name() == name).ok_or_else(|| {
+            RexebError::Other(format!("{} is not an installed package converted by rexeb", name))
+        })?;
+        targets.push(desc);
+    }
+    require_answer(args.yes, "removing packages needs confirmation")?;
+
+    let mut cmd = Command::new("sudo");
+    cmd.arg("pacman").arg(if args.recursive { "-Rs" } else { "-R" });
+    if args.yes {
+        cmd.arg("--noconfirm");
+    }
+    cmd.args(&args.pacman_args);
+    cmd.args(targets.iter().map(|d| d.name()));
+
+    let status = cmd.status()?;
+    if !status.success() {
+        return Err(RexebError::Pacman(format!("exited with status: {}", status)));
+    }
+
+    // The packages are gone; tidy up what rexeb kept of them
+    let config = crate::config::Config::load()?;
+    let repo = LocalRepo::from_config(&config)?;
+    let mut history = History::from_config(&config)?;
+    let mut cache = ConversionCache::from_config(&config)?;
+    for desc in targets {
+        if repo.remove(desc.name())?.is_some() {
+            println!("Removed {} from the {} repository", desc.name(), repo.name());
+        }
+        if let Some(sha256) = desc.xdata("rexeb_source_sha256") {
+            history.mark_removed(sha256)?;
+            if !args.keep_cache && cache.remove(sha256)? > 0 {
+                println!("Removed the cached conversion of {}", desc.name());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Execute the upgrade command
+pub async fn execute_upgrade(args: &super::UpgradeArgs) -> Result<()> {
+    use crate::error::RexebError;
+    use crate::history::{History, HistoryRecord};
+    use crate::models::Architecture;
+    use crate::parsers::deb::DebParser;
+    use crate::upstream::{self, Upstream};
+    use console::style;
+    use indicatif::{ProgressBar, ProgressStyle};
+
+    let config = crate::config::Config::load()?;
+    if config.network.offline {
+        return Err(RexebError::Network("offline mode is enabled".into()));
+    }
+    if args.install {
+        require_answer(args.yes, "installing upgrades needs confirmation")?;
+    }
+
+    // The latest conversion of a package tells where it came from and how it was converted
+    let history = History::from_config(&config)?;
+    let mut latest: Vec<&HistoryRecord> = Vec::new();
+    for record in history.records().iter().rev() {
+        if !latest.iter().any(|r| r.name == record.name) {
+            latest.push(record);
+        }
+    }
+    latest.reverse();
+    let wanted: Vec<(&HistoryRecord, Upstream)> = latest
+        .into_iter()
+        .filter(|r| args.packages.is_empty() || args.packages.contains(&r.name))
+        .filter_map(|r| Some((r, Upstream::parse(r.options.get("origin")?)?)))
+        .collect();
+    for name in &args.packages {
+        if !wanted.iter().any(|(r, _)| &r.name == name) {
+            return Err(RexebError::Other(format!(
+                "{} has no recorded conversion with an origin to upgrade from; convert it with --origin",
+                name
+            )));
+        }
+    }
+    if wanted.is_empty() {
+        println!("No conversions with an origin to upgrade from; convert with --origin to record one");
+        return Ok(());
+    }
+
+    let client = config.network.http_client()?;
+    let arch = Architecture::current().to_debian_name();
+    let download_dir = tempfile::TempDir::new()?;
+    let mut upgraded = Vec::new();
+    let mut failed = 0;
+
+    for (record, upstream) in wanted {
+        let result: Result<Option<PathBuf>> = async {
+            let candidate = upstream.latest(&client, &record.name, arch).await?;
+            // Versions from an index are checked before downloading anything
+            if candidate.version.is_some() && !upstream::newer(candidate.version.as_deref(), Some(&record.source_version)) {
+                println!("{} {} is up to date", record.name, record.source_version);
+                return Ok(None);
+            }
+            if args.check && candidate.version.is_some() {
+                println!("{} {} → {}", style(&record.name).bold(), record.source_version, candidate.version.unwrap_or_default());
+                return Ok(None);
+            }
+
+            let deb = upstream::download(&client, &candidate, download_dir.path()).await?;
+            let version = DebParser::control_fields(&deb)?.get("Version").cloned().unwrap_or_default();
+            if !upstream::newer(Some(&version), Some(&record.source_version)) {
+                println!("{} {} is up to date", record.name, record.source_version);
+                return Ok(None);
+            }
+            println!("{} {} → {}", style(&record.name).bold(), record.source_version, version);
+            if args.check {
+                return Ok(None);
+            }
+
+            let output_dir = args
+                .output
+                .clone()
+                .or_else(|| record.output_path.parent().filter(|dir| dir.is_dir()).map(Path::to_path_buf))
+                .unwrap_or(std::env::current_dir()?);
+            let convert_args = upgrade_convert_args(&deb, &output_dir, record, args.yes);
+            let pb = ProgressBar::new(100);
+            pb.set_style(
+                ProgressStyle::with_template("{prefix:.bold.dim} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+                    .unwrap()
+                    .progress_chars("█▓▒░ "),
+            );
+            pb.set_prefix(record.name.clone());
+            let progress = progress_for_bar(&pb);
+            let report = convert_single_package(&deb, &deb, &output_dir, &convert_args, None, pb, progress).await?;
+            Ok(Some(report.output_path))
+        }
+        .await;
+
+        match result {
+            Ok(Some(package)) => upgraded.push(package),
+            Ok(None) => {}
+            Err(e) => {
+                println!("{} {}: {}", style("✗").red(), record.name, e);
+                failed += 1;
+            }
+        }
+    }
+
+    // PKGBUILDs are left for the user to build
+    let packages: Vec<PathBuf> = upgraded.into_iter().filter(|p| p.to_string_lossy().contains(".pkg.tar")).collect();
+    if args.install && !packages.is_empty() {
+        let mut cmd = std::process::Command::new("sudo");
+        cmd.arg("pacman").arg("-U");
+        if args.yes {
+            cmd.arg("--noconfirm");
+        }
+        cmd.args(&packages);
+        let status = cmd.status()?;
+        if !status.success() {
+            return Err(RexebError::Pacman(format!("exited with status: {}", status)));
+        }
+        record_installed(&packages)?;
+    }
+
+    if failed > 0 {
+        return Err(RexebError::Other(format!("{} package(s) could not be upgraded", failed)));
+    }
+    Ok(())
+}
+
+/// Convert arguments repeating the settings of an earlier conversion
+///
+/// A version override is not repeated: the new version is the point.
+fn upgrade_convert_args(deb: &Path, output: &Path, record: &crate::history::HistoryRecord, yes: bool) -> super::ConvertArgs {
+    let mut args = internal_convert_args(vec![deb.to_path_buf()], output, yes);
+    let option = |key: &str| record.options.get(key).cloned();
+    args.name = option("name");
+    args.release = option("release");
+    args.origin = option("origin");
+    args.prefix = option("prefix");
+    args.output_name = option("output-name");
+    args.pkgbuild = option("pkgbuild").is_some();
+    args.skip_deps = option("skip-deps").is_some();
+    args.force = option("force").is_some();
+    if let Some(format) = option("format") {
+        use clap::ValueEnum;
+        if let Some(format) = super::OutputFormat::value_variants().iter().find(|f| f.extension() == format) {
+            args.format = *format;
+        }
+    }
+    args
+}
+
+/// Execute the man command
+pub async fn execute_man(args: &super::ManArgs) -> Result<()> {
+    use clap::CommandFactory;
+
+    let cmd = super::Cli::command();
+    match args.output {
+        Some(ref dir) => {
+            std::fs::create_dir_all(dir)?;
+            clap_mangen::generate_to(cmd, dir)?;
+            println!("Wrote man pages to {}", dir.display());
+        }
+        None => clap_mangen::Man::new(cmd).render(&mut std::io::stdout())?,
+    }
+    Ok(())
+}
+
+/// Execute the doctor command
+pub async fn execute_doctor(args: &super::DoctorArgs) -> Result<()> {
+    use super::doctor::{self, Status};
+    use crate::error::RexebError;
+    use console::style;
+
+    let checks = doctor::run_checks().await;
+    match args.format {
+        super::InfoFormat::Pretty => {
+            for check in &checks {
+                let mark = match check.status {
+                    Status::Ok => style("✓").green(),
+                    Status::Warning => style("⚠").yellow(),
+                    Status::Error => style("✗").red(),
+                };
+                println!("{} {:<18} {}", mark, check.name, check.detail);
+                if let Some(ref fix) = check.fix {
+                    println!("  {:<18} {} {}", "", style("→").cyan(), fix);
+                }
+            }
+            println!("\nrexeb {} on {}", crate::VERSION, std::env::consts::ARCH);
+        }
+        super::InfoFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&checks)?);
+        }
+        super::InfoFormat::Toml => {
+            let table = std::collections::BTreeMap::from([("checks", &checks)]);
+            println!("{}", toml::to_string_pretty(&table).map_err(|e| RexebError::Other(e.to_string()))?);
+        }
+        super::InfoFormat::Html => {
+            print!("{}", super::html::doctor(&checks));
+        }
+    }
+
+    let errors = checks.iter().filter(|c| c.status == Status::Error).count();
+    if errors > 0 {
+        return Err(RexebError::Findings(format!("{} check(s) failed", errors)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Dependency, DependencyType, PackageMetadata};
+
+    #[test]
+    fn test_uncertain_dependencies() {
+        let mut metadata = PackageMetadata::new("foo", "1.0");
+        let mut guessed = Dependency::new("libbar1");
+        guessed.arch_name = Some("bar".to_string());
+        guessed.confidence = 0.5;
+        metadata.add_dep(DependencyType::Depends, guessed);
+        metadata.add_dep(DependencyType::Depends, Dependency::new("libdebian-only1"));
+
+        assert_eq!(uncertain_dependencies(&metadata, 0.8, false), ["libbar1", "libdebian-only1"]);
+        // --lenient makes unmapped ones optional, so --non-interactive need not ask about them
+        assert_eq!(uncertain_dependencies(&metadata, 0.8, true), ["libbar1"]);
+    }
+
+    #[test]
+    fn test_split_pkgbuild_dir() {
+        use crate::models::PackageMetadata;
+
+        let output = Path::new("/tmp/out");
+        let packages = [PackageMetadata::new("app", "1.2.0"), PackageMetadata::new("app-data", "1.2.0")];
+        assert_eq!(split_pkgbuild_dir("app-base", &packages, output, None).unwrap(), output);
+        assert_eq!(
+            split_pkgbuild_dir("app-base", &packages, output, Some("{name}-{version}")).unwrap(),
+            output.join("app-base-1.2.0")
+        );
+        assert!(split_pkgbuild_dir("app-base", &packages, output, Some("{name}/x")).is_err());
+    }
+
+    #[test]
+    fn test_clean_scope() {
+        use clap::Parser;
+
+        let scope = |flags: &[&str]| {
+            let cli = crate::cli::Cli::try_parse_from(["rexeb", "clean"].iter().chain(flags)).unwrap();
+            let crate::cli::Commands::Clean(args) = cli.command else { panic!("not a clean") };
+            CleanScope::of(&args)
+        };
+        let everything = CleanScope { sandbox: true, cache: true, temp: true };
+        assert_eq!(scope(&[]), everything);
+        assert_eq!(scope(&["--all"]), everything);
+        assert_eq!(scope(&["--sandbox"]), CleanScope { sandbox: true, cache: false, temp: false });
+        // The roots live in the cache directory
+        assert_eq!(scope(&["--cache"]), CleanScope { sandbox: true, cache: true, temp: false });
+        assert_eq!(scope(&["--temp"]), CleanScope { sandbox: false, cache: false, temp: true });
+    }
+
+    #[test]
+    fn test_sandbox_network() {
+        let mut config = crate::config::Config::default();
+        assert!(!sandbox_network(&config, false, false));
+        assert!(sandbox_network(&config, true, false));
+
+        // The flags of this run win over the configuration either way
+        config.sandbox.network = true;
+        assert!(sandbox_network(&config, false, false));
+        assert!(!sandbox_network(&config, false, true));
+
+        // Of both flags the last one counts
+        use clap::Parser;
+        for (flags, network) in [(["--network", "--no-network"], false), (["--no-network", "--network"], true)] {
+            let cli = crate::cli::Cli::try_parse_from(["rexeb", "test", "foo.deb"].iter().chain(&flags)).unwrap();
+            let crate::cli::Commands::Test(args) = cli.command else { panic!("not a test") };
+            assert_eq!(sandbox_network(&config, args.network, args.no_network), network);
+        }
+    }
+
+    #[test]
+    fn test_review_without_answers() {
+        use clap::Parser;
+
+        let convert_args = |extra: &[&str]| {
+            let cli = crate::cli::Cli::try_parse_from(["rexeb", "convert", "foo.deb"].iter().chain(extra)).unwrap();
+            match cli.command {
+                crate::cli::Commands::Convert(args) => args,
+                _ => panic!("not a convert"),
+            }
+        };
+        let config = crate::config::Config::default();
+        let uncertain = ["libdebian-only1".to_string()];
+
+        // --non-interactive cannot review the guesses
+        let error = require_mappings(&uncertain, true, &convert_args(&[]), &config).unwrap_err();
+        assert!(matches!(error, crate::error::RexebError::InputRequired(_)));
+        assert_eq!(error.exit_code(), 3);
+        assert!(error.to_string().contains("libdebian-only1"));
+
+        // --yes accepts them, as do interactive runs and --skip-deps
+        assert!(require_mappings(&uncertain, true, &convert_args(&["--yes"]), &config).is_ok());
+        assert!(require_mappings(&uncertain, false, &convert_args(&[]), &config).is_ok());
+        assert!(require_mappings(&uncertain, true, &convert_args(&["--skip-deps"]), &config).is_ok());
+        assert!(require_mappings(&[], true, &convert_args(&[]), &config).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_aur_build_order() {
+        use crate::resolver::database::ArchPackageInfo;
+        use crate::resolver::mock::{aur_package, MemoryLookup, MockAur};
+
+        let with_deps = |name: &str, depends: &[&str], make_depends: &[&str]| {
+            let mut pkg = aur_package(name);
+            pkg.depends = Some(depends.iter().map(|d| d.to_string()).collect());
+            pkg.make_depends = Some(make_depends.iter().map(|d| d.to_string()).collect());
+            pkg
+        };
+        let lookup = MemoryLookup::default().with_package(ArchPackageInfo {
+            name: "glibc".to_string(),
+            version: "2.40-1".to_string(),
+            description: String::new(),
+            provides: Vec::new(),
+            replaces: Vec::new(),
+            sonames: Vec::new(),
+            depends: Vec::new(),
+            repo: Some("core".to_string()),
+        });
+        let aur = MockAur::new([
+            with_deps("foo", &["bar>=1.0", "glibc"], &[]),
+            with_deps("bar", &["glibc"], &["baz"]),
+            with_deps("baz", &[], &[]),
+        ]);
+        let requests = aur.requests.clone();
+
+        // AUR dependencies of AUR packages first, found breadth first
+        let order = aur_build_order(vec!["foo".to_string()], &lookup, &aur).await.unwrap();
+        let names: Vec<_> = order.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["baz", "bar", "foo"]);
+        // Repository packages are never asked of the AUR
+        assert_eq!(*requests.lock().unwrap(), ["foo", "bar", "baz"]);
+    }
+
+    #[test]
+    fn test_repo_install_commands() {
+        use clap::Parser;
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::ExitStatus;
+
+        let cli = crate::cli::Cli::try_parse_from([
+            "rexeb", "install", "--via-repo", "--yes", "--asdeps", "foo.deb", "--", "--needed",
+        ])
+        .unwrap();
+        let crate::cli::Commands::Install(args) = cli.command else { panic!("not an install") };
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = crate::repo::LocalRepo::new(temp_dir.path().join("repo"), "rexeb-install").unwrap();
+        let conf_path = temp_dir.path().join("pacman.conf");
+        let commands = repo_install_commands(&args, &repo, &conf_path, &["rexeb-install/foo".to_string()]);
+
+        let sync_db = "/var/lib/pacman/sync/rexeb-install.db";
+        assert_eq!(commands.setup, ["sudo", "install", "-m644", &repo.db_path().to_string_lossy(), sync_db]);
+        assert_eq!(
+            commands.install,
+            ["sudo", "pacman", "--config", &conf_path.to_string_lossy(), "-S", "--noconfirm", "--asdeps", "--needed", "rexeb-install/foo"]
+        );
+        assert_eq!(commands.cleanup, ["sudo", "rm", "-f", sync_db]);
+
+        // The database is removed again when pacman fails or cannot run
+        for outcome in [Ok(ExitStatus::from_raw(256)), Err(std::io::ErrorKind::NotFound)] {
+            let mut ran = Vec::new();
+            let result = run_repo_install(&commands, |argv| {
+                ran.push(argv[1].clone());
+                match argv[1] == "pacman" {
+                    true => outcome.map_err(std::io::Error::from),
+                    false => Ok(ExitStatus::from_raw(0)),
+                }
+            });
+            assert!(result.is_err());
+            assert_eq!(ran, ["install", "pacman", "rm"]);
+        }
+
+        // Nothing is installed or removed when the database could not be put in place
+        let mut ran = Vec::new();
+        let result = run_repo_install(&commands, |argv| {
+            ran.push(argv[1].clone());
+            Ok(ExitStatus::from_raw(256))
+        });
+        assert!(matches!(result, Err(crate::error::RexebError::Repository(_))));
+        assert_eq!(ran, ["install"]);
+    }
+
+    #[tokio::test]
+    async fn test_feasibility_reuses_analysis() {
+        use crate::analyzer::cache::AnalysisCache;
+        use crate::analyzer::rules::{Finding, Severity};
+        use crate::analyzer::AnalysisReport;
+        use crate::converter::ConversionCache;
+        use crate::parsers::deb::DebParser;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let control = b"Package: foo\nVersion: 1.0\nArchitecture: amd64\nDepends: libbar1\n";
+        let tarball = |name: &str, content: &[u8]| {
+            let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_uid(0);
+            header.set_gid(0);
+            header.set_mtime(0);
+            header.set_cksum();
+            tar.append_data(&mut header, name, content).unwrap();
+            tar.into_inner().unwrap().finish().unwrap()
+        };
+        let control_tar = tarball("./control", control);
+        let data_tar = tarball("./usr/share/doc/foo/README", b"foo\n");
+        let mut deb = ar::Builder::new(Vec::new());
+        for (name, content) in [("debian-binary", &b"2.0\n"[..]), ("control.tar.gz", &control_tar[..]), ("data.tar.gz", &data_tar[..])] {
+            deb.append(&ar::Header::new(name.as_bytes().to_vec(), content.len() as u64), content).unwrap();
+        }
+        let input = temp_dir.path().join("foo_1.0_amd64.deb");
+        std::fs::write(&input, deb.into_inner().unwrap()).unwrap();
+
+        // What `rexeb analyze` left behind
+        let config = crate::config::Config::default();
+        let cache = AnalysisCache::new(temp_dir.path().join("analysis"));
+        let analyzed = AnalysisReport {
+            findings: vec![
+                Finding { rule: "fhs-violation".into(), severity: Severity::Warning, message: "seen by analyze".into() },
+                Finding { rule: "unmapped-dependency".into(), severity: Severity::Warning, message: "libbar1".into() },
+            ],
+            dependency_count: 1,
+            unmapped_deps: vec!["libbar1".into()],
+            ..Default::default()
+        };
+        let input_sha256 = ConversionCache::file_sha256(&input).unwrap();
+        let options_hash = analysis_options_hash(&config, false, false, None).unwrap();
+        cache.store(&input_sha256, &options_hash, &PackageMetadata::new("foo", "1.0"), &analyzed).unwrap();
+
+        // The convert resolved libbar1, which the cached report knew nothing of
+        let parser = DebParser::new(&input).unwrap();
+        let mut metadata = parser.parse().unwrap();
+        metadata.dependencies.get_mut(&DependencyType::Depends).unwrap()[0].arch_name = Some("bar".into());
+
+        let report = feasibility_report(&cache, &config, &input, &parser, &metadata, false, false).await.unwrap();
+        assert!(report.findings.iter().any(|f| f.message == "seen by analyze"));
+        assert_eq!(report.findings_of("unmapped-dependency").count(), 0);
+        assert_eq!((report.dependency_count, report.mapped_count), (1, 1));
+
+        // --no-cache analyzes afresh
+        let report = feasibility_report(&cache, &config, &input, &parser, &metadata, false, true).await.unwrap();
+        assert!(!report.findings.iter().any(|f| f.message == "seen by analyze"));
+    }
+}
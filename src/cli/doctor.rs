@@ -0,0 +1,250 @@
+//! Environment diagnostics
+//!
+//! `rexeb doctor` checks what conversions, installs and sandbox tests rely
+//! on and says how to fix what is missing or stale. Its output is the
+//! first thing to ask a bug reporter for.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::resolver::{AurClient, PackageDatabase};
+use crate::sandbox::{Bootstrap, ChrootSandbox, NspawnSandbox};
+
+/// pacman's sync databases
+const SYNC_DIR: &str = "/var/lib/pacman/sync";
+
+/// Sync databases older than this probably miss current packages
+const SYNC_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Mapping databases older than this miss renamed and new packages
+const MAPPINGS_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 3600);
+
+/// Caches above this size are worth cleaning
+const CACHE_WARN_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Outcome of a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Fine
+    Ok,
+    /// Works, but something will bite
+    Warning,
+    /// Something rexeb needs is broken
+    Error,
+}
+
+/// Result of one check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Check {
+    /// What was checked
+    pub name: &'static str,
+    /// How it went
+    pub status: Status,
+    /// What was found
+    pub detail: String,
+    /// How to fix a problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self { name, status: Status::Ok, detail: detail.into(), fix: None }
+    }
+
+    fn warning(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Warning, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn error(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { name, status: Status::Error, detail: detail.into(), fix: Some(fix.into()) }
+    }
+}
+
+/// Run every check
+pub async fn run_checks() -> Vec<Check> {
+    let (config, config_check) = check_config();
+    let mut checks = vec![check_pacman(), check_sync_dbs(Path::new(SYNC_DIR)), config_check];
+    checks.push(check_aur(&config).await);
+    checks.push(check_sandbox(&config));
+    checks.push(check_mappings());
+    checks.push(check_caches(&config));
+    checks
+}
+
+fn check_config() -> (Config, Check) {
+    let path = Config::config_path().map(|p| p.display().to_string()).unwrap_or_default();
+    match Config::load() {
+        Ok(config) if Path::new(&path).exists() => (config, Check::ok("config", path)),
+        Ok(config) => (config, Check::ok("config", "no config file, using defaults")),
+        Err(e) => (
+            Config::default(),
+            Check::error("config", format!("{}: {}", path, e), "fix the file with `rexeb config edit` or start over with `rexeb config reset`"),
+        ),
+    }
+}
+
+fn check_pacman() -> Check {
+    match Command::new("pacman").arg("--version").output() {
+        Ok(output) if output.status.success() => match pacman_version(&String::from_utf8_lossy(&output.stdout)) {
+            Some(version) => Check::ok("pacman", format!("pacman {}", version)),
+            None => Check::ok("pacman", "installed, version unknown"),
+        },
+        _ => Check::error(
+            "pacman",
+            "pacman not found",
+            "converted packages can only be installed on Arch Linux or a derivative with pacman",
+        ),
+    }
+}
+
+/// Version from `pacman --version` output (` .--.  Pacman v7.0.0 - libalpm v15.0.0`)
+fn pacman_version(output: &str) -> Option<&str> {
+    let rest = &output[output.find("Pacman v")? + "Pacman v".len()..];
+    rest.split_whitespace().next()
+}
+
+fn check_sync_dbs(sync_dir: &Path) -> Check {
+    let newest = std::fs::read_dir(sync_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|e| e == "db"))
+        .filter_map(|entry| entry.metadata().and_then(|m| m.modified()).ok())
+        .max();
+    match newest {
+        None => Check::error("sync databases", format!("none in {}", sync_dir.display()), "run `sudo pacman -Syu`"),
+        Some(modified) => age_check("sync databases", modified, SYNC_MAX_AGE, "run `sudo pacman -Syu`"),
+    }
+}
+
+async fn check_aur(config: &Config) -> Check {
+    if config.network.offline {
+        return Check::warning("AUR", "offline mode is enabled", "set `network.offline = false` to resolve AUR dependencies");
+    }
+    let reachable = match AurClient::new(&config.network) {
+        Ok(aur) => aur.info(&["yay"]).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    match reachable {
+        Ok(()) => Check::ok("AUR", "reachable"),
+        Err(e) => Check::warning(
+            "AUR",
+            format!("not reachable: {}", e),
+            "check the connection, or set `network.proxy` / `network.timeout`",
+        ),
+    }
+}
+
+fn check_sandbox(config: &Config) -> Check {
+    let backends: Vec<&str> = [("systemd-nspawn", NspawnSandbox::is_available()), ("chroot", ChrootSandbox::is_available())]
+        .into_iter()
+        .filter_map(|(name, available)| available.then_some(name))
+        .collect();
+    if backends.is_empty() {
+        return Check::warning(
+            "sandbox",
+            "no backend available",
+            "install systemd or util-linux to use `rexeb test` and `rexeb analyze --trace-scripts`",
+        );
+    }
+    let roots = Bootstrap::from_config(config).cached().unwrap_or_default();
+    let root = match roots.last() {
+        Some(date) => format!("root of {}", date),
+        None => "no root bootstrapped yet".to_string(),
+    };
+    Check::ok("sandbox", format!("{} ({})", backends.join(", "), root))
+}
+
+fn check_mappings() -> Check {
+    let indexed = PackageDatabase::get_db_dir()
+        .ok()
+        .and_then(|dir| std::fs::metadata(dir.join("arch_packages.json")).ok())
+        .and_then(|m| m.modified().ok());
+    match indexed {
+        None => Check::warning("package database", "never updated", "run `rexeb update --all`"),
+        Some(modified) => age_check("package database", modified, MAPPINGS_MAX_AGE, "run `rexeb update --all`"),
+    }
+}
+
+fn check_caches(config: &Config) -> Check {
+    let cache = dir_size(&config.cache_dir());
+    let data = dir_size(&config.data_dir());
+    let detail = format!("cache {}, data {}", human_size(cache), human_size(data));
+    if cache > CACHE_WARN_SIZE {
+        return Check::warning("disk usage", detail, "run `rexeb clean --cache`");
+    }
+    Check::ok("disk usage", detail)
+}
+
+/// Warn when something was last updated longer than `max_age` ago
+fn age_check(name: &'static str, modified: SystemTime, max_age: Duration, fix: &str) -> Check {
+    let age = modified.elapsed().unwrap_or_default();
+    let detail = format!("updated {} ago", human_age(age));
+    match age > max_age {
+        true => Check::warning(name, detail, fix),
+        false => Check::ok(name, detail),
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+fn human_age(age: Duration) -> String {
+    let hours = age.as_secs() / 3600;
+    match hours {
+        0 => format!("{} minutes", age.as_secs() / 60),
+        1 => "1 hour".to_string(),
+        2..=47 => format!("{} hours", hours),
+        _ => format!("{} days", hours / 24),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_helpers() {
+        let output = "\n .--.                  Pacman v7.0.0 - libalpm v15.0.0\n/ _.-' .-.  .-.  .-.   Copyright (C) 2006-2024\n";
+        assert_eq!(pacman_version(output), Some("7.0.0"));
+        assert_eq!(pacman_version("bash: pacman: command not found"), None);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(check_sync_dbs(temp_dir.path()).status, Status::Error);
+        std::fs::write(temp_dir.path().join("core.db"), "").unwrap();
+        let check = check_sync_dbs(temp_dir.path());
+        assert_eq!((check.status, check.detail.as_str()), (Status::Ok, "updated 0 minutes ago"));
+
+        let stale = age_check("x", SystemTime::now() - Duration::from_secs(10 * 24 * 3600), SYNC_MAX_AGE, "update");
+        assert_eq!((stale.status, stale.detail.as_str(), stale.fix.as_deref()), (Status::Warning, "updated 10 days ago", Some("update")));
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(3 * 1024 * 1024 + 512 * 1024), "3.5 MiB");
+    }
+}
@@ -0,0 +1,527 @@
+//! Self-contained HTML reports
+//!
+//! Renders analysis, conversion, info, diff, verification, history, doctor and sandbox test results as a single HTML
+//! file with inline styles and no scripts, so a report can be attached to
+//! an upstream bug as it is. Sections are `<details>` elements: the
+//! interesting ones start open, long lists start collapsed.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::analyzer::rules::Severity;
+use crate::analyzer::AnalysisReport;
+use super::doctor::{Check, Status};
+use crate::converter::ConversionReport;
+use crate::diff::{PackageDiff, SourceCheck, Verification};
+use crate::history::HistoryRecord;
+use crate::models::{DependencyType, PackageMetadata};
+use crate::sandbox::TestReport;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; color: #222; }
+h1 { font-size: 1.5em; border-bottom: 2px solid #1793d1; padding-bottom: .3em; }
+details { margin: 1em 0; border: 1px solid #ddd; border-radius: 4px; padding: .5em 1em; }
+summary { font-weight: bold; cursor: pointer; }
+table { border-collapse: collapse; margin: .5em 0; }
+th, td { text-align: left; padding: .2em 1em .2em 0; vertical-align: top; }
+code, pre { font-family: monospace; }
+pre { background: #f6f6f6; padding: .5em; overflow-x: auto; }
+.error { color: #b00020; } .warning { color: #a15c00; } .info { color: #00629b; }
+.added { color: #1a7f37; } .removed { color: #b00020; }
+footer { margin-top: 2em; color: #888; font-size: .8em; }
+";
+
+/// Escape text for HTML
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// An HTML document built section by section
+struct Document {
+    title: String,
+    body: String,
+}
+
+impl Document {
+    fn new(title: &str) -> Self {
+        Self { title: title.to_string(), body: String::new() }
+    }
+
+    /// A collapsible section; `content` is HTML
+    fn section(&mut self, heading: &str, open: bool, content: &str) {
+        let _ = write!(
+            self.body,
+            "<details{}><summary>{}</summary>\n{}</details>\n",
+            if open { " open" } else { "" },
+            escape(heading),
+            content
+        );
+    }
+
+    fn finish(self) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+             <style>\n{STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n{body}\
+             <footer>Generated by rexeb {version}</footer>\n</body>\n</html>\n",
+            title = escape(&self.title),
+            body = self.body,
+            version = crate::VERSION,
+        )
+    }
+}
+
+/// A two-column table of already escaped values
+fn table<K: AsRef<str>>(rows: &[(K, String)]) -> String {
+    let mut html = String::from("<table>\n");
+    for (key, value) in rows {
+        let _ = writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", escape(key.as_ref()), value);
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// A list of already escaped items
+fn list<I: IntoIterator<Item = String>>(items: I, class: Option<&str>) -> String {
+    let class = class.map(|c| format!(" class=\"{}\"", c)).unwrap_or_default();
+    let mut html = String::from("<ul>\n");
+    for item in items {
+        let _ = writeln!(html, "<li{}>{}</li>", class, item);
+    }
+    html.push_str("</ul>\n");
+    html
+}
+
+fn kib(bytes: u64) -> String {
+    format!("{} KB", bytes / 1024)
+}
+
+fn path(path: &Path) -> String {
+    format!("<code>{}</code>", escape(&path.display().to_string()))
+}
+
+/// Summary rows shared by the analysis and info reports
+fn package_rows(metadata: &PackageMetadata) -> Vec<(&'static str, String)> {
+    let mut rows = vec![
+        ("Package", escape(&metadata.name)),
+        ("Version", escape(&metadata.full_version())),
+        ("Architecture", escape(&metadata.arch.to_string())),
+        ("Description", escape(&metadata.description)),
+    ];
+    if let Some(ref url) = metadata.url {
+        rows.push(("URL", escape(url)));
+    }
+    if let Some(ref maintainer) = metadata.maintainer {
+        rows.push(("Maintainer", escape(maintainer)));
+    }
+    rows.push(("Installed size", kib(metadata.installed_size)));
+    rows.push(("Files", metadata.files.len().to_string()));
+    rows
+}
+
+/// Declared relations of a package, one list per field
+fn dependencies(metadata: &PackageMetadata) -> String {
+    let mut html = String::new();
+    for dep_type in [
+        DependencyType::PreDepends,
+        DependencyType::Depends,
+        DependencyType::Recommends,
+        DependencyType::Suggests,
+        DependencyType::Conflicts,
+        DependencyType::Breaks,
+        DependencyType::Replaces,
+        DependencyType::Provides,
+    ] {
+        let deps = metadata.get_deps(dep_type);
+        if !deps.is_empty() {
+            let _ = writeln!(html, "<p>{}</p>", escape(dep_type.debian_field()));
+            html.push_str(&list(deps.iter().map(|dep| escape(&dep.to_string())), None));
+        }
+    }
+    html
+}
+
+fn file_list(metadata: &PackageMetadata) -> String {
+    list(metadata.files.iter().map(|file| path(file)), None)
+}
+
+/// Render an analysis report
+pub fn analysis(metadata: &PackageMetadata, report: &AnalysisReport, verified: bool) -> String {
+    let mut doc = Document::new(&format!("Analysis of {} {}", metadata.name, metadata.full_version()));
+    let mut rows = package_rows(metadata);
+    rows.push(("Feasibility", escape(&report.feasibility().to_string())));
+    doc.section("Summary", true, &table(&rows));
+
+    for (severity, heading) in [
+        (Severity::Error, "Errors"),
+        (Severity::Warning, "Warnings"),
+        (Severity::Info, "Notes"),
+    ] {
+        let findings: Vec<_> = report.with_severity(severity).collect();
+        if !findings.is_empty() {
+            let items = findings
+                .iter()
+                .map(|f| format!("<code>{}</code> {}", escape(&f.rule), escape(&f.message)));
+            doc.section(
+                &format!("{} ({})", heading, findings.len()),
+                severity != Severity::Info,
+                &list(items, Some(&severity.to_string())),
+            );
+        }
+    }
+
+    let mut deps = table(&[
+        ("Total", report.dependency_count.to_string()),
+        ("Mapped", report.mapped_count.to_string()),
+        ("Unmapped", report.unmapped_deps.len().to_string()),
+    ]);
+    if !report.unmapped_deps.is_empty() {
+        deps.push_str("<p>Unmapped dependencies</p>\n");
+        deps.push_str(&list(report.unmapped_deps.iter().map(|d| escape(d)), Some("warning")));
+    }
+    if !report.multilib_deps.is_empty() {
+        deps.push_str("<p>Multilib dependencies (enable [multilib] in pacman.conf)</p>\n");
+        deps.push_str(&list(report.multilib_deps.iter().map(|d| escape(d)), None));
+    }
+    deps.push_str(&dependencies(metadata));
+    doc.section("Dependencies", true, &deps);
+
+    if !report.security_issues.is_empty() {
+        let mut html = String::new();
+        for issue in &report.security_issues {
+            let _ = writeln!(
+                html,
+                "<p>{} {} ({})</p>",
+                escape(&issue.component.name),
+                escape(&issue.component.version),
+                path(&issue.component.path)
+            );
+            html.push_str(&list(
+                issue.vulnerabilities.iter().map(|v| {
+                    format!(
+                        "<code>{}</code> [{}] {}",
+                        escape(&v.id),
+                        escape(v.severity.as_deref().unwrap_or("unscored")),
+                        escape(&v.summary)
+                    )
+                }),
+                Some("error"),
+            ));
+        }
+        doc.section("Security issues", true, &html);
+    }
+
+    let types = &report.file_types;
+    let mut rows = vec![
+        ("Executables".to_string(), types.executables.to_string()),
+        ("Shared libraries".to_string(), types.shared_libraries.to_string()),
+        ("Objects and static archives".to_string(), (types.objects + types.static_archives).to_string()),
+    ];
+    for (interpreter, count) in &types.scripts {
+        rows.push((format!("{} scripts", interpreter), count.to_string()));
+    }
+    rows.push(("Data".to_string(), types.data.to_string()));
+    rows.push(("Symlinks".to_string(), types.symlinks.to_string()));
+    let mut html = table(&rows);
+    if !types.notable.is_empty() {
+        html.push_str(&list(
+            types.notable.iter().map(|n| format!("{}: {}", path(&n.path), escape(&n.note))),
+            None,
+        ));
+    }
+    doc.section("File types", false, &html);
+
+    let size = &report.size;
+    let mut rows = vec![("Total".to_string(), format!("{} in {} files", kib(size.total_size), size.file_count))];
+    for estimate in &size.compressed {
+        rows.push((format!("As .{}", estimate.format), format!("~{}", kib(estimate.size))));
+    }
+    let mut html = table(&rows);
+    if !size.directories.is_empty() {
+        html.push_str("<p>By directory</p>\n");
+        html.push_str(&list(size.directories.iter().map(|d| format!("{} {}", kib(d.size), path(&d.path))), None));
+    }
+    if !size.largest.is_empty() {
+        html.push_str("<p>Largest files</p>\n");
+        html.push_str(&list(size.largest.iter().map(|f| format!("{} {}", kib(f.size), path(&f.path))), None));
+    }
+    for duplicate in &size.duplicates {
+        let paths: Vec<String> = duplicate.paths.iter().map(|p| path(p)).collect();
+        let _ = writeln!(html, "<p>Identical ({} each): {}</p>", kib(duplicate.size), paths.join(", "));
+    }
+    doc.section("Size", false, &html);
+
+    if verified {
+        doc.section(
+            "File verification",
+            report.failed_files > 0,
+            &table(&[
+                ("Verified", report.verified_files.to_string()),
+                ("Failed", report.failed_files.to_string()),
+                ("Hashed", kib(report.verified_bytes)),
+            ]),
+        );
+    }
+
+    doc.section(&format!("Files ({})", metadata.files.len()), false, &file_list(metadata));
+    doc.finish()
+}
+
+/// Render the summaries of converted packages
+pub fn conversion(reports: &[(&Path, &ConversionReport)]) -> String {
+    let mut doc = Document::new("Conversion report");
+    for (input, report) in reports {
+        let mut html = table(&[("Input", path(input)), ("Output", path(&report.output_path))]);
+        if !report.changes.is_empty() {
+            html.push_str("<p>Changes</p>\n");
+            html.push_str(&list(report.changes.iter().map(|c| escape(c)), None));
+        }
+        if !report.warnings.is_empty() {
+            html.push_str("<p>Warnings</p>\n");
+            html.push_str(&list(report.warnings.iter().map(|w| escape(w)), Some("warning")));
+        }
+        let name = input.file_name().unwrap_or_default().to_string_lossy();
+        doc.section(&name, true, &html);
+    }
+    doc.finish()
+}
+
+/// Render package information
+pub fn info(metadata: &PackageMetadata) -> String {
+    let mut doc = Document::new(&format!("{} {}", metadata.name, metadata.full_version()));
+    doc.section("Package", true, &table(&package_rows(metadata)));
+    doc.section("Dependencies", true, &dependencies(metadata));
+    doc.section(&format!("Files ({})", metadata.files.len()), false, &file_list(metadata));
+    doc.finish()
+}
+
+/// Render the differences between two packages
+pub fn diff(changes: &PackageDiff) -> String {
+    let mut doc = Document::new(&format!("{} -> {}", changes.old, changes.new));
+    if changes.is_empty() {
+        doc.section("Differences", true, "<p>No differences</p>\n");
+        return doc.finish();
+    }
+
+    if !changes.fields.is_empty() {
+        let rows: Vec<_> = changes
+            .fields
+            .iter()
+            .map(|f| {
+                let value = format!(
+                    "{} -&gt; {}",
+                    escape(f.old.as_deref().unwrap_or("(none)")),
+                    escape(f.new.as_deref().unwrap_or("(none)"))
+                );
+                (f.field.as_str(), value)
+            })
+            .collect();
+        doc.section("Metadata", true, &table(&rows));
+    }
+
+    if !changes.relations.is_empty() {
+        let mut html = String::new();
+        for relation in &changes.relations {
+            let _ = writeln!(html, "<p>{}</p>", escape(&relation.relation));
+            html.push_str(&list(relation.removed.iter().map(|e| format!("- {}", escape(e))), Some("removed")));
+            html.push_str(&list(relation.added.iter().map(|e| format!("+ {}", escape(e))), Some("added")));
+        }
+        doc.section("Relations", true, &html);
+    }
+
+    let file_count = changes.added_files.len() + changes.removed_files.len() + changes.changed_files.len();
+    if file_count > 0 {
+        let mut html = list(changes.removed_files.iter().map(|p| format!("- {}", path(p))), Some("removed"));
+        html.push_str(&list(changes.added_files.iter().map(|p| format!("+ {}", path(p))), Some("added")));
+        html.push_str(&list(
+            changes.changed_files.iter().map(|f| format!("~ {} ({})", path(&f.path), escape(&f.changes.join(", ")))),
+            None,
+        ));
+        doc.section(&format!("Files ({})", file_count), false, &html);
+    }
+
+    for script in &changes.scripts {
+        let mut html = String::from("<pre>");
+        for line in &script.lines {
+            let class = if line.starts_with('+') { "added" } else { "removed" };
+            let _ = writeln!(html, "<span class=\"{}\">{}</span>", class, escape(line));
+        }
+        html.push_str("</pre>\n");
+        doc.section(&format!("Script {}", script.name), false, &html);
+    }
+    doc.finish()
+}
+
+/// Report of a sandboxed install test
+pub fn test(report: &TestReport) -> String {
+    let mut doc = Document::new(&report.package);
+    let mut html = String::from("<table>\n");
+    for step in &report.steps {
+        let (class, outcome) = if step.passed { ("added", "PASS") } else { ("removed", "FAIL") };
+        let log = step.log.as_ref().map_or(String::new(), |log| format!("<p>Log: <code>{}</code></p>", escape(&log.display().to_string())));
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"{}\">{}</td><th>{}</th><td><pre>{}</pre>{}</td></tr>",
+            class,
+            outcome,
+            escape(&step.name),
+            escape(&step.detail),
+            log
+        );
+    }
+    html.push_str("</table>\n");
+    if let Some(log_dir) = &report.log_dir {
+        let _ = writeln!(html, "<p>Logs: <code>{}</code></p>", escape(&log_dir.display().to_string()));
+    }
+    let heading = format!("Checks ({} of {} passed)", report.steps.len() - report.failures().count(), report.steps.len());
+    doc.section(&heading, true, &html);
+    doc.finish()
+}
+
+/// Render environment checks
+pub fn doctor(checks: &[Check]) -> String {
+    let mut doc = Document::new("rexeb doctor");
+    let mut html = String::from("<table>\n");
+    for check in checks {
+        let (class, outcome) = match check.status {
+            Status::Ok => ("added", "OK"),
+            Status::Warning => ("warning", "WARN"),
+            Status::Error => ("removed", "FAIL"),
+        };
+        let fix = check.fix.as_ref().map_or(String::new(), |fix| format!("<br><em>{}</em>", escape(fix)));
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"{}\">{}</td><th>{}</th><td>{}{}</td></tr>",
+            class,
+            outcome,
+            escape(check.name),
+            escape(&check.detail),
+            fix
+        );
+    }
+    html.push_str("</table>\n");
+    doc.section("Checks", true, &html);
+    doc.finish()
+}
+
+/// Render a package checked against its deb
+pub fn verify(result: &Verification) -> String {
+    let mut doc = Document::new(&format!("{} -> {}", result.deb, result.package));
+    let source = match result.source {
+        SourceCheck::Matches => "converted from this deb".to_string(),
+        SourceCheck::Differs { ref recorded } => format!("converted from another file (SHA-256 {})", escape(recorded)),
+        SourceCheck::NotRecorded => "not recorded".to_string(),
+    };
+    let mut rows = vec![("Source", source), ("Identical files", result.identical.to_string())];
+    for field in &result.fields {
+        let value = format!(
+            "{} -&gt; {}",
+            escape(field.old.as_deref().unwrap_or("(none)")),
+            escape(field.new.as_deref().unwrap_or("(none)"))
+        );
+        rows.push((field.field.as_str(), value));
+    }
+    doc.section("Summary", true, &table(&rows));
+
+    let file_count = result.moved.len() + result.altered.len() + result.dropped.len() + result.added.len();
+    if file_count > 0 {
+        let mut html = list(result.dropped.iter().map(|p| format!("- {}", path(p))), Some("removed"));
+        html.push_str(&list(
+            result.altered.iter().map(|f| format!("~ {} ({})", path(&f.path), escape(&f.changes.join(", ")))),
+            Some("warning"),
+        ));
+        html.push_str(&list(result.added.iter().map(|p| format!("+ {}", path(p))), Some("added")));
+        html.push_str(&list(result.moved.iter().map(|(from, to)| format!("{} -&gt; {}", path(from), path(to))), None));
+        doc.section(&format!("Files ({})", file_count), !result.dropped.is_empty() || !result.altered.is_empty(), &html);
+    }
+
+    if !result.dependencies.is_empty() {
+        let mut html = String::from("<table>\n");
+        for dependency in &result.dependencies {
+            let class = if dependency.outcome == "missing" { "removed" } else { "added" };
+            let _ = writeln!(
+                html,
+                "<tr><th>{}</th><td>{}</td><td class=\"{}\">{}</td></tr>",
+                escape(&dependency.debian),
+                escape(dependency.arch.as_deref().unwrap_or("(unmapped)")),
+                class,
+                escape(&dependency.outcome)
+            );
+        }
+        html.push_str("</table>\n");
+        doc.section("Dependencies", true, &html);
+    }
+
+    if !result.scripts_dropped.is_empty() {
+        doc.section(
+            "Scripts",
+            true,
+            &list(result.scripts_dropped.iter().map(|s| format!("- {}", escape(s))), Some("removed")),
+        );
+    }
+    doc.finish()
+}
+
+/// Render recorded conversions
+pub fn history(records: &[HistoryRecord]) -> String {
+    let mut doc = Document::new("Conversion history");
+    let mut html = String::from("<table>\n<tr><th>#</th><th>Package</th><th>Version</th><th>Converted</th><th>Installed</th><th>Output</th></tr>\n");
+    let date = |time: i64| {
+        chrono::DateTime::from_timestamp(time, 0).map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string()).unwrap_or_default()
+    };
+    for record in records {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            record.id,
+            escape(&record.name),
+            escape(&record.source_version),
+            date(record.created),
+            record.installed.map(date).unwrap_or_default(),
+            path(&record.output_path)
+        );
+    }
+    html.push_str("</table>\n");
+    doc.section(&format!("Conversions ({})", records.len()), true, &html);
+    doc.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analysis_html() {
+        let mut metadata = PackageMetadata::new("foo<bar>", "1.0");
+        metadata.files.push("/usr/bin/foo".into());
+        let report = AnalysisReport {
+            findings: vec![crate::analyzer::rules::Finding {
+                rule: "fhs-violation".into(),
+                severity: Severity::Warning,
+                message: "files in /srv & /opt".into(),
+            }],
+            unmapped_deps: vec!["libfoo1".into()],
+            ..Default::default()
+        };
+
+        let html = analysis(&metadata, &report, false);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>Analysis of foo&lt;bar&gt; 1.0-1</title>"));
+        assert!(html.contains("<details open><summary>Warnings (1)</summary>"));
+        assert!(html.contains("files in /srv &amp; /opt"));
+        assert!(html.contains("<li class=\"warning\">libfoo1</li>"));
+        assert!(html.contains("<details><summary>Files (1)</summary>"));
+        assert!(!html.contains("File verification"));
+        assert!(!html.contains("<script"));
+    }
+}
@@ -1,12 +1,31 @@
 //! Command-line interface for rexeb
 
+mod batch;
 mod commands;
+mod doctor;
+mod html;
 
 pub use commands::*;
 
 use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
+/// Exit codes, as assigned by [`crate::error::RexebError::exit_code`]
+const EXIT_CODES: &str = "\
+Exit codes:
+  0    success
+  1    any other failure
+  2    the command line was invalid
+  3    an answer was needed under --non-interactive
+  4    the input package could not be found or read
+  5    dependencies could not be resolved
+  6    building the package failed
+  7    conflicts with installed packages were detected
+  8    a package failed verification
+  9    pacman failed
+  10   findings failed the requested threshold
+  130  interrupted";
+
 /// Rexeb - A smarter, faster debtap alternative
 /// 
 /// Convert .deb packages to Arch Linux packages with intelligent
@@ -15,6 +34,7 @@ use std::path::PathBuf;
 #[command(name = "rexeb")]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
+#[command(after_long_help = EXIT_CODES)]
 pub struct Cli {
     /// Subcommand to execute
     #[command(subcommand)]
@@ -39,9 +59,19 @@ pub struct Cli {
     /// Use TUI interface
     #[arg(long, global = true)]
     pub tui: bool,
+
+    /// Also write logs to this file (format and level from the logging config)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
+    /// Fail with exit code 3 instead of asking or falling back to defaults
+    #[arg(long, global = true, env = "REXEB_NON_INTERACTIVE")]
+    pub non_interactive: bool,
 }
 
 /// Available commands
+// Parsed once per run, so boxing the large convert arguments gains nothing
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Convert a package to Arch Linux format
@@ -67,15 +97,49 @@ pub enum Commands {
 
     /// Clean cache and temporary files
     Clean(CleanArgs),
+
+    /// Manage the local repository of converted packages
+    Repo(RepoArgs),
+
+    /// Manage Debian to Arch package mappings
+    Map(MapArgs),
+
+    /// Show the differences between two packages
+    Diff(DiffArgs),
+
+    /// Check a converted package against the deb it came from
+    Verify(VerifyArgs),
+
+    /// Convert a package, install it in a sandbox and check that it works
+    Test(TestArgs),
+
+    /// List earlier conversions
+    List(ListArgs),
+
+    /// List or remove installed converted packages
+    Uninstall(UninstallArgs),
+
+    /// Reconvert packages whose origin offers a newer version
+    Upgrade(UpgradeArgs),
+
+    /// Generate man pages for rexeb and its subcommands
+    Man(ManArgs),
+
+    /// Check the environment and suggest fixes
+    Doctor(DoctorArgs),
 }
 
 /// Arguments for the convert command
 #[derive(Parser, Debug, Clone)]
 pub struct ConvertArgs {
-    /// Input package file(s)
+    /// Input package files, directories of .deb files or glob patterns; `-` reads standard input
     #[arg(required = true)]
     pub input: Vec<PathBuf>,
 
+    /// Look for .deb files in subdirectories of input directories too
+    #[arg(short, long)]
+    pub recursive: bool,
+
     /// Output directory (default: current directory)
     #[arg(short, long)]
     pub output: Option<PathBuf>,
@@ -84,7 +148,7 @@ pub struct ConvertArgs {
     #[arg(long)]
     pub skip_deps: bool,
 
-    /// Force conversion even with warnings
+    /// Force conversion even with warnings or a low feasibility score
     #[arg(short, long)]
     pub force: bool,
 
@@ -92,6 +156,10 @@ pub struct ConvertArgs {
     #[arg(short, long)]
     pub pkgbuild: bool,
 
+    /// Generate a single split PKGBUILD with this pkgbase for all inputs
+    #[arg(long, value_name = "PKGBASE")]
+    pub pkgbase: Option<String>,
+
     /// Skip interactive prompts (use defaults)
     #[arg(short = 'y', long)]
     pub yes: bool,
@@ -116,9 +184,80 @@ pub struct ConvertArgs {
     #[arg(long)]
     pub release: Option<String>,
 
+    /// Relocate the /usr payload below this prefix (e.g. /opt/{pkgname})
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    /// Name built packages (and PKGBUILD directories) by this template,
+    /// e.g. '{name}-{version}-{rel}-{arch}'; also {epoch} and {debian_version}
+    #[arg(long, value_name = "TEMPLATE")]
+    pub output_name: Option<String>,
+
+    /// URL the input package was downloaded from, or the sources.list line of its
+    /// apt repository (recorded in .PKGINFO; `rexeb upgrade` fetches from it)
+    #[arg(long)]
+    pub origin: Option<String>,
+
+    /// Turn Recommends into depends, optdepends or drop them
+    #[arg(long, value_name = "POLICY", value_parser = ["depends", "optdepends", "ignore"])]
+    pub recommends: Option<String>,
+
+    /// Turn unmapped dependencies into optdepends instead of failing installs
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Drop this Debian dependency (repeatable)
+    #[arg(long = "ignore-dep", value_name = "PACKAGE")]
+    pub ignore_deps: Vec<String>,
+
+    /// Run namcap on the built package
+    #[arg(long)]
+    pub namcap: bool,
+
+    /// Fail when namcap reports errors (implies --namcap)
+    #[arg(long)]
+    pub deny_namcap_errors: bool,
+
+    /// Simulate installing the built package with pacman and report what it would refuse
+    #[arg(long)]
+    pub simulate: bool,
+
+    /// Keep the deb's architecture even if the payload has no native code
+    #[arg(long)]
+    pub keep_arch: bool,
+
+    /// Always convert, ignoring cached results
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Review and edit the dependency mappings before building
+    #[arg(long)]
+    pub review: bool,
+
+    /// Write an HTML summary of the conversion to this file
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
     /// Output format
     #[arg(long, value_enum, default_value_t = OutputFormat::PkgTarZst)]
     pub format: OutputFormat,
+
+    /// How to report progress
+    #[arg(long, value_enum, default_value_t = ProgressFormat::Bars)]
+    pub progress_format: ProgressFormat,
+
+    /// Write progress events to this file or FIFO instead of standard output
+    #[arg(long, value_name = "FILE")]
+    pub progress_file: Option<PathBuf>,
+}
+
+/// How conversions report progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProgressFormat {
+    /// Progress bars on the terminal
+    Bars,
+    /// One JSON event per line, for front ends; implies no prompts
+    Ndjson,
 }
 
 /// Output format for converted packages
@@ -158,6 +297,10 @@ pub struct UpdateArgs {
     #[arg(short, long)]
     pub aur: bool,
 
+    /// Download the Arch repository databases
+    #[arg(short, long)]
+    pub repos: bool,
+
     /// Update all databases
     #[arg(short = 'A', long)]
     pub all: bool,
@@ -192,6 +335,8 @@ pub enum InfoFormat {
     Json,
     /// TOML output
     Toml,
+    /// Self-contained HTML report
+    Html,
 }
 
 /// Arguments for the search command
@@ -236,6 +381,182 @@ pub struct AnalyzeArgs {
     /// Output format
     #[arg(short, long, value_enum, default_value_t = InfoFormat::Pretty)]
     pub format: InfoFormat,
+
+    /// Exit with status 10 when findings at or above this severity exist
+    #[arg(long, value_enum)]
+    pub fail_on: Option<crate::analyzer::rules::Severity>,
+
+    /// Always analyze, ignoring cached reports
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Run preinst and postinst under strace in a sandbox and report what they do
+    #[arg(long)]
+    pub trace_scripts: bool,
+
+    /// Give traced scripts network access (default: sandbox.network)
+    #[arg(long, overrides_with = "no_network")]
+    pub network: bool,
+
+    /// Keep traced scripts off the network
+    #[arg(long)]
+    pub no_network: bool,
+}
+
+/// Arguments for the diff command
+#[derive(Parser, Debug)]
+pub struct DiffArgs {
+    /// Old package (.deb or .pkg.tar.*)
+    pub old: PathBuf,
+
+    /// New package (.deb or .pkg.tar.*)
+    pub new: PathBuf,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = InfoFormat::Pretty)]
+    pub format: InfoFormat,
+}
+
+/// Arguments for the verify command
+#[derive(Parser, Debug)]
+pub struct VerifyArgs {
+    /// The original .deb file
+    pub deb: PathBuf,
+
+    /// The package converted from it (.pkg.tar.*)
+    pub package: PathBuf,
+
+    /// Fail on any altered or dropped file, dropped script or missing dependency
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = InfoFormat::Pretty)]
+    pub format: InfoFormat,
+}
+
+/// Arguments for the test command
+#[derive(Parser, Debug)]
+pub struct TestArgs {
+    /// Package file to convert and test
+    pub input: PathBuf,
+
+    /// Skip dependency resolution
+    #[arg(long)]
+    pub skip_deps: bool,
+
+    /// Force conversion even with warnings or a low feasibility score
+    #[arg(short, long)]
+    pub force: bool,
+
+    /// Give the package network access in the sandbox (default: sandbox.network)
+    #[arg(long, overrides_with = "no_network")]
+    pub network: bool,
+
+    /// Keep the package off the network in the sandbox
+    #[arg(long)]
+    pub no_network: bool,
+
+    /// Bootstrap the sandbox from the current Arch snapshot instead of the cached one
+    #[arg(long)]
+    pub refresh_sandbox: bool,
+
+    /// Show the sandbox output while the test runs
+    #[arg(long)]
+    pub follow: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = InfoFormat::Pretty)]
+    pub format: InfoFormat,
+}
+
+/// Arguments for the list command
+#[derive(Parser, Debug)]
+pub struct ListArgs {
+    /// Only packages whose name contains this
+    pub name: Option<String>,
+
+    /// Only installed packages
+    #[arg(long, conflicts_with = "not_installed")]
+    pub installed: bool,
+
+    /// Only packages that were not installed
+    #[arg(long)]
+    pub not_installed: bool,
+
+    /// Only conversions on or after this date (YYYY-MM-DD)
+    #[arg(long)]
+    pub since: Option<chrono::NaiveDate>,
+
+    /// Show only the latest conversions
+    #[arg(short, long)]
+    pub limit: Option<usize>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = InfoFormat::Pretty)]
+    pub format: InfoFormat,
+}
+
+/// Arguments for the uninstall command
+#[derive(Parser, Debug)]
+pub struct UninstallArgs {
+    /// Packages to remove; without any, list the installed converted packages
+    pub packages: Vec<String>,
+
+    /// Skip confirmation prompts
+    #[arg(short = 'y', long)]
+    pub yes: bool,
+
+    /// Also remove dependencies nothing else needs (pacman -Rs)
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Keep the cached conversions of removed packages
+    #[arg(long)]
+    pub keep_cache: bool,
+
+    /// Pass additional flags to pacman
+    #[arg(last = true)]
+    pub pacman_args: Vec<String>,
+}
+
+/// Arguments for the upgrade command
+#[derive(Parser, Debug)]
+pub struct UpgradeArgs {
+    /// Packages to upgrade (default: every conversion with a known origin)
+    pub packages: Vec<String>,
+
+    /// Only show which packages have a newer version
+    #[arg(long)]
+    pub check: bool,
+
+    /// Install the upgraded packages
+    #[arg(short, long)]
+    pub install: bool,
+
+    /// Output directory (default: next to the previous conversion)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Skip confirmation prompts
+    #[arg(short = 'y', long)]
+    pub yes: bool,
+}
+
+/// Arguments for the man command
+#[derive(Parser, Debug)]
+pub struct ManArgs {
+    /// Write pages for every subcommand to this directory instead of printing rexeb(1)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// Arguments for the doctor command
+#[derive(Parser, Debug)]
+pub struct DoctorArgs {
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = InfoFormat::Pretty)]
+    pub format: InfoFormat,
 }
 
 /// Arguments for the install command
@@ -257,6 +578,18 @@ pub struct InstallArgs {
     #[arg(long)]
     pub asexplicit: bool,
 
+    /// Install through a throwaway local repository with `pacman -S`
+    #[arg(long)]
+    pub via_repo: bool,
+
+    /// Install AUR-only dependencies with this helper (`makepkg` builds them directly)
+    #[arg(long, value_name = "HELPER")]
+    pub aur_helper: Option<String>,
+
+    /// Do not install dependencies from the AUR
+    #[arg(long, conflicts_with = "aur_helper")]
+    pub no_aur: bool,
+
     /// Pass additional flags to pacman
     #[arg(last = true)]
     pub pacman_args: Vec<String>,
@@ -310,6 +643,10 @@ pub struct CleanArgs {
     #[arg(short, long)]
     pub temp: bool,
 
+    /// Clean the bootstrapped sandbox roots
+    #[arg(long)]
+    pub sandbox: bool,
+
     /// Clean everything
     #[arg(short, long)]
     pub all: bool,
@@ -319,10 +656,106 @@ pub struct CleanArgs {
     pub dry_run: bool,
 }
 
+/// Arguments for the repo command
+#[derive(Parser, Debug)]
+pub struct RepoArgs {
+    /// Repository subcommand
+    #[command(subcommand)]
+    pub command: RepoCommands,
+
+    /// Repository directory (overrides repo.path)
+    #[arg(long, global = true)]
+    pub repo_dir: Option<PathBuf>,
+
+    /// Repository name (overrides repo.name)
+    #[arg(long, global = true)]
+    pub repo_name: Option<String>,
+}
+
+/// Repository subcommands
+#[derive(Subcommand, Debug)]
+pub enum RepoCommands {
+    /// Add built packages to the repository
+    Add {
+        /// Package file(s) to add
+        #[arg(required = true)]
+        packages: Vec<PathBuf>,
+
+        /// Copy packages instead of moving them
+        #[arg(long)]
+        copy: bool,
+    },
+    /// Remove packages from the repository
+    Remove {
+        /// Package name(s) to remove
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// List packages in the repository
+    List,
+    /// Print the pacman.conf snippet enabling the repository
+    Conf,
+}
+
+/// Arguments for the map command
+#[derive(Parser, Debug)]
+pub struct MapArgs {
+    /// Mapping subcommand
+    #[command(subcommand)]
+    pub command: MapCommands,
+}
+
+/// Mapping subcommands
+#[derive(Subcommand, Debug)]
+pub enum MapCommands {
+    /// Map a Debian package to an Arch package
+    Add {
+        /// Debian package name
+        debian: String,
+        /// Arch package name
+        arch: String,
+        /// Confidence of the mapping (0.0 - 1.0)
+        #[arg(long, default_value_t = 1.0)]
+        confidence: f32,
+    },
+    /// Remove mappings
+    Remove {
+        /// Debian package name(s)
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// List mappings
+    List {
+        /// Only show mappings from this source
+        #[arg(long, value_enum)]
+        source: Option<crate::resolver::database::MappingSource>,
+    },
+    /// Write the saved mappings to a JSON file
+    Export {
+        /// Output file
+        file: PathBuf,
+    },
+    /// Add mappings from a JSON file written by `map export`
+    Import {
+        /// Input file
+        file: PathBuf,
+    },
+}
+
 impl Cli {
     /// Parse command line arguments
+    ///
+    /// Exits with [`USAGE_EXIT_CODE`](crate::error::USAGE_EXIT_CODE) on an
+    /// invalid command line, and successfully after `--help` or `--version`.
     pub fn parse_args() -> Self {
-        Self::parse()
+        Self::try_parse().unwrap_or_else(|e| {
+            let _ = e.print();
+            let code = match e.use_stderr() {
+                true => crate::error::USAGE_EXIT_CODE,
+                false => 0,
+            };
+            std::process::exit(code.into())
+        })
     }
 }
 
@@ -335,4 +768,33 @@ mod tests {
         use clap::CommandFactory;
         Cli::command().debug_assert();
     }
+
+    #[test]
+    fn test_non_interactive_flag() {
+        use clap::Parser;
+        let cli = Cli::try_parse_from(["rexeb", "install", "foo.deb", "--non-interactive"]).unwrap();
+        assert!(cli.non_interactive);
+        let error = crate::error::RexebError::InputRequired("installing needs confirmation".into());
+        assert_eq!(error.exit_code(), 3);
+    }
+
+    #[test]
+    fn test_fail_on_usage_error() {
+        use clap::Parser;
+        let usage = Cli::try_parse_from(["rexeb", "analyze", "foo.deb", "--fail-on", "eror"]).unwrap_err();
+        let findings = crate::error::RexebError::Findings("1 finding at or above error".into());
+        assert_ne!(usage.exit_code(), i32::from(findings.exit_code()));
+    }
+
+    #[test]
+    fn test_man_pages() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let args = ManArgs { output: Some(temp_dir.path().join("man1")) };
+        tokio::runtime::Runtime::new().unwrap().block_on(commands::execute_man(&args)).unwrap();
+
+        let page = std::fs::read_to_string(temp_dir.path().join("man1/rexeb-repo-add.1")).unwrap();
+        assert!(page.starts_with(".ie"));
+        assert!(page.contains("rexeb\\-repo\\-add"));
+        assert!(temp_dir.path().join("man1/rexeb.1").is_file());
+    }
 }
@@ -1,12 +1,13 @@
 //! Configuration management for rexeb
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 
 use crate::error::{RexebError, Result};
 
 /// Application configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     /// General settings
     #[serde(default)]
@@ -27,10 +28,26 @@ pub struct Config {
     /// Java-specific settings
     #[serde(default)]
     pub java: JavaConfig,
+
+    /// Local repository settings
+    #[serde(default)]
+    pub repo: RepoConfig,
+
+    /// Analyzer rule settings
+    #[serde(default)]
+    pub analyzer: AnalyzerConfig,
+
+    /// Sandbox settings
+    #[serde(default)]
+    pub sandbox: SandboxConfig,
+
+    /// Dependency corrections by package name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, PackageOverride>,
 }
 
 /// General configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct GeneralConfig {
     /// Cache directory
     pub cache_dir: Option<PathBuf>,
@@ -42,10 +59,13 @@ pub struct GeneralConfig {
     pub jobs: Option<usize>,
     /// Automatically accept prompts
     pub auto_yes: bool,
+    /// AUR helper installing AUR-only dependencies (`paru`, `yay`; unset uses makepkg)
+    pub aur_helper: Option<String>,
 }
 
 /// Conversion configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConversionConfig {
     /// Default output format
     pub default_format: String,
@@ -59,10 +79,50 @@ pub struct ConversionConfig {
     pub min_match_confidence: f32,
     /// Strip binaries
     pub strip_binaries: bool,
+    /// Handling of multiarch library dirs (relocate, ldconfig, keep)
+    pub multiarch_strategy: String,
+    /// Target Python version for site-packages (detected when unset)
+    pub python_version: Option<String>,
+    /// Byte-compile Python modules from .INSTALL
+    pub python_bytecompile: bool,
+    /// Rewrite script interpreters to their Arch paths
+    pub rewrite_shebangs: bool,
+    /// Drop Debian-only docs and recompress man/info pages
+    pub clean_docs: bool,
+    /// Drop AppArmor profiles and SELinux policy modules
+    pub strip_security_profiles: bool,
+    /// Relocate the `/usr` payload below this prefix (`{pkgname}` is expanded)
+    pub prefix: Option<String>,
+    /// Name of built packages without the extension, and of the directories
+    /// PKGBUILDs are written to (`{name}-{version}-{rel}-{arch}`)
+    pub output_name: Option<String>,
+    /// Handling of Recommends (depends, optdepends, ignore)
+    pub recommends_policy: String,
+    /// Run namcap on built packages
+    pub run_namcap: bool,
+    /// Fail the conversion when namcap reports errors
+    pub deny_namcap_errors: bool,
+    /// Simulate installing built packages with `pacman -U --print`
+    pub simulate_install: bool,
+    /// Emit packages without native code as `arch = any`
+    pub detect_arch_any: bool,
+    /// Ask which package to use when a fuzzy or AUR match scores below this
+    pub prompt_confidence: f32,
+    /// Debian dependencies dropped from every converted package
+    pub ignore_deps: Vec<String>,
+    /// Fuzzy match again when a mapping names a package that no longer exists
+    pub rematch_missing: bool,
+    /// Turn unmapped Depends into optdepends instead of shipping Debian names
+    pub lenient_deps: bool,
+    /// Rewrite systemd units using Debian accounts, `/etc/default` files or moved programs
+    pub fix_units: bool,
+    /// Refuse to convert without `--force` when the feasibility score is below this (0 disables)
+    pub min_feasibility: u8,
 }
 
 /// Network configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NetworkConfig {
     /// HTTP timeout in seconds
     pub timeout: u64,
@@ -72,15 +132,32 @@ pub struct NetworkConfig {
     pub aur_url: String,
     /// Enable offline mode
     pub offline: bool,
+    /// Arch mirror (`$repo` and `$arch` are substituted, like pacman's mirrorlist)
+    pub mirror: String,
+    /// Repositories whose databases `rexeb update` downloads
+    pub sync_repos: Vec<String>,
+    /// Curated mapping dataset fetched by `rexeb update --mappings`
+    ///
+    /// A `<url>.sha256` checksum must be published next to it.
+    pub mappings_url: Option<String>,
+    /// Keyring verifying the dataset's detached `<url>.sig` signature (with gpgv)
+    pub mappings_keyring: Option<PathBuf>,
+    /// NVD CVE API queried for bundled libraries by `rexeb analyze`
+    pub nvd_url: String,
+    /// NVD API key, raising the request rate limit
+    pub nvd_api_key: Option<String>,
 }
 
 /// Logging configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LoggingConfig {
     /// Log level
     pub level: String,
     /// Log file path
     pub file: Option<PathBuf>,
+    /// Format of the log file (text, json)
+    pub format: String,
     /// Enable colored output
     pub color: bool,
 }
@@ -96,16 +173,56 @@ pub struct JavaConfig {
     pub default_version: String,
 }
 
-impl Default for GeneralConfig {
-    fn default() -> Self {
-        Self {
-            cache_dir: None,
-            data_dir: None,
-            output_dir: None,
-            jobs: None,
-            auto_yes: false,
-        }
-    }
+/// Local repository configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepoConfig {
+    /// Repository name used in pacman.conf
+    pub name: String,
+    /// Repository directory (defaults to `<data_dir>/repo`)
+    pub path: Option<PathBuf>,
+}
+
+/// Sandbox configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SandboxConfig {
+    /// Give packages under test and traced scripts network access
+    ///
+    /// Setting up the sandbox (syncing databases, installing dependencies)
+    /// always has it.
+    pub network: bool,
+}
+
+/// Analyzer rule configuration (see [`crate::analyzer::rules`])
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyzerConfig {
+    /// Ids of rules that report nothing
+    pub disabled_rules: Vec<String>,
+    /// Severity overrides (`info`, `warning`, `error`) by rule id
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub severity: BTreeMap<String, String>,
+}
+
+/// Dependency corrections for one package (`[overrides."<name>"]`)
+///
+/// Entries use Arch syntax (`nss>=3.26`, `optdepends` style `name: reason`
+/// for `extra_optdepends`). Lists that are set replace the relations the
+/// deb declares; the `extra_` lists add to the resolved ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackageOverride {
+    /// Replacement for Depends and Pre-Depends
+    pub depends: Option<Vec<String>>,
+    /// Replacement for Conflicts and Breaks
+    pub conflicts: Option<Vec<String>>,
+    /// Replacement for Provides
+    pub provides: Option<Vec<String>>,
+    /// Dependencies added to the resolved ones
+    pub extra_depends: Vec<String>,
+    /// Optional dependencies added to the resolved ones
+    pub extra_optdepends: Vec<String>,
 }
 
 impl Default for ConversionConfig {
@@ -117,6 +234,28 @@ impl Default for ConversionConfig {
             keep_temp: false,
             min_match_confidence: 0.6,
             strip_binaries: true,
+            multiarch_strategy: "relocate".to_string(),
+            python_version: None,
+            python_bytecompile: false,
+            rewrite_shebangs: true,
+            clean_docs: false,
+            strip_security_profiles: false,
+            prefix: None,
+            output_name: None,
+            recommends_policy: "optdepends".to_string(),
+            run_namcap: false,
+            deny_namcap_errors: false,
+            simulate_install: false,
+            detect_arch_any: true,
+            prompt_confidence: 0.85,
+            ignore_deps: ["dpkg", "debconf", "install-info", "lsb-base", "init-system-helpers"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            rematch_missing: false,
+            lenient_deps: false,
+            fix_units: true,
+            min_feasibility: 50,
         }
     }
 }
@@ -128,15 +267,35 @@ impl Default for NetworkConfig {
             proxy: None,
             aur_url: "https://aur.archlinux.org/rpc".to_string(),
             offline: false,
+            mirror: "https://geo.mirror.pkgbuild.com/$repo/os/$arch".to_string(),
+            sync_repos: vec!["core".to_string(), "extra".to_string()],
+            mappings_url: None,
+            mappings_keyring: None,
+            nvd_url: "https://services.nvd.nist.gov/rest/json/cves/2.0".to_string(),
+            nvd_api_key: None,
         }
     }
 }
 
+impl NetworkConfig {
+    /// HTTP client honoring the timeout and proxy settings
+    pub fn http_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .user_agent(format!("{}/{}", crate::NAME, crate::VERSION))
+            .timeout(std::time::Duration::from_secs(self.timeout));
+        if let Some(ref proxy) = self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        Ok(builder.build()?)
+    }
+}
+
 impl Default for LoggingConfig {
     fn default() -> Self {
         Self {
             level: "info".to_string(),
             file: None,
+            format: "text".to_string(),
             color: true,
         }
     }
@@ -152,14 +311,11 @@ impl Default for JavaConfig {
     }
 }
 
-impl Default for Config {
+impl Default for RepoConfig {
     fn default() -> Self {
         Self {
-            general: GeneralConfig::default(),
-            conversion: ConversionConfig::default(),
-            network: NetworkConfig::default(),
-            logging: LoggingConfig::default(),
-            java: JavaConfig::default(),
+            name: "rexeb-local".to_string(),
+            path: None,
         }
     }
 }
@@ -228,26 +384,63 @@ impl Config {
             "general.output_dir" => self.general.output_dir.as_ref().map(|p| p.display().to_string()),
             "general.jobs" => self.general.jobs.map(|j| j.to_string()),
             "general.auto_yes" => Some(self.general.auto_yes.to_string()),
+            "general.aur_helper" => self.general.aur_helper.clone(),
             
             "conversion.default_format" => Some(self.conversion.default_format.clone()),
             "conversion.skip_deps" => Some(self.conversion.skip_deps.to_string()),
             "conversion.generate_pkgbuild" => Some(self.conversion.generate_pkgbuild.to_string()),
             "conversion.keep_temp" => Some(self.conversion.keep_temp.to_string()),
             "conversion.min_match_confidence" => Some(self.conversion.min_match_confidence.to_string()),
+            "conversion.multiarch_strategy" => Some(self.conversion.multiarch_strategy.clone()),
+            "conversion.python_version" => self.conversion.python_version.clone(),
+            "conversion.python_bytecompile" => Some(self.conversion.python_bytecompile.to_string()),
+            "conversion.rewrite_shebangs" => Some(self.conversion.rewrite_shebangs.to_string()),
+            "conversion.clean_docs" => Some(self.conversion.clean_docs.to_string()),
+            "conversion.strip_security_profiles" => Some(self.conversion.strip_security_profiles.to_string()),
+            "conversion.prefix" => self.conversion.prefix.clone(),
+            "conversion.output_name" => self.conversion.output_name.clone(),
+            "conversion.recommends_policy" => Some(self.conversion.recommends_policy.clone()),
+            "conversion.run_namcap" => Some(self.conversion.run_namcap.to_string()),
+            "conversion.deny_namcap_errors" => Some(self.conversion.deny_namcap_errors.to_string()),
+            "conversion.simulate_install" => Some(self.conversion.simulate_install.to_string()),
+            "conversion.detect_arch_any" => Some(self.conversion.detect_arch_any.to_string()),
+            "conversion.prompt_confidence" => Some(self.conversion.prompt_confidence.to_string()),
+            "conversion.ignore_deps" => Some(self.conversion.ignore_deps.join(",")),
+            "conversion.rematch_missing" => Some(self.conversion.rematch_missing.to_string()),
+            "conversion.lenient_deps" => Some(self.conversion.lenient_deps.to_string()),
+            "conversion.fix_units" => Some(self.conversion.fix_units.to_string()),
+            "conversion.min_feasibility" => Some(self.conversion.min_feasibility.to_string()),
             
             "network.timeout" => Some(self.network.timeout.to_string()),
             "network.proxy" => self.network.proxy.clone(),
             "network.aur_url" => Some(self.network.aur_url.clone()),
             "network.offline" => Some(self.network.offline.to_string()),
+            "network.mirror" => Some(self.network.mirror.clone()),
+            "network.sync_repos" => Some(self.network.sync_repos.join(",")),
+            "network.mappings_url" => self.network.mappings_url.clone(),
+            "network.mappings_keyring" => self.network.mappings_keyring.as_ref().map(|p| p.display().to_string()),
+            "network.nvd_url" => Some(self.network.nvd_url.clone()),
+            "network.nvd_api_key" => self.network.nvd_api_key.clone(),
             
             "logging.level" => Some(self.logging.level.clone()),
             "logging.file" => self.logging.file.as_ref().map(|p| p.display().to_string()),
+            "logging.format" => Some(self.logging.format.clone()),
             "logging.color" => Some(self.logging.color.to_string()),
             
             "java.conflict_strategy" => Some(self.java.conflict_strategy.clone()),
             "java.add_java_conflicts" => Some(self.java.add_java_conflicts.to_string()),
             "java.default_version" => Some(self.java.default_version.clone()),
             
+            "repo.name" => Some(self.repo.name.clone()),
+            "repo.path" => self.repo.path.as_ref().map(|p| p.display().to_string()),
+
+            "sandbox.network" => Some(self.sandbox.network.to_string()),
+
+            "analyzer.disabled_rules" => Some(self.analyzer.disabled_rules.join(",")),
+            _ if key.starts_with("analyzer.severity.") => {
+                self.analyzer.severity.get(&key["analyzer.severity.".len()..]).cloned()
+            }
+            
             _ => None,
         }
     }
@@ -274,6 +467,9 @@ impl Config {
                     RexebError::Config("Invalid boolean for auto_yes".into())
                 })?;
             }
+            "general.aur_helper" => {
+                self.general.aur_helper = if value.is_empty() { None } else { Some(value.to_string()) };
+            }
             
             "conversion.default_format" => {
                 self.conversion.default_format = value.to_string();
@@ -293,6 +489,104 @@ impl Config {
                     RexebError::Config("Invalid number for min_match_confidence".into())
                 })?;
             }
+            "conversion.min_feasibility" => {
+                self.conversion.min_feasibility = value
+                    .parse()
+                    .ok()
+                    .filter(|score| *score <= 100)
+                    .ok_or_else(|| RexebError::Config("Invalid score for min_feasibility (0-100)".into()))?;
+            }
+            "conversion.prompt_confidence" => {
+                self.conversion.prompt_confidence = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid number for prompt_confidence".into())
+                })?;
+            }
+            "conversion.rematch_missing" => {
+                self.conversion.rematch_missing = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for rematch_missing".into())
+                })?;
+            }
+            "conversion.lenient_deps" => {
+                self.conversion.lenient_deps = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for lenient_deps".into())
+                })?;
+            }
+            "conversion.fix_units" => {
+                self.conversion.fix_units = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for fix_units".into())
+                })?;
+            }
+            "conversion.ignore_deps" => {
+                self.conversion.ignore_deps = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|d| !d.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
+            "conversion.multiarch_strategy" => {
+                crate::converter::MultiarchStrategy::from_config(value)?;
+                self.conversion.multiarch_strategy = value.to_string();
+            }
+            "conversion.recommends_policy" => {
+                crate::models::RecommendsPolicy::from_config(value)?;
+                self.conversion.recommends_policy = value.to_string();
+            }
+            "conversion.run_namcap" => {
+                self.conversion.run_namcap = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for run_namcap".into())
+                })?;
+            }
+            "conversion.deny_namcap_errors" => {
+                self.conversion.deny_namcap_errors = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for deny_namcap_errors".into())
+                })?;
+            }
+            "conversion.simulate_install" => {
+                self.conversion.simulate_install = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for simulate_install".into())
+                })?;
+            }
+            "conversion.detect_arch_any" => {
+                self.conversion.detect_arch_any = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for detect_arch_any".into())
+                })?;
+            }
+            "conversion.python_version" => {
+                self.conversion.python_version = if value.is_empty() { None } else { Some(value.to_string()) };
+            }
+            "conversion.prefix" => {
+                if !value.is_empty() {
+                    crate::converter::PrefixRelocator::new(value, "pkgname")?;
+                }
+                self.conversion.prefix = if value.is_empty() { None } else { Some(value.to_string()) };
+            }
+            "conversion.output_name" => {
+                if !value.is_empty() {
+                    crate::models::PackageMetadata::new("pkgname", "1.0").output_name(value)?;
+                }
+                self.conversion.output_name = if value.is_empty() { None } else { Some(value.to_string()) };
+            }
+            "conversion.python_bytecompile" => {
+                self.conversion.python_bytecompile = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for python_bytecompile".into())
+                })?;
+            }
+            "conversion.rewrite_shebangs" => {
+                self.conversion.rewrite_shebangs = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for rewrite_shebangs".into())
+                })?;
+            }
+            "conversion.clean_docs" => {
+                self.conversion.clean_docs = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for clean_docs".into())
+                })?;
+            }
+            "conversion.strip_security_profiles" => {
+                self.conversion.strip_security_profiles = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for strip_security_profiles".into())
+                })?;
+            }
             
             "network.timeout" => {
                 self.network.timeout = value.parse().map_err(|_| {
@@ -310,6 +604,29 @@ impl Config {
                     RexebError::Config("Invalid boolean for offline".into())
                 })?;
             }
+            "network.mirror" => {
+                self.network.mirror = value.to_string();
+            }
+            "network.mappings_url" => {
+                self.network.mappings_url = if value.is_empty() { None } else { Some(value.to_string()) };
+            }
+            "network.mappings_keyring" => {
+                self.network.mappings_keyring = if value.is_empty() { None } else { Some(PathBuf::from(value)) };
+            }
+            "network.nvd_url" => {
+                self.network.nvd_url = value.to_string();
+            }
+            "network.nvd_api_key" => {
+                self.network.nvd_api_key = if value.is_empty() { None } else { Some(value.to_string()) };
+            }
+            "network.sync_repos" => {
+                self.network.sync_repos = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|r| !r.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
             
             "logging.level" => {
                 self.logging.level = value.to_string();
@@ -317,6 +634,12 @@ impl Config {
             "logging.file" => {
                 self.logging.file = if value.is_empty() { None } else { Some(PathBuf::from(value)) };
             }
+            "logging.format" => {
+                if !["text", "json"].contains(&value) {
+                    return Err(RexebError::Config(format!("Invalid log format: {} (expected text or json)", value)));
+                }
+                self.logging.format = value.to_string();
+            }
             "logging.color" => {
                 self.logging.color = value.parse().map_err(|_| {
                     RexebError::Config("Invalid boolean for color".into())
@@ -335,6 +658,44 @@ impl Config {
                 self.java.default_version = value.to_string();
             }
             
+            "repo.name" => {
+                self.repo.name = value.to_string();
+            }
+            "repo.path" => {
+                self.repo.path = if value.is_empty() { None } else { Some(PathBuf::from(value)) };
+            }
+
+            "sandbox.network" => {
+                self.sandbox.network = value.parse().map_err(|_| {
+                    RexebError::Config("Invalid boolean for network".into())
+                })?;
+            }
+
+            "analyzer.disabled_rules" => {
+                let rules: Vec<String> = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .map(String::from)
+                    .collect();
+                if let Some(unknown) = rules.iter().find(|id| !crate::analyzer::rules::is_known(id)) {
+                    return Err(RexebError::Config(format!("Unknown analyzer rule: {}", unknown)));
+                }
+                self.analyzer.disabled_rules = rules;
+            }
+            _ if key.starts_with("analyzer.severity.") => {
+                let id = &key["analyzer.severity.".len()..];
+                if !crate::analyzer::rules::is_known(id) {
+                    return Err(RexebError::Config(format!("Unknown analyzer rule: {}", id)));
+                }
+                if value.is_empty() {
+                    self.analyzer.severity.remove(id);
+                } else {
+                    crate::analyzer::rules::Severity::from_config(value)?;
+                    self.analyzer.severity.insert(id.to_string(), value.to_string());
+                }
+            }
+            
             _ => {
                 return Err(RexebError::Config(format!("Unknown configuration key: {}", key)));
             }
@@ -343,6 +704,15 @@ impl Config {
         Ok(())
     }
 
+    /// Overrides for a package, by its Arch name or else its Debian name
+    pub fn package_override(&self, metadata: &crate::models::PackageMetadata) -> Option<&PackageOverride> {
+        metadata
+            .arch_name
+            .as_ref()
+            .and_then(|name| self.overrides.get(name))
+            .or_else(|| self.overrides.get(&metadata.name))
+    }
+
     /// Get the cache directory
     pub fn cache_dir(&self) -> PathBuf {
         self.general.cache_dir.clone().unwrap_or_else(|| {
@@ -360,6 +730,11 @@ impl Config {
                 .join("rexeb")
         })
     }
+
+    /// Get the local repository directory
+    pub fn repo_dir(&self) -> PathBuf {
+        self.repo.path.clone().unwrap_or_else(|| self.data_dir().join("repo"))
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,70 @@
+//! Detection of architecture-independent payloads
+//!
+//! Many vendor debs are marked amd64 although they only ship scripts,
+//! data or fonts. Such packages are emitted as `arch = any` so they
+//! install on every Arch port.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::models::Architecture;
+
+use super::classify_file;
+
+/// Finds native code in a payload
+pub struct ArchDetector;
+
+impl ArchDetector {
+    /// First architecture-specific file below `root`, if any
+    pub fn find_native(root: &Path) -> Result<Option<PathBuf>> {
+        for entry in walkdir::WalkDir::new(root) {
+            let entry = entry?;
+            if entry.file_type().is_file() && classify_file(entry.path())?.is_native() {
+                return Ok(Some(entry.path().to_path_buf()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether the payload below `root` contains no native code
+    pub fn is_arch_independent(root: &Path) -> Result<bool> {
+        Ok(Self::find_native(root)?.is_none())
+    }
+
+    /// Architecture a conversion gives a payload declared as `declared`
+    ///
+    /// With `detect_any`, payloads without native code become `any`. An
+    /// unreadable payload keeps its architecture; it fails later anyway.
+    pub fn package_arch(declared: Architecture, root: &Path, detect_any: bool) -> Architecture {
+        match detect_any && declared != Architecture::Any && Self::is_arch_independent(root).unwrap_or(false) {
+            true => Architecture::Any,
+            false => declared,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_native_code() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::create_dir_all(root.join("usr/share/fonts")).unwrap();
+        fs::write(root.join("usr/bin/tool"), "#!/bin/sh\necho hi\n").unwrap();
+        fs::write(root.join("usr/share/fonts/a.ttf"), [0u8, 1, 0, 0]).unwrap();
+        fs::write(root.join("empty"), "").unwrap();
+        assert!(ArchDetector::is_arch_independent(root).unwrap());
+
+        fs::create_dir_all(root.join("usr/lib")).unwrap();
+        fs::write(root.join("usr/lib/libfoo.so.1"), b"\x7fELF\x02\x01\x01").unwrap();
+        assert_eq!(
+            ArchDetector::find_native(root).unwrap(),
+            Some(root.join("usr/lib/libfoo.so.1"))
+        );
+    }
+}
@@ -2,6 +2,7 @@
 //!
 //! Creates .pkg.tar.zst packages from extracted files and metadata
 
+use std::collections::HashMap;
 use std::fs::{self, File};
 use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
@@ -9,10 +10,16 @@ use std::path::{Path, PathBuf};
 use tar::Builder as TarBuilder;
 
 use crate::cli::OutputFormat;
+use crate::config::ConversionConfig;
 use crate::error::{RexebError, Result};
-use crate::models::PackageMetadata;
+use crate::models::{Architecture, PackageMetadata};
+use crate::progress::{Progress, Stage};
 
-use super::InstallScriptGenerator;
+use super::{
+    usrmerge_path, ArchDetector, ConversionReport, DesktopFixer, DocCleaner, InstallHook, InstallScriptGenerator, KernelModules,
+    MtreeWriter, MultiarchRemapper, MultiarchStrategy, PrefixRelocator, PythonBytecode, SecurityProfiles, ShebangRewriter, Templates, UnitFixer,
+    DEFAULT_PYTHON_VERSION,
+};
 
 /// Package builder for creating Arch Linux packages
 pub struct PackageConverter {
@@ -20,6 +27,16 @@ pub struct PackageConverter {
     metadata: PackageMetadata,
     /// Path to extracted data files
     data_dir: PathBuf,
+    /// Conversion settings
+    options: ConversionConfig,
+    /// User templates for generated files
+    templates: Templates,
+    /// Progress reporting
+    progress: Progress,
+    /// Architecture replaced by `any` because the payload has no native code
+    replaced_arch: Option<Architecture>,
+    /// Timestamp used for build dates and all archived mtimes
+    source_date_epoch: i64,
 }
 
 impl PackageConverter {
@@ -31,44 +48,145 @@ impl PackageConverter {
             return Err(RexebError::file_not_found(&data_dir));
         }
 
-        Ok(Self { metadata, data_dir })
+        let source_date_epoch = source_date_epoch(&data_dir)?;
+
+        Ok(Self {
+            metadata,
+            data_dir,
+            options: ConversionConfig::default(),
+            templates: Templates::default(),
+            progress: Progress::default(),
+            replaced_arch: None,
+            source_date_epoch,
+        })
     }
 
-    /// Build the Arch Linux package
-    pub fn build(&self, output_dir: &Path, format: OutputFormat) -> Result<PathBuf> {
-        let package_name = format!(
-            "{}-{}-{}.{}",
-            self.metadata.effective_name(),
-            self.metadata.version,
-            self.metadata.release,
-            self.metadata.arch.to_arch_name()
-        );
+    /// Use the given conversion settings
+    pub fn with_config(mut self, options: &ConversionConfig) -> Self {
+        self.options = options.clone();
+        if let Some(ref prefix) = options.prefix {
+            self.metadata.install_prefix = Some(prefix.replace("{pkgname}", self.metadata.effective_name()));
+        }
+        let arch = ArchDetector::package_arch(self.metadata.arch, &self.data_dir, options.detect_arch_any);
+        if arch != self.metadata.arch {
+            self.replaced_arch = Some(self.metadata.arch);
+            self.metadata.arch = arch;
+        }
+        self
+    }
+
+    /// Use the given user templates
+    pub fn with_templates(mut self, templates: Templates) -> Self {
+        self.templates = templates;
+        self
+    }
 
-        let output_path = output_dir.join(format!("{}.{}", package_name, format.extension()));
+    /// Report per-file progress while building
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Build the Arch Linux package
+    pub fn build(&self, output_dir: &Path, format: OutputFormat) -> Result<ConversionReport> {
+        let file_name = match self.options.output_name {
+            Some(ref template) => format!("{}.{}", self.metadata.output_name(template)?, format.extension()),
+            None => self.metadata.package_filename(format.extension()),
+        };
+        let output_path = output_dir.join(file_name);
 
         // Create temporary directory for package contents
         let temp_dir = tempfile::TempDir::new()?;
         let pkg_root = temp_dir.path();
+        let mut report = ConversionReport::default();
+        for dep in self.metadata.dependencies.values().flatten() {
+            for dep in std::iter::once(dep).chain(&dep.alternatives).filter(|d| d.missing) {
+                report.warn(format!(
+                    "{} is mapped to {}, which no repository or AUR package provides",
+                    dep.debian_name,
+                    dep.effective_name()
+                ));
+            }
+        }
+        if let Some(arch) = self.replaced_arch {
+            report.change(format!(
+                "Payload contains no native code, using arch=any instead of {}",
+                arch.to_arch_name()
+            ));
+        }
+
+        // Copy data files
+        self.copy_data_files(pkg_root, &mut report)?;
+
+        // Fix up the payload layout for Arch
+        self.progress.stage(Stage::Processing);
+        let hooks = self.run_passes(pkg_root, &mut report)?;
 
         // Create .BUILDINFO
+        self.progress.stage(Stage::Metadata);
         self.create_buildinfo(pkg_root)?;
 
         // Create .PKGINFO
         self.create_pkginfo(pkg_root)?;
 
         // Create .INSTALL if there are maintainer scripts
-        self.create_install_script(pkg_root)?;
-
-        // Copy data files
-        self.copy_data_files(pkg_root)?;
+        self.create_install_script(pkg_root, &hooks)?;
 
         // Create .MTREE (file metadata tree) - MUST be after all files are in place
+        normalize_mtimes(pkg_root, self.source_date_epoch)?;
         self.create_mtree(pkg_root)?;
 
         // Build the tar archive with compression
         self.create_archive(&output_path, pkg_root, format)?;
+        self.progress.stage(Stage::Done);
 
-        Ok(output_path)
+        report.output_path = output_path;
+        Ok(report)
+    }
+
+    /// Run the payload conversion passes over the package root
+    ///
+    /// Returns extra commands the passes want in the .INSTALL script.
+    fn run_passes(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<Vec<InstallHook>> {
+        let mut hooks = Vec::new();
+
+        let strategy = MultiarchStrategy::from_config(&self.options.multiarch_strategy)?;
+        MultiarchRemapper::new(strategy, self.metadata.effective_name()).apply(pkg_root, report)?;
+
+        let python_version = self.options.python_version.clone()
+            .or_else(PythonBytecode::detect_version)
+            .unwrap_or_else(|| DEFAULT_PYTHON_VERSION.to_string());
+        let mut python_modules =
+            PythonBytecode::new(python_version, self.options.python_bytecompile).apply(pkg_root, report)?;
+
+        if self.options.rewrite_shebangs {
+            ShebangRewriter::new().apply(pkg_root, report)?;
+        }
+
+        if self.options.clean_docs {
+            DocCleaner::new(self.metadata.effective_name()).apply(pkg_root, report)?;
+        }
+
+        if self.options.strip_security_profiles {
+            SecurityProfiles::detect(pkg_root)?.strip(pkg_root, report)?;
+        }
+
+        // After the passes that move files, so launchers follow them
+        DesktopFixer::new().apply(pkg_root, report)?;
+        UnitFixer::new(self.options.fix_units).apply(pkg_root, report)?;
+        KernelModules::detect(pkg_root)?.apply(report);
+
+        // Last, so the other passes see the regular /usr layout
+        if let Some(ref prefix) = self.metadata.install_prefix {
+            let relocator = PrefixRelocator::new(prefix, self.metadata.effective_name())?;
+            relocator.apply(pkg_root, report)?;
+            python_modules = python_modules.iter().map(|path| relocator.relocated(pkg_root, path)).collect();
+        }
+
+        // Byte-compile the modules where they are installed
+        hooks.extend(PythonBytecode::install_hooks(&python_modules, report));
+
+        Ok(hooks)
     }
 
     /// Create .BUILDINFO file
@@ -97,7 +215,7 @@ impl PackageConverter {
         lines.push(format!("pkgbuild_sha256sum = {}", &hash_hex[..32])); // Truncate to reasonable length
 
         lines.push(format!("packager = {} (converted by rexeb)", self.metadata.maintainer.as_deref().unwrap_or("Unknown")));
-        lines.push(format!("builddate = {}", chrono::Utc::now().timestamp()));
+        lines.push(format!("builddate = {}", self.source_date_epoch));
         lines.push("builddir = /tmp/rexeb".to_string());
         lines.push("startdir = /tmp/rexeb".to_string());
         lines.push("buildtool = rexeb".to_string());
@@ -115,125 +233,29 @@ impl PackageConverter {
     }
 
     /// Create .PKGINFO file
+    ///
+    /// The control file's Installed-Size is an estimate at best, so the
+    /// size is measured from the package root.
     fn create_pkginfo(&self, pkg_root: &Path) -> Result<()> {
         let pkginfo_path = pkg_root.join(".PKGINFO");
-        let content = self.metadata.to_pkginfo();
+        let mut metadata = self.metadata.clone();
+        metadata.installed_size = installed_size(pkg_root)?;
+        metadata.build_date = Some(self.source_date_epoch);
+        let content = metadata.to_pkginfo();
         fs::write(pkginfo_path, content)?;
         Ok(())
     }
 
     /// Create .MTREE file (file metadata)
     fn create_mtree(&self, pkg_root: &Path) -> Result<()> {
-        let mtree_path = pkg_root.join(".MTREE");
-
-        // Generate MTREE content - must use #mtree header
-        let mut mtree_content = String::new();
-        mtree_content.push_str("#mtree\n");
-        mtree_content.push_str("/set type=file uid=0 gid=0 mode=644\n");
-
-        // Add special files to MTREE (excluding .MTREE itself - can't hash itself)
-        let special_files = [".BUILDINFO", ".PKGINFO", ".INSTALL"];
-        for filename in special_files {
-            let path = pkg_root.join(filename);
-            if path.exists() {
-                if let Ok(metadata) = path.metadata() {
-                    let size = metadata.len();
-                    // Generate a simple SHA256 for the file
-                    if let Ok(content) = std::fs::read(&path) {
-                        use sha2::{Sha256, Digest};
-                        let hash = Sha256::new().chain_update(&content).finalize();
-                        let hash_hex = hex::encode(hash);
-                        mtree_content.push_str(&format!(
-                            "./{} time=0 size={} sha256digest={}\n",
-                            filename, size, hash_hex
-                        ));
-                    }
-                }
-            }
-        }
-
-        mtree_content.push_str("/set mode=755\n");
-
-        // Add data files from pkg_root (all files except special ones)
-        for entry in walkdir::WalkDir::new(pkg_root) {
-            let entry = entry?;
-            if let Ok(rel_path) = entry.path().strip_prefix(pkg_root) {
-                if rel_path.as_os_str().is_empty() {
-                    continue;
-                }
-
-                let path_str = rel_path.to_string_lossy();
-                
-                // Skip special files (already handled above)
-                if path_str.starts_with(".BUILDINFO")
-                    || path_str.starts_with(".PKGINFO")
-                    || path_str.starts_with(".MTREE")
-                    || path_str.starts_with(".INSTALL")
-                {
-                    continue;
-                }
-
-                let metadata = entry.metadata()?;
-
-                let file_type = if metadata.is_dir() {
-                    "dir"
-                } else if metadata.is_file() {
-                    "file"
-                } else if metadata.file_type().is_symlink() {
-                    "link"
-                } else {
-                    continue;
-                };
-
-                #[cfg(unix)]
-                let mode = {
-                    use std::os::unix::fs::PermissionsExt;
-                    metadata.permissions().mode() & 0o7777
-                };
-                #[cfg(not(unix))]
-                let mode = if metadata.is_dir() { 755 } else { 644 };
-
-                if metadata.is_dir() {
-                    mtree_content.push_str(&format!(
-                        "./{} time=0 mode={:o} type={}\n",
-                        path_str, mode, file_type
-                    ));
-                } else if metadata.is_file() {
-                    let size = metadata.len();
-                    // Generate SHA256 for regular files
-                    if let Ok(content) = std::fs::read(entry.path()) {
-                        use sha2::{Sha256, Digest};
-                        let hash = Sha256::new().chain_update(&content).finalize();
-                        let hash_hex = hex::encode(hash);
-                        mtree_content.push_str(&format!(
-                            "./{} time=0 size={} mode={:o} type={} sha256digest={}\n",
-                            path_str, size, mode, file_type, hash_hex
-                        ));
-                    }
-                } else if metadata.file_type().is_symlink() {
-                    #[cfg(unix)]
-                    if let Ok(target) = std::fs::read_link(entry.path()) {
-                        mtree_content.push_str(&format!(
-                            "./{} time=0 mode={:o} type={} link={}\n",
-                            path_str, mode, file_type, target.display()
-                        ));
-                    }
-                }
-            }
-        }
-
-        // Compress MTREE with gzip
-        let file = File::create(&mtree_path)?;
-        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
-        encoder.write_all(mtree_content.as_bytes())?;
-        encoder.finish()?;
-
-        Ok(())
+        MtreeWriter::from_dir(pkg_root)?.write(&pkg_root.join(".MTREE"))
     }
 
     /// Create .INSTALL file from maintainer scripts
-    fn create_install_script(&self, pkg_root: &Path) -> Result<()> {
-        let generator = InstallScriptGenerator::new(&self.metadata);
+    fn create_install_script(&self, pkg_root: &Path, hooks: &[InstallHook]) -> Result<()> {
+        let generator = InstallScriptGenerator::new(&self.metadata)
+            .with_hooks(hooks)
+            .with_templates(&self.templates);
         
         if let Some(content) = generator.generate()? {
             let install_path = pkg_root.join(".INSTALL");
@@ -244,17 +266,54 @@ impl PackageConverter {
     }
 
     /// Copy data files to package root
-    fn copy_data_files(&self, pkg_root: &Path) -> Result<()> {
+    ///
+    /// Paths below the usrmerge symlink directories (`/bin`, `/lib`, ...)
+    /// are moved into `/usr`. When both a legacy and a `/usr` path exist,
+    /// the `/usr` one wins.
+    ///
+    /// Hardlinked files are linked again in the package root instead of
+    /// being copied once per name.
+    fn copy_data_files(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        let mut merged = 0;
+        let mut linked: HashMap<(u64, u64), PathBuf> = HashMap::new();
+        let total = count_entries(&self.data_dir);
+        let mut copied = 0;
+
         for entry in walkdir::WalkDir::new(&self.data_dir) {
             let entry = entry?;
             let source = entry.path();
             
             if let Ok(rel_path) = source.strip_prefix(&self.data_dir) {
                 if rel_path.as_os_str().is_empty() {
+                    self.progress.update(Stage::Copying, 0, Some(total), None::<String>);
                     continue;
                 }
 
-                let dest = pkg_root.join(rel_path);
+                copied += 1;
+                self.progress.update(Stage::Copying, copied, Some(total), Some(rel_path.to_string_lossy()));
+
+                let merged_path = usrmerge_path(rel_path);
+                let dest = pkg_root.join(merged_path.as_deref().unwrap_or(rel_path));
+
+                if !entry.file_type().is_dir() && dest.symlink_metadata().is_ok() {
+                    if merged_path.is_some() {
+                        report.warn(format!(
+                            "usrmerge collision: /{} dropped in favour of /{}",
+                            rel_path.display(),
+                            dest.strip_prefix(pkg_root).unwrap_or(&dest).display()
+                        ));
+                        continue;
+                    }
+                    report.warn(format!(
+                        "usrmerge collision: /{} replaces a file from a legacy directory",
+                        rel_path.display()
+                    ));
+                    fs::remove_file(&dest)?;
+                }
+
+                if merged_path.is_some() && !entry.file_type().is_dir() {
+                    merged += 1;
+                }
                 
                 if entry.file_type().is_dir() {
                     fs::create_dir_all(&dest)?;
@@ -262,13 +321,24 @@ impl PackageConverter {
                     if let Some(parent) = dest.parent() {
                         fs::create_dir_all(parent)?;
                     }
-                    fs::copy(source, &dest)?;
+                    match hardlink_key(source)? {
+                        Some(key) => match linked.get(&key) {
+                            Some(first) => fs::hard_link(first, &dest)?,
+                            None => {
+                                fs::copy(source, &dest)?;
+                                linked.insert(key, dest.clone());
+                            }
+                        },
+                        None => {
+                            fs::copy(source, &dest)?;
+                        }
+                    }
                 } else if entry.file_type().is_symlink() {
                     #[cfg(unix)]
                     {
                         let target = fs::read_link(source)?;
-                        if dest.exists() || dest.symlink_metadata().is_ok() {
-                            fs::remove_file(&dest)?;
+                        if let Some(parent) = dest.parent() {
+                            fs::create_dir_all(parent)?;
                         }
                         std::os::unix::fs::symlink(target, &dest)?;
                     }
@@ -276,6 +346,10 @@ impl PackageConverter {
             }
         }
 
+        if merged > 0 {
+            report.change(format!("Moved {} entries from legacy directories into /usr (usrmerge)", merged));
+        }
+
         Ok(())
     }
 
@@ -306,20 +380,31 @@ impl PackageConverter {
     }
 
     /// Add files to tar archive with proper root ownership
+    ///
+    /// Additional names of a hardlinked file are stored as hardlink entries
+    /// pointing at the first name, like bsdtar does.
     fn add_package_files<W: Write>(&self, tar: &mut TarBuilder<W>, pkg_root: &Path) -> Result<()> {
+        let mut linked: HashMap<(u64, u64), PathBuf> = HashMap::new();
+        let total = count_entries(pkg_root);
+        let mut added = 0;
+        self.progress.update(Stage::Archiving, 0, Some(total), None::<String>);
+
         // Add special files first (in official Arch package order)
         let special_files = [".BUILDINFO", ".MTREE", ".PKGINFO", ".INSTALL"];
 
         for filename in special_files {
             let path = pkg_root.join(filename);
             if path.exists() {
+                added += 1;
+                self.progress.update(Stage::Archiving, added, Some(total), Some(filename));
                 self.append_file_with_root_owner(tar, &path, Path::new(filename))?;
             }
         }
 
-        // Add data files
+        // Add data files, sorted so the archive does not depend on the filesystem
         for entry in walkdir::WalkDir::new(pkg_root)
             .min_depth(1)
+            .sort_by_file_name()
             .into_iter()
             .filter_entry(|e| {
                 let name = e.file_name().to_string_lossy();
@@ -337,7 +422,18 @@ impl PackageConverter {
                     continue;
                 }
 
+                added += 1;
+                self.progress.update(Stage::Archiving, added, Some(total), Some(rel_path.to_string_lossy()));
+
                 if entry.file_type().is_file() {
+                    let key = hardlink_key(path)?;
+                    if let Some(first) = key.and_then(|k| linked.get(&k)) {
+                        self.append_hardlink_with_root_owner(tar, path, rel_path, first)?;
+                        continue;
+                    }
+                    if let Some(key) = key {
+                        linked.insert(key, rel_path.to_path_buf());
+                    }
                     self.append_file_with_root_owner(tar, path, rel_path)?;
                 } else if entry.file_type().is_dir() {
                     self.append_dir_with_root_owner(tar, path, rel_path)?;
@@ -354,6 +450,25 @@ impl PackageConverter {
         Ok(())
     }
 
+    /// Header for an entry owned by root with the normalized mtime
+    ///
+    /// Only permission bits are kept from `mode`.
+    fn root_header(&self, entry_type: tar::EntryType, mode: u32, size: u64) -> Result<tar::Header> {
+        let mut header = tar::Header::new_gnu();
+
+        header.set_size(size);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_username("root")?;
+        header.set_groupname("root")?;
+        header.set_mtime(self.source_date_epoch.max(0) as u64);
+        header.set_mode(mode & 0o7777);
+        header.set_entry_type(entry_type);
+        header.set_cksum();
+
+        Ok(header)
+    }
+
     /// Append a file to tar with root ownership (uid=0, gid=0)
     fn append_file_with_root_owner<W: Write>(
         &self,
@@ -362,29 +477,11 @@ impl PackageConverter {
         name: &Path,
     ) -> Result<()> {
         let metadata = path.metadata()?;
-        let mut header = tar::Header::new_gnu();
-        
-        header.set_size(metadata.len());
-        header.set_uid(0);
-        header.set_gid(0);
-        header.set_mtime(metadata.modified()?.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs());
-        
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            header.set_mode(metadata.permissions().mode());
-        }
-        #[cfg(not(unix))]
-        {
-            header.set_mode(0o644);
-        }
-        
-        header.set_entry_type(tar::EntryType::Regular);
-        header.set_cksum();
-        
+        let mut header = self.root_header(tar::EntryType::Regular, file_mode(&metadata, 0o644), metadata.len())?;
+
         let file = File::open(path)?;
         tar.append_data(&mut header, name, file)?;
-        
+
         Ok(())
     }
 
@@ -396,26 +493,8 @@ impl PackageConverter {
         name: &Path,
     ) -> Result<()> {
         let metadata = path.metadata()?;
-        let mut header = tar::Header::new_gnu();
-        
-        header.set_size(0);
-        header.set_uid(0);
-        header.set_gid(0);
-        header.set_mtime(metadata.modified()?.duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs());
-        
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            header.set_mode(metadata.permissions().mode());
-        }
-        #[cfg(not(unix))]
-        {
-            header.set_mode(0o755);
-        }
-        
-        header.set_entry_type(tar::EntryType::Directory);
-        header.set_cksum();
-        
+        let mut header = self.root_header(tar::EntryType::Directory, file_mode(&metadata, 0o755), 0)?;
+
         // Ensure directory name ends with slash
         let name_str = name.to_string_lossy();
         let name_with_slash = if !name_str.ends_with('/') {
@@ -423,9 +502,25 @@ impl PackageConverter {
         } else {
             name_str.to_string()
         };
-        
+
         tar.append_data(&mut header, Path::new(&name_with_slash), std::io::empty())?;
-        
+
+        Ok(())
+    }
+
+    /// Append a hardlink to an already archived file with root ownership
+    fn append_hardlink_with_root_owner<W: Write>(
+        &self,
+        tar: &mut TarBuilder<W>,
+        path: &Path,
+        name: &Path,
+        target: &Path,
+    ) -> Result<()> {
+        let metadata = path.metadata()?;
+        let mut header = self.root_header(tar::EntryType::Link, file_mode(&metadata, 0o644), 0)?;
+
+        tar.append_link(&mut header, name, target)?;
+
         Ok(())
     }
 
@@ -437,22 +532,99 @@ impl PackageConverter {
         name: &Path,
         target: &Path,
     ) -> Result<()> {
-        let mut header = tar::Header::new_gnu();
-        
-        header.set_size(0);
-        header.set_uid(0);
-        header.set_gid(0);
-        header.set_mtime(0);
-        header.set_mode(0o777);
-        header.set_entry_type(tar::EntryType::Symlink);
-        header.set_cksum();
-        
+        let mut header = self.root_header(tar::EntryType::Symlink, 0o777, 0)?;
+
         tar.append_link(&mut header, name, target)?;
-        
+
         Ok(())
     }
 }
 
+/// Permission bits of a file, or `fallback` where they are not available
+#[cfg(unix)]
+fn file_mode(metadata: &fs::Metadata, _fallback: u32) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+/// Permission bits of a file, or `fallback` where they are not available
+#[cfg(not(unix))]
+fn file_mode(_metadata: &fs::Metadata, fallback: u32) -> u32 {
+    fallback
+}
+
+/// Timestamp for a reproducible build of the payload in `data_dir`
+///
+/// Honors `SOURCE_DATE_EPOCH` like makepkg; otherwise the newest mtime in
+/// the payload is used, so converting the same deb twice gives the same
+/// archive.
+fn source_date_epoch(data_dir: &Path) -> Result<i64> {
+    if let Some(epoch) = std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|v| v.trim().parse().ok()) {
+        return Ok(epoch);
+    }
+
+    let mut newest = 0;
+    for entry in walkdir::WalkDir::new(data_dir).min_depth(1) {
+        let mtime = filetime::FileTime::from_last_modification_time(&entry?.path().symlink_metadata()?);
+        newest = newest.max(mtime.unix_seconds());
+    }
+    Ok(newest)
+}
+
+/// Set the mtime of everything below `pkg_root` to `epoch`
+fn normalize_mtimes(pkg_root: &Path, epoch: i64) -> Result<()> {
+    let time = filetime::FileTime::from_unix_time(epoch, 0);
+    // Children first, so setting a file's time does not touch its directory again
+    for entry in walkdir::WalkDir::new(pkg_root).min_depth(1).contents_first(true) {
+        filetime::set_symlink_file_times(entry?.path(), time, time)?;
+    }
+    Ok(())
+}
+
+/// Number of entries below `dir`, for progress totals
+fn count_entries(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir).min_depth(1).into_iter().count() as u64
+}
+
+/// Installed size of a package root the way makepkg measures it
+///
+/// Equivalent to `du -sk --apparent-size` (directories and symlinks count
+/// with their own size, hardlinked files once) over the payload, ignoring
+/// the top-level metadata files, rounded up to whole KiB.
+fn installed_size(pkg_root: &Path) -> Result<u64> {
+    let mut seen = std::collections::HashSet::new();
+    let mut total = 0;
+
+    let entries = walkdir::WalkDir::new(pkg_root)
+        .into_iter()
+        .filter_entry(|e| e.depth() != 1 || !e.file_name().to_string_lossy().starts_with('.'));
+    for entry in entries {
+        let entry = entry?;
+        if let Some(key) = hardlink_key(entry.path())? {
+            if !seen.insert(key) {
+                continue;
+            }
+        }
+        total += entry.path().symlink_metadata()?.len();
+    }
+
+    Ok(total.div_ceil(1024) * 1024)
+}
+
+/// Device/inode pair identifying a file with more than one hardlink
+#[cfg(unix)]
+fn hardlink_key(path: &Path) -> Result<Option<(u64, u64)>> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = path.symlink_metadata()?;
+    Ok((metadata.is_file() && metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino())))
+}
+
+/// Hardlinks are not tracked on this platform
+#[cfg(not(unix))]
+fn hardlink_key(_path: &Path) -> Result<Option<(u64, u64)>> {
+    Ok(None)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -473,4 +645,189 @@ mod tests {
         assert_eq!(converter.metadata.name, "test-package");
         assert_eq!(converter.metadata.version, "1.0.0");
     }
+
+    #[test]
+    fn test_copy_data_files_usrmerge() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        fs::create_dir_all(data_dir.join("bin")).unwrap();
+        fs::create_dir_all(data_dir.join("usr/bin")).unwrap();
+        fs::create_dir_all(data_dir.join("lib")).unwrap();
+        fs::write(data_dir.join("bin/tool"), "legacy").unwrap();
+        fs::write(data_dir.join("usr/bin/tool"), "usr").unwrap();
+        fs::write(data_dir.join("lib/libx.so"), "lib").unwrap();
+
+        let converter = PackageConverter::new(PackageMetadata::new("test", "1.0"), &data_dir).unwrap();
+        let pkg_root = temp_dir.path().join("pkg");
+        fs::create_dir_all(&pkg_root).unwrap();
+        let mut report = ConversionReport::default();
+        converter.copy_data_files(&pkg_root, &mut report).unwrap();
+
+        assert_eq!(fs::read_to_string(pkg_root.join("usr/bin/tool")).unwrap(), "usr");
+        assert!(pkg_root.join("usr/lib/libx.so").exists());
+        assert!(!pkg_root.join("bin").exists());
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_hardlinks_preserved() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        fs::create_dir_all(data_dir.join("usr/bin")).unwrap();
+        fs::write(data_dir.join("usr/bin/gzip"), "binary").unwrap();
+        fs::hard_link(data_dir.join("usr/bin/gzip"), data_dir.join("usr/bin/gunzip")).unwrap();
+
+        let converter = PackageConverter::new(PackageMetadata::new("test", "1.0"), &data_dir).unwrap();
+        let pkg_root = temp_dir.path().join("pkg");
+        fs::create_dir_all(&pkg_root).unwrap();
+        converter.copy_data_files(&pkg_root, &mut ConversionReport::default()).unwrap();
+        assert_eq!(
+            hardlink_key(&pkg_root.join("usr/bin/gzip")).unwrap(),
+            hardlink_key(&pkg_root.join("usr/bin/gunzip")).unwrap()
+        );
+
+        let mut tar = TarBuilder::new(Vec::new());
+        converter.add_package_files(&mut tar, &pkg_root).unwrap();
+        let data = tar.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(&data[..]);
+        let kinds: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().header().entry_type())
+            .filter(|t| !t.is_dir())
+            .collect();
+        assert_eq!(kinds, vec![tar::EntryType::Regular, tar::EntryType::Link]);
+    }
+
+    #[test]
+    fn test_installed_size() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::write(root.join("usr/bin/gzip"), vec![0u8; 3000]).unwrap();
+        fs::write(root.join(".PKGINFO"), vec![0u8; 5000]).unwrap();
+        let dirs: u64 = ["", "usr", "usr/bin"].iter().map(|d| fs::metadata(root.join(d)).unwrap().len()).sum();
+
+        let size = installed_size(root).unwrap();
+        assert_eq!(size, (3000 + dirs).div_ceil(1024) * 1024);
+
+        fs::hard_link(root.join("usr/bin/gzip"), root.join("usr/bin/gunzip")).unwrap();
+        let dirs: u64 = ["", "usr", "usr/bin"].iter().map(|d| fs::metadata(root.join(d)).unwrap().len()).sum();
+        assert_eq!(installed_size(root).unwrap(), (3000 + dirs).div_ceil(1024) * 1024);
+    }
+
+    #[test]
+    fn test_build_with_epoch() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        fs::create_dir_all(data_dir.join("usr/bin")).unwrap();
+        fs::write(data_dir.join("usr/bin/foo"), "foo").unwrap();
+
+        let mut metadata = PackageMetadata::new("foo", "1:2.3-4");
+        metadata.normalize_version();
+        let arch = metadata.arch.to_arch_name().to_string();
+
+        let converter = PackageConverter::new(metadata, &data_dir).unwrap();
+        assert!(converter.generate_buildinfo().contains("pkgver = 1:2.3-4\n"));
+
+        let report = converter.build(temp_dir.path(), OutputFormat::PkgTarGz).unwrap();
+        assert_eq!(
+            report.output_path.file_name().unwrap().to_string_lossy(),
+            format!("foo-1:2.3-4-{}.pkg.tar.gz", arch)
+        );
+        assert!(report.output_path.exists());
+    }
+
+    #[test]
+    fn test_build_is_reproducible() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        fs::create_dir_all(data_dir.join("usr/bin")).unwrap();
+        fs::create_dir_all(data_dir.join("usr/share/foo")).unwrap();
+        for name in ["usr/bin/foo", "usr/share/foo/b", "usr/share/foo/a"] {
+            fs::write(data_dir.join(name), name).unwrap();
+        }
+        let epoch = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        normalize_mtimes(&data_dir, 1_500_000_000).unwrap();
+        filetime::set_file_mtime(data_dir.join("usr/bin/foo"), epoch).unwrap();
+
+        let build = |dir: &str| {
+            let output_dir = temp_dir.path().join(dir);
+            fs::create_dir_all(&output_dir).unwrap();
+            let converter = PackageConverter::new(PackageMetadata::new("foo", "1.0"), &data_dir).unwrap();
+            fs::read(converter.build(&output_dir, OutputFormat::PkgTarGz).unwrap().output_path).unwrap()
+        };
+        let first = build("a");
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        assert_eq!(first, build("b"));
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(&first[..]));
+        let entries: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                (e.path().unwrap().to_string_lossy().into_owned(), e.header().mtime().unwrap())
+            })
+            .collect();
+        let epoch = epoch.unix_seconds() as u64;
+        assert!(entries.iter().all(|(_, mtime)| *mtime == epoch));
+        let names: Vec<_> = entries.iter().map(|(name, _)| name.as_str()).collect();
+        assert!(names.ends_with(&["usr/share/foo/", "usr/share/foo/a", "usr/share/foo/b"]));
+    }
+
+    #[test]
+    fn test_python_hooks_follow_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        fs::create_dir_all(data_dir.join("usr/lib/python3/dist-packages/foo")).unwrap();
+        fs::write(data_dir.join("usr/lib/python3/dist-packages/foo/__init__.py"), "").unwrap();
+
+        let options = ConversionConfig {
+            prefix: Some("/opt/{pkgname}".to_string()),
+            python_version: Some("3.13".to_string()),
+            python_bytecompile: true,
+            ..Default::default()
+        };
+        let report = PackageConverter::new(PackageMetadata::new("foo", "1.0"), &data_dir)
+            .unwrap()
+            .with_config(&options)
+            .build(temp_dir.path(), OutputFormat::PkgTarGz)
+            .unwrap();
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(report.output_path).unwrap()));
+        let mut install = String::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            if entry.path().unwrap() == Path::new(".INSTALL") {
+                std::io::Read::read_to_string(&mut entry, &mut install).unwrap();
+            }
+        }
+        assert!(install.contains("compileall -q -f '/opt/foo/lib/python3.13/site-packages/foo' "));
+        assert!(!install.contains("/usr/lib/python3.13"));
+    }
+
+    #[test]
+    fn test_build_progress_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        fs::create_dir_all(data_dir.join("usr/bin")).unwrap();
+        fs::write(data_dir.join("usr/bin/foo"), "foo").unwrap();
+
+        let (progress, rx) = Progress::channel();
+        PackageConverter::new(PackageMetadata::new("foo", "1.0"), &data_dir)
+            .unwrap()
+            .with_progress(progress)
+            .build(temp_dir.path(), OutputFormat::PkgTarGz)
+            .unwrap();
+
+        let events: Vec<_> = rx.try_iter().collect();
+        let copied = events.iter().rfind(|e| e.stage == Stage::Copying).unwrap();
+        assert_eq!((copied.current, copied.total), (3, Some(3)));
+        let archived = events.iter().rfind(|e| e.stage == Stage::Archiving).unwrap();
+        assert_eq!(Some(archived.current), archived.total);
+        assert_eq!(events.last().unwrap().stage, Stage::Done);
+        assert!(events.windows(2).all(|w| w[0].fraction() <= w[1].fraction()));
+    }
 }
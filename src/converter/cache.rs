@@ -0,0 +1,214 @@
+//! Conversion result cache
+//!
+//! Converting the same deb with the same settings and the same resolver
+//! state always gives the same package, so the result is kept and reused.
+//! Resolver state is the mapping and package database files, stamped by
+//! [`resolver_stamp`]. Records live in
+//! `<data_dir>/conversions.json`; the packages themselves are copied to
+//! `<cache_dir>/packages` so they survive the output directory being
+//! cleaned up and go away with `rexeb clean`. `rexeb install` converts into
+//! a directory of its own there.
+
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::error::Result;
+
+/// A cached conversion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheRecord {
+    /// SHA-256 of the input package
+    pub input_sha256: String,
+    /// Hash of the conversion settings
+    pub options_hash: String,
+    /// Cached copy of the built package
+    pub output_path: PathBuf,
+    /// When the record was created (unix time)
+    pub created: i64,
+}
+
+/// Cache of conversion results keyed by input and settings
+#[derive(Debug)]
+pub struct ConversionCache {
+    /// Record index file
+    index_path: PathBuf,
+    /// Directory holding cached packages
+    packages_dir: PathBuf,
+    /// Known records
+    records: Vec<CacheRecord>,
+}
+
+impl ConversionCache {
+    /// Open the cache at the given locations
+    pub fn new(index_path: impl Into<PathBuf>, packages_dir: impl Into<PathBuf>) -> Result<Self> {
+        let index_path = index_path.into();
+        let records = Self::read_index(&index_path)?;
+        Ok(Self { index_path, packages_dir: packages_dir.into(), records })
+    }
+
+    /// Open the cache in the configured data and cache directories
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::new(config.data_dir().join("conversions.json"), config.cache_dir().join("packages"))
+    }
+
+    /// SHA-256 of a file
+    pub fn file_sha256(path: &Path) -> Result<String> {
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    /// Hash of serializable conversion settings
+    pub fn options_hash(options: &impl Serialize) -> Result<String> {
+        Ok(hex::encode(Sha256::digest(serde_json::to_vec(options)?)))
+    }
+
+    /// Find a record whose cached package still exists
+    pub fn lookup(&self, input_sha256: &str, options_hash: &str) -> Option<&CacheRecord> {
+        self.records.iter().find(|r| {
+            r.input_sha256 == input_sha256 && r.options_hash == options_hash && r.output_path.is_file()
+        })
+    }
+
+    /// Copy a cached package into `output_dir`
+    pub fn fetch(&self, record: &CacheRecord, output_dir: &Path) -> Result<PathBuf> {
+        let dest = output_dir.join(record.output_path.file_name().unwrap_or_default());
+        if dest != record.output_path {
+            fs::create_dir_all(output_dir)?;
+            fs::copy(&record.output_path, &dest)?;
+        }
+        Ok(dest)
+    }
+
+    /// Keep a copy of a built package and record it
+    pub fn store(&mut self, input_sha256: &str, options_hash: &str, package: &Path) -> Result<()> {
+        let dir = self.packages_dir.join(&options_hash[..options_hash.len().min(16)]);
+        fs::create_dir_all(&dir)?;
+        let cached = dir.join(package.file_name().unwrap_or_default());
+        fs::copy(package, &cached)?;
+
+        // Other conversions may have stored records since we loaded
+        self.records = Self::read_index(&self.index_path)?;
+        self.records.retain(|r| !(r.input_sha256 == input_sha256 && r.options_hash == options_hash));
+        self.records.push(CacheRecord {
+            input_sha256: input_sha256.to_string(),
+            options_hash: options_hash.to_string(),
+            output_path: cached,
+            created: chrono::Utc::now().timestamp(),
+        });
+
+        if let Some(parent) = self.index_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.index_path, serde_json::to_string_pretty(&self.records)?)?;
+        Ok(())
+    }
+
+    /// Forget the conversions of an input and delete their packages
+    ///
+    /// Returns how many records were removed.
+    pub fn remove(&mut self, input_sha256: &str) -> Result<usize> {
+        self.records = Self::read_index(&self.index_path)?;
+        let before = self.records.len();
+        self.records.retain(|r| {
+            if r.input_sha256 != input_sha256 {
+                return true;
+            }
+            let _ = fs::remove_file(&r.output_path);
+            false
+        });
+
+        let removed = before - self.records.len();
+        if removed > 0 {
+            fs::write(&self.index_path, serde_json::to_string_pretty(&self.records)?)?;
+        }
+        Ok(removed)
+    }
+
+    fn read_index(path: &Path) -> Result<Vec<CacheRecord>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        // A corrupt index only costs a re-conversion
+        Ok(serde_json::from_str(&fs::read_to_string(path)?).unwrap_or_default())
+    }
+}
+
+/// Modification time and size of the files dependency resolution reads
+///
+/// Covers the rexeb package database (mappings, learned mappings, synced
+/// repositories, the negative lookup cache), the user's `mappings.toml` and
+/// `mappings.d`, and pacman's sync and local databases. Any change to them
+/// changes the stamp, so conversions are not reused across them.
+pub fn resolver_stamp() -> Vec<(PathBuf, std::time::SystemTime, u64)> {
+    use crate::resolver::pacman::PACMAN_DB_PATH;
+    use crate::resolver::user_mappings::{MAPPINGS_DIR, MAPPINGS_FILE};
+
+    let mut paths = Vec::new();
+    if let Ok(db_dir) = crate::resolver::PackageDatabase::get_db_dir() {
+        paths.extend(dir_files(&db_dir));
+        paths.extend(dir_files(&db_dir.join("sync")));
+    }
+    if let Some(config_dir) = dirs::config_dir() {
+        let config_dir = config_dir.join("rexeb");
+        paths.push(config_dir.join(MAPPINGS_FILE));
+        paths.extend(dir_files(&config_dir.join(MAPPINGS_DIR)));
+    }
+    paths.extend(dir_files(&Path::new(PACMAN_DB_PATH).join("sync")));
+    paths.push(Path::new(PACMAN_DB_PATH).join("local"));
+
+    let mut stamps: Vec<_> = paths
+        .into_iter()
+        .filter_map(|path| {
+            let metadata = fs::metadata(&path).ok()?;
+            Some((path, metadata.modified().ok()?, metadata.len()))
+        })
+        .collect();
+    stamps.sort();
+    stamps
+}
+
+/// Files directly inside a directory
+fn dir_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| p.is_file()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_store_and_lookup() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let package = root.join("foo-1.0-1-x86_64.pkg.tar.zst");
+        fs::write(&package, "pkg").unwrap();
+
+        let options = ConversionCache::options_hash(&("relocate", true)).unwrap();
+        let mut cache = ConversionCache::new(root.join("conversions.json"), root.join("packages")).unwrap();
+        assert!(cache.lookup("abc", &options).is_none());
+        cache.store("abc", &options, &package).unwrap();
+        fs::remove_file(&package).unwrap();
+
+        let cache = ConversionCache::new(root.join("conversions.json"), root.join("packages")).unwrap();
+        let record = cache.lookup("abc", &options).unwrap();
+        assert!(cache.lookup("abc", &ConversionCache::options_hash(&("keep", true)).unwrap()).is_none());
+
+        let fetched = cache.fetch(record, &root.join("out")).unwrap();
+        assert_eq!(fetched, root.join("out/foo-1.0-1-x86_64.pkg.tar.zst"));
+        assert_eq!(fs::read_to_string(fetched).unwrap(), "pkg");
+
+        let mut cache = cache;
+        let cached = cache.lookup("abc", &options).unwrap().output_path.clone();
+        assert_eq!(cache.remove("abc").unwrap(), 1);
+        assert!(!cached.exists());
+        assert!(cache.lookup("abc", &options).is_none());
+    }
+}
@@ -0,0 +1,254 @@
+//! File-type census of a payload
+//!
+//! Classifies every file from its first bytes: ELF objects by type, static
+//! archives, scripts by interpreter, plain data and symlinks. The analysis
+//! report shows the counts; arch detection and the passes rewriting ELF
+//! objects use the classification instead of sniffing files themselves.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+/// Bytes read to classify a file
+const HEADER_SIZE: usize = 256;
+
+/// ELF `e_type` values
+const ET_EXEC: u16 = 2;
+const ET_DYN: u16 = 3;
+
+/// What a payload file is
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FileKind {
+    /// ELF executable, position-independent ones included
+    Executable,
+    /// ELF shared library
+    SharedLibrary,
+    /// Any other ELF object (`.o` files, kernel modules)
+    Object,
+    /// `ar` archive of objects (`.a`)
+    StaticArchive,
+    /// Script run by the named interpreter
+    Script(String),
+    /// Anything else
+    Data,
+    /// Symbolic link
+    Symlink,
+}
+
+impl FileKind {
+    /// Classify a file from its name and first bytes
+    pub fn classify(name: &str, header: &[u8]) -> Self {
+        if header.starts_with(b"\x7fELF") {
+            // EI_DATA: 1 little endian, 2 big endian
+            let e_type = match header.get(16..18) {
+                Some(&[a, b]) if header[5] == 2 => u16::from_be_bytes([a, b]),
+                Some(&[a, b]) => u16::from_le_bytes([a, b]),
+                _ => 0,
+            };
+            return match e_type {
+                ET_EXEC => Self::Executable,
+                // PIE executables are ET_DYN too; libraries are named for their soname
+                ET_DYN if name.contains(".so") => Self::SharedLibrary,
+                ET_DYN => Self::Executable,
+                _ => Self::Object,
+            };
+        }
+        if header.starts_with(b"!<arch>\n") {
+            return Self::StaticArchive;
+        }
+        if let Some(line) = header.strip_prefix(b"#!") {
+            let line = String::from_utf8_lossy(line.split(|&b| b == b'\n').next().unwrap_or_default());
+            let mut words = line.split_whitespace();
+            let program = words.next().unwrap_or_default();
+            let program = program.rsplit('/').next().unwrap_or(program);
+            // `#!/usr/bin/env python3` names the interpreter after env
+            let interpreter = match program {
+                "env" => words.find(|w| !w.starts_with('-')).unwrap_or("env"),
+                other => other,
+            };
+            return Self::Script(interpreter.to_string());
+        }
+        Self::Data
+    }
+
+    /// Whether the file only runs on the architecture it was built for
+    pub fn is_native(&self) -> bool {
+        matches!(self, Self::Executable | Self::SharedLibrary | Self::Object | Self::StaticArchive)
+    }
+
+    /// Whether the file is an ELF object
+    pub fn is_elf(&self) -> bool {
+        matches!(self, Self::Executable | Self::SharedLibrary | Self::Object)
+    }
+}
+
+/// Classify one file
+pub fn classify_file(path: &Path) -> Result<FileKind> {
+    if path.symlink_metadata()?.file_type().is_symlink() {
+        return Ok(FileKind::Symlink);
+    }
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    File::open(path)?.take(HEADER_SIZE as u64).read_to_end(&mut header)?;
+    let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    Ok(FileKind::classify(&name, &header))
+}
+
+/// A file worth pointing out
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotableFile {
+    /// Path inside the package
+    pub path: PathBuf,
+    /// Why it stands out
+    pub note: String,
+}
+
+/// Counts per file type, with notable files
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CensusSummary {
+    /// ELF executables
+    pub executables: usize,
+    /// ELF shared libraries
+    pub shared_libraries: usize,
+    /// Other ELF objects
+    pub objects: usize,
+    /// Static archives
+    pub static_archives: usize,
+    /// Scripts per interpreter
+    pub scripts: BTreeMap<String, usize>,
+    /// Other files
+    pub data: usize,
+    /// Symbolic links
+    pub symlinks: usize,
+    /// Files that are unusual in a runtime package or will not run
+    pub notable: Vec<NotableFile>,
+}
+
+/// Every file of a payload with its type
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileCensus {
+    /// Paths relative to the package root, in walk order
+    pub files: Vec<(PathBuf, FileKind)>,
+}
+
+impl FileCensus {
+    /// Classify the files below a package root
+    pub fn scan(root: &Path) -> Result<Self> {
+        let mut files = Vec::new();
+        for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+            let entry = entry?;
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path()).to_path_buf();
+            files.push((rel, classify_file(entry.path())?));
+        }
+        Ok(Self { files })
+    }
+
+    /// Files with native code
+    pub fn native(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().filter(|(_, kind)| kind.is_native()).map(|(path, _)| path.as_path())
+    }
+
+    /// ELF objects, for the passes that strip or rewrite them
+    pub fn elf_files(&self) -> impl Iterator<Item = (&Path, &FileKind)> {
+        self.files.iter().filter(|(_, kind)| kind.is_elf()).map(|(path, kind)| (path.as_path(), kind))
+    }
+
+    /// Counts per type and notable files
+    ///
+    /// `root` is the package root, used to find interpreters the package ships.
+    pub fn summary(&self, root: &Path) -> CensusSummary {
+        let mut summary = CensusSummary::default();
+        for (path, kind) in &self.files {
+            let shown = Path::new("/").join(path);
+            match kind {
+                FileKind::Executable => summary.executables += 1,
+                FileKind::SharedLibrary => summary.shared_libraries += 1,
+                FileKind::Object => {
+                    summary.objects += 1;
+                    let is_module = path.to_string_lossy().contains(".ko");
+                    if !is_module {
+                        summary.notable.push(NotableFile {
+                            path: shown,
+                            note: "relocatable object; only useful for building against the package".into(),
+                        });
+                    }
+                }
+                FileKind::StaticArchive => {
+                    summary.static_archives += 1;
+                    summary.notable.push(NotableFile {
+                        path: shown,
+                        note: "static library; Arch packages rarely ship these".into(),
+                    });
+                }
+                FileKind::Script(interpreter) => {
+                    *summary.scripts.entry(interpreter.clone()).or_default() += 1;
+                    if let Some(missing) = missing_interpreter(root, path, interpreter) {
+                        summary.notable.push(NotableFile {
+                            path: shown,
+                            note: format!("runs {}, which is neither in the package nor on this system", missing),
+                        });
+                    }
+                }
+                FileKind::Data => summary.data += 1,
+                FileKind::Symlink => summary.symlinks += 1,
+            }
+        }
+        summary
+    }
+}
+
+/// The interpreter of a script when it cannot be found
+fn missing_interpreter(root: &Path, script: &Path, interpreter: &str) -> Option<String> {
+    let mut header = Vec::new();
+    File::open(root.join(script)).ok()?.take(HEADER_SIZE as u64).read_to_end(&mut header).ok()?;
+    let line = String::from_utf8_lossy(&header[2..]);
+    let program = line.lines().next()?.split_whitespace().next()?.to_string();
+
+    let candidates: Vec<String> = match program.ends_with("/env") {
+        true => ["/usr/bin", "/bin", "/usr/local/bin"].iter().map(|dir| format!("{}/{}", dir, interpreter)).collect(),
+        false => vec![program.clone()],
+    };
+    let found = candidates.iter().any(|candidate| {
+        Path::new(candidate).exists() || root.join(candidate.trim_start_matches('/')).exists()
+    });
+    (!found).then_some(if program.ends_with("/env") { interpreter.to_string() } else { program })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_file_census() {
+        assert_eq!(FileKind::classify("foo", b"#!/usr/bin/env -S python3 -u\n"), FileKind::Script("python3".into()));
+        assert_eq!(FileKind::classify("libfoo.so.1", b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0"), FileKind::SharedLibrary);
+        assert_eq!(FileKind::classify("foo", b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0"), FileKind::Executable);
+        assert_eq!(FileKind::classify("foo.o", b"\x7fELF\x02\x02\x01\0\0\0\0\0\0\0\0\0\0\x01"), FileKind::Object);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::create_dir_all(root.join("usr/lib")).unwrap();
+        fs::write(root.join("usr/bin/tool"), "#!/bin/sh\necho hi\n").unwrap();
+        fs::write(root.join("usr/bin/old"), "#!/usr/bin/rexeb-no-such-interpreter\n").unwrap();
+        fs::write(root.join("usr/lib/libfoo.a"), "!<arch>\n").unwrap();
+        std::os::unix::fs::symlink("tool", root.join("usr/bin/tool2")).unwrap();
+
+        let census = FileCensus::scan(root).unwrap();
+        assert_eq!(census.native().collect::<Vec<_>>(), [Path::new("usr/lib/libfoo.a")]);
+
+        let summary = census.summary(root);
+        assert_eq!(summary.scripts.get("sh"), Some(&1));
+        assert_eq!((summary.static_archives, summary.symlinks), (1, 1));
+        let notable: Vec<_> = summary.notable.iter().map(|n| n.path.to_str().unwrap()).collect();
+        assert_eq!(notable, ["/usr/bin/old", "/usr/lib/libfoo.a"]);
+    }
+}
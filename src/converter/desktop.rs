@@ -0,0 +1,426 @@
+//! Desktop entry fixups and validation
+//!
+//! Launchers in `/usr/share/applications` often name absolute paths that
+//! the other passes moved (`/usr/lib/x86_64-linux-gnu/...`, `/usr/sbin/...`)
+//! and carry keys only Debian's menu tooling understands. This pass points
+//! Exec/TryExec/Icon at the files the package actually ships, strips the
+//! Debian-only keys and reports entries that would not work on Arch.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+use super::{usrmerge_path, ConversionReport, MULTIARCH_TRIPLETS};
+
+/// Directory holding desktop entries, relative to the package root
+pub const APPLICATIONS_DIR: &str = "usr/share/applications";
+
+/// Key prefixes only meaningful on Debian and Ubuntu
+const DEBIAN_KEY_PREFIXES: &[&str] = &["X-Debian-", "X-Ubuntu-"];
+
+/// Keys holding a path (or a command whose first word may be one)
+const PATH_KEYS: &[&str] = &["Exec", "TryExec", "Icon"];
+
+/// Converter pass fixing `.desktop` files
+pub struct DesktopFixer;
+
+impl DesktopFixer {
+    /// Create a new fixer
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Fix and validate all desktop entries in the package root
+    pub fn apply(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        for path in desktop_files(pkg_root)? {
+            let rel = path.strip_prefix(pkg_root).unwrap_or(&path).to_path_buf();
+            let content = fs::read_to_string(&path)?;
+
+            let mut removed = 0;
+            let mut lines = Vec::new();
+            let mut group = String::new();
+            for line in content.lines() {
+                if let Some(name) = group_name(line) {
+                    group = name.to_string();
+                    lines.push(line.to_string());
+                    continue;
+                }
+
+                let Some((key, value)) = line.split_once('=') else {
+                    lines.push(line.to_string());
+                    continue;
+                };
+                let key = key.trim();
+
+                if DEBIAN_KEY_PREFIXES.iter().any(|p| key.starts_with(p)) {
+                    removed += 1;
+                    continue;
+                }
+
+                let in_entry = group == "Desktop Entry" || group.starts_with("Desktop Action ");
+                if in_entry && PATH_KEYS.contains(&key) {
+                    if let Some(fixed) = Self::fix_value(pkg_root, key, value.trim()) {
+                        report.change(format!("Fixed {} in /{}: {} -> {}", key, rel.display(), value.trim(), fixed));
+                        lines.push(format!("{}={}", key, fixed));
+                        continue;
+                    }
+                }
+
+                lines.push(line.to_string());
+            }
+
+            if removed > 0 {
+                report.change(format!("Removed {} Debian-only keys from /{}", removed, rel.display()));
+            }
+
+            let mut updated = lines.join("\n");
+            if content.ends_with('\n') {
+                updated.push('\n');
+            }
+            if updated != content {
+                fs::write(&path, &updated)?;
+            }
+
+            for problem in validate_desktop_entry(&updated)
+                .into_iter()
+                .chain(Self::missing_paths(pkg_root, &updated))
+            {
+                report.warn(format!("/{}: {}", rel.display(), problem));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// New value for a path key whose target moved, if any
+    fn fix_value(pkg_root: &Path, key: &str, value: &str) -> Option<String> {
+        let (path, rest) = split_command(key, value);
+        if !path.starts_with('/') || exists_in(pkg_root, path) {
+            return None;
+        }
+
+        let fixed = relocation_candidates(path)
+            .into_iter()
+            .find(|candidate| exists_in(pkg_root, candidate))?;
+        Some(format!("{}{}", quote_like(value, &fixed), rest))
+    }
+
+    /// Absolute paths the entry names that exist neither in the package nor on the host
+    fn missing_paths(pkg_root: &Path, content: &str) -> Vec<String> {
+        let mut missing = Vec::new();
+        let mut group = String::new();
+
+        for line in content.lines() {
+            if let Some(name) = group_name(line) {
+                group = name.to_string();
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            let in_entry = group == "Desktop Entry" || group.starts_with("Desktop Action ");
+            if !in_entry || !PATH_KEYS.contains(&key) {
+                continue;
+            }
+
+            let (path, _) = split_command(key, value.trim());
+            if path.starts_with('/') && !exists_in(pkg_root, path) && !Path::new(path).exists() {
+                missing.push(format!("{} refers to {}, which is not in the package", key, path));
+            }
+        }
+
+        missing
+    }
+}
+
+impl Default for DesktopFixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// All `.desktop` files below the applications directory
+pub fn desktop_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let dir = root.join(APPLICATIONS_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(&dir).sort_by_file_name() {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.path().extension().is_some_and(|e| e == "desktop") {
+            files.push(entry.into_path());
+        }
+    }
+    Ok(files)
+}
+
+/// Check a desktop entry against the basics of the Desktop Entry spec
+///
+/// Returns a description of each problem found.
+pub fn validate_desktop_entry(content: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut group: Option<String> = None;
+    let mut seen = HashSet::new();
+    let mut entry_keys = std::collections::HashMap::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let number = number + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = group_name(trimmed) {
+            if group.is_none() && name != "Desktop Entry" {
+                problems.push(format!("first group is [{}], expected [Desktop Entry]", name));
+            }
+            if !seen.insert(format!("[{}]", name)) {
+                problems.push(format!("line {}: duplicate group [{}]", number, name));
+            }
+            group = Some(name.to_string());
+            continue;
+        }
+
+        let Some((key, value)) = trimmed.split_once('=') else {
+            problems.push(format!("line {}: not a key=value pair", number));
+            continue;
+        };
+        let key = key.trim();
+        let Some(ref current) = group else {
+            problems.push(format!("line {}: {} appears before any group", number, key));
+            continue;
+        };
+        if !is_valid_key(key) {
+            problems.push(format!("line {}: invalid key '{}'", number, key));
+        }
+        if !seen.insert(format!("{}\0{}", current, key)) {
+            problems.push(format!("line {}: duplicate key {}", number, key));
+        }
+        if current == "Desktop Entry" {
+            entry_keys.entry(key.to_string()).or_insert_with(|| value.trim().to_string());
+        }
+    }
+
+    if group.is_none() {
+        problems.push("missing [Desktop Entry] group".to_string());
+        return problems;
+    }
+
+    for required in ["Type", "Name"] {
+        if !entry_keys.contains_key(required) {
+            problems.push(format!("missing required key {}", required));
+        }
+    }
+
+    let dbus_activatable = entry_keys.get("DBusActivatable").is_some_and(|v| v == "true");
+    match entry_keys.get("Type").map(String::as_str) {
+        Some("Application") if !entry_keys.contains_key("Exec") && !dbus_activatable => {
+            problems.push("Application entry without Exec".to_string());
+        }
+        Some("Link") if !entry_keys.contains_key("URL") => {
+            problems.push("Link entry without URL".to_string());
+        }
+        Some("Application") | Some("Link") | Some("Directory") | None => {}
+        Some(other) => problems.push(format!("unknown Type {}", other)),
+    }
+
+    problems
+}
+
+/// Command line of an application entry, as a launcher would run it
+///
+/// Gives the `[Desktop Entry]` Exec split into arguments, with quoting
+/// resolved and field codes (`%f`, `%U`, ...) dropped. Hidden entries and
+/// entries of other types have none.
+pub fn desktop_exec(content: &str) -> Option<Vec<String>> {
+    let mut group = "";
+    let mut keys = std::collections::HashMap::new();
+    for line in content.lines() {
+        if let Some(name) = group_name(line) {
+            group = name;
+        } else if let Some((key, value)) = line.split_once('=') {
+            if group == "Desktop Entry" {
+                keys.entry(key.trim()).or_insert(value.trim());
+            }
+        }
+    }
+    if keys.get("Type").is_some_and(|t| *t != "Application") || keys.get("Hidden").is_some_and(|h| *h == "true") {
+        return None;
+    }
+
+    let argv: Vec<String> = split_exec(keys.get("Exec")?)
+        .into_iter()
+        .filter_map(|arg| {
+            let mut expanded = String::new();
+            let mut chars = arg.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    // `%%` is a literal percent sign, anything else a field code
+                    '%' if chars.next() == Some('%') => expanded.push('%'),
+                    '%' => {}
+                    c => expanded.push(c),
+                }
+            }
+            (!expanded.is_empty()).then_some(expanded)
+        })
+        .collect();
+    (!argv.is_empty()).then_some(argv)
+}
+
+/// Split an Exec value into arguments
+///
+/// Arguments are separated by spaces; double quotes group them and a
+/// backslash escapes the next character inside quotes.
+fn split_exec(value: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quoted = false;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                current.get_or_insert_with(String::new);
+            }
+            '\\' if quoted => current.get_or_insert_with(String::new).extend(chars.next()),
+            c if c.is_whitespace() && !quoted => args.extend(current.take()),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    args
+}
+
+/// Name of a `[group]` header line
+fn group_name(line: &str) -> Option<&str> {
+    line.trim().strip_prefix('[')?.strip_suffix(']')
+}
+
+/// Keys are `[A-Za-z0-9-]+` with an optional `[locale]` suffix
+fn is_valid_key(key: &str) -> bool {
+    let (base, locale) = match key.split_once('[') {
+        Some((base, rest)) => (base, rest.strip_suffix(']')),
+        None => (key, Some("")),
+    };
+    !base.is_empty()
+        && base.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        && locale.is_some_and(|l| !l.contains(['[', ']']))
+}
+
+/// Split a value into its path part and the remainder
+///
+/// For Exec this is the program (without quotes) and its arguments.
+fn split_command<'a>(key: &str, value: &'a str) -> (&'a str, &'a str) {
+    if key != "Exec" {
+        return (value, "");
+    }
+    if let Some(quoted) = value.strip_prefix('"') {
+        if let Some(end) = quoted.find('"') {
+            return (&quoted[..end], &quoted[end + 1..]);
+        }
+    }
+    match value.find(char::is_whitespace) {
+        Some(end) => (&value[..end], &value[end..]),
+        None => (value, ""),
+    }
+}
+
+/// Re-add the quotes the original value used around its program
+fn quote_like(original: &str, path: &str) -> String {
+    if original.starts_with('"') {
+        format!("\"{}\"", path)
+    } else {
+        path.to_string()
+    }
+}
+
+/// Where the other passes may have moved an absolute path
+pub(super) fn relocation_candidates(path: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    let merged = usrmerge_path(Path::new(path)).map(|p| format!("/{}", p.display()));
+
+    for base in std::iter::once(path.to_string()).chain(merged.clone()) {
+        for triplet in MULTIARCH_TRIPLETS {
+            let needle = format!("/{}/", triplet);
+            if base.contains(&needle) {
+                candidates.push(base.replacen(&needle, "/", 1));
+            }
+        }
+    }
+    candidates.extend(merged);
+    candidates
+}
+
+/// Whether an absolute path exists (possibly as a symlink) in the package root
+pub(super) fn exists_in(pkg_root: &Path, path: &str) -> bool {
+    pkg_root.join(path.trim_start_matches('/')).symlink_metadata().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fix_desktop_entry() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::create_dir_all(root.join("usr/lib/foo")).unwrap();
+        fs::create_dir_all(root.join(APPLICATIONS_DIR)).unwrap();
+        fs::write(root.join("usr/bin/foo"), "").unwrap();
+        fs::write(root.join("usr/lib/foo/foo.png"), "").unwrap();
+        fs::write(
+            root.join(APPLICATIONS_DIR).join("foo.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Foo\n\
+             Exec=\"/usr/sbin/foo\" --open %U\nTryExec=/sbin/foo\n\
+             Icon=/usr/lib/x86_64-linux-gnu/foo/foo.png\n\
+             X-Ubuntu-Gettext-Domain=foo\nX-Debian-Section=net\n\n\
+             [Desktop Action new]\nName=New\nExec=/opt/missing/foo --new\n",
+        )
+        .unwrap();
+
+        let mut report = ConversionReport::default();
+        DesktopFixer::new().apply(root, &mut report).unwrap();
+
+        let fixed = fs::read_to_string(root.join(APPLICATIONS_DIR).join("foo.desktop")).unwrap();
+        assert!(fixed.contains("Exec=\"/usr/bin/foo\" --open %U\n"));
+        assert!(fixed.contains("TryExec=/usr/bin/foo\n"));
+        assert!(fixed.contains("Icon=/usr/lib/foo/foo.png\n"));
+        assert!(!fixed.contains("X-"));
+        assert_eq!(report.changes.len(), 4);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("/opt/missing/foo"));
+    }
+
+    #[test]
+    fn test_validate_desktop_entry() {
+        assert!(validate_desktop_entry("[Desktop Entry]\nType=Application\nName=Foo\nName[de]=Foo\nExec=foo\n").is_empty());
+
+        let problems = validate_desktop_entry("# launcher\nType=Application\n[Desktop Entry]\nType=Service\nType=Link\nbad line\nIcon Name=x\n");
+        assert_eq!(
+            problems,
+            vec![
+                "line 2: Type appears before any group",
+                "line 5: duplicate key Type",
+                "line 6: not a key=value pair",
+                "line 7: invalid key 'Icon Name'",
+                "missing required key Name",
+                "unknown Type Service",
+            ]
+        );
+        assert_eq!(validate_desktop_entry(""), vec!["missing [Desktop Entry] group"]);
+
+        assert_eq!(
+            desktop_exec("[Desktop Entry]\nType=Application\nExec=\"/opt/foo bar/foo\" --title \"a \\\"b\\\"\" 100%% %U\n[Desktop Action new]\nExec=foo --new\n"),
+            Some(vec!["/opt/foo bar/foo".to_string(), "--title".into(), "a \"b\"".into(), "100%".into()])
+        );
+        assert_eq!(desktop_exec("[Desktop Entry]\nType=Link\nURL=https://example.org\n"), None);
+        assert_eq!(desktop_exec("[Desktop Entry]\nType=Application\nHidden=true\nExec=foo\n"), None);
+    }
+}
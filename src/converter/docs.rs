@@ -0,0 +1,297 @@
+//! Documentation cleanup and man/info page recompression
+//!
+//! Drops Debian-only documentation (changelog.Debian, README.Debian,
+//! doc-base registrations, lintian overrides), moves the Debian copyright
+//! file to Arch's license directory and recompresses man and info pages
+//! with gzip like makepkg's `zipman` option.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::Result;
+
+use super::ConversionReport;
+
+/// Directories removed outright
+const DEBIAN_ONLY_DIRS: &[&str] = &["usr/share/doc-base", "usr/share/lintian"];
+
+/// File name prefixes below /usr/share/doc that only make sense on Debian
+const DEBIAN_ONLY_DOCS: &[&str] = &["changelog.Debian", "README.Debian", "NEWS.Debian"];
+
+/// Directories whose pages are (re)compressed
+const PAGE_DIRS: &[&str] = &["usr/share/man", "usr/share/info"];
+
+/// Converter pass cleaning up documentation
+pub struct DocCleaner<'a> {
+    /// Package name, used for the license directory
+    pkgname: &'a str,
+}
+
+impl<'a> DocCleaner<'a> {
+    /// Create a new cleaner
+    pub fn new(pkgname: &'a str) -> Self {
+        Self { pkgname }
+    }
+
+    /// Apply the cleanup to the package root
+    pub fn apply(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        let before = dir_size(pkg_root);
+
+        let removed = self.remove_debian_docs(pkg_root)?;
+        if removed > 0 {
+            report.change(format!("Removed {} Debian-only documentation entries", removed));
+        }
+
+        self.move_copyright(pkg_root, report)?;
+
+        let recompressed = self.recompress_pages(pkg_root)?;
+        if recompressed > 0 {
+            report.change(format!("Recompressed {} man/info pages with gzip", recompressed));
+        }
+
+        let after = dir_size(pkg_root);
+        if before > after {
+            report.change(format!("Documentation cleanup saved {} KiB", (before - after) / 1024));
+        }
+
+        Ok(())
+    }
+
+    /// Remove Debian-only documentation files and directories
+    fn remove_debian_docs(&self, pkg_root: &Path) -> Result<usize> {
+        let mut removed = 0;
+
+        for dir in DEBIAN_ONLY_DIRS {
+            let path = pkg_root.join(dir);
+            if path.is_dir() {
+                fs::remove_dir_all(&path)?;
+                removed += 1;
+            }
+        }
+
+        let doc_dir = pkg_root.join("usr/share/doc");
+        if doc_dir.is_dir() {
+            let mut to_remove = Vec::new();
+            for entry in walkdir::WalkDir::new(&doc_dir) {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy();
+                if !entry.file_type().is_dir() && DEBIAN_ONLY_DOCS.iter().any(|p| name.starts_with(p)) {
+                    to_remove.push(entry.path().to_path_buf());
+                }
+            }
+            for path in to_remove {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+            remove_empty_dirs(&doc_dir)?;
+        }
+
+        // Info directory index is generated by install-info on the target
+        let info_dir = pkg_root.join("usr/share/info/dir");
+        if info_dir.exists() {
+            fs::remove_file(info_dir)?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Move Debian's copyright file to /usr/share/licenses/<pkgname>
+    fn move_copyright(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        let doc_dir = pkg_root.join("usr/share/doc");
+        if !doc_dir.is_dir() {
+            return Ok(());
+        }
+
+        let mut candidates: Vec<PathBuf> = fs::read_dir(&doc_dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path().join("copyright"))
+            .filter(|p| p.is_file())
+            .collect();
+        candidates.sort();
+
+        let Some(copyright) = candidates.first() else {
+            return Ok(());
+        };
+
+        let license_dir = pkg_root.join("usr/share/licenses").join(self.pkgname);
+        let dest = license_dir.join("copyright");
+        if dest.exists() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&license_dir)?;
+        fs::rename(copyright, &dest)?;
+        remove_empty_dirs(&doc_dir)?;
+
+        report.change(format!("Moved copyright file to /usr/share/licenses/{}/", self.pkgname));
+        Ok(())
+    }
+
+    /// Recompress man and info pages with gzip
+    fn recompress_pages(&self, pkg_root: &Path) -> Result<usize> {
+        let mut count = 0;
+
+        for dir in PAGE_DIRS {
+            let base = pkg_root.join(dir);
+            if !base.is_dir() {
+                continue;
+            }
+
+            let mut renamed: Vec<(PathBuf, PathBuf)> = Vec::new();
+            let files: Vec<PathBuf> = walkdir::WalkDir::new(&base)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.into_path())
+                .collect();
+
+            for path in files {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                if name.ends_with(".gz") {
+                    continue;
+                }
+
+                let raw = fs::read(&path)?;
+                let (content, stem) = if let Some(stem) = name.strip_suffix(".bz2") {
+                    let mut out = Vec::new();
+                    bzip2::read::BzDecoder::new(&raw[..]).read_to_end(&mut out)?;
+                    (out, stem.to_string())
+                } else if let Some(stem) = name.strip_suffix(".xz") {
+                    let mut out = Vec::new();
+                    xz2::read::XzDecoder::new(&raw[..]).read_to_end(&mut out)?;
+                    (out, stem.to_string())
+                } else if let Some(stem) = name.strip_suffix(".zst") {
+                    (zstd::decode_all(&raw[..])?, stem.to_string())
+                } else {
+                    (raw, name.clone())
+                };
+
+                let new_path = path.with_file_name(format!("{}.gz", stem));
+                let mut encoder = GzEncoder::new(File::create(&new_path)?, Compression::best());
+                encoder.write_all(&content)?;
+                encoder.finish()?;
+                fs::remove_file(&path)?;
+
+                renamed.push((path, new_path));
+                count += 1;
+            }
+
+            fix_page_symlinks(&base, &renamed)?;
+        }
+
+        Ok(count)
+    }
+}
+
+/// Point symlinks at recompressed pages and give them a .gz suffix
+fn fix_page_symlinks(base: &Path, renamed: &[(PathBuf, PathBuf)]) -> Result<()> {
+    let links: Vec<PathBuf> = walkdir::WalkDir::new(base)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path_is_symlink())
+        .map(|e| e.into_path())
+        .collect();
+
+    for link in links {
+        let target = fs::read_link(&link)?;
+        let resolved = link.parent().unwrap_or(base).join(&target);
+
+        let Some((_, new_target)) = renamed.iter().find(|(old, _)| *old == resolved) else {
+            continue;
+        };
+
+        let new_name = new_target.file_name().unwrap_or_default();
+        let new_link_target = target.with_file_name(new_name);
+        let link_name = link.file_name().unwrap_or_default().to_string_lossy();
+        let stem = [".bz2", ".xz", ".zst"]
+            .iter()
+            .find_map(|ext| link_name.strip_suffix(ext))
+            .unwrap_or(&link_name);
+        let new_link = link.with_file_name(format!("{}.gz", stem));
+
+        fs::remove_file(&link)?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(new_link_target, new_link)?;
+    }
+
+    Ok(())
+}
+
+/// Remove empty directories below (and including) `dir`
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(dir).contents_first(true) {
+        let entry = entry?;
+        if entry.file_type().is_dir() && fs::read_dir(entry.path())?.next().is_none() {
+            fs::remove_dir(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+/// Total size of regular files below `dir`
+fn dir_size(dir: &Path) -> u64 {
+    walkdir::WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.metadata().ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use tempfile::TempDir;
+
+    fn gunzip(path: &Path) -> String {
+        let mut out = String::new();
+        GzDecoder::new(File::open(path).unwrap()).read_to_string(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_removes_debian_docs_and_moves_copyright() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let doc = root.join("usr/share/doc/foo");
+        fs::create_dir_all(&doc).unwrap();
+        fs::create_dir_all(root.join("usr/share/doc-base")).unwrap();
+        fs::write(doc.join("changelog.Debian.gz"), "x").unwrap();
+        fs::write(doc.join("README.Debian"), "x").unwrap();
+        fs::write(doc.join("copyright"), "MIT").unwrap();
+
+        let mut report = ConversionReport::default();
+        DocCleaner::new("foo").apply(root, &mut report).unwrap();
+
+        assert!(!root.join("usr/share/doc-base").exists());
+        assert!(!doc.exists());
+        assert_eq!(
+            fs::read_to_string(root.join("usr/share/licenses/foo/copyright")).unwrap(),
+            "MIT"
+        );
+    }
+
+    #[test]
+    fn test_recompresses_man_pages_and_links() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let man = root.join("usr/share/man/man1");
+        fs::create_dir_all(&man).unwrap();
+        fs::write(man.join("foo.1"), ".TH FOO 1").unwrap();
+        std::os::unix::fs::symlink("foo.1", man.join("bar.1")).unwrap();
+
+        let mut report = ConversionReport::default();
+        DocCleaner::new("foo").apply(root, &mut report).unwrap();
+
+        assert_eq!(gunzip(&man.join("foo.1.gz")), ".TH FOO 1");
+        assert!(!man.join("foo.1").exists());
+        assert_eq!(fs::read_link(man.join("bar.1.gz")).unwrap(), Path::new("foo.1.gz"));
+    }
+}
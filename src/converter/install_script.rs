@@ -8,15 +8,91 @@ use regex::Regex;
 use crate::error::Result;
 use crate::models::{MaintainerScript, PackageMetadata};
 
+use super::Templates;
+
+/// Directories systemd loads units from
+const SYSTEMD_UNIT_DIRS: &[&str] = &[
+    "/usr/lib/systemd/system",
+    "/lib/systemd/system",
+    "/etc/systemd/system",
+];
+
+/// SysV service management command families
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SysvCommand {
+    /// `update-rc.d <service> <action>`
+    UpdateRc,
+    /// `invoke-rc.d`, `service` or `/etc/init.d/<service>`
+    Invoke,
+}
+
+lazy_static::lazy_static! {
+    /// SysV commands in command position, capturing the service, the
+    /// action and trailing arguments/redirections
+    static ref SYSV_COMMANDS: Vec<(SysvCommand, Regex)> = {
+        let pre = r"(?P<pre>(?:^|[;&|({]|\bthen|\belse|\bdo)\s*)";
+        let svc = r"(?P<svc>[\w.@+-]+)";
+        let action = r#"(?P<action>"?\$?\{?[\w-]+\}?"?)(?P<rest>[^;&|\n]*)"#;
+        vec![
+            (SysvCommand::UpdateRc, format!(r"{}(?:/usr/s?bin/)?update-rc\.d\s+(?:-f\s+)?{}\s+{}", pre, svc, action)),
+            (SysvCommand::Invoke, format!(r"{}(?:/usr/s?bin/)?invoke-rc\.d\s+(?:--[\w-]+\s+)*{}\s+{}", pre, svc, action)),
+            (SysvCommand::Invoke, format!(r"{}(?:/usr)?(?:/s?bin/)?service\s+{}\s+{}", pre, svc, action)),
+            (SysvCommand::Invoke, format!(r#"{}"?/etc/init\.d/{}"?\s+{}"#, pre, svc, action)),
+        ]
+        .into_iter()
+        .map(|(kind, re)| (kind, Regex::new(&format!("(?m){}", re)).unwrap()))
+        .collect()
+    };
+}
+
+/// An extra command appended to a .install function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstallHook {
+    /// Target function (e.g. `post_install`)
+    pub function: &'static str,
+    /// Shell command to run
+    pub command: String,
+}
+
+impl InstallHook {
+    /// Create a new hook
+    pub fn new(function: &'static str, command: impl Into<String>) -> Self {
+        Self {
+            function,
+            command: command.into(),
+        }
+    }
+}
+
 /// Generator for Arch Linux .install scripts
 pub struct InstallScriptGenerator<'a> {
     metadata: &'a PackageMetadata,
+    /// Extra commands added by converter passes
+    hooks: Vec<InstallHook>,
+    /// User templates overriding the generated script
+    templates: Option<&'a Templates>,
 }
 
 impl<'a> InstallScriptGenerator<'a> {
     /// Create a new install script generator
     pub fn new(metadata: &'a PackageMetadata) -> Self {
-        Self { metadata }
+        Self {
+            metadata,
+            hooks: Vec::new(),
+            templates: None,
+        }
+    }
+
+    /// Append extra commands to the generated functions
+    pub fn with_hooks(mut self, hooks: &[InstallHook]) -> Self {
+        self.hooks.extend_from_slice(hooks);
+        self
+    }
+
+    /// Render through the user's `install.hbs` template if there is one
+    pub fn with_templates(mut self, templates: &'a Templates) -> Self {
+        self.templates = Some(templates);
+        self
     }
 
     /// Generate the .install script content
@@ -24,7 +100,7 @@ impl<'a> InstallScriptGenerator<'a> {
     pub fn generate(&self) -> Result<Option<String>> {
         let has_scripts = self.metadata.scripts.iter().any(|(_, content)| !content.is_empty());
         
-        if !has_scripts {
+        if !has_scripts && self.hooks.is_empty() {
             return Ok(None);
         }
 
@@ -35,36 +111,55 @@ impl<'a> InstallScriptGenerator<'a> {
         output.push_str("# Converted from Debian maintainer scripts\n\n");
 
         // Generate each function
-        if let Some(content) = self.metadata.get_script(MaintainerScript::PreInst) {
-            output.push_str(&self.generate_function("pre_install", content, false)?);
-            output.push_str(&self.generate_function("pre_upgrade", content, true)?);
-        }
+        let functions = [
+            ("pre_install", MaintainerScript::PreInst, false),
+            ("pre_upgrade", MaintainerScript::PreInst, true),
+            ("post_install", MaintainerScript::PostInst, false),
+            ("post_upgrade", MaintainerScript::PostInst, true),
+            ("pre_remove", MaintainerScript::PreRm, false),
+            ("post_remove", MaintainerScript::PostRm, false),
+        ];
+
+        let notes = self.sysv_notes();
+        let mut bodies = Vec::new();
+
+        for (name, script_type, is_upgrade) in functions {
+            let script = self.metadata.get_script(script_type);
+            let mut hooks: Vec<&str> = self.hooks
+                .iter()
+                .filter(|h| h.function == name)
+                .map(|h| h.command.as_str())
+                .collect();
+            if name == "post_install" {
+                hooks.extend(notes.iter().map(String::as_str));
+            }
 
-        if let Some(content) = self.metadata.get_script(MaintainerScript::PostInst) {
-            output.push_str(&self.generate_function("post_install", content, false)?);
-            output.push_str(&self.generate_function("post_upgrade", content, true)?);
-        }
+            if script.is_none() && hooks.is_empty() {
+                continue;
+            }
 
-        if let Some(content) = self.metadata.get_script(MaintainerScript::PreRm) {
-            output.push_str(&self.generate_function("pre_remove", content, false)?);
+            let body = self.generate_body(script.unwrap_or(""), is_upgrade, &hooks)?;
+            output.push_str(&format!("{}() {{\n{}\n}}\n\n", name, body));
+            bodies.push((name, body));
         }
 
-        if let Some(content) = self.metadata.get_script(MaintainerScript::PostRm) {
-            output.push_str(&self.generate_function("post_remove", content, false)?);
+        match self.templates {
+            Some(templates) => templates.render_install(self.metadata, &bodies, output).map(Some),
+            None => Ok(Some(output)),
         }
-
-        Ok(Some(output))
     }
 
-    /// Generate a single function
-    fn generate_function(&self, name: &str, content: &str, is_upgrade: bool) -> Result<String> {
-        let translated = self.translate_script(content, is_upgrade)?;
-        
-        Ok(format!(
-            "{}() {{\n{}\n}}\n\n",
-            name,
-            self.indent(&translated, 2)
-        ))
+    /// Generate the indented body of a single function
+    fn generate_body(&self, content: &str, is_upgrade: bool, hooks: &[&str]) -> Result<String> {
+        let mut translated = self.translate_script(content, is_upgrade)?;
+        for hook in hooks {
+            if !translated.is_empty() {
+                translated.push('\n');
+            }
+            translated.push_str(hook);
+        }
+
+        Ok(self.indent(&translated, 2))
     }
 
     /// Translate Debian script content to Arch-compatible bash
@@ -152,14 +247,6 @@ impl<'a> InstallScriptGenerator<'a> {
                 r#"dpkg-maintscript-helper\s+(\w+)\s+([^\s]+)"#
             ).unwrap();
             
-            static ref UPDATE_RC: Regex = Regex::new(
-                r#"update-rc\.d\s+(\S+)\s+(\S+)"#
-            ).unwrap();
-            
-            static ref INVOKE_RC: Regex = Regex::new(
-                r#"invoke-rc\.d\s+(\S+)\s+(\S+)"#
-            ).unwrap();
-            
             static ref SYSTEMCTL: Regex = Regex::new(
                 r#"systemctl\s+(enable|disable|start|stop|restart|reload)\s+(\S+)"#
             ).unwrap();
@@ -184,29 +271,8 @@ impl<'a> InstallScriptGenerator<'a> {
             return Ok(format!("# Skipped dpkg command: {}", line.trim()));
         }
 
-        // update-rc.d -> systemctl
-        if let Some(caps) = UPDATE_RC.captures(&line) {
-            let service = &caps[1];
-            let action = &caps[2];
-            match action {
-                "defaults" | "enable" => {
-                    line = format!("systemctl enable {}.service 2>/dev/null || true", service);
-                }
-                "remove" | "disable" => {
-                    line = format!("systemctl disable {}.service 2>/dev/null || true", service);
-                }
-                _ => {
-                    line = format!("# update-rc.d {} {}", service, action);
-                }
-            }
-        }
-
-        // invoke-rc.d -> systemctl
-        if let Some(caps) = INVOKE_RC.captures(&line) {
-            let service = &caps[1];
-            let action = &caps[2];
-            line = format!("systemctl {} {}.service 2>/dev/null || true", action, service);
-        }
+        // update-rc.d, invoke-rc.d, service, /etc/init.d -> systemctl
+        line = self.translate_sysv(&line);
 
         // Ensure ldconfig is present for library packages
         if LDCONFIG.is_match(&line) {
@@ -234,17 +300,106 @@ impl<'a> InstallScriptGenerator<'a> {
         Ok(line)
     }
 
+    /// Rewrite SysV service management commands into systemctl calls
+    ///
+    /// Arch's systemd does not run SysV init scripts, so commands for
+    /// services without a native unit are replaced by a no-op. Enabling
+    /// services is left to the user, as Arch packages never do it.
+    fn translate_sysv(&self, line: &str) -> String {
+        let mut line = line.to_string();
+
+        for (kind, re) in SYSV_COMMANDS.iter() {
+            line = re
+                .replace_all(&line, |caps: &regex::Captures| {
+                    let service = &caps["svc"];
+                    let action = caps["action"].trim_matches('"');
+                    let command = caps[0][caps["pre"].len()..].trim();
+
+                    let replacement = match (self.has_unit(service), kind, action) {
+                        (true, SysvCommand::UpdateRc, "remove" | "disable") => {
+                            Some(format!("systemctl disable {}.service 2>/dev/null || true", service))
+                        }
+                        (true, SysvCommand::UpdateRc, _) => None,
+                        (true, _, action) => {
+                            Some(format!("systemctl {} {}.service 2>/dev/null || true", action, service))
+                        }
+                        (false, _, _) => None,
+                    };
+
+                    let replacement = replacement.unwrap_or_else(|| {
+                        if caps.get(0).is_some_and(|m| m.end() == line.len()) {
+                            format!("true  # dropped: {}", command)
+                        } else {
+                            "true".to_string()
+                        }
+                    });
+
+                    let sep = if caps["rest"].ends_with(char::is_whitespace) { " " } else { "" };
+                    format!("{}{}{}", &caps["pre"], replacement, sep)
+                })
+                .to_string();
+        }
+
+        line
+    }
+
+    /// Check whether the package ships a systemd unit for a service
+    fn has_unit(&self, service: &str) -> bool {
+        let unit = format!("{}.service", service);
+        self.metadata.files.iter().any(|f| {
+            f.file_name().is_some_and(|n| n.to_string_lossy() == unit)
+                && f.parent().is_some_and(|p| {
+                    SYSTEMD_UNIT_DIRS.iter().any(|d| p == std::path::Path::new(d))
+                })
+        })
+    }
+
+    /// Post-install messages for SysV services touched by the scripts
+    fn sysv_notes(&self) -> Vec<String> {
+        use std::collections::BTreeSet;
+
+        let mut sysv_only = BTreeSet::new();
+        let mut enable = BTreeSet::new();
+
+        for content in self.metadata.scripts.values() {
+            for (kind, re) in SYSV_COMMANDS.iter() {
+                for caps in re.captures_iter(content) {
+                    let service = caps["svc"].to_string();
+                    if !self.has_unit(&service) {
+                        sysv_only.insert(service);
+                    } else if *kind == SysvCommand::UpdateRc
+                        && matches!(&caps["action"], "defaults" | "enable" | "defaults-disabled")
+                    {
+                        enable.insert(service);
+                    }
+                }
+            }
+        }
+
+        let mut notes: Vec<String> = sysv_only
+            .into_iter()
+            .map(|s| format!(
+                "echo \"==> {} only ships a SysV init script, which systemd on Arch does not run\"",
+                s
+            ))
+            .collect();
+        notes.extend(enable.into_iter().map(|s| {
+            format!("echo \"==> To start {} at boot: systemctl enable --now {}.service\"", s, s)
+        }));
+        notes
+    }
+
     /// Clean up the script output
     fn cleanup_script(&self, script: &str) -> String {
         let mut lines: Vec<&str> = script.lines().collect();
         
         // Remove trailing empty lines
-        while lines.last().map_or(false, |l| l.trim().is_empty()) {
+        while lines.last().is_some_and(|l| l.trim().is_empty()) {
             lines.pop();
         }
 
         // Remove leading empty lines
-        while lines.first().map_or(false, |l| l.trim().is_empty()) {
+        while lines.first().is_some_and(|l| l.trim().is_empty()) {
             lines.remove(0);
         }
 
@@ -289,6 +444,7 @@ mod tests {
     fn test_translate_systemctl() {
         let mut metadata = PackageMetadata::new("test", "1.0");
         metadata.scripts = HashMap::new();
+        metadata.files = vec!["/lib/systemd/system/myservice.service".into()];
         
         let generator = InstallScriptGenerator::new(&metadata);
         
@@ -309,6 +465,50 @@ mod tests {
         assert!(result.contains("ldconfig"));
     }
 
+    #[test]
+    fn test_translate_sysv_commands() {
+        let mut metadata = PackageMetadata::new("test", "1.0");
+        metadata.files = vec!["/lib/systemd/system/foo.service".into()];
+        metadata.scripts.insert(
+            MaintainerScript::PostInst,
+            "update-rc.d foo defaults >/dev/null\nupdate-rc.d bar defaults\n".to_string(),
+        );
+        let generator = InstallScriptGenerator::new(&metadata);
+
+        assert_eq!(
+            generator.translate_line("if [ -x /etc/init.d/foo ]; then /etc/init.d/foo restart; fi").unwrap(),
+            "if [ -x /etc/init.d/foo ]; then systemctl restart foo.service 2>/dev/null || true; fi"
+        );
+        assert_eq!(
+            generator.translate_line("  invoke-rc.d --skip-systemd-native foo $_dh_action || exit 1").unwrap(),
+            "  systemctl $_dh_action foo.service 2>/dev/null || true || exit 1"
+        );
+        assert_eq!(
+            generator.translate_line("service bar stop").unwrap(),
+            "true  # dropped: service bar stop"
+        );
+        assert_eq!(
+            generator.translate_line("update-rc.d -f foo remove >/dev/null").unwrap(),
+            "systemctl disable foo.service 2>/dev/null || true"
+        );
+
+        let output = generator.generate().unwrap().unwrap();
+        assert!(output.contains("true  # dropped: update-rc.d foo defaults >/dev/null"));
+        assert!(output.contains("bar only ships a SysV init script"));
+        assert!(output.contains("systemctl enable --now foo.service"));
+    }
+
+    #[test]
+    fn test_hooks_without_scripts() {
+        let metadata = PackageMetadata::new("test", "1.0");
+        let hooks = [InstallHook::new("post_install", "echo hi")];
+        let generator = InstallScriptGenerator::new(&metadata).with_hooks(&hooks);
+
+        let output = generator.generate().unwrap().unwrap();
+        assert!(output.contains("post_install() {\n  echo hi\n}"));
+        assert!(!output.contains("pre_remove"));
+    }
+
     #[test]
     fn test_indent() {
         let metadata = PackageMetadata::new("test", "1.0");
@@ -0,0 +1,226 @@
+//! Kernel module and DKMS payloads
+//!
+//! Prebuilt `.ko` files are tied to the exact kernel they were built for,
+//! which is never an Arch kernel, so a converted binary package cannot
+//! load them. Source shipped for DKMS (`/usr/src/<name>-<version>/dkms.conf`)
+//! is rebuilt by the hooks of Arch's `dkms` package, which makes a
+//! `<name>-dkms` PKGBUILD the package to convert such a deb into.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::models::{DependencyType, PackageMetadata};
+
+use super::ConversionReport;
+
+/// Directories holding modules, relative to the package root
+const MODULE_DIRS: &[&str] = &["lib/modules", "usr/lib/modules"];
+
+/// Module source registered with DKMS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DkmsSource {
+    /// Source directory, relative to the package root
+    pub dir: PathBuf,
+    /// `PACKAGE_NAME` from dkms.conf
+    pub name: String,
+    /// `PACKAGE_VERSION` from dkms.conf
+    pub version: String,
+}
+
+/// Kernel modules and DKMS sources in a payload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KernelModules {
+    /// Prebuilt modules with the kernel release they were built for
+    pub prebuilt: Vec<(PathBuf, String)>,
+    /// DKMS module sources
+    pub dkms: Vec<DkmsSource>,
+}
+
+impl KernelModules {
+    /// Find the modules and DKMS sources below a package root
+    pub fn detect(root: &Path) -> Result<Self> {
+        let mut found = Self::default();
+
+        for dir in MODULE_DIRS {
+            let dir = root.join(dir);
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in walkdir::WalkDir::new(&dir).sort_by_file_name() {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy();
+                if !entry.file_type().is_file() || !is_module(&name) {
+                    continue;
+                }
+                let rel = entry.path().strip_prefix(&dir).unwrap_or(entry.path());
+                let release = rel
+                    .components()
+                    .next()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                found
+                    .prebuilt
+                    .push((entry.path().strip_prefix(root).unwrap_or(entry.path()).to_path_buf(), release));
+            }
+        }
+
+        let src = root.join("usr/src");
+        if src.is_dir() {
+            let mut dirs: Vec<_> = fs::read_dir(&src)?.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+            dirs.sort();
+            for dir in dirs {
+                let Ok(conf) = fs::read_to_string(dir.join("dkms.conf")) else {
+                    continue;
+                };
+                let dir_name = dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                let (fallback_name, fallback_version) = dir_name.rsplit_once('-').unwrap_or((&dir_name, ""));
+                found.dkms.push(DkmsSource {
+                    name: conf_value(&conf, "PACKAGE_NAME").unwrap_or_else(|| fallback_name.to_string()),
+                    version: conf_value(&conf, "PACKAGE_VERSION").unwrap_or_else(|| fallback_version.to_string()),
+                    dir: dir.strip_prefix(root).unwrap_or(&dir).to_path_buf(),
+                });
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Whether there is nothing kernel-related
+    pub fn is_empty(&self) -> bool {
+        self.prebuilt.is_empty() && self.dkms.is_empty()
+    }
+
+    /// Kernel releases the prebuilt modules target
+    pub fn kernel_releases(&self) -> Vec<&str> {
+        let mut releases: Vec<&str> = self.prebuilt.iter().map(|(_, r)| r.as_str()).collect();
+        releases.sort_unstable();
+        releases.dedup();
+        releases
+    }
+
+    /// Warn about what the binary package will not do on Arch
+    pub fn apply(&self, report: &mut ConversionReport) {
+        if !self.prebuilt.is_empty() {
+            report.warn(format!(
+                "{} prebuilt kernel module(s) for {} will not load on Arch kernels; {}",
+                self.prebuilt.len(),
+                self.kernel_releases().join(", "),
+                match self.dkms.is_empty() {
+                    true => "rebuild them from source",
+                    false => "convert with --pkgbuild for a DKMS package instead",
+                }
+            ));
+        }
+        for source in &self.dkms {
+            report.warn(format!(
+                "/{} is DKMS source; Arch's dkms hooks build it only if dkms is a dependency",
+                source.dir.display()
+            ));
+        }
+    }
+}
+
+/// Whether a file name is a (possibly compressed) kernel module
+fn is_module(name: &str) -> bool {
+    [".ko", ".ko.xz", ".ko.zst", ".ko.gz"].iter().any(|ext| name.ends_with(ext))
+}
+
+/// Unquoted value of a `KEY="value"` line in dkms.conf
+fn conf_value(conf: &str, key: &str) -> Option<String> {
+    conf.lines().find_map(|line| {
+        let value = line.trim().strip_prefix(key)?.strip_prefix('=')?;
+        let value = value.trim().trim_matches(['"', '\'']);
+        // `PACKAGE_VERSION="#MODULE_VERSION#"` is filled in by Debian's build
+        (!value.is_empty() && !value.contains('#')).then(|| value.to_string())
+    })
+}
+
+/// PKGBUILD of a `<name>-dkms` package shipping the module source
+///
+/// The payload is copied as in the binary PKGBUILD, minus the prebuilt
+/// modules; Arch's dkms hooks build the source for every installed kernel.
+pub fn dkms_pkgbuild(metadata: &PackageMetadata, sources: &[DkmsSource]) -> String {
+    let name = metadata.effective_name();
+    let pkgname = match name.ends_with("-dkms") {
+        true => name.to_string(),
+        false => format!("{}-dkms", name),
+    };
+    let module_names: Vec<String> = sources.iter().map(|s| format!("'{}'", s.name)).collect();
+
+    let mut depends = vec!["'dkms'".to_string()];
+    for dep in [DependencyType::Depends, DependencyType::PreDepends]
+        .into_iter()
+        .flat_map(|t| metadata.get_deps(t))
+    {
+        let entry = format!("'{}'", dep.to_arch_string());
+        if dep.effective_name() != "dkms" && !depends.contains(&entry) {
+            depends.push(entry);
+        }
+    }
+
+    let mut lines = vec!["# Maintainer: Converted by rexeb".to_string()];
+    if let Some(ref maintainer) = metadata.maintainer {
+        lines.push(format!("# Original: {}", maintainer));
+    }
+    lines.push(String::new());
+    lines.push(format!("pkgname={}", pkgname));
+    if let Some(epoch) = metadata.epoch.filter(|e| *e > 0) {
+        lines.push(format!("epoch={}", epoch));
+    }
+    lines.push(format!("pkgver={}", metadata.version));
+    lines.push(format!("pkgrel={}", metadata.release));
+    lines.push(format!("pkgdesc=\"{} (DKMS)\"", metadata.description.replace('"', "\\\"")));
+    lines.push("arch=('any')".to_string());
+    if let Some(ref url) = metadata.url {
+        lines.push(format!("url=\"{}\"", url));
+    }
+    lines.push(format!("license=('{}')", metadata.license.to_pkgbuild()));
+    lines.push(format!("depends=({})", depends.join(" ")));
+    lines.push(format!("provides=({})", module_names.join(" ")));
+    lines.push(format!("conflicts=({})", module_names.join(" ")));
+    lines.push(String::new());
+    lines.push("package() {".to_string());
+    lines.push("    cp -a \"$srcdir\"/* \"$pkgdir\"/".to_string());
+    lines.push("    # Prebuilt modules only load on the kernel they were built for".to_string());
+    lines.push("    rm -rf \"$pkgdir\"/lib/modules \"$pkgdir\"/usr/lib/modules".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_detect_kernel_modules() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("lib/modules/6.1.0-18-amd64/updates")).unwrap();
+        fs::write(root.join("lib/modules/6.1.0-18-amd64/updates/foo.ko"), "").unwrap();
+        fs::create_dir_all(root.join("usr/src/foo-1.2")).unwrap();
+        fs::write(
+            root.join("usr/src/foo-1.2/dkms.conf"),
+            "PACKAGE_NAME=\"foo\"\nPACKAGE_VERSION=\"#MODULE_VERSION#\"\nBUILT_MODULE_NAME[0]=\"foo\"\n",
+        )
+        .unwrap();
+
+        let modules = KernelModules::detect(root).unwrap();
+        assert_eq!(modules.kernel_releases(), ["6.1.0-18-amd64"]);
+        assert_eq!(
+            modules.dkms,
+            [DkmsSource {
+                dir: PathBuf::from("usr/src/foo-1.2"),
+                name: "foo".to_string(),
+                version: "1.2".to_string(),
+            }]
+        );
+
+        let metadata = PackageMetadata::new("foo", "1.2");
+        let pkgbuild = dkms_pkgbuild(&metadata, &modules.dkms);
+        assert!(pkgbuild.contains("pkgname=foo-dkms\n"));
+        assert!(pkgbuild.contains("depends=('dkms')\n"));
+    }
+}
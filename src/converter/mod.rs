@@ -1,7 +1,44 @@
 //! Package converter for building Arch Linux packages
 
+mod arch;
 mod builder;
+mod cache;
+mod census;
+mod desktop;
+mod docs;
 mod install_script;
+mod kernel;
+mod mtree;
+mod multiarch;
+mod namcap;
+mod prefix;
+mod python;
+mod relocate;
+mod report;
+mod security;
+mod shebang;
+mod simulate;
+mod systemd;
+mod templates;
+mod usrmerge;
 
+pub use arch::*;
 pub use builder::*;
-pub use install_script::*;
\ No newline at end of file
+pub use cache::*;
+pub use census::*;
+pub use desktop::*;
+pub use docs::*;
+pub use install_script::*;
+pub use kernel::*;
+pub use mtree::*;
+pub use multiarch::*;
+pub use namcap::*;
+pub use prefix::*;
+pub use python::*;
+pub use report::*;
+pub use security::*;
+pub use shebang::*;
+pub use simulate::*;
+pub use systemd::*;
+pub use templates::*;
+pub use usrmerge::*;
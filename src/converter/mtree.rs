@@ -0,0 +1,404 @@
+//! .MTREE generation
+//!
+//! Produces the same output as makepkg, which runs
+//! `bsdtar --format=mtree --options='!all,use-set,type,uid,gid,mode,time,size,md5,sha256,link'`
+//! over the package root and gzips the result. pacman and paccheck compare
+//! installed files against these entries, so the format follows libarchive
+//! byte for byte: entries are grouped per directory (files before
+//! subdirectories), `/set` lines carry the most common mode among a
+//! directory's children, and names are escaped as octal.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::Result;
+
+/// Kind of an .MTREE entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MtreeKind {
+    /// Regular file
+    File {
+        /// Size in bytes
+        size: u64,
+        /// Hex-encoded MD5 digest
+        md5: String,
+        /// Hex-encoded SHA-256 digest
+        sha256: String,
+    },
+    /// Directory
+    Dir,
+    /// Symbolic link
+    Link {
+        /// Link target as stored on disk
+        target: PathBuf,
+    },
+}
+
+/// A single .MTREE entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MtreeEntry {
+    /// Path relative to the package root
+    pub path: PathBuf,
+    /// Entry kind
+    pub kind: MtreeKind,
+    /// Permission bits
+    pub mode: u32,
+    /// Modification time, seconds
+    pub mtime: i64,
+    /// Modification time, nanoseconds
+    pub mtime_nsec: u32,
+}
+
+impl MtreeEntry {
+    /// Build an entry from a file below the package root
+    pub fn from_path(pkg_root: &Path, path: &Path) -> Result<Self> {
+        let metadata = path.symlink_metadata()?;
+        let rel = path.strip_prefix(pkg_root).unwrap_or(path).to_path_buf();
+
+        let kind = if metadata.file_type().is_symlink() {
+            MtreeKind::Link { target: fs::read_link(path)? }
+        } else if metadata.is_dir() {
+            MtreeKind::Dir
+        } else {
+            let (md5, sha256) = digest_file(path)?;
+            MtreeKind::File { size: metadata.len(), md5, sha256 }
+        };
+
+        #[cfg(unix)]
+        let (mode, mtime, mtime_nsec) = {
+            use std::os::unix::fs::MetadataExt;
+            (metadata.mode() & 0o7777, metadata.mtime(), metadata.mtime_nsec() as u32)
+        };
+        #[cfg(not(unix))]
+        let (mode, mtime, mtime_nsec) = {
+            let mode = if metadata.is_dir() { 0o755 } else { 0o644 };
+            let mtime = metadata
+                .modified()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default();
+            (mode, mtime.as_secs() as i64, mtime.subsec_nanos())
+        };
+
+        Ok(Self { path: rel, kind, mode, mtime, mtime_nsec })
+    }
+
+    /// File name bytes, used for libarchive's C-locale ordering
+    fn name(&self) -> String {
+        self.path.file_name().unwrap_or_default().to_string_lossy().to_string()
+    }
+
+    fn is_dir(&self) -> bool {
+        self.kind == MtreeKind::Dir
+    }
+}
+
+/// Writer producing makepkg-compatible .MTREE files
+#[derive(Debug, Clone, Default)]
+pub struct MtreeWriter {
+    /// Entries to write
+    entries: Vec<MtreeEntry>,
+}
+
+impl MtreeWriter {
+    /// Create a writer from a list of entries
+    pub fn new(entries: Vec<MtreeEntry>) -> Self {
+        Self { entries }
+    }
+
+    /// Collect entries for everything below the package root except .MTREE
+    pub fn from_dir(pkg_root: &Path) -> Result<Self> {
+        let mut entries = Vec::new();
+
+        for entry in walkdir::WalkDir::new(pkg_root)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| e.depth() != 1 || e.file_name() != ".MTREE")
+        {
+            let entry = entry?;
+            entries.push(MtreeEntry::from_path(pkg_root, entry.path())?);
+        }
+
+        Ok(Self::new(entries))
+    }
+
+    /// Render the uncompressed .MTREE text
+    pub fn render(&self) -> String {
+        let mut children: HashMap<PathBuf, Vec<&MtreeEntry>> = HashMap::new();
+        for entry in &self.entries {
+            let parent = entry.path.parent().unwrap_or(Path::new("")).to_path_buf();
+            children.entry(parent).or_default().push(entry);
+        }
+        for list in children.values_mut() {
+            list.sort_by_key(|a| a.name());
+        }
+
+        let mut out = String::from("#mtree\n");
+        let mut set_mode = None;
+        let root = Path::new("");
+        emit_set(children.get(root), &mut set_mode, &mut out);
+        render_dir(root, &children, &mut set_mode, &mut out);
+        out
+    }
+
+    /// Write the gzip-compressed .MTREE file
+    pub fn write(&self, path: &Path) -> Result<()> {
+        // Like `gzip -n`: no file name, zero timestamp
+        let file = File::create(path)?;
+        let mut encoder = flate2::write::GzEncoder::new(file, flate2::Compression::default());
+        encoder.write_all(self.render().as_bytes())?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
+
+/// Write all entries of a directory: files first, then subdirectories
+fn render_dir(
+    dir: &Path,
+    children: &HashMap<PathBuf, Vec<&MtreeEntry>>,
+    set_mode: &mut Option<u32>,
+    out: &mut String,
+) {
+    let Some(entries) = children.get(dir) else {
+        return;
+    };
+
+    for entry in entries.iter().filter(|e| !e.is_dir()) {
+        write_entry(entry, set_mode.unwrap_or(0o644), out);
+    }
+
+    for entry in entries.iter().filter(|e| e.is_dir()) {
+        emit_set(children.get(&entry.path), set_mode, out);
+        write_entry(entry, set_mode.unwrap_or(0o644), out);
+        render_dir(&entry.path, children, set_mode, out);
+    }
+}
+
+/// Emit a `/set` line if the most common mode among `entries` changed
+///
+/// Mirrors libarchive's attribute counter: values are ranked by count and
+/// a later value only overtakes an earlier one with a strictly higher
+/// count. Once the initial `/set` is written, it only changes when the
+/// winning mode is used at least twice.
+fn emit_set(entries: Option<&Vec<&MtreeEntry>>, set_mode: &mut Option<u32>, out: &mut String) {
+    let mut counts: Vec<(u32, usize)> = Vec::new();
+    for entry in entries.into_iter().flatten() {
+        let idx = match counts.iter().position(|(m, _)| *m == entry.mode) {
+            Some(idx) => idx,
+            None => {
+                counts.push((entry.mode, 0));
+                counts.len() - 1
+            }
+        };
+        counts[idx].1 += 1;
+
+        let mut i = idx;
+        while i > 0 && counts[i - 1].1 < counts[i].1 {
+            counts.swap(i - 1, i);
+            i -= 1;
+        }
+    }
+
+    match (*set_mode, counts.first()) {
+        (None, best) => {
+            let mode = best.map(|(m, _)| *m).unwrap_or(0o644);
+            out.push_str(&format!("/set type=file uid=0 gid=0 mode={:o}\n", mode));
+            *set_mode = Some(mode);
+        }
+        (Some(current), Some(&(mode, count))) if count >= 2 && mode != current => {
+            out.push_str(&format!("/set mode={:o}\n", mode));
+            *set_mode = Some(mode);
+        }
+        _ => {}
+    }
+}
+
+/// Write a single entry line, omitting values covered by `/set`
+fn write_entry(entry: &MtreeEntry, set_mode: u32, out: &mut String) {
+    out.push_str("./");
+    out.push_str(&escape(&entry.path.to_string_lossy()));
+
+    if entry.mtime_nsec > 0 {
+        out.push_str(&format!(" time={}.{:09}", entry.mtime, entry.mtime_nsec));
+    } else {
+        out.push_str(&format!(" time={}.0", entry.mtime));
+    }
+
+    if entry.mode != set_mode {
+        out.push_str(&format!(" mode={:o}", entry.mode));
+    }
+
+    match &entry.kind {
+        MtreeKind::Link { target } => {
+            out.push_str(" type=link link=");
+            out.push_str(&escape(&target.to_string_lossy()));
+        }
+        MtreeKind::Dir => out.push_str(" type=dir"),
+        MtreeKind::File { size, md5, sha256 } => {
+            out.push_str(&format!(" size={} md5digest={} sha256digest={}", size, md5, sha256));
+        }
+    }
+
+    out.push('\n');
+}
+
+/// Escape a name the way libarchive's mtree writer does
+fn escape(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for &b in name.as_bytes() {
+        if b <= b' ' || b >= 0x7f || matches!(b, b'#' | b'=' | b'\\') {
+            out.push_str(&format!("\\{:03o}", b));
+        } else {
+            out.push(b as char);
+        }
+    }
+    out
+}
+
+/// Compute MD5 and SHA-256 digests of a file in one pass
+fn digest_file(path: &Path) -> Result<(String, String)> {
+    let mut file = File::open(path)?;
+    let mut md5 = md5::Context::new();
+    let mut sha256 = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        md5.consume(&buf[..n]);
+        sha256.update(&buf[..n]);
+    }
+
+    Ok((format!("{:x}", md5.compute()), hex::encode(sha256.finalize())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn file(path: &str, mode: u32, content: &str) -> MtreeEntry {
+        let md5 = format!("{:x}", md5::compute(content));
+        let sha256 = hex::encode(Sha256::digest(content.as_bytes()));
+        MtreeEntry {
+            path: PathBuf::from(path),
+            kind: MtreeKind::File { size: content.len() as u64, md5, sha256 },
+            mode,
+            mtime: 1700000000,
+            mtime_nsec: 0,
+        }
+    }
+
+    fn dir(path: &str, mode: u32) -> MtreeEntry {
+        MtreeEntry {
+            path: PathBuf::from(path),
+            kind: MtreeKind::Dir,
+            mode,
+            mtime: 1700000000,
+            mtime_nsec: 0,
+        }
+    }
+
+    #[test]
+    fn test_matches_bsdtar_output() {
+        // Expected output generated with bsdtar 3.8.2 using makepkg's options
+        let entries = vec![
+            file(".BUILDINFO", 0o644, "b"),
+            file(".PKGINFO", 0o644, "a"),
+            dir("usr", 0o755),
+            dir("usr/bin", 0o755),
+            file("usr/bin/foo", 0o755, "c"),
+            dir("usr/lib", 0o755),
+            MtreeEntry {
+                path: PathBuf::from("usr/lib/libfoo.so"),
+                kind: MtreeKind::Link { target: PathBuf::from("libfoo.so.1") },
+                mode: 0o777,
+                mtime: 1700000000,
+                mtime_nsec: 0,
+            },
+            file("usr/lib/libfoo.so.1", 0o644, "e"),
+            dir("usr/share", 0o755),
+            dir("usr/share/doc", 0o755),
+            dir("usr/share/doc/foo", 0o755),
+            file("usr/share/doc/foo/README", 0o644, "d"),
+            file("zroot", 0o644, "f"),
+        ];
+
+        let expected = "\
+#mtree
+/set type=file uid=0 gid=0 mode=644
+./.BUILDINFO time=1700000000.0 size=1 md5digest=92eb5ffee6ae2fec3ad71c777531578f sha256digest=3e23e8160039594a33894f6564e1b1348bbd7a0088d42c4acb73eeaed59c009d
+./.PKGINFO time=1700000000.0 size=1 md5digest=0cc175b9c0f1b6a831c399e269772661 sha256digest=ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb
+./zroot time=1700000000.0 size=1 md5digest=8fa14cdd754f91cc6554c9e71929cce7 sha256digest=252f10c83610ebca1a059c0bae8255eba2f95be4d1d7bcfa89d7248a82d9f111
+/set mode=755
+./usr time=1700000000.0 type=dir
+./usr/bin time=1700000000.0 type=dir
+./usr/bin/foo time=1700000000.0 size=1 md5digest=4a8a08f09d37b73795649038408b5f33 sha256digest=2e7d2c03a9507ae265ecf5b5356885a53393a2029d241394997265a1a25aefc6
+./usr/lib time=1700000000.0 type=dir
+./usr/lib/libfoo.so time=1700000000.0 mode=777 type=link link=libfoo.so.1
+./usr/lib/libfoo.so.1 time=1700000000.0 mode=644 size=1 md5digest=e1671797c52e15f763380b45e841ec32 sha256digest=3f79bb7b435b05321651daefd374cdc681dc06faa65e374e38337b88ca046dea
+./usr/share time=1700000000.0 type=dir
+./usr/share/doc time=1700000000.0 type=dir
+./usr/share/doc/foo time=1700000000.0 type=dir
+./usr/share/doc/foo/README time=1700000000.0 mode=644 size=1 md5digest=8277e0910d750195b448797616e091ad sha256digest=18ac3e7343f016890c510e93f935261169d9e3f565436429830faf0934f4f8e4
+";
+        assert_eq!(MtreeWriter::new(entries).render(), expected);
+    }
+
+    #[test]
+    fn test_set_changes_and_escaping() {
+        // Expected output generated with bsdtar 3.8.2
+        let mut late = file("a/1", 0o644, "q");
+        late.mtime_nsec = 500_000_000;
+        let entries = vec![
+            file(".PKGINFO", 0o644, "x"),
+            dir("a", 0o755),
+            late,
+            file("a/2", 0o644, "q"),
+            dir("b", 0o700),
+            file("b/x y=z", 0o644, "q"),
+            dir("c", 0o755),
+        ];
+
+        let rendered = MtreeWriter::new(entries).render();
+        let lines: Vec<_> = rendered.lines().map(|l| l.split(" size=").next().unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "#mtree",
+                "/set type=file uid=0 gid=0 mode=755",
+                "./.PKGINFO time=1700000000.0 mode=644",
+                "/set mode=644",
+                "./a time=1700000000.0 mode=755 type=dir",
+                "./a/1 time=1700000000.500000000",
+                "./a/2 time=1700000000.0",
+                "./b time=1700000000.0 mode=700 type=dir",
+                "./b/x\\040y\\075z time=1700000000.0",
+                "./c time=1700000000.0 mode=755 type=dir",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_dir_skips_mtree() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join(".PKGINFO"), "pkgname = foo\n").unwrap();
+        fs::write(root.join(".MTREE"), "").unwrap();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::write(root.join("usr/bin/foo"), "bin").unwrap();
+
+        let writer = MtreeWriter::from_dir(root).unwrap();
+        let rendered = writer.render();
+
+        assert!(rendered.contains("./.PKGINFO "));
+        assert!(rendered.contains("./usr/bin/foo "));
+        assert!(!rendered.contains(".MTREE"));
+        assert_eq!(writer.entries.len(), 4);
+    }
+}
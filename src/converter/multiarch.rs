@@ -0,0 +1,220 @@
+//! Multiarch library path remapping
+//!
+//! Debian installs libraries into triplet directories such as
+//! `/usr/lib/x86_64-linux-gnu/`, which are not on Arch's linker path.
+//! This pass either moves their contents into `/usr/lib` or ships an
+//! `ld.so.conf.d` snippet so the dynamic linker can find them.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{RexebError, Result};
+
+use super::relocate::{merge_tree, rewrite_symlinks};
+use super::ConversionReport;
+
+/// Debian multiarch tuples that may appear in package payloads
+pub const MULTIARCH_TRIPLETS: &[&str] = &[
+    "x86_64-linux-gnu",
+    "i386-linux-gnu",
+    "aarch64-linux-gnu",
+    "arm-linux-gnueabihf",
+];
+
+/// How to deal with multiarch library directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultiarchStrategy {
+    /// Move the contents into the parent lib directory
+    Relocate,
+    /// Keep the layout and register the directories with ld.so
+    LdConfig,
+    /// Leave the payload untouched
+    Keep,
+}
+
+impl MultiarchStrategy {
+    /// Parse the strategy from its configuration name
+    pub fn from_config(value: &str) -> Result<Self> {
+        match value {
+            "relocate" => Ok(Self::Relocate),
+            "ldconfig" => Ok(Self::LdConfig),
+            "keep" => Ok(Self::Keep),
+            _ => Err(RexebError::Config(format!(
+                "Invalid multiarch strategy '{}' (expected relocate, ldconfig or keep)",
+                value
+            ))),
+        }
+    }
+}
+
+/// Converter pass handling multiarch triplet directories
+pub struct MultiarchRemapper<'a> {
+    /// Strategy to apply
+    strategy: MultiarchStrategy,
+    /// Package name, used for the ld.so.conf.d snippet
+    pkgname: &'a str,
+}
+
+impl<'a> MultiarchRemapper<'a> {
+    /// Create a new remapper
+    pub fn new(strategy: MultiarchStrategy, pkgname: &'a str) -> Self {
+        Self { strategy, pkgname }
+    }
+
+    /// Apply the configured strategy to the package root
+    pub fn apply(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        match self.strategy {
+            MultiarchStrategy::Relocate => self.relocate(pkg_root, report),
+            MultiarchStrategy::LdConfig => self.write_ld_conf(pkg_root, report),
+            MultiarchStrategy::Keep => Ok(()),
+        }
+    }
+
+    /// Find triplet directories present in the payload, relative to the root
+    ///
+    /// Runs after the usrmerge, so `/lib/<triplet>` is already under `usr/lib`.
+    fn find_triplet_dirs(pkg_root: &Path) -> Vec<String> {
+        MULTIARCH_TRIPLETS
+            .iter()
+            .map(|triplet| format!("usr/lib/{}", triplet))
+            .filter(|rel| pkg_root.join(rel).symlink_metadata().map(|m| m.is_dir()).unwrap_or(false))
+            .collect()
+    }
+
+    /// Move triplet directory contents into the parent lib directory
+    fn relocate(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        for rel in Self::find_triplet_dirs(pkg_root) {
+            let src = pkg_root.join(&rel);
+            let parent_rel = Path::new(&rel).parent().unwrap_or(Path::new("usr/lib"));
+            let dest = pkg_root.join(parent_rel);
+
+            let conflicts = merge_tree(&src, &dest)?;
+            for conflict in &conflicts {
+                let shown = conflict.strip_prefix(pkg_root).unwrap_or(conflict);
+                report.warn(format!(
+                    "Multiarch conflict: /{} already exists in /{}, left in place",
+                    shown.display(),
+                    parent_rel.display()
+                ));
+            }
+
+            let old_prefix = Path::new("/").join(&rel);
+            let new_prefix = Path::new("/").join(parent_rel);
+            let links = rewrite_symlinks(pkg_root, &old_prefix, &new_prefix, &conflicts)?;
+
+            match conflicts.len() {
+                0 => report.change(format!(
+                    "Relocated /{} to /{} ({} symlinks rewritten)",
+                    rel,
+                    parent_rel.display(),
+                    links
+                )),
+                left => report.change(format!(
+                    "Partly relocated /{} to /{} ({} entries left in place, {} symlinks rewritten)",
+                    rel,
+                    parent_rel.display(),
+                    left,
+                    links
+                )),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Ship an ld.so.conf.d snippet listing the triplet directories
+    fn write_ld_conf(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        let dirs = Self::find_triplet_dirs(pkg_root);
+        if dirs.is_empty() {
+            return Ok(());
+        }
+
+        let conf_dir = pkg_root.join("etc/ld.so.conf.d");
+        fs::create_dir_all(&conf_dir)?;
+
+        let conf_name = format!("{}-multiarch.conf", self.pkgname);
+        let content: String = dirs.iter().map(|d| format!("/{}\n", d)).collect();
+        fs::write(conf_dir.join(&conf_name), content)?;
+
+        report.change(format!(
+            "Added /etc/ld.so.conf.d/{} for {}",
+            conf_name,
+            dirs.iter().map(|d| format!("/{}", d)).collect::<Vec<_>>().join(", ")
+        ));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_relocate_multiarch() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let triplet_dir = root.join("usr/lib/x86_64-linux-gnu");
+        fs::create_dir_all(&triplet_dir).unwrap();
+        fs::write(triplet_dir.join("libfoo.so.1"), "elf").unwrap();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        std::os::unix::fs::symlink(
+            "/usr/lib/x86_64-linux-gnu/libfoo.so.1",
+            root.join("usr/bin/link"),
+        )
+        .unwrap();
+
+        let mut report = ConversionReport::default();
+        MultiarchRemapper::new(MultiarchStrategy::Relocate, "foo")
+            .apply(root, &mut report)
+            .unwrap();
+
+        assert!(root.join("usr/lib/libfoo.so.1").exists());
+        assert!(!triplet_dir.exists());
+        assert_eq!(
+            fs::read_link(root.join("usr/bin/link")).unwrap(),
+            Path::new("/usr/lib/libfoo.so.1")
+        );
+        assert_eq!(report.changes.len(), 1);
+    }
+
+    #[test]
+    fn test_partial_relocation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let triplet_dir = root.join("usr/lib/x86_64-linux-gnu");
+        fs::create_dir_all(&triplet_dir).unwrap();
+        fs::write(triplet_dir.join("libfoo.so.1"), "triplet").unwrap();
+        fs::write(root.join("usr/lib/libfoo.so.1"), "parent").unwrap();
+        std::os::unix::fs::symlink("/usr/lib/x86_64-linux-gnu/libfoo.so.1", root.join("usr/lib/libfoo.so")).unwrap();
+
+        let mut report = ConversionReport::default();
+        MultiarchRemapper::new(MultiarchStrategy::Relocate, "foo")
+            .apply(root, &mut report)
+            .unwrap();
+
+        // The link still reaches the library it was made for
+        assert_eq!(
+            fs::read_link(root.join("usr/lib/libfoo.so")).unwrap(),
+            Path::new("/usr/lib/x86_64-linux-gnu/libfoo.so.1")
+        );
+        assert!(report.changes[0].starts_with("Partly relocated"));
+        assert_eq!(report.warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_ldconfig_snippet() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("usr/lib/aarch64-linux-gnu")).unwrap();
+
+        let mut report = ConversionReport::default();
+        MultiarchRemapper::new(MultiarchStrategy::LdConfig, "foo")
+            .apply(root, &mut report)
+            .unwrap();
+
+        let conf = fs::read_to_string(root.join("etc/ld.so.conf.d/foo-multiarch.conf")).unwrap();
+        assert_eq!(conf, "/usr/lib/aarch64-linux-gnu\n");
+    }
+}
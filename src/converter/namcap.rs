@@ -0,0 +1,134 @@
+//! Post-build validation with namcap
+//!
+//! namcap is Arch's package linter. Running it on the built package
+//! catches missing library dependencies, bad permissions and similar
+//! problems the conversion itself cannot see.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{RexebError, Result};
+
+use super::ConversionReport;
+
+/// Severity of a namcap finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamcapSeverity {
+    /// `E:` lines
+    Error,
+    /// `W:` lines
+    Warning,
+    /// `I:` lines
+    Info,
+}
+
+/// A single namcap finding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamcapFinding {
+    /// Severity
+    pub severity: NamcapSeverity,
+    /// Message without the package name and severity prefix
+    pub message: String,
+}
+
+/// Runner for namcap
+pub struct Namcap;
+
+impl Namcap {
+    /// Whether namcap is installed
+    pub fn is_available() -> bool {
+        Command::new("namcap")
+            .arg("--version")
+            .output()
+            .is_ok()
+    }
+
+    /// Run namcap on a built package
+    pub fn check(package: &Path) -> Result<Vec<NamcapFinding>> {
+        let output = Command::new("namcap")
+            .arg(package)
+            .output()
+            .map_err(|_| RexebError::Validation("namcap not found (install the namcap package)".into()))?;
+
+        if !output.status.success() {
+            return Err(RexebError::Validation(format!(
+                "namcap failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(Self::parse_output(&String::from_utf8_lossy(&output.stdout)))
+    }
+
+    /// Parse namcap output lines of the form `<pkgname> E: <message>`
+    pub fn parse_output(output: &str) -> Vec<NamcapFinding> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let (_, rest) = line.split_once(' ')?;
+                let (tag, message) = rest.split_once(": ")?;
+                let severity = match tag {
+                    "E" => NamcapSeverity::Error,
+                    "W" => NamcapSeverity::Warning,
+                    "I" => NamcapSeverity::Info,
+                    _ => return None,
+                };
+                Some(NamcapFinding { severity, message: message.trim().to_string() })
+            })
+            .collect()
+    }
+
+    /// Add findings to the report
+    ///
+    /// With `deny_errors`, any error finding fails with
+    /// [`RexebError::Validation`].
+    pub fn apply(findings: &[NamcapFinding], report: &mut ConversionReport, deny_errors: bool) -> Result<()> {
+        let mut errors = Vec::new();
+
+        for finding in findings {
+            match finding.severity {
+                NamcapSeverity::Error => {
+                    report.warn(format!("namcap error: {}", finding.message));
+                    errors.push(finding.message.as_str());
+                }
+                NamcapSeverity::Warning => report.warn(format!("namcap: {}", finding.message)),
+                NamcapSeverity::Info => {}
+            }
+        }
+
+        if deny_errors && !errors.is_empty() {
+            return Err(RexebError::Validation(format!(
+                "namcap reported {} error(s): {}",
+                errors.len(),
+                errors.join("; ")
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_apply() {
+        let output = "foo E: Dependency libx11 detected and not included (libraries ['usr/lib/libX11.so.6'] needed in files ['usr/bin/foo'])\n\
+                      foo W: File (usr/share/foo/.keep) exists in a non-standard directory.\n\
+                      foo I: Depends as namcap sees them: depends=(glibc)\n\
+                      garbage line\n";
+
+        let findings = Namcap::parse_output(output);
+        assert_eq!(findings.len(), 3);
+        assert_eq!(findings[0].severity, NamcapSeverity::Error);
+        assert!(findings[0].message.starts_with("Dependency libx11 detected"));
+
+        let mut report = ConversionReport::default();
+        Namcap::apply(&findings, &mut report, false).unwrap();
+        assert_eq!(report.warnings.len(), 2);
+
+        assert!(Namcap::apply(&findings, &mut ConversionReport::default(), true).is_err());
+        assert!(Namcap::apply(&findings[1..], &mut ConversionReport::default(), true).is_ok());
+    }
+}
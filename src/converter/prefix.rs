@@ -0,0 +1,266 @@
+//! Relocation of the payload below a private prefix
+//!
+//! Proprietary debs often ship a self-contained tree in `/usr` that is
+//! risky to merge with the system. This pass moves the `/usr` payload
+//! below a prefix such as `/opt/<pkg>` and keeps only the integration
+//! points (desktop entries, icons, units, ...) in `/usr`, with
+//! `/usr/bin` symlinks for the relocated executables.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::error::{RexebError, Result};
+
+use super::relocate::{merge_tree, rewrite_symlinks};
+use super::ConversionReport;
+
+/// Directories below `/usr` that stay in place so the system finds them
+const EXPORTED_DIRS: &[&str] = &[
+    "usr/share/applications",
+    "usr/share/icons",
+    "usr/share/pixmaps",
+    "usr/share/metainfo",
+    "usr/share/mime",
+    "usr/share/dbus-1",
+    "usr/share/polkit-1",
+    "usr/share/man",
+    "usr/share/licenses",
+    "usr/share/bash-completion",
+    "usr/share/zsh",
+    "usr/share/fish",
+    "usr/lib/systemd",
+    "usr/lib/udev",
+    "usr/lib/sysusers.d",
+    "usr/lib/tmpfiles.d",
+];
+
+/// Exported directories whose text files may reference relocated paths
+const REWRITE_DIRS: &[&str] = &[
+    "usr/share/applications",
+    "usr/share/dbus-1",
+    "usr/lib/systemd",
+];
+
+lazy_static::lazy_static! {
+    static ref USR_PATH: Regex = Regex::new(r#"/usr/[^\s"';:=]+"#).unwrap();
+}
+
+/// Converter pass relocating `/usr` below a prefix
+pub struct PrefixRelocator {
+    /// Absolute target prefix, e.g. `/opt/foo`
+    prefix: PathBuf,
+}
+
+impl PrefixRelocator {
+    /// Create a relocator for `prefix`, expanding `{pkgname}`
+    pub fn new(prefix: &str, pkgname: &str) -> Result<Self> {
+        let prefix = PathBuf::from(prefix.replace("{pkgname}", pkgname));
+
+        let valid = prefix.is_absolute()
+            && prefix.components().count() > 1
+            && prefix.components().all(|c| matches!(c, std::path::Component::RootDir | std::path::Component::Normal(_)))
+            && !prefix.starts_with("/usr");
+        if !valid {
+            return Err(RexebError::Config(format!(
+                "Invalid prefix '{}': must be an absolute path outside /usr",
+                prefix.display()
+            )));
+        }
+
+        Ok(Self { prefix })
+    }
+
+    /// Target prefix
+    pub fn prefix(&self) -> &Path {
+        &self.prefix
+    }
+
+    /// Where an absolute `/usr` path of the payload is after [`apply`](Self::apply)
+    ///
+    /// Paths that stayed in `/usr`, being exported or in conflict, are
+    /// returned unchanged.
+    pub fn relocated(&self, pkg_root: &Path, path: &Path) -> PathBuf {
+        let Ok(rest) = path.strip_prefix("/usr") else {
+            return path.to_path_buf();
+        };
+        let target = pkg_root.join(self.prefix.strip_prefix("/").unwrap_or(&self.prefix));
+        if pkg_root.join("usr").join(rest).symlink_metadata().is_err() && target.join(rest).symlink_metadata().is_ok() {
+            self.prefix.join(rest)
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    /// Move the `/usr` payload below the prefix
+    pub fn apply(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        let usr = pkg_root.join("usr");
+        if !usr.is_dir() {
+            return Ok(());
+        }
+
+        let prefix_rel = self.prefix.strip_prefix("/").unwrap_or(&self.prefix);
+        let target = pkg_root.join(prefix_rel);
+
+        let conflicts = merge_tree(&usr, &target)?;
+        for conflict in &conflicts {
+            let rel = conflict.strip_prefix(pkg_root).unwrap_or(conflict);
+            report.warn(format!("/{} already exists below {}, kept in /usr", rel.display(), self.prefix.display()));
+        }
+
+        // Move the integration points back
+        for dir in EXPORTED_DIRS {
+            let relocated = target.join(Path::new(dir).strip_prefix("usr").unwrap_or(Path::new(dir)));
+            if relocated.is_dir() {
+                merge_tree(&relocated, &pkg_root.join(dir))?;
+            }
+        }
+        remove_empty_dirs(&target)?;
+
+        let links = rewrite_symlinks(pkg_root, Path::new("/usr"), &self.prefix, &conflicts)?;
+        let rewritten = self.rewrite_references(pkg_root, report)?;
+        let linked = self.link_binaries(pkg_root, &target)?;
+
+        report.change(format!(
+            "Relocated /usr payload to {} ({} binaries linked, {} symlinks and {} files rewritten)",
+            self.prefix.display(),
+            linked,
+            links,
+            rewritten
+        ));
+
+        Ok(())
+    }
+
+    /// Create `/usr/bin` symlinks for executables below the prefix
+    fn link_binaries(&self, pkg_root: &Path, target: &Path) -> Result<usize> {
+        let bin_dir = pkg_root.join("usr/bin");
+        let mut linked = 0;
+
+        for sub in ["bin", "sbin"] {
+            let dir = target.join(sub);
+            if !dir.is_dir() {
+                continue;
+            }
+
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    continue;
+                }
+
+                let link = bin_dir.join(entry.file_name());
+                if link.symlink_metadata().is_ok() {
+                    continue;
+                }
+
+                fs::create_dir_all(&bin_dir)?;
+                #[cfg(unix)]
+                std::os::unix::fs::symlink(self.prefix.join(sub).join(entry.file_name()), &link)?;
+                linked += 1;
+            }
+        }
+
+        Ok(linked)
+    }
+
+    /// Point `/usr/...` paths in desktop entries, D-Bus services and units
+    /// at the prefix when the file was relocated
+    fn rewrite_references(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<usize> {
+        let mut rewritten = 0;
+
+        for dir in REWRITE_DIRS {
+            let dir = pkg_root.join(dir);
+            if !dir.is_dir() {
+                continue;
+            }
+
+            for entry in walkdir::WalkDir::new(&dir) {
+                let entry = entry?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let Ok(content) = fs::read_to_string(entry.path()) else {
+                    continue;
+                };
+
+                let updated = USR_PATH.replace_all(&content, |caps: &regex::Captures| {
+                    self.relocated(pkg_root, Path::new(&caps[0])).display().to_string()
+                });
+
+                if updated != content {
+                    fs::write(entry.path(), updated.as_bytes())?;
+                    let rel = entry.path().strip_prefix(pkg_root).unwrap_or(entry.path());
+                    report.change(format!("Rewrote /usr paths in /{}", rel.display()));
+                    rewritten += 1;
+                }
+            }
+        }
+
+        Ok(rewritten)
+    }
+}
+
+/// Remove empty directories below (and including) `dir`
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    for entry in walkdir::WalkDir::new(dir).contents_first(true) {
+        let entry = entry?;
+        if entry.file_type().is_dir() && fs::read_dir(entry.path())?.next().is_none() {
+            fs::remove_dir(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_relocate_to_opt() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::create_dir_all(root.join("usr/lib/foo")).unwrap();
+        fs::create_dir_all(root.join("usr/share/applications")).unwrap();
+        fs::create_dir_all(root.join("etc")).unwrap();
+        fs::write(root.join("usr/bin/foo"), "#!/bin/sh\n").unwrap();
+        fs::write(root.join("usr/lib/foo/libfoo.so"), "").unwrap();
+        fs::write(root.join("etc/foo.conf"), "").unwrap();
+        fs::write(
+            root.join("usr/share/applications/foo.desktop"),
+            "[Desktop Entry]\nExec=/usr/bin/foo %U\nIcon=foo\nTryExec=/usr/bin/foo\n",
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("/usr/lib/foo/libfoo.so", root.join("usr/lib/libfoo.so")).unwrap();
+
+        let relocator = PrefixRelocator::new("/opt/{pkgname}", "foo").unwrap();
+        let mut report = ConversionReport::default();
+        relocator.apply(root, &mut report).unwrap();
+
+        assert!(root.join("opt/foo/bin/foo").is_file());
+        assert!(root.join("opt/foo/lib/foo/libfoo.so").is_file());
+        assert!(root.join("etc/foo.conf").exists());
+        assert!(!root.join("opt/foo/share").exists());
+        assert_eq!(fs::read_link(root.join("usr/bin/foo")).unwrap(), Path::new("/opt/foo/bin/foo"));
+        assert_eq!(
+            fs::read_link(root.join("opt/foo/lib/libfoo.so")).unwrap(),
+            Path::new("/opt/foo/lib/foo/libfoo.so")
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("usr/share/applications/foo.desktop")).unwrap(),
+            "[Desktop Entry]\nExec=/opt/foo/bin/foo %U\nIcon=foo\nTryExec=/opt/foo/bin/foo\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        assert!(PrefixRelocator::new("opt/foo", "foo").is_err());
+        assert!(PrefixRelocator::new("/usr/local", "foo").is_err());
+        assert!(PrefixRelocator::new("/opt/../usr", "foo").is_err());
+        assert!(PrefixRelocator::new("/", "foo").is_err());
+        assert_eq!(PrefixRelocator::new("/opt/{pkgname}", "bar").unwrap().prefix(), Path::new("/opt/bar"));
+    }
+}
@@ -0,0 +1,304 @@
+//! Python bytecode and site-packages handling
+//!
+//! Debian ships `.pyc` files compiled for its own interpreter and installs
+//! modules into `/usr/lib/python3/dist-packages`. Arch uses a versioned
+//! `/usr/lib/python3.X/site-packages`, so modules are moved there and
+//! stale bytecode is dropped. Bytecode can optionally be regenerated from
+//! the .INSTALL script.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+use crate::error::Result;
+
+use super::relocate::merge_tree;
+use super::{ConversionReport, InstallHook};
+
+/// Python version assumed when it cannot be detected
+pub const DEFAULT_PYTHON_VERSION: &str = "3.13";
+
+/// Converter pass for Python payloads
+pub struct PythonBytecode {
+    /// Arch Python version (e.g. "3.13")
+    target_version: String,
+    /// Byte-compile modules from .INSTALL
+    bytecompile: bool,
+}
+
+impl PythonBytecode {
+    /// Create a new pass targeting the given Python version
+    pub fn new(target_version: impl Into<String>, bytecompile: bool) -> Self {
+        Self {
+            target_version: target_version.into(),
+            bytecompile,
+        }
+    }
+
+    /// Detect the Python version installed on this system
+    ///
+    /// The interpreter only runs for the first conversion of a run.
+    pub fn detect_version() -> Option<String> {
+        static DETECTED: OnceLock<Option<String>> = OnceLock::new();
+        DETECTED
+            .get_or_init(|| {
+                let output = Command::new("python3")
+                    .arg("-c")
+                    .arg("import sys; print('%d.%d' % sys.version_info[:2])")
+                    .output()
+                    .ok()?;
+
+                if !output.status.success() {
+                    return None;
+                }
+
+                let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                (!version.is_empty()).then_some(version)
+            })
+            .clone()
+    }
+
+    /// Site-packages directory relative to the package root
+    fn site_packages(&self) -> String {
+        format!("usr/lib/python{}/site-packages", self.target_version)
+    }
+
+    /// Apply the pass, returning the module paths to byte-compile from
+    /// .INSTALL (see [`install_hooks`](Self::install_hooks))
+    pub fn apply(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<Vec<PathBuf>> {
+        let removed = Self::remove_bytecode(pkg_root)?;
+        if removed > 0 {
+            report.change(format!("Removed {} stale Python bytecode entries", removed));
+        }
+
+        self.relocate_modules(pkg_root, report)?;
+        self.check_extension_modules(pkg_root, report)?;
+
+        let site_packages = pkg_root.join(self.site_packages());
+        if !self.bytecompile || !site_packages.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut targets: Vec<PathBuf> = fs::read_dir(&site_packages)?
+            .filter_map(|e| e.ok())
+            .map(|e| Path::new("/").join(self.site_packages()).join(e.file_name()))
+            .collect();
+        targets.sort();
+        Ok(targets)
+    }
+
+    /// .INSTALL hooks byte-compiling `targets` and removing the bytecode again
+    ///
+    /// Built once the payload has its final layout, as later passes may move
+    /// the modules.
+    pub fn install_hooks(targets: &[PathBuf], report: &mut ConversionReport) -> Vec<InstallHook> {
+        if targets.is_empty() {
+            return Vec::new();
+        }
+
+        let targets = targets
+            .iter()
+            .map(|t| format!("'{}'", t.display().to_string().replace('\'', "'\\''")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let compile = format!("python -m compileall -q -f {} >/dev/null 2>&1 || true", targets);
+        let cleanup = format!(
+            "find {} -type d -name __pycache__ -exec rm -rf {{}} + 2>/dev/null || true",
+            targets
+        );
+
+        report.change("Added Python byte-compilation to .INSTALL");
+
+        vec![
+            InstallHook::new("post_install", compile.clone()),
+            InstallHook::new("post_upgrade", compile),
+            InstallHook::new("pre_remove", cleanup),
+        ]
+    }
+
+    /// Remove stale bytecode below the Python library directories
+    ///
+    /// That is `__pycache__` directories and legacy `foo.pyc`/`foo.pyo`
+    /// files next to their `foo.py`. Bytecode without a source is all some
+    /// applications ship (PyInstaller bundles, vendored runtimes), so it is
+    /// kept, as is anything outside `/usr/lib/python*`.
+    fn remove_bytecode(pkg_root: &Path) -> Result<usize> {
+        let lib_dir = pkg_root.join("usr/lib");
+        if !lib_dir.is_dir() {
+            return Ok(0);
+        }
+
+        let mut removed = 0;
+        for python_dir in fs::read_dir(&lib_dir)? {
+            let python_dir = python_dir?;
+            if !python_dir.file_name().to_string_lossy().starts_with("python") || !python_dir.file_type()?.is_dir() {
+                continue;
+            }
+
+            let mut walker = walkdir::WalkDir::new(python_dir.path()).into_iter();
+            while let Some(entry) = walker.next() {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy();
+
+                if entry.file_type().is_dir() && name == "__pycache__" {
+                    fs::remove_dir_all(entry.path())?;
+                    walker.skip_current_dir();
+                    removed += 1;
+                } else if entry.file_type().is_file()
+                    && (name.ends_with(".pyc") || name.ends_with(".pyo"))
+                    && entry.path().with_extension("py").is_file()
+                {
+                    fs::remove_file(entry.path())?;
+                    removed += 1;
+                }
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Move Debian module directories into Arch's site-packages
+    fn relocate_modules(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        let lib_dir = pkg_root.join("usr/lib");
+        if !lib_dir.is_dir() {
+            return Ok(());
+        }
+
+        let target_dir = format!("python{}", self.target_version);
+        let dest = pkg_root.join(self.site_packages());
+
+        for entry in fs::read_dir(&lib_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with("python3") || !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            for sub in ["dist-packages", "site-packages"] {
+                let src = entry.path().join(sub);
+                if !src.is_dir() || (name == target_dir && sub == "site-packages") {
+                    continue;
+                }
+
+                for conflict in merge_tree(&src, &dest)? {
+                    report.warn(format!(
+                        "Python module conflict: {} already exists in /{}",
+                        conflict.strip_prefix(pkg_root).unwrap_or(&conflict).display(),
+                        self.site_packages()
+                    ));
+                }
+
+                report.change(format!("Moved /usr/lib/{}/{} to /{}", name, sub, self.site_packages()));
+            }
+
+            // Drop the interpreter directory if nothing else lived there
+            if entry.path().exists() && fs::read_dir(entry.path())?.next().is_none() {
+                fs::remove_dir(entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Warn about compiled extension modules built for another interpreter
+    fn check_extension_modules(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        lazy_static::lazy_static! {
+            static ref CPYTHON_TAG: Regex = Regex::new(r"\.cpython-(\d)(\d+)-[^.]*\.so$").unwrap();
+        }
+
+        let expected = self.target_version.replace('.', "");
+
+        for entry in walkdir::WalkDir::new(pkg_root) {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy();
+            if let Some(caps) = CPYTHON_TAG.captures(&name) {
+                let tag = format!("{}{}", &caps[1], &caps[2]);
+                if tag != expected {
+                    report.warn(format!(
+                        "Python extension {} targets CPython {}.{}, Arch uses {}",
+                        entry.path().strip_prefix(pkg_root).unwrap_or(entry.path()).display(),
+                        &caps[1],
+                        &caps[2],
+                        self.target_version
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_relocates_dist_packages_and_drops_bytecode() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let dist = root.join("usr/lib/python3/dist-packages/foo");
+        fs::create_dir_all(dist.join("__pycache__")).unwrap();
+        fs::write(dist.join("__init__.py"), "").unwrap();
+        fs::write(dist.join("__pycache__/__init__.cpython-310.pyc"), "").unwrap();
+
+        let mut report = ConversionReport::default();
+        let targets = PythonBytecode::new("3.13", true).apply(root, &mut report).unwrap();
+        let hooks = PythonBytecode::install_hooks(&targets, &mut report);
+
+        let site = root.join("usr/lib/python3.13/site-packages/foo");
+        assert!(site.join("__init__.py").exists());
+        assert!(!site.join("__pycache__").exists());
+        assert!(!root.join("usr/lib/python3").exists());
+        assert_eq!(hooks.len(), 3);
+        assert!(hooks[0].command.contains("'/usr/lib/python3.13/site-packages/foo'"));
+
+        // Module names reach the shell as single words
+        let targets = [PathBuf::from("/usr/lib/python3.13/site-packages/it's $(here)")];
+        let hooks = PythonBytecode::install_hooks(&targets, &mut report);
+        assert_eq!(
+            hooks[2].command,
+            "find '/usr/lib/python3.13/site-packages/it'\\''s $(here)' -type d -name __pycache__ -exec rm -rf {} + 2>/dev/null || true"
+        );
+    }
+
+    #[test]
+    fn test_keeps_sourceless_bytecode() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let site = root.join("usr/lib/python3.13/site-packages/foo");
+        let bundle = root.join("opt/app/lib");
+        fs::create_dir_all(&site).unwrap();
+        fs::create_dir_all(bundle.join("__pycache__")).unwrap();
+        for file in ["mod.py", "mod.pyc", "compiled.pyc"] {
+            fs::write(site.join(file), "").unwrap();
+        }
+        fs::write(bundle.join("app.pyc"), "").unwrap();
+        fs::write(bundle.join("__pycache__/app.cpython-310.pyc"), "").unwrap();
+
+        assert_eq!(PythonBytecode::remove_bytecode(root).unwrap(), 1);
+        assert!(!site.join("mod.pyc").exists());
+        assert!(site.join("compiled.pyc").exists());
+        assert!(bundle.join("app.pyc").exists());
+        assert!(bundle.join("__pycache__/app.cpython-310.pyc").exists());
+    }
+
+    #[test]
+    fn test_extension_abi_warning() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let site = root.join("usr/lib/python3.13/site-packages");
+        fs::create_dir_all(&site).unwrap();
+        fs::write(site.join("_speedups.cpython-310-x86_64-linux-gnu.so"), "").unwrap();
+
+        let mut report = ConversionReport::default();
+        let targets = PythonBytecode::new("3.13", false).apply(root, &mut report).unwrap();
+
+        assert!(targets.is_empty());
+        assert_eq!(report.warnings.len(), 1);
+    }
+}
@@ -0,0 +1,108 @@
+//! Helpers for moving payload trees around inside the package root
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+/// Move everything below `src` into `dest`, merging directories.
+///
+/// Entries whose destination already exists (and is not a directory that
+/// can be merged) are left in place and returned as conflicts. `src` is
+/// removed when it ends up empty.
+pub(crate) fn merge_tree(src: &Path, dest: &Path) -> Result<Vec<PathBuf>> {
+    let mut conflicts = Vec::new();
+    fs::create_dir_all(dest)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let source = entry.path();
+        let target = dest.join(entry.file_name());
+
+        match target.symlink_metadata() {
+            Err(_) => fs::rename(&source, &target)?,
+            Ok(meta) if meta.is_dir() && entry.file_type()?.is_dir() => {
+                conflicts.extend(merge_tree(&source, &target)?);
+            }
+            Ok(_) => conflicts.push(source),
+        }
+    }
+
+    if fs::read_dir(src)?.next().is_none() {
+        fs::remove_dir(src)?;
+    }
+
+    Ok(conflicts)
+}
+
+/// Rewrite absolute symlinks pointing below `old_prefix` to `new_prefix`.
+///
+/// Only links whose rewritten target exists inside `pkg_root` are touched,
+/// so links into paths that stayed put keep working. Links into
+/// `left_behind` (conflicts from [`merge_tree`]) are kept too: whatever
+/// sits at the new path is a different file. Returns the number of
+/// rewritten links.
+pub(crate) fn rewrite_symlinks(
+    pkg_root: &Path,
+    old_prefix: &Path,
+    new_prefix: &Path,
+    left_behind: &[PathBuf],
+) -> Result<usize> {
+    let mut rewritten = 0;
+
+    for entry in walkdir::WalkDir::new(pkg_root) {
+        let entry = entry?;
+        if !entry.path_is_symlink() {
+            continue;
+        }
+
+        let target = fs::read_link(entry.path())?;
+        let Ok(rest) = target.strip_prefix(old_prefix) else {
+            continue;
+        };
+        let old_in_root = pkg_root.join(target.strip_prefix("/").unwrap_or(&target));
+        if left_behind.iter().any(|kept| old_in_root.starts_with(kept)) {
+            continue;
+        }
+
+        let new_target = new_prefix.join(rest);
+        let in_root = pkg_root.join(new_target.strip_prefix("/").unwrap_or(&new_target));
+        if in_root.symlink_metadata().is_err() {
+            continue;
+        }
+
+        fs::remove_file(entry.path())?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&new_target, entry.path())?;
+        rewritten += 1;
+    }
+
+    Ok(rewritten)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_merge_tree_reports_conflicts() {
+        let temp_dir = TempDir::new().unwrap();
+        let src = temp_dir.path().join("src");
+        let dest = temp_dir.path().join("dest");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::create_dir_all(dest.join("sub")).unwrap();
+        fs::write(src.join("a"), "a").unwrap();
+        fs::write(src.join("sub/b"), "b").unwrap();
+        fs::write(src.join("c"), "new").unwrap();
+        fs::write(dest.join("c"), "old").unwrap();
+
+        let conflicts = merge_tree(&src, &dest).unwrap();
+
+        assert_eq!(conflicts, vec![src.join("c")]);
+        assert!(dest.join("a").exists());
+        assert!(dest.join("sub/b").exists());
+        assert_eq!(fs::read_to_string(dest.join("c")).unwrap(), "old");
+        assert!(src.exists());
+    }
+}
@@ -0,0 +1,31 @@
+//! Conversion report
+//!
+//! Collects what the converter changed in the payload and any problems
+//! it ran into, so the CLI can summarize them after a build.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Summary of a single package conversion
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ConversionReport {
+    /// Path of the built package
+    pub output_path: PathBuf,
+    /// Modifications applied to the payload
+    pub changes: Vec<String>,
+    /// Problems that did not abort the conversion
+    pub warnings: Vec<String>,
+}
+
+impl ConversionReport {
+    /// Record a modification made to the payload
+    pub fn change(&mut self, message: impl Into<String>) {
+        self.changes.push(message.into());
+    }
+
+    /// Record a non-fatal problem
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+}
@@ -0,0 +1,162 @@
+//! AppArmor and SELinux artifacts
+//!
+//! Debian packages ship AppArmor profiles and some ship SELinux policy
+//! modules. Neither framework is active on a default Arch system: AppArmor
+//! has to be enabled on the kernel command line, and SELinux is not
+//! supported at all. The profiles are inert there, and once a user enables
+//! AppArmor a profile written for Debian paths can confine the program
+//! wrongly. With `strip_security_profiles` the converter drops them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+use super::ConversionReport;
+
+/// Directories holding AppArmor profiles, relative to the root
+const APPARMOR_DIRS: &[&str] = &["etc/apparmor.d", "usr/share/apparmor"];
+
+/// Directories holding SELinux policy, relative to the root
+const SELINUX_DIRS: &[&str] = &["etc/selinux", "usr/share/selinux"];
+
+/// Mandatory access control framework
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framework {
+    /// AppArmor profiles
+    AppArmor,
+    /// SELinux policy modules
+    SELinux,
+}
+
+impl Framework {
+    /// Name for messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::AppArmor => "AppArmor",
+            Self::SELinux => "SELinux",
+        }
+    }
+
+    /// What the files do on Arch
+    pub fn note(&self) -> &'static str {
+        match self {
+            Self::AppArmor => "AppArmor is not enabled on a default Arch system, so they do nothing until it is, \
+                               and then confine the program with rules written for Debian",
+            Self::SELinux => "Arch does not support SELinux, so they do nothing",
+        }
+    }
+
+    fn dirs(&self) -> &'static [&'static str] {
+        match self {
+            Self::AppArmor => APPARMOR_DIRS,
+            Self::SELinux => SELINUX_DIRS,
+        }
+    }
+}
+
+/// AppArmor and SELinux files of a payload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecurityProfiles {
+    /// Files relative to the root, with their framework
+    pub files: Vec<(PathBuf, Framework)>,
+}
+
+impl SecurityProfiles {
+    /// Find the files below a package root
+    pub fn detect(root: &Path) -> Result<Self> {
+        let mut files = Vec::new();
+        for framework in [Framework::AppArmor, Framework::SELinux] {
+            for dir in framework.dirs() {
+                let dir = root.join(dir);
+                if !dir.is_dir() {
+                    continue;
+                }
+                for entry in walkdir::WalkDir::new(&dir).sort_by_file_name() {
+                    let entry = entry?;
+                    if !entry.file_type().is_dir() {
+                        let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+                        files.push((rel.to_path_buf(), framework));
+                    }
+                }
+            }
+        }
+        Ok(Self { files })
+    }
+
+    /// Files of one framework
+    pub fn of(&self, framework: Framework) -> impl Iterator<Item = &Path> {
+        self.files.iter().filter(move |(_, f)| *f == framework).map(|(path, _)| path.as_path())
+    }
+
+    /// Whether no files were found
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Remove the files and the directories left empty
+    pub fn strip(&self, root: &Path, report: &mut ConversionReport) -> Result<()> {
+        for framework in [Framework::AppArmor, Framework::SELinux] {
+            let count = self.of(framework).count();
+            if count == 0 {
+                continue;
+            }
+            for path in self.of(framework) {
+                fs::remove_file(root.join(path))?;
+            }
+            for dir in framework.dirs() {
+                remove_empty_dirs(&root.join(dir))?;
+            }
+            report.change(format!("Removed {} {} file(s)", count, framework.name()));
+        }
+        Ok(())
+    }
+}
+
+/// Remove a directory tree if it holds no files
+fn remove_empty_dirs(dir: &Path) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() && !path.is_symlink() {
+            remove_empty_dirs(&path)?;
+        }
+    }
+    if fs::read_dir(dir)?.next().is_none() {
+        fs::remove_dir(dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_security_profiles() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("etc/apparmor.d/local")).unwrap();
+        fs::create_dir_all(root.join("usr/share/selinux/packages")).unwrap();
+        fs::create_dir_all(root.join("usr/bin")).unwrap();
+        fs::write(root.join("etc/apparmor.d/usr.bin.foo"), "profile foo {}").unwrap();
+        fs::write(root.join("etc/apparmor.d/local/usr.bin.foo"), "").unwrap();
+        fs::write(root.join("usr/share/selinux/packages/foo.pp.bz2"), "").unwrap();
+        fs::write(root.join("usr/bin/foo"), "").unwrap();
+
+        let profiles = SecurityProfiles::detect(root).unwrap();
+        assert_eq!(profiles.of(Framework::AppArmor).count(), 2);
+        assert_eq!(
+            profiles.of(Framework::SELinux).collect::<Vec<_>>(),
+            [Path::new("usr/share/selinux/packages/foo.pp.bz2")]
+        );
+
+        let mut report = ConversionReport::default();
+        profiles.strip(root, &mut report).unwrap();
+        assert_eq!(report.changes, ["Removed 2 AppArmor file(s)", "Removed 1 SELinux file(s)"]);
+        assert!(!root.join("etc/apparmor.d").exists() && !root.join("usr/share/selinux").exists());
+        assert!(root.join("usr/bin/foo").exists());
+    }
+}
@@ -0,0 +1,180 @@
+//! Shebang rewriting
+//!
+//! Scripts from Debian often name versioned interpreters
+//! (`/usr/bin/python3.10`, `/usr/bin/perl5.36`) or sbin paths that do not
+//! exist on Arch. This pass rewrites them to their Arch equivalents.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::error::Result;
+
+use super::ConversionReport;
+
+/// Longest shebang line we look at
+const MAX_SHEBANG_LEN: usize = 256;
+
+lazy_static::lazy_static! {
+    /// Interpreter path rewrites, applied in order
+    static ref PATH_RULES: Vec<(Regex, &'static str)> = vec![
+        (Regex::new(r"^/usr/bin/python3\.\d+$").unwrap(), "/usr/bin/python3"),
+        (Regex::new(r"^/usr/bin/perl5(\.\d+)+$").unwrap(), "/usr/bin/perl"),
+        (Regex::new(r"^/usr/bin/ruby\d\.\d+$").unwrap(), "/usr/bin/ruby"),
+        (Regex::new(r"^/usr/bin/php\d\.\d+$").unwrap(), "/usr/bin/php"),
+        (Regex::new(r"^/usr/bin/nodejs$").unwrap(), "/usr/bin/node"),
+        (Regex::new(r"^/(?:usr/)?s?bin/(.+)$").unwrap(), "/usr/bin/$1"),
+    ];
+
+    /// Interpreter name rewrites for `/usr/bin/env <name>` shebangs
+    static ref ENV_RULES: Vec<(Regex, &'static str)> = vec![
+        (Regex::new(r"^python3\.\d+$").unwrap(), "python3"),
+        (Regex::new(r"^perl5(\.\d+)+$").unwrap(), "perl"),
+        (Regex::new(r"^ruby\d\.\d+$").unwrap(), "ruby"),
+        (Regex::new(r"^php\d\.\d+$").unwrap(), "php"),
+        (Regex::new(r"^nodejs$").unwrap(), "node"),
+    ];
+}
+
+/// Converter pass rewriting script interpreters
+pub struct ShebangRewriter;
+
+impl ShebangRewriter {
+    /// Create a new rewriter
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Rewrite shebangs of all regular files in the package root
+    pub fn apply(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        for entry in walkdir::WalkDir::new(pkg_root) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let Some(line) = Self::read_shebang(entry.path())? else {
+                continue;
+            };
+
+            if let Some(new_line) = Self::rewrite_line(&line) {
+                let content = fs::read(entry.path())?;
+                let mut updated = new_line.clone().into_bytes();
+                updated.extend_from_slice(&content[line.len()..]);
+                fs::write(entry.path(), updated)?;
+
+                let rel = entry.path().strip_prefix(pkg_root).unwrap_or(entry.path());
+                report.change(format!(
+                    "Rewrote shebang in /{}: {} -> {}",
+                    rel.display(),
+                    line.trim_start_matches("#!").trim(),
+                    new_line.trim_start_matches("#!").trim()
+                ));
+            } else if line.contains("python2") {
+                let rel = entry.path().strip_prefix(pkg_root).unwrap_or(entry.path());
+                report.warn(format!("/{} requires Python 2, which Arch does not ship", rel.display()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the first line of a file if it is a shebang
+    fn read_shebang(path: &Path) -> Result<Option<String>> {
+        let mut buf = [0u8; MAX_SHEBANG_LEN];
+        let mut file = File::open(path)?;
+        let n = file.read(&mut buf)?;
+
+        if n < 2 || &buf[..2] != b"#!" {
+            return Ok(None);
+        }
+
+        let end = buf[..n].iter().position(|&b| b == b'\n').unwrap_or(n);
+        Ok(std::str::from_utf8(&buf[..end]).ok().map(str::to_string))
+    }
+
+    /// Rewrite a shebang line, returning `None` if nothing changes
+    fn rewrite_line(line: &str) -> Option<String> {
+        let body = line.strip_prefix("#!")?.trim();
+        let mut parts = body.splitn(2, char::is_whitespace);
+        let interpreter = parts.next()?;
+        let args = parts.next().map(str::trim).unwrap_or("");
+
+        let new_interpreter = PATH_RULES
+            .iter()
+            .find(|(re, _)| re.is_match(interpreter))
+            .map(|(re, rep)| re.replace(interpreter, *rep).to_string())
+            .unwrap_or_else(|| interpreter.to_string());
+
+        let new_args = if new_interpreter == "/usr/bin/env" && !args.is_empty() {
+            let mut env_parts = args.splitn(2, char::is_whitespace);
+            let name = env_parts.next().unwrap_or("");
+            let rest = env_parts.next().unwrap_or("");
+            let new_name = ENV_RULES
+                .iter()
+                .find(|(re, _)| re.is_match(name))
+                .map(|(re, rep)| re.replace(name, *rep).to_string())
+                .unwrap_or_else(|| name.to_string());
+            [new_name.as_str(), rest].join(" ").trim().to_string()
+        } else {
+            args.to_string()
+        };
+
+        let new_line = if new_args.is_empty() {
+            format!("#!{}", new_interpreter)
+        } else {
+            format!("#!{} {}", new_interpreter, new_args)
+        };
+
+        (new_interpreter != interpreter || new_args != args).then_some(new_line)
+    }
+}
+
+impl Default for ShebangRewriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rewrite_line() {
+        assert_eq!(
+            ShebangRewriter::rewrite_line("#!/usr/bin/python3.10"),
+            Some("#!/usr/bin/python3".to_string())
+        );
+        assert_eq!(
+            ShebangRewriter::rewrite_line("#!/usr/bin/perl5.36 -w"),
+            Some("#!/usr/bin/perl -w".to_string())
+        );
+        assert_eq!(
+            ShebangRewriter::rewrite_line("#!/bin/sh -e"),
+            Some("#!/usr/bin/sh -e".to_string())
+        );
+        assert_eq!(
+            ShebangRewriter::rewrite_line("#!/usr/bin/env python3.11 -u"),
+            Some("#!/usr/bin/env python3 -u".to_string())
+        );
+        assert_eq!(ShebangRewriter::rewrite_line("#!/usr/bin/bash"), None);
+    }
+
+    #[test]
+    fn test_apply_records_rewrites() {
+        let temp_dir = TempDir::new().unwrap();
+        let script = temp_dir.path().join("usr/bin/tool");
+        fs::create_dir_all(script.parent().unwrap()).unwrap();
+        fs::write(&script, "#!/usr/bin/python3.10\nprint('hi')\n").unwrap();
+
+        let mut report = ConversionReport::default();
+        ShebangRewriter::new().apply(temp_dir.path(), &mut report).unwrap();
+
+        assert_eq!(fs::read_to_string(&script).unwrap(), "#!/usr/bin/python3\nprint('hi')\n");
+        assert_eq!(report.changes.len(), 1);
+    }
+}
@@ -0,0 +1,135 @@
+//! Install simulation with pacman
+//!
+//! `pacman -U --print` prepares the transaction for the built package
+//! without committing it: architecture, dependency and package conflict
+//! problems fail it exactly as a real install would. Preparing does not
+//! check files, so the package's file list (`pacman -Qlp`) is compared with
+//! the installed packages' files the way pacman does at commit time.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::{RexebError, Result};
+use crate::resolver::InstalledFiles;
+
+use super::ConversionReport;
+
+/// Outcome of a simulated install
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Simulation {
+    /// Packages the transaction would install, as `name version`
+    pub targets: Vec<String>,
+    /// Errors pacman would report, in pacman's wording
+    pub problems: Vec<String>,
+}
+
+/// Runner for the simulated install
+pub struct InstallSimulation;
+
+impl InstallSimulation {
+    /// Whether pacman is installed
+    pub fn is_available() -> bool {
+        Command::new("pacman").arg("--version").output().is_ok()
+    }
+
+    /// Simulate installing a built package
+    pub fn check(package: &Path) -> Result<Simulation> {
+        let output = pacman(&["-U", "--print", "--print-format", "%n %v"], package)?;
+        let mut simulation = Self::parse_output(
+            output.status.success(),
+            &String::from_utf8_lossy(&output.stdout),
+            &String::from_utf8_lossy(&output.stderr),
+        );
+
+        let listing = pacman(&["-Qlp"], package)?;
+        if listing.status.success() {
+            let installed = InstalledFiles::system()?;
+            simulation
+                .problems
+                .extend(Self::file_conflicts(&String::from_utf8_lossy(&listing.stdout), &installed));
+        }
+
+        Ok(simulation)
+    }
+
+    /// Read `pacman -U --print` output
+    pub fn parse_output(success: bool, stdout: &str, stderr: &str) -> Simulation {
+        let targets = match success {
+            true => stdout.lines().map(str::trim).filter(|l| !l.is_empty()).map(String::from).collect(),
+            false => Vec::new(),
+        };
+        // `error: failed to prepare transaction (...)` followed by `:: <detail>` lines
+        let problems = stderr
+            .lines()
+            .filter_map(|line| line.strip_prefix("error: ").or_else(|| line.strip_prefix(":: ")))
+            .map(|line| line.trim().to_string())
+            .collect();
+        Simulation { targets, problems }
+    }
+
+    /// Conflicts of `pacman -Qlp` output with installed files, worded as pacman does
+    ///
+    /// Files owned by an installed version of the same package are replaced
+    /// by the upgrade and do not conflict.
+    pub fn file_conflicts(listing: &str, installed: &InstalledFiles) -> Vec<String> {
+        listing
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .filter(|(_, path)| !path.ends_with('/'))
+            .filter_map(|(name, path)| {
+                let owner = installed.owner(Path::new(path))?;
+                (owner != name).then(|| format!("{}: {} exists in filesystem (owned by {})", name, path, owner))
+            })
+            .collect()
+    }
+
+    /// Add the outcome to the report
+    pub fn apply(simulation: &Simulation, report: &mut ConversionReport) {
+        for problem in &simulation.problems {
+            report.warn(format!("pacman: {}", problem));
+        }
+        // A single target is the package itself
+        if simulation.targets.len() > 1 {
+            report.change(format!("pacman would install {}", simulation.targets.join(", ")));
+        }
+    }
+}
+
+/// Run pacman on a package file in the C locale, so messages can be parsed
+fn pacman(args: &[&str], package: &Path) -> Result<std::process::Output> {
+    Command::new("pacman")
+        .args(args)
+        .arg(package)
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|_| RexebError::Validation("pacman not found; install simulation needs an Arch system".into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_simulation() {
+        let stderr = "error: failed to prepare transaction (could not satisfy dependencies)\n\
+                      :: unable to satisfy dependency 'libfoo.so=1-64' required by foo\n\
+                      warning: foo-1.0-1 is up to date -- reinstalling\n";
+        let simulation = InstallSimulation::parse_output(false, "", stderr);
+        assert!(simulation.targets.is_empty());
+        assert_eq!(
+            simulation.problems,
+            [
+                "failed to prepare transaction (could not satisfy dependencies)",
+                "unable to satisfy dependency 'libfoo.so=1-64' required by foo",
+            ]
+        );
+
+        let simulation = InstallSimulation::parse_output(true, "libbar 2.0-1\nfoo 1.0-1\n", "");
+        assert_eq!(simulation.targets, ["libbar 2.0-1", "foo 1.0-1"]);
+        assert!(simulation.problems.is_empty());
+
+        let mut report = ConversionReport::default();
+        InstallSimulation::apply(&simulation, &mut report);
+        assert_eq!(report.changes, ["pacman would install libbar 2.0-1, foo 1.0-1"]);
+    }
+}
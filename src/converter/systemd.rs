@@ -0,0 +1,288 @@
+//! systemd unit fixups and validation
+//!
+//! Units written for Debian run as Debian's system accounts (`www-data`,
+//! `nogroup`), read `/etc/default` files and start programs at paths the
+//! other passes may have moved. This pass renames the accounts Arch has
+//! under another name, moves `/etc/default` files to `/etc/conf.d`, points
+//! Exec lines at the moved programs and reports what it cannot fix.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+use super::desktop::{exists_in, relocation_candidates};
+use super::{usrmerge_path, ConversionReport};
+
+/// Directories holding units, relative to the package root
+pub const UNIT_DIRS: &[&str] = &[
+    "usr/lib/systemd/system",
+    "usr/lib/systemd/user",
+    "lib/systemd/system",
+    "etc/systemd/system",
+];
+
+/// Unit types checked
+const UNIT_EXTENSIONS: &[&str] = &["service", "socket", "timer"];
+
+/// Keys naming a program to run
+const EXEC_KEYS: &[&str] = &[
+    "ExecCondition",
+    "ExecStartPre",
+    "ExecStart",
+    "ExecStartPost",
+    "ExecReload",
+    "ExecStop",
+    "ExecStopPost",
+];
+
+/// Keys naming accounts
+const ACCOUNT_KEYS: &[&str] = &["User", "Group", "SupplementaryGroups", "SocketUser", "SocketGroup"];
+
+/// Debian system accounts and their Arch counterparts
+const DEBIAN_ACCOUNTS: &[(&str, &str)] = &[("www-data", "http"), ("nogroup", "nobody")];
+
+/// What kind of problem a unit has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitIssueKind {
+    /// A Debian-only user or group
+    Account,
+    /// An `/etc/default` environment file
+    EnvironmentFile,
+    /// A program that is missing or moved
+    Executable,
+}
+
+/// A problem in one line of a unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitIssue {
+    /// Kind of problem
+    pub kind: UnitIssueKind,
+    /// Line number, from 1
+    pub line: usize,
+    /// What is wrong
+    pub message: String,
+    /// The line rewritten to work on Arch, when that is possible
+    pub fix: Option<String>,
+}
+
+/// Find the problems in a unit
+///
+/// `locate` maps an absolute path to where it is after conversion: the
+/// path itself, the path it was moved to, or `None` when it is in neither
+/// the package nor the system.
+pub fn review_unit(content: &str, locate: impl Fn(&str) -> Option<String>) -> Vec<UnitIssue> {
+    let mut issues = Vec::new();
+
+    for (number, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with(['#', ';', '[']) {
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once('=') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let issue = |kind, message: String, fix: Option<String>| UnitIssue {
+            kind,
+            line: number + 1,
+            message,
+            fix,
+        };
+
+        if ACCOUNT_KEYS.contains(&key) {
+            let arch_name = |account: &str| DEBIAN_ACCOUNTS.iter().find(|(debian, _)| *debian == account).map(|(_, arch)| *arch);
+            let renamed: Vec<String> = value
+                .split_whitespace()
+                .filter_map(|a| arch_name(a).map(|arch| format!("{} is named {}", a, arch)))
+                .collect();
+            if !renamed.is_empty() {
+                let fixed: Vec<&str> = value.split_whitespace().map(|a| arch_name(a).unwrap_or(a)).collect();
+                issues.push(issue(
+                    UnitIssueKind::Account,
+                    format!("{}={}: {} on Arch", key, value, renamed.join(", ")),
+                    Some(format!("{}={}", key, fixed.join(" "))),
+                ));
+            }
+            for account in value.split_whitespace() {
+                if account.starts_with("Debian-") || account == "_apt" {
+                    issues.push(issue(
+                        UnitIssueKind::Account,
+                        format!(
+                            "{}={} is a Debian account; create it with a sysusers.d entry or use DynamicUser=yes",
+                            key, account
+                        ),
+                        None,
+                    ));
+                }
+            }
+        } else if key == "EnvironmentFile" {
+            let optional = value.starts_with('-');
+            let path = value.trim_start_matches('-');
+            if let Some(name) = path.strip_prefix("/etc/default/") {
+                // A file nobody ships must not stop the unit from starting
+                let dash = if optional || locate(path).is_none() { "-" } else { "" };
+                issues.push(issue(
+                    UnitIssueKind::EnvironmentFile,
+                    format!("EnvironmentFile={} is a Debian defaults file; Arch uses /etc/conf.d", path),
+                    Some(format!("EnvironmentFile={}/etc/conf.d/{}", dash, name)),
+                ));
+            }
+        } else if EXEC_KEYS.contains(&key) {
+            let command = value.trim_start_matches(['-', '@', ':', '+', '!']);
+            let program = command.split_whitespace().next().unwrap_or_default();
+            if !program.starts_with('/') {
+                continue;
+            }
+            match locate(program) {
+                Some(ref located) if located == program => {}
+                Some(located) => issues.push(issue(
+                    UnitIssueKind::Executable,
+                    format!("{} runs {}, which conversion moves to {}", key, program, located),
+                    Some(format!("{}={}", key, value.replacen(program, &located, 1))),
+                )),
+                None => issues.push(issue(
+                    UnitIssueKind::Executable,
+                    format!("{} runs {}, which is neither in the package nor on this system", key, program),
+                    None,
+                )),
+            }
+        }
+    }
+
+    issues
+}
+
+/// All service, socket and timer units below the unit directories
+pub fn unit_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for dir in UNIT_DIRS {
+        let dir = root.join(dir);
+        if !dir.is_dir() {
+            continue;
+        }
+        for entry in walkdir::WalkDir::new(&dir).sort_by_file_name() {
+            let entry = entry?;
+            if entry.file_type().is_file()
+                && entry.path().extension().is_some_and(|e| UNIT_EXTENSIONS.iter().any(|u| e == *u))
+            {
+                files.push(entry.into_path());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Converter pass fixing systemd units
+pub struct UnitFixer {
+    /// Rewrite units, or only report their problems
+    fix: bool,
+}
+
+impl UnitFixer {
+    /// Create a new fixer
+    pub fn new(fix: bool) -> Self {
+        Self { fix }
+    }
+
+    /// Fix and report the units in the package root
+    pub fn apply(&self, pkg_root: &Path, report: &mut ConversionReport) -> Result<()> {
+        let locate = |path: &str| {
+            // `/sbin/foo` keeps working through the filesystem package's symlinks
+            let merged = usrmerge_path(Path::new(path)).is_some_and(|p| pkg_root.join(p).symlink_metadata().is_ok());
+            if exists_in(pkg_root, path) || Path::new(path).exists() || merged {
+                return Some(path.to_string());
+            }
+            relocation_candidates(path)
+                .into_iter()
+                .find(|candidate| exists_in(pkg_root, candidate))
+        };
+
+        for path in unit_files(pkg_root)? {
+            let rel = path.strip_prefix(pkg_root).unwrap_or(&path).to_path_buf();
+            let content = fs::read_to_string(&path)?;
+            let issues = review_unit(&content, locate);
+            if issues.is_empty() {
+                continue;
+            }
+
+            let mut lines: Vec<String> = content.lines().map(String::from).collect();
+            for issue in issues {
+                match issue.fix {
+                    Some(ref fixed) if self.fix => {
+                        if issue.kind == UnitIssueKind::EnvironmentFile {
+                            self.move_environment_file(pkg_root, &lines[issue.line - 1], report)?;
+                        }
+                        report.change(format!("Fixed /{}:{}: {}", rel.display(), issue.line, issue.message));
+                        lines[issue.line - 1] = fixed.clone();
+                    }
+                    _ => report.warn(format!("/{}:{}: {}", rel.display(), issue.line, issue.message)),
+                }
+            }
+
+            let mut updated = lines.join("\n");
+            if content.ends_with('\n') {
+                updated.push('\n');
+            }
+            if updated != content {
+                fs::write(&path, updated)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move a shipped `/etc/default` file named by an EnvironmentFile line to `/etc/conf.d`
+    fn move_environment_file(&self, pkg_root: &Path, line: &str, report: &mut ConversionReport) -> Result<()> {
+        let Some(name) = line
+            .split_once('=')
+            .and_then(|(_, value)| value.trim().trim_start_matches('-').strip_prefix("/etc/default/"))
+        else {
+            return Ok(());
+        };
+
+        let source = pkg_root.join("etc/default").join(name);
+        let target = pkg_root.join("etc/conf.d").join(name);
+        if source.is_file() && !target.exists() {
+            fs::create_dir_all(pkg_root.join("etc/conf.d"))?;
+            fs::rename(&source, &target)?;
+            report.change(format!("Moved /etc/default/{} to /etc/conf.d/{}", name, name));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_fix_unit() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("usr/lib/systemd/system")).unwrap();
+        fs::create_dir_all(root.join("usr/lib/foo")).unwrap();
+        fs::create_dir_all(root.join("etc/default")).unwrap();
+        fs::write(root.join("usr/lib/foo/food"), "").unwrap();
+        fs::write(root.join("etc/default/foo"), "OPTS=\n").unwrap();
+        fs::write(
+            root.join("usr/lib/systemd/system/foo.service"),
+            "[Service]\nUser=www-data\nGroup=Debian-foo\nEnvironmentFile=/etc/default/foo\n\
+             ExecStart=/usr/lib/x86_64-linux-gnu/foo/food $OPTS\nExecStartPost=/opt/missing\n",
+        )
+        .unwrap();
+
+        let mut report = ConversionReport::default();
+        UnitFixer::new(true).apply(root, &mut report).unwrap();
+
+        let unit = fs::read_to_string(root.join("usr/lib/systemd/system/foo.service")).unwrap();
+        assert_eq!(
+            unit,
+            "[Service]\nUser=http\nGroup=Debian-foo\nEnvironmentFile=/etc/conf.d/foo\n\
+             ExecStart=/usr/lib/foo/food $OPTS\nExecStartPost=/opt/missing\n"
+        );
+        assert!(root.join("etc/conf.d/foo").is_file());
+        assert_eq!(report.warnings.len(), 2);
+    }
+}
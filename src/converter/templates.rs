@@ -0,0 +1,202 @@
+//! User-overridable output templates
+//!
+//! Handlebars templates in `~/.config/rexeb/templates/` replace the
+//! generated PKGBUILD (`PKGBUILD.hbs`) and .INSTALL (`install.hbs`).
+//! Templates see the package metadata plus the content rexeb would have
+//! generated as `generated`, so they can wrap it instead of starting
+//! from scratch.
+
+use std::path::{Path, PathBuf};
+
+use handlebars::Handlebars;
+use serde_json::{json, Value};
+
+use crate::error::{RexebError, Result};
+use crate::models::{Dependency, DependencyType, PackageMetadata};
+
+/// Template file for PKGBUILDs
+pub const PKGBUILD_TEMPLATE: &str = "PKGBUILD.hbs";
+
+/// Template file for .INSTALL scripts
+pub const INSTALL_TEMPLATE: &str = "install.hbs";
+
+/// Set of user templates
+#[derive(Debug, Clone, Default)]
+pub struct Templates {
+    registry: Handlebars<'static>,
+    /// Registered (name, source) pairs
+    sources: Vec<(String, String)>,
+}
+
+impl Templates {
+    /// Default template directory
+    pub fn default_dir() -> Result<PathBuf> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| RexebError::Config("Could not find config directory".into()))?;
+        Ok(config_dir.join("rexeb").join("templates"))
+    }
+
+    /// Load the templates from the default directory
+    pub fn load() -> Result<Self> {
+        Self::from_dir(&Self::default_dir()?)
+    }
+
+    /// Load the templates present in `dir`
+    ///
+    /// Missing files (or a missing directory) leave the built-in output
+    /// in place.
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let mut templates = Self::default();
+
+        for name in [PKGBUILD_TEMPLATE, INSTALL_TEMPLATE] {
+            let path = dir.join(name);
+            if path.is_file() {
+                templates.register(name, &std::fs::read_to_string(&path)?)?;
+            }
+        }
+
+        Ok(templates)
+    }
+
+    /// Register template source under `name`
+    pub fn register(&mut self, name: &str, source: &str) -> Result<()> {
+        // Output is shell, not HTML
+        self.registry.register_escape_fn(handlebars::no_escape);
+        self.registry
+            .register_template_string(name, source)
+            .map_err(|e| RexebError::Template(format!("{}: {}", name, e)))?;
+        self.sources.retain(|(n, _)| n != name);
+        self.sources.push((name.to_string(), source.to_string()));
+        Ok(())
+    }
+
+    /// Registered template sources, for cache keys
+    pub fn sources(&self) -> &[(String, String)] {
+        &self.sources
+    }
+
+    /// Whether a template is registered under `name`
+    pub fn has(&self, name: &str) -> bool {
+        self.registry.has_template(name)
+    }
+
+    /// Render the PKGBUILD, falling back to the built-in one
+    pub fn render_pkgbuild(&self, metadata: &PackageMetadata) -> Result<String> {
+        self.render_generated_pkgbuild(metadata, metadata.to_pkgbuild())
+    }
+
+    /// Render a PKGBUILD around generated content other than the default one
+    pub fn render_generated_pkgbuild(&self, metadata: &PackageMetadata, generated: String) -> Result<String> {
+        if !self.has(PKGBUILD_TEMPLATE) {
+            return Ok(generated);
+        }
+
+        let mut context = Self::context(metadata)?;
+        context["generated"] = json!(generated);
+        self.render(PKGBUILD_TEMPLATE, &context)
+    }
+
+    /// Render the .INSTALL script from its generated functions
+    ///
+    /// `functions` maps function names to their (indented) bodies.
+    pub fn render_install(
+        &self,
+        metadata: &PackageMetadata,
+        functions: &[(&str, String)],
+        generated: String,
+    ) -> Result<String> {
+        if !self.has(INSTALL_TEMPLATE) {
+            return Ok(generated);
+        }
+
+        let mut context = Self::context(metadata)?;
+        context["functions"] = functions
+            .iter()
+            .map(|(name, body)| (name.to_string(), json!(body)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+        context["generated"] = json!(generated);
+        self.render(INSTALL_TEMPLATE, &context)
+    }
+
+    fn render(&self, name: &str, context: &Value) -> Result<String> {
+        self.registry
+            .render(name, context)
+            .map_err(|e| RexebError::Template(format!("{}: {}", name, e)))
+    }
+
+    /// Metadata fields plus the derived values the generators use
+    fn context(metadata: &PackageMetadata) -> Result<Value> {
+        let mapped = |types: &[DependencyType], format: fn(&Dependency) -> String| -> Vec<String> {
+            types
+                .iter()
+                .flat_map(|t| metadata.get_deps(*t))
+                .filter(|d| d.is_mapped())
+                .map(format)
+                .collect()
+        };
+
+        let mut context = serde_json::to_value(metadata)?;
+        context["pkgname"] = json!(metadata.effective_name());
+        context["pkgver"] = json!(metadata.version);
+        context["pkgrel"] = json!(metadata.release);
+        context["full_version"] = json!(metadata.full_version());
+        context["arch"] = json!(metadata.arch.to_arch_name());
+        context["license"] = json!(metadata.license.to_pkgbuild());
+        context["groups"] = json!(metadata.groups());
+        context["xdata"] = json!(metadata.xdata());
+        context["depends"] = json!(mapped(
+            &[DependencyType::Depends, DependencyType::PreDepends],
+            Dependency::to_arch_string
+        ));
+        context["optdepends"] = json!(mapped(
+            &[DependencyType::Recommends, DependencyType::Suggests],
+            Dependency::to_optdepend_string
+        ));
+        context["makedepends"] = json!(mapped(&[DependencyType::BuildDepends], Dependency::to_arch_string));
+        context["rexeb_version"] = json!(crate::VERSION);
+
+        Ok(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pkgbuild_template() {
+        let mut metadata = PackageMetadata::new("foo", "1.0");
+        metadata.description = "A <tool> & more".into();
+
+        let templates = Templates::default();
+        assert_eq!(templates.render_pkgbuild(&metadata).unwrap(), metadata.to_pkgbuild());
+
+        let mut templates = Templates::default();
+        templates
+            .register(PKGBUILD_TEMPLATE, "# Maintainer: ACME <pkg@acme.example>\n# {{description}}\n{{generated}}")
+            .unwrap();
+        let rendered = templates.render_pkgbuild(&metadata).unwrap();
+        assert!(rendered.starts_with("# Maintainer: ACME <pkg@acme.example>\n# A <tool> & more\n"));
+        assert!(rendered.ends_with(&metadata.to_pkgbuild()));
+    }
+
+    #[test]
+    fn test_install_template_and_errors() {
+        let metadata = PackageMetadata::new("foo", "1.0");
+        let mut templates = Templates::default();
+        templates
+            .register(INSTALL_TEMPLATE, "post_install() {\n{{functions.post_install}}\n  logger installed {{pkgname}}\n}\n")
+            .unwrap();
+
+        let rendered = templates
+            .render_install(&metadata, &[("post_install", "  ldconfig".into())], String::new())
+            .unwrap();
+        assert_eq!(rendered, "post_install() {\n  ldconfig\n  logger installed foo\n}\n");
+
+        assert!(matches!(
+            templates.register(PKGBUILD_TEMPLATE, "{{#if}}"),
+            Err(RexebError::Template(_))
+        ));
+    }
+}
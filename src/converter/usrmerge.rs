@@ -0,0 +1,158 @@
+//! usrmerge path normalization
+//!
+//! On Arch, `/bin`, `/sbin`, `/lib`, `/lib64` and `/usr/sbin` are symlinks
+//! owned by the `filesystem` package. Packages shipping real files below
+//! them are rejected by pacman, so the payload is moved into `/usr`.
+//! `filesystem` owns a few more symlinks the converter leaves alone; a
+//! payload clashing with one of them is only reported.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+/// Directories that are symlinks on Arch, and where their content belongs
+pub const USRMERGE_DIRS: &[(&str, &str)] = &[
+    ("bin", "usr/bin"),
+    ("sbin", "usr/bin"),
+    ("usr/sbin", "usr/bin"),
+    ("lib", "usr/lib"),
+    ("lib64", "usr/lib"),
+    ("usr/lib64", "usr/lib"),
+];
+
+/// Other paths the `filesystem` package owns as symlinks
+pub const FILESYSTEM_SYMLINKS: &[&str] = &["etc/mtab", "etc/os-release", "usr/local/share/man", "var/lock", "var/run"];
+
+/// Map a root-relative payload path to its usrmerged location
+///
+/// Returns `None` if the path is unaffected by usrmerge.
+pub fn usrmerge_path(rel_path: &Path) -> Option<PathBuf> {
+    let rel_path = rel_path.strip_prefix("/").unwrap_or(rel_path);
+
+    // Longest prefixes first so usr/sbin is not mistaken for something else
+    let mut dirs: Vec<_> = USRMERGE_DIRS.to_vec();
+    dirs.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+
+    for (from, to) in dirs {
+        if let Ok(rest) = rel_path.strip_prefix(from) {
+            return Some(Path::new(to).join(rest));
+        }
+    }
+
+    None
+}
+
+/// Find payload files that end up on the same path after usrmerge
+///
+/// Each pair is `(legacy path, path it collides with)`, both absolute.
+pub fn usrmerge_collisions(files: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+    use std::collections::HashMap;
+
+    let mut targets: HashMap<PathBuf, &PathBuf> = HashMap::new();
+    let mut collisions = Vec::new();
+
+    // Unaffected paths claim their location first
+    let (legacy, native): (Vec<_>, Vec<_>) = files
+        .iter()
+        .partition(|f| usrmerge_path(f).is_some());
+
+    for file in native {
+        targets.insert(Path::new("/").join(file.strip_prefix("/").unwrap_or(file)), file);
+    }
+
+    for file in legacy {
+        let target = Path::new("/").join(usrmerge_path(file).unwrap_or_default());
+        if let Some(existing) = targets.get(&target) {
+            collisions.push((file.clone(), (*existing).clone()));
+        } else {
+            targets.insert(target, file);
+        }
+    }
+
+    collisions
+}
+
+/// Find payload entries pacman refuses because `filesystem` owns a symlink there
+///
+/// Each pair is `(payload path, symlink it clashes with)`, both absolute.
+/// The usrmerge directories only clash when shipped as something other
+/// than a directory, since their content is moved; for the other symlinks
+/// every entry at or below them does.
+pub fn filesystem_symlink_conflicts(root: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut conflicts = Vec::new();
+
+    for (dir, _) in USRMERGE_DIRS {
+        if root.join(dir).symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+            conflicts.push((Path::new("/").join(dir), Path::new("/").join(dir)));
+        }
+    }
+
+    for link in FILESYSTEM_SYMLINKS {
+        let path = root.join(link);
+        if path.symlink_metadata().is_err() {
+            continue;
+        }
+        for entry in walkdir::WalkDir::new(&path).sort_by_file_name() {
+            let entry = entry?;
+            if entry.depth() == 0 && entry.file_type().is_dir() {
+                continue;
+            }
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            conflicts.push((Path::new("/").join(rel), Path::new("/").join(link)));
+        }
+    }
+
+    Ok(conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usrmerge_path() {
+        assert_eq!(usrmerge_path(Path::new("bin/ls")), Some(PathBuf::from("usr/bin/ls")));
+        assert_eq!(usrmerge_path(Path::new("/sbin/init")), Some(PathBuf::from("usr/bin/init")));
+        assert_eq!(usrmerge_path(Path::new("usr/sbin/foo")), Some(PathBuf::from("usr/bin/foo")));
+        assert_eq!(usrmerge_path(Path::new("lib64/ld.so")), Some(PathBuf::from("usr/lib/ld.so")));
+        assert_eq!(usrmerge_path(Path::new("usr/bin/foo")), None);
+        assert_eq!(usrmerge_path(Path::new("library/x")), None);
+    }
+
+    #[test]
+    fn test_usrmerge_collisions() {
+        let files = vec![
+            PathBuf::from("/bin/foo"),
+            PathBuf::from("/usr/bin/foo"),
+            PathBuf::from("/sbin/bar"),
+        ];
+        let collisions = usrmerge_collisions(&files);
+        assert_eq!(collisions, vec![(PathBuf::from("/bin/foo"), PathBuf::from("/usr/bin/foo"))]);
+
+        // Two legacy directories merging into the same one
+        let files = vec![PathBuf::from("/bin/foo"), PathBuf::from("/sbin/foo")];
+        assert_eq!(usrmerge_collisions(&files), vec![(PathBuf::from("/sbin/foo"), PathBuf::from("/bin/foo"))]);
+    }
+
+    #[test]
+    fn test_filesystem_symlink_conflicts() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("bin")).unwrap();
+        std::fs::create_dir_all(root.join("var/run/foo")).unwrap();
+        std::fs::create_dir_all(root.join("usr")).unwrap();
+        std::fs::write(root.join("bin/foo"), "").unwrap();
+        std::fs::write(root.join("var/run/foo/pid"), "").unwrap();
+        std::os::unix::fs::symlink("lib", root.join("usr/lib64")).unwrap();
+
+        let conflicts = filesystem_symlink_conflicts(root).unwrap();
+        assert_eq!(
+            conflicts,
+            vec![
+                (PathBuf::from("/usr/lib64"), PathBuf::from("/usr/lib64")),
+                (PathBuf::from("/var/run/foo"), PathBuf::from("/var/run")),
+                (PathBuf::from("/var/run/foo/pid"), PathBuf::from("/var/run")),
+            ]
+        );
+    }
+}
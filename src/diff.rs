@@ -0,0 +1,792 @@
+//! Differences between two packages
+//!
+//! Compares a deb with the package converted from it, or two builds of a
+//! converted package: metadata fields, dependency relations, the file list
+//! with sizes, checksums, modes and link targets, and maintainer or install
+//! scripts. Both sides are read into [`PackageContents`] first, so any
+//! combination of `.deb` and `.pkg.tar.*` files can be compared.
+//!
+//! [`verify`] goes further for a deb and the package converted from it:
+//! it follows files moved by the conversion, checks the package records
+//! the deb as its source and accounts for every dependency.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::ConversionConfig;
+use crate::converter::ArchDetector;
+use crate::error::{RexebError, Result};
+use crate::models::{DependencyType, MaintainerScript};
+use crate::parsers::deb::DebParser;
+use crate::repo::parse_pkginfo;
+
+/// Relations compared, with their `.PKGINFO` keys
+const RELATIONS: &[(&str, &str)] = &[
+    ("depends", "depend"),
+    ("optdepends", "optdepend"),
+    ("provides", "provides"),
+    ("conflicts", "conflict"),
+    ("replaces", "replaces"),
+];
+
+/// A file, symlink target included
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// Size in bytes
+    pub size: u64,
+    /// SHA-256 of the content
+    pub sha256: String,
+    /// Permission bits
+    pub mode: u32,
+    /// Target, for symlinks
+    pub link: Option<PathBuf>,
+}
+
+/// What a package consists of, in a form common to debs and Arch packages
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageContents {
+    /// Metadata fields (name, version, arch, description, ...)
+    pub fields: BTreeMap<String, String>,
+    /// Relations (depends, provides, ...) and their entries
+    pub relations: BTreeMap<String, BTreeSet<String>>,
+    /// Files and symlinks by absolute path
+    pub files: BTreeMap<PathBuf, FileEntry>,
+    /// Scripts by name (`postinst`, `.INSTALL`, ...)
+    pub scripts: BTreeMap<String, String>,
+    /// `xdata` entries of an Arch package (`rexeb_source_sha256`, ...);
+    /// for a deb, the ones a conversion would record (`debian_version`)
+    pub xdata: BTreeMap<String, String>,
+}
+
+impl PackageContents {
+    /// Read a `.deb` or `.pkg.tar.*` file
+    pub fn load(path: &Path) -> Result<Self> {
+        let name = path.to_string_lossy();
+        if name.ends_with(".deb") {
+            Self::from_deb(path)
+        } else if name.contains(".pkg.tar") {
+            Self::from_arch_package(path)
+        } else {
+            Err(RexebError::UnsupportedFormat(format!(
+                "{} (expected a .deb or .pkg.tar.* file)",
+                path.display()
+            )))
+        }
+    }
+
+    /// Read a Debian package
+    pub fn from_deb(path: &Path) -> Result<Self> {
+        Self::read_deb(path, None)
+    }
+
+    /// Read a Debian package with the version and architecture a conversion
+    /// with `options` would give it
+    pub fn from_deb_converted(path: &Path, options: &ConversionConfig) -> Result<Self> {
+        Self::read_deb(path, Some(options))
+    }
+
+    fn read_deb(path: &Path, converted: Option<&ConversionConfig>) -> Result<Self> {
+        let parser = DebParser::new(path)?;
+        let mut metadata = parser.parse()?;
+
+        let arch = match converted {
+            Some(options) => {
+                metadata.normalize_version();
+                ArchDetector::package_arch(metadata.arch, parser.extract_dir(), options.detect_arch_any)
+                    .to_arch_name()
+                    .to_string()
+            }
+            None => metadata.arch.to_string(),
+        };
+
+        let mut contents = Self::default();
+        contents.fields.insert("name".into(), metadata.name.clone());
+        contents.fields.insert("version".into(), metadata.full_version());
+        contents.fields.insert("arch".into(), arch);
+        contents.fields.insert("description".into(), metadata.description.clone());
+        if let Some(ref url) = metadata.url {
+            contents.fields.insert("url".into(), url.clone());
+        }
+        if let Some(ref version) = metadata.provenance.original_version {
+            contents.xdata.insert("debian_version".into(), version.clone());
+        }
+
+        for (relation, types) in [
+            ("depends", &[DependencyType::Depends, DependencyType::PreDepends][..]),
+            ("optdepends", &[DependencyType::Recommends, DependencyType::Suggests]),
+            ("provides", &[DependencyType::Provides]),
+            ("conflicts", &[DependencyType::Conflicts, DependencyType::Breaks]),
+            ("replaces", &[DependencyType::Replaces]),
+        ] {
+            let entries: BTreeSet<String> = types
+                .iter()
+                .flat_map(|t| metadata.get_deps(*t))
+                .map(|dep| dep.to_arch_string())
+                .collect();
+            if !entries.is_empty() {
+                contents.relations.insert(relation.to_string(), entries);
+            }
+        }
+
+        for script in [
+            MaintainerScript::PreInst,
+            MaintainerScript::PostInst,
+            MaintainerScript::PreRm,
+            MaintainerScript::PostRm,
+        ] {
+            if let Some(content) = metadata.get_script(script) {
+                contents.scripts.insert(format!("{:?}", script).to_lowercase(), content.to_string());
+            }
+        }
+
+        let root = parser.extract_dir();
+        for entry in walkdir::WalkDir::new(root).sort_by_file_name() {
+            let entry = entry?;
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            let rel = entry.path().strip_prefix(root).unwrap_or(entry.path());
+            let file = match entry.path_is_symlink() {
+                true => FileEntry {
+                    size: 0,
+                    sha256: String::new(),
+                    mode: 0o777,
+                    link: Some(std::fs::read_link(entry.path())?),
+                },
+                false => {
+                    use std::os::unix::fs::PermissionsExt;
+                    let mut file = File::open(entry.path())?;
+                    FileEntry {
+                        size: entry.metadata()?.len(),
+                        sha256: sha256_of(&mut file)?,
+                        mode: entry.metadata()?.permissions().mode() & 0o7777,
+                        link: None,
+                    }
+                }
+            };
+            contents.files.insert(Path::new("/").join(rel), file);
+        }
+
+        Ok(contents)
+    }
+
+    /// Read a built Arch package
+    pub fn from_arch_package(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let name = path.to_string_lossy();
+        let reader: Box<dyn Read> = if name.ends_with(".zst") {
+            Box::new(zstd::Decoder::new(file)?)
+        } else if name.ends_with(".xz") {
+            Box::new(xz2::read::XzDecoder::new(file))
+        } else if name.ends_with(".gz") {
+            Box::new(flate2::read::GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+
+        let mut contents = Self::default();
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let entry_path = entry.path()?.into_owned();
+            let entry_name = entry_path.to_string_lossy().into_owned();
+
+            match entry_name.as_str() {
+                ".PKGINFO" => {
+                    let mut content = String::new();
+                    entry.read_to_string(&mut content)?;
+                    for (key, values) in parse_pkginfo(&content) {
+                        match RELATIONS.iter().find(|(_, pkginfo_key)| *pkginfo_key == key) {
+                            Some((relation, _)) => {
+                                contents.relations.insert(relation.to_string(), values.into_iter().collect());
+                            }
+                            None if key == "xdata" => {
+                                for value in values {
+                                    if let Some((name, value)) = value.split_once('=') {
+                                        contents.xdata.insert(name.to_string(), value.to_string());
+                                    }
+                                }
+                            }
+                            None if ["pkgname", "pkgver", "arch", "pkgdesc", "url"].contains(&key.as_str()) => {
+                                let field = match key.as_str() {
+                                    "pkgname" => "name",
+                                    "pkgver" => "version",
+                                    "pkgdesc" => "description",
+                                    other => other,
+                                };
+                                contents.fields.insert(field.to_string(), values.join(" "));
+                            }
+                            None => {}
+                        }
+                    }
+                }
+                ".INSTALL" => {
+                    let mut content = String::new();
+                    entry.read_to_string(&mut content)?;
+                    contents.scripts.insert(entry_name, content);
+                }
+                _ if entry_name.starts_with('.') => {}
+                _ => {
+                    let header = entry.header();
+                    let file = match header.entry_type() {
+                        tar::EntryType::Directory => continue,
+                        tar::EntryType::Symlink => FileEntry {
+                            size: 0,
+                            sha256: String::new(),
+                            mode: 0o777,
+                            link: entry.link_name()?.map(|l| l.into_owned()),
+                        },
+                        _ => {
+                            let mode = header.mode()? & 0o7777;
+                            FileEntry {
+                                size: header.size()?,
+                                mode,
+                                sha256: sha256_of(&mut entry)?,
+                                link: None,
+                            }
+                        }
+                    };
+                    contents.files.insert(Path::new("/").join(entry_path), file);
+                }
+            }
+        }
+
+        if !contents.fields.contains_key("name") {
+            return Err(RexebError::Extraction(format!("{} has no .PKGINFO", path.display())));
+        }
+        Ok(contents)
+    }
+
+    /// `name version` for headings
+    pub fn label(&self) -> String {
+        let get = |key: &str| self.fields.get(key).map(String::as_str).unwrap_or_default();
+        format!("{} {}", get("name"), get("version"))
+    }
+}
+
+/// SHA-256 of everything a reader yields
+fn sha256_of(reader: &mut impl Read) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(reader, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// A metadata field that differs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// Field name
+    pub field: String,
+    /// Value in the old package
+    pub old: Option<String>,
+    /// Value in the new package
+    pub new: Option<String>,
+}
+
+/// Entries added to and removed from a relation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelationChange {
+    /// Relation name (`depends`, ...)
+    pub relation: String,
+    /// Entries only in the new package
+    pub added: Vec<String>,
+    /// Entries only in the old package
+    pub removed: Vec<String>,
+}
+
+/// A file present in both packages that differs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileChange {
+    /// Absolute path
+    pub path: PathBuf,
+    /// What differs (`content`, `mode 644 -> 755`, ...)
+    pub changes: Vec<String>,
+}
+
+/// A script that differs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptChange {
+    /// Script name
+    pub name: String,
+    /// Changed lines, `-` for removed and `+` for added
+    pub lines: Vec<String>,
+}
+
+/// Differences between two packages
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageDiff {
+    /// `name version` of the old package
+    pub old: String,
+    /// `name version` of the new package
+    pub new: String,
+    /// Metadata fields that differ
+    pub fields: Vec<FieldChange>,
+    /// Relations that differ
+    pub relations: Vec<RelationChange>,
+    /// Files only in the new package
+    pub added_files: Vec<PathBuf>,
+    /// Files only in the old package
+    pub removed_files: Vec<PathBuf>,
+    /// Files in both that differ
+    pub changed_files: Vec<FileChange>,
+    /// Scripts that differ, were added or were removed
+    pub scripts: Vec<ScriptChange>,
+}
+
+impl PackageDiff {
+    /// Whether the packages are the same
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+            && self.relations.is_empty()
+            && self.added_files.is_empty()
+            && self.removed_files.is_empty()
+            && self.changed_files.is_empty()
+            && self.scripts.is_empty()
+    }
+}
+
+/// Compare two packages
+pub fn diff(old: &PackageContents, new: &PackageContents) -> PackageDiff {
+    let mut result = PackageDiff {
+        old: old.label(),
+        new: new.label(),
+        ..Default::default()
+    };
+
+    let field_names: BTreeSet<&String> = old.fields.keys().chain(new.fields.keys()).collect();
+    for field in field_names {
+        let (a, b) = (old.fields.get(field), new.fields.get(field));
+        if a != b {
+            result.fields.push(FieldChange {
+                field: field.clone(),
+                old: a.cloned(),
+                new: b.cloned(),
+            });
+        }
+    }
+
+    let empty = BTreeSet::new();
+    for (relation, _) in RELATIONS {
+        let a = old.relations.get(*relation).unwrap_or(&empty);
+        let b = new.relations.get(*relation).unwrap_or(&empty);
+        let added: Vec<String> = b.difference(a).cloned().collect();
+        let removed: Vec<String> = a.difference(b).cloned().collect();
+        if !added.is_empty() || !removed.is_empty() {
+            result.relations.push(RelationChange {
+                relation: relation.to_string(),
+                added,
+                removed,
+            });
+        }
+    }
+
+    for (path, b) in &new.files {
+        let Some(a) = old.files.get(path) else {
+            result.added_files.push(path.clone());
+            continue;
+        };
+        let mut changes = Vec::new();
+        if a.link != b.link {
+            let show = |link: &Option<PathBuf>| link.as_ref().map_or("(file)".to_string(), |l| l.display().to_string());
+            changes.push(format!("link {} -> {}", show(&a.link), show(&b.link)));
+        } else if a.sha256 != b.sha256 {
+            changes.push(match a.size == b.size {
+                true => "content".to_string(),
+                false => format!("size {} -> {}", a.size, b.size),
+            });
+        }
+        if a.mode != b.mode && a.link.is_none() && b.link.is_none() {
+            changes.push(format!("mode {:o} -> {:o}", a.mode, b.mode));
+        }
+        if !changes.is_empty() {
+            result.changed_files.push(FileChange { path: path.clone(), changes });
+        }
+    }
+    result.removed_files = old.files.keys().filter(|p| !new.files.contains_key(*p)).cloned().collect();
+
+    let script_names: BTreeSet<&String> = old.scripts.keys().chain(new.scripts.keys()).collect();
+    for name in script_names {
+        let a = old.scripts.get(name).map(String::as_str).unwrap_or_default();
+        let b = new.scripts.get(name).map(String::as_str).unwrap_or_default();
+        if a != b {
+            result.scripts.push(ScriptChange {
+                name: name.clone(),
+                lines: diff_lines(a, b),
+            });
+        }
+    }
+
+    result
+}
+
+/// Removed and added lines between two texts, in order
+fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence lengths of the suffixes
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = match a[i] == b[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(format!("-{}", a[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+{}", b[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// Directories merged into /usr on Arch, with where their files go
+const MERGED_DIRS: &[(&str, &str)] = &[
+    ("/bin/", "/usr/bin/"),
+    ("/sbin/", "/usr/bin/"),
+    ("/usr/sbin/", "/usr/bin/"),
+    ("/lib64/", "/usr/lib/"),
+    ("/lib/", "/usr/lib/"),
+];
+
+/// Whether the package names the deb as its source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceCheck {
+    /// The recorded SHA-256 is the deb's
+    Matches,
+    /// The package was converted from another file
+    Differs {
+        /// SHA-256 recorded in the package
+        recorded: String,
+    },
+    /// The package records no source
+    NotRecorded,
+}
+
+/// What became of a dependency of the deb
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyCheck {
+    /// Debian dependency name
+    pub debian: String,
+    /// Arch package it maps to, if known
+    pub arch: Option<String>,
+    /// `depends`, `optdepends`, or `missing` when the package has neither
+    pub outcome: String,
+}
+
+/// How faithfully a package reproduces the deb it was converted from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Verification {
+    /// `name version` of the deb
+    pub deb: String,
+    /// `name version` of the package
+    pub package: String,
+    /// Whether the package records the deb as its source
+    pub source: SourceCheck,
+    /// Metadata fields that differ
+    pub fields: Vec<FieldChange>,
+    /// Files with the same content at the same path
+    pub identical: usize,
+    /// Files with the same content at another path, as (deb path, package path)
+    pub moved: Vec<(PathBuf, PathBuf)>,
+    /// Files whose content or mode changed
+    pub altered: Vec<FileChange>,
+    /// Files of the deb the package does not have
+    pub dropped: Vec<PathBuf>,
+    /// Files only the package has
+    pub added: Vec<PathBuf>,
+    /// The deb's hard dependencies
+    pub dependencies: Vec<DependencyCheck>,
+    /// Maintainer scripts of the deb left without an install script
+    pub scripts_dropped: Vec<String>,
+}
+
+impl Verification {
+    /// Whether the package holds exactly the deb's files and dependencies
+    pub fn is_exact(&self) -> bool {
+        self.source == SourceCheck::Matches
+            && self.altered.is_empty()
+            && self.dropped.is_empty()
+            && self.added.is_empty()
+            && self.scripts_dropped.is_empty()
+            && self.dependencies.iter().all(|d| d.outcome != "missing")
+    }
+}
+
+/// Check a converted package against the deb it came from
+///
+/// `deb_sha256` is the hash of the deb file; `map` gives the Arch package
+/// a Debian dependency maps to.
+pub fn verify(
+    deb: &PackageContents,
+    deb_sha256: &str,
+    package: &PackageContents,
+    map: impl Fn(&str) -> Option<String>,
+) -> Verification {
+    let source = match package.xdata.get("rexeb_source_sha256") {
+        Some(recorded) if recorded == deb_sha256 => SourceCheck::Matches,
+        Some(recorded) => SourceCheck::Differs { recorded: recorded.clone() },
+        None => SourceCheck::NotRecorded,
+    };
+
+    // `deb` is read as converted, so a bad version mapping or architecture
+    // choice shows up next to the recorded Debian version
+    let fields = ["name", "version", "arch"]
+        .into_iter()
+        .map(|field| (field, deb.fields.get(field), package.fields.get(field)))
+        .chain([("debian_version", deb.xdata.get("debian_version"), package.xdata.get("debian_version"))])
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| FieldChange { field: field.to_string(), old: old.cloned(), new: new.cloned() })
+        .collect();
+
+    let deb_label = match (deb.fields.get("name"), deb.xdata.get("debian_version")) {
+        (Some(name), Some(version)) => format!("{} {}", name, version),
+        _ => deb.label(),
+    };
+    let mut result = Verification {
+        deb: deb_label,
+        package: package.label(),
+        source,
+        fields,
+        identical: 0,
+        moved: Vec::new(),
+        altered: Vec::new(),
+        dropped: Vec::new(),
+        added: Vec::new(),
+        dependencies: Vec::new(),
+        scripts_dropped: Vec::new(),
+    };
+
+    let mut claimed = BTreeSet::new();
+    let mut unmatched = Vec::new();
+    for (path, entry) in &deb.files {
+        let target = [path.clone()]
+            .into_iter()
+            .chain(merged_path(path))
+            .find(|p| package.files.contains_key(p) && !claimed.contains(p));
+        let Some(target) = target else {
+            unmatched.push((path, entry));
+            continue;
+        };
+        let packaged = &package.files[&target];
+        claimed.insert(target.clone());
+
+        let mut changes = Vec::new();
+        if entry.link != packaged.link || entry.sha256 != packaged.sha256 {
+            changes.push(match (&entry.link, &packaged.link) {
+                (None, None) => "content".to_string(),
+                _ => "link target".to_string(),
+            });
+        }
+        if entry.mode != packaged.mode && entry.link.is_none() && packaged.link.is_none() {
+            changes.push(format!("mode {:o} -> {:o}", entry.mode, packaged.mode));
+        }
+        match (changes.is_empty(), &target == path) {
+            (true, true) => result.identical += 1,
+            (true, false) => result.moved.push((path.clone(), target)),
+            (false, _) => result.altered.push(FileChange { path: path.clone(), changes }),
+        }
+    }
+
+    // Files relocated elsewhere (a prefix, a renamed directory) keep their content
+    let mut by_hash: BTreeMap<&str, Vec<&PathBuf>> = BTreeMap::new();
+    for (path, entry) in &package.files {
+        if !claimed.contains(path) && entry.link.is_none() {
+            by_hash.entry(entry.sha256.as_str()).or_default().push(path);
+        }
+    }
+    for (path, entry) in unmatched {
+        let found = match entry.link {
+            None => by_hash.get_mut(entry.sha256.as_str()).and_then(|paths| paths.pop()),
+            Some(_) => None,
+        };
+        match found {
+            Some(target) => {
+                claimed.insert(target.clone());
+                result.moved.push((path.clone(), target.clone()));
+            }
+            None => result.dropped.push(path.clone()),
+        }
+    }
+    result.added = package.files.keys().filter(|p| !claimed.contains(*p)).cloned().collect();
+
+    let names = |relation: &str, contents: &PackageContents| -> BTreeSet<String> {
+        contents
+            .relations
+            .get(relation)
+            .into_iter()
+            .flatten()
+            .map(|entry| relation_name(entry).to_string())
+            .collect()
+    };
+    let (depends, optdepends) = (names("depends", package), names("optdepends", package));
+    for debian in names("depends", deb) {
+        let arch = map(&debian);
+        let candidates = [Some(debian.clone()), arch.clone()];
+        let outcome = if candidates.iter().flatten().any(|name| depends.contains(name)) {
+            "depends"
+        } else if candidates.iter().flatten().any(|name| optdepends.contains(name)) {
+            "optdepends"
+        } else {
+            "missing"
+        };
+        result.dependencies.push(DependencyCheck { debian, arch, outcome: outcome.to_string() });
+    }
+
+    if !package.scripts.contains_key(".INSTALL") {
+        result.scripts_dropped = deb.scripts.keys().cloned().collect();
+    }
+    result
+}
+
+/// Where a file in a directory merged into /usr ends up
+fn merged_path(path: &Path) -> Option<PathBuf> {
+    let path = path.to_string_lossy();
+    MERGED_DIRS
+        .iter()
+        .find_map(|(from, to)| path.strip_prefix(from).map(|rest| PathBuf::from(format!("{}{}", to, rest))))
+}
+
+/// Package name of a relation entry (`foo>=1.0`, `foo: why`)
+fn relation_name(entry: &str) -> &str {
+    entry.split(['<', '>', '=', ':', ' ']).next().unwrap_or(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(sha256: &str, size: u64, mode: u32) -> FileEntry {
+        FileEntry {
+            size,
+            sha256: sha256.to_string(),
+            mode,
+            link: None,
+        }
+    }
+
+    #[test]
+    fn test_diff_packages() {
+        let mut old = PackageContents::default();
+        old.fields.insert("name".into(), "foo".into());
+        old.fields.insert("version".into(), "1.0-1".into());
+        old.relations.insert("depends".into(), ["glibc".to_string(), "zlib".to_string()].into());
+        old.files.insert("/usr/bin/foo".into(), file("a", 10, 0o755));
+        old.files.insert("/usr/share/foo/old".into(), file("b", 1, 0o644));
+        old.scripts.insert(".INSTALL".into(), "post_install() {\n  ldconfig\n}\n".into());
+
+        let mut new = old.clone();
+        new.fields.insert("version".into(), "1.1-1".into());
+        new.relations.insert("depends".into(), ["glibc".to_string(), "openssl".to_string()].into());
+        new.files.remove(Path::new("/usr/share/foo/old"));
+        new.files.insert("/usr/bin/foo".into(), file("c", 12, 0o775));
+        new.files.insert("/usr/share/foo/new".into(), file("d", 1, 0o644));
+        new.scripts.insert(".INSTALL".into(), "post_install() {\n  systemd-sysusers\n}\n".into());
+
+        let diff = diff(&old, &new);
+        assert_eq!((diff.old.as_str(), diff.new.as_str()), ("foo 1.0-1", "foo 1.1-1"));
+        assert_eq!(diff.fields.len(), 1);
+        assert_eq!(diff.relations[0].added, ["openssl"]);
+        assert_eq!(diff.relations[0].removed, ["zlib"]);
+        assert_eq!(diff.added_files, [Path::new("/usr/share/foo/new")]);
+        assert_eq!(diff.removed_files, [Path::new("/usr/share/foo/old")]);
+        assert_eq!(diff.changed_files[0].changes, ["size 10 -> 12", "mode 755 -> 775"]);
+        assert_eq!(diff.scripts[0].lines, ["-  ldconfig", "+  systemd-sysusers"]);
+        assert!(super::diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_verify_conversion() {
+        let mut deb = PackageContents::default();
+        deb.fields.insert("name".into(), "foo".into());
+        deb.fields.insert("version".into(), "1.0-1".into());
+        deb.relations.insert("depends".into(), ["libc6>=2.34".to_string(), "libfoo1".to_string(), "zlib1g".to_string()].into());
+        deb.files.insert("/usr/bin/foo".into(), file("a", 10, 0o755));
+        deb.files.insert("/bin/foo-helper".into(), file("b", 5, 0o755));
+        deb.files.insert("/usr/share/foo/data".into(), file("c", 3, 0o644));
+        deb.files.insert("/usr/share/foo/run.sh".into(), file("d", 20, 0o755));
+        deb.files.insert("/usr/share/doc/foo/changelog.Debian.gz".into(), file("e", 7, 0o644));
+        deb.scripts.insert("postinst".into(), "ldconfig\n".into());
+
+        let mut package = deb.clone();
+        package.relations.insert("depends".into(), ["glibc>=2.34".to_string(), "zlib".to_string()].into());
+        package.files = BTreeMap::new();
+        package.files.insert("/usr/bin/foo".into(), file("a", 10, 0o755));
+        package.files.insert("/usr/bin/foo-helper".into(), file("b", 5, 0o755));
+        package.files.insert("/opt/foo/data".into(), file("c", 3, 0o644));
+        package.files.insert("/usr/share/foo/run.sh".into(), file("f", 21, 0o755));
+        package.files.insert("/usr/lib/sysusers.d/foo.conf".into(), file("g", 9, 0o644));
+        package.xdata.insert("rexeb_source_sha256".into(), "abc".into());
+        deb.xdata.insert("debian_version".into(), "1.0-0ubuntu1".into());
+        package.xdata.insert("debian_version".into(), "1.0-0ubuntu1".into());
+
+        let map = |name: &str| match name {
+            "libc6" => Some("glibc".to_string()),
+            "zlib1g" => Some("zlib".to_string()),
+            _ => None,
+        };
+        let result = verify(&deb, "abc", &package, map);
+        assert_eq!(result.source, SourceCheck::Matches);
+        assert!(result.fields.is_empty());
+        assert_eq!(result.identical, 1);
+        assert_eq!(
+            result.moved,
+            [
+                (PathBuf::from("/bin/foo-helper"), PathBuf::from("/usr/bin/foo-helper")),
+                (PathBuf::from("/usr/share/foo/data"), PathBuf::from("/opt/foo/data")),
+            ]
+        );
+        assert_eq!(result.altered[0].path, Path::new("/usr/share/foo/run.sh"));
+        assert_eq!(result.dropped, [Path::new("/usr/share/doc/foo/changelog.Debian.gz")]);
+        assert_eq!(result.added, [Path::new("/usr/lib/sysusers.d/foo.conf")]);
+        let outcomes: Vec<_> = result.dependencies.iter().map(|d| (d.debian.as_str(), d.outcome.as_str())).collect();
+        assert_eq!(outcomes, [("libc6", "depends"), ("libfoo1", "missing"), ("zlib1g", "depends")]);
+        assert_eq!(result.scripts_dropped, ["postinst"]);
+        assert!(!result.is_exact());
+
+        let other = verify(&deb, "def", &package, map);
+        assert_eq!(other.source, SourceCheck::Differs { recorded: "abc".into() });
+    }
+
+    #[test]
+    fn test_verify_metadata_mismatch() {
+        let mut deb = PackageContents::default();
+        deb.fields.insert("name".into(), "foo".into());
+        deb.fields.insert("version".into(), "1:2.0-1".into());
+        deb.fields.insert("arch".into(), "x86_64".into());
+        deb.xdata.insert("debian_version".into(), "1:2.0-1".into());
+        let package = deb.clone();
+        assert!(verify(&deb, "abc", &package, |_| None).fields.is_empty());
+
+        // The epoch got lost on the way
+        let mut package = deb.clone();
+        package.fields.insert("version".into(), "2.0-1".into());
+        let fields = verify(&deb, "abc", &package, |_| None).fields;
+        assert_eq!(fields.len(), 1);
+        assert_eq!((fields[0].field.as_str(), fields[0].new.as_deref()), ("version", Some("2.0-1")));
+
+        // Native code shipped as any
+        let mut package = deb.clone();
+        package.fields.insert("arch".into(), "any".into());
+        let fields = verify(&deb, "abc", &package, |_| None).fields;
+        assert_eq!(fields.len(), 1);
+        assert_eq!((fields[0].field.as_str(), fields[0].old.as_deref()), ("arch", Some("x86_64")));
+    }
+}
@@ -7,75 +7,127 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 pub enum RexebError {
     #[error("IO error: {0}")]
+    /// I/O failure
     Io(#[from] std::io::Error),
 
     #[error("Walkdir error: {0}")]
+    /// Directory traversal failure
     WalkDir(#[from] walkdir::Error),
 
     #[error("Failed to parse .deb package: {0}")]
+    /// Malformed .deb archive
     DebParsing(String),
 
     #[error("Invalid control file: {0}")]
+    /// Malformed control file
     InvalidControl(String),
 
     #[error("Missing required field in control file: {0}")]
+    /// Required control field is absent
     MissingField(String),
 
     #[error("Failed to extract archive: {0}")]
+    /// Archive extraction failure
     Extraction(String),
 
     #[error("Invalid architecture: {0}")]
+    /// Unknown or unsupported architecture
     InvalidArchitecture(String),
 
     #[error("Dependency resolution failed: {0}")]
+    /// Dependency resolution failure
     DependencyResolution(String),
 
     #[error("Package building failed: {0}")]
+    /// Package build failure
     PackageBuild(String),
 
     #[error("File not found: {path}")]
-    FileNotFound { path: PathBuf },
+    /// Referenced file does not exist
+    FileNotFound {
+        /// Missing path
+        path: PathBuf,
+    },
 
     #[error("Unsupported package format: {0}")]
+    /// Input format is not supported
     UnsupportedFormat(String),
 
     #[error("Network error: {0}")]
+    /// Network failure
     Network(String),
 
     #[error("AUR API error: {0}")]
+    /// Error reported by the AUR RPC
     AurApi(String),
 
     #[error("Script translation error: {0}")]
+    /// Maintainer script translation failure
     ScriptTranslation(String),
 
     #[error("Conflict detected: {0}")]
+    /// Conflict with installed packages
     Conflict(String),
 
     #[error("Validation error: {0}")]
+    /// Validation failure
     Validation(String),
 
+    #[error("pacman failed: {0}")]
+    /// pacman (or an AUR helper wrapping it) failed
+    Pacman(String),
+
+    #[error("Repository error: {0}")]
+    /// Local repository failure
+    Repository(String),
+
+    #[error("Template error: {0}")]
+    /// Invalid or failing user template
+    Template(String),
+
     #[error("Configuration error: {0}")]
+    /// Invalid configuration
     Config(String),
 
     #[error("Serialization error: {0}")]
+    /// JSON (de)serialization failure
     Serialization(#[from] serde_json::Error),
 
     #[error("TOML error: {0}")]
+    /// TOML parsing failure
     Toml(#[from] toml::de::Error),
 
     #[error("Regex error: {0}")]
+    /// Invalid regular expression
     Regex(#[from] regex::Error),
 
     #[error("HTTP request error: {0}")]
+    /// HTTP client failure
     Http(#[from] reqwest::Error),
 
     #[error("{0}")]
+    /// Analysis found problems at or above the requested severity
+    Findings(String),
+
+    #[error("Cancelled: {0}")]
+    /// The user cancelled a running operation
+    Cancelled(String),
+
+    #[error("Input required: {0}")]
+    /// A question came up with `--non-interactive` in effect
+    InputRequired(String),
+
+    #[error("{0}")]
+    /// Any other error
     Other(String),
 }
 
 /// Result type alias for rexeb operations
 pub type Result<T> = std::result::Result<T, RexebError>;
 
+/// Exit status for an invalid command line, as clap reports it
+pub const USAGE_EXIT_CODE: u8 = 2;
+
 impl RexebError {
     /// Create a new parsing error
     pub fn parse(msg: impl Into<String>) -> Self {
@@ -96,4 +148,67 @@ impl RexebError {
     pub fn file_not_found(path: impl Into<PathBuf>) -> Self {
         Self::FileNotFound { path: path.into() }
     }
+
+    /// Process exit status for this error
+    ///
+    /// The codes are stable, so scripts can branch on the kind of failure:
+    ///
+    /// | Code | Failure |
+    /// |------|---------|
+    /// | 1 | anything not listed below |
+    /// | 2 | the command line was invalid ([`USAGE_EXIT_CODE`]) |
+    /// | 3 | an answer was needed under `--non-interactive` |
+    /// | 4 | the input package could not be found or read |
+    /// | 5 | dependencies could not be resolved |
+    /// | 6 | building the package failed |
+    /// | 7 | conflicts with installed packages were detected |
+    /// | 8 | a package failed verification (`verify`, namcap, checksums) |
+    /// | 9 | pacman failed |
+    /// | 10 | findings failed the requested threshold (`analyze --fail-on`, `test`, `doctor`) |
+    /// | 130 | interrupted |
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::InputRequired(_) => 3,
+            Self::FileNotFound { .. }
+            | Self::DebParsing(_)
+            | Self::InvalidControl(_)
+            | Self::MissingField(_)
+            | Self::Extraction(_)
+            | Self::InvalidArchitecture(_)
+            | Self::UnsupportedFormat(_) => 4,
+            Self::DependencyResolution(_) | Self::AurApi(_) => 5,
+            Self::PackageBuild(_) | Self::ScriptTranslation(_) | Self::Template(_) => 6,
+            Self::Conflict(_) => 7,
+            Self::Validation(_) => 8,
+            Self::Pacman(_) => 9,
+            Self::Findings(_) => 10,
+            Self::Cancelled(_) => 130,
+            _ => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        let codes = [
+            RexebError::Other("x".into()).exit_code(),
+            RexebError::InputRequired("x".into()).exit_code(),
+            RexebError::parse("x").exit_code(),
+            RexebError::dependency("x").exit_code(),
+            RexebError::PackageBuild("x".into()).exit_code(),
+            RexebError::Conflict("x".into()).exit_code(),
+            RexebError::Validation("x".into()).exit_code(),
+            RexebError::Pacman("x".into()).exit_code(),
+            RexebError::Findings("x".into()).exit_code(),
+            RexebError::Cancelled("x".into()).exit_code(),
+        ];
+        assert_eq!(codes, [1, 3, 4, 5, 6, 7, 8, 9, 10, 130]);
+        assert_eq!(RexebError::extract("x").exit_code(), 4);
+        assert_eq!(RexebError::file_not_found("foo.deb").exit_code(), 4);
+        assert!(!codes.contains(&USAGE_EXIT_CODE));
+    }
 }
\ No newline at end of file
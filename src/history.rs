@@ -0,0 +1,209 @@
+//! Conversion history
+//!
+//! Every conversion is recorded with the input it came from, the settings
+//! it used and whether the result was installed, so converted packages can
+//! be found again later (`rexeb list`). Records live in
+//! `<data_dir>/history.json`, oldest first.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::Result;
+
+/// Serializes updates of the index by conversions running side by side
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// A recorded conversion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    /// Number of the record, counting from 1
+    pub id: u64,
+    /// Package name in the source package
+    pub name: String,
+    /// Version of the source package
+    pub source_version: String,
+    /// The converted file
+    pub input_path: PathBuf,
+    /// SHA-256 of the converted file
+    pub input_sha256: String,
+    /// The package or PKGBUILD written
+    pub output_path: PathBuf,
+    /// Conversion settings given on the command line
+    #[serde(default)]
+    pub options: BTreeMap<String, String>,
+    /// When the conversion happened (unix time)
+    pub created: i64,
+    /// When the result was installed (unix time)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installed: Option<i64>,
+    /// When the installed result was removed again (unix time)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<i64>,
+}
+
+/// What to show of the history
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Only packages whose name contains this
+    pub name: Option<String>,
+    /// Only installed (`Some(true)`) or not installed packages
+    pub installed: Option<bool>,
+    /// Only conversions at or after this time (unix time)
+    pub since: Option<i64>,
+}
+
+impl HistoryFilter {
+    /// Whether a record passes the filter
+    pub fn matches(&self, record: &HistoryRecord) -> bool {
+        self.name.as_ref().is_none_or(|name| record.name.contains(name.as_str()))
+            && self.installed.is_none_or(|installed| record.installed.is_some() == installed)
+            && self.since.is_none_or(|since| record.created >= since)
+    }
+}
+
+/// The recorded conversions
+#[derive(Debug)]
+pub struct History {
+    /// Record index file
+    path: PathBuf,
+    /// Known records
+    records: Vec<HistoryRecord>,
+}
+
+impl History {
+    /// Open the history kept in `path`
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let records = Self::read_index(&path)?;
+        Ok(Self { path, records })
+    }
+
+    /// Open the history in the configured data directory
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::new(config.data_dir().join("history.json"))
+    }
+
+    /// All records, oldest first
+    pub fn records(&self) -> &[HistoryRecord] {
+        &self.records
+    }
+
+    /// Records passing a filter, oldest first
+    pub fn query<'a>(&'a self, filter: &'a HistoryFilter) -> impl Iterator<Item = &'a HistoryRecord> + 'a {
+        self.records.iter().filter(move |r| filter.matches(r))
+    }
+
+    /// Record a conversion, numbering it; returns the number
+    pub fn add(&mut self, mut record: HistoryRecord) -> Result<u64> {
+        self.update(|records| {
+            record.id = records.last().map_or(1, |r| r.id + 1);
+            let id = record.id;
+            records.push(record);
+            id
+        })
+    }
+
+    /// Mark the conversions that produced `packages` as installed
+    ///
+    /// Returns how many records changed.
+    pub fn mark_installed(&mut self, packages: &[PathBuf]) -> Result<usize> {
+        let now = chrono::Utc::now().timestamp();
+        self.update(|records| {
+            let mut changed = 0;
+            for package in packages {
+                // The latest conversion writing a file is the one installed
+                if let Some(record) = records.iter_mut().rev().find(|r| &r.output_path == package) {
+                    record.installed = Some(now);
+                    record.removed = None;
+                    changed += 1;
+                }
+            }
+            changed
+        })
+    }
+
+    /// Mark the installed conversions of an input as removed
+    ///
+    /// Returns how many records changed.
+    pub fn mark_removed(&mut self, input_sha256: &str) -> Result<usize> {
+        let now = chrono::Utc::now().timestamp();
+        self.update(|records| {
+            let mut changed = 0;
+            for record in records.iter_mut().filter(|r| r.input_sha256 == input_sha256 && r.installed.is_some()) {
+                record.installed = None;
+                record.removed = Some(now);
+                changed += 1;
+            }
+            changed
+        })
+    }
+
+    /// Change the records as stored and write them back
+    fn update<T>(&mut self, change: impl FnOnce(&mut Vec<HistoryRecord>) -> T) -> Result<T> {
+        let _guard = INDEX_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // Other conversions may have added records since we loaded
+        self.records = Self::read_index(&self.path)?;
+        let result = change(&mut self.records);
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.records)?)?;
+        Ok(result)
+    }
+
+    fn read_index(path: &Path) -> Result<Vec<HistoryRecord>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(name: &str, output: &str, created: i64) -> HistoryRecord {
+        HistoryRecord {
+            id: 0,
+            name: name.into(),
+            source_version: "1.0-1".into(),
+            input_path: format!("{}_1.0-1_amd64.deb", name).into(),
+            input_sha256: "abc".into(),
+            output_path: output.into(),
+            options: BTreeMap::new(),
+            created,
+            installed: None,
+            removed: None,
+        }
+    }
+
+    #[test]
+    fn test_history() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("history.json");
+        let mut history = History::new(&path).unwrap();
+        assert_eq!(history.add(record("foo", "/out/foo.pkg.tar.zst", 100)).unwrap(), 1);
+        assert_eq!(history.add(record("libfoo", "/out/libfoo.pkg.tar.zst", 200)).unwrap(), 2);
+        assert_eq!(history.add(record("bar", "/out/bar.pkg.tar.zst", 300)).unwrap(), 3);
+        assert_eq!(history.mark_installed(&["/out/foo.pkg.tar.zst".into(), "/tmp/x".into()]).unwrap(), 1);
+
+        let history = History::new(&path).unwrap();
+        let ids = |filter: HistoryFilter| history.query(&filter).map(|r| r.id).collect::<Vec<_>>();
+        assert_eq!(ids(HistoryFilter::default()), [1, 2, 3]);
+        assert_eq!(ids(HistoryFilter { name: Some("foo".into()), ..Default::default() }), [1, 2]);
+        assert_eq!(ids(HistoryFilter { installed: Some(true), ..Default::default() }), [1]);
+        assert_eq!(ids(HistoryFilter { installed: Some(false), since: Some(200), ..Default::default() }), [2, 3]);
+
+        let mut history = history;
+        assert_eq!(history.mark_removed("abc").unwrap(), 1);
+        assert!(history.records()[0].installed.is_none() && history.records()[0].removed.is_some());
+    }
+}
@@ -35,13 +35,18 @@ pub mod analyzer;
 pub mod cli;
 pub mod config;
 pub mod converter;
+pub mod diff;
 pub mod error;
+pub mod history;
 pub mod models;
 pub mod parsers;
+pub mod progress;
+pub mod repo;
 pub mod resolver;
 pub mod sandbox;
 #[cfg(feature = "tui")]
 pub mod tui;
+pub mod upstream;
 
 // Re-export commonly used types
 pub use error::{RexebError, Result};
@@ -100,9 +105,25 @@ pub async fn convert(
     let resolver = DependencyResolver::new()?;
     resolver.resolve(&mut metadata).await?;
 
+    let config = config::Config::load()?;
+    if let Some(overrides) = config.package_override(&metadata) {
+        metadata.apply_override(overrides);
+    }
+
     // Build the package
-    let converter = PackageConverter::new(metadata, parser.extract_dir())?;
-    converter.build(output_dir, OutputFormat::PkgTarZst)
+    metadata.apply_recommends_policy(models::RecommendsPolicy::from_config(&config.conversion.recommends_policy)?);
+    if config.conversion.lenient_deps {
+        metadata.demote_unmapped();
+    }
+    let learned = resolver::learned_mappings(&metadata);
+    let converter = PackageConverter::new(metadata, parser.extract_dir())?
+        .with_config(&config.conversion)
+        .with_templates(converter::Templates::load()?);
+    let report = converter.build(output_dir, OutputFormat::PkgTarZst)?;
+
+    // Later conversions reuse what worked here
+    resolver::PackageDatabase::learn(&learned)?;
+    Ok(report.output_path)
 }
 
 /// Analyze a package without converting
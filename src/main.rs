@@ -2,12 +2,16 @@
 //!
 //! Main entry point for the rexeb CLI application.
 
+use std::path::Path;
 use std::process::ExitCode;
+use std::sync::Mutex;
 
 use console::style;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Layer, Registry};
 
 use rexeb::cli::{self, Cli, Commands};
+use rexeb::config::{Config, LoggingConfig};
 use rexeb::error::Result;
 
 /// Application banner
@@ -33,12 +37,16 @@ async fn main() -> ExitCode {
         Ok(()) => ExitCode::SUCCESS,
         Err(e) => {
             eprintln!("{} {}", style("Error:").red().bold(), e);
-            ExitCode::FAILURE
+            ExitCode::from(e.exit_code())
         }
     }
 }
 
 /// Set up logging based on CLI arguments
+///
+/// The terminal gets messages at the level chosen by `-v`/`-q`; a log file
+/// (`--log-file` or `logging.file`) keeps everything at `logging.level`,
+/// as text or JSON lines per `logging.format`.
 fn setup_logging(cli: &Cli) {
     let level = if cli.verbose {
         "debug"
@@ -51,18 +59,51 @@ fn setup_logging(cli: &Cli) {
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(level));
 
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(false)
-        .without_time()
+    // A broken config is reported by the command itself
+    let logging = Config::load().map(|c| c.logging).unwrap_or_default();
+    let file = match cli.log_file.as_ref().or(logging.file.as_ref()) {
+        Some(path) => match file_layer(path, &logging) {
+            Ok(layer) => Some(layer),
+            Err(e) => {
+                eprintln!("{} Cannot write log file {}: {}", style("Warning:").yellow().bold(), path.display(), e);
+                None
+            }
+        },
+        None => None,
+    };
+
+    tracing_subscriber::registry()
+        .with(file)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_target(false)
+                .without_time()
+                .with_filter(filter),
+        )
         .init();
 }
 
+/// Layer appending to a log file
+fn file_layer(path: &Path, logging: &LoggingConfig) -> std::io::Result<Box<dyn Layer<Registry> + Send + Sync>> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    let filter = EnvFilter::new(&logging.level);
+    let layer = tracing_subscriber::fmt::layer().with_writer(Mutex::new(file)).with_ansi(false);
+    Ok(match logging.format.as_str() {
+        "json" => layer.json().with_filter(filter).boxed(),
+        _ => layer.with_filter(filter).boxed(),
+    })
+}
+
 /// Main application logic
 async fn run(cli: Cli) -> Result<()> {
     // Show banner for main commands (not quiet mode)
     if !cli.quiet {
         match &cli.command {
+            // Standard output is the event stream
+            Commands::Convert(args) if args.progress_format == cli::ProgressFormat::Ndjson && args.progress_file.is_none() => {}
             Commands::Convert(_) | Commands::Install(_) => {
                 println!("{}", style(BANNER).cyan());
                 println!("  {} v{}\n", 
@@ -74,6 +115,8 @@ async fn run(cli: Cli) -> Result<()> {
         }
     }
 
+    cli::set_non_interactive(cli.non_interactive);
+
     // Set number of parallel jobs
     if let Some(jobs) = cli.jobs {
         rayon::ThreadPoolBuilder::new()
@@ -124,6 +167,36 @@ async fn run(cli: Cli) -> Result<()> {
         Commands::Clean(args) => {
             cli::execute_clean(&args).await
         }
+        Commands::Repo(args) => {
+            cli::execute_repo(&args).await
+        }
+        Commands::Map(args) => {
+            cli::execute_map(&args).await
+        }
+        Commands::Diff(args) => {
+            cli::execute_diff(&args).await
+        }
+        Commands::Verify(args) => {
+            cli::execute_verify(&args).await
+        }
+        Commands::Test(args) => {
+            cli::execute_test(&args).await
+        }
+        Commands::List(args) => {
+            cli::execute_list(&args).await
+        }
+        Commands::Uninstall(args) => {
+            cli::execute_uninstall(&args).await
+        }
+        Commands::Upgrade(args) => {
+            cli::execute_upgrade(&args).await
+        }
+        Commands::Man(args) => {
+            cli::execute_man(&args).await
+        }
+        Commands::Doctor(args) => {
+            cli::execute_doctor(&args).await
+        }
     }
 }
 
@@ -138,4 +211,23 @@ mod tests {
         assert!(!BANNER.trim().is_empty());
         assert!(BANNER.lines().count() >= 6); // The logo has 6 lines
     }
+
+    #[test]
+    fn test_json_log_file() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("logs/rexeb.log");
+        let logging = LoggingConfig { format: "json".into(), ..Default::default() };
+        let subscriber = tracing_subscriber::registry().with(file_layer(&path, &logging).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(package = "foo", "converted");
+            tracing::debug!("below the configured level");
+        });
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = log.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["level"], "INFO");
+        assert_eq!(lines[0]["fields"]["message"], "converted");
+        assert_eq!(lines[0]["fields"]["package"], "foo");
+    }
 }
@@ -59,6 +59,20 @@ impl Architecture {
         }
     }
 
+    /// ELF `e_machine` and class of native objects, `None` for `any`
+    ///
+    /// Every supported architecture is little endian.
+    pub fn elf_machine(&self) -> Option<(u16, bool)> {
+        use object::elf::{EM_386, EM_AARCH64, EM_ARM, EM_X86_64};
+        match self {
+            Self::X86_64 => Some((EM_X86_64, true)),
+            Self::I686 => Some((EM_386, false)),
+            Self::Aarch64 => Some((EM_AARCH64, true)),
+            Self::Armv7h => Some((EM_ARM, false)),
+            Self::Any => None,
+        }
+    }
+
     /// Check if this is a 64-bit architecture
     pub fn is_64bit(&self) -> bool {
         matches!(self, Self::X86_64 | Self::Aarch64)
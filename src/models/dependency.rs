@@ -54,6 +54,76 @@ impl fmt::Display for VersionOp {
     }
 }
 
+/// Rewrite of a Debian version for the mapped Arch package
+///
+/// Applied in order to the normalized version when formatting the
+/// constraint; an empty result drops the constraint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum VersionTransform {
+    /// Drop the version constraint (version schemes are unrelated)
+    Drop,
+    /// Regex replacement (`$1` etc. refer to capture groups)
+    Replace {
+        /// Regular expression matched against the version
+        pattern: String,
+        /// Replacement text
+        replacement: String,
+    },
+    /// Keep only the first `components` dot-separated components
+    Truncate {
+        /// Number of components kept
+        components: usize,
+    },
+}
+
+impl VersionTransform {
+    /// Apply the transform to a version
+    ///
+    /// An invalid pattern leaves the version unchanged.
+    pub fn apply(&self, version: &str) -> String {
+        match self {
+            Self::Drop => String::new(),
+            Self::Replace { pattern, replacement } => match Regex::new(pattern) {
+                Ok(re) => re.replace(version, replacement.as_str()).into_owned(),
+                Err(_) => version.to_string(),
+            },
+            Self::Truncate { components } => {
+                version.split('.').take(*components).collect::<Vec<_>>().join(".")
+            }
+        }
+    }
+}
+
+/// How a dependency's Arch name was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolutionSource {
+    /// Mapping table, repository package or provide
+    Database,
+    /// Package shipping the library's soname
+    Soname,
+    /// Approximate name match
+    Fuzzy,
+    /// AUR package or provider
+    Aur,
+    /// Chosen by the user
+    User,
+}
+
+impl fmt::Display for ResolutionSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Self::Database => "database",
+            Self::Soname => "soname",
+            Self::Fuzzy => "fuzzy",
+            Self::Aur => "AUR",
+            Self::User => "user",
+        };
+        write!(f, "{}", label)
+    }
+}
+
 /// Represents a package dependency
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Dependency {
@@ -71,6 +141,21 @@ pub struct Dependency {
     pub is_virtual: bool,
     /// Confidence score for the mapping (0.0 - 1.0)
     pub confidence: f32,
+    /// Short description of the mapped package (optdepends reason)
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Version rewrites from the package mapping
+    #[serde(default)]
+    pub version_transforms: Vec<VersionTransform>,
+    /// How the Arch name was found
+    #[serde(default)]
+    pub source: Option<ResolutionSource>,
+    /// Best match scoring below `min_match_confidence`, left for review
+    #[serde(default)]
+    pub suggestion: Option<String>,
+    /// Mapped name exists neither in the sync databases nor in the AUR
+    #[serde(default)]
+    pub missing: bool,
 }
 
 impl Dependency {
@@ -84,6 +169,11 @@ impl Dependency {
             alternatives: Vec::new(),
             is_virtual: false,
             confidence: 0.0,
+            description: None,
+            version_transforms: Vec::new(),
+            source: None,
+            suggestion: None,
+            missing: false,
         }
     }
 
@@ -97,7 +187,26 @@ impl Dependency {
             alternatives: Vec::new(),
             is_virtual: false,
             confidence: 0.0,
+            description: None,
+            version_transforms: Vec::new(),
+            source: None,
+            suggestion: None,
+            missing: false,
+        }
+    }
+
+    /// Create an already mapped dependency from Arch syntax (`name>=1.0`)
+    pub fn from_arch(spec: &str) -> Self {
+        let spec = spec.trim();
+        let (name, constraint) = spec.split_at(spec.find(['<', '>', '=']).unwrap_or(spec.len()));
+
+        let mut dep = Self::new(name);
+        if let Some(op) = [">=", "<=", "=", ">", "<"].into_iter().find(|op| constraint.starts_with(op)) {
+            dep.version_op = VersionOp::from_debian(op);
+            dep.version = Some(constraint[op.len()..].trim().to_string());
         }
+        dep.set_arch_name(name, 1.0);
+        dep
     }
 
     /// Set the Arch package name
@@ -116,20 +225,38 @@ impl Dependency {
         self.arch_name.is_some()
     }
 
+    /// Version constraint in Arch terms, if any survives normalization
+    pub fn arch_constraint(&self) -> Option<(VersionOp, String)> {
+        let (op, ver) = (self.version_op?, self.version.as_ref()?);
+        let normalized_ver = self
+            .version_transforms
+            .iter()
+            .fold(Self::normalize_version_for_arch(ver), |v, t| t.apply(&v));
+        (!normalized_ver.is_empty()).then_some((op, normalized_ver))
+    }
+
     /// Format for Arch Linux PKGBUILD
     pub fn to_arch_string(&self) -> String {
         let name = self.effective_name();
-        match (&self.version_op, &self.version) {
-            (Some(op), Some(ver)) => {
-                let normalized_ver = Self::normalize_version_for_arch(ver);
-                // If version is empty after normalization, just return the name
-                if normalized_ver.is_empty() {
-                    name.to_string()
-                } else {
-                    format!("{}{}{}", name, op, normalized_ver)
-                }
-            }
-            _ => name.to_string(),
+        match self.arch_constraint() {
+            Some((op, ver)) => format!("{}{}{}", name, op, ver),
+            None => name.to_string(),
+        }
+    }
+
+    /// Format for an optdepends entry, with the package description as
+    /// the reason when known (`name: reason`)
+    pub fn to_optdepend_string(&self) -> String {
+        let reason = self
+            .description
+            .as_deref()
+            .and_then(|d| d.lines().next())
+            .map(str::trim)
+            .filter(|d| !d.is_empty());
+
+        match reason {
+            Some(reason) => format!("{}: {}", self.to_arch_string(), reason),
+            None => self.to_arch_string(),
         }
     }
 
@@ -147,7 +274,7 @@ impl Dependency {
         if let Some(pos) = v.rfind('-') {
             let suffix = &v[pos + 1..];
             // Check if it looks like a Debian revision
-            if suffix.chars().next().map_or(false, |c| c.is_ascii_digit()) ||
+            if suffix.chars().next().is_some_and(|c| c.is_ascii_digit()) ||
                suffix.contains("ubuntu") || suffix.contains("debian") ||
                suffix.contains("build") || suffix.contains("deb")
             {
@@ -172,7 +299,7 @@ impl Dependency {
     fn parse_single(s: &str) -> Result<Self> {
         lazy_static::lazy_static! {
             static ref DEP_RE: Regex = Regex::new(
-                r"^\s*([a-zA-Z0-9][a-zA-Z0-9+._-]*)\s*(?:\(\s*(<<|>>|<=|>=|=|<|>)\s*([^)]+)\s*\))?\s*(?:\[([^\]]+)\])?\s*$"
+                r"^\s*([a-zA-Z0-9][a-zA-Z0-9+._-]*)(?::[a-z0-9-]+)?\s*(?:\(\s*(<<|>>|<=|>=|=|<|>)\s*([^)]+)\s*\))?\s*(?:\[([^\]]+)\])?\s*(?:<[^>]*>\s*)*$"
             ).unwrap();
         }
 
@@ -194,6 +321,11 @@ impl Dependency {
                 alternatives: Vec::new(),
                 is_virtual: false,
                 confidence: 0.0,
+                description: None,
+                version_transforms: Vec::new(),
+                source: None,
+            suggestion: None,
+            missing: false,
             })
         } else {
             // Fallback: just treat the whole thing as a package name
@@ -301,6 +433,33 @@ impl DependencyType {
     }
 }
 
+/// How Debian Recommends are carried over to the Arch package
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecommendsPolicy {
+    /// Turn mapped Recommends into hard depends
+    Depends,
+    /// Keep them as optdepends
+    #[default]
+    OptDepends,
+    /// Drop them
+    Ignore,
+}
+
+impl RecommendsPolicy {
+    /// Parse the policy from its configuration name
+    pub fn from_config(value: &str) -> Result<Self> {
+        match value {
+            "depends" => Ok(Self::Depends),
+            "optdepends" => Ok(Self::OptDepends),
+            "ignore" => Ok(Self::Ignore),
+            _ => Err(RexebError::Config(format!(
+                "Invalid recommends policy '{}' (expected depends, optdepends or ignore)",
+                value
+            ))),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -336,5 +495,62 @@ mod tests {
         assert_eq!(deps[0].debian_name, "libc6");
         assert_eq!(deps[1].debian_name, "libssl1.1");
         assert_eq!(deps[2].debian_name, "zlib1g");
+
+        // Build-Depends syntax: multiarch qualifiers, arch lists, build profiles
+        let deps = Dependency::parse_list("python3:any (>= 3.9), libfoo-dev [amd64] <!nocheck>").unwrap();
+        assert_eq!(deps[0].debian_name, "python3");
+        assert_eq!(deps[0].version.as_deref(), Some("3.9"));
+        assert_eq!(deps[1].debian_name, "libfoo-dev");
+    }
+
+    #[test]
+    fn test_parse_build_depends() {
+        let deps = Dependency::parse_list(
+            "debhelper-compat (= 13),\n python3:native (>= 3.9~), libfoo-dev:amd64 [amd64 arm64],\n \
+             python3-pytest <!nocheck>, xvfb [!s390x] <!nocheck> <!cross>,\n \
+             libgtk-3-dev <!nocheck> | libgtk2.0-dev:any (>= 2.24) [linux-any] <!stage1>",
+        )
+        .unwrap();
+        let names: Vec<_> = deps.iter().map(|d| d.debian_name.as_str()).collect();
+        assert_eq!(names, ["debhelper-compat", "python3", "libfoo-dev", "python3-pytest", "xvfb", "libgtk-3-dev"]);
+
+        // Qualifiers stay out of the name, constraints are kept
+        assert_eq!(deps[1].version_op, Some(VersionOp::Ge));
+        assert_eq!(deps[1].version.as_deref(), Some("3.9~"));
+        assert!(deps[2].version.is_none());
+
+        // Alternatives take the same syntax as the first choice
+        assert_eq!(deps[5].alternatives.len(), 1);
+        assert_eq!(deps[5].alternatives[0].debian_name, "libgtk2.0-dev");
+        assert_eq!(deps[5].alternatives[0].version.as_deref(), Some("2.24"));
+    }
+
+    #[test]
+    fn test_optdepend_reason() {
+        let mut dep = Dependency::new("cups");
+        assert_eq!(dep.to_optdepend_string(), "cups");
+
+        dep.description = Some("The CUPS Printing System - daemon package\nmore".into());
+        assert_eq!(dep.to_optdepend_string(), "cups: The CUPS Printing System - daemon package");
+    }
+
+    #[test]
+    fn test_version_transforms() {
+        let mut dep = Dependency::parse_single("default-jre (>= 2:1.11-6)").unwrap();
+        dep.set_arch_name("java-runtime", 1.0);
+        dep.version_transforms = vec![VersionTransform::Replace {
+            pattern: r"^1\.(\d+).*$".into(),
+            replacement: "$1".into(),
+        }];
+        assert_eq!(dep.to_arch_string(), "java-runtime>=11");
+
+        dep.version_transforms = vec![VersionTransform::Truncate { components: 1 }];
+        assert_eq!(dep.to_arch_string(), "java-runtime>=1");
+
+        dep.version_transforms.push(VersionTransform::Drop);
+        assert_eq!(dep.to_arch_string(), "java-runtime");
+
+        let rule: VersionTransform = serde_json::from_str(r#"{"type": "truncate", "components": 2}"#).unwrap();
+        assert_eq!(rule.apply("1.6.43"), "1.6");
     }
 }
\ No newline at end of file
@@ -4,7 +4,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
-use super::{Architecture, Dependency, DependencyType};
+use super::{Architecture, Dependency, DependencyType, RecommendsPolicy};
+use crate::config::PackageOverride;
+use crate::error::{RexebError, Result};
 
 /// Source package format
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -130,6 +132,7 @@ pub enum License {
 
 impl License {
     /// Parse license from string
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         let s_lower = s.to_lowercase();
         
@@ -172,6 +175,58 @@ impl License {
     }
 }
 
+/// Where a converted package came from
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Provenance {
+    /// SHA-256 of the source package file
+    pub source_sha256: Option<String>,
+    /// Version string exactly as found in the source package
+    pub original_version: Option<String>,
+    /// URL the source package was obtained from
+    pub origin_url: Option<String>,
+}
+
+/// Owner and mode of a payload entry, as the source archive records them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileOwnership {
+    /// Absolute path in the payload
+    pub path: PathBuf,
+    /// Owning user id
+    pub uid: u64,
+    /// Owning group id
+    pub gid: u64,
+    /// Owning user name, if recorded
+    pub user: Option<String>,
+    /// Owning group name, if recorded
+    pub group: Option<String>,
+    /// Permission bits
+    pub mode: u32,
+    /// Whether the entry is a directory
+    pub is_dir: bool,
+}
+
+impl FileOwnership {
+    /// Whether root owns the entry
+    pub fn is_root(&self) -> bool {
+        self.uid == 0 && self.gid == 0
+    }
+
+    /// Whether anyone may write to it, leaving out sticky directories like `/tmp`
+    pub fn is_world_writable(&self) -> bool {
+        self.mode & 0o002 != 0 && self.mode & 0o1000 == 0
+    }
+
+    /// Whether nobody but root may access it at all
+    pub fn is_inaccessible(&self) -> bool {
+        self.mode & 0o777 == 0
+    }
+
+    /// Whether the entry has a non-root owner or an unusual mode
+    pub fn is_notable(&self) -> bool {
+        !self.is_root() || self.is_world_writable() || self.is_inaccessible()
+    }
+}
+
 /// Package metadata extracted from source package
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackageMetadata {
@@ -215,8 +270,23 @@ pub struct PackageMetadata {
     pub files: Vec<PathBuf>,
     /// MD5 sums of files (if available)
     pub md5sums: HashMap<PathBuf, String>,
+    /// SHA-256 sums of files, from a `sha256sums` control file (if available)
+    #[serde(default)]
+    pub sha256sums: HashMap<PathBuf, String>,
     /// Extra metadata fields
     pub extra: HashMap<String, String>,
+    /// Payload entries with a non-root owner or an unusual mode
+    #[serde(default)]
+    pub ownership: Vec<FileOwnership>,
+    /// Prefix the `/usr` payload is relocated to (e.g. `/opt/foo`)
+    #[serde(default)]
+    pub install_prefix: Option<String>,
+    /// Origin of the source package
+    #[serde(default)]
+    pub provenance: Provenance,
+    /// Build timestamp written to .PKGINFO (defaults to now)
+    #[serde(default)]
+    pub build_date: Option<i64>,
 }
 
 impl PackageMetadata {
@@ -243,7 +313,12 @@ impl PackageMetadata {
             conffiles: Vec::new(),
             files: Vec::new(),
             md5sums: HashMap::new(),
+            sha256sums: HashMap::new(),
             extra: HashMap::new(),
+            ownership: Vec::new(),
+            install_prefix: None,
+            provenance: Provenance::default(),
+            build_date: None,
         }
     }
 
@@ -260,6 +335,111 @@ impl PackageMetadata {
         }
     }
 
+    /// Get the package file name for the given archive extension
+    ///
+    /// Follows makepkg's `$pkgname-$epoch:$pkgver-$pkgrel-$arch` naming;
+    /// the epoch is only included when it is non-zero.
+    pub fn package_filename(&self, extension: &str) -> String {
+        format!(
+            "{}-{}-{}.{}",
+            self.effective_name(),
+            self.full_version(),
+            self.arch.to_arch_name(),
+            extension
+        )
+    }
+
+    /// Render an `--output-name` template
+    ///
+    /// Placeholders are `{name}`, `{epoch}` (empty without one),
+    /// `{version}`, `{rel}`, `{arch}` and `{debian_version}`. The result
+    /// names a single file or directory, so it may not contain `/`.
+    pub fn output_name(&self, template: &str) -> Result<String> {
+        let mut rendered = String::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| RexebError::Config(format!("Unclosed placeholder in output name {}", template)))?;
+            let value = match &rest[start + 1..start + end] {
+                "name" => self.effective_name().to_string(),
+                "epoch" => self.epoch.filter(|e| *e > 0).map(|e| e.to_string()).unwrap_or_default(),
+                "version" => self.version.clone(),
+                "rel" => self.release.clone(),
+                "arch" => self.arch.to_arch_name().to_string(),
+                "debian_version" => self.provenance.original_version.clone().unwrap_or_else(|| self.full_version()),
+                other => {
+                    return Err(RexebError::Config(format!(
+                        "Unknown placeholder {{{}}} in output name (use name, epoch, version, rel, arch, debian_version)",
+                        other
+                    )))
+                }
+            };
+            rendered.push_str(&value);
+            rest = &rest[start + end + 1..];
+        }
+        rendered.push_str(rest);
+
+        if rendered.is_empty() || rendered.contains('/') || rendered == "." || rendered == ".." {
+            return Err(RexebError::Config(format!("Output name {} does not give a file name", template)));
+        }
+        Ok(rendered)
+    }
+
+    /// Debian section without its archive area (`contrib/net` -> `net`)
+    pub fn debian_section(&self) -> Option<&str> {
+        self.section
+            .as_deref()
+            .and_then(|s| s.rsplit('/').next())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+    }
+
+    /// Pacman groups for the package, derived from the Debian section
+    ///
+    /// Every converted package is in the `rexeb` group; packages with a
+    /// section are also in `rexeb-<section>`.
+    pub fn groups(&self) -> Vec<String> {
+        let mut groups = vec!["rexeb".to_string()];
+        if let Some(section) = self.debian_section() {
+            let section: String = section
+                .to_lowercase()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+                .collect();
+            groups.push(format!("rexeb-{}", section));
+        }
+        groups
+    }
+
+    /// Extra `xdata` entries recording the Debian origin
+    ///
+    /// `rexeb_version` is always present, so converted packages can be
+    /// told apart from native ones with `pacman -Qi`.
+    pub fn xdata(&self) -> Vec<String> {
+        let mut xdata = vec![format!("rexeb_version={}", crate::VERSION)];
+        if let Some(ref sha256) = self.provenance.source_sha256 {
+            xdata.push(format!("rexeb_source_sha256={}", sha256));
+        }
+        if let Some(ref url) = self.provenance.origin_url {
+            xdata.push(format!("rexeb_origin={}", url));
+        }
+        if let Some(ref version) = self.provenance.original_version {
+            xdata.push(format!("debian_version={}", version));
+        }
+        if let Some(ref section) = self.section {
+            xdata.push(format!("debian_section={}", section));
+        }
+        if let Some(ref priority) = self.priority {
+            xdata.push(format!("debian_priority={}", priority));
+        }
+        if let Some(ref prefix) = self.install_prefix {
+            xdata.push(format!("rexeb_prefix={}", prefix));
+        }
+        xdata
+    }
+
     /// Get dependencies of a specific type
     pub fn get_deps(&self, dep_type: DependencyType) -> &[Dependency] {
         self.dependencies.get(&dep_type).map(|v| v.as_slice()).unwrap_or(&[])
@@ -270,6 +450,90 @@ impl PackageMetadata {
         self.dependencies.entry(dep_type).or_default().push(dep);
     }
 
+    /// Apply the Recommends policy
+    ///
+    /// With [`RecommendsPolicy::Depends`] only mapped Recommends are
+    /// promoted; unmapped ones stay optional so the package remains
+    /// installable.
+    pub fn apply_recommends_policy(&mut self, policy: RecommendsPolicy) {
+        match policy {
+            RecommendsPolicy::OptDepends => {}
+            RecommendsPolicy::Ignore => {
+                self.dependencies.remove(&DependencyType::Recommends);
+            }
+            RecommendsPolicy::Depends => {
+                let recommends = self.dependencies.remove(&DependencyType::Recommends).unwrap_or_default();
+                let (mapped, unmapped): (Vec<_>, Vec<_>) = recommends.into_iter().partition(|d| d.is_mapped());
+                for dep in mapped {
+                    if !self.get_deps(DependencyType::Depends).iter().any(|d| d.effective_name() == dep.effective_name()) {
+                        self.add_dep(DependencyType::Depends, dep);
+                    }
+                }
+                if !unmapped.is_empty() {
+                    self.dependencies.insert(DependencyType::Recommends, unmapped);
+                }
+            }
+        }
+    }
+
+    /// Move unmapped Depends and Pre-Depends to the optional dependencies
+    ///
+    /// Shipping the Debian names would make `pacman -U` fail; as optdepends
+    /// they stay visible, noted as unmapped. Returns the names moved.
+    pub fn demote_unmapped(&mut self) -> Vec<String> {
+        let mut demoted = Vec::new();
+        for dep_type in [DependencyType::Depends, DependencyType::PreDepends] {
+            let Some(deps) = self.dependencies.remove(&dep_type) else {
+                continue;
+            };
+            let (mapped, unmapped): (Vec<_>, Vec<_>) = deps.into_iter().partition(|d| d.is_mapped());
+            if !mapped.is_empty() {
+                self.dependencies.insert(dep_type, mapped);
+            }
+            for mut dep in unmapped {
+                demoted.push(dep.debian_name.clone());
+                dep.description = Some("unmapped Debian dependency".to_string());
+                self.add_dep(DependencyType::Suggests, dep);
+            }
+        }
+        demoted
+    }
+
+    /// Apply the user's dependency corrections for this package
+    ///
+    /// Runs after resolution; the given entries are used as they are.
+    pub fn apply_override(&mut self, overrides: &PackageOverride) {
+        let replacements: [(&Option<Vec<String>>, &[DependencyType]); 3] = [
+            (&overrides.depends, &[DependencyType::Depends, DependencyType::PreDepends]),
+            (&overrides.conflicts, &[DependencyType::Conflicts, DependencyType::Breaks]),
+            (&overrides.provides, &[DependencyType::Provides]),
+        ];
+        for (specs, types) in replacements {
+            let Some(specs) = specs else {
+                continue;
+            };
+            for dep_type in types {
+                self.dependencies.remove(dep_type);
+            }
+            for spec in specs {
+                self.add_dep(types[0], Dependency::from_arch(spec));
+            }
+        }
+
+        for spec in &overrides.extra_depends {
+            let dep = Dependency::from_arch(spec);
+            if !self.get_deps(DependencyType::Depends).iter().any(|d| d.effective_name() == dep.effective_name()) {
+                self.add_dep(DependencyType::Depends, dep);
+            }
+        }
+        for spec in &overrides.extra_optdepends {
+            let (spec, reason) = spec.split_once(':').unwrap_or((spec, ""));
+            let mut dep = Dependency::from_arch(spec);
+            dep.description = Some(reason.trim().to_string()).filter(|r| !r.is_empty());
+            self.add_dep(DependencyType::Suggests, dep);
+        }
+    }
+
     /// Set maintainer script
     pub fn set_script(&mut self, script_type: MaintainerScript, content: String) {
         self.scripts.insert(script_type, content);
@@ -328,6 +592,9 @@ impl PackageMetadata {
         let mut lines = Vec::new();
         
         lines.push(format!("pkgname = {}", self.effective_name()));
+        for xdata in self.xdata() {
+            lines.push(format!("xdata = {}", xdata));
+        }
         lines.push(format!("pkgver = {}", self.full_version()));
         lines.push(format!("pkgdesc = {}", self.description));
         
@@ -335,7 +602,7 @@ impl PackageMetadata {
             lines.push(format!("url = {}", url));
         }
         
-        lines.push(format!("builddate = {}", chrono::Utc::now().timestamp()));
+        lines.push(format!("builddate = {}", self.build_date.unwrap_or_else(|| chrono::Utc::now().timestamp())));
         
         if let Some(ref maintainer) = self.maintainer {
             lines.push(format!("packager = {}", maintainer));
@@ -345,6 +612,10 @@ impl PackageMetadata {
         lines.push(format!("arch = {}", self.arch.to_arch_name()));
         lines.push(format!("license = {}", self.license.to_pkgbuild()));
 
+        for group in self.groups() {
+            lines.push(format!("group = {}", group));
+        }
+
         // Dependencies
         for dep in self.get_deps(DependencyType::Depends) {
             lines.push(format!("depend = {}", dep.to_arch_string()));
@@ -355,10 +626,10 @@ impl PackageMetadata {
         
         // Optional dependencies
         for dep in self.get_deps(DependencyType::Recommends) {
-            lines.push(format!("optdepend = {}", dep.to_arch_string()));
+            lines.push(format!("optdepend = {}", dep.to_optdepend_string()));
         }
         for dep in self.get_deps(DependencyType::Suggests) {
-            lines.push(format!("optdepend = {}", dep.to_arch_string()));
+            lines.push(format!("optdepend = {}", dep.to_optdepend_string()));
         }
 
         // Conflicts
@@ -411,65 +682,146 @@ impl PackageMetadata {
         
         lines.push(format!("license=('{}')", self.license.to_pkgbuild()));
 
-        // Dependencies
-        let deps: Vec<String> = self.get_deps(DependencyType::Depends)
-            .iter()
-            .chain(self.get_deps(DependencyType::PreDepends).iter())
-            .filter(|d| d.is_mapped())
-            .map(|d| format!("'{}'", d.to_arch_string()))
-            .collect();
-        if !deps.is_empty() {
-            lines.push(format!("depends=({})", deps.join(" ")));
+        let groups: Vec<String> = self.groups().iter().map(|g| format!("'{}'", g)).collect();
+        lines.push(format!("groups=({})", groups.join(" ")));
+
+        for (field, values) in self.pkgbuild_relations() {
+            lines.push(format!("{}=({})", field, values.join(" ")));
         }
 
-        // Optional dependencies
-        let optdeps: Vec<String> = self.get_deps(DependencyType::Recommends)
-            .iter()
-            .chain(self.get_deps(DependencyType::Suggests).iter())
-            .filter(|d| d.is_mapped())
-            .map(|d| format!("'{}'", d.to_arch_string()))
-            .collect();
-        if !optdeps.is_empty() {
-            lines.push(format!("optdepends=({})", optdeps.join(" ")));
+        lines.push(String::new());
+        lines.push("package() {".to_string());
+        lines.push("    cp -a \"$srcdir\"/* \"$pkgdir\"/".to_string());
+        lines.push("}".to_string());
+
+        lines.join("\n")
+    }
+
+    /// Generate a split PKGBUILD covering several related packages
+    ///
+    /// Version, epoch and release are shared through `pkgbase`, so all
+    /// packages must agree on them. Each package gets its own
+    /// `package_<name>()` function copying from `$srcdir/<name>`.
+    pub fn to_split_pkgbuild(pkgbase: &str, packages: &[Self]) -> Result<String> {
+        let Some(base) = packages.first() else {
+            return Err(RexebError::PackageBuild("No packages for split PKGBUILD".into()));
+        };
+        if let Some(other) = packages.iter().find(|p| p.full_version() != base.full_version()) {
+            return Err(RexebError::PackageBuild(format!(
+                "{} has version {} but {} has {}; split packages must share a version",
+                other.effective_name(),
+                other.full_version(),
+                base.effective_name(),
+                base.full_version()
+            )));
         }
 
-        // Conflicts
-        let conflicts: Vec<String> = self.get_deps(DependencyType::Conflicts)
-            .iter()
-            .chain(self.get_deps(DependencyType::Breaks).iter())
-            .filter(|d| d.is_mapped())
-            .map(|d| format!("'{}'", d.to_arch_string()))
-            .collect();
-        if !conflicts.is_empty() {
-            lines.push(format!("conflicts=({})", conflicts.join(" ")));
+        let quote = |v: &str| format!("'{}'", v);
+        let mut arches: Vec<String> = Vec::new();
+        for pkg in packages {
+            let arch = pkg.arch.to_arch_name().to_string();
+            if !arches.contains(&arch) {
+                arches.push(arch);
+            }
         }
 
-        // Replaces
-        let replaces: Vec<String> = self.get_deps(DependencyType::Replaces)
-            .iter()
-            .filter(|d| d.is_mapped())
-            .map(|d| format!("'{}'", d.to_arch_string()))
-            .collect();
-        if !replaces.is_empty() {
-            lines.push(format!("replaces=({})", replaces.join(" ")));
+        let mut lines = Vec::new();
+        lines.push("# Maintainer: Converted by rexeb".to_string());
+        if let Some(ref maintainer) = base.maintainer {
+            lines.push(format!("# Original: {}", maintainer));
+        }
+        lines.push(String::new());
+
+        lines.push(format!("pkgbase={}", pkgbase));
+        let names: Vec<String> = packages.iter().map(|p| quote(p.effective_name())).collect();
+        lines.push(format!("pkgname=({})", names.join(" ")));
+
+        if let Some(epoch) = base.epoch.filter(|e| *e > 0) {
+            lines.push(format!("epoch={}", epoch));
         }
 
-        // Provides
-        let provides: Vec<String> = self.get_deps(DependencyType::Provides)
-            .iter()
-            .filter(|d| d.is_mapped())
-            .map(|d| format!("'{}'", d.to_arch_string()))
-            .collect();
-        if !provides.is_empty() {
-            lines.push(format!("provides=({})", provides.join(" ")));
+        lines.push(format!("pkgver={}", base.version));
+        lines.push(format!("pkgrel={}", base.release));
+        lines.push(format!("pkgdesc=\"{}\"", base.description.replace('"', "\\\"")));
+        let arch_list: Vec<String> = arches.iter().map(|a| quote(a)).collect();
+        lines.push(format!("arch=({})", arch_list.join(" ")));
+
+        if let Some(ref url) = base.url {
+            lines.push(format!("url=\"{}\"", url));
         }
 
-        lines.push(String::new());
-        lines.push("package() {".to_string());
-        lines.push("    cp -a \"$srcdir\"/* \"$pkgdir\"/".to_string());
-        lines.push("}".to_string());
+        lines.push(format!("license=('{}')", base.license.to_pkgbuild()));
 
-        lines.join("\n")
+        // Build dependencies belong to the base, not to the package functions
+        let mut makedepends: Vec<String> = Vec::new();
+        for pkg in packages {
+            for (field, values) in pkg.pkgbuild_relations() {
+                if field != "makedepends" {
+                    continue;
+                }
+                for value in values {
+                    if !makedepends.contains(&value) {
+                        makedepends.push(value);
+                    }
+                }
+            }
+        }
+        if !makedepends.is_empty() {
+            lines.push(format!("makedepends=({})", makedepends.join(" ")));
+        }
+
+        for pkg in packages {
+            let name = pkg.effective_name();
+            lines.push(String::new());
+            lines.push(format!("package_{}() {{", name));
+            lines.push(format!("    pkgdesc=\"{}\"", pkg.description.replace('"', "\\\"")));
+            if arches.len() > 1 {
+                lines.push(format!("    arch=('{}')", pkg.arch.to_arch_name()));
+            }
+            if pkg.license != base.license {
+                lines.push(format!("    license=('{}')", pkg.license.to_pkgbuild()));
+            }
+            let groups: Vec<String> = pkg.groups().iter().map(|g| quote(g)).collect();
+            lines.push(format!("    groups=({})", groups.join(" ")));
+            for (field, values) in pkg.pkgbuild_relations() {
+                if field != "makedepends" {
+                    lines.push(format!("    {}=({})", field, values.join(" ")));
+                }
+            }
+            lines.push(format!("    cp -a \"$srcdir\"/{}/* \"$pkgdir\"/", name));
+            lines.push("}".to_string());
+        }
+
+        Ok(lines.join("\n"))
+    }
+
+    /// Non-empty PKGBUILD relation arrays with quoted, mapped entries
+    fn pkgbuild_relations(&self) -> Vec<(&'static str, Vec<String>)> {
+        let fields: [(&'static str, &[DependencyType]); 6] = [
+            ("depends", &[DependencyType::Depends, DependencyType::PreDepends]),
+            ("makedepends", &[DependencyType::BuildDepends]),
+            ("optdepends", &[DependencyType::Recommends, DependencyType::Suggests]),
+            ("conflicts", &[DependencyType::Conflicts, DependencyType::Breaks]),
+            ("replaces", &[DependencyType::Replaces]),
+            ("provides", &[DependencyType::Provides]),
+        ];
+
+        fields
+            .iter()
+            .filter_map(|(field, types)| {
+                let values: Vec<String> = types
+                    .iter()
+                    .flat_map(|t| self.get_deps(*t))
+                    .filter(|d| d.is_mapped())
+                    .map(|d| match *field {
+                        "optdepends" => d.to_optdepend_string(),
+                        _ => d.to_arch_string(),
+                    })
+                    .map(|v| format!("'{}'", v.replace('\'', "'\\''")))
+                    .collect();
+                (!values.is_empty()).then_some((*field, values))
+            })
+            .collect()
     }
 }
 
@@ -477,4 +829,190 @@ impl Default for PackageMetadata {
     fn default() -> Self {
         Self::new("unknown", "0.0.0")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_handling() {
+        let mut metadata = PackageMetadata::new("foo", "2:1.4.0-3ubuntu1");
+        metadata.normalize_version();
+
+        assert_eq!(metadata.epoch, Some(2));
+        assert_eq!(metadata.version, "1.4.0");
+        assert_eq!(metadata.release, "3");
+        assert_eq!(metadata.full_version(), "2:1.4.0-3");
+        assert_eq!(
+            metadata.package_filename("pkg.tar.zst"),
+            format!("foo-2:1.4.0-3-{}.pkg.tar.zst", metadata.arch.to_arch_name())
+        );
+        assert!(metadata.to_pkginfo().contains("pkgver = 2:1.4.0-3\n"));
+
+        let pkgbuild = metadata.to_pkgbuild();
+        assert!(pkgbuild.contains("epoch=2\npkgver=1.4.0\npkgrel=3\n"));
+    }
+
+    #[test]
+    fn test_section_groups_and_xdata() {
+        let mut metadata = PackageMetadata::new("foo", "1.0");
+        metadata.section = Some("contrib/net".into());
+        metadata.priority = Some("optional".into());
+        metadata.provenance = Provenance {
+            source_sha256: Some("ab12".into()),
+            original_version: Some("1.0-0ubuntu2".into()),
+            origin_url: Some("https://example.com/foo.deb".into()),
+        };
+
+        assert_eq!(metadata.groups(), vec!["rexeb", "rexeb-net"]);
+        assert_eq!(
+            &metadata.xdata()[1..4],
+            ["rexeb_source_sha256=ab12", "rexeb_origin=https://example.com/foo.deb", "debian_version=1.0-0ubuntu2"]
+        );
+
+        let pkginfo = metadata.to_pkginfo();
+        assert!(pkginfo.contains("group = rexeb-net"));
+        assert!(pkginfo.contains("xdata = debian_section=contrib/net\n"));
+        assert!(pkginfo.contains("xdata = debian_priority=optional\n"));
+        assert!(pkginfo.contains(&format!("xdata = rexeb_version={}\n", crate::VERSION)));
+        assert!(metadata.to_pkgbuild().contains("groups=('rexeb' 'rexeb-net')"));
+    }
+
+    #[test]
+    fn test_zero_epoch_omitted() {
+        let mut metadata = PackageMetadata::new("foo", "0:1.0-1");
+        metadata.normalize_version();
+
+        assert_eq!(metadata.full_version(), "1.0-1");
+        assert!(!metadata.to_pkgbuild().contains("epoch="));
+        assert!(metadata.package_filename("pkg.tar.zst").starts_with("foo-1.0-1-"));
+    }
+
+    #[test]
+    fn test_output_name() {
+        let mut metadata = PackageMetadata::new("foo", "2:1.0-3ubuntu1");
+        metadata.provenance.original_version = Some("2:1.0-3ubuntu1".into());
+        metadata.normalize_version();
+        assert_eq!(
+            metadata.output_name("{name}-{version}-{rel}-{arch}").unwrap(),
+            format!("foo-{}-{}-{}", metadata.version, metadata.release, metadata.arch.to_arch_name())
+        );
+        assert_eq!(metadata.output_name("{name}_{debian_version}").unwrap(), "foo_2:1.0-3ubuntu1");
+        assert_eq!(metadata.output_name("{name}-e{epoch}").unwrap(), "foo-e2");
+        assert!(metadata.output_name("{name}-{pkgver}").is_err());
+        assert!(metadata.output_name("{arch}/{name}").is_err());
+        assert!(metadata.output_name("{name").is_err());
+    }
+
+    #[test]
+    fn test_recommends_policy() {
+        let mut metadata = PackageMetadata::new("foo", "1.0");
+        let mut mapped = Dependency::new("libfoo-data");
+        mapped.set_arch_name("foo-data", 1.0);
+        metadata.add_dep(DependencyType::Recommends, mapped);
+        metadata.add_dep(DependencyType::Recommends, Dependency::new("debian-only"));
+
+        let mut ignored = metadata.clone();
+        ignored.apply_recommends_policy(RecommendsPolicy::Ignore);
+        assert!(ignored.get_deps(DependencyType::Recommends).is_empty());
+
+        metadata.apply_recommends_policy(RecommendsPolicy::Depends);
+        let depends: Vec<_> = metadata.get_deps(DependencyType::Depends).iter().map(|d| d.effective_name()).collect();
+        assert_eq!(depends, ["foo-data"]);
+        assert_eq!(metadata.get_deps(DependencyType::Recommends).len(), 1);
+        assert!(RecommendsPolicy::from_config("sometimes").is_err());
+
+        let mut lenient = PackageMetadata::new("foo", "1.0");
+        let mut libc = Dependency::new("libc6");
+        libc.set_arch_name("glibc", 1.0);
+        lenient.add_dep(DependencyType::Depends, libc);
+        lenient.add_dep(DependencyType::PreDepends, Dependency::new("libdebian-only1"));
+        assert_eq!(lenient.demote_unmapped(), ["libdebian-only1"]);
+        assert!(lenient.to_pkginfo().contains("depend = glibc\noptdepend = libdebian-only1: unmapped Debian dependency"));
+    }
+
+    #[test]
+    fn test_package_override() {
+        let config: crate::config::Config = toml::from_str(
+            "[overrides.\"google-chrome-stable\"]\n\
+             depends = [\"gtk3\", \"nss>=3.26\"]\n\
+             conflicts = []\n\
+             extra_depends = [\"gtk3\", \"libxss\"]\n\
+             extra_optdepends = [\"kdialog: KDE dialogs\"]\n",
+        )
+        .unwrap();
+
+        let mut metadata = PackageMetadata::new("google-chrome-stable", "1.0");
+        metadata.add_dep(DependencyType::PreDepends, Dependency::new("dpkg"));
+        metadata.add_dep(DependencyType::Depends, Dependency::new("libgtk-3-0"));
+        let mut conflict = Dependency::new("chromium-browser");
+        conflict.set_arch_name("chromium", 0.9);
+        metadata.add_dep(DependencyType::Breaks, conflict);
+        let mut provides = Dependency::new("www-browser");
+        provides.set_arch_name("www-browser", 1.0);
+        metadata.add_dep(DependencyType::Provides, provides);
+
+        metadata.apply_override(config.package_override(&metadata).unwrap());
+        let relations: HashMap<_, _> = metadata.pkgbuild_relations().into_iter().collect();
+        assert_eq!(relations["depends"], ["'gtk3'", "'nss>=3.26'", "'libxss'"]);
+        assert_eq!(relations["optdepends"], ["'kdialog: KDE dialogs'"]);
+        assert_eq!(relations["provides"], ["'www-browser'"]);
+        assert!(!relations.contains_key("conflicts"));
+    }
+
+    #[test]
+    fn test_build_depends_pkgbuild() {
+        let mut metadata = PackageMetadata::new("foo", "1.0");
+        let deps = Dependency::parse_list(
+            "python3:native (>= 3.9), libfoo-dev:amd64 [amd64] <!nocheck>, \
+             libgtk-3-dev <!nocheck> | libgtk2.0-dev, dh-debian-only",
+        )
+        .unwrap();
+        for (mut dep, arch_name) in deps.into_iter().zip(["python", "foo", "gtk3", ""]) {
+            if !arch_name.is_empty() {
+                dep.set_arch_name(arch_name, 1.0);
+            }
+            metadata.add_dep(DependencyType::BuildDepends, dep);
+        }
+
+        // One entry per relation, whichever alternative it resolved to; unmapped ones are left out
+        let pkgbuild = metadata.to_pkgbuild();
+        assert!(pkgbuild.contains("\nmakedepends=('python>=3.9' 'foo' 'gtk3')\n"));
+        assert!(!pkgbuild.contains("dh-debian-only"));
+        assert!(!pkgbuild.contains("\ndepends=('python"));
+    }
+
+    #[test]
+    fn test_split_pkgbuild() {
+        let mut app = PackageMetadata::new("app", "1.2.0");
+        app.arch = Architecture::X86_64;
+        app.description = "The app".into();
+        let mut dep = Dependency::new("app-data");
+        dep.set_arch_name("app-data", 1.0);
+        app.add_dep(DependencyType::Depends, dep);
+        let mut data = PackageMetadata::new("app-data", "1.2.0");
+        data.arch = Architecture::Any;
+        data.description = "Data files".into();
+        let build_dep = |name: &str| {
+            let mut dep = Dependency::new(name);
+            dep.set_arch_name(name, 1.0);
+            dep
+        };
+        app.add_dep(DependencyType::BuildDepends, build_dep("cmake"));
+        data.add_dep(DependencyType::BuildDepends, build_dep("cmake"));
+        data.add_dep(DependencyType::BuildDepends, build_dep("python"));
+        assert!(data.to_pkgbuild().contains("\nmakedepends=('cmake' 'python')\n"));
+
+        let pkgbuild = PackageMetadata::to_split_pkgbuild("app", &[app.clone(), data.clone()]).unwrap();
+        assert!(pkgbuild.contains("pkgbase=app\npkgname=('app' 'app-data')\n"));
+        assert!(pkgbuild.contains("arch=('x86_64' 'any')"));
+        assert!(pkgbuild.contains("')\nmakedepends=('cmake' 'python')\n\npackage_app() {"));
+        assert_eq!(pkgbuild.matches("makedepends").count(), 1);
+        assert!(pkgbuild.contains("package_app-data() {\n    pkgdesc=\"Data files\"\n    arch=('any')\n"));
+        assert!(pkgbuild.contains("    depends=('app-data')\n    cp -a \"$srcdir\"/app/* \"$pkgdir\"/\n}"));
+
+        data.version = "1.3.0".into();
+        assert!(PackageMetadata::to_split_pkgbuild("app", &[app, data]).is_err());
+    }
+}
@@ -6,9 +6,10 @@
 //! - data.tar.{gz,xz,zst,bz2}: actual package files
 
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read};
-use std::path::{Path, PathBuf};
+use std::fs::{self, File};
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
 
 use flate2::read::GzDecoder;
 use tar::Archive;
@@ -17,25 +18,36 @@ use xz2::read::XzDecoder;
 
 use crate::error::{RexebError, Result};
 use crate::models::{
-    Architecture, Dependency, DependencyType, License, MaintainerScript, PackageFormat,
+    Architecture, Dependency, DependencyType, FileOwnership, License, MaintainerScript, PackageFormat,
     PackageMetadata,
 };
+use crate::progress::{Progress, Stage};
 
 /// Parser for Debian .deb packages
 pub struct DebParser {
     /// Path to the .deb file
     path: PathBuf,
-    /// Temporary directory for extraction
+    /// Temporary directory for extraction (kept alive for the parser's lifetime)
+    #[allow(dead_code)]
     temp_dir: TempDir,
     /// Path to extracted control directory
     control_dir: PathBuf,
     /// Path to extracted data directory
     data_dir: PathBuf,
+    /// Progress reporting
+    progress: Progress,
+    /// Payload entries with a non-root owner or an unusual mode
+    ownership: Vec<FileOwnership>,
 }
 
 impl DebParser {
     /// Create a new parser for the given .deb file
     pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_progress(path, Progress::default())
+    }
+
+    /// Create a new parser reporting extraction and parsing progress
+    pub fn with_progress(path: impl AsRef<Path>, progress: Progress) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
 
         if !path.exists() {
@@ -54,6 +66,8 @@ impl DebParser {
             temp_dir,
             control_dir,
             data_dir,
+            progress,
+            ownership: Vec::new(),
         };
 
         parser.extract_archive()?;
@@ -61,6 +75,32 @@ impl DebParser {
         Ok(parser)
     }
 
+    /// Read only the control fields of a .deb, without extracting the payload
+    pub fn control_fields(path: impl AsRef<Path>) -> Result<HashMap<String, String>> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(RexebError::file_not_found(path));
+        }
+        let mut archive = ar::Archive::new(File::open(path)?);
+        while let Some(entry) = archive.next_entry() {
+            let entry = entry.map_err(|e| RexebError::Extraction(e.to_string()))?;
+            let name = String::from_utf8_lossy(entry.header().identifier()).into_owned();
+            if !name.starts_with("control.tar") {
+                continue;
+            }
+            let mut control = Archive::new(decoder(entry, &name)?);
+            for member in control.entries()? {
+                let mut member = member?;
+                if member.path()?.file_name().is_some_and(|n| n == "control") {
+                    let mut content = String::new();
+                    member.read_to_string(&mut content)?;
+                    return Ok(parse_control_fields(&content));
+                }
+            }
+        }
+        Err(RexebError::InvalidControl("control file not found".into()))
+    }
+
     /// Get the extraction directory path
     pub fn extract_dir(&self) -> &Path {
         self.data_dir.as_path()
@@ -70,20 +110,26 @@ impl DebParser {
     fn extract_archive(&mut self) -> Result<()> {
         let file = File::open(&self.path)?;
         let mut archive = ar::Archive::new(file);
+        let mut members = 0;
 
+        self.progress.stage(Stage::Extracting);
         while let Some(entry) = archive.next_entry() {
             let mut entry = entry.map_err(|e| RexebError::Extraction(e.to_string()))?;
             let name = std::str::from_utf8(entry.header().identifier())
                 .map_err(|e| RexebError::Extraction(e.to_string()))?
                 .to_string();
 
+            members += 1;
+            self.progress.update(Stage::Extracting, members, None, Some(name.as_str()));
+
             if name == "debian-binary" {
                 // Version file, skip for now
                 continue;
             } else if name.starts_with("control.tar") {
-                self.extract_tar(&mut entry, &name, &self.control_dir.clone())?;
+                self.extract_tar(&mut entry, &name, &self.control_dir.clone()).map_err(|e| member_error(&name, e))?;
             } else if name.starts_with("data.tar") {
-                self.extract_tar(&mut entry, &name, &self.data_dir.clone())?;
+                self.ownership =
+                    self.extract_tar(&mut entry, &name, &self.data_dir.clone()).map_err(|e| member_error(&name, e))?;
             }
         }
 
@@ -91,38 +137,47 @@ impl DebParser {
     }
 
     /// Extract a tar archive (with compression detection)
-    fn extract_tar<R: Read>(&self, reader: &mut R, name: &str, dest: &Path) -> Result<()> {
-        // Detect compression from filename
-        if name.ends_with(".gz") {
-            let decoder = GzDecoder::new(reader);
-            let mut archive = Archive::new(decoder);
-            archive.unpack(dest)?;
-        } else if name.ends_with(".xz") {
-            let decoder = XzDecoder::new(reader);
-            let mut archive = Archive::new(decoder);
-            archive.unpack(dest)?;
-        } else if name.ends_with(".zst") {
-            let decoder = zstd::Decoder::new(reader)?;
-            let mut archive = Archive::new(decoder);
-            archive.unpack(dest)?;
-        } else if name.ends_with(".bz2") {
-            // bz2 is less common but still supported
-            let mut data = Vec::new();
-            reader.read_to_end(&mut data)?;
-            let decoder = bzip2::read::BzDecoder::new(&data[..]);
-            let mut archive = Archive::new(decoder);
-            archive.unpack(dest)?;
-        } else {
-            // Try uncompressed tar
-            let mut archive = Archive::new(reader);
-            archive.unpack(dest)?;
+    ///
+    /// Returns the entries with a non-root owner or an unusual mode.
+    fn extract_tar<R: Read>(&self, reader: &mut R, name: &str, dest: &Path) -> Result<Vec<FileOwnership>> {
+        let mut archive = Archive::new(decoder(reader, name)?);
+        let mut ownership = Vec::new();
+        // Directory modes are applied last, so a mode 000 directory does not block its content
+        let mut dir_modes = Vec::new();
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let header = entry.header();
+            let kind = header.entry_type();
+            let rel: PathBuf = entry.path()?.components().filter(|c| matches!(c, Component::Normal(_))).collect();
+            let owner = FileOwnership {
+                path: Path::new("/").join(&rel),
+                uid: header.uid()?,
+                gid: header.gid()?,
+                user: header.username().ok().flatten().map(str::to_string),
+                group: header.groupname().ok().flatten().map(str::to_string),
+                mode: header.mode()?,
+                is_dir: kind.is_dir(),
+            };
+
+            entry.unpack_in(dest)?;
+            if kind.is_dir() {
+                fs::set_permissions(dest.join(&rel), fs::Permissions::from_mode(0o755))?;
+                dir_modes.push((dest.join(&rel), owner.mode));
+            }
+            if (kind.is_file() || kind.is_dir()) && owner.is_notable() {
+                ownership.push(owner);
+            }
+        }
+        for (dir, mode) in dir_modes.into_iter().rev() {
+            fs::set_permissions(dir, fs::Permissions::from_mode(mode & 0o777))?;
         }
 
-        Ok(())
+        Ok(ownership)
     }
 
     /// Parse the package and return metadata
     pub fn parse(&self) -> Result<PackageMetadata> {
+        self.progress.stage(Stage::Parsing);
         let control = self.parse_control()?;
         let mut metadata = self.build_metadata(&control)?;
 
@@ -132,15 +187,28 @@ impl DebParser {
         // Parse conffiles
         self.parse_conffiles(&mut metadata)?;
 
-        // Parse md5sums
-        self.parse_md5sums(&mut metadata)?;
+        // Parse md5sums and sha256sums
+        metadata.md5sums = self.parse_checksums("md5sums")?;
+        metadata.sha256sums = self.parse_checksums("sha256sums")?;
 
         // Collect file list
         self.collect_files(&mut metadata)?;
+        metadata.ownership = self.ownership.clone();
+
+        metadata.provenance.source_sha256 = Some(self.sha256()?);
 
         Ok(metadata)
     }
 
+    /// SHA-256 of the .deb file
+    fn sha256(&self) -> Result<String> {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        std::io::copy(&mut File::open(&self.path)?, &mut hasher)?;
+        Ok(hex::encode(hasher.finalize()))
+    }
+
     /// Parse the control file
     fn parse_control(&self) -> Result<HashMap<String, String>> {
         let control_path = self.control_dir.join("control");
@@ -149,40 +217,7 @@ impl DebParser {
             return Err(RexebError::InvalidControl("control file not found".into()));
         }
 
-        let file = File::open(&control_path)?;
-        let reader = BufReader::new(file);
-
-        let mut fields: HashMap<String, String> = HashMap::new();
-        let mut current_key: Option<String> = None;
-        let mut current_value = String::new();
-
-        for line in reader.lines() {
-            let line = line?;
-
-            if line.starts_with(' ') || line.starts_with('\t') {
-                // Continuation line
-                if current_key.is_some() {
-                    current_value.push('\n');
-                    current_value.push_str(line.trim());
-                }
-            } else if let Some(colon_pos) = line.find(':') {
-                // Save previous field
-                if let Some(key) = current_key.take() {
-                    fields.insert(key, current_value.trim().to_string());
-                }
-
-                // Start new field
-                current_key = Some(line[..colon_pos].to_string());
-                current_value = line[colon_pos + 1..].trim().to_string();
-            }
-        }
-
-        // Save last field
-        if let Some(key) = current_key {
-            fields.insert(key, current_value.trim().to_string());
-        }
-
-        Ok(fields)
+        Ok(parse_control_fields(&fs::read_to_string(&control_path)?))
     }
 
     /// Build PackageMetadata from control fields
@@ -197,8 +232,9 @@ impl DebParser {
             .ok_or_else(|| RexebError::MissingField("Version".into()))?
             .clone();
 
-        let mut metadata = PackageMetadata::new(name, version);
+        let mut metadata = PackageMetadata::new(name, version.clone());
         metadata.source_format = PackageFormat::Deb;
+        metadata.provenance.original_version = Some(version);
 
         // Architecture
         if let Some(arch) = control.get("Architecture") {
@@ -273,6 +309,10 @@ impl DebParser {
             ("Replaces", DependencyType::Replaces),
             ("Provides", DependencyType::Provides),
             ("Breaks", DependencyType::Breaks),
+            // Present in control files built from source packages
+            ("Build-Depends", DependencyType::BuildDepends),
+            ("Build-Depends-Arch", DependencyType::BuildDepends),
+            ("Build-Depends-Indep", DependencyType::BuildDepends),
         ];
 
         for (field_name, dep_type) in dep_fields {
@@ -325,23 +365,24 @@ impl DebParser {
         Ok(())
     }
 
-    /// Parse md5sums file
-    fn parse_md5sums(&self, metadata: &mut PackageMetadata) -> Result<()> {
-        let md5sums_path = self.control_dir.join("md5sums");
+    /// Parse a checksum file (`md5sums`, `sha256sums`) in `<hash>  <path>` format
+    fn parse_checksums(&self, name: &str) -> Result<HashMap<PathBuf, String>> {
+        let mut sums = HashMap::new();
+        let sums_path = self.control_dir.join(name);
 
-        if md5sums_path.exists() {
-            let content = std::fs::read_to_string(&md5sums_path)?;
+        if sums_path.exists() {
+            let content = std::fs::read_to_string(&sums_path)?;
             for line in content.lines() {
                 let parts: Vec<&str> = line.splitn(2, char::is_whitespace).collect();
                 if parts.len() == 2 {
                     let hash = parts[0].trim();
                     let path = parts[1].trim();
-                    metadata.md5sums.insert(PathBuf::from(path), hash.to_string());
+                    sums.insert(PathBuf::from(path), hash.to_string());
                 }
             }
         }
 
-        Ok(())
+        Ok(sums)
     }
 
     /// Collect list of files in the data archive
@@ -360,13 +401,72 @@ impl DebParser {
     }
 }
 
+
+/// A truncated or corrupt member surfaces as an I/O error; report it as a
+/// broken package rather than a local I/O failure
+fn member_error(name: &str, error: RexebError) -> RexebError {
+    match error {
+        RexebError::Io(e) => RexebError::Extraction(format!("{}: {}", name, e)),
+        e => e,
+    }
+}
+
+/// Decompress an archive member, detecting the compression from its name
+fn decoder<'a, R: Read + 'a>(mut reader: R, name: &str) -> Result<Box<dyn Read + 'a>> {
+    Ok(if name.ends_with(".gz") {
+        Box::new(GzDecoder::new(reader))
+    } else if name.ends_with(".xz") {
+        Box::new(XzDecoder::new(reader))
+    } else if name.ends_with(".zst") {
+        Box::new(zstd::Decoder::new(reader)?)
+    } else if name.ends_with(".bz2") {
+        // bz2 is less common but still supported
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Box::new(bzip2::read::BzDecoder::new(std::io::Cursor::new(data)))
+    } else {
+        // Try uncompressed tar
+        Box::new(reader)
+    })
+}
+
+/// Parse the fields of a control file or one stanza of an index
+pub fn parse_control_fields(content: &str) -> HashMap<String, String> {
+    let mut fields: HashMap<String, String> = HashMap::new();
+    let mut current_key: Option<String> = None;
+    let mut current_value = String::new();
+
+    for line in content.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            // Continuation line
+            if current_key.is_some() {
+                current_value.push('\n');
+                current_value.push_str(line.trim());
+            }
+        } else if let Some(colon_pos) = line.find(':') {
+            // Save previous field
+            if let Some(key) = current_key.take() {
+                fields.insert(key, current_value.trim().to_string());
+            }
+
+            // Start new field
+            current_key = Some(line[..colon_pos].to_string());
+            current_value = line[colon_pos + 1..].trim().to_string();
+        }
+    }
+
+    // Save last field
+    if let Some(key) = current_key {
+        fields.insert(key, current_value.trim().to_string());
+    }
+
+    fields
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    // Tests would require sample .deb files
-    // For now, just test control file parsing
-
     #[test]
     fn test_parse_control_content() {
         let content = r#"Package: example
@@ -381,31 +481,33 @@ Description: An example package
  spanning multiple lines.
 "#;
 
-        // Parse manually for testing
-        let mut fields: HashMap<String, String> = HashMap::new();
-        let mut current_key: Option<String> = None;
-        let mut current_value = String::new();
-
-        for line in content.lines() {
-            if line.starts_with(' ') || line.starts_with('\t') {
-                if current_key.is_some() {
-                    current_value.push('\n');
-                    current_value.push_str(line.trim());
-                }
-            } else if let Some(colon_pos) = line.find(':') {
-                if let Some(key) = current_key.take() {
-                    fields.insert(key, current_value.trim().to_string());
-                }
-                current_key = Some(line[..colon_pos].to_string());
-                current_value = line[colon_pos + 1..].trim().to_string();
-            }
-        }
-        if let Some(key) = current_key {
-            fields.insert(key, current_value.trim().to_string());
-        }
+        let fields = parse_control_fields(content);
 
         assert_eq!(fields.get("Package"), Some(&"example".to_string()));
         assert_eq!(fields.get("Version"), Some(&"1.0-1".to_string()));
         assert_eq!(fields.get("Architecture"), Some(&"amd64".to_string()));
     }
+
+    #[test]
+    fn test_truncated_deb() {
+        use std::io::Write;
+
+        let mut data = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        data.write_all(&[0u8; 4096]).unwrap();
+        let data = data.finish().unwrap();
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("foo.deb");
+        let mut deb = ar::Builder::new(File::create(&path).unwrap());
+        for (name, content) in [("debian-binary", &b"2.0\n"[..]), ("data.tar.gz", &data[..data.len() / 2])] {
+            let header = ar::Header::new(name.as_bytes().to_vec(), content.len() as u64);
+            deb.append(&header, content).unwrap();
+        }
+        drop(deb);
+
+        let error = DebParser::new(&path).err().unwrap();
+        assert!(matches!(error, RexebError::Extraction(_)), "{:?}", error);
+        assert_eq!(error.exit_code(), 4);
+        assert_eq!(DebParser::new(temp_dir.path().join("missing.deb")).err().unwrap().exit_code(), 4);
+    }
 }
\ No newline at end of file
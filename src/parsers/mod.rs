@@ -1,5 +1,6 @@
 //! Package parsers for different formats
 
 pub mod deb;
+pub mod shell;
 
-// Future: rpm, apk, appimage parsers
\ No newline at end of file
+// Future: rpm, apk, appimage parsers
@@ -0,0 +1,790 @@
+//! POSIX shell parser for maintainer scripts
+//!
+//! Covers what Debian maintainer scripts use: simple commands with
+//! assignments and redirections, pipelines, `&&`/`||` lists, `if`,
+//! `case`, `while`/`until`/`for` loops, groups, subshells, function
+//! definitions and here-documents. Comments are dropped by the lexer, so
+//! commented-out code never reaches the tree. Words keep their source
+//! text; command substitutions are collected so callers can parse them
+//! in turn.
+
+use crate::error::{RexebError, Result};
+
+/// A word with its source text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    /// Source text, quotes included
+    pub raw: String,
+    /// Bodies of `$(...)` and backquote substitutions inside the word
+    pub substitutions: Vec<String>,
+}
+
+impl Word {
+    /// The word without quotes, `None` when it contains expansions
+    pub fn literal(&self) -> Option<String> {
+        let mut out = String::new();
+        let mut chars = self.raw.chars();
+        let mut double = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' if !double => {
+                    for c in chars.by_ref() {
+                        if c == '\'' {
+                            break;
+                        }
+                        out.push(c);
+                    }
+                }
+                '"' => double = !double,
+                '\\' => out.extend(chars.next()),
+                '$' | '`' => return None,
+                _ => out.push(c),
+            }
+        }
+        Some(out)
+    }
+}
+
+/// A command with its words, in order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    /// Line the command starts on, from 1
+    pub line: usize,
+    /// `NAME=value` prefixes
+    pub assignments: Vec<Word>,
+    /// Command name and arguments
+    pub words: Vec<Word>,
+    /// Redirections as operator and target (`>`, `/etc/foo`)
+    pub redirects: Vec<(String, Word)>,
+}
+
+impl Command {
+    /// Command name without quotes, `None` for assignments only or a computed name
+    pub fn name(&self) -> Option<String> {
+        self.words.first().and_then(Word::literal)
+    }
+
+    /// Source text of the words
+    pub fn text(&self) -> String {
+        self.words.iter().map(|w| w.raw.as_str()).collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// `&&` or `||`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AndOr {
+    /// Run the right side if the left succeeded
+    And,
+    /// Run the right side if the left failed
+    Or,
+}
+
+/// One arm of a `case`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseArm {
+    /// Patterns, as source text
+    pub patterns: Vec<String>,
+    /// Commands run for the patterns
+    pub body: Vec<Node>,
+}
+
+/// A node of the syntax tree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// A simple command
+    Simple(Command),
+    /// Commands connected by pipes
+    Pipeline {
+        /// Whether the status is negated with `!`
+        negated: bool,
+        /// The commands
+        commands: Vec<Node>,
+    },
+    /// `left && right` or `left || right`
+    List {
+        /// Operator
+        op: AndOr,
+        /// Command run first
+        left: Box<Node>,
+        /// Command run depending on its status
+        right: Box<Node>,
+    },
+    /// `if`/`elif`/`else`
+    If {
+        /// Conditions and the bodies they guard, `if` first
+        branches: Vec<(Vec<Node>, Vec<Node>)>,
+        /// `else` body
+        otherwise: Vec<Node>,
+    },
+    /// `case word in ... esac`
+    Case {
+        /// Word matched against the patterns
+        word: Word,
+        /// Arms in order
+        arms: Vec<CaseArm>,
+    },
+    /// `while` or `until` loop
+    Loop {
+        /// Condition commands
+        condition: Vec<Node>,
+        /// Loop body
+        body: Vec<Node>,
+    },
+    /// `for name in words`
+    For {
+        /// Loop variable
+        name: String,
+        /// Words iterated over
+        items: Vec<Word>,
+        /// Loop body
+        body: Vec<Node>,
+    },
+    /// `{ ...; }` or `( ... )`
+    Group(Vec<Node>),
+    /// `name() body`
+    Function {
+        /// Function name
+        name: String,
+        /// Function body
+        body: Box<Node>,
+    },
+}
+
+/// Parse a script into its top-level commands
+pub fn parse(script: &str) -> Result<Vec<Node>> {
+    let tokens = Lexer::new(script).tokenize()?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let nodes = parser.list(&[])?;
+    match parser.peek() {
+        Token::Eof => Ok(nodes),
+        other => Err(parser.error(&format!("unexpected {}", other.describe()))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(Word),
+    Op(&'static str),
+    Newline,
+    Eof,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(w) => format!("'{}'", w.raw),
+            Token::Op(op) => format!("'{}'", op),
+            Token::Newline => "newline".to_string(),
+            Token::Eof => "end of script".to_string(),
+        }
+    }
+}
+
+/// Operators, longest first
+const OPERATORS: &[&str] = &[
+    "<<-", ";;", "&&", "||", "<<", ">>", ">&", "<&", ">|", "<>", ";", "&", "|", "(", ")", "<", ">",
+];
+
+struct Lexer {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    tokens: Vec<(Token, usize)>,
+    /// Here-document delimiters waiting for the end of the line, with `<<-` tab stripping
+    heredocs: Vec<(String, bool)>,
+}
+
+impl Lexer {
+    fn new(script: &str) -> Self {
+        Self {
+            chars: script.chars().collect(),
+            pos: 0,
+            line: 1,
+            tokens: Vec::new(),
+            heredocs: Vec::new(),
+        }
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn error(&self, message: &str) -> RexebError {
+        RexebError::ScriptTranslation(format!("line {}: {}", self.line, message))
+    }
+
+    fn tokenize(mut self) -> Result<Vec<(Token, usize)>> {
+        while let Some(c) = self.peek_at(0) {
+            match c {
+                ' ' | '\t' | '\r' => self.pos += 1,
+                '\\' if self.peek_at(1) == Some('\n') => {
+                    self.pos += 2;
+                    self.line += 1;
+                }
+                '#' => {
+                    while self.peek_at(0).is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                '\n' => {
+                    self.pos += 1;
+                    self.tokens.push((Token::Newline, self.line));
+                    self.line += 1;
+                    self.read_heredocs()?;
+                }
+                _ => {
+                    if let Some(op) = OPERATORS.iter().find(|op| self.starts_with(op)) {
+                        self.pos += op.len();
+                        self.tokens.push((Token::Op(op), self.line));
+                        if op.starts_with("<<") {
+                            self.queue_heredoc(*op == "<<-")?;
+                        }
+                    } else {
+                        let line = self.line;
+                        let word = self.word()?;
+                        // `2>` and `2>&1`: the descriptor belongs to the redirection
+                        let is_fd = word.raw.chars().all(|c| c.is_ascii_digit())
+                            && matches!(self.peek_at(0), Some('<' | '>'));
+                        if !is_fd {
+                            self.tokens.push((Token::Word(word), line));
+                        }
+                    }
+                }
+            }
+        }
+        self.tokens.push((Token::Eof, self.line));
+        Ok(self.tokens)
+    }
+
+    fn starts_with(&self, s: &str) -> bool {
+        s.chars().enumerate().all(|(i, c)| self.peek_at(i) == Some(c))
+    }
+
+    /// Read one word, up to unquoted whitespace or an operator
+    fn word(&mut self) -> Result<Word> {
+        let mut raw = String::new();
+        let mut substitutions = Vec::new();
+
+        while let Some(c) = self.peek_at(0) {
+            if matches!(c, ' ' | '\t' | '\r' | '\n' | ';' | '&' | '|' | '(' | ')' | '<' | '>') {
+                break;
+            }
+            match c {
+                '\'' => {
+                    let end = self.find_from(self.pos + 1, '\'').ok_or_else(|| self.error("unterminated '"))?;
+                    self.take_until(&mut raw, end + 1);
+                }
+                '"' => {
+                    raw.push('"');
+                    self.pos += 1;
+                    loop {
+                        match self.peek_at(0) {
+                            None => return Err(self.error("unterminated \"")),
+                            Some('"') => {
+                                raw.push('"');
+                                self.pos += 1;
+                                break;
+                            }
+                            Some('\\') => {
+                                let end = (self.pos + 2).min(self.chars.len());
+                                self.take_until(&mut raw, end);
+                            }
+                            Some('$') if self.peek_at(1) == Some('(') => {
+                                substitutions.push(self.substitution(&mut raw)?);
+                            }
+                            Some('`') => substitutions.push(self.backquote(&mut raw)?),
+                            Some(_) => {
+                                let end = self.pos + 1;
+                                self.take_until(&mut raw, end);
+                            }
+                        }
+                    }
+                }
+                '\\' => {
+                    let end = (self.pos + 2).min(self.chars.len());
+                    self.take_until(&mut raw, end);
+                }
+                '$' if self.peek_at(1) == Some('(') => substitutions.push(self.substitution(&mut raw)?),
+                '$' if self.peek_at(1) == Some('{') => {
+                    let end = self.find_from(self.pos + 2, '}').ok_or_else(|| self.error("unterminated ${"))?;
+                    self.take_until(&mut raw, end + 1);
+                }
+                '`' => substitutions.push(self.backquote(&mut raw)?),
+                _ => {
+                    let end = self.pos + 1;
+                    self.take_until(&mut raw, end);
+                }
+            }
+        }
+
+        Ok(Word { raw, substitutions })
+    }
+
+    fn find_from(&self, from: usize, c: char) -> Option<usize> {
+        (from..self.chars.len()).find(|&i| self.chars[i] == c)
+    }
+
+    /// Move the characters up to `end` into the word, counting lines
+    fn take_until(&mut self, raw: &mut String, end: usize) {
+        for &c in &self.chars[self.pos..end] {
+            if c == '\n' {
+                self.line += 1;
+            }
+            raw.push(c);
+        }
+        self.pos = end;
+    }
+
+    /// Read `$(...)`, returning its body
+    fn substitution(&mut self, raw: &mut String) -> Result<String> {
+        let start = self.pos + 2;
+        let mut depth = 0;
+        let mut i = start;
+        let mut quote: Option<char> = None;
+        loop {
+            let c = *self.chars.get(i).ok_or_else(|| self.error("unterminated $("))?;
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some('"'), '\\') => i += 1,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '\\') => i += 1,
+                (None, '(') => depth += 1,
+                (None, ')') if depth == 0 => break,
+                (None, ')') => depth -= 1,
+                _ => {}
+            }
+            i += 1;
+        }
+        let body: String = self.chars[start..i].iter().collect();
+        self.take_until(raw, i + 1);
+        Ok(body)
+    }
+
+    /// Read a backquoted substitution, returning its body
+    fn backquote(&mut self, raw: &mut String) -> Result<String> {
+        let mut i = self.pos + 1;
+        while self.chars.get(i).is_some_and(|&c| c != '`') {
+            if self.chars[i] == '\\' {
+                i += 1;
+            }
+            i += 1;
+        }
+        if i >= self.chars.len() {
+            return Err(self.error("unterminated `"));
+        }
+        let body: String = self.chars[self.pos + 1..i].iter().collect();
+        self.take_until(raw, i + 1);
+        Ok(body)
+    }
+
+    /// Note the delimiter of a here-document whose body starts on the next line
+    fn queue_heredoc(&mut self, strip_tabs: bool) -> Result<()> {
+        while matches!(self.peek_at(0), Some(' ' | '\t')) {
+            self.pos += 1;
+        }
+        let delimiter = self.word()?;
+        let name = delimiter.raw.replace(['\'', '"', '\\'], "");
+        self.tokens.push((Token::Word(delimiter), self.line));
+        self.heredocs.push((name, strip_tabs));
+        Ok(())
+    }
+
+    /// Skip the bodies of the here-documents started on the previous line
+    fn read_heredocs(&mut self) -> Result<()> {
+        for (delimiter, strip_tabs) in std::mem::take(&mut self.heredocs) {
+            loop {
+                if self.pos >= self.chars.len() {
+                    return Err(self.error(&format!("here-document '{}' is not terminated", delimiter)));
+                }
+                let end = self.find_from(self.pos, '\n').unwrap_or(self.chars.len());
+                let line: String = self.chars[self.pos..end].iter().collect();
+                self.pos = (end + 1).min(self.chars.len());
+                self.line += 1;
+                let line = if strip_tabs { line.trim_start_matches('\t') } else { &line };
+                if line == delimiter {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Words that end a list when seen in command position
+const RESERVED_ENDS: &[&str] = &["then", "elif", "else", "fi", "do", "done", "esac", "}"];
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: &str) -> RexebError {
+        RexebError::ScriptTranslation(format!("line {}: {}", self.line(), message))
+    }
+
+    /// The current token as an unquoted word
+    fn keyword(&self) -> Option<String> {
+        match self.peek() {
+            Token::Word(w) => w.literal(),
+            _ => None,
+        }
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        self.keyword().as_deref() == Some(keyword)
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if !self.at_keyword(keyword) {
+            return Err(self.error(&format!("expected '{}', found {}", keyword, self.peek().describe())));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn expect_op(&mut self, op: &str) -> Result<()> {
+        if !matches!(self.peek(), Token::Op(o) if *o == op) {
+            return Err(self.error(&format!("expected '{}', found {}", op, self.peek().describe())));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == &Token::Newline {
+            self.advance();
+        }
+    }
+
+    /// Commands up to one of the given keywords, `)`, `;;` or the end
+    fn list(&mut self, ends: &[&str]) -> Result<Vec<Node>> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                Token::Eof | Token::Op(")") | Token::Op(";;") => break,
+                _ => {}
+            }
+            if self.keyword().is_some_and(|k| ends.contains(&k.as_str()) || RESERVED_ENDS.contains(&k.as_str())) {
+                break;
+            }
+
+            nodes.push(self.and_or()?);
+            match self.peek() {
+                Token::Op(";") | Token::Op("&") | Token::Newline => {
+                    self.advance();
+                }
+                _ => {}
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn and_or(&mut self) -> Result<Node> {
+        let mut node = self.pipeline()?;
+        loop {
+            let op = match self.peek() {
+                Token::Op("&&") => AndOr::And,
+                Token::Op("||") => AndOr::Or,
+                _ => return Ok(node),
+            };
+            self.advance();
+            self.skip_newlines();
+            let right = self.pipeline()?;
+            node = Node::List {
+                op,
+                left: Box::new(node),
+                right: Box::new(right),
+            };
+        }
+    }
+
+    fn pipeline(&mut self) -> Result<Node> {
+        let negated = self.at_keyword("!");
+        if negated {
+            self.advance();
+        }
+        let mut commands = vec![self.command()?];
+        while self.peek() == &Token::Op("|") {
+            self.advance();
+            self.skip_newlines();
+            commands.push(self.command()?);
+        }
+        if commands.len() == 1 && !negated {
+            return Ok(commands.remove(0));
+        }
+        Ok(Node::Pipeline { negated, commands })
+    }
+
+    fn command(&mut self) -> Result<Node> {
+        let node = match self.keyword().as_deref() {
+            Some("if") => self.if_clause()?,
+            Some("while") | Some("until") => {
+                self.advance();
+                let condition = self.list(&["do"])?;
+                self.expect_keyword("do")?;
+                let body = self.list(&["done"])?;
+                self.expect_keyword("done")?;
+                Node::Loop { condition, body }
+            }
+            Some("for") => self.for_clause()?,
+            Some("case") => self.case_clause()?,
+            Some("{") => {
+                self.advance();
+                let body = self.list(&["}"])?;
+                self.expect_keyword("}")?;
+                Node::Group(body)
+            }
+            _ if self.peek() == &Token::Op("(") => {
+                self.advance();
+                let body = self.list(&[])?;
+                self.expect_op(")")?;
+                Node::Group(body)
+            }
+            _ => {
+                // `name() compound`
+                if let (Token::Word(word), Some(Token::Op("(")), Some(Token::Op(")"))) =
+                    (self.peek(), self.tokens.get(self.pos + 1).map(|t| &t.0), self.tokens.get(self.pos + 2).map(|t| &t.0))
+                {
+                    let name = word.raw.clone();
+                    self.pos += 3;
+                    self.skip_newlines();
+                    let body = self.command()?;
+                    return Ok(Node::Function { name, body: Box::new(body) });
+                }
+                return self.simple();
+            }
+        };
+        self.redirections()?;
+        Ok(node)
+    }
+
+    /// Skip redirections after a compound command
+    fn redirections(&mut self) -> Result<()> {
+        while let Token::Op(op) = self.peek() {
+            if !op.starts_with(['<', '>']) {
+                break;
+            }
+            self.advance();
+            match self.advance() {
+                Token::Word(_) => {}
+                other => return Err(self.error(&format!("missing redirection target before {}", other.describe()))),
+            }
+        }
+        Ok(())
+    }
+
+    fn simple(&mut self) -> Result<Node> {
+        let line = self.line();
+        let mut command = Command {
+            line,
+            assignments: Vec::new(),
+            words: Vec::new(),
+            redirects: Vec::new(),
+        };
+        loop {
+            match self.peek().clone() {
+                Token::Word(word) => {
+                    self.advance();
+                    let is_assignment = command.words.is_empty()
+                        && word.raw.split_once('=').is_some_and(|(name, _)| {
+                            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                        });
+                    if is_assignment {
+                        command.assignments.push(word);
+                    } else {
+                        command.words.push(word);
+                    }
+                }
+                Token::Op(op) if op.starts_with(['<', '>']) => {
+                    self.advance();
+                    match self.advance() {
+                        Token::Word(target) => command.redirects.push((op.to_string(), target)),
+                        other => {
+                            return Err(self.error(&format!("missing redirection target before {}", other.describe())))
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+        if command.words.is_empty() && command.assignments.is_empty() {
+            return Err(self.error(&format!("unexpected {}", self.peek().describe())));
+        }
+        Ok(Node::Simple(command))
+    }
+
+    fn if_clause(&mut self) -> Result<Node> {
+        self.expect_keyword("if")?;
+        let mut branches = Vec::new();
+        let mut otherwise = Vec::new();
+        loop {
+            let condition = self.list(&["then"])?;
+            self.expect_keyword("then")?;
+            let body = self.list(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+            match self.keyword().as_deref() {
+                Some("elif") => {
+                    self.advance();
+                }
+                Some("else") => {
+                    self.advance();
+                    otherwise = self.list(&["fi"])?;
+                    self.expect_keyword("fi")?;
+                    break;
+                }
+                _ => {
+                    self.expect_keyword("fi")?;
+                    break;
+                }
+            }
+        }
+        Ok(Node::If { branches, otherwise })
+    }
+
+    fn for_clause(&mut self) -> Result<Node> {
+        self.expect_keyword("for")?;
+        let name = self.keyword().ok_or_else(|| self.error("expected a loop variable"))?;
+        self.advance();
+        self.skip_newlines();
+
+        let mut items = Vec::new();
+        if self.at_keyword("in") {
+            self.advance();
+            while let Token::Word(word) = self.peek().clone() {
+                items.push(word);
+                self.advance();
+            }
+        }
+        if self.peek() == &Token::Op(";") {
+            self.advance();
+        }
+        self.skip_newlines();
+        self.expect_keyword("do")?;
+        let body = self.list(&["done"])?;
+        self.expect_keyword("done")?;
+        Ok(Node::For { name, items, body })
+    }
+
+    fn case_clause(&mut self) -> Result<Node> {
+        self.expect_keyword("case")?;
+        let word = match self.advance() {
+            Token::Word(word) => word,
+            other => return Err(self.error(&format!("expected a word after case, found {}", other.describe()))),
+        };
+        self.skip_newlines();
+        self.expect_keyword("in")?;
+
+        let mut arms = Vec::new();
+        loop {
+            self.skip_newlines();
+            if self.at_keyword("esac") {
+                self.advance();
+                break;
+            }
+            if self.peek() == &Token::Op("(") {
+                self.advance();
+            }
+            let mut patterns = Vec::new();
+            loop {
+                match self.advance() {
+                    Token::Word(w) => patterns.push(w.raw),
+                    other => return Err(self.error(&format!("expected a case pattern, found {}", other.describe()))),
+                }
+                match self.advance() {
+                    Token::Op("|") => continue,
+                    Token::Op(")") => break,
+                    other => return Err(self.error(&format!("expected ')' after pattern, found {}", other.describe()))),
+                }
+            }
+            let body = self.list(&["esac"])?;
+            arms.push(CaseArm { patterns, body });
+            if self.peek() == &Token::Op(";;") {
+                self.advance();
+            }
+        }
+        Ok(Node::Case { word, arms })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(nodes: &[Node], out: &mut Vec<String>) {
+        for node in nodes {
+            match node {
+                Node::Simple(c) => out.extend(c.name()),
+                Node::Pipeline { commands, .. } | Node::Group(commands) => names(commands, out),
+                Node::List { left, right, .. } => names(&[(**left).clone(), (**right).clone()], out),
+                Node::If { branches, otherwise } => {
+                    for (condition, body) in branches {
+                        names(condition, out);
+                        names(body, out);
+                    }
+                    names(otherwise, out);
+                }
+                Node::Case { arms, .. } => arms.iter().for_each(|arm| names(&arm.body, out)),
+                Node::Loop { condition, body } => {
+                    names(condition, out);
+                    names(body, out);
+                }
+                Node::For { body, .. } => names(body, out),
+                Node::Function { body, .. } => names(&[(**body).clone()], out),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_maintainer_script() {
+        let script = r#"#!/bin/sh
+set -e
+# db_get foo/bar
+case "$1" in
+    configure|reconfigure)
+        if [ -e /usr/share/debconf/confmodule ]; then
+            . /usr/share/debconf/confmodule
+            db_get foo/enable || true
+        fi
+        getent passwd foo >/dev/null 2>&1 || adduser --system --group foo
+        cat > /etc/foo.conf <<EOF
+adduser inside a here-document
+EOF
+        for d in /var/lib/foo /var/log/foo; do mkdir -p "$d"; done
+        ;;
+    abort-upgrade) ;;
+esac
+fix() { chown -R foo: "$(dirname /var/lib/foo/x)"; }
+"#;
+        let nodes = parse(script).unwrap();
+        let mut found = Vec::new();
+        names(&nodes, &mut found);
+        assert_eq!(found, ["set", "[", ".", "db_get", "true", "getent", "adduser", "cat", "mkdir", "chown"]);
+
+        let Node::Function { body, .. } = &nodes[2] else { panic!("expected a function") };
+        let Node::Group(commands) = &**body else { panic!("expected a group") };
+        let Node::Simple(chown) = &commands[0] else { panic!("expected a command") };
+        assert_eq!(chown.words[3].substitutions, ["dirname /var/lib/foo/x"]);
+        assert_eq!(chown.line, 18);
+
+        assert!(parse("if true; then echo").is_err());
+    }
+}
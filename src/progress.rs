@@ -0,0 +1,339 @@
+//! Conversion progress reporting
+//!
+//! The parser, resolver and builder emit [`ProgressEvent`]s through a
+//! [`Progress`] handle, so front ends (CLI progress bars) can show real
+//! progress instead of guessing. Front ends in other processes
+//! read them as an [`EventStream`] of JSON lines.
+
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+
+use crate::error::Result;
+
+use serde::Serialize;
+
+/// Stage of a conversion, in execution order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Unpacking the source package
+    Extracting,
+    /// Reading control data and scripts
+    Parsing,
+    /// Mapping dependencies to Arch packages
+    Resolving,
+    /// Copying the payload into the package root
+    Copying,
+    /// Running the payload conversion passes
+    Processing,
+    /// Writing .PKGINFO, .INSTALL, .MTREE, ...
+    Metadata,
+    /// Writing the compressed archive
+    Archiving,
+    /// Conversion finished
+    Done,
+}
+
+impl Stage {
+    /// Human readable label
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Extracting => "Extracting package",
+            Self::Parsing => "Parsing package",
+            Self::Resolving => "Resolving dependencies",
+            Self::Copying => "Copying files",
+            Self::Processing => "Converting payload",
+            Self::Metadata => "Writing metadata",
+            Self::Archiving => "Compressing package",
+            Self::Done => "Done",
+        }
+    }
+
+    /// Start and end of this stage as fractions of a whole conversion
+    pub fn span(&self) -> (f64, f64) {
+        match self {
+            Self::Extracting => (0.0, 0.1),
+            Self::Parsing => (0.1, 0.2),
+            Self::Resolving => (0.2, 0.5),
+            Self::Copying => (0.5, 0.65),
+            Self::Processing => (0.65, 0.75),
+            Self::Metadata => (0.75, 0.8),
+            Self::Archiving => (0.8, 1.0),
+            Self::Done => (1.0, 1.0),
+        }
+    }
+}
+
+/// A progress update
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProgressEvent {
+    /// Current stage
+    pub stage: Stage,
+    /// Items processed so far in this stage
+    pub current: u64,
+    /// Total items in this stage, when known
+    pub total: Option<u64>,
+    /// Item being processed (file, dependency, ...)
+    pub item: Option<String>,
+}
+
+impl ProgressEvent {
+    /// Overall progress of the conversion (0.0 - 1.0)
+    pub fn fraction(&self) -> f64 {
+        let (start, end) = self.stage.span();
+        let within = match self.total {
+            Some(total) if total > 0 => (self.current as f64 / total as f64).min(1.0),
+            _ => 0.0,
+        };
+        start + (end - start) * within
+    }
+}
+
+/// Callback receiving progress events
+pub type ProgressCallback = Arc<dyn Fn(&ProgressEvent) + Send + Sync>;
+
+/// Handle for emitting progress events
+///
+/// The default handle discards all events.
+#[derive(Clone, Default)]
+pub struct Progress {
+    callback: Option<ProgressCallback>,
+}
+
+impl Progress {
+    /// Deliver events to a callback
+    pub fn new(callback: impl Fn(&ProgressEvent) + Send + Sync + 'static) -> Self {
+        Self { callback: Some(Arc::new(callback)) }
+    }
+
+    /// Deliver events through a channel
+    pub fn channel() -> (Self, Receiver<ProgressEvent>) {
+        let (tx, rx) = mpsc::channel();
+        let tx = std::sync::Mutex::new(tx);
+        let progress = Self::new(move |event| {
+            if let Ok(tx) = tx.lock() {
+                let _ = tx.send(event.clone());
+            }
+        });
+        (progress, rx)
+    }
+
+    /// Emit an event
+    pub fn emit(&self, event: ProgressEvent) {
+        if let Some(ref callback) = self.callback {
+            callback(&event);
+        }
+    }
+
+    /// Announce the start of a stage
+    pub fn stage(&self, stage: Stage) {
+        self.update(stage, 0, None, None::<String>);
+    }
+
+    /// Report item counts within a stage
+    pub fn update(&self, stage: Stage, current: u64, total: Option<u64>, item: Option<impl Into<String>>) {
+        if self.callback.is_some() {
+            self.emit(ProgressEvent { stage, current, total, item: item.map(Into::into) });
+        }
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress").field("enabled", &self.callback.is_some()).finish()
+    }
+}
+
+/// A line of the NDJSON event stream
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// Conversion of an input started
+    Started {
+        /// The input package
+        input: String,
+    },
+    /// A stage started
+    StageStarted {
+        /// The input package
+        input: String,
+        /// The stage
+        stage: Stage,
+    },
+    /// A stage finished
+    StageFinished {
+        /// The input package
+        input: String,
+        /// The stage
+        stage: Stage,
+    },
+    /// Items processed within a stage
+    Progress {
+        /// The input package
+        input: String,
+        /// Current stage
+        stage: Stage,
+        /// Overall progress of the conversion (0 - 100)
+        percent: u8,
+        /// Items processed so far in this stage
+        current: u64,
+        /// Total items in this stage, when known
+        total: Option<u64>,
+        /// Item being processed
+        item: Option<String>,
+    },
+    /// The conversion warned about something
+    Warning {
+        /// The input package
+        input: String,
+        /// The warning
+        message: String,
+    },
+    /// The conversion succeeded
+    Finished {
+        /// The input package
+        input: String,
+        /// The package or PKGBUILD written
+        output: String,
+    },
+    /// The conversion failed
+    Failed {
+        /// The input package
+        input: String,
+        /// Why
+        error: String,
+    },
+}
+
+/// Writer of [`StreamEvent`]s, one JSON object per line
+///
+/// Clones share the writer, so conversions running side by side never
+/// interleave their lines.
+#[derive(Clone)]
+pub struct EventStream {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl EventStream {
+    /// Stream to a writer
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self { writer: Arc::new(Mutex::new(Box::new(writer))) }
+    }
+
+    /// Stream to standard output
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+
+    /// Stream to a file or FIFO; opening a FIFO waits for its reader
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self::new(std::fs::File::create(path)?))
+    }
+
+    /// Write an event
+    ///
+    /// A reader that went away does not stop the conversion.
+    pub fn emit(&self, event: &StreamEvent) {
+        let Ok(line) = serde_json::to_string(event) else {
+            return;
+        };
+        if let Ok(mut writer) = self.writer.lock() {
+            let _ = writeln!(writer, "{}", line).and_then(|_| writer.flush());
+        }
+    }
+
+    /// Progress handle streaming the events of converting `input`
+    ///
+    /// Stage changes become `stage_finished`/`stage_started` pairs; every
+    /// event is also sent as `progress`.
+    pub fn progress(&self, input: &str) -> Progress {
+        let stream = self.clone();
+        let input = input.to_string();
+        let current = Mutex::new(None::<Stage>);
+        Progress::new(move |event| {
+            if let Ok(mut current) = current.lock() {
+                if *current != Some(event.stage) {
+                    if let Some(stage) = current.take() {
+                        stream.emit(&StreamEvent::StageFinished { input: input.clone(), stage });
+                    }
+                    if event.stage != Stage::Done {
+                        stream.emit(&StreamEvent::StageStarted { input: input.clone(), stage: event.stage });
+                        *current = Some(event.stage);
+                    }
+                }
+            }
+            stream.emit(&StreamEvent::Progress {
+                input: input.clone(),
+                stage: event.stage,
+                percent: (event.fraction() * 100.0) as u8,
+                current: event.current,
+                total: event.total,
+                item: event.item.clone(),
+            });
+        })
+    }
+}
+
+impl fmt::Debug for EventStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventStream").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_and_fraction() {
+        let (progress, rx) = Progress::channel();
+        progress.stage(Stage::Resolving);
+        progress.update(Stage::Copying, 5, Some(10), Some("usr/bin/foo"));
+        drop(progress);
+
+        let events: Vec<_> = rx.iter().collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].fraction(), 0.2);
+        assert_eq!(events[1].item.as_deref(), Some("usr/bin/foo"));
+        assert!((events[1].fraction() - 0.575).abs() < 1e-9);
+
+        // The default handle is a no-op
+        Progress::default().stage(Stage::Done);
+    }
+
+    #[test]
+    fn test_event_stream() {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+        impl Write for Buffer {
+            fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(data)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let stream = EventStream::new(buffer.clone());
+        let progress = stream.progress("foo.deb");
+        progress.stage(Stage::Extracting);
+        progress.update(Stage::Copying, 1, Some(2), Some("usr/bin/foo"));
+        progress.stage(Stage::Done);
+        stream.emit(&StreamEvent::Warning { input: "foo.deb".into(), message: "no license".into() });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = output.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let kinds: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(
+            kinds,
+            ["stage_started", "progress", "stage_finished", "stage_started", "progress", "stage_finished", "progress", "warning"]
+        );
+        assert_eq!(events[4]["percent"], 57);
+        assert_eq!(events[4]["stage"], "copying");
+        assert_eq!(events[7]["message"], "no license");
+    }
+}
@@ -0,0 +1,223 @@
+//! pacman `desc` file format
+//!
+//! Repository and local databases store package metadata as sections of
+//! the form `%KEY%` followed by one value per line and a blank line.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{RexebError, Result};
+
+/// Parsed `desc` file, keeping section order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Desc {
+    /// Sections as (key, values), keys without the surrounding `%`
+    fields: Vec<(String, Vec<String>)>,
+}
+
+impl Desc {
+    /// Parse `desc` content
+    pub fn parse(content: &str) -> Self {
+        let mut desc = Self::default();
+        let mut current: Option<(String, Vec<String>)> = None;
+
+        for line in content.lines() {
+            if let Some(key) = line.strip_prefix('%').and_then(|l| l.strip_suffix('%')) {
+                if let Some(field) = current.take() {
+                    desc.fields.push(field);
+                }
+                current = Some((key.to_string(), Vec::new()));
+            } else if !line.is_empty() {
+                if let Some((_, ref mut values)) = current {
+                    values.push(line.to_string());
+                }
+            }
+        }
+
+        if let Some(field) = current {
+            desc.fields.push(field);
+        }
+
+        desc
+    }
+
+    /// Render the `desc` content
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (key, values) in &self.fields {
+            out.push_str(&format!("%{}%\n", key));
+            for value in values {
+                out.push_str(value);
+                out.push('\n');
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// First value of a section
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).first().map(String::as_str)
+    }
+
+    /// All values of a section
+    pub fn get_all(&self, key: &str) -> &[String] {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Set a section, skipping it entirely when there are no values
+    pub fn set(&mut self, key: &str, values: Vec<String>) {
+        self.fields.retain(|(k, _)| k != key);
+        if !values.is_empty() {
+            self.fields.push((key.to_string(), values));
+        }
+    }
+
+    /// Package name
+    pub fn name(&self) -> &str {
+        self.get("NAME").unwrap_or_default()
+    }
+
+    /// Package version including epoch and release
+    pub fn version(&self) -> &str {
+        self.get("VERSION").unwrap_or_default()
+    }
+
+    /// Package file name in the repository
+    pub fn filename(&self) -> Option<&str> {
+        self.get("FILENAME")
+    }
+
+    /// Value of an `XDATA` entry such as `rexeb_version`
+    pub fn xdata(&self, key: &str) -> Option<&str> {
+        self.get_all("XDATA")
+            .iter()
+            .find_map(|entry| entry.strip_prefix(key)?.strip_prefix('='))
+    }
+
+    /// Build a repository `desc` entry for a package file
+    ///
+    /// Section order follows `repo-add`.
+    pub fn from_package(package: &Path) -> Result<Self> {
+        let pkginfo = read_pkginfo(package)?;
+        let get = |key: &str| pkginfo.get(key).cloned().unwrap_or_default();
+
+        let content = std::fs::read(package)?;
+        let md5sum = format!("{:x}", md5::compute(&content));
+        let sha256sum = hex::encode(Sha256::digest(&content));
+        let filename = package
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mut desc = Self::default();
+        desc.set("FILENAME", vec![filename]);
+        desc.set("NAME", get("pkgname"));
+        let base = get("pkgbase");
+        desc.set("BASE", if base.is_empty() { get("pkgname") } else { base });
+        desc.set("VERSION", get("pkgver"));
+        desc.set("DESC", get("pkgdesc"));
+        desc.set("GROUPS", get("group"));
+        desc.set("CSIZE", vec![content.len().to_string()]);
+        desc.set("ISIZE", get("size"));
+        desc.set("MD5SUM", vec![md5sum]);
+        desc.set("SHA256SUM", vec![sha256sum]);
+        desc.set("URL", get("url"));
+        desc.set("LICENSE", get("license"));
+        desc.set("ARCH", get("arch"));
+        desc.set("BUILDDATE", get("builddate"));
+        desc.set("PACKAGER", get("packager"));
+        desc.set("REPLACES", get("replaces"));
+        desc.set("CONFLICTS", get("conflict"));
+        desc.set("PROVIDES", get("provides"));
+        desc.set("DEPENDS", get("depend"));
+        desc.set("OPTDEPENDS", get("optdepend"));
+        desc.set("MAKEDEPENDS", get("makedepend"));
+        desc.set("CHECKDEPENDS", get("checkdepend"));
+
+        if desc.name().is_empty() || desc.version().is_empty() {
+            return Err(RexebError::Repository(format!(
+                "{} has no pkgname/pkgver in .PKGINFO",
+                package.display()
+            )));
+        }
+
+        Ok(desc)
+    }
+}
+
+/// Parse `.PKGINFO` content into key -> values
+pub fn parse_pkginfo(content: &str) -> HashMap<String, Vec<String>> {
+    let mut info: HashMap<String, Vec<String>> = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(" = ") {
+            info.entry(key.trim().to_string()).or_default().push(value.trim().to_string());
+        }
+    }
+    info
+}
+
+/// Read `.PKGINFO` from a built package archive
+pub fn read_pkginfo(package: &Path) -> Result<HashMap<String, Vec<String>>> {
+    let file = File::open(package)?;
+    let name = package.to_string_lossy();
+
+    let reader: Box<dyn Read> = if name.ends_with(".zst") {
+        Box::new(zstd::Decoder::new(file)?)
+    } else if name.ends_with(".xz") {
+        Box::new(xz2::read::XzDecoder::new(file))
+    } else if name.ends_with(".gz") {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.as_os_str() == ".PKGINFO" {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            return Ok(parse_pkginfo(&content));
+        }
+    }
+
+    Err(RexebError::Repository(format!("{} has no .PKGINFO", package.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_desc_roundtrip() {
+        let content = "%NAME%\nfoo\n\n%VERSION%\n1:2.0-1\n\n%DEPENDS%\nglibc\nbash\n\n";
+        let desc = Desc::parse(content);
+
+        assert_eq!(desc.name(), "foo");
+        assert_eq!(desc.version(), "1:2.0-1");
+        assert_eq!(desc.get_all("DEPENDS"), ["glibc", "bash"]);
+        assert_eq!(desc.render(), content);
+    }
+
+    #[test]
+    fn test_parse_pkginfo() {
+        let info = parse_pkginfo("# generated\npkgname = foo\ndepend = glibc\ndepend = zlib\n");
+
+        assert_eq!(info["pkgname"], ["foo"]);
+        assert_eq!(info["depend"], ["glibc", "zlib"]);
+        assert!(!info.contains_key("# generated"));
+    }
+}
@@ -0,0 +1,309 @@
+//! Local pacman repository management
+//!
+//! Keeps converted packages in a directory together with a `<name>.db`
+//! repository database in the format `repo-add` writes, so the directory
+//! can be added to pacman.conf without needing pacman's repo tools.
+
+mod desc;
+
+pub use desc::*;
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+use crate::config::Config;
+use crate::error::{RexebError, Result};
+
+/// pacman's database of installed packages
+pub const LOCAL_DB: &str = "/var/lib/pacman/local";
+
+/// Installed packages converted by rexeb, by name
+///
+/// Reads the `desc` entries of pacman's local database at `local_db`;
+/// converted packages carry a `rexeb_version` in their `XDATA`.
+pub fn converted_packages(local_db: &Path) -> Result<Vec<Desc>> {
+    let mut packages = Vec::new();
+    for entry in fs::read_dir(local_db)? {
+        let desc_path = entry?.path().join("desc");
+        if !desc_path.is_file() {
+            continue;
+        }
+        let desc = Desc::parse(&fs::read_to_string(&desc_path)?);
+        if desc.xdata("rexeb_version").is_some() {
+            packages.push(desc);
+        }
+    }
+    packages.sort_by(|a, b| a.name().cmp(b.name()));
+    Ok(packages)
+}
+
+/// A local pacman repository of converted packages
+#[derive(Debug, Clone)]
+pub struct LocalRepo {
+    /// Repository directory
+    path: PathBuf,
+    /// Repository name as used in pacman.conf
+    name: String,
+}
+
+impl LocalRepo {
+    /// Create a handle for a repository directory
+    pub fn new(path: impl Into<PathBuf>, name: impl Into<String>) -> Result<Self> {
+        let name = name.into();
+        if name.is_empty()
+            || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(RexebError::Repository(format!("Invalid repository name '{}'", name)));
+        }
+
+        Ok(Self { path: path.into(), name })
+    }
+
+    /// Create a handle for the repository configured in `[repo]`
+    pub fn from_config(config: &Config) -> Result<Self> {
+        Self::new(config.repo_dir(), config.repo.name.clone())
+    }
+
+    /// Repository directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Repository name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Path of the compressed database
+    pub fn db_path(&self) -> PathBuf {
+        self.path.join(format!("{}.db.tar.gz", self.name))
+    }
+
+    /// Read all entries of the repository database
+    pub fn entries(&self) -> Result<Vec<Desc>> {
+        let db_path = self.db_path();
+        if !db_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut archive = tar::Archive::new(GzDecoder::new(File::open(&db_path)?));
+        let mut entries = Vec::new();
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if entry.path()?.file_name().is_some_and(|n| n == "desc") {
+                let mut content = String::new();
+                entry.read_to_string(&mut content)?;
+                entries.push(Desc::parse(&content));
+            }
+        }
+
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+        Ok(entries)
+    }
+
+    /// Add a package to the repository
+    ///
+    /// The package file is moved (or copied) into the repository directory
+    /// and replaces any older version of the same package.
+    pub fn add(&self, package: &Path, move_file: bool) -> Result<Desc> {
+        if !package.is_file() {
+            return Err(RexebError::file_not_found(package));
+        }
+
+        fs::create_dir_all(&self.path)?;
+        let file_name = package
+            .file_name()
+            .ok_or_else(|| RexebError::Repository(format!("Invalid package path {}", package.display())))?;
+        let dest = self.path.join(file_name);
+
+        if package.canonicalize()? != dest.canonicalize().unwrap_or_default() {
+            if !move_file {
+                fs::copy(package, &dest)?;
+            } else if fs::rename(package, &dest).is_err() {
+                // Different filesystem
+                fs::copy(package, &dest)?;
+                fs::remove_file(package)?;
+            }
+        }
+
+        let desc = Desc::from_package(&dest)?;
+        let mut entries = self.entries()?;
+
+        for old in entries.iter().filter(|e| e.name() == desc.name()) {
+            if let Some(old_file) = old.filename().filter(|f| *f != desc.filename().unwrap_or_default()) {
+                let _ = fs::remove_file(self.path.join(old_file));
+            }
+        }
+        entries.retain(|e| e.name() != desc.name());
+        entries.push(desc.clone());
+
+        self.write_db(&entries)?;
+        Ok(desc)
+    }
+
+    /// Remove a package and its file from the repository
+    ///
+    /// Returns the removed entry, or `None` if the package was not present.
+    pub fn remove(&self, name: &str) -> Result<Option<Desc>> {
+        let mut entries = self.entries()?;
+        let Some(pos) = entries.iter().position(|e| e.name() == name) else {
+            return Ok(None);
+        };
+
+        let removed = entries.remove(pos);
+        if let Some(file) = removed.filename() {
+            let _ = fs::remove_file(self.path.join(file));
+        }
+
+        self.write_db(&entries)?;
+        Ok(Some(removed))
+    }
+
+    /// pacman.conf section enabling this repository
+    pub fn pacman_conf(&self) -> String {
+        let path = self.path.canonicalize().unwrap_or_else(|_| self.path.clone());
+        format!(
+            "[{}]\nSigLevel = Optional TrustAll\nServer = file://{}\n",
+            self.name,
+            path.display()
+        )
+    }
+
+    /// Full pacman configuration layering this repository on top of `base`
+    ///
+    /// Used for throwaway installs: the system configuration is included
+    /// unchanged and the repository is appended as an extra section.
+    pub fn overlay_pacman_conf(&self, base: &Path) -> String {
+        format!("[options]\nInclude = {}\n\n{}", base.display(), self.pacman_conf())
+    }
+
+    /// Write the database and the `<name>.db` symlink
+    fn write_db(&self, entries: &[Desc]) -> Result<()> {
+        let db_path = self.db_path();
+        let tmp_path = db_path.with_extension("gz.tmp");
+        let mtime = chrono::Utc::now().timestamp() as u64;
+
+        {
+            let encoder = GzEncoder::new(File::create(&tmp_path)?, flate2::Compression::default());
+            let mut tar = tar::Builder::new(encoder);
+
+            for desc in entries {
+                let dir = format!("{}-{}/", desc.name(), desc.version());
+
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_mode(0o755);
+                header.set_size(0);
+                header.set_mtime(mtime);
+                header.set_cksum();
+                tar.append_data(&mut header, &dir, std::io::empty())?;
+
+                let content = desc.render();
+                let mut header = tar::Header::new_gnu();
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_mode(0o644);
+                header.set_size(content.len() as u64);
+                header.set_mtime(mtime);
+                header.set_cksum();
+                tar.append_data(&mut header, format!("{}desc", dir), content.as_bytes())?;
+            }
+
+            tar.into_inner()?.finish()?;
+        }
+
+        fs::rename(&tmp_path, &db_path)?;
+
+        #[cfg(unix)]
+        {
+            let link = self.path.join(format!("{}.db", self.name));
+            let _ = fs::remove_file(&link);
+            std::os::unix::fs::symlink(db_path.file_name().unwrap_or_default(), &link)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn make_package(dir: &Path, name: &str, version: &str) -> PathBuf {
+        let path = dir.join(format!("{}-{}-x86_64.pkg.tar.gz", name, version));
+        let pkginfo = format!("pkgname = {}\npkgver = {}\narch = x86_64\nsize = 100\n", name, version);
+
+        let mut tar = tar::Builder::new(GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::default()));
+        let mut header = tar::Header::new_gnu();
+        header.set_size(pkginfo.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, ".PKGINFO", pkginfo.as_bytes()).unwrap();
+        tar.into_inner().unwrap().finish().unwrap().flush().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_add_replace_remove() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo = LocalRepo::new(temp_dir.path().join("repo"), "rexeb-local").unwrap();
+
+        let v1 = make_package(temp_dir.path(), "foo", "1.0-1");
+        repo.add(&v1, true).unwrap();
+        assert!(!v1.exists());
+        assert!(repo.path().join("rexeb-local.db").exists());
+
+        let v2 = make_package(temp_dir.path(), "foo", "2.0-1");
+        repo.add(&v2, false).unwrap();
+        assert!(v2.exists());
+        assert!(!repo.path().join("foo-1.0-1-x86_64.pkg.tar.gz").exists());
+
+        let entries = repo.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].version(), "2.0-1");
+        assert_eq!(entries[0].get("CSIZE"), Some(fs::metadata(&v2).unwrap().len().to_string().as_str()));
+
+        assert!(repo.remove("foo").unwrap().is_some());
+        assert!(repo.entries().unwrap().is_empty());
+        assert!(repo.remove("foo").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_converted_packages() {
+        let temp_dir = TempDir::new().unwrap();
+        for (dir, desc) in [
+            ("foo-1.0-1", "%NAME%\nfoo\n\n%VERSION%\n1.0-1\n\n%XDATA%\npkgtype=pkg\nrexeb_version=0.1.0\nrexeb_source_sha256=ab12\n\n"),
+            ("glibc-2.40-1", "%NAME%\nglibc\n\n%VERSION%\n2.40-1\n\n%XDATA%\npkgtype=pkg\n\n"),
+        ] {
+            fs::create_dir(temp_dir.path().join(dir)).unwrap();
+            fs::write(temp_dir.path().join(dir).join("desc"), desc).unwrap();
+        }
+        fs::write(temp_dir.path().join("ALPM_DB_VERSION"), "9\n").unwrap();
+
+        let packages = converted_packages(temp_dir.path()).unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].name(), "foo");
+        assert_eq!(packages[0].xdata("rexeb_source_sha256"), Some("ab12"));
+        assert_eq!(packages[0].xdata("rexeb_source"), None);
+    }
+
+    #[test]
+    fn test_invalid_name_and_conf() {
+        assert!(LocalRepo::new("/tmp/x", "bad name").is_err());
+
+        let repo = LocalRepo::new("/srv/repo", "converted").unwrap();
+        assert_eq!(
+            repo.pacman_conf(),
+            "[converted]\nSigLevel = Optional TrustAll\nServer = file:///srv/repo\n"
+        );
+        assert!(repo
+            .overlay_pacman_conf(Path::new("/etc/pacman.conf"))
+            .starts_with("[options]\nInclude = /etc/pacman.conf\n\n[converted]\n"));
+    }
+}
@@ -2,46 +2,71 @@
 
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
+use crate::config::NetworkConfig;
 use crate::error::{RexebError, Result};
 
-/// AUR RPC endpoint
-const AUR_RPC_URL: &str = "https://aur.archlinux.org/rpc/v5";
+/// RPC interface version spoken by this client
+const RPC_VERSION: &str = "v5";
+
+/// Most names sent in one `/info` request, keeping the URL within server limits
+pub const INFO_BATCH_SIZE: usize = 100;
 
 /// AUR package info
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AurPackage {
+    /// Package name
     #[serde(rename = "Name")]
     pub name: String,
+    /// Package version
     #[serde(rename = "Version")]
     pub version: String,
+    /// Package description
     #[serde(rename = "Description")]
     pub description: Option<String>,
+    /// Upstream URL
     #[serde(rename = "URL")]
     pub url: Option<String>,
+    /// Package base name
     #[serde(rename = "PackageBase")]
     pub package_base: String,
+    /// Number of votes
     #[serde(rename = "NumVotes")]
     pub num_votes: u32,
+    /// Popularity score
     #[serde(rename = "Popularity")]
     pub popularity: f64,
+    /// Timestamp when flagged out of date
     #[serde(rename = "OutOfDate")]
     pub out_of_date: Option<i64>,
+    /// Maintainer username
     #[serde(rename = "Maintainer")]
     pub maintainer: Option<String>,
+    /// Timestamp of first submission
     #[serde(rename = "FirstSubmitted")]
     pub first_submitted: i64,
+    /// Timestamp of last modification
     #[serde(rename = "LastModified")]
     pub last_modified: i64,
+    /// Provided names
     #[serde(rename = "Provides")]
     pub provides: Option<Vec<String>>,
+    /// Replaced packages
     #[serde(rename = "Replaces")]
     pub replaces: Option<Vec<String>>,
+    /// Conflicting packages
     #[serde(rename = "Conflicts")]
     pub conflicts: Option<Vec<String>>,
+    /// Runtime dependencies
+    #[serde(rename = "Depends", default)]
+    pub depends: Option<Vec<String>>,
+    /// Build-time dependencies
+    #[serde(rename = "MakeDepends", default)]
+    pub make_depends: Option<Vec<String>>,
 }
 
 /// AUR RPC response
 #[derive(Debug, Deserialize)]
+#[allow(dead_code)]
 struct AurResponse {
     #[serde(rename = "resultcount")]
     result_count: usize,
@@ -57,18 +82,24 @@ struct AurResponse {
 pub struct AurClient {
     client: Client,
     base_url: String,
+    offline: bool,
 }
 
 impl AurClient {
-    /// Create a new AUR client
-    pub fn new() -> Self {
-        Self {
-            client: Client::builder()
-                .user_agent(format!("{}/{}", crate::NAME, crate::VERSION))
-                .build()
-                .unwrap_or_default(),
-            base_url: AUR_RPC_URL.to_string(),
-        }
+    /// Create an AUR client using the configured endpoint, timeout and proxy
+    ///
+    /// In offline mode every request fails without touching the network.
+    pub fn new(network: &NetworkConfig) -> Result<Self> {
+        Ok(Self {
+            client: network.http_client()?,
+            base_url: rpc_url(&network.aur_url),
+            offline: network.offline,
+        })
+    }
+
+    /// Whether requests are disabled by offline mode
+    pub fn is_offline(&self) -> bool {
+        self.offline
     }
 
     /// Search for packages by name (keyword search)
@@ -78,28 +109,42 @@ impl AurClient {
     }
 
     /// Get info for specific packages
+    ///
+    /// Names are sent as multiple `arg[]` parameters of one request
+    /// (split into batches of [`INFO_BATCH_SIZE`]).
     pub async fn info(&self, names: &[&str]) -> Result<Vec<AurPackage>> {
-        if names.is_empty() {
-            return Ok(Vec::new());
-        }
+        let mut packages = Vec::new();
 
-        let mut url = format!("{}/info", self.base_url);
-        
-        // Build query string manually to handle multiple 'arg[]' parameters
-        let params: Vec<String> = names.iter()
-            .map(|n| format!("arg[]={}", n))
-            .collect();
-        
-        if !params.is_empty() {
-            url.push('?');
-            url.push_str(&params.join("&"));
+        for batch in names.chunks(INFO_BATCH_SIZE) {
+            let url = self.info_url(batch)?;
+            packages.extend(self.make_request(url.as_str()).await?);
         }
 
-        self.make_request(&url).await
+        Ok(packages)
+    }
+
+    /// Git URL of a package base's PKGBUILD repository
+    pub fn clone_url(&self, package_base: &str) -> String {
+        let site = self.base_url.trim_end_matches(RPC_VERSION).trim_end_matches('/');
+        let site = site.strip_suffix("/rpc").unwrap_or(site);
+        format!("{}/{}.git", site, package_base)
+    }
+
+    /// `/info` URL querying the given names (escaped, `libstdc++6` has a `+`)
+    fn info_url(&self, names: &[&str]) -> Result<reqwest::Url> {
+        reqwest::Url::parse_with_params(
+            &format!("{}/info", self.base_url),
+            names.iter().map(|name| ("arg[]", *name)),
+        )
+        .map_err(|e| RexebError::Network(e.to_string()))
     }
 
     /// Helper to make requests and parse response
     async fn make_request(&self, url: &str) -> Result<Vec<AurPackage>> {
+        if self.offline {
+            return Err(RexebError::Network("offline mode is enabled".into()));
+        }
+
         let resp = self.client.get(url)
             .send()
             .await
@@ -131,7 +176,7 @@ impl AurClient {
         // This filtering happens client-side since RPC search is broad
         results.retain(|pkg| {
             pkg.name == capability || 
-            pkg.provides.as_ref().map_or(false, |p| p.iter().any(|prov| prov == capability))
+            pkg.provides.as_ref().is_some_and(|p| p.iter().any(|prov| prov == capability))
         });
         
         // Sort by popularity
@@ -143,7 +188,22 @@ impl AurClient {
 
 impl Default for AurClient {
     fn default() -> Self {
-        Self::new()
+        let network = NetworkConfig::default();
+        Self {
+            client: network.http_client().unwrap_or_default(),
+            base_url: rpc_url(&network.aur_url),
+            offline: network.offline,
+        }
+    }
+}
+
+/// Versioned RPC endpoint below the configured AUR RPC URL
+fn rpc_url(aur_url: &str) -> String {
+    let url = aur_url.trim_end_matches('/');
+    if url.ends_with(RPC_VERSION) {
+        url.to_string()
+    } else {
+        format!("{}/{}", url, RPC_VERSION)
     }
 }
 
@@ -153,7 +213,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_aur_search() {
-        let client = AurClient::new();
+        let client = AurClient::default();
         // Search for a known package (e.g., yay)
         let results = client.search("yay").await;
         
@@ -164,4 +224,28 @@ mod tests {
             assert!(pkgs.iter().any(|p| p.name == "yay"));
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_info_url() {
+        let url = AurClient::default().info_url(&["yay", "libstdc++6"]).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://aur.archlinux.org/rpc/v5/info?arg%5B%5D=yay&arg%5B%5D=libstdc%2B%2B6"
+        );
+        assert_eq!(AurClient::default().clone_url("yay-bin"), "https://aur.archlinux.org/yay-bin.git");
+    }
+
+    #[tokio::test]
+    async fn test_offline_skips_requests() {
+        let network = NetworkConfig {
+            aur_url: "http://aur.invalid/rpc/v5/".to_string(),
+            offline: true,
+            ..NetworkConfig::default()
+        };
+        let client = AurClient::new(&network).unwrap();
+        assert_eq!(client.base_url, "http://aur.invalid/rpc/v5");
+
+        let err = client.info(&["yay"]).await.unwrap_err();
+        assert!(err.to_string().contains("offline"));
+    }
+}
@@ -1,10 +1,37 @@
 //! Package database for Debian to Arch package mappings
 
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use serde::{Deserialize, Serialize};
 
+use crate::config::NetworkConfig;
 use crate::error::{RexebError, Result};
+use crate::models::VersionTransform;
+
+use super::pacman::PacmanDb;
+use super::user_mappings::UserMappings;
+
+/// Age after which downloaded sync databases are fetched again
+const SYNC_DB_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// Age after which the remote mapping dataset is fetched again
+const REMOTE_MAPPINGS_MAX_AGE: Duration = Duration::from_secs(24 * 3600);
+
+/// Downloaded mapping dataset, relative to the database directory
+const REMOTE_MAPPINGS_FILE: &str = "remote_mappings.json";
+
+/// Names no source could map, relative to the database directory
+const MISSES_FILE: &str = "lookup_misses.json";
+
+/// Age after which a failed lookup is tried against the AUR again
+const MISS_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Arch provides standing for a Debian virtual package of another name
+const VIRTUAL_ALIASES: &[(&str, &str)] = &[
+    ("mail-transport-agent", "smtp-server"),
+    ("x-terminal-emulator", "terminal-emulator"),
+];
 
 /// Package database containing mappings and package info
 pub struct PackageDatabase {
@@ -16,6 +43,14 @@ pub struct PackageDatabase {
     arch_packages: HashMap<String, ArchPackageInfo>,
     /// AUR package cache
     aur_packages: HashMap<String, AurPackageInfo>,
+    /// Installed packages (name -> version)
+    installed: HashMap<String, String>,
+    /// Debian packages the user never wants as dependencies
+    dropped: HashSet<String>,
+    /// Mappings read from the user's mapping files (not saved to `mappings.json`)
+    file_mappings: HashSet<String>,
+    /// Debian names the AUR recently had nothing for
+    misses: HashSet<String>,
     /// Database directory
     db_dir: PathBuf,
 }
@@ -31,10 +66,13 @@ pub struct PackageMapping {
     pub confidence: f32,
     /// Source of the mapping
     pub source: MappingSource,
+    /// Rewrites turning Debian versions into Arch versions
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub version_transforms: Vec<VersionTransform>,
 }
 
 /// Source of a package mapping
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
 pub enum MappingSource {
     /// Built-in/hardcoded mapping
     Builtin,
@@ -46,35 +84,75 @@ pub enum MappingSource {
     User,
     /// Automatically detected
     Auto,
+    /// From the remote mapping dataset
+    Remote,
+}
+
+impl MappingSource {
+    /// Short lowercase name, as accepted on the command line
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Builtin => "builtin",
+            Self::ArchRepo => "arch-repo",
+            Self::Aur => "aur",
+            Self::User => "user",
+            Self::Auto => "auto",
+            Self::Remote => "remote",
+        }
+    }
 }
 
 /// Info about an Arch repository package
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArchPackageInfo {
+    /// Package name
     pub name: String,
+    /// Package version
     pub version: String,
+    /// Package description
     pub description: String,
+    /// Provided names
     pub provides: Vec<String>,
+    /// Replaced packages
     pub replaces: Vec<String>,
+    /// Shared libraries provided, by soname
+    #[serde(default)]
+    pub sonames: Vec<String>,
+    /// Dependencies, without versions
+    #[serde(default)]
+    pub depends: Vec<String>,
+    /// Sync repository (`None` for packages only installed locally)
+    #[serde(default)]
+    pub repo: Option<String>,
 }
 
 /// Info about an AUR package
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AurPackageInfo {
+    /// Package name
     pub name: String,
+    /// Package version
     pub version: String,
+    /// Package description
     pub description: String,
+    /// Number of votes
     pub votes: u32,
+    /// Popularity score
     pub popularity: f64,
+    /// Timestamp when flagged out of date
     pub out_of_date: Option<i64>,
 }
 
 /// Search result
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResult {
+    /// Package name
     pub name: String,
+    /// Package description
     pub description: String,
+    /// Package version
     pub version: String,
+    /// Relevance score
     pub score: f32,
 }
 
@@ -89,6 +167,10 @@ impl PackageDatabase {
             virtual_packages: HashMap::new(),
             arch_packages: HashMap::new(),
             aur_packages: HashMap::new(),
+            installed: HashMap::new(),
+            dropped: HashSet::new(),
+            file_mappings: HashSet::new(),
+            misses: HashSet::new(),
             db_dir,
         };
 
@@ -98,11 +180,20 @@ impl PackageDatabase {
         // Try to load cached databases
         db.load_cached_data()?;
 
+        // The user's own mappings override everything else
+        db.merge_user_mappings(UserMappings::system()?);
+
+        // The system's pacman databases are more current than any cache
+        match PacmanDb::system() {
+            Ok(pacman) => db.merge_pacman_db(&pacman),
+            Err(e) => tracing::warn!("Could not read pacman databases: {}", e),
+        }
+
         Ok(db)
     }
 
     /// Get the database directory
-    fn get_db_dir() -> Result<PathBuf> {
+    pub fn get_db_dir() -> Result<PathBuf> {
         let dir = dirs::data_dir()
             .ok_or_else(|| RexebError::Config("Could not find data directory".into()))?
             .join("rexeb")
@@ -692,10 +783,33 @@ impl PackageDatabase {
                     arch_name: arch.to_string(),
                     confidence,
                     source: MappingSource::Builtin,
+                    version_transforms: Vec::new(),
                 },
             );
         }
 
+        // Version schemes that differ between the mapped packages
+        let java_version = VersionTransform::Replace {
+            // Debian's 1.8/1.11 are Java 8/11, as provided by Arch's JREs
+            pattern: r"^1\.(\d+).*$".to_string(),
+            replacement: "$1".to_string(),
+        };
+        let version_rules = [
+            ("default-jre", java_version.clone()),
+            ("default-jre-headless", java_version.clone()),
+            ("default-jdk", java_version.clone()),
+            ("default-jdk-headless", java_version),
+            // MySQL and MariaDB versions are unrelated
+            ("libmysqlclient21", VersionTransform::Drop),
+            ("default-mysql-client", VersionTransform::Drop),
+        ];
+
+        for (debian, transform) in version_rules {
+            if let Some(mapping) = self.mappings.get_mut(debian) {
+                mapping.version_transforms.push(transform);
+            }
+        }
+
         // Virtual packages
         let virtuals = [
             ("debconf", vec!["dialog", "whiptail"]),
@@ -713,6 +827,7 @@ impl PackageDatabase {
             ("java17-runtime", vec!["jre17-openjdk"]),
             ("java21-runtime", vec!["jre21-openjdk"]),
             ("www-browser", vec!["firefox", "chromium", "vivaldi"]),
+            ("x-www-browser", vec!["firefox", "chromium", "vivaldi"]),
             ("x-terminal-emulator", vec!["alacritty", "kitty", "gnome-terminal", "konsole"]),
             ("editor", vec!["vim", "nano", "emacs"]),
             ("mail-transport-agent", vec!["postfix", "exim"]),
@@ -728,6 +843,13 @@ impl PackageDatabase {
 
     /// Load cached database files
     fn load_cached_data(&mut self) -> Result<()> {
+        // Load the remote dataset, which refines the built-in mappings
+        let remote_path = self.db_dir.join(REMOTE_MAPPINGS_FILE);
+        if remote_path.exists() {
+            let content = std::fs::read_to_string(&remote_path)?;
+            self.merge_remote_mappings(serde_json::from_str(&content)?);
+        }
+
         // Load custom mappings
         let mappings_path = self.db_dir.join("mappings.json");
         if mappings_path.exists() {
@@ -750,17 +872,169 @@ impl PackageDatabase {
             self.aur_packages = serde_json::from_str(&content)?;
         }
 
+        self.misses = read_misses(&self.db_dir.join(MISSES_FILE), unix_now())?.into_keys().collect();
+
+        Ok(())
+    }
+
+    /// Add the packages of pacman's sync and local databases
+    ///
+    /// Sync packages replace cached entries of the same name; installed
+    /// packages missing from the repositories (AUR, local builds) are added
+    /// as well so they can satisfy dependencies.
+    pub fn merge_pacman_db(&mut self, pacman: &PacmanDb) {
+        for package in pacman.sync_packages() {
+            self.arch_packages.insert(package.name.clone(), package.to_info());
+        }
+        for package in pacman.local_packages() {
+            self.arch_packages
+                .entry(package.name.clone())
+                .or_insert_with(|| package.to_info());
+            self.installed.insert(package.name.clone(), package.version.clone());
+        }
+    }
+
+    /// Add mappings and drops from the user's mapping files
+    pub fn merge_user_mappings(&mut self, user: UserMappings) {
+        for name in &user.dropped {
+            self.mappings.remove(name);
+        }
+        self.file_mappings.extend(user.mappings.keys().cloned());
+        self.mappings.extend(user.mappings);
+        self.dropped.extend(user.dropped);
+    }
+
+    /// Whether the user asked for a Debian package never to be a dependency
+    pub fn is_dropped(&self, debian_name: &str) -> bool {
+        self.dropped.contains(debian_name)
+    }
+
+    /// Whether the AUR had nothing for a Debian name within the last week
+    pub fn is_known_miss(&self, debian_name: &str) -> bool {
+        self.misses.contains(debian_name)
+    }
+
+    /// Remember Debian names that no source could map
+    ///
+    /// Later conversions skip the AUR for them until the entries expire.
+    pub fn record_misses(names: &[String]) -> Result<()> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        Self::record_misses_into(&Self::get_db_dir()?.join(MISSES_FILE), names, unix_now())
+    }
+
+    /// Merge misses into a misses file, dropping expired entries
+    fn record_misses_into(path: &Path, names: &[String], now: u64) -> Result<()> {
+        static MISSES_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        let _guard = MISSES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut misses = read_misses(path, now)?;
+        misses.extend(names.iter().map(|name| (name.clone(), now)));
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&misses)?)?;
         Ok(())
     }
 
+    /// Version of an installed package
+    pub fn installed_version(&self, name: &str) -> Option<&str> {
+        self.installed.get(name).map(String::as_str)
+    }
+
     /// Save database to disk
+    ///
+    /// Only mappings added at runtime are written; built-in ones and those
+    /// from the user's mapping files are loaded from their source each time.
     pub fn save(&self) -> Result<()> {
-        let mappings_path = self.db_dir.join("mappings.json");
-        let content = serde_json::to_string_pretty(&self.mappings)?;
-        std::fs::write(mappings_path, content)?;
+        self.export_mappings(&self.db_dir.join("mappings.json"))
+    }
+
+    /// Write the mappings [`save`](Self::save) persists to a JSON file
+    pub fn export_mappings(&self, path: &Path) -> Result<()> {
+        let saved: HashMap<&String, &PackageMapping> = self
+            .mappings
+            .iter()
+            .filter(|(name, mapping)| {
+                !matches!(mapping.source, MappingSource::Builtin | MappingSource::Remote)
+                    && !self.file_mappings.contains(*name)
+            })
+            .collect();
+        std::fs::write(path, serde_json::to_string_pretty(&saved)?)?;
         Ok(())
     }
 
+    /// Add the mappings of a JSON file written by [`export_mappings`](Self::export_mappings)
+    ///
+    /// Returns the number of mappings imported.
+    pub fn import_mappings(&mut self, path: &Path) -> Result<usize> {
+        let imported: HashMap<String, PackageMapping> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        let count = imported.len();
+        for (name, mapping) in imported {
+            self.file_mappings.remove(&name);
+            self.mappings.insert(name, mapping);
+        }
+        Ok(count)
+    }
+
+    /// All known mappings
+    pub fn mappings(&self) -> impl Iterator<Item = &PackageMapping> {
+        self.mappings.values()
+    }
+
+    /// Remove a mapping, returning it
+    ///
+    /// Built-in mappings come back on the next load.
+    pub fn remove_mapping(&mut self, debian: &str) -> Option<PackageMapping> {
+        self.file_mappings.remove(debian);
+        self.mappings.remove(debian)
+    }
+
+    /// Remember resolutions that worked in `mappings.json`
+    ///
+    /// Stored as [`MappingSource::Auto`] so later conversions resolve the
+    /// same way without guessing or network access. Mappings the user made
+    /// are kept. Returns the number of mappings stored.
+    pub fn learn(mappings: &[PackageMapping]) -> Result<usize> {
+        if mappings.is_empty() {
+            return Ok(0);
+        }
+        Self::learn_into(&Self::get_db_dir()?.join("mappings.json"), mappings)
+    }
+
+    /// [`learn`](Self::learn) into a specific mappings file
+    fn learn_into(path: &Path, mappings: &[PackageMapping]) -> Result<usize> {
+        // Packages converted in parallel finish at the same time
+        static LEARN_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+        let _guard = LEARN_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut saved: HashMap<String, PackageMapping> = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            HashMap::new()
+        };
+
+        let mut learned = 0;
+        for mapping in mappings {
+            if saved.get(&mapping.debian_name).is_some_and(|m| m.source == MappingSource::User) {
+                continue;
+            }
+            saved.insert(
+                mapping.debian_name.clone(),
+                PackageMapping { source: MappingSource::Auto, ..mapping.clone() },
+            );
+            learned += 1;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&saved)?)?;
+        Ok(learned)
+    }
+
     /// Look up a package mapping
     pub fn lookup(&self, debian_name: &str) -> Result<Option<(String, f32)>> {
         // Check direct mapping first
@@ -773,14 +1047,71 @@ impl PackageDatabase {
             return Ok(Some((debian_name.to_string(), 1.0)));
         }
 
-        // Check provides
-        for (name, info) in &self.arch_packages {
-            if info.provides.contains(&debian_name.to_string()) {
-                return Ok(Some((name.clone(), 0.9)));
-            }
+        // Check provides, preferring installed providers, then by name
+        let provider = self
+            .arch_packages
+            .iter()
+            .filter(|(_, info)| info.provides.iter().any(|p| p == debian_name))
+            .map(|(name, _)| name)
+            .min_by_key(|name| (!self.installed.contains_key(*name), name.as_str()));
+
+        Ok(provider.map(|name| (name.clone(), 0.9)))
+    }
+
+    /// Whether an Arch package or provide of this name exists
+    ///
+    /// `None` without synced repository data to check against.
+    pub fn package_exists(&self, name: &str) -> Option<bool> {
+        let known = self.arch_packages.contains_key(name)
+            || self.installed.contains_key(name)
+            || self.aur_packages.contains_key(name)
+            || self.arch_packages.values().any(|info| info.provides.iter().any(|p| p == name));
+        match known {
+            true => Some(true),
+            false if self.arch_packages.is_empty() => None,
+            false => Some(false),
         }
+    }
 
-        Ok(None)
+    /// Cached Arch package by name
+    pub fn arch_package(&self, name: &str) -> Option<&ArchPackageInfo> {
+        self.arch_packages.get(name)
+    }
+
+    /// Package providing a name, by name first, installed ones preferred
+    pub fn provider(&self, name: &str) -> Option<&ArchPackageInfo> {
+        self.arch_packages.get(name).or_else(|| {
+            self.arch_packages
+                .values()
+                .filter(|info| info.provides.iter().any(|p| p == name))
+                .min_by_key(|info| (!self.installed.contains_key(&info.name), info.name.as_str()))
+        })
+    }
+
+    /// Package providing a shared library, preferring installed packages
+    pub fn soname_provider(&self, soname: &str) -> Option<&str> {
+        self.arch_packages
+            .iter()
+            .filter(|(_, info)| info.sonames.iter().any(|s| s == soname))
+            .map(|(name, _)| name.as_str())
+            .min_by_key(|name| (!self.installed.contains_key(*name), *name))
+    }
+
+    /// Version rewrites of a direct mapping
+    pub fn version_transforms(&self, debian_name: &str) -> &[VersionTransform] {
+        self.mappings
+            .get(debian_name)
+            .map(|m| m.version_transforms.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Description of a cached Arch or AUR package
+    pub fn description(&self, name: &str) -> Option<&str> {
+        self.arch_packages
+            .get(name)
+            .map(|p| p.description.as_str())
+            .or_else(|| self.aur_packages.get(name).map(|p| p.description.as_str()))
+            .filter(|d| !d.is_empty())
     }
 
     /// Check if a package is virtual
@@ -793,16 +1124,120 @@ impl PackageDatabase {
         self.virtual_packages.get(name)
     }
 
+    /// Concrete package for a Debian virtual package, with a confidence
+    ///
+    /// Candidates are the known providers followed by Arch packages whose
+    /// provides name the virtual package. An installed candidate wins, then
+    /// one from the repositories; without any repository data the first
+    /// known provider is used.
+    pub fn virtual_provider(&self, name: &str) -> Option<(String, f32)> {
+        let provide_names: Vec<&str> = std::iter::once(name)
+            .chain(VIRTUAL_ALIASES.iter().filter(|(debian, _)| *debian == name).map(|(_, arch)| *arch))
+            .collect();
+
+        let mut candidates: Vec<&str> = self
+            .virtual_packages
+            .get(name)
+            .map(|providers| providers.iter().map(String::as_str).collect())
+            .unwrap_or_default();
+        let mut providers: Vec<&str> = self
+            .arch_packages
+            .values()
+            .filter(|info| info.provides.iter().any(|p| provide_names.contains(&p.as_str())))
+            .map(|info| info.name.as_str())
+            .collect();
+        providers.sort();
+        for provider in providers {
+            if !candidates.contains(&provider) {
+                candidates.push(provider);
+            }
+        }
+
+        if let Some(installed) = candidates.iter().find(|c| self.installed.contains_key(**c)) {
+            return Some((installed.to_string(), 0.9));
+        }
+        if let Some(available) = candidates.iter().find(|c| self.arch_packages.contains_key(**c)) {
+            return Some((available.to_string(), 0.85));
+        }
+        if self.arch_packages.is_empty() {
+            return candidates.first().map(|c| (c.to_string(), 0.7));
+        }
+        None
+    }
+
     /// Get all Arch package names for fuzzy matching
     pub fn get_arch_package_names(&self) -> Vec<&str> {
         self.arch_packages.keys().map(|s| s.as_str()).collect()
     }
 
     /// Update package mappings from online sources
-    pub async fn update_mappings(&self, _force: bool) -> Result<()> {
-        // TODO: Implement fetching from online sources
-        tracing::info!("Package mappings are up to date");
-        Ok(())
+    ///
+    /// Fetches the dataset at `network.mappings_url`, checks it against the
+    /// published `<url>.sha256` (and the `<url>.sig` signature when a keyring
+    /// is configured) and merges it. A dataset younger than a day is reused
+    /// unless `force` is set. Returns the number of mappings in the dataset.
+    pub async fn update_mappings(&mut self, network: &NetworkConfig, force: bool) -> Result<usize> {
+        let Some(ref url) = network.mappings_url else {
+            tracing::info!("No mapping dataset configured (network.mappings_url)");
+            return Ok(0);
+        };
+        if network.offline {
+            return Err(RexebError::Network("offline mode is enabled".into()));
+        }
+
+        let path = self.db_dir.join(REMOTE_MAPPINGS_FILE);
+        let fresh = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|m| m.elapsed().ok())
+            .is_some_and(|age| age < REMOTE_MAPPINGS_MAX_AGE);
+
+        let content = if fresh && !force {
+            std::fs::read(&path)?
+        } else {
+            let client = network.http_client()?;
+            tracing::info!("Downloading {}", url);
+            let content = client.get(url).send().await?.error_for_status()?.bytes().await?.to_vec();
+            let checksum = client
+                .get(format!("{}.sha256", url))
+                .send()
+                .await?
+                .error_for_status()?
+                .text()
+                .await?;
+            verify_sha256(&content, &checksum)?;
+
+            if let Some(ref keyring) = network.mappings_keyring {
+                let signature = client.get(format!("{}.sig", url)).send().await?.error_for_status()?.bytes().await?;
+                verify_signature(&content, &signature, keyring)?;
+            }
+
+            let tmp_path = path.with_extension("json.part");
+            std::fs::write(&tmp_path, &content)?;
+            std::fs::rename(&tmp_path, &path)?;
+            content
+        };
+
+        let remote: HashMap<String, PackageMapping> = serde_json::from_slice(&content)?;
+        let count = remote.len();
+        self.merge_remote_mappings(remote);
+        Ok(count)
+    }
+
+    /// Add mappings from the remote dataset
+    ///
+    /// They replace built-in mappings but never mappings the user made.
+    fn merge_remote_mappings(&mut self, remote: HashMap<String, PackageMapping>) {
+        for (name, mut mapping) in remote {
+            let replaceable = self
+                .mappings
+                .get(&name)
+                .is_none_or(|m| matches!(m.source, MappingSource::Builtin | MappingSource::Remote));
+            if replaceable {
+                mapping.source = MappingSource::Remote;
+                self.mappings.insert(name, mapping);
+            }
+        }
     }
 
     /// Update virtual packages database
@@ -812,6 +1247,59 @@ impl PackageDatabase {
         Ok(())
     }
 
+    /// Download the configured repositories' sync databases
+    ///
+    /// The databases are kept in `<db_dir>/sync` and indexed into the Arch
+    /// package cache. Databases younger than an hour are reused unless
+    /// `force` is set. Returns the number of indexed packages.
+    pub async fn update_arch_packages(&mut self, network: &NetworkConfig, force: bool) -> Result<usize> {
+        if network.offline {
+            return Err(RexebError::Network("offline mode is enabled".into()));
+        }
+
+        let sync_dir = self.db_dir.join("sync");
+        std::fs::create_dir_all(&sync_dir)?;
+        let client = network.http_client()?;
+
+        for repo in &network.sync_repos {
+            let path = sync_dir.join(format!("{}.db", repo));
+            let fresh = std::fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|m| m.elapsed().ok())
+                .is_some_and(|age| age < SYNC_DB_MAX_AGE);
+            if fresh && !force {
+                continue;
+            }
+
+            let url = sync_db_url(&network.mirror, repo, std::env::consts::ARCH);
+            tracing::info!("Downloading {}", url);
+            let response = client.get(&url).send().await?.error_for_status()?;
+            let content = response.bytes().await?;
+
+            let tmp_path = path.with_extension("db.part");
+            std::fs::write(&tmp_path, &content)?;
+            std::fs::rename(&tmp_path, &path)?;
+        }
+
+        let synced = self.index_sync_dbs(&self.db_dir.clone())?;
+        let arch_path = self.db_dir.join("arch_packages.json");
+        std::fs::write(arch_path, serde_json::to_string(&synced)?)?;
+        Ok(synced.len())
+    }
+
+    /// Index the sync databases in `<db_path>/sync` into the Arch package cache
+    ///
+    /// Returns the indexed packages.
+    fn index_sync_dbs(&mut self, db_path: &Path) -> Result<HashMap<String, ArchPackageInfo>> {
+        let synced: HashMap<_, _> = PacmanDb::load(db_path)?
+            .sync_packages()
+            .map(|p| (p.name.clone(), p.to_info()))
+            .collect();
+        self.arch_packages.extend(synced.clone());
+        Ok(synced)
+    }
+
     /// Update AUR cache
     pub async fn update_aur_cache(&self, _force: bool) -> Result<()> {
         // TODO: Implement AUR cache update
@@ -866,6 +1354,7 @@ impl PackageDatabase {
 
     /// Add a custom mapping
     pub fn add_mapping(&mut self, debian: &str, arch: &str, confidence: f32) {
+        self.file_mappings.remove(debian);
         self.mappings.insert(
             debian.to_string(),
             PackageMapping {
@@ -873,7 +1362,249 @@ impl PackageDatabase {
                 arch_name: arch.to_string(),
                 confidence,
                 source: MappingSource::User,
+                version_transforms: Vec::new(),
             },
         );
     }
 }
+
+/// Check data against a published checksum (`sha256sum` output or a bare hash)
+fn verify_sha256(data: &[u8], checksum: &str) -> Result<()> {
+    use sha2::{Digest, Sha256};
+
+    let expected = checksum.split_whitespace().next().unwrap_or_default().to_lowercase();
+    let actual = hex::encode(Sha256::digest(data));
+    if expected != actual {
+        return Err(RexebError::Validation(format!(
+            "mapping dataset checksum mismatch (expected {}, got {})",
+            expected, actual
+        )));
+    }
+    Ok(())
+}
+
+/// Check a detached signature with `gpgv` against the given keyring
+fn verify_signature(data: &[u8], signature: &[u8], keyring: &Path) -> Result<()> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let data_path = temp_dir.path().join("mappings.json");
+    let sig_path = temp_dir.path().join("mappings.json.sig");
+    std::fs::write(&data_path, data)?;
+    std::fs::write(&sig_path, signature)?;
+
+    let output = std::process::Command::new("gpgv")
+        .arg("--keyring")
+        .arg(keyring)
+        .arg(&sig_path)
+        .arg(&data_path)
+        .output()
+        .map_err(|e| RexebError::Validation(format!("could not run gpgv: {}", e)))?;
+    if !output.status.success() {
+        return Err(RexebError::Validation(format!(
+            "mapping dataset signature is invalid: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// URL of a repository's sync database on a mirror
+fn sync_db_url(mirror: &str, repo: &str, arch: &str) -> String {
+    format!(
+        "{}/{}.db",
+        mirror.replace("$repo", repo).replace("$arch", arch).trim_end_matches('/'),
+        repo
+    )
+}
+
+/// Seconds since the Unix epoch
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Unexpired entries of a misses file (name -> time recorded)
+fn read_misses(path: &Path, now: u64) -> Result<HashMap<String, u64>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let mut misses: HashMap<String, u64> = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    misses.retain(|_, recorded| now.saturating_sub(*recorded) < MISS_MAX_AGE.as_secs());
+    Ok(misses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+
+    #[test]
+    fn test_index_sync_dbs() {
+        assert_eq!(
+            sync_db_url("https://mirror.example/$repo/os/$arch/", "extra", "x86_64"),
+            "https://mirror.example/extra/os/x86_64/extra.db"
+        );
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("sync")).unwrap();
+        let file = File::create(temp_dir.path().join("sync/extra.db")).unwrap();
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+        let desc = "%NAME%\nrexeb-test-pkg\n\n%VERSION%\n1.0-1\n\n%PROVIDES%\nrexeb-test-virtual\nlibrexebtest-4.0.so=37-64\n\n";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(desc.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, "rexeb-test-pkg-1.0-1/desc", desc.as_bytes()).unwrap();
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let mut db = PackageDatabase::new().unwrap();
+        assert_eq!(db.index_sync_dbs(temp_dir.path()).unwrap().len(), 1);
+        assert_eq!(db.lookup("rexeb-test-pkg").unwrap(), Some(("rexeb-test-pkg".to_string(), 1.0)));
+        assert_eq!(db.lookup("rexeb-test-virtual").unwrap(), Some(("rexeb-test-pkg".to_string(), 0.9)));
+        assert_eq!(db.soname_provider("librexebtest-4.0.so.37"), Some("rexeb-test-pkg"));
+    }
+
+    #[test]
+    fn test_save_and_import_mappings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut db = PackageDatabase::new().unwrap();
+        db.db_dir = temp_dir.path().to_path_buf();
+
+        let mut user = UserMappings::default();
+        user.mappings.insert("rexeb-test-file".to_string(), PackageMapping {
+            debian_name: "rexeb-test-file".to_string(),
+            arch_name: "file".to_string(),
+            confidence: 1.0,
+            source: MappingSource::User,
+            version_transforms: Vec::new(),
+        });
+        db.merge_user_mappings(user);
+        db.add_mapping("rexeb-test-added", "added", 0.7);
+        db.save().unwrap();
+
+        let saved: HashMap<String, PackageMapping> =
+            serde_json::from_str(&fs::read_to_string(temp_dir.path().join("mappings.json")).unwrap()).unwrap();
+        assert_eq!(saved.keys().collect::<Vec<_>>(), ["rexeb-test-added"]);
+
+        assert!(db.remove_mapping("rexeb-test-added").is_some());
+        assert_eq!(db.lookup("rexeb-test-added").unwrap(), None);
+        assert_eq!(db.import_mappings(&temp_dir.path().join("mappings.json")).unwrap(), 1);
+        assert_eq!(db.lookup("rexeb-test-added").unwrap(), Some(("added".to_string(), 0.7)));
+    }
+
+    #[test]
+    fn test_remote_mappings() {
+        let data = b"{}";
+        let hash = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(data));
+        assert!(verify_sha256(data, &format!("{}  mappings.json\n", hash)).is_ok());
+        assert!(verify_sha256(b"tampered", &hash).is_err());
+
+        let mut db = PackageDatabase::new().unwrap();
+        db.add_mapping("rexeb-test-user", "mine", 1.0);
+        let remote = ["adduser", "rexeb-test-user", "rexeb-test-remote"]
+            .into_iter()
+            .map(|name| {
+                (name.to_string(), PackageMapping {
+                    debian_name: name.to_string(),
+                    arch_name: "community".to_string(),
+                    confidence: 0.95,
+                    source: MappingSource::User,
+                    version_transforms: Vec::new(),
+                })
+            })
+            .collect();
+        db.merge_remote_mappings(remote);
+
+        assert_eq!(db.lookup("adduser").unwrap(), Some(("community".to_string(), 0.95)));
+        assert_eq!(db.lookup("rexeb-test-user").unwrap(), Some(("mine".to_string(), 1.0)));
+        assert_eq!(db.mappings["rexeb-test-remote"].source, MappingSource::Remote);
+    }
+
+    #[test]
+    fn test_learn_mappings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("mappings.json");
+        let mapping = |debian: &str, arch: &str, source| PackageMapping {
+            debian_name: debian.to_string(),
+            arch_name: arch.to_string(),
+            confidence: 0.8,
+            source,
+            version_transforms: Vec::new(),
+        };
+        let mine = HashMap::from([("libfoo1".to_string(), mapping("libfoo1", "foo", MappingSource::User))]);
+        fs::write(&path, serde_json::to_string(&mine).unwrap()).unwrap();
+
+        let learned = [mapping("libfoo1", "foo-git", MappingSource::Auto), mapping("libbar2", "bar", MappingSource::Aur)];
+        assert_eq!(PackageDatabase::learn_into(&path, &learned).unwrap(), 1);
+
+        let saved: HashMap<String, PackageMapping> = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved["libfoo1"].arch_name, "foo");
+        assert_eq!(saved["libbar2"].source, MappingSource::Auto);
+    }
+
+    #[test]
+    fn test_virtual_provider() {
+        let mut db = PackageDatabase::new().unwrap();
+        db.arch_packages.clear();
+        db.installed.clear();
+        assert_eq!(db.virtual_provider("www-browser"), Some(("firefox".to_string(), 0.7)));
+
+        let package = |name: &str, provides: &[&str]| ArchPackageInfo {
+            name: name.to_string(),
+            version: "1.0-1".to_string(),
+            description: String::new(),
+            provides: provides.iter().map(|p| p.to_string()).collect(),
+            replaces: Vec::new(),
+            sonames: Vec::new(),
+            depends: Vec::new(),
+            repo: None,
+        };
+        for info in [package("chromium", &[]), package("postfix", &["smtp-server"]), package("opensmtpd", &["smtp-server"])] {
+            db.arch_packages.insert(info.name.clone(), info);
+        }
+        assert_eq!(db.virtual_provider("www-browser"), Some(("chromium".to_string(), 0.85)));
+        assert_eq!(db.virtual_provider("mail-transport-agent"), Some(("postfix".to_string(), 0.85)));
+
+        db.installed.insert("opensmtpd".to_string(), "7.5-1".to_string());
+        assert_eq!(db.virtual_provider("mail-transport-agent"), Some(("opensmtpd".to_string(), 0.9)));
+        assert_eq!(db.virtual_provider("editor"), None);
+    }
+
+    #[test]
+    fn test_package_exists() {
+        let mut db = PackageDatabase::new().unwrap();
+        db.arch_packages.clear();
+        db.installed.clear();
+        db.aur_packages.clear();
+        assert_eq!(db.package_exists("rexeb-test-renamed"), None);
+
+        let info = ArchPackageInfo {
+            name: "rexeb-test-new".to_string(),
+            version: "1.0-1".to_string(),
+            description: String::new(),
+            provides: vec!["rexeb-test-virtual".to_string()],
+            replaces: vec!["rexeb-test-renamed".to_string()],
+            sonames: Vec::new(),
+            depends: Vec::new(),
+            repo: None,
+        };
+        db.arch_packages.insert(info.name.clone(), info);
+        assert_eq!(db.package_exists("rexeb-test-new"), Some(true));
+        assert_eq!(db.package_exists("rexeb-test-virtual"), Some(true));
+        assert_eq!(db.package_exists("rexeb-test-renamed"), Some(false));
+    }
+
+    #[test]
+    fn test_record_misses() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(MISSES_FILE);
+        let week = MISS_MAX_AGE.as_secs();
+
+        PackageDatabase::record_misses_into(&path, &["rexeb-test-old".to_string()], 1000).unwrap();
+        PackageDatabase::record_misses_into(&path, &["rexeb-test-new".to_string()], 1000 + week / 2).unwrap();
+        assert_eq!(read_misses(&path, 1000 + week / 2).unwrap().len(), 2);
+
+        let misses = read_misses(&path, 1000 + week).unwrap();
+        assert_eq!(misses.into_keys().collect::<Vec<_>>(), ["rexeb-test-new"]);
+    }
+}
@@ -0,0 +1,90 @@
+//! Language ecosystem packages
+//!
+//! Debian and Arch both package Python, Perl, Ruby and Node.js modules
+//! under a prefix, but not the same one (`python3-foo` is `python-foo`,
+//! `libfoo-bar-perl` is `perl-foo-bar`, `node-foo` is `nodejs-foo`).
+//! Renaming only gives candidates: the resolver uses one once the
+//! repositories or the AUR confirm it exists.
+
+/// Language whose modules are packaged under a name prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ecosystem {
+    /// `python3-*`
+    Python,
+    /// `lib*-perl`
+    Perl,
+    /// `ruby-*`
+    Ruby,
+    /// `node-*`
+    Node,
+}
+
+impl Ecosystem {
+    /// Ecosystem and module name of a Debian package
+    pub fn detect(debian_name: &str) -> Option<(Self, &str)> {
+        let detected = if let Some(module) = debian_name.strip_prefix("python3-") {
+            (Self::Python, module)
+        } else if let Some(module) = debian_name.strip_prefix("lib").and_then(|n| n.strip_suffix("-perl")) {
+            (Self::Perl, module)
+        } else if let Some(module) = debian_name.strip_prefix("ruby-") {
+            (Self::Ruby, module)
+        } else if let Some(module) = debian_name.strip_prefix("node-") {
+            (Self::Node, module)
+        } else {
+            return None;
+        };
+        Some(detected).filter(|(_, module)| !module.is_empty())
+    }
+
+    /// Prefix of Arch packages in this ecosystem
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Python => "python-",
+            Self::Perl => "perl-",
+            Self::Ruby => "ruby-",
+            Self::Node => "nodejs-",
+        }
+    }
+
+    /// Arch names a module may be packaged as, most likely first
+    pub fn candidates(self, module: &str) -> Vec<String> {
+        let mut candidates = vec![format!("{}{}", self.prefix(), module)];
+        match self {
+            // Prebuilt wheels in the AUR
+            Self::Python => candidates.push(format!("python-{}-bin", module)),
+            // `libwww-perl` is `perl-libwww`
+            Self::Perl => candidates.push(format!("perl-lib{}", module)),
+            Self::Node => candidates.push(format!("node-{}", module)),
+            Self::Ruby => {}
+        }
+        candidates
+    }
+
+    /// Whether an AUR search result belongs to a module
+    ///
+    /// Accepts the plain name and suffixed variants (`-git`, `-bin`), but
+    /// not other modules sharing the prefix (`python-foobar` for `foo`).
+    pub fn is_variant(self, module: &str, name: &str) -> bool {
+        let base = format!("{}{}", self.prefix(), module);
+        name.strip_prefix(&base)
+            .is_some_and(|rest| rest.is_empty() || ["-git", "-bin", "-hg", "-svn"].contains(&rest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_ecosystem() {
+        assert_eq!(Ecosystem::detect("python3-requests"), Some((Ecosystem::Python, "requests")));
+        assert_eq!(Ecosystem::detect("libdbd-mysql-perl"), Some((Ecosystem::Perl, "dbd-mysql")));
+        assert_eq!(Ecosystem::detect("node-semver"), Some((Ecosystem::Node, "semver")));
+        assert_eq!(Ecosystem::detect("python3"), None);
+        assert_eq!(Ecosystem::detect("libperl5.36"), None);
+
+        assert_eq!(Ecosystem::Perl.candidates("www"), ["perl-www", "perl-libwww"]);
+        assert!(Ecosystem::Python.is_variant("foo", "python-foo-git"));
+        assert!(!Ecosystem::Python.is_variant("foo", "python-foobar"));
+    }
+}
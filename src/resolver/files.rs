@@ -0,0 +1,204 @@
+//! Library ownership from pacman's files databases
+//!
+//! Debian names shared library packages after the library's soname
+//! (`libssl3` ships `libssl.so.3`). Looking that soname up in the
+//! `<dbpath>/sync/*.files` databases (what `pacman -F` reads) finds the
+//! Arch package that ships it, which maps the long tail of library
+//! packages far better than name similarity.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::error::Result;
+use crate::repo::Desc;
+
+use super::pacman::{open_db_archive, sync_databases, PACMAN_DB_PATH};
+
+/// Directory whose libraries are indexed, relative to the root
+const LIBRARY_DIR: &str = "usr/lib/";
+
+/// Directory of the multilib (32-bit x86) libraries, relative to the root
+const LIB32_DIR: &str = "usr/lib32/";
+
+/// Suffixes Debian appends to library packages on ABI transitions
+const TRANSITION_SUFFIXES: &[&str] = &["t64", "v5", "c2a", "c2", "ldbl", "gf"];
+
+/// Package suffixes that never name a runtime library
+const NON_LIBRARY_SUFFIXES: &[&str] = &["-dev", "-dbg", "-doc", "-common", "-data", "-bin", "-tools", "-utils"];
+
+lazy_static::lazy_static! {
+    /// `libgtk-3-0` style names: soname base and version separated by a dash
+    static ref DASHED_RE: Regex = Regex::new(r"^(lib.+)-(\d[\d.]*)$").unwrap();
+    /// `libssl3` style names: version appended directly
+    static ref PLAIN_RE: Regex = Regex::new(r"^(lib.*[^\d.-])(\d[\d.]*)$").unwrap();
+    /// Digits following letters, where Debian drops a dash (`glib2.0` for `glib-2.0`)
+    static ref LETTER_DIGIT_RE: Regex = Regex::new(r"^(lib[a-z+]+)(\d[\d.]*)$").unwrap();
+}
+
+/// Index of the libraries shipped by repository packages
+#[derive(Debug, Clone, Default)]
+pub struct FilesDb {
+    /// Library file name (below /usr/lib) -> owning package
+    libraries: HashMap<String, String>,
+    /// Library file name (below /usr/lib32) -> owning package
+    lib32_libraries: HashMap<String, String>,
+}
+
+impl FilesDb {
+    /// Index the `.files` databases below `db_path`
+    ///
+    /// Missing databases (never synced with `pacman -Fy`) give an empty index.
+    pub fn load(db_path: &Path) -> Result<Self> {
+        let mut db = Self::default();
+
+        for path in sync_databases(db_path, "files") {
+            // Entries are <pkgdir>/desc and <pkgdir>/files
+            let mut names: HashMap<String, String> = HashMap::new();
+            let mut libraries: Vec<(String, String)> = Vec::new();
+            let mut lib32_libraries: Vec<(String, String)> = Vec::new();
+
+            let mut archive = open_db_archive(&path)?;
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let entry_path = entry.path()?.into_owned();
+                let Some(dir) = entry_path.parent().map(|p| p.to_string_lossy().into_owned()) else {
+                    continue;
+                };
+                let Some(file_name) = entry_path.file_name() else {
+                    continue;
+                };
+
+                let mut content = String::new();
+                if file_name == "desc" {
+                    entry.read_to_string(&mut content)?;
+                    names.insert(dir, Desc::parse(&content).name().to_string());
+                } else if file_name == "files" {
+                    entry.read_to_string(&mut content)?;
+                    for (prefix, found) in [(LIBRARY_DIR, &mut libraries), (LIB32_DIR, &mut lib32_libraries)] {
+                        found.extend(
+                            content
+                                .lines()
+                                .filter_map(|line| line.strip_prefix(prefix))
+                                .filter(|name| !name.contains('/') && name.contains(".so"))
+                                .map(|name| (name.to_string(), dir.clone())),
+                        );
+                    }
+                }
+            }
+
+            for (found, index) in [(libraries, &mut db.libraries), (lib32_libraries, &mut db.lib32_libraries)] {
+                for (library, dir) in found {
+                    if let Some(owner) = names.get(&dir) {
+                        index.entry(library).or_insert_with(|| owner.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(db)
+    }
+
+    /// Index the system's files databases
+    pub fn system() -> Result<Self> {
+        Self::load(Path::new(PACMAN_DB_PATH))
+    }
+
+    /// Package shipping `/usr/lib/<library>`
+    pub fn library_owner(&self, library: &str) -> Option<&str> {
+        self.libraries.get(library).map(String::as_str)
+    }
+
+    /// Package shipping `/usr/lib32/<library>`, from the multilib repository
+    pub fn lib32_owner(&self, library: &str) -> Option<&str> {
+        self.lib32_libraries.get(library).map(String::as_str)
+    }
+
+    /// Package shipping the library a Debian library package is named after
+    pub fn owner_for_debian_package(&self, debian_name: &str) -> Option<&str> {
+        debian_library_sonames(debian_name)
+            .iter()
+            .find_map(|soname| self.library_owner(soname))
+    }
+
+    /// Whether no libraries are indexed
+    pub fn is_empty(&self) -> bool {
+        self.libraries.is_empty()
+    }
+}
+
+/// Sonames a Debian library package name may stand for, most likely first
+///
+/// Follows Debian policy 8.1: the package is named after the soname with
+/// `.so.` removed and a dash added when the name ends in a digit.
+pub fn debian_library_sonames(debian_name: &str) -> Vec<String> {
+    let name = debian_name.split(':').next().unwrap_or(debian_name);
+    if !name.starts_with("lib") || NON_LIBRARY_SUFFIXES.iter().any(|s| name.ends_with(s)) {
+        return Vec::new();
+    }
+
+    let name = TRANSITION_SUFFIXES
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+        .unwrap_or(name);
+
+    let Some(captures) = DASHED_RE.captures(name).or_else(|| PLAIN_RE.captures(name)) else {
+        return Vec::new();
+    };
+    let (base, version) = (&captures[1], &captures[2]);
+
+    let mut sonames = vec![format!("{}.so.{}", base, version)];
+    if let Some(split) = LETTER_DIGIT_RE.captures(base) {
+        sonames.push(format!("{}-{}.so.{}", &split[1], &split[2], version));
+    }
+    sonames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+
+    #[test]
+    fn test_debian_library_sonames() {
+        assert_eq!(debian_library_sonames("libssl3t64"), ["libssl.so.3"]);
+        assert_eq!(debian_library_sonames("libc6"), ["libc.so.6"]);
+        assert_eq!(debian_library_sonames("libstdc++6"), ["libstdc++.so.6"]);
+        assert_eq!(debian_library_sonames("libgtk-3-0"), ["libgtk-3.so.0"]);
+        assert_eq!(debian_library_sonames("libwebkit2gtk-4.0-37"), ["libwebkit2gtk-4.0.so.37"]);
+        assert_eq!(debian_library_sonames("libpng16-16"), ["libpng16.so.16", "libpng-16.so.16"]);
+        assert_eq!(debian_library_sonames("libglib2.0-0"), ["libglib2.0.so.0", "libglib-2.0.so.0"]);
+        assert!(debian_library_sonames("libssl-dev").is_empty());
+        assert!(debian_library_sonames("curl").is_empty());
+    }
+
+    #[test]
+    fn test_files_db_owner() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("sync")).unwrap();
+
+        let file = File::create(temp_dir.path().join("sync/core.files")).unwrap();
+        let mut tar = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+        for (path, content) in [
+            ("openssl-3.3.1-1/desc", "%NAME%\nopenssl\n\n%VERSION%\n3.3.1-1\n\n"),
+            ("openssl-3.3.1-1/files", "%FILES%\nusr/\nusr/lib/\nusr/lib/libssl.so\nusr/lib/libssl.so.3\nusr/lib/engines-3/afalg.so\n"),
+            ("lib32-openssl-1:3.3.1-1/desc", "%NAME%\nlib32-openssl\n\n%VERSION%\n1:3.3.1-1\n\n"),
+            ("lib32-openssl-1:3.3.1-1/files", "%FILES%\nusr/\nusr/lib32/\nusr/lib32/libssl.so.3\n"),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, path, content.as_bytes()).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+
+        let db = FilesDb::load(temp_dir.path()).unwrap();
+        assert_eq!(db.owner_for_debian_package("libssl3t64"), Some("openssl"));
+        assert_eq!(db.library_owner("afalg.so"), None);
+        assert_eq!(db.owner_for_debian_package("libcrypto3"), None);
+        assert_eq!(db.lib32_owner("libssl.so.3"), Some("lib32-openssl"));
+    }
+}
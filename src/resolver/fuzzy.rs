@@ -6,7 +6,7 @@ use strsim::{jaro_winkler, normalized_damerau_levenshtein};
 
 use crate::error::Result;
 
-use super::PackageDatabase;
+use super::traits::Lookup;
 
 /// Fuzzy matcher for finding similar package names
 pub struct FuzzyMatcher {
@@ -32,12 +32,12 @@ impl FuzzyMatcher {
     }
 
     /// Find the best matching Arch package for a Debian package name
-    pub fn find_best_match(&self, debian_name: &str, db: &PackageDatabase) -> Result<Option<(String, f32)>> {
-        let candidates = db.get_arch_package_names();
+    pub fn find_best_match(&self, debian_name: &str, lookup: &dyn Lookup) -> Result<Option<(String, f32)>> {
+        let candidates = lookup.package_names();
         
         if candidates.is_empty() {
             // Fall back to heuristic matching
-            return Ok(self.heuristic_match(debian_name));
+            return Ok(self.heuristic_match(debian_name).filter(|(_, s)| *s >= self.min_score));
         }
 
         let mut best_match: Option<(String, f32)> = None;
@@ -45,16 +45,14 @@ impl FuzzyMatcher {
         for candidate in candidates {
             let score = self.calculate_score(debian_name, candidate);
             
-            if score >= self.min_score {
-                if best_match.as_ref().map_or(true, |(_, s)| score > *s) {
-                    best_match = Some((candidate.to_string(), score));
-                }
+            if score >= self.min_score && best_match.as_ref().is_none_or(|(_, s)| score > *s) {
+                best_match = Some((candidate.to_string(), score));
             }
         }
 
         // Also try heuristic matching
-        if let Some((heuristic_name, heuristic_score)) = self.heuristic_match(debian_name) {
-            if best_match.as_ref().map_or(true, |(_, s)| heuristic_score > *s) {
+        if let Some((heuristic_name, heuristic_score)) = self.heuristic_match(debian_name).filter(|(_, s)| *s >= self.min_score) {
+            if best_match.as_ref().is_none_or(|(_, s)| heuristic_score > *s) {
                 best_match = Some((heuristic_name, heuristic_score));
             }
         }
@@ -129,7 +127,7 @@ impl FuzzyMatcher {
 
         // Remove version numbers at the end (e.g., libfoo6 -> libfoo)
         let mut chars: Vec<char> = normalized.chars().collect();
-        while chars.last().map_or(false, |c| c.is_ascii_digit()) {
+        while chars.last().is_some_and(|c| c.is_ascii_digit()) {
             chars.pop();
         }
         normalized = chars.into_iter().collect();
@@ -179,7 +177,7 @@ impl FuzzyMatcher {
         // lib*N -> lib* (remove version number)
         if name.starts_with("lib") {
             let mut stripped = name.clone();
-            while stripped.chars().last().map_or(false, |c| c.is_ascii_digit()) {
+            while stripped.chars().last().is_some_and(|c| c.is_ascii_digit()) {
                 stripped.pop();
             }
             if stripped != name && stripped.len() > 3 {
@@ -231,8 +229,8 @@ impl FuzzyMatcher {
     }
 
     /// Find multiple matches sorted by score
-    pub fn find_matches(&self, debian_name: &str, db: &PackageDatabase, limit: usize) -> Result<Vec<(String, f32)>> {
-        let candidates = db.get_arch_package_names();
+    pub fn find_matches(&self, debian_name: &str, lookup: &dyn Lookup, limit: usize) -> Result<Vec<(String, f32)>> {
+        let candidates = lookup.package_names();
         let mut matches: Vec<(String, f32)> = Vec::new();
 
         for candidate in candidates {
@@ -243,7 +241,7 @@ impl FuzzyMatcher {
         }
 
         // Add heuristic matches
-        if let Some(heuristic) = self.heuristic_match(debian_name) {
+        if let Some(heuristic) = self.heuristic_match(debian_name).filter(|(_, s)| *s >= self.min_score) {
             // Only add if not already in matches
             if !matches.iter().any(|(n, _)| n == &heuristic.0) {
                 matches.push(heuristic);
@@ -0,0 +1,330 @@
+//! Installability check for resolved dependencies
+//!
+//! Simulates what `pacman -U` would need for a converted package: where
+//! each mapped dependency comes from, whether the available version meets
+//! the constraint, and whether repository dependencies can be pulled in
+//! in turn. Problems are reported before the package is built rather than
+//! when pacman refuses to install it.
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::models::{Dependency, DependencyType, PackageMetadata, VersionOp};
+
+use super::traits::Lookup;
+
+/// Where a dependency would come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Availability {
+    /// Already installed (at this version)
+    Installed(String),
+    /// Pulled from a sync repository
+    Repo(String),
+    /// Has to be built from the AUR
+    Aur,
+    /// Cannot be satisfied, with the reason
+    Unsatisfiable(String),
+    /// No repository data to decide with
+    Unknown,
+}
+
+impl fmt::Display for Availability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Installed(version) => write!(f, "installed ({})", version),
+            Self::Repo(repo) => write!(f, "{}", repo),
+            Self::Aur => write!(f, "AUR"),
+            Self::Unsatisfiable(reason) => write!(f, "unsatisfiable: {}", reason),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Result of an installability check
+#[derive(Debug, Clone, Default)]
+pub struct Installability {
+    /// Required dependencies (Arch form) and where they come from
+    pub dependencies: Vec<(String, Availability)>,
+    /// Installed packages the converted package would replace through conflicts
+    pub conflicts: Vec<String>,
+}
+
+impl Installability {
+    /// Check the required dependencies of a resolved package
+    pub fn check(db: &dyn Lookup, metadata: &PackageMetadata) -> Self {
+        let mut result = Self::default();
+        let conflicts: Vec<&str> = [DependencyType::Conflicts, DependencyType::Breaks]
+            .into_iter()
+            .flat_map(|t| metadata.get_deps(t))
+            .filter(|d| d.is_mapped())
+            .map(|d| d.effective_name())
+            .collect();
+
+        let required = [DependencyType::Depends, DependencyType::PreDepends]
+            .into_iter()
+            .flat_map(|t| metadata.get_deps(t))
+            .filter(|d| d.is_mapped());
+        for dep in required {
+            let availability = match conflicts.contains(&dep.effective_name()) {
+                true => Availability::Unsatisfiable("also listed in conflicts".to_string()),
+                false => availability(db, dep),
+            };
+            result.dependencies.push((dep.to_arch_string(), availability));
+        }
+
+        // Repository packages bring their own dependencies
+        let mut seen: HashSet<String> = HashSet::new();
+        let mut missing = Vec::new();
+        for (name, availability) in &result.dependencies {
+            if let Availability::Repo(_) = availability {
+                let name = name.split(['<', '>', '=']).next().unwrap_or(name);
+                missing.extend(missing_dependencies(db, name, &mut seen));
+            }
+        }
+        for (needed_by, name) in missing {
+            result.dependencies.push((
+                name,
+                Availability::Unsatisfiable(format!("needed by {}, not in any repository", needed_by)),
+            ));
+        }
+
+        result.conflicts = conflicts
+            .into_iter()
+            .filter(|name| db.installed_version(name).is_some())
+            .map(String::from)
+            .collect();
+        result
+    }
+
+    /// Dependencies that cannot be satisfied
+    pub fn unsatisfiable(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.dependencies.iter().filter_map(|(name, availability)| match availability {
+            Availability::Unsatisfiable(reason) => Some((name.as_str(), reason.as_str())),
+            _ => None,
+        })
+    }
+
+    /// One-line count by source, e.g. `2 installed, 1 from extra, 1 from AUR`
+    pub fn summary(&self) -> String {
+        let mut counts: Vec<(String, usize)> = Vec::new();
+        for (_, availability) in &self.dependencies {
+            let label = match availability {
+                Availability::Installed(_) => "installed".to_string(),
+                Availability::Repo(repo) => format!("from {}", repo),
+                Availability::Aur => "from AUR".to_string(),
+                Availability::Unsatisfiable(_) => "unsatisfiable".to_string(),
+                Availability::Unknown => "unknown".to_string(),
+            };
+            match counts.iter_mut().find(|(l, _)| *l == label) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((label, 1)),
+            }
+        }
+        counts
+            .iter()
+            .map(|(label, count)| format!("{} {}", count, label))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Where one mapped dependency would come from
+fn availability(db: &dyn Lookup, dep: &Dependency) -> Availability {
+    let name = dep.effective_name();
+    let constraint = dep.arch_constraint();
+    let satisfies = |version: &str| {
+        constraint
+            .as_ref()
+            .is_none_or(|(op, required)| satisfies_constraint(version, *op, required))
+    };
+
+    if let Some(version) = db.installed_version(name) {
+        if satisfies(version) {
+            return Availability::Installed(version.to_string());
+        }
+    }
+
+    match db.provider(name) {
+        // Versioned provides are not tracked, only the package's own version
+        Some(info) if info.name == name && !satisfies(&info.version) => Availability::Unsatisfiable(format!(
+            "{} {} available",
+            name, info.version
+        )),
+        Some(info) => match db.installed_version(&info.name) {
+            Some(version) => Availability::Installed(version.to_string()),
+            None => info.repo.clone().map_or(Availability::Aur, Availability::Repo),
+        },
+        None if dep.missing => Availability::Unsatisfiable("no repository or AUR package provides it".to_string()),
+        None => match db.package_exists(name) {
+            None => Availability::Unknown,
+            Some(_) => Availability::Aur,
+        },
+    }
+}
+
+/// Dependencies of a repository package (recursively) that nothing provides
+///
+/// Returns `(needed by, name)` pairs.
+fn missing_dependencies(db: &dyn Lookup, name: &str, seen: &mut HashSet<String>) -> Vec<(String, String)> {
+    let mut missing = Vec::new();
+    let mut queue = vec![name.to_string()];
+
+    while let Some(name) = queue.pop() {
+        if !seen.insert(name.clone()) {
+            continue;
+        }
+        let Some(info) = db.provider(&name) else {
+            continue;
+        };
+        for dep in &info.depends {
+            if db.provider(dep).is_some() || db.installed_version(dep).is_some() {
+                queue.push(dep.clone());
+            } else if seen.insert(dep.clone()) {
+                missing.push((info.name.clone(), dep.clone()));
+            }
+        }
+    }
+
+    missing
+}
+
+/// Whether a full version meets an Arch version constraint
+pub fn satisfies_constraint(version: &str, op: VersionOp, required: &str) -> bool {
+    let ordering = vercmp(version, required);
+    match op {
+        VersionOp::Eq => ordering == Ordering::Equal,
+        VersionOp::Ge => ordering != Ordering::Less,
+        VersionOp::Le => ordering != Ordering::Greater,
+        VersionOp::Gt => ordering == Ordering::Greater,
+        VersionOp::Lt => ordering == Ordering::Less,
+    }
+}
+
+/// Compare two `[epoch:]version[-release]` strings like pacman's vercmp
+///
+/// The release is only compared when both sides have one, so `1.2`
+/// matches any `1.2-N`.
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    let (epoch_a, version_a, release_a) = split_evr(a);
+    let (epoch_b, version_b, release_b) = split_evr(b);
+
+    rpmvercmp(epoch_a, epoch_b)
+        .then_with(|| rpmvercmp(version_a, version_b))
+        .then_with(|| match (release_a, release_b) {
+            (Some(a), Some(b)) => rpmvercmp(a, b),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Split into epoch (default `0`), version and release
+fn split_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let (epoch, rest) = match evr.split_once(':') {
+        Some((epoch, rest)) if epoch.bytes().all(|b| b.is_ascii_digit()) => (epoch, rest),
+        _ => ("0", evr),
+    };
+    match rest.rsplit_once('-') {
+        Some((version, release)) => (epoch, version, Some(release)),
+        None => (epoch, rest, None),
+    }
+}
+
+/// libalpm's segment-wise version comparison
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        let (start_i, start_j) = (i, j);
+        while i < a.len() && !a[i].is_ascii_alphanumeric() {
+            i += 1;
+        }
+        while j < b.len() && !b[j].is_ascii_alphanumeric() {
+            j += 1;
+        }
+        if i >= a.len() || j >= b.len() {
+            break;
+        }
+        // More separators sort higher
+        if i - start_i != j - start_j {
+            return (i - start_i).cmp(&(j - start_j));
+        }
+
+        let numeric = a[i].is_ascii_digit();
+        let segment = |s: &[u8], mut k: usize| {
+            while k < s.len() && (if numeric { s[k].is_ascii_digit() } else { s[k].is_ascii_alphabetic() }) {
+                k += 1;
+            }
+            k
+        };
+        let (end_i, end_j) = (segment(a, i), segment(b, j));
+        // Segments of different types: numbers are newer
+        if end_j == j {
+            return if numeric { Ordering::Greater } else { Ordering::Less };
+        }
+
+        let (mut seg_a, mut seg_b) = (&a[i..end_i], &b[j..end_j]);
+        if numeric {
+            while seg_a.first() == Some(&b'0') {
+                seg_a = &seg_a[1..];
+            }
+            while seg_b.first() == Some(&b'0') {
+                seg_b = &seg_b[1..];
+            }
+            let by_length = seg_a.len().cmp(&seg_b.len());
+            if by_length != Ordering::Equal {
+                return by_length;
+            }
+        }
+        let by_content = seg_a.cmp(seg_b);
+        if by_content != Ordering::Equal {
+            return by_content;
+        }
+
+        i = end_i;
+        j = end_j;
+    }
+
+    let (rest_a, rest_b) = (&a[i.min(a.len())..], &b[j.min(b.len())..]);
+    if rest_a.is_empty() && rest_b.is_empty() {
+        return Ordering::Equal;
+    }
+    // `1.0` is newer than `1.0alpha` but older than `1.0.1`
+    if (rest_a.is_empty() && !rest_b[0].is_ascii_alphabetic()) || rest_a.first().is_some_and(u8::is_ascii_alphabetic) {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vercmp() {
+        let cases = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "1.0.1", Ordering::Less),
+            ("1.0a", "1.0", Ordering::Less),
+            ("1.0.a", "1.0", Ordering::Greater),
+            ("1.10", "1.9", Ordering::Greater),
+            ("1.001", "1.1", Ordering::Equal),
+            ("1:1.0", "2.0", Ordering::Greater),
+            ("1.0-2", "1.0-10", Ordering::Less),
+            ("1.0-2", "1.0", Ordering::Equal),
+            ("1.0rc1", "1.0", Ordering::Less),
+            ("1.0_1", "1.0.1", Ordering::Equal),
+            ("1.0..1", "1.0.1", Ordering::Greater),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(vercmp(a, b), expected, "{} vs {}", a, b);
+            assert_eq!(vercmp(b, a), expected.reverse(), "{} vs {}", b, a);
+        }
+        assert!(satisfies_constraint("3.26-1", VersionOp::Ge, "3.26"));
+        assert!(!satisfies_constraint("3.25-1", VersionOp::Ge, "3.26"));
+    }
+}
@@ -0,0 +1,276 @@
+//! In-memory resolver components
+//!
+//! Stand-ins for the package database, fuzzy matcher and AUR client that
+//! read nothing from disk and never touch the network, for composing a
+//! [`DependencyResolver`](super::DependencyResolver) in tests:
+//!
+//! ```
+//! use rexeb::resolver::mock::{aur_package, MemoryLookup, MockAur};
+//! use rexeb::resolver::DependencyResolver;
+//!
+//! let resolver = DependencyResolver::builder()
+//!     .lookup(MemoryLookup::default().with_mapping("libc6", "glibc"))
+//!     .aur(MockAur::new([aur_package("foo-git")]))
+//!     .build()
+//!     .unwrap();
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::error::{RexebError, Result};
+use crate::models::VersionTransform;
+
+use super::aur::AurPackage;
+use super::database::ArchPackageInfo;
+use super::traits::{AurApi, AurFuture, Lookup, Matcher};
+
+/// [`Lookup`] over maps filled by the caller
+#[derive(Debug, Default)]
+pub struct MemoryLookup {
+    /// Debian name -> (Arch name, confidence)
+    pub mappings: HashMap<String, (String, f32)>,
+    /// Version rewrites by Debian name
+    pub version_transforms: HashMap<String, Vec<VersionTransform>>,
+    /// Arch packages by name
+    pub packages: HashMap<String, ArchPackageInfo>,
+    /// Installed packages and their versions
+    pub installed: HashMap<String, String>,
+    /// Virtual packages and their known providers
+    pub virtuals: HashMap<String, Vec<String>>,
+    /// Dropped Debian names
+    pub dropped: HashSet<String>,
+    /// Names the AUR had nothing for, including recorded ones
+    pub misses: Mutex<HashSet<String>>,
+}
+
+impl MemoryLookup {
+    /// Add a direct mapping
+    pub fn with_mapping(mut self, debian_name: &str, arch_name: &str) -> Self {
+        self.mappings.insert(debian_name.to_string(), (arch_name.to_string(), 1.0));
+        self
+    }
+
+    /// Add a repository package
+    pub fn with_package(mut self, info: ArchPackageInfo) -> Self {
+        self.packages.insert(info.name.clone(), info);
+        self
+    }
+
+    /// Mark a package as installed
+    pub fn with_installed(mut self, name: &str, version: &str) -> Self {
+        self.installed.insert(name.to_string(), version.to_string());
+        self
+    }
+
+    /// Add a virtual package and its providers
+    pub fn with_virtual(mut self, name: &str, providers: &[&str]) -> Self {
+        self.virtuals
+            .insert(name.to_string(), providers.iter().map(|p| p.to_string()).collect());
+        self
+    }
+}
+
+impl Lookup for MemoryLookup {
+    fn lookup(&self, debian_name: &str) -> Result<Option<(String, f32)>> {
+        if let Some(mapping) = self.mappings.get(debian_name) {
+            return Ok(Some(mapping.clone()));
+        }
+        Ok(self.packages.contains_key(debian_name).then(|| (debian_name.to_string(), 1.0)))
+    }
+
+    fn version_transforms(&self, debian_name: &str) -> &[VersionTransform] {
+        self.version_transforms.get(debian_name).map_or(&[], Vec::as_slice)
+    }
+
+    fn soname_provider(&self, soname: &str) -> Option<&str> {
+        self.packages
+            .values()
+            .find(|info| info.sonames.iter().any(|s| s == soname))
+            .map(|info| info.name.as_str())
+    }
+
+    fn virtual_provider(&self, name: &str) -> Option<(String, f32)> {
+        let providers = self.virtuals.get(name)?;
+        if let Some(installed) = providers.iter().find(|p| self.installed.contains_key(*p)) {
+            return Some((installed.clone(), 0.9));
+        }
+        if let Some(available) = providers.iter().find(|p| self.packages.contains_key(*p)) {
+            return Some((available.clone(), 0.85));
+        }
+        providers.first().map(|p| (p.clone(), 0.7))
+    }
+
+    fn is_virtual(&self, name: &str) -> Result<bool> {
+        Ok(self.virtuals.contains_key(name))
+    }
+
+    fn is_dropped(&self, debian_name: &str) -> bool {
+        self.dropped.contains(debian_name)
+    }
+
+    fn is_known_miss(&self, debian_name: &str) -> bool {
+        self.misses.lock().unwrap().contains(debian_name)
+    }
+
+    fn record_misses(&self, names: &[String]) -> Result<()> {
+        self.misses.lock().unwrap().extend(names.iter().cloned());
+        Ok(())
+    }
+
+    fn description(&self, name: &str) -> Option<&str> {
+        self.packages
+            .get(name)
+            .map(|info| info.description.as_str())
+            .filter(|d| !d.is_empty())
+    }
+
+    fn installed_version(&self, name: &str) -> Option<&str> {
+        self.installed.get(name).map(String::as_str)
+    }
+
+    fn package_exists(&self, name: &str) -> Option<bool> {
+        match self.packages.is_empty() {
+            true => None,
+            false => Some(self.installed.contains_key(name) || self.provider(name).is_some()),
+        }
+    }
+
+    fn provider(&self, name: &str) -> Option<&ArchPackageInfo> {
+        self.packages
+            .get(name)
+            .or_else(|| self.packages.values().find(|info| info.provides.iter().any(|p| p == name)))
+    }
+
+    fn package_names(&self) -> Vec<&str> {
+        self.packages.keys().map(String::as_str).collect()
+    }
+}
+
+/// [`Matcher`] answering from a fixed table of candidates
+#[derive(Debug, Default)]
+pub struct FixedMatcher {
+    /// Candidates with scores by Debian name, best first
+    pub candidates: HashMap<String, Vec<(String, f32)>>,
+}
+
+impl FixedMatcher {
+    /// Add a candidate for a Debian name
+    pub fn with_candidate(mut self, debian_name: &str, arch_name: &str, score: f32) -> Self {
+        let candidates = self.candidates.entry(debian_name.to_string()).or_default();
+        candidates.push((arch_name.to_string(), score));
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        self
+    }
+}
+
+impl Matcher for FixedMatcher {
+    fn best_match(&self, debian_name: &str, _lookup: &dyn Lookup) -> Result<Option<(String, f32)>> {
+        Ok(self.candidates.get(debian_name).and_then(|c| c.first().cloned()))
+    }
+
+    fn matches(&self, debian_name: &str, _lookup: &dyn Lookup, limit: usize) -> Result<Vec<(String, f32)>> {
+        let mut matches = self.candidates.get(debian_name).cloned().unwrap_or_default();
+        matches.truncate(limit);
+        Ok(matches)
+    }
+}
+
+/// [`AurApi`] serving a fixed set of packages and logging what was asked
+#[derive(Debug, Default)]
+pub struct MockAur {
+    /// Packages in the mock AUR
+    pub packages: Vec<AurPackage>,
+    /// Fail every request like offline mode
+    pub offline: bool,
+    /// Names and capabilities asked for, in order; clone to watch after handing the mock over
+    pub requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockAur {
+    /// Mock AUR with these packages
+    pub fn new(packages: impl IntoIterator<Item = AurPackage>) -> Self {
+        Self {
+            packages: packages.into_iter().collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Mock AUR that refuses every request
+    pub fn offline() -> Self {
+        Self {
+            offline: true,
+            ..Self::default()
+        }
+    }
+
+    /// Fail like the real client does in offline mode
+    fn check_online(&self) -> Result<()> {
+        match self.offline {
+            true => Err(RexebError::Network("offline mode is enabled".into())),
+            false => Ok(()),
+        }
+    }
+}
+
+impl AurApi for MockAur {
+    fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    fn info<'a>(&'a self, names: &'a [&'a str]) -> AurFuture<'a, Vec<AurPackage>> {
+        Box::pin(async move {
+            self.check_online()?;
+            self.requests.lock().unwrap().extend(names.iter().map(|n| n.to_string()));
+            Ok(self
+                .packages
+                .iter()
+                .filter(|pkg| names.contains(&pkg.name.as_str()))
+                .cloned()
+                .collect())
+        })
+    }
+
+    fn search<'a>(&'a self, query: &'a str) -> AurFuture<'a, Vec<AurPackage>> {
+        Box::pin(async move {
+            self.check_online()?;
+            self.requests.lock().unwrap().push(query.to_string());
+            Ok(self.packages.iter().filter(|pkg| pkg.name.contains(query)).cloned().collect())
+        })
+    }
+
+    fn find_providers<'a>(&'a self, capability: &'a str) -> AurFuture<'a, Vec<AurPackage>> {
+        Box::pin(async move {
+            self.check_online()?;
+            self.requests.lock().unwrap().push(capability.to_string());
+            Ok(self
+                .packages
+                .iter()
+                .filter(|pkg| pkg.name == capability || pkg.provides.iter().flatten().any(|p| p == capability))
+                .cloned()
+                .collect())
+        })
+    }
+}
+
+/// AUR package with only a name and version filled in
+pub fn aur_package(name: &str) -> AurPackage {
+    AurPackage {
+        name: name.to_string(),
+        version: "1.0-1".to_string(),
+        description: None,
+        url: None,
+        package_base: name.to_string(),
+        num_votes: 0,
+        popularity: 0.0,
+        out_of_date: None,
+        maintainer: None,
+        first_submitted: 0,
+        last_modified: 0,
+        provides: None,
+        replaces: None,
+        conflicts: None,
+        depends: None,
+        make_depends: None,
+    }
+}
@@ -2,58 +2,226 @@
 
 pub mod aur;
 pub mod database;
+pub mod ecosystem;
+pub mod files;
 pub mod fuzzy;
+pub mod installability;
 pub mod mapper;
+pub mod mock;
+pub mod pacman;
+pub mod traits;
+pub mod user_mappings;
 
-pub use aur::AurClient;
+pub use aur::{AurClient, AurPackage};
 pub use database::PackageDatabase;
+pub use ecosystem::Ecosystem;
+pub use files::FilesDb;
 pub use fuzzy::FuzzyMatcher;
+pub use installability::Installability;
 pub use mapper::PackageMapper;
+pub use pacman::{InstalledFiles, PacmanDb};
+pub use traits::{AurApi, Lookup, Matcher};
+pub use user_mappings::UserMappings;
 
-use crate::error::Result;
-use crate::models::{Dependency, DependencyType, PackageMetadata};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use crate::config::Config;
+use crate::error::{RexebError, Result};
+use crate::models::{Dependency, DependencyType, PackageMetadata, ResolutionSource};
+use crate::progress::{Progress, Stage};
+
+/// Relationship fields the resolver maps, in resolution order
+pub const RESOLVED_TYPES: [DependencyType; 9] = [
+    DependencyType::Depends,
+    DependencyType::PreDepends,
+    DependencyType::Recommends,
+    DependencyType::Suggests,
+    DependencyType::Conflicts,
+    DependencyType::Replaces,
+    DependencyType::Provides,
+    DependencyType::Breaks,
+    DependencyType::BuildDepends,
+];
+
+/// Fields whose mapped packages must exist to be installable
+const VERIFIED_TYPES: [DependencyType; 5] = [
+    DependencyType::Depends,
+    DependencyType::PreDepends,
+    DependencyType::Recommends,
+    DependencyType::Suggests,
+    DependencyType::BuildDepends,
+];
+
+/// Number of candidates offered when asking about an uncertain match
+const PROMPT_CANDIDATES: usize = 5;
+
+/// Answer to a question about an uncertain mapping
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MappingChoice {
+    /// Map the dependency to this Arch package
+    Use(String),
+    /// Leave the dependency unmapped
+    Skip,
+}
+
+/// Asks which Arch package a Debian dependency maps to
+///
+/// Called with the Debian name and the candidates with their scores, best first.
+pub type MappingPrompt = Arc<dyn Fn(&str, &[(String, f32)]) -> MappingChoice + Send + Sync>;
 
 /// Dependency resolver that maps Debian packages to Arch packages
 pub struct DependencyResolver {
     /// Package database for lookups
-    db: PackageDatabase,
-    /// Fuzzy matcher for approximate matching
-    fuzzy: FuzzyMatcher,
-    /// AUR client for online lookups
-    aur: AurClient,
+    lookup: Box<dyn Lookup>,
+    /// Library index from the files databases, loaded on first use
+    files: std::sync::OnceLock<FilesDb>,
+    /// Matcher for approximate matching
+    matcher: Box<dyn Matcher>,
+    /// AUR access for online lookups
+    aur: Box<dyn AurApi>,
+    /// Progress reporting
+    progress: Progress,
+    /// Confidence below which matches are confirmed, and how to ask
+    prompt: Option<(f32, MappingPrompt)>,
+    /// Answers given during this run, by Debian name
+    answers: Mutex<HashMap<String, MappingChoice>>,
+    /// Fail where a question would be needed instead of using a default
+    fail_on_questions: bool,
+    /// Debian dependencies dropped without resolving
+    ignored: HashSet<String>,
+    /// Approximate matches scoring below this are left for review
+    min_confidence: f32,
+    /// Fuzzy match again when a mapped package does not exist
+    rematch_missing: bool,
 }
 
 impl DependencyResolver {
-    /// Create a new dependency resolver
+    /// Create a new dependency resolver from the system databases and user config
     pub fn new() -> Result<Self> {
-        Ok(Self {
-            db: PackageDatabase::new()?,
-            fuzzy: FuzzyMatcher::new(),
-            aur: AurClient::new(),
-        })
+        ResolverBuilder::from_config(&Config::load()?).build()
+    }
+
+    /// Compose a resolver from its components, with default settings
+    pub fn builder() -> ResolverBuilder {
+        ResolverBuilder::from_config(&Config::default())
+    }
+
+    /// Report per-dependency progress
+    pub fn with_progress(mut self, progress: Progress) -> Self {
+        self.progress = progress;
+        self
+    }
+
+    /// Ask before using fuzzy or AUR matches scoring below `threshold`
+    ///
+    /// Chosen packages are marked [`ResolutionSource::User`], so
+    /// [`learned_mappings`] picks them up.
+    pub fn with_prompt(mut self, threshold: f32, prompt: MappingPrompt) -> Self {
+        self.prompt = Some((threshold, prompt));
+        self
+    }
+
+    /// Fail with [`RexebError::InputRequired`] instead of answering
+    /// questions with defaults, such as the `prompt` Java conflict strategy
+    pub fn fail_on_questions(mut self) -> Self {
+        self.fail_on_questions = true;
+        self
+    }
+
+    /// Drop these Debian dependencies as well as the configured ones
+    pub fn with_ignored(mut self, names: impl IntoIterator<Item = String>) -> Self {
+        self.ignored.extend(names);
+        self
     }
 
     /// Resolve all dependencies in a package
     pub async fn resolve(&self, metadata: &mut PackageMetadata) -> Result<()> {
-        let dep_types = [
-            DependencyType::Depends,
-            DependencyType::PreDepends,
-            DependencyType::Recommends,
-            DependencyType::Suggests,
-            DependencyType::Conflicts,
-            DependencyType::Replaces,
-            DependencyType::Provides,
-            DependencyType::Breaks,
-        ];
+        self.resolve_with_progress(metadata, &self.progress).await
+    }
+
+    /// Resolve all dependencies in a package, reporting to `progress`
+    ///
+    /// For one resolver shared by several conversions, each with its own
+    /// progress.
+    pub async fn resolve_with_progress(&self, metadata: &mut PackageMetadata, progress: &Progress) -> Result<()> {
+        // Dependencies the user never wants disappear entirely
+        for deps in metadata.dependencies.values_mut() {
+            deps.retain(|dep| !self.is_ignored(&dep.debian_name));
+            for dep in deps.iter_mut() {
+                dep.alternatives.retain(|alt| !self.is_ignored(&alt.debian_name));
+            }
+        }
+
+        let total = RESOLVED_TYPES.iter().map(|t| metadata.get_deps(*t).len() as u64).sum();
+        let mut resolved = 0;
+        progress.update(Stage::Resolving, 0, Some(total), None::<String>);
+
+        // Local sources first, so the AUR is asked about the rest in one request
+        let mut unresolved = Vec::new();
+        for dep_type in RESOLVED_TYPES {
+            if let Some(deps) = metadata.dependencies.get_mut(&dep_type) {
+                for dep in deps.iter_mut() {
+                    if !self.resolve_local(dep)? {
+                        unresolved.push(dep.debian_name.clone());
+                    }
+                    for alt in dep.alternatives.iter_mut() {
+                        if !self.resolve_local(alt)? {
+                            unresolved.push(alt.debian_name.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        // Names the AUR recently had nothing for are not asked again
+        unresolved.retain(|name| !self.lookup.is_known_miss(name));
+        // Language modules may be in the AUR under their Arch name
+        let renamed: Vec<String> = unresolved
+            .iter()
+            .filter_map(|name| Ecosystem::detect(name))
+            .flat_map(|(ecosystem, module)| ecosystem.candidates(module))
+            .collect();
+        unresolved.extend(renamed);
+        let aur_info = self.aur_info(unresolved).await;
 
-        for dep_type in dep_types {
+        let mut misses = Vec::new();
+        for dep_type in RESOLVED_TYPES {
             if let Some(deps) = metadata.dependencies.get_mut(&dep_type) {
                 for dep in deps.iter_mut() {
-                    self.resolve_single(dep).await?;
-                    
+                    resolved += 1;
+                    progress.update(Stage::Resolving, resolved, Some(total), Some(dep.debian_name.as_str()));
+                    if self.resolve_remote(dep, &aur_info).await? {
+                        misses.push(dep.debian_name.clone());
+                    }
+
                     // Also resolve alternatives
                     for alt in dep.alternatives.iter_mut() {
-                        self.resolve_single(alt).await?;
+                        if self.resolve_remote(alt, &aur_info).await? {
+                            misses.push(alt.debian_name.clone());
+                        }
+                    }
+                }
+            }
+        }
+        if let Err(e) = self.lookup.record_misses(&misses) {
+            tracing::warn!("Could not save failed lookups: {}", e);
+        }
+
+        self.verify_mappings(metadata).await?;
+
+        for deps in metadata.dependencies.values_mut() {
+            for dep in deps.iter_mut() {
+                prefer_installed(dep, |name| self.lookup.installed_version(name).is_some());
+            }
+        }
+
+        // Reasons for optdepends
+        for dep_type in [DependencyType::Recommends, DependencyType::Suggests] {
+            if let Some(deps) = metadata.dependencies.get_mut(&dep_type) {
+                for dep in deps.iter_mut().filter(|d| d.description.is_none()) {
+                    if let Some(description) = dep.arch_name.as_deref().and_then(|n| self.lookup.description(n)) {
+                        dep.description = Some(description.to_string());
                     }
                 }
             }
@@ -65,51 +233,300 @@ impl DependencyResolver {
         Ok(())
     }
 
-    /// Resolve a single dependency
-    async fn resolve_single(&self, dep: &mut Dependency) -> Result<()> {
+    /// Whether a dependency is ignored or dropped by the user's mappings
+    fn is_ignored(&self, debian_name: &str) -> bool {
+        self.ignored.contains(debian_name) || self.lookup.is_dropped(debian_name)
+    }
+
+    /// Resolve a dependency from the local databases
+    ///
+    /// Returns whether the dependency is mapped.
+    fn resolve_local(&self, dep: &mut Dependency) -> Result<bool> {
         // Skip if already resolved
         if dep.is_mapped() {
-            return Ok(());
+            return Ok(true);
         }
 
         // 1. Try exact mapping from local DB
-        if let Some((arch_name, confidence)) = self.db.lookup(&dep.debian_name)? {
+        if let Some((arch_name, confidence)) = self.lookup.lookup(&dep.debian_name)? {
             dep.set_arch_name(arch_name, confidence);
-            return Ok(());
+            dep.source = Some(ResolutionSource::Database);
+            dep.version_transforms = self.lookup.version_transforms(&dep.debian_name).to_vec();
+            return Ok(true);
         }
 
-        // 2. Try fuzzy matching against local DB
-        if let Some((arch_name, confidence)) = self.fuzzy.find_best_match(&dep.debian_name, &self.db)? {
-            dep.set_arch_name(arch_name, confidence);
+        // 2. Library packages: whoever ships the soname on Arch
+        if let Some(owner) = self.soname_owner(&dep.debian_name) {
+            dep.set_arch_name(owner, 0.95);
+            dep.source = Some(ResolutionSource::Soname);
+            return Ok(true);
+        }
+
+        // 3. Virtual packages: a concrete provider, preferably installed
+        if let Some((provider, confidence)) = self.lookup.virtual_provider(&dep.debian_name) {
+            dep.set_arch_name(provider, confidence);
+            dep.source = Some(ResolutionSource::Database);
+            dep.is_virtual = true;
+            return Ok(true);
+        }
+
+        // 4. Language modules: the renamed package, once the repositories confirm it
+        if let Some((ecosystem, module)) = Ecosystem::detect(&dep.debian_name) {
+            let candidates = ecosystem.candidates(module);
+            if let Some(info) = candidates.iter().find_map(|c| self.lookup.provider(c)) {
+                dep.set_arch_name(info.name.clone(), 0.95);
+                dep.source = Some(ResolutionSource::Database);
+                return Ok(true);
+            }
+            // Not in the repositories: the AUR decides, not name similarity
+            if self.lookup.package_exists(&candidates[0]).is_some() {
+                return Ok(false);
+            }
+        }
+
+        // 5. Try fuzzy matching against local DB
+        if let Some((arch_name, confidence)) = self.matcher.best_match(&dep.debian_name, self.lookup.as_ref())? {
+            self.accept(dep, arch_name, confidence, ResolutionSource::Fuzzy, None)?;
+            return Ok(dep.is_mapped());
+        }
+
+        Ok(false)
+    }
+
+    /// AUR packages named like any of the given dependencies, in a single query
+    ///
+    /// Lookup failures leave the names to the later steps.
+    async fn aur_info(&self, mut names: Vec<String>) -> HashMap<String, AurPackage> {
+        names.sort();
+        names.dedup();
+        if names.is_empty() || self.aur.is_offline() {
+            return HashMap::new();
+        }
+
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        match self.aur.info(&names).await {
+            Ok(packages) => packages.into_iter().map(|pkg| (pkg.name.clone(), pkg)).collect(),
+            Err(e) => {
+                tracing::debug!("AUR info lookup failed: {}", e);
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Resolve what the local databases could not, using the AUR
+    ///
+    /// Returns whether the AUR was asked and had nothing.
+    async fn resolve_remote(&self, dep: &mut Dependency, aur_info: &HashMap<String, AurPackage>) -> Result<bool> {
+        // Skip if already resolved
+        if dep.is_mapped() {
+            return Ok(false);
+        }
+
+        // 6. Exact name match in the AUR, for language modules also the Arch name
+        let ecosystem = Ecosystem::detect(&dep.debian_name).map(|(e, module)| (e, module.to_string()));
+        let names = std::iter::once(dep.debian_name.clone())
+            .chain(ecosystem.iter().flat_map(|(e, module)| e.candidates(module)));
+        if let Some(pkg) = names.into_iter().find_map(|name| aur_info.get(&name)) {
+            let confidence = if pkg.name == dep.debian_name { 1.0 } else { 0.9 };
+            dep.set_arch_name(&pkg.name, confidence);
+            dep.source = Some(ResolutionSource::Aur);
+            dep.description = pkg.description.clone();
+            return Ok(false);
+        }
+
+        // 7. Try AUR provider search (for virtual packages or libraries),
+        //    for language modules a search within the ecosystem's packages
+        let mut missed = false;
+        if !self.lookup.is_known_miss(&dep.debian_name) {
+            let found = match ecosystem {
+                Some((ecosystem, ref module)) => self.aur.search(module).await.map(|mut results| {
+                    results.retain(|pkg| ecosystem.is_variant(module, &pkg.name));
+                    results.sort_by(|a, b| b.popularity.partial_cmp(&a.popularity).unwrap_or(std::cmp::Ordering::Equal));
+                    results
+                }),
+                None => self.aur.find_providers(&dep.debian_name).await,
+            };
+            if let Ok(providers) = found {
+                if let Some(pkg) = providers.first() {
+                    // If we found a provider, use it but with lower confidence
+                    // unless the names match exactly
+                    let confidence = if pkg.name == dep.debian_name { 1.0 } else { 0.8 };
+                    self.accept(dep, pkg.name.clone(), confidence, ResolutionSource::Aur, pkg.description.clone())?;
+                    return Ok(false);
+                }
+                missed = true;
+            }
+        }
+
+        // 8. Check if it's a known virtual package in local DB
+        if self.lookup.is_virtual(&dep.debian_name)? {
+            dep.is_virtual = true;
+        }
+
+        Ok(missed)
+    }
+
+    /// Flag mappings naming packages found neither in the repositories nor the AUR
+    ///
+    /// Builtin and learned mappings go stale when Arch renames packages.
+    /// Nothing is flagged without synced repository data or when the AUR
+    /// cannot be asked.
+    async fn verify_mappings(&self, metadata: &mut PackageMetadata) -> Result<()> {
+        let checked = |dep: &Dependency| {
+            dep.is_mapped() && !matches!(dep.source, Some(ResolutionSource::Aur | ResolutionSource::User))
+        };
+
+        let mut unknown: Vec<String> = Vec::new();
+        for dep in VERIFIED_TYPES.iter().flat_map(|t| metadata.get_deps(*t)) {
+            for dep in std::iter::once(dep).chain(&dep.alternatives).filter(|d| checked(d)) {
+                let name = dep.effective_name();
+                if self.lookup.package_exists(name) == Some(false) && !unknown.iter().any(|n| n == name) {
+                    unknown.push(name.to_string());
+                }
+            }
+        }
+        if unknown.is_empty() || self.aur.is_offline() {
             return Ok(());
         }
 
-        // 3. Try AUR search
-        // First try exact name match in AUR
-        if let Ok(results) = self.aur.info(&[&dep.debian_name]).await {
-            if let Some(pkg) = results.first() {
-                dep.set_arch_name(&pkg.name, 1.0);
+        let names: Vec<&str> = unknown.iter().map(String::as_str).collect();
+        let in_aur: HashSet<String> = match self.aur.info(&names).await {
+            Ok(packages) => packages.into_iter().map(|pkg| pkg.name).collect(),
+            Err(e) => {
+                tracing::debug!("AUR lookup of mapped packages failed: {}", e);
                 return Ok(());
             }
+        };
+        unknown.retain(|name| !in_aur.contains(name));
+
+        for dep_type in VERIFIED_TYPES {
+            if let Some(deps) = metadata.dependencies.get_mut(&dep_type) {
+                for dep in deps.iter_mut() {
+                    if checked(dep) && unknown.iter().any(|n| n == dep.effective_name()) {
+                        self.replace_missing(dep)?;
+                    }
+                    for alt in dep.alternatives.iter_mut() {
+                        if checked(alt) && unknown.iter().any(|n| n == alt.effective_name()) {
+                            self.replace_missing(alt)?;
+                        }
+                    }
+                }
+            }
         }
 
-        // 4. Try AUR provider search (for virtual packages or libraries)
-        if let Ok(providers) = self.aur.find_providers(&dep.debian_name).await {
-            if let Some(pkg) = providers.first() {
-                // If we found a provider, use it but with lower confidence
-                // unless the names match exactly
-                let confidence = if pkg.name == dep.debian_name { 1.0 } else { 0.8 };
-                dep.set_arch_name(&pkg.name, confidence);
+        Ok(())
+    }
+
+    /// Fuzzy match a dependency whose mapped package does not exist, or flag it
+    fn replace_missing(&self, dep: &mut Dependency) -> Result<()> {
+        let dead = dep.arch_name.take().unwrap_or_default();
+        let replacement = match self.rematch_missing {
+            true => self
+                .matcher
+                .matches(&dep.debian_name, self.lookup.as_ref(), PROMPT_CANDIDATES)?
+                .into_iter()
+                .find(|(name, _)| *name != dead && self.lookup.package_exists(name) == Some(true)),
+            false => None,
+        };
+
+        match replacement {
+            Some((name, score)) => {
+                tracing::warn!("{} was mapped to missing package {}, trying {}", dep.debian_name, dead, name);
+                dep.version_transforms.clear();
+                self.accept(dep, name, score, ResolutionSource::Fuzzy, None)
+            }
+            None => {
+                tracing::warn!("{} is mapped to {}, which no repository or AUR package provides", dep.debian_name, dead);
+                dep.arch_name = Some(dead);
+                dep.missing = true;
+                Ok(())
+            }
+        }
+    }
+
+    /// Use an approximate match, asking first when it scores below the prompt threshold
+    ///
+    /// Without a prompt, matches below `min_match_confidence` are not used;
+    /// the dependency stays unmapped with the match as its suggestion.
+    fn accept(
+        &self,
+        dep: &mut Dependency,
+        arch_name: String,
+        confidence: f32,
+        source: ResolutionSource,
+        description: Option<String>,
+    ) -> Result<()> {
+        let (arch_name, confidence, source, description) = match self.prompt {
+            Some((threshold, ref prompt)) if confidence < threshold => match self.ask(&dep.debian_name, &arch_name, confidence, prompt)? {
+                MappingChoice::Use(chosen) if chosen == arch_name => (chosen, 1.0, ResolutionSource::User, description),
+                MappingChoice::Use(chosen) => {
+                    let description = self.lookup.description(&chosen).map(String::from);
+                    (chosen, 1.0, ResolutionSource::User, description)
+                }
+                MappingChoice::Skip => return Ok(()),
+            },
+            _ if confidence < self.min_confidence => {
+                tracing::warn!(
+                    "{} needs review: best match {} ({:.0}%) is below min_match_confidence",
+                    dep.debian_name,
+                    arch_name,
+                    confidence * 100.0
+                );
+                dep.suggestion = Some(arch_name);
+                dep.confidence = confidence;
                 return Ok(());
             }
+            _ => (arch_name, confidence, source, description),
+        };
+
+        dep.set_arch_name(arch_name, confidence);
+        dep.source = Some(source);
+        if description.is_some() {
+            dep.description = description;
         }
+        Ok(())
+    }
 
-        // 5. Check if it's a known virtual package in local DB
-        if self.db.is_virtual(&dep.debian_name)? {
-            dep.is_virtual = true;
+    /// Ask which package an uncertain match should use, once per Debian name
+    fn ask(&self, debian_name: &str, found: &str, confidence: f32, prompt: &MappingPrompt) -> Result<MappingChoice> {
+        if let Some(choice) = self.answers.lock().unwrap().get(debian_name) {
+            return Ok(choice.clone());
         }
 
-        Ok(())
+        let mut candidates = vec![(found.to_string(), confidence)];
+        for candidate in self.matcher.matches(debian_name, self.lookup.as_ref(), PROMPT_CANDIDATES)? {
+            if candidates.len() < PROMPT_CANDIDATES && !candidates.iter().any(|(n, _)| *n == candidate.0) {
+                candidates.push(candidate);
+            }
+        }
+        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        let choice = prompt(debian_name, &candidates);
+        self.answers.lock().unwrap().insert(debian_name.to_string(), choice.clone());
+        Ok(choice)
+    }
+
+    /// Arch package shipping the library a Debian library package is named after
+    ///
+    /// Soname provides are checked first; the files databases also cover
+    /// libraries without an explicit provide.
+    fn soname_owner(&self, debian_name: &str) -> Option<String> {
+        let sonames = files::debian_library_sonames(debian_name);
+        sonames
+            .iter()
+            .find_map(|soname| self.lookup.soname_provider(soname))
+            .or_else(|| sonames.iter().find_map(|soname| self.files_db().library_owner(soname)))
+            .map(String::from)
+    }
+
+    /// Library index, read from the files databases on first use
+    fn files_db(&self) -> &FilesDb {
+        self.files.get_or_init(|| {
+            FilesDb::system().unwrap_or_else(|e| {
+                tracing::warn!("Could not read pacman files databases: {}", e);
+                FilesDb::default()
+            })
+        })
     }
 
     /// Handle Java dependency conflicts by ensuring virtual package usage and conflict avoidance
@@ -154,10 +571,10 @@ impl DependencyResolver {
             for dep in deps.iter_mut() {
                 if let Some(ref arch_name) = dep.arch_name {
                     if java_jre_patterns.iter().any(|p| arch_name.contains(p)) {
-                        jre_deps.push((dep_type.clone(), dep.debian_name.clone(), arch_name.clone()));
+                        jre_deps.push((*dep_type, dep.debian_name.clone(), arch_name.clone()));
                     }
                     if java_jdk_patterns.iter().any(|p| arch_name.contains(p)) {
-                        jdk_deps.push((dep_type.clone(), dep.debian_name.clone(), arch_name.clone()));
+                        jdk_deps.push((*dep_type, dep.debian_name.clone(), arch_name.clone()));
                     }
                 }
             }
@@ -199,6 +616,12 @@ impl DependencyResolver {
                         }
                     }
                 }
+                "prompt" if self.fail_on_questions => {
+                    return Err(RexebError::InputRequired(format!(
+                        "{} depends on both a JRE and a JDK; set java.conflict_strategy to choose one",
+                        metadata.name
+                    )));
+                }
                 "prompt" => {
                     // For now, default to prefer-jdk when prompt is requested
                     // In a real implementation, this would ask the user
@@ -241,6 +664,11 @@ impl DependencyResolver {
         Ok(())
     }
 
+    /// Check whether the resolved dependencies could be installed here
+    pub fn installability(&self, metadata: &PackageMetadata) -> Installability {
+        Installability::check(self.lookup.as_ref(), metadata)
+    }
+
     /// Get resolution statistics
     pub fn stats(&self, metadata: &PackageMetadata) -> ResolutionStats {
         let mut stats = ResolutionStats::default();
@@ -269,10 +697,117 @@ impl DependencyResolver {
     }
 }
 
+/// Assembles a [`DependencyResolver`] from injectable components
+///
+/// Components left unset are the real ones: the package database, a fuzzy
+/// matcher, the AUR client and the pacman files databases.
+pub struct ResolverBuilder {
+    /// Package database
+    lookup: Option<Box<dyn Lookup>>,
+    /// Approximate matcher
+    matcher: Option<Box<dyn Matcher>>,
+    /// AUR access
+    aur: Option<Box<dyn AurApi>>,
+    /// Library index
+    files: Option<FilesDb>,
+    /// Settings for the default components and the resolver itself
+    config: Config,
+}
+
+impl ResolverBuilder {
+    /// Start from a config's conversion and network settings
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            lookup: None,
+            matcher: None,
+            aur: None,
+            files: None,
+            config: config.clone(),
+        }
+    }
+
+    /// Use this package database
+    pub fn lookup(mut self, lookup: impl Lookup + 'static) -> Self {
+        self.lookup = Some(Box::new(lookup));
+        self
+    }
+
+    /// Use this matcher for approximate matches
+    pub fn matcher(mut self, matcher: impl Matcher + 'static) -> Self {
+        self.matcher = Some(Box::new(matcher));
+        self
+    }
+
+    /// Use this AUR access
+    pub fn aur(mut self, aur: impl AurApi + 'static) -> Self {
+        self.aur = Some(Box::new(aur));
+        self
+    }
+
+    /// Use this library index instead of reading the files databases
+    pub fn files(mut self, files: FilesDb) -> Self {
+        self.files = Some(files);
+        self
+    }
+
+    /// Leave approximate matches scoring below this for review
+    pub fn min_confidence(mut self, min_confidence: f32) -> Self {
+        self.config.conversion.min_match_confidence = min_confidence;
+        self
+    }
+
+    /// Fuzzy match again when a mapped package does not exist
+    pub fn rematch_missing(mut self, rematch: bool) -> Self {
+        self.config.conversion.rematch_missing = rematch;
+        self
+    }
+
+    /// Build the resolver, loading the components not given
+    pub fn build(self) -> Result<DependencyResolver> {
+        let conversion = self.config.conversion;
+        let lookup: Box<dyn Lookup> = match self.lookup {
+            Some(lookup) => lookup,
+            None => Box::new(PackageDatabase::new()?),
+        };
+        let matcher: Box<dyn Matcher> = match self.matcher {
+            Some(matcher) => matcher,
+            None => Box::new(FuzzyMatcher::new().with_min_score(conversion.min_match_confidence)),
+        };
+        let aur: Box<dyn AurApi> = match self.aur {
+            Some(aur) => aur,
+            None => Box::new(AurClient::new(&self.config.network)?),
+        };
+        let files = std::sync::OnceLock::new();
+        if let Some(index) = self.files {
+            let _ = files.set(index);
+        }
+
+        Ok(DependencyResolver {
+            lookup,
+            files,
+            matcher,
+            aur,
+            progress: Progress::default(),
+            prompt: None,
+            fail_on_questions: false,
+            answers: Mutex::new(HashMap::new()),
+            ignored: conversion.ignore_deps.into_iter().collect(),
+            min_confidence: conversion.min_match_confidence,
+            rematch_missing: conversion.rematch_missing,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::models::{Dependency, DependencyType};
+    use mock::{aur_package, FixedMatcher, MemoryLookup, MockAur};
+
+    /// Resolver that reads nothing from disk and never asks the real AUR
+    fn offline_resolver(lookup: MemoryLookup, aur: MockAur) -> ResolverBuilder {
+        DependencyResolver::builder().lookup(lookup).aur(aur).files(FilesDb::default())
+    }
 
     #[test]
     fn test_java_conflict_resolution() {
@@ -335,6 +870,281 @@ mod tests {
         assert!(!jre_exists, "JRE dependency should be removed with default prefer-jdk strategy");
         assert!(jdk_exists, "JDK dependency should be preserved with default prefer-jdk strategy");
     }
+
+    #[test]
+    fn test_prompt_for_uncertain_match() {
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let log = asked.clone();
+        let resolver = offline_resolver(MemoryLookup::default(), MockAur::offline())
+            .build()
+            .unwrap()
+            .with_prompt(
+                0.85,
+                Arc::new(move |name: &str, candidates: &[(String, f32)]| {
+                    log.lock().unwrap().push((name.to_string(), candidates.to_vec()));
+                    MappingChoice::Skip
+                }),
+            );
+
+        let mut confident = Dependency::new("rexeb-test-sure");
+        resolver.accept(&mut confident, "sure".to_string(), 0.9, ResolutionSource::Fuzzy, None).unwrap();
+        assert_eq!(confident.arch_name.as_deref(), Some("sure"));
+        assert_eq!(confident.source, Some(ResolutionSource::Fuzzy));
+
+        for _ in 0..2 {
+            let mut uncertain = Dependency::new("rexeb-test-unsure");
+            resolver.accept(&mut uncertain, "unsure".to_string(), 0.7, ResolutionSource::Fuzzy, None).unwrap();
+            assert!(!uncertain.is_mapped());
+        }
+
+        let asked = asked.lock().unwrap();
+        assert_eq!(asked.len(), 1);
+        assert_eq!(asked[0].0, "rexeb-test-unsure");
+        assert_eq!(asked[0].1[0], ("unsure".to_string(), 0.7));
+    }
+
+    #[test]
+    fn test_low_confidence_needs_review() {
+        let resolver = offline_resolver(MemoryLookup::default(), MockAur::offline())
+            .min_confidence(0.85)
+            .build()
+            .unwrap();
+
+        let mut dep = Dependency::new("rexeb-test-vague");
+        resolver.accept(&mut dep, "vague".to_string(), 0.8, ResolutionSource::Aur, None).unwrap();
+        assert!(!dep.is_mapped());
+        assert_eq!(dep.suggestion.as_deref(), Some("vague"));
+        assert_eq!(dep.confidence, 0.8);
+
+        let mut dep = Dependency::new("rexeb-test-clear");
+        resolver.accept(&mut dep, "clear".to_string(), 0.9, ResolutionSource::Aur, None).unwrap();
+        assert_eq!(dep.arch_name.as_deref(), Some("clear"));
+        assert!(dep.suggestion.is_none());
+    }
+
+    #[test]
+    fn test_prefer_installed_alternative() {
+        let mapped = |debian: &str, arch: &str| {
+            let mut dep = Dependency::new(debian);
+            dep.set_arch_name(arch, 1.0);
+            dep
+        };
+        let group = || {
+            let mut dep = mapped("default-mta", "postfix");
+            dep.alternatives.push(Dependency::new("rexeb-test-mta"));
+            dep.alternatives.push(mapped("exim4", "exim"));
+            dep
+        };
+
+        let mut dep = group();
+        prefer_installed(&mut dep, |name| name == "exim");
+        assert_eq!(dep.effective_name(), "exim");
+        let alternatives: Vec<_> = dep.alternatives.iter().map(|d| d.debian_name.as_str()).collect();
+        assert_eq!(alternatives, ["rexeb-test-mta", "default-mta"]);
+
+        let mut dep = group();
+        prefer_installed(&mut dep, |_| false);
+        assert_eq!(dep.effective_name(), "postfix");
+
+        let mut dep = group();
+        dep.arch_name = None;
+        prefer_installed(&mut dep, |_| false);
+        assert_eq!(dep.effective_name(), "exim");
+    }
+
+    #[tokio::test]
+    async fn test_ignored_dependencies() {
+        let resolver = offline_resolver(MemoryLookup::default().with_mapping("libc6", "glibc"), MockAur::offline())
+            .build()
+            .unwrap()
+            .with_ignored(["dpkg".to_string(), "rexeb-test-noise".to_string()]);
+
+        let mut metadata = PackageMetadata::new("test-ignore", "1.0");
+        let mut libc = Dependency::new("libc6");
+        libc.alternatives.push(Dependency::new("rexeb-test-noise"));
+        metadata.add_dep(DependencyType::Depends, libc);
+        metadata.add_dep(DependencyType::Depends, Dependency::new("rexeb-test-noise"));
+        metadata.add_dep(DependencyType::PreDepends, Dependency::new("dpkg"));
+
+        resolver.resolve(&mut metadata).await.unwrap();
+        let depends = metadata.get_deps(DependencyType::Depends);
+        assert_eq!(depends.len(), 1);
+        assert_eq!(depends[0].effective_name(), "glibc");
+        assert!(depends[0].alternatives.is_empty());
+        assert!(metadata.get_deps(DependencyType::PreDepends).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_with_mock_components() {
+        let mut provider = aur_package("foo-bin");
+        provider.provides = Some(vec!["rexeb-test-foo".to_string()]);
+        let aur = MockAur::new([aur_package("bar"), provider]);
+        let requests = aur.requests.clone();
+        let resolver = offline_resolver(MemoryLookup::default(), aur)
+            .matcher(FixedMatcher::default().with_candidate("libbaz1", "baz", 0.9))
+            .build()
+            .unwrap();
+
+        let mut metadata = PackageMetadata::new("test-mock", "1.0");
+        for name in ["bar", "rexeb-test-foo", "libbaz1", "rexeb-test-nothing"] {
+            metadata.add_dep(DependencyType::Depends, Dependency::new(name));
+        }
+        resolver.resolve(&mut metadata).await.unwrap();
+
+        let names: Vec<_> = metadata.get_deps(DependencyType::Depends).iter().map(|d| d.arch_name.clone()).collect();
+        assert_eq!(names, [Some("bar".into()), Some("foo-bin".into()), Some("baz".into()), None]);
+        assert!(resolver.lookup.is_known_miss("rexeb-test-nothing"));
+
+        // The recorded miss keeps the AUR out of the next resolution
+        let asked = requests.lock().unwrap().len();
+        let mut metadata = PackageMetadata::new("test-mock", "1.0");
+        metadata.add_dep(DependencyType::Depends, Dependency::new("rexeb-test-nothing"));
+        resolver.resolve(&mut metadata).await.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), asked);
+    }
+
+    #[tokio::test]
+    async fn test_soname_provides() {
+        let provides = vec!["libfoo.so=3-64".to_string()];
+        let lookup = MemoryLookup::default().with_package(database::ArchPackageInfo {
+            name: "foo-libs".to_string(),
+            version: "3.1-1".to_string(),
+            description: String::new(),
+            sonames: provides.iter().filter_map(|p| pacman::soname_from_provide(p)).collect(),
+            provides,
+            replaces: Vec::new(),
+            depends: Vec::new(),
+            repo: Some("extra".to_string()),
+        });
+        let resolver = offline_resolver(lookup, MockAur::offline())
+            .matcher(FixedMatcher::default())
+            .build()
+            .unwrap();
+
+        let mut metadata = PackageMetadata::new("test-soname", "1.0");
+        metadata.add_dep(DependencyType::Depends, Dependency::new("libfoo3"));
+        // Only soname 3 is provided, so nothing ships the older library
+        metadata.add_dep(DependencyType::Depends, Dependency::new("libfoo2"));
+        resolver.resolve(&mut metadata).await.unwrap();
+
+        let depends = metadata.get_deps(DependencyType::Depends);
+        assert_eq!(depends[0].arch_name.as_deref(), Some("foo-libs"));
+        assert_eq!(depends[0].confidence, 0.95);
+        assert_eq!(depends[1].arch_name, None);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_unmapped_dependency() {
+        let resolver = offline_resolver(MemoryLookup::default().with_mapping("libc6", "glibc"), MockAur::offline())
+            .matcher(FixedMatcher::default())
+            .build()
+            .unwrap()
+            .fail_on_questions();
+
+        let mut metadata = PackageMetadata::new("test-lenient", "1.0");
+        metadata.add_dep(DependencyType::Depends, Dependency::new("libc6"));
+        metadata.add_dep(DependencyType::Depends, Dependency::new("rexeb-test-debian-only"));
+        // Nothing maps it, which is not an error in itself
+        resolver.resolve(&mut metadata).await.unwrap();
+        assert!(!metadata.get_deps(DependencyType::Depends)[1].is_mapped());
+
+        // --lenient: the package installs without it and still names it
+        assert_eq!(metadata.demote_unmapped(), ["rexeb-test-debian-only"]);
+        let depends: Vec<_> = metadata.get_deps(DependencyType::Depends).iter().map(|d| d.effective_name()).collect();
+        assert_eq!(depends, ["glibc"]);
+        assert_eq!(metadata.get_deps(DependencyType::Suggests)[0].debian_name, "rexeb-test-debian-only");
+        assert!(resolver.installability(&metadata).unsatisfiable().next().is_none());
+        let pkginfo = metadata.to_pkginfo();
+        assert!(!pkginfo.contains("\ndepend = rexeb-test-debian-only"));
+        assert!(pkginfo.contains("optdepend = rexeb-test-debian-only: unmapped Debian dependency"));
+    }
+
+    #[tokio::test]
+    async fn test_ecosystem_resolution() {
+        let repo_package = |name: &str, provides: &[&str]| database::ArchPackageInfo {
+            name: name.to_string(),
+            version: "1.0-1".to_string(),
+            description: String::new(),
+            provides: provides.iter().map(|p| p.to_string()).collect(),
+            replaces: Vec::new(),
+            sonames: Vec::new(),
+            depends: Vec::new(),
+            repo: Some("extra".to_string()),
+        };
+        let lookup = MemoryLookup::default()
+            .with_package(repo_package("python-attrs", &[]))
+            .with_package(repo_package("perl-libwww", &["perl-lwp"]));
+        let aur = MockAur::new([aur_package("nodejs-semver-git"), aur_package("nodejs-semverx"), aur_package("python-foo-bin")]);
+        let resolver = offline_resolver(lookup, aur)
+            .matcher(FixedMatcher::default().with_candidate("python3-foo", "python-attrs", 0.9))
+            .build()
+            .unwrap();
+
+        let mut metadata = PackageMetadata::new("test-ecosystem", "1.0");
+        for name in ["python3-attrs", "libwww-perl", "python3-foo", "node-semver", "ruby-nothing"] {
+            metadata.add_dep(DependencyType::Depends, Dependency::new(name));
+        }
+        resolver.resolve(&mut metadata).await.unwrap();
+
+        let names: Vec<_> = metadata.get_deps(DependencyType::Depends).iter().map(|d| d.arch_name.clone()).collect();
+        assert_eq!(
+            names,
+            [Some("python-attrs".into()), Some("perl-libwww".into()), Some("python-foo-bin".into()), Some("nodejs-semver-git".into()), None]
+        );
+    }
+}
+
+/// Mappings worth remembering once a package converted successfully
+///
+/// Fuzzy, AUR and interactive resolutions; passing them to
+/// [`PackageDatabase::learn`] makes later conversions deterministic.
+pub fn learned_mappings(metadata: &PackageMetadata) -> Vec<database::PackageMapping> {
+    let mut learned: Vec<database::PackageMapping> = Vec::new();
+
+    for dep in metadata.dependencies.values().flatten() {
+        for dep in std::iter::once(dep).chain(&dep.alternatives) {
+            let guessed = matches!(
+                dep.source,
+                Some(ResolutionSource::Fuzzy | ResolutionSource::Aur | ResolutionSource::User)
+            );
+            let Some(ref arch_name) = dep.arch_name else {
+                continue;
+            };
+            if guessed && !learned.iter().any(|m| m.debian_name == dep.debian_name) {
+                learned.push(database::PackageMapping {
+                    debian_name: dep.debian_name.clone(),
+                    arch_name: arch_name.clone(),
+                    confidence: dep.confidence,
+                    source: database::MappingSource::Auto,
+                    version_transforms: Vec::new(),
+                });
+            }
+        }
+    }
+
+    learned
+}
+
+/// Make the alternative to use the primary entry of an `a | b | c` group
+///
+/// An installed mapped alternative wins; otherwise the first entry stays,
+/// unless it is unmapped and a later one is not.
+fn prefer_installed(dep: &mut Dependency, is_installed: impl Fn(&str) -> bool) {
+    let installed = |d: &Dependency| d.arch_name.as_deref().is_some_and(&is_installed);
+    if dep.alternatives.is_empty() || installed(dep) {
+        return;
+    }
+
+    let chosen = dep
+        .alternatives
+        .iter()
+        .position(installed)
+        .or_else(|| (!dep.is_mapped()).then(|| dep.alternatives.iter().position(Dependency::is_mapped)).flatten());
+    if let Some(chosen) = chosen {
+        let mut alternatives = std::mem::take(&mut dep.alternatives);
+        std::mem::swap(dep, &mut alternatives[chosen]);
+        dep.alternatives = alternatives;
+    }
 }
 
 /// Statistics about dependency resolution
@@ -0,0 +1,338 @@
+//! Reader for pacman's own package databases
+//!
+//! Parses the sync databases (`<dbpath>/sync/*.db`) and the local database
+//! (`<dbpath>/local/<name>-<version>/desc`) directly, the same data libalpm
+//! reads, so lookups see the repositories and installed packages of the
+//! running system without linking against libalpm.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::repo::Desc;
+
+use super::database::ArchPackageInfo;
+
+/// Default pacman database directory
+pub const PACMAN_DB_PATH: &str = "/var/lib/pacman";
+
+/// A package from a pacman database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacmanPackage {
+    /// Package name
+    pub name: String,
+    /// Full version (`epoch:pkgver-pkgrel`)
+    pub version: String,
+    /// Package description
+    pub description: String,
+    /// Provided names, without versions
+    pub provides: Vec<String>,
+    /// Replaced packages, without versions
+    pub replaces: Vec<String>,
+    /// Shared libraries provided (`libfoo.so=1-64` gives `libfoo.so.1`)
+    pub sonames: Vec<String>,
+    /// Dependencies, without versions
+    pub depends: Vec<String>,
+    /// Repository the package comes from (`None` for the local database)
+    pub repo: Option<String>,
+}
+
+impl PacmanPackage {
+    /// Build from a `desc` entry
+    fn from_desc(desc: &Desc, repo: Option<&str>) -> Self {
+        let names = |key: &str| desc.get_all(key).iter().map(|v| strip_version(v).to_string()).collect();
+        Self {
+            name: desc.name().to_string(),
+            version: desc.version().to_string(),
+            description: desc.get("DESC").unwrap_or_default().to_string(),
+            provides: names("PROVIDES"),
+            replaces: names("REPLACES"),
+            sonames: desc.get_all("PROVIDES").iter().filter_map(|p| soname_from_provide(p)).collect(),
+            depends: names("DEPENDS"),
+            repo: repo.map(String::from),
+        }
+    }
+
+    /// Convert to the resolver's package info
+    pub fn to_info(&self) -> ArchPackageInfo {
+        ArchPackageInfo {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            description: self.description.clone(),
+            provides: self.provides.clone(),
+            replaces: self.replaces.clone(),
+            sonames: self.sonames.clone(),
+            depends: self.depends.clone(),
+            repo: self.repo.clone(),
+        }
+    }
+}
+
+/// Sync and local package lists of a pacman installation
+#[derive(Debug, Clone, Default)]
+pub struct PacmanDb {
+    /// Packages available from the sync repositories, by name
+    sync: HashMap<String, PacmanPackage>,
+    /// Installed packages, by name
+    local: HashMap<String, PacmanPackage>,
+}
+
+impl PacmanDb {
+    /// Load the databases below `db_path`
+    ///
+    /// Missing directories (e.g. on a non-Arch host) give an empty database.
+    pub fn load(db_path: &Path) -> Result<Self> {
+        let mut db = Self::default();
+
+        for path in sync_databases(db_path, "db") {
+            let repo = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            for desc in read_sync_db(&path)? {
+                db.sync
+                    .entry(desc.name().to_string())
+                    .or_insert_with(|| PacmanPackage::from_desc(&desc, Some(&repo)));
+            }
+        }
+
+        if let Ok(entries) = fs::read_dir(db_path.join("local")) {
+            for entry in entries {
+                let desc_path = entry?.path().join("desc");
+                if desc_path.is_file() {
+                    let desc = Desc::parse(&fs::read_to_string(&desc_path)?);
+                    db.local.insert(desc.name().to_string(), PacmanPackage::from_desc(&desc, None));
+                }
+            }
+        }
+
+        Ok(db)
+    }
+
+    /// Load the system databases
+    pub fn system() -> Result<Self> {
+        Self::load(Path::new(PACMAN_DB_PATH))
+    }
+
+    /// Packages available from the sync repositories
+    pub fn sync_packages(&self) -> impl Iterator<Item = &PacmanPackage> {
+        self.sync.values()
+    }
+
+    /// Installed packages
+    pub fn local_packages(&self) -> impl Iterator<Item = &PacmanPackage> {
+        self.local.values()
+    }
+
+    /// Sync repository package by name
+    pub fn sync_package(&self, name: &str) -> Option<&PacmanPackage> {
+        self.sync.get(name)
+    }
+
+    /// Installed package by name
+    pub fn installed(&self, name: &str) -> Option<&PacmanPackage> {
+        self.local.get(name)
+    }
+
+    /// Whether the databases contain no packages at all
+    pub fn is_empty(&self) -> bool {
+        self.sync.is_empty() && self.local.is_empty()
+    }
+}
+
+/// Owners of the files installed on the system
+///
+/// Read from `<dbpath>/local/*/files`, the lists `pacman -Qo` searches, so
+/// a whole package can be checked without running pacman once per file.
+#[derive(Debug, Clone, Default)]
+pub struct InstalledFiles {
+    /// Path without the leading slash -> owning package
+    owners: HashMap<String, String>,
+}
+
+impl InstalledFiles {
+    /// Load the file lists of the local database below `db_path`
+    ///
+    /// A missing database (e.g. on a non-Arch host) gives an empty index.
+    pub fn load(db_path: &Path) -> Result<Self> {
+        let mut installed = Self::default();
+        let Ok(entries) = fs::read_dir(db_path.join("local")) else {
+            return Ok(installed);
+        };
+
+        for entry in entries {
+            let dir = entry?.path();
+            let (Ok(desc), Ok(files)) = (fs::read_to_string(dir.join("desc")), fs::read_to_string(dir.join("files")))
+            else {
+                continue;
+            };
+            let name = Desc::parse(&desc).name().to_string();
+            // Directories are shared between packages and never conflict
+            for path in Desc::parse(&files).get_all("FILES").iter().filter(|p| !p.ends_with('/')) {
+                installed.owners.insert(path.to_string(), name.clone());
+            }
+        }
+
+        Ok(installed)
+    }
+
+    /// Load the system's local database
+    pub fn system() -> Result<Self> {
+        Self::load(Path::new(PACMAN_DB_PATH))
+    }
+
+    /// Package owning an absolute or root-relative path
+    pub fn owner(&self, path: &Path) -> Option<&str> {
+        let path = path.strip_prefix("/").unwrap_or(path);
+        self.owners.get(path.to_str()?).map(String::as_str)
+    }
+
+    /// Whether no files are indexed
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+}
+
+/// Open a database archive for reading
+///
+/// Databases may be gzip, zstd or xz compressed, or plain tar.
+pub fn open_db_archive(path: &Path) -> Result<tar::Archive<Box<dyn Read>>> {
+    let mut file = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 4];
+    let read = file.read(&mut magic)?;
+    let file = std::io::Cursor::new(magic[..read].to_vec()).chain(file);
+
+    let reader: Box<dyn Read> = match magic {
+        [0x1f, 0x8b, ..] => Box::new(flate2::read::GzDecoder::new(file)),
+        [0x28, 0xb5, 0x2f, 0xfd] => Box::new(zstd::Decoder::new(file)?),
+        [0xfd, 0x37, 0x7a, 0x58] => Box::new(xz2::read::XzDecoder::new(file)),
+        _ => Box::new(file),
+    };
+
+    Ok(tar::Archive::new(reader))
+}
+
+/// Read all `desc` entries of a sync database archive
+pub fn read_sync_db(path: &Path) -> Result<Vec<Desc>> {
+    let mut entries = Vec::new();
+    let mut archive = open_db_archive(path)?;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if entry.path()?.file_name().is_some_and(|n| n == "desc") {
+            let mut content = String::new();
+            entry.read_to_string(&mut content)?;
+            entries.push(Desc::parse(&content));
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Sync database files with the given extension (`db` or `files`)
+///
+/// Sorted by repository name; pacman.conf order is not known here.
+pub fn sync_databases(db_path: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(db_path.join("sync")) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|e| e == extension))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    paths
+}
+
+/// Soname of a library provide as makepkg generates them
+///
+/// `libfoo.so=1-64` (soname version 1, 64-bit) gives `libfoo.so.1`.
+pub fn soname_from_provide(provide: &str) -> Option<String> {
+    let (name, version) = provide.split_once('=')?;
+    if !name.ends_with(".so") {
+        return None;
+    }
+    let version = version.rsplit_once('-').map_or(version, |(v, _)| v);
+    Some(format!("{}.{}", name, version))
+}
+
+/// Package name of a dependency-style string (`libfoo.so=1-64` -> `libfoo.so`)
+fn strip_version(spec: &str) -> &str {
+    spec.split(['=', '<', '>']).next().unwrap_or(spec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use tempfile::TempDir;
+
+    fn append(tar: &mut tar::Builder<impl std::io::Write>, path: &str, content: &str) {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, path, content.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_load_sync_and_local() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path();
+        fs::create_dir_all(db_path.join("sync")).unwrap();
+        fs::create_dir_all(db_path.join("local/foo-bin-2.0-1")).unwrap();
+
+        let file = File::create(db_path.join("sync/extra.db")).unwrap();
+        let mut tar = tar::Builder::new(GzEncoder::new(file, flate2::Compression::default()));
+        append(
+            &mut tar,
+            "libpng-1.6.43-1/desc",
+            "%NAME%\nlibpng\n\n%VERSION%\n1.6.43-1\n\n%DESC%\nPNG library\n\n%PROVIDES%\nlibpng16.so=16-64\n\n",
+        );
+        tar.into_inner().unwrap().finish().unwrap();
+        fs::write(db_path.join("sync/extra.files"), "ignored").unwrap();
+
+        fs::write(
+            db_path.join("local/foo-bin-2.0-1/desc"),
+            "%NAME%\nfoo-bin\n\n%VERSION%\n2.0-1\n\n%PROVIDES%\nrexeb-test-foo=2.0\n\n%REPLACES%\nfoo-old<2\n\n",
+        )
+        .unwrap();
+
+        let db = PacmanDb::load(db_path).unwrap();
+        let libpng = db.sync_package("libpng").unwrap();
+        assert_eq!(libpng.repo.as_deref(), Some("extra"));
+        assert_eq!(libpng.provides, ["libpng16.so"]);
+        assert_eq!(libpng.sonames, ["libpng16.so.16"]);
+        assert_eq!(libpng.description, "PNG library");
+
+        let foo = db.installed("foo-bin").unwrap();
+        assert_eq!(foo.version, "2.0-1");
+        assert_eq!(foo.replaces, ["foo-old"]);
+        assert!(db.sync_package("foo-bin").is_none());
+
+        let mut packages = crate::resolver::PackageDatabase::new().unwrap();
+        packages.merge_pacman_db(&db);
+        assert_eq!(packages.lookup("rexeb-test-foo").unwrap(), Some(("foo-bin".to_string(), 0.9)));
+        assert_eq!(packages.installed_version("foo-bin"), Some("2.0-1"));
+
+        assert!(PacmanDb::load(&db_path.join("missing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_installed_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path();
+        fs::create_dir_all(db_path.join("local/foo-2.0-1")).unwrap();
+        fs::write(db_path.join("local/foo-2.0-1/desc"), "%NAME%\nfoo\n\n%VERSION%\n2.0-1\n\n").unwrap();
+        fs::write(
+            db_path.join("local/foo-2.0-1/files"),
+            "%FILES%\nusr/\nusr/bin/\nusr/bin/foo\n\n%BACKUP%\netc/foo.conf\t0123\n\n",
+        )
+        .unwrap();
+
+        let installed = InstalledFiles::load(db_path).unwrap();
+        assert_eq!(installed.owner(Path::new("/usr/bin/foo")), Some("foo"));
+        assert_eq!(installed.owner(Path::new("usr/bin/foo")), Some("foo"));
+        assert_eq!(installed.owner(Path::new("/usr/bin/")), None);
+        assert_eq!(installed.owner(Path::new("/etc/foo.conf")), None);
+        assert!(InstalledFiles::load(&db_path.join("missing")).unwrap().is_empty());
+    }
+}
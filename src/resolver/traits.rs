@@ -0,0 +1,167 @@
+//! Components the dependency resolver is built from
+//!
+//! The resolver only talks to its package database, fuzzy matcher and AUR
+//! client through these traits, so any of them can be swapped out, e.g. for
+//! the in-memory implementations in [`super::mock`] in tests that must not
+//! touch the filesystem or the network.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::error::Result;
+use crate::models::VersionTransform;
+
+use super::aur::{AurClient, AurPackage};
+use super::database::{ArchPackageInfo, PackageDatabase};
+use super::fuzzy::FuzzyMatcher;
+
+/// Future returned by [`AurApi`] requests
+pub type AurFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Package mappings and Arch package data
+pub trait Lookup: Send + Sync {
+    /// Arch package a Debian name maps to, with a confidence
+    fn lookup(&self, debian_name: &str) -> Result<Option<(String, f32)>>;
+
+    /// Version rewrites of a direct mapping
+    fn version_transforms(&self, debian_name: &str) -> &[VersionTransform];
+
+    /// Package providing a shared library
+    fn soname_provider(&self, soname: &str) -> Option<&str>;
+
+    /// Concrete package for a Debian virtual package, with a confidence
+    fn virtual_provider(&self, name: &str) -> Option<(String, f32)>;
+
+    /// Whether a name is a known virtual package
+    fn is_virtual(&self, name: &str) -> Result<bool>;
+
+    /// Whether the user asked for a Debian package never to be a dependency
+    fn is_dropped(&self, debian_name: &str) -> bool;
+
+    /// Whether the AUR recently had nothing for a Debian name
+    fn is_known_miss(&self, debian_name: &str) -> bool;
+
+    /// Remember Debian names that no source could map
+    fn record_misses(&self, names: &[String]) -> Result<()>;
+
+    /// Description of an Arch or AUR package
+    fn description(&self, name: &str) -> Option<&str>;
+
+    /// Version of an installed package
+    fn installed_version(&self, name: &str) -> Option<&str>;
+
+    /// Whether a package or provide of this name exists, `None` when unknown
+    fn package_exists(&self, name: &str) -> Option<bool>;
+
+    /// Package providing a name
+    fn provider(&self, name: &str) -> Option<&ArchPackageInfo>;
+
+    /// All Arch package names, for fuzzy matching
+    fn package_names(&self) -> Vec<&str>;
+}
+
+/// Approximate matching of Debian names against Arch packages
+pub trait Matcher: Send + Sync {
+    /// Best match scoring above the matcher's threshold
+    fn best_match(&self, debian_name: &str, lookup: &dyn Lookup) -> Result<Option<(String, f32)>>;
+
+    /// Up to `limit` matches, best first
+    fn matches(&self, debian_name: &str, lookup: &dyn Lookup, limit: usize) -> Result<Vec<(String, f32)>>;
+}
+
+/// AUR queries the resolver makes
+pub trait AurApi: Send + Sync {
+    /// Whether requests are disabled
+    fn is_offline(&self) -> bool;
+
+    /// Packages with exactly these names
+    fn info<'a>(&'a self, names: &'a [&'a str]) -> AurFuture<'a, Vec<AurPackage>>;
+
+    /// Keyword search over package names and descriptions
+    fn search<'a>(&'a self, query: &'a str) -> AurFuture<'a, Vec<AurPackage>>;
+
+    /// Packages named like or providing a capability
+    fn find_providers<'a>(&'a self, capability: &'a str) -> AurFuture<'a, Vec<AurPackage>>;
+}
+
+impl Lookup for PackageDatabase {
+    fn lookup(&self, debian_name: &str) -> Result<Option<(String, f32)>> {
+        PackageDatabase::lookup(self, debian_name)
+    }
+
+    fn version_transforms(&self, debian_name: &str) -> &[VersionTransform] {
+        PackageDatabase::version_transforms(self, debian_name)
+    }
+
+    fn soname_provider(&self, soname: &str) -> Option<&str> {
+        PackageDatabase::soname_provider(self, soname)
+    }
+
+    fn virtual_provider(&self, name: &str) -> Option<(String, f32)> {
+        PackageDatabase::virtual_provider(self, name)
+    }
+
+    fn is_virtual(&self, name: &str) -> Result<bool> {
+        PackageDatabase::is_virtual(self, name)
+    }
+
+    fn is_dropped(&self, debian_name: &str) -> bool {
+        PackageDatabase::is_dropped(self, debian_name)
+    }
+
+    fn is_known_miss(&self, debian_name: &str) -> bool {
+        PackageDatabase::is_known_miss(self, debian_name)
+    }
+
+    fn record_misses(&self, names: &[String]) -> Result<()> {
+        PackageDatabase::record_misses(names)
+    }
+
+    fn description(&self, name: &str) -> Option<&str> {
+        PackageDatabase::description(self, name)
+    }
+
+    fn installed_version(&self, name: &str) -> Option<&str> {
+        PackageDatabase::installed_version(self, name)
+    }
+
+    fn package_exists(&self, name: &str) -> Option<bool> {
+        PackageDatabase::package_exists(self, name)
+    }
+
+    fn provider(&self, name: &str) -> Option<&ArchPackageInfo> {
+        PackageDatabase::provider(self, name)
+    }
+
+    fn package_names(&self) -> Vec<&str> {
+        self.get_arch_package_names()
+    }
+}
+
+impl Matcher for FuzzyMatcher {
+    fn best_match(&self, debian_name: &str, lookup: &dyn Lookup) -> Result<Option<(String, f32)>> {
+        self.find_best_match(debian_name, lookup)
+    }
+
+    fn matches(&self, debian_name: &str, lookup: &dyn Lookup, limit: usize) -> Result<Vec<(String, f32)>> {
+        self.find_matches(debian_name, lookup, limit)
+    }
+}
+
+impl AurApi for AurClient {
+    fn is_offline(&self) -> bool {
+        AurClient::is_offline(self)
+    }
+
+    fn info<'a>(&'a self, names: &'a [&'a str]) -> AurFuture<'a, Vec<AurPackage>> {
+        Box::pin(AurClient::info(self, names))
+    }
+
+    fn search<'a>(&'a self, query: &'a str) -> AurFuture<'a, Vec<AurPackage>> {
+        Box::pin(AurClient::search(self, query))
+    }
+
+    fn find_providers<'a>(&'a self, capability: &'a str) -> AurFuture<'a, Vec<AurPackage>> {
+        Box::pin(AurClient::find_providers(self, capability))
+    }
+}
@@ -0,0 +1,176 @@
+//! User-defined package mappings
+//!
+//! Read from `~/.config/rexeb/mappings.toml` and the drop-in files in
+//! `~/.config/rexeb/mappings.d/*.toml` (in name order, later files win):
+//!
+//! ```toml
+//! # Dependencies removed from converted packages
+//! drop = ["debconf", "install-info"]
+//!
+//! [mappings]
+//! libfoo1 = "foo"
+//! "libstdc++6" = "gcc-libs"
+//! libbar2 = { arch = "bar", version_transforms = [{ type = "truncate", components = 2 }] }
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{RexebError, Result};
+use crate::models::VersionTransform;
+
+use super::database::{MappingSource, PackageMapping};
+
+/// Main mapping file, relative to the config directory
+pub const MAPPINGS_FILE: &str = "mappings.toml";
+
+/// Drop-in directory, relative to the config directory
+pub const MAPPINGS_DIR: &str = "mappings.d";
+
+/// Contents of one mapping file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MappingFile {
+    /// Debian packages never mapped, removed from dependency lists
+    drop: Vec<String>,
+    /// Debian name -> Arch package
+    mappings: HashMap<String, MappingEntry>,
+}
+
+/// Right-hand side of a mapping
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MappingEntry {
+    /// Just the Arch package name
+    Name(String),
+    /// Arch package with version rewrites
+    Full {
+        /// Arch package name
+        arch: String,
+        /// Rewrites turning Debian versions into Arch versions
+        #[serde(default)]
+        version_transforms: Vec<VersionTransform>,
+    },
+}
+
+/// Mappings and drops from the user's mapping files
+#[derive(Debug, Clone, Default)]
+pub struct UserMappings {
+    /// Mappings by Debian name
+    pub mappings: HashMap<String, PackageMapping>,
+    /// Debian names to drop
+    pub dropped: HashSet<String>,
+}
+
+impl UserMappings {
+    /// Read the mapping files below a config directory
+    ///
+    /// Missing files are fine; malformed ones are reported with their path.
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let mut user = Self::default();
+
+        for path in mapping_files(config_dir)? {
+            let content = fs::read_to_string(&path)?;
+            let file: MappingFile = toml::from_str(&content)
+                .map_err(|e| RexebError::Config(format!("{}: {}", path.display(), e)))?;
+            user.merge(file);
+        }
+
+        Ok(user)
+    }
+
+    /// Read the mapping files of the current user
+    pub fn system() -> Result<Self> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| RexebError::Config("Could not find config directory".into()))?;
+        Self::load(&config_dir.join("rexeb"))
+    }
+
+    /// Add a file's entries, overriding earlier files
+    fn merge(&mut self, file: MappingFile) {
+        for (debian_name, entry) in file.mappings {
+            let (arch_name, version_transforms) = match entry {
+                MappingEntry::Name(arch) => (arch, Vec::new()),
+                MappingEntry::Full { arch, version_transforms } => (arch, version_transforms),
+            };
+            self.dropped.remove(&debian_name);
+            self.mappings.insert(
+                debian_name.clone(),
+                PackageMapping {
+                    debian_name,
+                    arch_name,
+                    confidence: 1.0,
+                    source: MappingSource::User,
+                    version_transforms,
+                },
+            );
+        }
+
+        for debian_name in file.drop {
+            self.mappings.remove(&debian_name);
+            self.dropped.insert(debian_name);
+        }
+    }
+}
+
+/// `mappings.toml` followed by the drop-ins in name order
+fn mapping_files(config_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    let main = config_dir.join(MAPPINGS_FILE);
+    if main.is_file() {
+        files.push(main);
+    }
+
+    let dir = config_dir.join(MAPPINGS_DIR);
+    if dir.is_dir() {
+        let mut drop_ins: Vec<PathBuf> = fs::read_dir(&dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == "toml"))
+            .collect();
+        drop_ins.sort();
+        files.extend(drop_ins);
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_user_mappings() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_dir = temp_dir.path();
+        fs::create_dir_all(config_dir.join(MAPPINGS_DIR)).unwrap();
+
+        fs::write(
+            config_dir.join(MAPPINGS_FILE),
+            "drop = [\"debconf\", \"install-info\"]\n\n[mappings]\nlibfoo1 = \"foo\"\n\
+             libbar2 = { arch = \"bar\", version_transforms = [{ type = \"truncate\", components = 2 }] }\n",
+        )
+        .unwrap();
+        fs::write(config_dir.join(MAPPINGS_DIR).join("20-late.toml"), "drop = [\"libfoo1\"]\n").unwrap();
+        fs::write(
+            config_dir.join(MAPPINGS_DIR).join("10-early.toml"),
+            "[mappings]\ninstall-info = \"texinfo\"\nlibfoo1 = \"foo-git\"\n",
+        )
+        .unwrap();
+        fs::write(config_dir.join(MAPPINGS_DIR).join("README"), "not toml").unwrap();
+
+        let user = UserMappings::load(config_dir).unwrap();
+        assert_eq!(user.mappings["install-info"].arch_name, "texinfo");
+        assert_eq!(user.mappings["libbar2"].version_transforms, [VersionTransform::Truncate { components: 2 }]);
+        assert!(!user.mappings.contains_key("libfoo1"));
+        assert_eq!(user.dropped, HashSet::from(["debconf".to_string(), "libfoo1".to_string()]));
+
+        fs::write(config_dir.join(MAPPINGS_FILE), "[mappings]\nlibfoo1 = 3\n").unwrap();
+        let err = UserMappings::load(config_dir).unwrap_err();
+        assert!(err.to_string().contains("mappings.toml"));
+    }
+}
@@ -0,0 +1,408 @@
+//! Base system for the sandboxes
+//!
+//! Sandboxes run in a minimal Arch root kept in the cache directory. It is
+//! made with `pacstrap` when arch-install-scripts is installed, otherwise
+//! from the official bootstrap tarball of the configured mirror, checked
+//! against the `sha256sums.txt` published next to it.
+//!
+//! Roots are kept per Arch snapshot date, the release date of the bootstrap
+//! tarball or the day pacstrap ran, and reused until a refresh is asked
+//! for; a refresh bootstraps the current snapshot and drops older roots.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::{Config, NetworkConfig};
+use crate::error::{RexebError, Result};
+
+use super::create_sandbox;
+
+/// Directory below the cache directory holding the root and downloads
+const SANDBOX_DIR: &str = "sandbox";
+
+/// Marker written once the root is complete
+const READY_MARKER: &str = ".rexeb-bootstrap";
+
+/// Packages pacstrap installs
+const BOOTSTRAP_PACKAGES: &[&str] = &["base"];
+
+/// Directory of the bootstrap tarball, relative to the mirror base
+const ISO_PATH: &str = "iso/latest";
+
+/// Directory below the sandbox directory holding one root per snapshot date
+const ROOTS_DIR: &str = "roots";
+
+/// Format of snapshot dates, as in the release file names
+const DATE_FORMAT: &str = "%Y.%m.%d";
+
+lazy_static::lazy_static! {
+    /// Dated bootstrap tarball in `sha256sums.txt`
+    static ref RELEASE_RE: regex::Regex =
+        regex::Regex::new(r"archlinux-bootstrap-(\d{4}\.\d{2}\.\d{2})-").unwrap();
+}
+
+/// Creates and finds the sandbox root
+pub struct Bootstrap {
+    /// Directory holding the roots and downloads
+    dir: PathBuf,
+    /// Mirror, proxy and offline settings
+    network: NetworkConfig,
+    /// Bootstrap the current snapshot even if a root is cached
+    refresh: bool,
+}
+
+impl Bootstrap {
+    /// Bootstrap into `dir`
+    pub fn new(dir: impl Into<PathBuf>, network: NetworkConfig) -> Self {
+        Self {
+            dir: dir.into(),
+            network,
+            refresh: false,
+        }
+    }
+
+    /// Bootstrap into the cache directory
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.cache_dir().join(SANDBOX_DIR), config.network.clone())
+    }
+
+    /// Bootstrap the current snapshot instead of reusing a cached root
+    pub fn with_refresh(mut self, refresh: bool) -> Self {
+        self.refresh = refresh;
+        self
+    }
+
+    /// The directory holding everything bootstrapped
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The root directory for a snapshot date
+    pub fn root(&self, date: &str) -> PathBuf {
+        self.dir.join(ROOTS_DIR).join(date)
+    }
+
+    /// Snapshot dates of the complete roots, oldest first
+    pub fn cached(&self) -> Result<Vec<String>> {
+        let dir = self.dir.join(ROOTS_DIR);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut dates = Vec::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            if entry.path().join(READY_MARKER).exists() {
+                dates.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        dates.sort();
+        Ok(dates)
+    }
+
+    /// The newest complete root
+    pub fn current(&self) -> Result<Option<PathBuf>> {
+        Ok(self.cached()?.last().map(|date| self.root(date)))
+    }
+
+    /// Whether a root was bootstrapped completely
+    pub fn is_ready(&self) -> bool {
+        self.current().is_ok_and(|root| root.is_some())
+    }
+
+    /// Return the newest root, bootstrapping one if none is cached or a
+    /// refresh was asked for
+    pub async fn ensure(&self) -> Result<PathBuf> {
+        if !self.refresh {
+            if let Some(root) = self.current()? {
+                return Ok(root);
+            }
+        }
+        std::fs::create_dir_all(&self.dir)?;
+
+        let root = if which("pacstrap") {
+            let root = self.fresh_root(&today())?;
+            tracing::info!("Bootstrapping the sandbox root with pacstrap");
+            let mut cmd = Command::new("sudo");
+            cmd.arg("pacstrap").arg("-c").arg(&root).args(BOOTSTRAP_PACKAGES);
+            run(&mut cmd, "pacstrap")?;
+            root
+        } else {
+            self.unpack_tarball().await?
+        };
+
+        std::fs::write(root.join(READY_MARKER), crate::VERSION)?;
+        self.prune(&root)?;
+        Ok(root)
+    }
+
+    /// An empty directory for the root of a snapshot date
+    fn fresh_root(&self, date: &str) -> Result<PathBuf> {
+        let root = self.root(date);
+        if root.exists() {
+            remove(&root)?;
+        }
+        std::fs::create_dir_all(&root)?;
+        Ok(root)
+    }
+
+    /// Remove every cached root but `keep`
+    fn prune(&self, keep: &Path) -> Result<()> {
+        for date in self.cached()? {
+            let root = self.root(&date);
+            if root != keep {
+                tracing::info!("Removing the sandbox root of {}", date);
+                remove(&root)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the roots, snapshots and downloads; false if there were none
+    pub fn clean(&self) -> Result<bool> {
+        if !self.dir.exists() {
+            return Ok(false);
+        }
+        remove(&self.dir)?;
+        Ok(true)
+    }
+
+    /// Copy the newest root, for runs that must not change it
+    pub async fn snapshot(&self) -> Result<Snapshot> {
+        let root = self.ensure().await?;
+        let path = self.dir.join(format!("snapshot-{}", std::process::id()));
+        let mut cmd = Command::new("sudo");
+        cmd.args(["cp", "-a", "--reflink=auto"]).arg(&root).arg(&path);
+        run(&mut cmd, "cp")?;
+        Ok(Snapshot { path })
+    }
+
+    /// Unpack the official bootstrap tarball and set up pacman in it,
+    /// returning the root
+    async fn unpack_tarball(&self) -> Result<PathBuf> {
+        let arch = std::env::consts::ARCH;
+        if arch != "x86_64" {
+            return Err(RexebError::Other(format!(
+                "No bootstrap tarball is published for {}; install arch-install-scripts to use pacstrap",
+                arch
+            )));
+        }
+        if self.network.offline {
+            return Err(RexebError::Network("offline mode is enabled".into()));
+        }
+
+        let file = format!("archlinux-bootstrap-{}.tar.zst", arch);
+        let base = format!("{}/{}", mirror_base(&self.network.mirror), ISO_PATH);
+        let tarball = self.dir.join(&file);
+        let client = self.network.http_client()?;
+
+        let sums = client
+            .get(format!("{}/sha256sums.txt", base))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let expected = published_checksum(&sums, &file)
+            .ok_or_else(|| RexebError::Validation(format!("{} is not listed in sha256sums.txt", file)))?;
+
+        let date = release_date(&sums).unwrap_or_else(today);
+        if self.cached()?.contains(&date) {
+            tracing::info!("The sandbox root of {} is the latest release", date);
+            return Ok(self.root(&date));
+        }
+        let root = self.fresh_root(&date)?;
+
+        if crate::converter::ConversionCache::file_sha256(&tarball).ok().as_deref() != Some(expected.as_str()) {
+            tracing::info!("Downloading {}/{}", base, file);
+            let mut response = client.get(format!("{}/{}", base, file)).send().await?.error_for_status()?;
+            let part = tarball.with_extension("zst.part");
+            let mut out = std::fs::File::create(&part)?;
+            while let Some(chunk) = response.chunk().await? {
+                out.write_all(&chunk)?;
+            }
+            out.sync_all()?;
+
+            let actual = crate::converter::ConversionCache::file_sha256(&part)?;
+            if actual != expected {
+                std::fs::remove_file(&part)?;
+                return Err(RexebError::Validation(format!(
+                    "bootstrap tarball checksum mismatch (expected {}, got {})",
+                    expected, actual
+                )));
+            }
+            std::fs::rename(&part, &tarball)?;
+        }
+
+        // The tarball's top directory is root.<arch>
+        let mut cmd = Command::new("sudo");
+        cmd.args(["tar", "--zstd", "-xpf"])
+            .arg(&tarball)
+            .arg("-C")
+            .arg(&root)
+            .args(["--strip-components=1", "--numeric-owner"]);
+        run(&mut cmd, "tar")?;
+
+        // The shipped mirrorlist is all comments
+        let mut tee = Command::new("sudo")
+            .arg("tee")
+            .arg(root.join("etc/pacman.d/mirrorlist"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+        if let Some(stdin) = tee.stdin.as_mut() {
+            writeln!(stdin, "Server = {}", self.network.mirror)?;
+        }
+        if !tee.wait()?.success() {
+            return Err(RexebError::Other("Writing the sandbox mirrorlist failed".into()));
+        }
+
+        let mut sandbox = create_sandbox(&root)?;
+        sandbox.init()?;
+        for args in [&["--init"][..], &["--populate", "archlinux"]] {
+            let code = sandbox.run_command("pacman-key", args).await?;
+            if code != 0 {
+                return Err(RexebError::Other(format!("pacman-key {} failed with exit code {}", args[0], code)));
+            }
+        }
+
+        Ok(root)
+    }
+}
+
+/// Throwaway copy of the root, removed when dropped
+pub struct Snapshot {
+    path: PathBuf,
+}
+
+impl Snapshot {
+    /// Root directory of the copy
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        let _ = remove(&self.path);
+    }
+}
+
+/// Remove a directory tree, through sudo where its files belong to root
+fn remove(path: &Path) -> Result<()> {
+    match std::fs::remove_dir_all(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            run(Command::new("sudo").arg("rm").arg("-rf").arg(path), "rm")
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Today's date as a snapshot date
+fn today() -> String {
+    chrono::Utc::now().format(DATE_FORMAT).to_string()
+}
+
+/// Release date of the bootstrap tarball listed in `sha256sums.txt`
+fn release_date(sums: &str) -> Option<String> {
+    RELEASE_RE.captures(sums).map(|c| c[1].to_string())
+}
+
+/// Whether a program is on the PATH
+fn which(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Run a command, failing unless it succeeds
+fn run(cmd: &mut Command, name: &str) -> Result<()> {
+    let status = cmd.status()?;
+    if !status.success() {
+        return Err(RexebError::Other(format!("{} failed with exit code {}", name, status.code().unwrap_or(-1))));
+    }
+    Ok(())
+}
+
+/// Mirror URL without the `$repo/os/$arch` part
+fn mirror_base(mirror: &str) -> &str {
+    mirror
+        .find("/$repo")
+        .map_or(mirror, |end| &mirror[..end])
+        .trim_end_matches('/')
+}
+
+/// Checksum of a file in `sha256sum` output
+fn published_checksum(sums: &str, file: &str) -> Option<String> {
+    sums.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let hash = fields.next()?;
+        (fields.next()?.trim_start_matches('*') == file).then(|| hash.to_lowercase())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bootstrap_sources() {
+        assert_eq!(mirror_base("https://geo.mirror.pkgbuild.com/$repo/os/$arch"), "https://geo.mirror.pkgbuild.com");
+        assert_eq!(mirror_base("https://mirror.example/arch/"), "https://mirror.example/arch");
+
+        let sums = "0a1b  archlinux-2026.10.01-x86_64.iso\n\
+                    4e5f  archlinux-bootstrap-2026.10.01-x86_64.tar.zst\n\
+                    C2D3  archlinux-bootstrap-x86_64.tar.zst\n";
+        assert_eq!(published_checksum(sums, "archlinux-bootstrap-x86_64.tar.zst").as_deref(), Some("c2d3"));
+        assert_eq!(published_checksum(sums, "archlinux-bootstrap-x86_64.tar.gz"), None);
+        assert_eq!(release_date(sums).as_deref(), Some("2026.10.01"));
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bootstrap = Bootstrap::new(temp_dir.path(), NetworkConfig::default());
+        assert!(!bootstrap.is_ready());
+        for date in ["2026.10.01", "2026.09.01", "2026.11.01"] {
+            std::fs::create_dir_all(bootstrap.root(date)).unwrap();
+        }
+        for date in ["2026.09.01", "2026.10.01"] {
+            std::fs::write(bootstrap.root(date).join(READY_MARKER), "").unwrap();
+        }
+        assert!(bootstrap.is_ready());
+        assert_eq!(bootstrap.cached().unwrap(), ["2026.09.01", "2026.10.01"]);
+        assert_eq!(bootstrap.current().unwrap(), Some(bootstrap.root("2026.10.01")));
+    }
+
+    #[test]
+    fn test_snapshot_roots() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let bootstrap = Bootstrap::new(temp_dir.path().join("sandbox"), NetworkConfig::default());
+        let ready = |date: &str| {
+            let root = bootstrap.fresh_root(date).unwrap();
+            std::fs::write(root.join(READY_MARKER), "").unwrap();
+            root
+        };
+
+        // One root per snapshot date, named so dates sort in order
+        assert_eq!(bootstrap.root("2026.10.01"), temp_dir.path().join("sandbox/roots/2026.10.01"));
+        assert!(chrono::NaiveDate::parse_from_str(&today(), DATE_FORMAT).is_ok());
+        assert_eq!(release_date("1234  archlinux-bootstrap-x86_64.tar.zst\n"), None);
+
+        // Refreshing replaces a root of the same date
+        let root = ready("2026.09.01");
+        std::fs::write(root.join("stale"), "").unwrap();
+        assert!(!bootstrap.fresh_root("2026.09.01").unwrap().join("stale").exists());
+
+        // A new snapshot drops the older ones
+        ready("2026.09.01");
+        ready("2026.09.15");
+        let newest = ready("2026.10.01");
+        bootstrap.prune(&newest).unwrap();
+        assert_eq!(bootstrap.cached().unwrap(), ["2026.10.01"]);
+        assert_eq!(bootstrap.current().unwrap(), Some(newest));
+
+        // clean --sandbox
+        assert!(bootstrap.clean().unwrap());
+        assert!(!bootstrap.dir().exists());
+        assert!(!bootstrap.is_ready());
+        assert!(!bootstrap.clean().unwrap());
+    }
+}
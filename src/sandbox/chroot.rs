@@ -0,0 +1,140 @@
+//! chroot + unshare sandbox implementation
+//!
+//! For minimal systems without systemd or a container runtime. Commands
+//! run in fresh mount, PID, IPC and UTS namespaces created by `unshare`,
+//! with `/proc`, a minimal `/dev` and the host's `resolv.conf` mounted into
+//! the root before `chroot`. The mounts live in the private mount namespace
+//! and go away with the command.
+//!
+//! This is not an isolation boundary. Commands run as the host's root, with
+//! no user namespace, so a hostile package can escape the chroot. It keeps
+//! test installs off the host filesystem, nothing more.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::error::{RexebError, Result};
+use super::process::run_process;
+use super::{check_root, copy_from_root, copy_into_root, RunOptions, Sandbox, SandboxFuture};
+
+/// Mounts the root's API filesystems, then runs the command chrooted
+///
+/// `/dev` is a fresh tmpfs with only the basic device nodes bound in, so
+/// the host's disks are out of reach.
+/// Arguments are the root followed by the command and its arguments.
+const ENTER_SCRIPT: &str = r#"set -e
+root=$1
+shift
+mount -t proc proc "$root/proc"
+mount -t tmpfs -o mode=755,nosuid dev "$root/dev"
+for node in null zero full random urandom tty; do
+    : > "$root/dev/$node"
+    mount --bind "/dev/$node" "$root/dev/$node"
+done
+ln -s /proc/self/fd "$root/dev/fd"
+ln -s /proc/self/fd/0 "$root/dev/stdin"
+ln -s /proc/self/fd/1 "$root/dev/stdout"
+ln -s /proc/self/fd/2 "$root/dev/stderr"
+if [ -f /etc/resolv.conf ]; then
+    if [ ! -e "$root/etc/resolv.conf" ]; then
+        rm -f "$root/etc/resolv.conf"
+        : > "$root/etc/resolv.conf"
+    fi
+    mount --bind /etc/resolv.conf "$root/etc/resolv.conf"
+fi
+exec chroot "$root" "$@"
+"#;
+
+/// Sandbox using chroot in namespaces created by unshare
+pub struct ChrootSandbox {
+    root_dir: PathBuf,
+}
+
+impl ChrootSandbox {
+    /// Create a new chroot sandbox
+    pub fn new(root_dir: &Path) -> Result<Self> {
+        Ok(Self {
+            root_dir: root_dir.to_path_buf(),
+        })
+    }
+
+    /// Check if unshare and chroot are available
+    pub fn is_available() -> bool {
+        ["unshare", "chroot"].iter().all(|tool| {
+            Command::new(tool)
+                .arg("--version")
+                .output()
+                .is_ok_and(|output| output.status.success())
+        })
+    }
+
+    /// Arguments of the `unshare` invocation running `command`
+    fn unshare_args(&self, command: &str, args: &[&str], network: bool) -> Vec<String> {
+        let mut argv: Vec<String> = ["unshare", "--mount", "--pid", "--fork", "--ipc", "--uts", "--propagation", "private"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        if !network {
+            // A network namespace of its own has only a loopback interface
+            argv.push("--net".to_string());
+        }
+        argv.extend(["sh", "-c", ENTER_SCRIPT, "rexeb-sandbox"].map(String::from));
+        argv.push(self.root_dir.to_string_lossy().into_owned());
+        argv.push(command.to_string());
+        argv.extend(args.iter().map(|a| a.to_string()));
+        argv
+    }
+}
+
+impl Sandbox for ChrootSandbox {
+    fn init(&mut self) -> Result<()> {
+        if !Self::is_available() {
+            return Err(RexebError::Other("unshare or chroot not found".into()));
+        }
+
+        check_root(&self.root_dir)?;
+
+        // Mount points for the API filesystems
+        for dir in ["proc", "dev", "etc"] {
+            std::fs::create_dir_all(self.root_dir.join(dir))?;
+        }
+
+        Ok(())
+    }
+
+    fn run<'a>(&'a self, command: &'a str, args: &'a [&'a str], options: RunOptions) -> SandboxFuture<'a, i32> {
+        let mut cmd = tokio::process::Command::new("sudo");
+        cmd.args(self.unshare_args(command, args, options.network));
+
+        Box::pin(run_process(cmd, options))
+    }
+
+    fn copy_in(&self, src: &Path, dest: &Path) -> Result<()> {
+        copy_into_root(&self.root_dir, src, dest)
+    }
+
+    fn copy_out(&self, src: &Path, dest: &Path) -> Result<()> {
+        copy_from_root(&self.root_dir, src, dest)
+    }
+
+    fn cleanup(&mut self) -> Result<()> {
+        // Mounts disappear with the mount namespace; the root is the caller's
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unshare_args() {
+        let sandbox = ChrootSandbox::new(Path::new("/tmp/root")).unwrap();
+        let argv = sandbox.unshare_args("pacman", &["-U", "/tmp/foo.pkg.tar.zst"], true);
+        assert_eq!(argv[0], "unshare");
+        assert!(!argv.contains(&"--net".to_string()));
+        assert_eq!(argv[argv.len() - 4..], ["/tmp/root", "pacman", "-U", "/tmp/foo.pkg.tar.zst"]);
+        assert!(argv.iter().any(|a| a.contains("mount --bind /etc/resolv.conf")));
+        assert!(!argv.iter().any(|a| a.contains("--rbind /dev")));
+        assert!(sandbox.unshare_args("sh", &[], false).contains(&"--net".to_string()));
+    }
+}
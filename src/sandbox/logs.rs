@@ -0,0 +1,108 @@
+//! Logs of sandbox runs
+//!
+//! Every command a run executes in the sandbox gets a log file holding the
+//! command, its exit code and everything it printed, next to a `run.json`
+//! index and the run's report. Runs are kept below `<data_dir>/logs`, one
+//! directory each, so the output of a failed test can be read afterwards.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::Result;
+
+/// Directory below the data directory holding the runs
+const LOGS_DIR: &str = "logs";
+
+/// A command run in the sandbox
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// What the command was for
+    pub name: String,
+    /// The command line
+    pub command: String,
+    /// Exit code of the command
+    pub exit_code: i32,
+    /// Log file of the output
+    pub file: PathBuf,
+}
+
+/// Log directory of one run
+pub struct RunLog {
+    dir: PathBuf,
+    entries: Vec<LogEntry>,
+}
+
+impl RunLog {
+    /// Start a run below `base`, in a directory named after the time and `label`
+    pub fn create(base: &Path, label: &str) -> Result<Self> {
+        let label: String = label
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "._-".contains(c) { c } else { '_' })
+            .collect();
+        let dir = base.join(format!("{}-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), label));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir, entries: Vec::new() })
+    }
+
+    /// Start a run below the data directory
+    pub fn from_config(config: &Config, label: &str) -> Result<Self> {
+        Self::create(&config.data_dir().join(LOGS_DIR), label)
+    }
+
+    /// The run's directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Commands logged so far
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    /// Write a command's output to its own file and return the file
+    pub fn record(&mut self, name: &str, command: &str, exit_code: i32, output: &str) -> Result<PathBuf> {
+        let slug: String = name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        let file = self.dir.join(format!("{:02}-{}.log", self.entries.len() + 1, slug.trim_matches('-')));
+        std::fs::write(&file, format!("$ {}\n# exit code {}\n\n{}", command, exit_code, output))?;
+
+        self.entries.push(LogEntry {
+            name: name.to_string(),
+            command: command.to_string(),
+            exit_code,
+            file: file.clone(),
+        });
+        Ok(file)
+    }
+
+    /// Write the index and the run's report
+    pub fn finish(&self, report: &impl Serialize) -> Result<()> {
+        std::fs::write(self.dir.join("run.json"), serde_json::to_string_pretty(&self.entries)?)?;
+        std::fs::write(self.dir.join("report.json"), serde_json::to_string_pretty(report)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_log() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut log = RunLog::create(temp_dir.path(), "foo 1.0/1").unwrap();
+        assert!(log.dir().file_name().unwrap().to_string_lossy().ends_with("-foo_1.0_1"));
+
+        let file = log.record("/usr/bin/foo --version", "sh -c ...", 127, "not found\n").unwrap();
+        assert_eq!(file.file_name().unwrap(), "01-usr-bin-foo---version.log");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "$ sh -c ...\n# exit code 127\n\nnot found\n");
+
+        log.finish(&"report").unwrap();
+        let index: Vec<LogEntry> = serde_json::from_slice(&std::fs::read(log.dir().join("run.json")).unwrap()).unwrap();
+        assert_eq!(index, log.entries());
+    }
+}
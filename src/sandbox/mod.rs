@@ -1,19 +1,41 @@
 //! Sandbox environment for package testing and building
 
+mod bootstrap;
+mod chroot;
+mod logs;
 mod nspawn;
+mod process;
+mod trace;
+mod verify;
 
+pub use bootstrap::{Bootstrap, Snapshot};
+pub use chroot::ChrootSandbox;
+pub use logs::{LogEntry, RunLog};
 pub use nspawn::NspawnSandbox;
+pub use process::{Cancellation, OutputLine, RunOptions, SandboxFuture};
+pub use trace::{trace_maintainer_scripts, ScriptTrace, ScriptTracer};
+pub use verify::{InstallTest, TestReport, TestStep};
 
 use std::path::Path;
-use crate::error::Result;
+use std::process::Command;
+use crate::config::Config;
+use crate::error::{RexebError, Result};
 
 /// Trait for sandbox implementations
-pub trait Sandbox {
+pub trait Sandbox: Send + Sync {
     /// Initialize the sandbox
     fn init(&mut self) -> Result<()>;
 
-    /// Run a command inside the sandbox
-    fn run_command(&self, command: &str, args: &[&str]) -> Result<i32>;
+    /// Run a command inside the sandbox, returning its exit code
+    ///
+    /// Fails with [`RexebError::Cancelled`] once the options' cancellation
+    /// fires, after stopping the command.
+    fn run<'a>(&'a self, command: &'a str, args: &'a [&'a str], options: RunOptions) -> SandboxFuture<'a, i32>;
+
+    /// Run a command inside the sandbox with network access
+    fn run_command<'a>(&'a self, command: &'a str, args: &'a [&'a str]) -> SandboxFuture<'a, i32> {
+        self.run(command, args, RunOptions::new())
+    }
 
     /// Copy a file into the sandbox
     fn copy_in(&self, src: &Path, dest: &Path) -> Result<()>;
@@ -25,8 +47,84 @@ pub trait Sandbox {
     fn cleanup(&mut self) -> Result<()>;
 }
 
-/// Create a new default sandbox
+/// Create a new sandbox with the best backend available
+///
+/// systemd-nspawn when systemd runs the host, otherwise chroot in
+/// namespaces created by unshare. The chroot backend runs commands as the
+/// host's root and is not an isolation boundary; don't use it to contain
+/// untrusted packages.
 pub fn create_sandbox(root_dir: &Path) -> Result<Box<dyn Sandbox>> {
-    // For now, we only support systemd-nspawn
-    Ok(Box::new(NspawnSandbox::new(root_dir)?))
-}
\ No newline at end of file
+    if NspawnSandbox::is_available() {
+        return Ok(Box::new(NspawnSandbox::new(root_dir)?));
+    }
+    if ChrootSandbox::is_available() {
+        return Ok(Box::new(ChrootSandbox::new(root_dir)?));
+    }
+    Err(RexebError::Other("No sandbox backend available; install systemd-nspawn or util-linux".into()))
+}
+
+/// Create a sandbox on the cached base system, bootstrapping it first if
+/// needed or if `refresh` asks for the current snapshot
+pub async fn create_bootstrapped_sandbox(config: &Config, refresh: bool) -> Result<Box<dyn Sandbox>> {
+    let root = Bootstrap::from_config(config).with_refresh(refresh).ensure().await?;
+    create_sandbox(&root)
+}
+
+/// Fail unless a root holds an Arch system
+fn check_root(root_dir: &Path) -> Result<()> {
+    if !root_dir.join("usr/bin/pacman").exists() {
+        return Err(RexebError::Other(format!(
+            "{} holds no Arch system; bootstrap it with sandbox::Bootstrap",
+            root_dir.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Copy a file or directory into a sandbox root
+fn copy_into_root(root_dir: &Path, src: &Path, dest: &Path) -> Result<()> {
+    let target = root_dir.join(dest.strip_prefix("/").unwrap_or(dest));
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    if src.is_dir() {
+        // Recursive copy
+        // For simplicity using cp command
+        Command::new("cp")
+            .arg("-r")
+            .arg(src)
+            .arg(&target)
+            .status()?;
+    } else {
+        std::fs::copy(src, &target)?;
+    }
+
+    Ok(())
+}
+
+/// Copy a file or directory out of a sandbox root
+fn copy_from_root(root_dir: &Path, src: &Path, dest: &Path) -> Result<()> {
+    let source = root_dir.join(src.strip_prefix("/").unwrap_or(src));
+
+    if !source.exists() {
+        return Err(RexebError::file_not_found(&source));
+    }
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    if source.is_dir() {
+        Command::new("cp")
+            .arg("-r")
+            .arg(&source)
+            .arg(dest)
+            .status()?;
+    } else {
+        std::fs::copy(&source, dest)?;
+    }
+
+    Ok(())
+}
@@ -3,7 +3,8 @@
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use crate::error::{RexebError, Result};
-use super::Sandbox;
+use super::process::run_process;
+use super::{check_root, copy_from_root, copy_into_root, RunOptions, Sandbox, SandboxFuture};
 
 /// Sandbox using systemd-nspawn
 pub struct NspawnSandbox {
@@ -18,6 +19,11 @@ impl NspawnSandbox {
         })
     }
 
+    /// Check if systemd-nspawn is available and systemd runs the host
+    pub fn is_available() -> bool {
+        Path::new("/run/systemd/system").is_dir() && Self::check_availability().is_ok()
+    }
+
     /// Check if systemd-nspawn is available
     fn check_availability() -> Result<()> {
         let status = Command::new("systemd-nspawn")
@@ -30,85 +36,48 @@ impl NspawnSandbox {
         }
         Ok(())
     }
+
+    /// Arguments of the `systemd-nspawn` invocation running `command`
+    fn nspawn_args(&self, command: &str, args: &[&str], network: bool) -> Vec<String> {
+        let mut argv = vec![
+            "systemd-nspawn".to_string(),
+            "-D".to_string(),
+            self.root_dir.to_string_lossy().into_owned(),
+            "--as-pid2".to_string(), // Run as PID 2 (init is PID 1)
+        ];
+        if !network {
+            // Only a loopback interface
+            argv.push("--private-network".to_string());
+        }
+        argv.push(command.to_string());
+        argv.extend(args.iter().map(|a| a.to_string()));
+        argv
+    }
 }
 
 impl Sandbox for NspawnSandbox {
     fn init(&mut self) -> Result<()> {
         Self::check_availability()?;
         
-        // Ensure root directory exists
-        if !self.root_dir.exists() {
-            std::fs::create_dir_all(&self.root_dir)?;
-        }
+        // The base system comes from Bootstrap
+        check_root(&self.root_dir)?;
 
-        // Initialize a minimal Arch system if empty
-        // In a real scenario, we might want to bootstrap pacstrap here
-        // For now, we assume the user provides a rootfs or we use a temporary one
-        
         Ok(())
     }
 
-    fn run_command(&self, command: &str, args: &[&str]) -> Result<i32> {
-        let mut cmd = Command::new("sudo");
-        cmd.arg("systemd-nspawn")
-           .arg("-D")
-           .arg(&self.root_dir)
-           .arg("--as-pid2") // Run as PID 2 (init is PID 1)
-           .arg(command);
-        
-        for arg in args {
-            cmd.arg(arg);
-        }
+    fn run<'a>(&'a self, command: &'a str, args: &'a [&'a str], options: RunOptions) -> SandboxFuture<'a, i32> {
+        let mut cmd = tokio::process::Command::new("sudo");
+        cmd.args(self.nspawn_args(command, args, options.network));
 
-        let status = cmd.status()?;
-        
-        Ok(status.code().unwrap_or(-1))
+        Box::pin(run_process(cmd, options))
     }
 
     fn copy_in(&self, src: &Path, dest: &Path) -> Result<()> {
-        let target = self.root_dir.join(dest.strip_prefix("/").unwrap_or(dest));
-        
-        if let Some(parent) = target.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-        
-        if src.is_dir() {
-            // Recursive copy
-            // For simplicity using cp command
-            Command::new("cp")
-                .arg("-r")
-                .arg(src)
-                .arg(&target)
-                .status()?;
-        } else {
-            std::fs::copy(src, &target)?;
-        }
-        
-        Ok(())
+        copy_into_root(&self.root_dir, src, dest)
     }
 
     fn copy_out(&self, src: &Path, dest: &Path) -> Result<()> {
-        let source = self.root_dir.join(src.strip_prefix("/").unwrap_or(src));
-        
-        if !source.exists() {
-            return Err(RexebError::file_not_found(&source));
-        }
-
-        if let Some(parent) = dest.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
-
-        if source.is_dir() {
-            Command::new("cp")
-                .arg("-r")
-                .arg(&source)
-                .arg(dest)
-                .status()?;
-        } else {
-            std::fs::copy(&source, dest)?;
-        }
-
-        Ok(())
+        copy_from_root(&self.root_dir, src, dest)
     }
 
     fn cleanup(&mut self) -> Result<()> {
@@ -116,4 +85,22 @@ impl Sandbox for NspawnSandbox {
         // but if we created a persistent root, we might want to keep it or delete it based on config
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nspawn_args() {
+        let sandbox = NspawnSandbox::new(Path::new("/tmp/root")).unwrap();
+        assert_eq!(
+            sandbox.nspawn_args("pacman", &["-U", "/tmp/foo.pkg.tar.zst"], true),
+            ["systemd-nspawn", "-D", "/tmp/root", "--as-pid2", "pacman", "-U", "/tmp/foo.pkg.tar.zst"]
+        );
+        assert_eq!(
+            sandbox.nspawn_args("sh", &["-c", "true"], false),
+            ["systemd-nspawn", "-D", "/tmp/root", "--as-pid2", "--private-network", "sh", "-c", "true"]
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Running sandbox commands
+//!
+//! Backends build the host command that enters the sandbox and hand it to
+//! [`run_process`], which streams its output line by line to the caller
+//! and stops it when the run is cancelled.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+use crate::error::{RexebError, Result};
+
+/// Future returned by [`super::Sandbox`] commands
+pub type SandboxFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// Time a cancelled command gets to exit after SIGTERM
+const TERMINATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A line a sandbox command printed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputLine {
+    /// Line on standard output
+    Stdout(String),
+    /// Line on standard error
+    Stderr(String),
+}
+
+impl OutputLine {
+    /// The line without its stream
+    pub fn text(&self) -> &str {
+        match self {
+            Self::Stdout(line) | Self::Stderr(line) => line,
+        }
+    }
+}
+
+/// Stops running sandbox commands; clones share the state
+#[derive(Debug, Clone)]
+pub struct Cancellation {
+    sender: Arc<watch::Sender<bool>>,
+    receiver: watch::Receiver<bool>,
+}
+
+impl Cancellation {
+    /// A cancellation that has not happened
+    pub fn new() -> Self {
+        let (sender, receiver) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+            receiver,
+        }
+    }
+
+    /// Cancel every command run with this or a clone of it
+    pub fn cancel(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Whether the run was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Wait until the run is cancelled
+    pub async fn cancelled(&self) {
+        let mut receiver = self.receiver.clone();
+        let _ = receiver.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+impl Default for Cancellation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How a sandbox command runs
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+    /// Whether the command gets network access
+    pub network: bool,
+    /// Where output lines go; without one the command prints to the terminal
+    pub output: Option<mpsc::UnboundedSender<OutputLine>>,
+    /// Cancellation stopping the command
+    pub cancel: Cancellation,
+}
+
+impl RunOptions {
+    /// Network access, output on the terminal, no cancellation
+    pub fn new() -> Self {
+        Self {
+            network: true,
+            output: None,
+            cancel: Cancellation::new(),
+        }
+    }
+
+    /// Give or deny network access
+    pub fn network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Stream output lines to a channel
+    pub fn output(mut self, output: mpsc::UnboundedSender<OutputLine>) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Stop the command when `cancel` fires
+    pub fn cancel(mut self, cancel: Cancellation) -> Self {
+        self.cancel = cancel;
+        self
+    }
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run a host command entering the sandbox, returning its exit code
+pub(super) async fn run_process(mut cmd: Command, options: RunOptions) -> Result<i32> {
+    if options.cancel.is_cancelled() {
+        return Err(RexebError::Cancelled("sandbox run".into()));
+    }
+    if options.output.is_some() {
+        cmd.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+    let mut child = cmd.spawn()?;
+
+    let mut readers = Vec::new();
+    if let Some(output) = &options.output {
+        if let Some(stdout) = child.stdout.take() {
+            readers.push(forward(stdout, output.clone(), OutputLine::Stdout));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            readers.push(forward(stderr, output.clone(), OutputLine::Stderr));
+        }
+    }
+    drop(options.output);
+
+    let status = tokio::select! {
+        status = child.wait() => status?,
+        _ = options.cancel.cancelled() => {
+            // sudo passes SIGTERM on to the command, but not SIGKILL
+            if let Some(pid) = child.id() {
+                let _ = Command::new("kill").arg("-TERM").arg(pid.to_string()).status().await;
+            }
+            if tokio::time::timeout(TERMINATE_TIMEOUT, child.wait()).await.is_err() {
+                child.kill().await?;
+            }
+            return Err(RexebError::Cancelled("sandbox run".into()));
+        }
+    };
+
+    for reader in readers {
+        let _ = reader.await;
+    }
+    Ok(status.code().unwrap_or(-1))
+}
+
+/// Send the lines of a pipe to a channel until it closes
+fn forward<R>(pipe: R, output: mpsc::UnboundedSender<OutputLine>, line: fn(String) -> OutputLine) -> JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(pipe).lines();
+        // Keep reading after the receiver is gone so the command never blocks
+        while let Ok(Some(text)) = lines.next_line().await {
+            let _ = output.send(line(text));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_process() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let code = run_process(cmd, RunOptions::new().output(sender)).await.unwrap();
+        assert_eq!(code, 3);
+
+        let mut lines = Vec::new();
+        while let Some(line) = receiver.recv().await {
+            lines.push(line);
+        }
+        lines.sort_by_key(|l| l.text().to_string());
+        assert_eq!(lines, [OutputLine::Stderr("err".into()), OutputLine::Stdout("out".into())]);
+
+        let cancel = Cancellation::new();
+        let mut cmd = Command::new("sleep");
+        cmd.arg("30");
+        let run = tokio::spawn(run_process(cmd, RunOptions::new().cancel(cancel.clone())));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancel.cancel();
+        assert!(matches!(run.await.unwrap(), Err(RexebError::Cancelled(_))));
+    }
+}
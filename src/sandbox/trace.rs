@@ -0,0 +1,299 @@
+//! Traced maintainer script runs
+//!
+//! Reading a maintainer script only shows what it might do; running it
+//! under `strace` in a throwaway copy of the sandbox root shows what it
+//! does: the programs it runs, the users and groups it creates, the files
+//! it writes or removes and the services it enables. Scripts run the way
+//! dpkg runs them on a fresh install: `preinst install`, then the payload
+//! is unpacked, then `postinst configure`.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::error::{RexebError, Result};
+use crate::models::{MaintainerScript, PackageMetadata};
+
+use super::{create_sandbox, Bootstrap, RunOptions, Sandbox};
+
+/// Directory inside the sandbox for scripts, payload and traces
+const WORK_DIR: &str = "/tmp/rexeb-trace";
+
+/// System calls traced
+const SYSCALLS: &str = "execve,openat,creat,mkdir,mkdirat,rename,renameat,renameat2,unlink,unlinkat,\
+                        symlink,symlinkat,link,linkat";
+
+/// Paths whose changes say nothing about the script
+const IGNORED_PREFIXES: &[&str] = &["/proc", "/sys", "/dev", "/tmp", "/var/tmp"];
+
+/// Commands creating users and groups
+const USER_COMMANDS: &[&str] = &["useradd", "adduser"];
+const GROUP_COMMANDS: &[&str] = &["groupadd", "addgroup"];
+
+/// Commands enabling services, with the subcommand that does
+const SERVICE_COMMANDS: &[&str] = &["systemctl", "deb-systemd-helper"];
+
+lazy_static::lazy_static! {
+    /// `[pid] name(args) = result`
+    static ref SYSCALL_RE: Regex = Regex::new(r"^(?:\d+\s+)?(\w+)\((.*)\)\s+=\s+(-?\d+)").unwrap();
+    /// Quoted string arguments
+    static ref STRING_RE: Regex = Regex::new(r#""((?:[^"\\]|\\.)*)""#).unwrap();
+}
+
+/// What a traced maintainer script did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScriptTrace {
+    /// Script name (`postinst`)
+    pub script: String,
+    /// Exit code of the script
+    pub exit_code: i32,
+    /// Programs run, in order of first use
+    pub programs: Vec<String>,
+    /// Users created
+    pub users: BTreeSet<String>,
+    /// Groups created
+    pub groups: BTreeSet<String>,
+    /// Files and directories created or written
+    pub written: BTreeSet<PathBuf>,
+    /// Files removed
+    pub removed: BTreeSet<PathBuf>,
+    /// Units enabled
+    pub services: BTreeSet<String>,
+}
+
+impl ScriptTrace {
+    /// Read `strace -f` output
+    pub fn parse(script: &str, exit_code: i32, strace: &str) -> Self {
+        let mut trace = Self {
+            script: script.to_string(),
+            exit_code,
+            ..Default::default()
+        };
+
+        for line in strace.lines() {
+            let Some(captures) = SYSCALL_RE.captures(line) else {
+                continue;
+            };
+            if captures[3].starts_with('-') {
+                continue;
+            }
+            let (call, args) = (&captures[1], &captures[2]);
+            let strings: Vec<String> = STRING_RE.captures_iter(args).map(|c| c[1].replace("\\\"", "\"")).collect();
+
+            match call {
+                "execve" => trace.record_exec(&strings),
+                "openat" | "creat" => {
+                    let writes = call == "creat" || ["O_WRONLY", "O_RDWR", "O_CREAT"].iter().any(|f| args.contains(f));
+                    if writes {
+                        trace.record_path(strings.first(), false);
+                    }
+                }
+                "unlink" | "unlinkat" => trace.record_path(strings.last(), true),
+                _ => trace.record_path(strings.last(), false),
+            }
+        }
+
+        trace
+    }
+
+    fn record_exec(&mut self, argv: &[String]) {
+        let Some(path) = argv.first() else {
+            return;
+        };
+        let program = path.rsplit('/').next().unwrap_or(path).to_string();
+        let operands: Vec<&String> = argv.iter().skip(2).filter(|a| !a.starts_with('-')).collect();
+
+        if USER_COMMANDS.contains(&program.as_str()) {
+            // `adduser user group` adds an existing user to a group
+            if program != "adduser" || operands.len() == 1 {
+                self.users.extend(operands.last().map(|u| u.to_string()));
+            }
+        } else if GROUP_COMMANDS.contains(&program.as_str()) {
+            self.groups.extend(operands.last().map(|g| g.to_string()));
+        } else if SERVICE_COMMANDS.contains(&program.as_str()) && operands.first().is_some_and(|s| *s == "enable") {
+            self.services.extend(operands[1..].iter().map(|s| s.to_string()));
+        }
+
+        if !self.programs.contains(&program) {
+            self.programs.push(program);
+        }
+    }
+
+    fn record_path(&mut self, path: Option<&String>, removed: bool) {
+        let Some(path) = path.filter(|p| p.starts_with('/')) else {
+            return;
+        };
+        if IGNORED_PREFIXES.iter().any(|prefix| Path::new(path).starts_with(prefix)) {
+            return;
+        }
+        let path = PathBuf::from(path);
+        // Enabling a unit links it into a .wants directory
+        if path.parent().and_then(|p| p.extension()).is_some_and(|e| e == "wants") {
+            self.services.extend(path.file_name().map(|n| n.to_string_lossy().into_owned()));
+        }
+        match removed {
+            true => self.removed.insert(path),
+            false => self.written.insert(path),
+        };
+    }
+
+    /// Summary of what the script did, for reports
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        let list = |items: &mut dyn Iterator<Item = String>| items.collect::<Vec<_>>().join(", ");
+        if !self.programs.is_empty() {
+            parts.push(format!("ran {}", self.programs.join(", ")));
+        }
+        if !self.users.is_empty() {
+            parts.push(format!("created user(s) {}", list(&mut self.users.iter().cloned())));
+        }
+        if !self.groups.is_empty() {
+            parts.push(format!("created group(s) {}", list(&mut self.groups.iter().cloned())));
+        }
+        if !self.written.is_empty() {
+            parts.push(format!("wrote {}", list(&mut self.written.iter().map(|p| p.display().to_string()))));
+        }
+        if !self.removed.is_empty() {
+            parts.push(format!("removed {}", list(&mut self.removed.iter().map(|p| p.display().to_string()))));
+        }
+        if !self.services.is_empty() {
+            parts.push(format!("enabled {}", list(&mut self.services.iter().cloned())));
+        }
+        if parts.is_empty() {
+            parts.push("did nothing visible".to_string());
+        }
+        parts.join("; ")
+    }
+}
+
+/// Runs maintainer scripts under strace in a sandbox
+pub struct ScriptTracer<'a> {
+    sandbox: &'a dyn Sandbox,
+    /// Host directory for the payload archive and the traces
+    work_dir: tempfile::TempDir,
+    /// Whether scripts get network access
+    network: bool,
+}
+
+impl<'a> ScriptTracer<'a> {
+    /// Prepare an initialized sandbox, installing strace into it
+    pub async fn new(sandbox: &'a dyn Sandbox) -> Result<Self> {
+        let code = sandbox.run_command("pacman", &["-Sy", "--noconfirm", "--needed", "strace"]).await?;
+        if code != 0 {
+            return Err(RexebError::Other(format!("Installing strace in the sandbox failed with exit code {}", code)));
+        }
+        Ok(Self {
+            sandbox,
+            work_dir: tempfile::TempDir::new()?,
+            network: false,
+        })
+    }
+
+    /// Give the scripts network access
+    pub fn with_network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Unpack a payload into the sandbox root
+    pub async fn unpack(&self, data_dir: &Path) -> Result<()> {
+        let archive = self.work_dir.path().join("payload.tar");
+        let mut builder = tar::Builder::new(std::fs::File::create(&archive)?);
+        builder.follow_symlinks(false);
+        builder.append_dir_all(".", data_dir)?;
+        builder.into_inner()?;
+
+        let target = Path::new(WORK_DIR).join("payload.tar");
+        self.sandbox.copy_in(&archive, &target)?;
+        let target = target.to_string_lossy();
+        let code = self.sandbox.run_command("tar", &["-xpf", &target, "-C", "/"]).await?;
+        if code != 0 {
+            return Err(RexebError::Extraction(format!("Unpacking the payload in the sandbox failed with exit code {}", code)));
+        }
+        Ok(())
+    }
+
+    /// Run a script with the given arguments and trace it
+    pub async fn trace(&self, name: &str, content: &str, args: &[&str]) -> Result<ScriptTrace> {
+        let script = self.work_dir.path().join(name);
+        std::fs::write(&script, content)?;
+        let target = format!("{}/{}", WORK_DIR, name);
+        let log = format!("{}/{}.strace", WORK_DIR, name);
+        self.sandbox.copy_in(&script, Path::new(&target))?;
+
+        let mut argv = vec!["-f", "-qq", "-s", "512", "-o", &log, "-e", SYSCALLS, "sh", &target];
+        argv.extend(args);
+        let exit_code = self.sandbox.run("strace", &argv, RunOptions::new().network(self.network)).await?;
+
+        let host_log = self.work_dir.path().join(format!("{}.strace", name));
+        self.sandbox.copy_out(Path::new(&log), &host_log)?;
+        let strace = String::from_utf8_lossy(&std::fs::read(&host_log)?).into_owned();
+        Ok(ScriptTrace::parse(name, exit_code, &strace))
+    }
+}
+
+/// Trace a package's preinst and postinst in a copy of the sandbox root
+///
+/// The scripts get network access if `network` is set.
+pub async fn trace_maintainer_scripts(
+    config: &Config,
+    metadata: &PackageMetadata,
+    data_dir: &Path,
+    network: bool,
+) -> Result<Vec<ScriptTrace>> {
+    let preinst = metadata.get_script(MaintainerScript::PreInst);
+    let postinst = metadata.get_script(MaintainerScript::PostInst);
+    if preinst.is_none() && postinst.is_none() {
+        return Ok(Vec::new());
+    }
+
+    let snapshot = Bootstrap::from_config(config).snapshot().await?;
+    let mut sandbox = create_sandbox(snapshot.path())?;
+    sandbox.init()?;
+    let tracer = ScriptTracer::new(sandbox.as_ref()).await?.with_network(network);
+
+    let mut traces = Vec::new();
+    if let Some(script) = preinst {
+        traces.push(tracer.trace("preinst", script, &["install"]).await?);
+    }
+    tracer.unpack(data_dir).await?;
+    if let Some(script) = postinst {
+        traces.push(tracer.trace("postinst", script, &["configure"]).await?);
+    }
+    sandbox.cleanup()?;
+
+    Ok(traces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_strace() {
+        let strace = r#"100 execve("/usr/bin/sh", ["sh", "/tmp/rexeb-trace/postinst", "configure"], 0x7ffd /* 12 vars */) = 0
+101 execve("/usr/bin/useradd", ["useradd", "--system", "-d", "/var/lib/foo", "foo"], 0x55 /* 12 vars */) = 0
+102 execve("/usr/bin/systemctl", ["systemctl", "enable", "foo.service"], 0x55 /* 12 vars */) = 0
+102 symlinkat("/usr/lib/systemd/system/foo.service", AT_FDCWD, "/etc/systemd/system/multi-user.target.wants/foo.service") = 0
+103 execve("/usr/sbin/dpkg-maintscript-helper", ["dpkg-maintscript-helper"], 0x55 /* 12 vars */) = -1 ENOENT (No such file or directory)
+104 openat(AT_FDCWD, "/etc/foo.conf", O_WRONLY|O_CREAT|O_TRUNC, 0644) = 3
+104 openat(AT_FDCWD, "/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3
+104 openat(AT_FDCWD, "/tmp/scratch", O_WRONLY|O_CREAT, 0600) = 4
+105 mkdir("/var/lib/foo", 0755) = 0
+106 unlinkat(AT_FDCWD, "/etc/foo.old", 0) = 0
+"#;
+        let trace = ScriptTrace::parse("postinst", 0, strace);
+        assert_eq!(trace.programs, ["sh", "useradd", "systemctl"]);
+        assert_eq!(trace.users.iter().collect::<Vec<_>>(), ["foo"]);
+        assert_eq!(trace.services.iter().collect::<Vec<_>>(), ["foo.service"]);
+        assert_eq!(
+            trace.written.iter().map(|p| p.to_str().unwrap()).collect::<Vec<_>>(),
+            ["/etc/foo.conf", "/etc/systemd/system/multi-user.target.wants/foo.service", "/var/lib/foo"]
+        );
+        assert_eq!(trace.removed.iter().collect::<Vec<_>>(), [Path::new("/etc/foo.old")]);
+        assert!(trace.summary().starts_with("ran sh, useradd, systemctl; created user(s) foo; wrote /etc/foo.conf"));
+    }
+}
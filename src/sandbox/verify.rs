@@ -0,0 +1,391 @@
+//! Install verification in a sandbox
+//!
+//! Installs a built package into a sandbox and checks what static analysis
+//! cannot: that pacman accepts it, that `pacman -Qkk` finds the installed
+//! files intact, that the `.INSTALL` scriptlets run without errors and that
+//! the shipped programs start: each `/usr/bin` program is asked for
+//! `--version`, and each desktop entry's Exec runs under `xvfb-run` for a
+//! few seconds, which catches libraries and modules only loaded at runtime. With a [`RunLog`] every command's output
+//! is kept and each check refers to its log; output can also be followed
+//! live, and a [`Cancellation`] stops the run between or during commands.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::converter::{desktop_exec, APPLICATIONS_DIR};
+use crate::diff::PackageContents;
+use crate::error::Result;
+
+use super::{Cancellation, OutputLine, RunLog, RunOptions, Sandbox};
+
+/// Directory inside the sandbox for the package
+const WORK_DIR: &str = "/tmp/rexeb-test";
+
+/// Seconds a program gets to answer `--version`
+const VERSION_TIMEOUT: u32 = 10;
+
+/// Programs tried at most, in path order
+const MAX_PROGRAMS: usize = 25;
+
+/// Seconds a desktop application runs before it counts as started
+const GUI_TIMEOUT: u32 = 10;
+
+/// Package providing `xvfb-run`
+const XVFB_PACKAGE: &str = "xorg-server-xvfb";
+
+/// What pacman prints when a scriptlet fails; the transaction still succeeds
+const SCRIPTLET_ERROR: &str = "error: command failed to execute correctly";
+
+/// Dynamic loader message for a missing library
+const LOADER_ERROR: &str = "error while loading shared libraries";
+
+/// One check of a test run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestStep {
+    /// What was checked
+    pub name: String,
+    /// Whether the check passed
+    pub passed: bool,
+    /// Outcome in a few words, or the relevant output on failure
+    pub detail: String,
+    /// Log of the command behind the check
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<PathBuf>,
+}
+
+/// Outcome of installing a package in a sandbox
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestReport {
+    /// `name version` of the package
+    pub package: String,
+    /// Checks in the order they ran
+    pub steps: Vec<TestStep>,
+    /// Directory of the run's logs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_dir: Option<PathBuf>,
+}
+
+impl TestReport {
+    /// Whether every check passed
+    pub fn passed(&self) -> bool {
+        self.steps.iter().all(|s| s.passed)
+    }
+
+    /// Checks that failed
+    pub fn failures(&self) -> impl Iterator<Item = &TestStep> {
+        self.steps.iter().filter(|s| !s.passed)
+    }
+
+    fn step(&mut self, name: impl Into<String>, passed: bool, detail: impl Into<String>, log: Option<PathBuf>) {
+        self.steps.push(TestStep {
+            name: name.into(),
+            passed,
+            detail: detail.into(),
+            log,
+        });
+    }
+}
+
+/// Runs the checks for one package
+pub struct InstallTest<'a> {
+    sandbox: &'a dyn Sandbox,
+    /// Whether the package gets network access
+    network: bool,
+    /// Where command output is kept
+    log: Option<RunLog>,
+    /// Where command output is followed
+    output: Option<mpsc::UnboundedSender<OutputLine>>,
+    /// Stops the run
+    cancel: Cancellation,
+}
+
+impl<'a> InstallTest<'a> {
+    /// Test in an initialized sandbox
+    pub fn new(sandbox: &'a dyn Sandbox) -> Self {
+        Self {
+            sandbox,
+            network: false,
+            log: None,
+            output: None,
+            cancel: Cancellation::new(),
+        }
+    }
+
+    /// Give the installation, scriptlets and programs network access
+    ///
+    /// Dependencies are installed beforehand either way.
+    pub fn with_network(mut self, network: bool) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Keep every command's output in a run log
+    pub fn with_log(mut self, log: RunLog) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    /// Send every command's output lines to a channel as they are printed
+    pub fn with_output(mut self, output: mpsc::UnboundedSender<OutputLine>) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Stop the run when `cancel` fires
+    pub fn with_cancellation(mut self, cancel: Cancellation) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Install the package, run the checks and remove it again
+    ///
+    /// The sandbox must reach the package's repository dependencies.
+    pub async fn run(&mut self, package: &Path) -> Result<TestReport> {
+        let mut report = self.checks(package).await?;
+        if let Some(log) = &self.log {
+            report.log_dir = Some(log.dir().to_path_buf());
+            log.finish(&report)?;
+        }
+        Ok(report)
+    }
+
+    async fn checks(&mut self, package: &Path) -> Result<TestReport> {
+        let contents = PackageContents::from_arch_package(package)?;
+        let name = contents.fields.get("name").cloned().unwrap_or_default();
+        let mut report = TestReport {
+            package: contents.label(),
+            steps: Vec::new(),
+            log_dir: None,
+        };
+
+        let file_name = package.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let target = PathBuf::from(WORK_DIR).join(&file_name);
+        self.sandbox.copy_in(package, &target)?;
+
+        let (code, output, log) = self.capture("sync databases", "pacman -Sy --noconfirm", &[], true).await?;
+        if code != 0 {
+            report.step("sync databases", false, last_lines(&output, 5), log);
+            return Ok(report);
+        }
+
+        // Dependencies come first, so the install itself needs no network
+        let depends: Vec<&str> = contents.relations.get("depends").into_iter().flatten().map(String::as_str).collect();
+        if !depends.is_empty() {
+            let script = "pacman -S --noconfirm --needed --asdeps \"$@\"";
+            let (code, output, log) = self.capture("install dependencies", script, &depends, true).await?;
+            if code != 0 {
+                report.step("install dependencies", false, last_lines(&output, 10), log);
+                return Ok(report);
+            }
+        }
+
+        let script = "pacman -U --noconfirm \"$1\"";
+        let (code, output, log) = self.capture("install", script, &[&target.to_string_lossy()], self.network).await?;
+        report.step("install", code == 0, match code {
+            0 => "pacman installed the package".to_string(),
+            _ => last_lines(&output, 10),
+        }, log.clone());
+        if code != 0 {
+            return Ok(report);
+        }
+
+        if contents.scripts.contains_key(".INSTALL") {
+            let failed: Vec<&str> = output.lines().filter(|l| l.contains(SCRIPTLET_ERROR)).collect();
+            report.step(".INSTALL scriptlets", failed.is_empty(), match failed.is_empty() {
+                true => "ran without errors".to_string(),
+                false => last_lines(&output, 10),
+            }, log);
+        }
+
+        let (code, output, log) = self.capture("pacman -Qkk", "pacman -Qkk \"$1\"", &[&name], self.network).await?;
+        report.step("pacman -Qkk", code == 0, match code {
+            0 => "installed files match the package".to_string(),
+            _ => output.lines().filter(|l| l.starts_with("warning") || l.starts_with("error")).collect::<Vec<_>>().join("\n"),
+        }, log);
+
+        let programs: Vec<&PathBuf> = contents
+            .files
+            .iter()
+            .filter(|(path, entry)| path.starts_with("/usr/bin") && (entry.link.is_some() || entry.mode & 0o111 != 0))
+            .map(|(path, _)| path)
+            .take(MAX_PROGRAMS)
+            .collect();
+        for program in programs {
+            let script = format!("timeout {} \"$1\" --version < /dev/null", VERSION_TIMEOUT);
+            let step = format!("{} --version", program.display());
+            let (code, output, log) = self.capture(&step, &script, &[&program.to_string_lossy()], self.network).await?;
+            let (passed, detail) = program_outcome(code, &output);
+            report.step(step, passed, detail, log);
+        }
+
+        let applications = Path::new("/").join(APPLICATIONS_DIR);
+        let entries: Vec<&PathBuf> = contents
+            .files
+            .keys()
+            .filter(|path| path.starts_with(&applications) && path.extension().is_some_and(|e| e == "desktop"))
+            .collect();
+        if !entries.is_empty() {
+            self.smoke_desktop_entries(&entries, &mut report).await?;
+        }
+
+        // Leave the sandbox as it was
+        self.capture("remove", "pacman -Rns --noconfirm \"$1\"", &[&name], true).await?;
+
+        Ok(report)
+    }
+
+    /// Start each desktop entry's application on a virtual display
+    async fn smoke_desktop_entries(&mut self, entries: &[&PathBuf], report: &mut TestReport) -> Result<()> {
+        let script = format!("pacman -S --noconfirm --needed {}", XVFB_PACKAGE);
+        let (code, output, log) = self.capture("install xvfb", &script, &[], true).await?;
+        if code != 0 {
+            report.step("install xvfb", false, last_lines(&output, 5), log);
+            return Ok(());
+        }
+
+        let host_dir = tempfile::TempDir::new()?;
+        for entry in entries {
+            let name = entry.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let copy = host_dir.path().join(&name);
+            self.sandbox.copy_out(entry, &copy)?;
+            let Some(argv) = desktop_exec(&std::fs::read_to_string(&copy)?) else {
+                continue;
+            };
+
+            let step = format!("{} (Exec)", name);
+            let script = gui_script();
+            let args: Vec<&str> = argv.iter().map(String::as_str).collect();
+            let (code, output, log) = self.capture(&step, &script, &args, self.network).await?;
+            let (passed, detail) = gui_outcome(code, &output);
+            report.step(step, passed, detail, log);
+        }
+        Ok(())
+    }
+
+    /// Run a shell command in the sandbox, returning its exit code, output
+    /// and log file
+    async fn capture(&mut self, name: &str, script: &str, args: &[&str], network: bool) -> Result<(i32, String, Option<PathBuf>)> {
+        let argv = shell_argv(script, args);
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let options = RunOptions::new().network(network).output(sender).cancel(self.cancel.clone());
+        let follow = self.output.clone();
+        let collect = async move {
+            let mut output = String::new();
+            while let Some(line) = receiver.recv().await {
+                output.push_str(line.text());
+                output.push('\n');
+                if let Some(follow) = &follow {
+                    let _ = follow.send(line);
+                }
+            }
+            output
+        };
+        let (code, output) = tokio::join!(self.sandbox.run("sh", &argv, options), collect);
+        let code = code?;
+
+        let log = match &mut self.log {
+            Some(log) => {
+                let command = std::iter::once(script).chain(args.iter().copied()).collect::<Vec<_>>().join(" ");
+                Some(log.record(name, &command, code, &output)?)
+            }
+            None => None,
+        };
+        Ok((code, output, log))
+    }
+}
+
+/// Arguments for `sh` running a script, whose positional parameters are `args`
+fn shell_argv<'s>(script: &'s str, args: &[&'s str]) -> Vec<&'s str> {
+    let mut argv = vec!["-c", script, "sh"];
+    argv.extend(args);
+    argv
+}
+
+/// Script starting a desktop application, given as the positional
+/// parameters, on a virtual display until the timeout stops it
+fn gui_script() -> String {
+    format!("xvfb-run -a timeout {} \"$@\" < /dev/null", GUI_TIMEOUT)
+}
+
+/// Whether a program's `--version` run shows it starts, and why
+fn program_outcome(code: i32, output: &str) -> (bool, String) {
+    if let Some(line) = output.lines().find(|l| l.contains(LOADER_ERROR)) {
+        return (false, line.trim().to_string());
+    }
+    match code {
+        0 => (true, output.lines().next().unwrap_or("exited successfully").trim().to_string()),
+        124 => (false, format!("did not exit within {} seconds", VERSION_TIMEOUT)),
+        126 | 127 => (false, last_lines(output, 3)),
+        // Plenty of programs reject --version, which still shows they start
+        code => (true, format!("starts, exits with {} on --version", code)),
+    }
+}
+
+/// Whether a desktop application's run shows it starts, and why
+///
+/// Applications are expected to keep running until the timeout stops them.
+fn gui_outcome(code: i32, output: &str) -> (bool, String) {
+    if let Some(line) = output.lines().find(|l| l.contains(LOADER_ERROR)) {
+        return (false, line.trim().to_string());
+    }
+    match code {
+        124 => (true, format!("still running after {} seconds", GUI_TIMEOUT)),
+        0 => (true, "exited successfully".to_string()),
+        code => (false, format!("exited with {}:\n{}", code, last_lines(output, 5))),
+    }
+}
+
+/// The last lines of command output
+fn last_lines(output: &str, count: usize) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_outcome() {
+        assert_eq!(program_outcome(0, "foo 1.2\n"), (true, "foo 1.2".to_string()));
+        assert!(program_outcome(2, "unknown option --version").0);
+        assert_eq!(
+            program_outcome(127, "/usr/bin/foo: error while loading shared libraries: libbar.so.1: cannot open shared object file\n").1,
+            "/usr/bin/foo: error while loading shared libraries: libbar.so.1: cannot open shared object file"
+        );
+        assert!(!program_outcome(124, "").0);
+        assert!(gui_outcome(124, "").0);
+        assert!(!gui_outcome(1, "foo: cannot open display").0);
+        assert_eq!(last_lines("a\nb\nc", 2), "b\nc");
+    }
+
+    #[test]
+    fn test_gui_command() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let exec = desktop_exec("[Desktop Entry]\nType=Application\nExec=\"/opt/foo bar/foo\" --title \"a 'b' $HOME\" %U\n").unwrap();
+        let args: Vec<&str> = exec.iter().map(String::as_str).collect();
+        let script = gui_script();
+        let argv = shell_argv(&script, &args);
+        assert_eq!(argv[..3], ["-c", "xvfb-run -a timeout 10 \"$@\" < /dev/null", "sh"]);
+        assert_eq!(argv[3..], ["/opt/foo bar/foo", "--title", "a 'b' $HOME"]);
+
+        // The Exec arguments reach xvfb-run unsplit and unexpanded
+        let bin = tempfile::TempDir::new().unwrap();
+        let xvfb_run = bin.path().join("xvfb-run");
+        std::fs::write(&xvfb_run, "#!/bin/sh\nprintf '%s\\n' \"$@\"\n").unwrap();
+        std::fs::set_permissions(&xvfb_run, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let output = std::process::Command::new("sh")
+            .args(&argv)
+            .env("PATH", format!("{}:/usr/bin:/bin", bin.path().display()))
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "-a\ntimeout\n10\n/opt/foo bar/foo\n--title\na 'b' $HOME\n"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Upstream sources of converted packages
+//!
+//! A conversion's `--origin` says where its deb came from: either the URL
+//! of the deb itself, or an apt repository as a sources.list line
+//! (`deb https://example.com/apt stable main`). `rexeb upgrade` asks the
+//! origin for its current version and downloads it when it is newer.
+
+use std::cmp::Ordering;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{RexebError, Result};
+use crate::parsers::deb::parse_control_fields;
+use crate::resolver::installability::vercmp;
+
+/// Where a package can be fetched again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Upstream {
+    /// A URL serving the deb, typically one always pointing at the latest release
+    Url(String),
+    /// An apt repository
+    Apt {
+        /// Repository base URL
+        base: String,
+        /// Suite (distribution) name
+        suite: String,
+        /// Components to search
+        components: Vec<String>,
+    },
+}
+
+/// A package version offered upstream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Candidate {
+    /// Version, when known before downloading
+    pub version: Option<String>,
+    /// Download URL of the deb
+    pub url: String,
+    /// SHA-256 the download must have, if published
+    pub sha256: Option<String>,
+}
+
+impl Upstream {
+    /// Understand a recorded origin; `None` for anything not fetchable
+    pub fn parse(origin: &str) -> Option<Self> {
+        let mut words = origin.split_whitespace();
+        match words.next()? {
+            "deb" => {
+                // Options in brackets (`[arch=amd64 signed-by=...]`) do not matter here
+                let rest = origin.trim_start().strip_prefix("deb")?.trim_start();
+                let rest = match rest.strip_prefix('[') {
+                    Some(options) => options.split_once(']')?.1,
+                    None => rest,
+                };
+                let mut words = rest.split_whitespace();
+                let base = words.next()?.trim_end_matches('/').to_string();
+                let suite = words.next()?.to_string();
+                let components: Vec<String> = words.map(String::from).collect();
+                (!components.is_empty()).then_some(Self::Apt { base, suite, components })
+            }
+            url if (url.starts_with("https://") || url.starts_with("http://")) && words.next().is_none() => {
+                Some(Self::Url(url.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    /// The package as currently offered upstream
+    ///
+    /// A plain URL is offered as is; its version is only known once the
+    /// deb is downloaded.
+    pub async fn latest(&self, client: &reqwest::Client, name: &str, arch: &str) -> Result<Candidate> {
+        let (base, suite, components) = match self {
+            Self::Url(url) => return Ok(Candidate { version: None, url: url.clone(), sha256: None }),
+            Self::Apt { base, suite, components } => (base, suite, components),
+        };
+
+        let mut best: Option<Candidate> = None;
+        for component in components {
+            let index_url = format!("{}/dists/{}/{}/binary-{}/Packages", base, suite, component, arch);
+            let Some(index) = fetch_index(client, &index_url).await? else {
+                continue;
+            };
+            for candidate in find_in_index(&index, base, name, arch) {
+                if best.as_ref().is_none_or(|b| newer(candidate.version.as_deref(), b.version.as_deref())) {
+                    best = Some(candidate);
+                }
+            }
+        }
+        best.ok_or_else(|| RexebError::Network(format!("{} is not in {} {}", name, base, suite)))
+    }
+}
+
+/// Whether version `a` is newer than `b`, unknown versions never being newer
+pub fn newer(a: Option<&str>, b: Option<&str>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => vercmp(a, b) == Ordering::Greater,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// Download a candidate into `dir`, checking its checksum when published
+pub async fn download(client: &reqwest::Client, candidate: &Candidate, dir: &Path) -> Result<PathBuf> {
+    let mut response = client.get(&candidate.url).send().await?.error_for_status()?;
+    // Redirects of "latest" URLs lead to the real file name
+    let file = response
+        .url()
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|name| name.ends_with(".deb"))
+        .unwrap_or("download.deb")
+        .to_string();
+    let path = dir.join(file);
+
+    let mut out = std::fs::File::create(&path)?;
+    while let Some(chunk) = response.chunk().await? {
+        out.write_all(&chunk)?;
+    }
+    out.sync_all()?;
+
+    if let Some(ref expected) = candidate.sha256 {
+        let actual = crate::converter::ConversionCache::file_sha256(&path)?;
+        if &actual != expected {
+            std::fs::remove_file(&path)?;
+            return Err(RexebError::Validation(format!(
+                "{}: checksum mismatch (expected {}, got {})",
+                candidate.url, expected, actual
+            )));
+        }
+    }
+    Ok(path)
+}
+
+/// Fetch a Packages index, compressed or not; `None` if the repository has none
+async fn fetch_index(client: &reqwest::Client, url: &str) -> Result<Option<String>> {
+    for suffix in [".gz", ""] {
+        let response = client.get(format!("{}{}", url, suffix)).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            continue;
+        }
+        let bytes = response.error_for_status()?.bytes().await?;
+        let mut index = String::new();
+        match suffix {
+            ".gz" => flate2::read::GzDecoder::new(&bytes[..]).read_to_string(&mut index)?,
+            _ => (&bytes[..]).read_to_string(&mut index)?,
+        };
+        return Ok(Some(index));
+    }
+    Ok(None)
+}
+
+/// Entries of a package in a Packages index
+fn find_in_index(index: &str, base: &str, name: &str, arch: &str) -> Vec<Candidate> {
+    index
+        .split("\n\n")
+        .map(parse_control_fields)
+        .filter(|fields| fields.get("Package").is_some_and(|p| p == name))
+        .filter(|fields| fields.get("Architecture").is_none_or(|a| a == arch || a == "all"))
+        .filter_map(|fields| {
+            Some(Candidate {
+                version: Some(fields.get("Version")?.clone()),
+                url: format!("{}/{}", base, fields.get("Filename")?),
+                sha256: fields.get("SHA256").cloned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_origin_and_index() {
+        assert_eq!(Upstream::parse("https://example.com/foo/latest"), Some(Upstream::Url("https://example.com/foo/latest".into())));
+        assert_eq!(
+            Upstream::parse("deb [arch=amd64 signed-by=/usr/share/keyrings/foo.gpg] https://example.com/apt/ stable main extra"),
+            Some(Upstream::Apt {
+                base: "https://example.com/apt".into(),
+                suite: "stable".into(),
+                components: vec!["main".into(), "extra".into()],
+            })
+        );
+        assert_eq!(Upstream::parse("deb https://example.com/apt stable"), None);
+        assert_eq!(Upstream::parse("/home/user/foo.deb"), None);
+
+        let index = "Package: foo\nVersion: 1.2-1\nArchitecture: amd64\nFilename: pool/f/foo_1.2-1_amd64.deb\nSHA256: ab12\n\n\
+                     Package: foo\nVersion: 1.10-1\nArchitecture: amd64\nFilename: pool/f/foo_1.10-1_amd64.deb\n\n\
+                     Package: foo\nVersion: 2.0-1\nArchitecture: arm64\nFilename: pool/f/foo_2.0-1_arm64.deb\n\n\
+                     Package: foobar\nVersion: 3.0\nArchitecture: all\nFilename: pool/f/foobar_3.0_all.deb\n";
+        let found = find_in_index(index, "https://example.com/apt", "foo", "amd64");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].url, "https://example.com/apt/pool/f/foo_1.2-1_amd64.deb");
+        assert_eq!(found[0].sha256.as_deref(), Some("ab12"));
+        assert!(newer(found[1].version.as_deref(), found[0].version.as_deref()));
+        assert!(!newer(None, Some("1.0")));
+    }
+}